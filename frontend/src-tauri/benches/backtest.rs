@@ -0,0 +1,130 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rusqlite::Connection;
+
+#[path = "../src/columnar.rs"]
+#[allow(dead_code, unused_imports)]
+mod columnar;
+#[path = "../src/models.rs"]
+#[allow(dead_code, unused_imports)]
+mod models;
+#[path = "../src/rolling.rs"]
+#[allow(dead_code, unused_imports)]
+mod rolling;
+#[path = "../src/snapshot.rs"]
+#[allow(dead_code, unused_imports)]
+mod snapshot;
+#[path = "../src/utils.rs"]
+#[allow(dead_code, unused_imports)]
+mod utils;
+
+use columnar::{write_columnar, MappedBars};
+use models::Bar;
+
+/// Minute bars of an index over a few years
+fn minute_bars(len: usize) -> Vec<Bar> {
+    (0..len)
+        .map(|i| {
+            let close = 3000.0 + ((i * 37) % 101) as f64 * 0.5;
+            Bar {
+                timestamp: 1_600_000_000 + i as i64 * 60,
+                open: close - 0.25,
+                high: close + 1.0,
+                low: close - 1.0,
+                close,
+                volume: 1e6 + (i % 17) as f64,
+            }
+        })
+        .collect()
+}
+
+/// The same bars as rows of a SQLite table, the read path the mapping replaces
+fn sqlite_bars(bars: &[Bar]) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE bars (timestamp INTEGER PRIMARY KEY, open REAL, high REAL,
+         low REAL, close REAL, volume REAL)",
+    )
+    .unwrap();
+    let tx = conn.unchecked_transaction().unwrap();
+    {
+        let mut insert = tx
+            .prepare("INSERT INTO bars VALUES (?1, ?2, ?3, ?4, ?5, ?6)")
+            .unwrap();
+        for b in bars {
+            insert
+                .execute((b.timestamp, b.open, b.high, b.low, b.close, b.volume))
+                .unwrap();
+        }
+    }
+    tx.commit().unwrap();
+    conn
+}
+
+fn load_rows(conn: &Connection) -> Vec<Bar> {
+    conn.prepare_cached(
+        "SELECT timestamp, open, high, low, close, volume FROM bars ORDER BY timestamp",
+    )
+    .unwrap()
+    .query_map([], |row| {
+        Ok(Bar {
+            timestamp: row.get(0)?,
+            open: row.get(1)?,
+            high: row.get(2)?,
+            low: row.get(3)?,
+            close: row.get(4)?,
+            volume: row.get(5)?,
+        })
+    })
+    .unwrap()
+    .collect::<Result<_, _>>()
+    .unwrap()
+}
+
+/// Fast and slow moving averages of an MA-cross run
+fn ma_cross(closes: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    (
+        rolling::rolling_mean(closes, 5),
+        rolling::rolling_mean(closes, 20),
+    )
+}
+
+fn bench_load(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("ssi-bench-{}", std::process::id()));
+    let mut group = c.benchmark_group("backtest_load");
+    group.sample_size(20);
+    for len in [25_000, 250_000] {
+        let bars = minute_bars(len);
+        let conn = sqlite_bars(&bars);
+        let path = dir.join(format!("{}.col", len));
+        write_columnar(&path, &bars).unwrap();
+
+        // Bars handed to the simulation
+        group.bench_with_input(BenchmarkId::new("sqlite_rows", len), &conn, |b, conn| {
+            b.iter(|| load_rows(black_box(conn)))
+        });
+        group.bench_with_input(BenchmarkId::new("columnar", len), &path, |b, path| {
+            b.iter(|| {
+                let mapped = MappedBars::open(black_box(path)).unwrap();
+                mapped.to_bars(0..mapped.len())
+            })
+        });
+
+        // Strategy indicators, which read the closes alone
+        group.bench_with_input(BenchmarkId::new("sqlite_signals", len), &conn, |b, conn| {
+            b.iter(|| {
+                let rows = load_rows(black_box(conn));
+                ma_cross(&rows.iter().map(|bar| bar.close).collect::<Vec<_>>())
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("columnar_signals", len),
+            &path,
+            |b, path| b.iter(|| ma_cross(MappedBars::open(black_box(path)).unwrap().closes())),
+        );
+    }
+    group.finish();
+    std::fs::remove_dir_all(dir).ok();
+}
+
+criterion_group!(benches, bench_load);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+#[path = "../src/rolling.rs"]
+#[allow(dead_code, unused_imports)]
+mod rolling;
+
+fn series(len: usize) -> Vec<f64> {
+    (0..len).map(|i| 10.0 + ((i * 37) % 101) as f64 * 0.05).collect()
+}
+
+fn bench_rolling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rolling");
+    for len in [2_500, 250_000] {
+        let values = series(len);
+        group.bench_with_input(BenchmarkId::new("mean_20", len), &values, |b, v| {
+            b.iter(|| rolling::rolling_mean(black_box(v), 20))
+        });
+        group.bench_with_input(BenchmarkId::new("std_20", len), &values, |b, v| {
+            b.iter(|| rolling::rolling_std(black_box(v), 20))
+        });
+        group.bench_with_input(BenchmarkId::new("max_20", len), &values, |b, v| {
+            b.iter(|| rolling::rolling_max(black_box(v), 20))
+        });
+        group.bench_with_input(BenchmarkId::new("min_20", len), &values, |b, v| {
+            b.iter(|| rolling::rolling_min(black_box(v), 20))
+        });
+    }
+    group.finish();
+}
+
+fn bench_ema(c: &mut Criterion) {
+    // A full-market universe: many symbols with a few years of daily bars
+    let universe: Vec<Vec<f64>> = (0..512).map(|_| series(2_500)).collect();
+    let refs: Vec<&[f64]> = universe.iter().map(|s| s.as_slice()).collect();
+
+    c.bench_function("ema_12_single_x512", |b| {
+        b.iter(|| refs.iter().map(|s| rolling::ema(black_box(s), 12)).collect::<Vec<_>>())
+    });
+    c.bench_function("ema_12_many_x512", |b| b.iter(|| rolling::ema_many(black_box(&refs), 12)));
+}
+
+criterion_group!(benches, bench_rolling, bench_ema);
+criterion_main!(benches);
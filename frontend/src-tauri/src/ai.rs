@@ -0,0 +1,672 @@
+//! AI models and the routing policy that picks one per task.
+//!
+//! Models are configured by name: local ones served by an Ollama-compatible
+//! server on this machine, and hosted ones behind an OpenAI-compatible chat
+//! API with an API key kept in an encrypted file. Each model is either
+//! cheap or powerful. Short classification and sentiment tasks default to a
+//! cheap model and analyses to a powerful one; inputs longer than
+//! `long_input_chars` always go to a powerful model. Settings can pin any
+//! task to a model with its own fallbacks, and every AI command takes a
+//! `model` parameter that overrides the routing for that call. A failing
+//! model falls through to the next in the chain. Hosted models are skipped
+//! while a compliance policy disables external AI.
+//!
+//! Prose output (answers and analyses) passes a guardrail before anyone sees
+//! it: sentences that read like personal trading advice ("建议买入",
+//! "you should sell") are removed or the whole output withheld, as the
+//! compliance policy says, and a disclaimer is appended. The original text is
+//! kept in the tamper-evident `ai_advice_audit.jsonl`, which has no command.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager, State};
+
+use crate::audit::{AuditAction, AuditLog};
+use crate::compliance::{AdviceGuardrail, AdviceMode, CompliancePolicy, Subsystem};
+use crate::secure_store::EncryptedFile;
+use crate::settings::SettingsStore;
+use crate::utils::generate_id;
+
+const DEFAULT_DISCLAIMER: &str =
+    "以上内容仅为信息分析，不构成投资建议。投资有风险，决策请结合自身情况独立判断。";
+const REMOVED_NOTICE: &str = "（原输出中针对个人的买卖操作建议已按合规要求移除。）";
+const WITHHELD_NOTICE: &str = "该输出包含针对个人的买卖操作建议，已按合规要求隐藏。";
+
+/// Phrases that turn analysis into individual trading advice, matched lowercased
+const ADVICE_PHRASES: &[&str] = &[
+    "建议买入",
+    "建议卖出",
+    "建议增持",
+    "建议减持",
+    "建议加仓",
+    "建议减仓",
+    "建议清仓",
+    "建议持有",
+    "建议止损",
+    "建议抄底",
+    "可以买入",
+    "可以卖出",
+    "果断买入",
+    "立即买入",
+    "马上买入",
+    "立即卖出",
+    "马上卖出",
+    "推荐买入",
+    "满仓",
+    "梭哈",
+    "你应该买",
+    "你应该卖",
+    "您应该买",
+    "您应该卖",
+    "you should buy",
+    "you should sell",
+    "i recommend buying",
+    "i recommend selling",
+    "we recommend buying",
+    "we recommend selling",
+    "buy now",
+    "sell now",
+    "go all in",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiTask {
+    Classification,
+    Sentiment,
+    Summary,
+    ResearchQuestion,
+    Analysis,
+}
+
+impl AiTask {
+    pub const ALL: [AiTask; 5] = [
+        AiTask::Classification,
+        AiTask::Sentiment,
+        AiTask::Summary,
+        AiTask::ResearchQuestion,
+        AiTask::Analysis,
+    ];
+
+    /// Tasks whose output is prose shown to the user
+    fn gives_prose(self) -> bool {
+        matches!(self, AiTask::ResearchQuestion | AiTask::Analysis)
+    }
+
+    fn default_tier(self) -> Tier {
+        match self {
+            AiTask::Classification | AiTask::Sentiment | AiTask::Summary => Tier::Cheap,
+            AiTask::ResearchQuestion | AiTask::Analysis => Tier::Powerful,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tier {
+    Cheap,
+    Powerful,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelProvider {
+    /// Ollama-compatible server on this machine
+    Local,
+    /// OpenAI-compatible chat completions API
+    Hosted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelConfig {
+    /// What settings, routes and overrides call the model
+    pub name: String,
+    pub provider: ModelProvider,
+    /// Base URL, e.g. `http://127.0.0.1:11434` or `https://api.deepseek.com/v1`
+    pub endpoint: String,
+    /// Model id sent to the provider
+    pub model: String,
+    pub tier: Tier,
+}
+
+/// A task pinned to a model, with models to try when it fails
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRoute {
+    pub model: String,
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiSettings {
+    pub models: Vec<ModelConfig>,
+    /// Per-task routes replacing the tier defaults
+    pub routes: BTreeMap<AiTask, TaskRoute>,
+    /// Inputs longer than this go to a powerful model whatever the task
+    pub long_input_chars: usize,
+    /// Requests a batch keeps in flight at once
+    pub batch_concurrency: usize,
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        let local = |name: &str, model: &str, tier| ModelConfig {
+            name: name.to_string(),
+            provider: ModelProvider::Local,
+            endpoint: "http://127.0.0.1:11434".to_string(),
+            model: model.to_string(),
+            tier,
+        };
+        Self {
+            models: vec![
+                local("local-small", "qwen2.5:3b", Tier::Cheap),
+                local("local-large", "qwen2.5:14b", Tier::Powerful),
+            ],
+            routes: BTreeMap::new(),
+            long_input_chars: 4_000,
+            batch_concurrency: 4,
+        }
+    }
+}
+
+impl AiSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.models.is_empty() {
+            return Err("At least one AI model is required".to_string());
+        }
+        if !(1..=16).contains(&self.batch_concurrency) {
+            return Err("Batch concurrency must be between 1 and 16".to_string());
+        }
+        for (i, model) in self.models.iter().enumerate() {
+            if model.name.trim().is_empty() || model.model.trim().is_empty() {
+                return Err("AI models need a name and a model id".to_string());
+            }
+            if self.models[..i].iter().any(|m| m.name == model.name) {
+                return Err(format!("Duplicate AI model name: {}", model.name));
+            }
+            url::Url::parse(&model.endpoint)
+                .map_err(|e| format!("Invalid endpoint for {}: {}", model.name, e))?;
+        }
+        for (task, route) in &self.routes {
+            for name in std::iter::once(&route.model).chain(&route.fallbacks) {
+                if self.find(name).is_none() {
+                    return Err(format!("Route for {:?} uses unknown model {}", task, name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Option<&ModelConfig> {
+        self.models.iter().find(|m| m.name == name)
+    }
+}
+
+/// Models to try for a task, in order
+pub fn route(
+    settings: &AiSettings,
+    task: AiTask,
+    input_chars: usize,
+    override_model: Option<&str>,
+    hosted_allowed: bool,
+) -> Result<Vec<ModelConfig>, String> {
+    if let Some(name) = override_model {
+        let model = settings
+            .find(name)
+            .ok_or_else(|| format!("Unknown AI model: {}", name))?;
+        if model.provider == ModelProvider::Hosted && !hosted_allowed {
+            return Err(format!(
+                "{} is a hosted model and external AI is disabled by policy",
+                name
+            ));
+        }
+        return Ok(vec![model.clone()]);
+    }
+
+    let chain: Vec<&ModelConfig> = match settings.routes.get(&task) {
+        Some(route) => std::iter::once(&route.model)
+            .chain(&route.fallbacks)
+            .filter_map(|name| settings.find(name))
+            .collect(),
+        None => {
+            let tier = if input_chars > settings.long_input_chars {
+                Tier::Powerful
+            } else {
+                task.default_tier()
+            };
+            let preferred = settings.models.iter().filter(|m| m.tier == tier);
+            let others = settings.models.iter().filter(|m| m.tier != tier);
+            preferred.chain(others).collect()
+        }
+    };
+    let chain: Vec<ModelConfig> = chain
+        .into_iter()
+        .filter(|m| hosted_allowed || m.provider == ModelProvider::Local)
+        .cloned()
+        .collect();
+    if chain.is_empty() {
+        return Err(format!(
+            "No AI model available for {:?}; hosted models are disabled by policy",
+            task
+        ));
+    }
+    Ok(chain)
+}
+
+/// API keys of hosted models, by model name
+pub struct AiKeyVault {
+    file: EncryptedFile,
+    keys: Mutex<HashMap<String, String>>,
+}
+
+impl AiKeyVault {
+    pub fn load(path: PathBuf, key_path: PathBuf) -> Self {
+        let file = EncryptedFile::new(path, &key_path);
+        let keys = file.read();
+        Self {
+            file,
+            keys: Mutex::new(keys),
+        }
+    }
+
+    fn get(&self, model: &str) -> Option<String> {
+        self.keys.lock().unwrap().get(model).cloned()
+    }
+
+    fn set(&self, model: &str, key: Option<String>) -> Result<(), String> {
+        let mut keys = self.keys.lock().unwrap();
+        match key {
+            Some(key) => keys.insert(model.to_string(), key),
+            None => keys.remove(model),
+        };
+        self.file.write(&*keys, "AI API keys")
+    }
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    choices: Vec<OpenAiChoice>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiChoice {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    content: String,
+}
+
+async fn chat(
+    model: &ModelConfig,
+    api_key: Option<String>,
+    system: &str,
+    user: &str,
+) -> Result<String, String> {
+    let messages = json!([
+        { "role": "system", "content": system },
+        { "role": "user", "content": user },
+    ]);
+    let base = model.endpoint.trim_end_matches('/');
+    let client = reqwest::Client::new();
+    let request = match model.provider {
+        ModelProvider::Local => client.post(format!("{}/api/chat", base)).json(&json!({
+            "model": model.model,
+            "stream": false,
+            "messages": messages,
+        })),
+        ModelProvider::Hosted => {
+            let key = api_key.ok_or_else(|| format!("No API key set for {}", model.name))?;
+            client
+                .post(format!("{}/chat/completions", base))
+                .bearer_auth(key)
+                .json(&json!({ "model": model.model, "messages": messages }))
+        }
+    };
+    let response = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to reach AI model {}: {}", model.name, e))?;
+    let content = match model.provider {
+        ModelProvider::Local => response
+            .json::<OllamaResponse>()
+            .await
+            .map(|r| r.message.content),
+        ModelProvider::Hosted => response.json::<OpenAiResponse>().await.map(|r| {
+            r.choices
+                .into_iter()
+                .next()
+                .map(|c| c.message.content)
+                .unwrap_or_default()
+        }),
+    }
+    .map_err(|e| format!("Failed to parse response of {}: {}", model.name, e))?;
+    Ok(content.trim().to_string())
+}
+
+/// Split after sentence-ending punctuation and line breaks, keeping the delimiters
+fn sentences(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, n)| n);
+        let ends = matches!(c, '。' | '！' | '？' | '；' | '!' | '?' | '\n')
+            || (c == '.' && next.map_or(true, char::is_whitespace));
+        if ends {
+            let end = i + c.len_utf8();
+            parts.push(&text[start..end]);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        parts.push(&text[start..]);
+    }
+    parts
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Guarded {
+    pub text: String,
+    /// Advice phrases found in the original
+    pub flagged: Vec<String>,
+}
+
+/// Filter advice-like sentences out of model output; `None` when there are none
+pub fn guard_advice(text: &str, guardrail: &AdviceGuardrail) -> Option<Guarded> {
+    let mut flagged = Vec::new();
+    let mut kept = String::new();
+    for sentence in sentences(text) {
+        let lower = sentence.to_lowercase();
+        let found: Vec<&str> = ADVICE_PHRASES
+            .iter()
+            .copied()
+            .filter(|phrase| lower.contains(phrase))
+            .collect();
+        if found.is_empty() {
+            kept.push_str(sentence);
+        } else {
+            flagged.extend(found.into_iter().map(str::to_string));
+            // Keep the paragraph structure of what is left
+            if sentence.ends_with('\n') {
+                kept.push('\n');
+            }
+        }
+    }
+    if flagged.is_empty() {
+        return None;
+    }
+    let disclaimer = match guardrail.disclaimer.trim() {
+        "" => DEFAULT_DISCLAIMER,
+        custom => custom,
+    };
+    let text = match guardrail.mode {
+        AdviceMode::Rewrite => format!("{}\n\n{}\n\n{}", kept.trim(), REMOVED_NOTICE, disclaimer),
+        AdviceMode::Withhold => format!("{}\n\n{}", WITHHELD_NOTICE, disclaimer),
+    };
+    Some(Guarded { text, flagged })
+}
+
+/// Originals of AI output rewritten by the advice guardrail
+pub struct AdviceAudit(pub AuditLog);
+
+/// Apply the advice guardrail to prose output, auditing the original
+fn guard_output(app: &AppHandle, task: AiTask, model: &str, text: String) -> String {
+    if !task.gives_prose() {
+        return text;
+    }
+    let guardrail = app.state::<CompliancePolicy>().policy.advice.clone();
+    let Some(guarded) = guard_advice(&text, &guardrail) else {
+        return text;
+    };
+    warn!(
+        "{:?} output of {} resembled trading advice ({}); {:?} applied",
+        task,
+        model,
+        guarded.flagged.join(", "),
+        guardrail.mode
+    );
+    if let Err(e) = app.state::<AdviceAudit>().0.record(
+        &format!("ai:{}", model),
+        AuditAction::Update,
+        format!("ai_output:{}", generate_id("output")),
+        Some(&text),
+        Some(&guarded.text),
+    ) {
+        warn!("Failed to audit rewritten AI output: {}", e);
+    }
+    guarded.text
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Completion {
+    pub text: String,
+    /// Name of the model that answered
+    pub model: String,
+}
+
+/// Run a prompt on the models routed for `task`, falling back down the chain
+pub async fn complete(
+    app: &AppHandle,
+    task: AiTask,
+    system: &str,
+    user: &str,
+    override_model: Option<&str>,
+) -> Result<Completion, String> {
+    let settings = app.state::<SettingsStore>().get().ai;
+    let hosted_allowed = app
+        .state::<CompliancePolicy>()
+        .allows(Subsystem::ExternalAi);
+    let chain = route(
+        &settings,
+        task,
+        system.chars().count() + user.chars().count(),
+        override_model,
+        hosted_allowed,
+    )?;
+    let mut errors = Vec::new();
+    for model in chain {
+        let key = app.state::<AiKeyVault>().get(&model.name);
+        match chat(&model, key, system, user).await {
+            Ok(text) => {
+                info!("{:?} answered by {}", task, model.name);
+                return Ok(Completion {
+                    text: guard_output(app, task, &model.name, text),
+                    model: model.name,
+                });
+            }
+            Err(e) => {
+                warn!("{}; trying the next model", e);
+                errors.push(e);
+            }
+        }
+    }
+    Err(errors.join("; "))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelStatus {
+    #[serde(flatten)]
+    pub config: ModelConfig,
+    pub has_key: bool,
+    /// Whether routing may use the model now
+    pub usable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiRouting {
+    pub models: Vec<ModelStatus>,
+    /// Model chain of each task for a short input
+    pub routes: BTreeMap<AiTask, Vec<String>>,
+    pub external_ai_allowed: bool,
+}
+
+/// Configured models and the chain each task currently routes to
+#[tauri::command]
+pub fn get_ai_routing(
+    settings: State<'_, SettingsStore>,
+    policy: State<'_, CompliancePolicy>,
+    keys: State<'_, AiKeyVault>,
+) -> Result<AiRouting, String> {
+    let settings = settings.get().ai;
+    let allowed = policy.allows(Subsystem::ExternalAi);
+    let models = settings
+        .models
+        .iter()
+        .map(|m| {
+            let has_key = keys.get(&m.name).is_some();
+            ModelStatus {
+                usable: m.provider == ModelProvider::Local || (allowed && has_key),
+                config: m.clone(),
+                has_key,
+            }
+        })
+        .collect();
+    let routes = AiTask::ALL
+        .into_iter()
+        .map(|task| {
+            let chain = route(&settings, task, 0, None, allowed).unwrap_or_default();
+            (task, chain.into_iter().map(|m| m.name).collect())
+        })
+        .collect();
+    Ok(AiRouting {
+        models,
+        routes,
+        external_ai_allowed: allowed,
+    })
+}
+
+/// Store the API key of a hosted model
+#[tauri::command]
+pub fn set_ai_api_key(
+    settings: State<'_, SettingsStore>,
+    keys: State<'_, AiKeyVault>,
+    model: String,
+    api_key: String,
+) -> Result<(), String> {
+    let settings = settings.get().ai;
+    let config = settings
+        .find(&model)
+        .ok_or_else(|| format!("Unknown AI model: {}", model))?;
+    if config.provider != ModelProvider::Hosted {
+        return Err(format!("{} runs locally and needs no API key", model));
+    }
+    let api_key = api_key.trim().to_string();
+    if api_key.is_empty() {
+        return Err("API key is required".to_string());
+    }
+    keys.set(&model, Some(api_key))
+}
+
+#[tauri::command]
+pub fn remove_ai_api_key(keys: State<'_, AiKeyVault>, model: String) -> Result<(), String> {
+    keys.set(&model, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(chain: Result<Vec<ModelConfig>, String>) -> Vec<String> {
+        chain.unwrap().into_iter().map(|m| m.name).collect()
+    }
+
+    #[test]
+    fn test_routing_by_task_length_and_override() {
+        let mut settings = AiSettings::default();
+        settings.models.push(ModelConfig {
+            name: "deepseek".to_string(),
+            provider: ModelProvider::Hosted,
+            endpoint: "https://api.deepseek.com/v1".to_string(),
+            model: "deepseek-chat".to_string(),
+            tier: Tier::Powerful,
+        });
+        assert!(settings.validate().is_ok());
+
+        assert_eq!(
+            names(route(&settings, AiTask::Sentiment, 200, None, true)),
+            ["local-small", "local-large", "deepseek"]
+        );
+        assert_eq!(
+            names(route(&settings, AiTask::Analysis, 200, None, true)),
+            ["local-large", "deepseek", "local-small"]
+        );
+        // Long inputs escalate, and policy removes hosted models
+        assert_eq!(
+            names(route(&settings, AiTask::Sentiment, 10_000, None, false)),
+            ["local-large", "local-small"]
+        );
+
+        settings.routes.insert(
+            AiTask::Summary,
+            TaskRoute {
+                model: "deepseek".to_string(),
+                fallbacks: vec!["local-small".to_string()],
+            },
+        );
+        assert_eq!(
+            names(route(&settings, AiTask::Summary, 200, None, true)),
+            ["deepseek", "local-small"]
+        );
+        assert_eq!(
+            names(route(
+                &settings,
+                AiTask::Summary,
+                200,
+                Some("local-large"),
+                true
+            )),
+            ["local-large"]
+        );
+        assert!(route(&settings, AiTask::Summary, 200, Some("deepseek"), false).is_err());
+        assert!(route(&settings, AiTask::Summary, 200, Some("gpt-9"), true).is_err());
+
+        settings.routes.insert(
+            AiTask::Analysis,
+            TaskRoute {
+                model: "missing".to_string(),
+                fallbacks: Vec::new(),
+            },
+        );
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_advice_rewritten_with_disclaimer() {
+        let guardrail = AdviceGuardrail::default();
+        assert!(guard_advice("营收同比增长15% [1]。毛利率小幅下降。", &guardrail).is_none());
+
+        let text =
+            "营收同比增长15% [1]。建议买入并设好止损！\n估值处于历史中位。You should BUY now.";
+        let guarded = guard_advice(text, &guardrail).unwrap();
+        assert_eq!(guarded.flagged, ["建议买入", "you should buy", "buy now"]);
+        assert!(guarded
+            .text
+            .starts_with("营收同比增长15% [1]。\n估值处于历史中位。\n\n"));
+        assert!(!guarded.text.contains("建议买入") && !guarded.text.contains("BUY"));
+        assert!(guarded.text.ends_with(DEFAULT_DISCLAIMER));
+
+        let withhold = AdviceGuardrail {
+            mode: AdviceMode::Withhold,
+            disclaimer: "仅供内部研究参考".to_string(),
+        };
+        let guarded = guard_advice(text, &withhold).unwrap();
+        assert_eq!(
+            guarded.text,
+            format!("{}\n\n仅供内部研究参考", WITHHELD_NOTICE)
+        );
+    }
+}
@@ -0,0 +1,603 @@
+//! Bulk AI jobs such as sentiment for hundreds of headlines.
+//!
+//! A batch runs one task over many items. Short tasks (classification,
+//! sentiment, one-line summaries) pack up to `MAX_PACKED` items into a single
+//! request and ask for a JSON array of results, so 500 headlines cost a few
+//! dozen model calls; longer tasks send one item per request. Requests run
+//! `batch_concurrency` at a time. Each item ends up done or failed on its
+//! own, so one bad response fails only the items it covered, and the batch
+//! is checkpointed to disk after every response. A cancelled or interrupted
+//! batch keeps its finished items: resuming it processes only what is still
+//! pending plus, on request, what failed. Unfinished batches resume at
+//! startup.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tokio::task::JoinSet;
+
+use crate::ai::{self, AiTask, Completion};
+use crate::compliance::{CompliancePolicy, Subsystem};
+use crate::executor::{Priority, ResourceClass};
+use crate::settings::SettingsStore;
+use crate::tasks::{spawn_task_with, TaskContext, TaskHandle};
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+/// Items packed into one request for short tasks
+const MAX_PACKED: usize = 20;
+/// Characters of input packed into one request, kept under the long-input threshold
+const MAX_PACKED_CHARS: usize = 3_000;
+const MAX_ITEMS: usize = 5_000;
+/// Finished batches kept on disk
+const MAX_BATCHES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchInput {
+    /// Caller's key for the item, e.g. a news id
+    pub id: String,
+    pub input: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ItemStatus {
+    Pending,
+    Done { text: String, model: String },
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchItem {
+    pub id: String,
+    pub input: String,
+    #[serde(flatten)]
+    pub status: ItemStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AiBatch {
+    pub id: String,
+    pub task: AiTask,
+    /// Model override for every request
+    pub model: Option<String>,
+    pub items: Vec<BatchItem>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemFailure {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub id: String,
+    pub task: AiTask,
+    pub total: usize,
+    pub succeeded: usize,
+    pub pending: usize,
+    pub failures: Vec<ItemFailure>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchHandle {
+    pub batch_id: String,
+    pub task_id: String,
+}
+
+/// Tasks short enough to pack several items into one request
+fn packable(task: AiTask) -> bool {
+    matches!(
+        task,
+        AiTask::Classification | AiTask::Sentiment | AiTask::Summary
+    )
+}
+
+fn instruction(task: AiTask) -> &'static str {
+    match task {
+        AiTask::Classification => {
+            "把内容归入一个简短的类别，如 业绩、分红、并购、监管、人事、经营、其他"
+        }
+        AiTask::Sentiment => "判断内容对相关股票的情绪，只回答 positive、neutral 或 negative",
+        AiTask::Summary => "用一句话概括内容",
+        AiTask::ResearchQuestion => "回答问题，资料不足时直接说明，不要编造数据",
+        AiTask::Analysis => "对内容做简要分析，指出关键事实和影响",
+    }
+}
+
+impl AiBatch {
+    fn new(task: AiTask, model: Option<String>, inputs: Vec<BatchInput>) -> Self {
+        let now = get_timestamp();
+        Self {
+            id: generate_id("batch"),
+            task,
+            model,
+            items: inputs
+                .into_iter()
+                .map(|input| BatchItem {
+                    id: input.id,
+                    input: input.input,
+                    status: ItemStatus::Pending,
+                })
+                .collect(),
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.items.iter().any(|i| i.status == ItemStatus::Pending)
+    }
+
+    /// Mark failed items pending again
+    fn retry_failed(&mut self) {
+        for item in &mut self.items {
+            if matches!(item.status, ItemStatus::Failed { .. }) {
+                item.status = ItemStatus::Pending;
+            }
+        }
+    }
+
+    /// Pending items grouped into requests, by index
+    fn plan(&self) -> Vec<Vec<usize>> {
+        let pending = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.status == ItemStatus::Pending);
+        if !packable(self.task) {
+            return pending.map(|(i, _)| vec![i]).collect();
+        }
+        let mut chunks: Vec<Vec<usize>> = Vec::new();
+        let mut chars = 0;
+        for (i, item) in pending {
+            let len = item.input.chars().count();
+            match chunks.last_mut() {
+                Some(chunk) if chunk.len() < MAX_PACKED && chars + len <= MAX_PACKED_CHARS => {
+                    chunk.push(i);
+                    chars += len;
+                }
+                _ => {
+                    chunks.push(vec![i]);
+                    chars = len;
+                }
+            }
+        }
+        chunks
+    }
+
+    /// System and user prompt of one request
+    fn prompt(&self, chunk: &[usize]) -> (String, String) {
+        let instruction = instruction(self.task);
+        if !packable(self.task) {
+            return (
+                format!("{}。", instruction),
+                self.items[chunk[0]].input.clone(),
+            );
+        }
+        let system = format!(
+            "{}。输入是编号列表，逐条处理，只输出 JSON 数组，\
+             形如 [{{\"index\": 1, \"result\": \"...\"}}]，每个编号一项。",
+            instruction
+        );
+        let user = chunk
+            .iter()
+            .enumerate()
+            .map(|(n, &i)| format!("[{}] {}", n + 1, self.items[i].input.trim()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        (system, user)
+    }
+
+    /// Record the response to one request
+    fn apply(&mut self, chunk: &[usize], result: Result<Completion, String>) {
+        let completion = match result {
+            Ok(completion) => completion,
+            Err(error) => {
+                for &i in chunk {
+                    self.items[i].status = ItemStatus::Failed {
+                        error: error.clone(),
+                    };
+                }
+                return;
+            }
+        };
+        if !packable(self.task) {
+            self.items[chunk[0]].status = ItemStatus::Done {
+                text: completion.text,
+                model: completion.model,
+            };
+            return;
+        }
+        let mut results = parse_packed(&completion.text);
+        for (n, &i) in chunk.iter().enumerate() {
+            self.items[i].status = match results.remove(&(n + 1)) {
+                Some(text) => ItemStatus::Done {
+                    text,
+                    model: completion.model.clone(),
+                },
+                None => ItemStatus::Failed {
+                    error: format!("{} returned no result for this item", completion.model),
+                },
+            };
+        }
+    }
+
+    pub fn summary(&self) -> BatchSummary {
+        let count = |f: fn(&ItemStatus) -> bool| self.items.iter().filter(|i| f(&i.status)).count();
+        BatchSummary {
+            id: self.id.clone(),
+            task: self.task,
+            total: self.items.len(),
+            succeeded: count(|s| matches!(s, ItemStatus::Done { .. })),
+            pending: count(|s| *s == ItemStatus::Pending),
+            failures: self
+                .items
+                .iter()
+                .filter_map(|item| match &item.status {
+                    ItemStatus::Failed { error } => Some(ItemFailure {
+                        id: item.id.clone(),
+                        error: error.clone(),
+                    }),
+                    _ => None,
+                })
+                .collect(),
+            created_at: self.created_at.clone(),
+            updated_at: self.updated_at.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PackedResult {
+    index: usize,
+    result: serde_json::Value,
+}
+
+/// Results of a packed request by item number, tolerating text around the array
+fn parse_packed(text: &str) -> HashMap<usize, String> {
+    let (Some(start), Some(end)) = (text.find('['), text.rfind(']')) else {
+        return HashMap::new();
+    };
+    if end < start {
+        return HashMap::new();
+    }
+    serde_json::from_str::<Vec<PackedResult>>(&text[start..=end])
+        .unwrap_or_default()
+        .into_iter()
+        .map(|r| {
+            let text = match r.result {
+                serde_json::Value::String(s) => s.trim().to_string(),
+                other => other.to_string(),
+            };
+            (r.index, text)
+        })
+        .collect()
+}
+
+pub struct BatchStore {
+    path: PathBuf,
+    batches: Mutex<BTreeMap<String, AiBatch>>,
+    /// Batches with a task in flight
+    running: Mutex<HashSet<String>>,
+}
+
+impl BatchStore {
+    pub fn load(path: PathBuf) -> Self {
+        let batches = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            batches: Mutex::new(batches),
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<AiBatch> {
+        self.batches.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<AiBatch> {
+        self.batches.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Record a checkpoint, dropping the oldest finished batches beyond the limit
+    pub fn save(&self, mut batch: AiBatch) -> Result<(), String> {
+        batch.updated_at = get_timestamp();
+        let mut batches = self.batches.lock().unwrap();
+        batches.insert(batch.id.clone(), batch);
+        let finished: Vec<String> = batches
+            .values()
+            .filter(|b| b.is_finished())
+            .map(|b| b.id.clone())
+            .collect();
+        // Ids are time-ordered, so the first are the oldest
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(MAX_BATCHES))
+        {
+            batches.remove(id);
+        }
+        let content = serde_json::to_string(&*batches)
+            .map_err(|e| format!("Failed to serialize AI batches: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save AI batches: {}", e))
+    }
+}
+
+/// Clears a batch's running flag when its task ends, however it ends
+struct RunningGuard {
+    app: AppHandle,
+    batch_id: String,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.app
+            .state::<BatchStore>()
+            .running
+            .lock()
+            .unwrap()
+            .remove(&self.batch_id);
+    }
+}
+
+fn run(ctx: &TaskContext, app: &AppHandle, mut batch: AiBatch) -> Result<BatchSummary, String> {
+    let store = app.state::<BatchStore>();
+    let concurrency = app.state::<SettingsStore>().get().ai.batch_concurrency;
+    let total = batch.items.len() as u64;
+    let mut chunks = batch.plan().into_iter();
+    tauri::async_runtime::block_on(async {
+        let mut in_flight = JoinSet::new();
+        loop {
+            while in_flight.len() < concurrency {
+                let Some(chunk) = chunks.next() else {
+                    break;
+                };
+                let (system, user) = batch.prompt(&chunk);
+                let app = app.clone();
+                let task = batch.task;
+                let model = batch.model.clone();
+                in_flight.spawn(async move {
+                    let result = ai::complete(&app, task, &system, &user, model.as_deref()).await;
+                    (chunk, result)
+                });
+            }
+            // Dropping the set on cancellation aborts the requests still in flight
+            let Some(joined) = in_flight.join_next().await else {
+                break;
+            };
+            let (chunk, result) = joined.map_err(|e| format!("AI batch request failed: {}", e))?;
+            if let Err(e) = &result {
+                warn!("Batch {} request failed: {}", batch.id, e);
+            }
+            batch.apply(&chunk, result);
+            store.save(batch.clone())?;
+            let settled = batch
+                .items
+                .iter()
+                .filter(|i| i.status != ItemStatus::Pending)
+                .count();
+            ctx.report("process", settled as u64, total);
+            ctx.checkpoint()?;
+        }
+        Ok::<(), String>(())
+    })?;
+    let summary = batch.summary();
+    info!(
+        "AI batch {} finished: {} of {} succeeded, {} failed",
+        batch.id,
+        summary.succeeded,
+        summary.total,
+        summary.failures.len()
+    );
+    Ok(summary)
+}
+
+fn start_batch(app: &AppHandle, batch: AiBatch) -> Result<String, String> {
+    let batch_id = batch.id.clone();
+    if !app
+        .state::<BatchStore>()
+        .running
+        .lock()
+        .unwrap()
+        .insert(batch_id.clone())
+    {
+        return Err(format!("AI batch {} is already running", batch_id));
+    }
+    let handle = app.clone();
+    Ok(spawn_task_with(
+        app,
+        "ai_batch",
+        ResourceClass::Network,
+        Priority::Background,
+        move |ctx: TaskContext| {
+            let _guard = RunningGuard {
+                app: handle.clone(),
+                batch_id,
+            };
+            run(&ctx, &handle, batch)
+        },
+    ))
+}
+
+/// Continue batches an earlier session left unfinished
+pub fn resume_batches(app: &AppHandle) {
+    let pending: Vec<AiBatch> = app
+        .state::<BatchStore>()
+        .list()
+        .into_iter()
+        .filter(|b| !b.is_finished())
+        .collect();
+    for batch in pending {
+        info!("Resuming AI batch {}", batch.id);
+        if let Err(e) = start_batch(app, batch) {
+            warn!("Failed to resume AI batch: {}", e);
+        }
+    }
+}
+
+/// Run an AI task over many items in the background
+#[tauri::command]
+pub fn run_ai_batch(
+    app: AppHandle,
+    task_kind: AiTask,
+    items: Vec<BatchInput>,
+    model: Option<String>,
+) -> Result<BatchHandle, String> {
+    if items.is_empty() || items.len() > MAX_ITEMS {
+        return Err(format!("A batch needs between 1 and {} items", MAX_ITEMS));
+    }
+    let mut ids = HashSet::new();
+    if let Some(item) = items.iter().find(|item| !ids.insert(item.id.as_str())) {
+        return Err(format!("Duplicate batch item id: {}", item.id));
+    }
+    if let Some(name) = &model {
+        let settings = app.state::<SettingsStore>().get().ai;
+        let hosted_allowed = app
+            .state::<CompliancePolicy>()
+            .allows(Subsystem::ExternalAi);
+        ai::route(&settings, task_kind, 0, Some(name), hosted_allowed)?;
+    }
+    let batch = AiBatch::new(task_kind, model, items);
+    app.state::<BatchStore>().save(batch.clone())?;
+    info!(
+        "Starting AI batch {} ({:?}, {} items)",
+        batch.id,
+        task_kind,
+        batch.items.len()
+    );
+    Ok(BatchHandle {
+        batch_id: batch.id.clone(),
+        task_id: start_batch(&app, batch)?,
+    })
+}
+
+/// Continue a batch, optionally retrying the items that failed
+#[tauri::command]
+pub fn resume_ai_batch(
+    app: AppHandle,
+    batch_id: String,
+    retry_failed: bool,
+) -> Result<TaskHandle, String> {
+    let store = app.state::<BatchStore>();
+    let mut batch = store
+        .get(&batch_id)
+        .ok_or_else(|| format!("AI batch not found: {}", batch_id))?;
+    if retry_failed {
+        batch.retry_failed();
+        store.save(batch.clone())?;
+    }
+    if batch.is_finished() {
+        return Err(format!("AI batch {} has nothing left to process", batch_id));
+    }
+    Ok(TaskHandle {
+        task_id: start_batch(&app, batch)?,
+    })
+}
+
+/// A batch with every item's status and result
+#[tauri::command]
+pub fn get_ai_batch(store: State<'_, BatchStore>, batch_id: String) -> Result<AiBatch, String> {
+    store
+        .get(&batch_id)
+        .ok_or_else(|| format!("AI batch not found: {}", batch_id))
+}
+
+#[tauri::command]
+pub fn list_ai_batches(store: State<'_, BatchStore>) -> Result<Vec<BatchSummary>, String> {
+    Ok(store.list().iter().rev().map(AiBatch::summary).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(n: usize, len: usize) -> Vec<BatchInput> {
+        (0..n)
+            .map(|i| BatchInput {
+                id: format!("news{}", i),
+                input: "涨".repeat(len),
+            })
+            .collect()
+    }
+
+    fn completion(text: &str) -> Result<Completion, String> {
+        Ok(Completion {
+            text: text.to_string(),
+            model: "local-small".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_packing_and_partial_failures() {
+        let batch = AiBatch::new(AiTask::Sentiment, None, inputs(45, 10));
+        let sizes: Vec<usize> = batch.plan().iter().map(Vec::len).collect();
+        assert_eq!(sizes, [20, 20, 5]);
+        // Long items get fewer neighbours, and long tasks go one by one
+        let batch = AiBatch::new(AiTask::Summary, None, inputs(4, 1_000));
+        assert_eq!(
+            batch.plan().iter().map(Vec::len).collect::<Vec<_>>(),
+            [3, 1]
+        );
+        let batch = AiBatch::new(AiTask::Analysis, None, inputs(3, 10));
+        assert_eq!(batch.plan().len(), 3);
+
+        let mut batch = AiBatch::new(AiTask::Sentiment, None, inputs(4, 10));
+        let (_, user) = batch.prompt(&[0, 1, 2]);
+        assert!(user.starts_with("[1] ") && user.contains("\n[3] "));
+        batch.apply(
+            &[0, 1, 2],
+            completion("结果如下：\n[{\"index\": 1, \"result\": \"positive\"}, {\"index\": 3, \"result\": \" negative \"}]"),
+        );
+        batch.apply(
+            &[3],
+            Err("Failed to reach AI model local-small".to_string()),
+        );
+        assert_eq!(
+            batch.items[2].status,
+            ItemStatus::Done {
+                text: "negative".to_string(),
+                model: "local-small".to_string()
+            }
+        );
+        let summary = batch.summary();
+        assert_eq!((summary.succeeded, summary.pending), (2, 0));
+        assert_eq!(
+            summary
+                .failures
+                .iter()
+                .map(|f| f.id.as_str())
+                .collect::<Vec<_>>(),
+            ["news1", "news3"]
+        );
+        assert!(batch.is_finished());
+        assert!(parse_packed("无法解析").is_empty());
+    }
+
+    #[test]
+    fn test_resume_skips_finished_items() {
+        let path = std::env::temp_dir().join(format!("ssi-ai-batch-{}.json", std::process::id()));
+        let store = BatchStore::load(path.clone());
+        let mut batch = AiBatch::new(AiTask::Summary, None, inputs(3, 10));
+        batch.apply(&[0], completion("[{\"index\": 1, \"result\": \"摘要\"}]"));
+        batch.apply(&[1], Err("timeout".to_string()));
+        store.save(batch.clone()).unwrap();
+
+        let mut reloaded = BatchStore::load(path.clone()).get(&batch.id).unwrap();
+        assert!(!reloaded.is_finished());
+        assert_eq!(reloaded.plan(), [vec![2]]);
+        reloaded.retry_failed();
+        assert_eq!(reloaded.plan(), [vec![1, 2]]);
+        std::fs::remove_file(path).ok();
+    }
+}
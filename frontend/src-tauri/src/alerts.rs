@@ -0,0 +1,643 @@
+//! Price alert engine.
+//!
+//! Alerts stored in the database are checked against every quote that
+//! changed. An enabled, armed alert whose condition holds fires a price
+//! alert notification and is disarmed. A one-shot alert then stays quiet
+//! until it is re-enabled or snoozed; a recurring one re-arms once the value
+//! has moved back past the threshold by its hysteresis band, so a price
+//! oscillating around the threshold fires once per real crossing instead of
+//! on every tick. Snoozing a fired alert, for some minutes or until the next
+//! A-share session opens, arms it again but keeps it quiet until then; if the
+//! condition still holds when the snooze ends, it fires again. Every firing
+//! is kept in the alert's trigger history.
+//!
+//! Quotes of a market in its pre-open are passed over, since auction
+//! prices are indicative until the open.
+//!
+//! Besides the quote itself, alerts compare recent moves and volume and the
+//! daily moving average. [`QuoteHistory`] keeps the last [`HISTORY`] of each
+//! symbol's quotes for the moves over a window of minutes and for volume
+//! spikes, which set the volume of the latest window against the average of
+//! the windows before it. Moving averages are of completed daily closes from
+//! the columnar cache.
+//!
+//! Alert symbols needn't be on screen: every [`HOLD_INTERVAL`] the symbols
+//! of enabled alerts, and the holdings portfolio alerts value, are held at
+//! quote level under [`OWNER`] so the quote poller keeps them fresh, and the
+//! daily bars of moving average alerts are fetched if they aren't cached yet.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::calendar;
+use crate::columnar::ColumnarStore;
+use crate::db::{Alert, AlertKind, Alerts, Database, Rearm};
+use crate::delivery::{self, AlertMessage};
+use crate::executor::Priority;
+use crate::kline::{self, series_interval, Adjust, KlinePeriod};
+use crate::market_clock::{MarketClock, MarketPhase};
+use crate::models::Market;
+use crate::notifications::{self, Notification, NotificationAction, NotificationCategory};
+use crate::portfolio_alerts;
+use crate::quotes::Quote;
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::subscriptions::{self, SubscriptionLevel, SubscriptionRegistry};
+
+/// Subscription owner of alert symbols
+pub const OWNER: &str = "alerts";
+
+/// Longest snooze, in minutes
+const MAX_SNOOZE_MINUTES: u32 = 7 * 24 * 60;
+/// Quote history kept per symbol, enough for the longest window and the
+/// windows before it that a volume spike is measured against
+const HISTORY: i64 = 4 * 60 * 60;
+/// Time between refreshes of the alert symbols held
+const HOLD_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Snooze {
+    Minutes(u32),
+    /// Until the next A-share session opens
+    NextSession,
+}
+
+impl Snooze {
+    /// Unix seconds the snooze ends at
+    fn until(self, now: i64) -> Result<i64, String> {
+        match self {
+            Snooze::Minutes(minutes) if (1..=MAX_SNOOZE_MINUTES).contains(&minutes) => {
+                Ok(now + i64::from(minutes) * 60)
+            }
+            Snooze::Minutes(minutes) => Err(format!(
+                "Snooze must be between 1 and {} minutes, got {}",
+                MAX_SNOOZE_MINUTES, minutes
+            )),
+            Snooze::NextSession => {
+                let local = chrono::DateTime::from_timestamp(now, 0)
+                    .unwrap_or_default()
+                    .with_timezone(&sessions::timezone(Market::Cn));
+                Ok(calendar::next_open(local).timestamp())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+    Fire,
+    Rearm,
+}
+
+/// A symbol's quote at one time
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    timestamp: i64,
+    price: f64,
+    /// Shares traded so far today
+    volume: f64,
+}
+
+/// Recent quotes by symbol, oldest first
+#[derive(Default)]
+pub struct QuoteHistory(Mutex<HashMap<String, VecDeque<Sample>>>);
+
+impl QuoteHistory {
+    fn record(&self, quotes: &[Quote]) {
+        let mut history = self.0.lock().unwrap();
+        for quote in quotes {
+            let samples = history.entry(quote.symbol.clone()).or_default();
+            // Volume counts from zero again on a new trading day
+            if samples
+                .back()
+                .is_some_and(|last| quote.volume < last.volume || quote.timestamp < last.timestamp)
+            {
+                samples.clear();
+            }
+            samples.push_back(Sample {
+                timestamp: quote.timestamp,
+                price: quote.price,
+                volume: quote.volume,
+            });
+            while samples
+                .front()
+                .is_some_and(|first| first.timestamp < quote.timestamp - HISTORY)
+            {
+                samples.pop_front();
+            }
+        }
+        history.retain(|_, samples| !samples.is_empty());
+    }
+
+    fn samples(&self, symbol: &str) -> Vec<Sample> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(symbol)
+            .map(|samples| samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Latest sample at or before `timestamp`
+fn sample_at(samples: &[Sample], timestamp: i64) -> Option<&Sample> {
+    samples.iter().rev().find(|s| s.timestamp <= timestamp)
+}
+
+/// Change in percent over the last `minutes`, once the history reaches back
+/// that far
+fn move_pct(samples: &[Sample], minutes: u32) -> Option<f64> {
+    let last = samples.last()?;
+    let base = sample_at(samples, last.timestamp - i64::from(minutes) * 60)?;
+    (base.price > 0.0).then(|| (last.price / base.price - 1.0) * 100.0)
+}
+
+/// Volume of the last `minutes` over the average of the windows of as many
+/// minutes before it, once there is at least one
+fn volume_ratio(samples: &[Sample], minutes: u32) -> Option<f64> {
+    let span = i64::from(minutes) * 60;
+    let (first, last) = (samples.first()?, samples.last()?);
+    let start = sample_at(samples, last.timestamp - span)?;
+    let earlier = (start.timestamp - first.timestamp) as f64 / span as f64;
+    if earlier < 1.0 {
+        return None;
+    }
+    let average = (start.volume - first.volume) / earlier;
+    (average > 0.0).then(|| (last.volume - start.volume) / average)
+}
+
+/// Distance in percent of `price` from the average of the last `days` closes
+fn ma_distance(closes: &[f64], days: u32, price: f64) -> Option<f64> {
+    let days = days as usize;
+    if days == 0 || closes.len() < days {
+        return None;
+    }
+    let average = closes[closes.len() - days..].iter().sum::<f64>() / days as f64;
+    (average > 0.0).then(|| (price / average - 1.0) * 100.0)
+}
+
+/// Closes of a symbol's completed daily bars, leaving out the quote's day
+fn daily_closes(store: &ColumnarStore, quote: &Quote) -> Vec<f64> {
+    let interval = series_interval(KlinePeriod::Daily, Adjust::None);
+    let Ok(bars) = store.open(&quote.symbol, &interval) else {
+        return Vec::new();
+    };
+    let market = Market::of(&quote.symbol).unwrap_or(Market::Cn);
+    let today = sessions::trading_day(market, quote.timestamp);
+    let completed = bars
+        .timestamps()
+        .iter()
+        .take_while(|ts| sessions::trading_day(market, **ts) < today)
+        .count();
+    bars.closes()[..completed].to_vec()
+}
+
+/// The value an alert compares with its threshold, from the quote, the
+/// symbol's recent `samples` and its daily `closes`; `None` while there
+/// isn't enough history
+fn observed(alert: &Alert, quote: &Quote, samples: &[Sample], closes: &[f64]) -> Option<f64> {
+    let window = alert.window.unwrap_or(0);
+    match alert.kind {
+        AlertKind::PriceAbove | AlertKind::PriceBelow => Some(quote.price),
+        AlertKind::ChangeAbove | AlertKind::ChangeBelow => Some(quote.change_pct),
+        AlertKind::MoveAbove | AlertKind::MoveBelow => move_pct(samples, window),
+        AlertKind::VolumeSpike => volume_ratio(samples, window),
+        AlertKind::MaCrossAbove | AlertKind::MaCrossBelow => {
+            ma_distance(closes, window, quote.price)
+        }
+    }
+}
+
+fn is_above(kind: AlertKind) -> bool {
+    matches!(
+        kind,
+        AlertKind::PriceAbove
+            | AlertKind::ChangeAbove
+            | AlertKind::MoveAbove
+            | AlertKind::VolumeSpike
+            | AlertKind::MaCrossAbove
+    )
+}
+
+fn transition(alert: &Alert, value: f64, now: i64) -> Option<Transition> {
+    if !alert.enabled {
+        return None;
+    }
+    let above = is_above(alert.kind);
+    if alert.armed {
+        let snoozed = alert.snoozed_until.is_some_and(|until| now < until);
+        let holds = if above {
+            value >= alert.threshold
+        } else {
+            value <= alert.threshold
+        };
+        return (!snoozed && holds).then_some(Transition::Fire);
+    }
+    let Rearm::Recurring { hysteresis } = alert.rearm else {
+        return None;
+    };
+    let cleared = if above {
+        value < alert.threshold - hysteresis
+    } else {
+        value > alert.threshold + hysteresis
+    };
+    cleared.then_some(Transition::Rearm)
+}
+
+fn notification(alert: &Alert, quote: &Quote) -> Notification {
+    let condition = match alert.kind {
+        AlertKind::PriceAbove => format!("价格升至 {:.2} 以上", alert.threshold),
+        AlertKind::PriceBelow => format!("价格跌至 {:.2} 以下", alert.threshold),
+        AlertKind::ChangeAbove => format!("涨跌幅高于 {:.2}%", alert.threshold),
+        AlertKind::ChangeBelow => format!("涨跌幅低于 {:.2}%", alert.threshold),
+        AlertKind::MoveAbove => format!(
+            "{} 分钟涨幅达 {:.2}%",
+            alert.window.unwrap_or(0),
+            alert.threshold
+        ),
+        AlertKind::MoveBelow => format!(
+            "{} 分钟涨跌幅低于 {:.2}%",
+            alert.window.unwrap_or(0),
+            alert.threshold
+        ),
+        AlertKind::VolumeSpike => format!(
+            "{} 分钟成交量放大 {:.1} 倍以上",
+            alert.window.unwrap_or(0),
+            alert.threshold
+        ),
+        AlertKind::MaCrossAbove if alert.threshold == 0.0 => {
+            format!("站上 {} 日均线", alert.window.unwrap_or(0))
+        }
+        AlertKind::MaCrossAbove => format!(
+            "高于 {} 日均线 {:.2}%",
+            alert.window.unwrap_or(0),
+            alert.threshold
+        ),
+        AlertKind::MaCrossBelow if alert.threshold == 0.0 => {
+            format!("跌破 {} 日均线", alert.window.unwrap_or(0))
+        }
+        AlertKind::MaCrossBelow => format!(
+            "偏离 {} 日均线 {:.2}% 以下",
+            alert.window.unwrap_or(0),
+            alert.threshold
+        ),
+    };
+    let mut body = format!("现价 {:.2}（{:+.2}%）", quote.price, quote.change_pct);
+    if !alert.note.is_empty() {
+        body = format!("{}\n{}", body, alert.note);
+    }
+    Notification {
+        category: NotificationCategory::PriceAlert,
+        title: format!("{} {}", alert.symbol, condition),
+        body,
+        actions: vec![
+            NotificationAction::open_chart(&alert.symbol),
+            NotificationAction::snooze_alert(&alert.id, 60, "暂停 1 小时"),
+            NotificationAction::dismiss(),
+        ],
+    }
+}
+
+/// Fire and re-arm alerts on the symbols of freshly changed quotes
+pub fn evaluate(app: &AppHandle, quotes: &[Quote]) -> Result<(), String> {
+    if quotes.is_empty() {
+        return Ok(());
+    }
+    let history = app.state::<QuoteHistory>();
+    history.record(quotes);
+    let store = app.state::<ColumnarStore>();
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    let alerts = Alerts(&conn);
+    let now = Utc::now().timestamp();
+    let policy = app.state::<SettingsStore>().get().notifications;
+    // Each symbol's history and closes, read once however many alerts it has
+    let mut recent: HashMap<String, Vec<Sample>> = HashMap::new();
+    let mut daily: HashMap<String, Vec<f64>> = HashMap::new();
+    let clock = app.state::<MarketClock>();
+    for alert in alerts.list(None)? {
+        let Some(quote) = quotes.iter().find(|q| q.symbol == alert.symbol) else {
+            continue;
+        };
+        // Auction prices are indicative until the open
+        let market = Market::of(&quote.symbol).unwrap_or(Market::Cn);
+        if clock.phase(market) == MarketPhase::PreOpen {
+            continue;
+        }
+        let samples = recent
+            .entry(alert.symbol.clone())
+            .or_insert_with(|| history.samples(&alert.symbol));
+        if matches!(
+            alert.kind,
+            AlertKind::MaCrossAbove | AlertKind::MaCrossBelow
+        ) {
+            daily
+                .entry(alert.symbol.clone())
+                .or_insert_with(|| daily_closes(&store, quote));
+        }
+        let closes = daily
+            .get(&alert.symbol)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let Some(value) = observed(&alert, quote, samples, closes) else {
+            continue;
+        };
+        match transition(&alert, value, now) {
+            Some(Transition::Fire) => {
+                let fired = alerts.fire(&alert.id, value, quote.price)?;
+                info!(
+                    "Alert {} fired on {} at {}",
+                    fired.id, fired.symbol, quote.price
+                );
+                let notification = notification(&fired, quote);
+                if !policy.allows_alert(&fired.symbol) {
+                    info!("Alert {} held back by quiet hours or snooze", fired.id);
+                    if let Err(e) = notifications::record(app, &notification) {
+                        warn!("{}", e);
+                    }
+                    continue;
+                }
+                delivery::deliver(
+                    app,
+                    &fired.channels,
+                    AlertMessage {
+                        title: notification.title.clone(),
+                        body: notification.body.clone(),
+                    },
+                );
+                if let Err(e) = notifications::notify(app, notification) {
+                    warn!("{}", e);
+                }
+            }
+            Some(Transition::Rearm) => {
+                alerts.arm(&alert.id)?;
+                info!("Alert {} re-armed on {}", alert.id, alert.symbol);
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Symbols of enabled alerts and of the holdings portfolio alerts value,
+/// and those of them on moving averages
+fn alert_symbols(app: &AppHandle) -> Result<(Vec<String>, Vec<String>), String> {
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    let enabled: Vec<Alert> = Alerts(&conn)
+        .list(None)?
+        .into_iter()
+        .filter(|alert| alert.enabled)
+        .collect();
+    let averaged = enabled
+        .iter()
+        .filter(|alert| {
+            matches!(
+                alert.kind,
+                AlertKind::MaCrossAbove | AlertKind::MaCrossBelow
+            )
+        })
+        .map(|alert| alert.symbol.clone())
+        .collect();
+    let mut symbols: Vec<String> = enabled.into_iter().map(|alert| alert.symbol).collect();
+    symbols.extend(portfolio_alerts::watched_symbols(app));
+    Ok((symbols, averaged))
+}
+
+/// Keep the symbols of enabled alerts polled, and the daily bars of moving
+/// average alerts cached, for as long as the app runs
+pub fn start_alert_watch(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match alert_symbols(&handle) {
+                Ok((symbols, averaged)) => {
+                    let changes = handle.state::<SubscriptionRegistry>().hold(
+                        OWNER,
+                        &symbols,
+                        SubscriptionLevel::Quote,
+                    );
+                    subscriptions::emit_changes(&handle, changes);
+                    let interval = series_interval(KlinePeriod::Daily, Adjust::None);
+                    for symbol in averaged {
+                        if handle.state::<ColumnarStore>().exists(&symbol, &interval) {
+                            continue;
+                        }
+                        if let Err(e) = kline::candles(
+                            &handle,
+                            &symbol,
+                            KlinePeriod::Daily,
+                            Adjust::None,
+                            Priority::Background,
+                        )
+                        .await
+                        {
+                            warn!("Failed to fetch daily bars for alerts on {}: {}", symbol, e);
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to read alerts: {}", e),
+            }
+            tokio::time::sleep(HOLD_INTERVAL).await;
+        }
+    });
+}
+
+/// Quiet a fired alert for a while, after which it fires again if still met
+#[tauri::command]
+pub fn snooze_alert(
+    db: State<'_, Database>,
+    alert_id: String,
+    snooze: Snooze,
+) -> Result<Alert, String> {
+    apply_snooze(&db, &alert_id, snooze)
+}
+
+/// Snooze an alert, from the command or a notification's button
+pub fn apply_snooze(db: &Database, alert_id: &str, snooze: Snooze) -> Result<Alert, String> {
+    let until = snooze.until(Utc::now().timestamp())?;
+    let conn = db.conn()?;
+    let alert = Alerts(&conn).snooze(alert_id, until)?;
+    info!("Alert {} snoozed until {}", alert_id, until);
+    Ok(alert)
+}
+
+/// Choose between one-shot and recurring with a hysteresis band
+#[tauri::command]
+pub fn set_alert_rearm(
+    db: State<'_, Database>,
+    alert_id: String,
+    rearm: Rearm,
+) -> Result<Alert, String> {
+    let conn = db.conn()?;
+    Alerts(&conn).set_rearm(&alert_id, rearm)
+}
+
+/// Route an alert to delivery channels besides the desktop
+#[tauri::command]
+pub fn set_alert_channels(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    alert_id: String,
+    channels: Vec<String>,
+) -> Result<Alert, String> {
+    settings.get().delivery.check_routes(&channels)?;
+    let conn = db.conn()?;
+    Alerts(&conn).set_channels(&alert_id, &channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Currency;
+
+    fn alert(kind: AlertKind, threshold: f64, rearm: Rearm) -> Alert {
+        Alert {
+            id: "alert-1".to_string(),
+            symbol: "SH600519".to_string(),
+            kind,
+            threshold,
+            enabled: true,
+            note: String::new(),
+            created_at: String::new(),
+            triggered_at: None,
+            rearm,
+            armed: true,
+            snoozed_until: None,
+            window: None,
+            channels: Vec::new(),
+        }
+    }
+
+    fn quote(price: f64) -> Quote {
+        Quote {
+            symbol: "SH600519".to_string(),
+            name: "贵州茅台".to_string(),
+            price,
+            prev_close: 1680.0,
+            open: 1680.0,
+            high: price,
+            low: price,
+            volume: 0.0,
+            amount: 0.0,
+            change: price - 1680.0,
+            change_pct: (price / 1680.0 - 1.0) * 100.0,
+            currency: Currency::Cny,
+            timestamp: 0,
+            source: "tencent".to_string(),
+        }
+    }
+
+    /// Run an alert over a price path, returning the prices it fired at
+    fn fired_at(mut alert: Alert, prices: &[f64]) -> Vec<f64> {
+        let mut fired = Vec::new();
+        for &price in prices {
+            let value = observed(&alert, &quote(price), &[], &[]).unwrap();
+            match transition(&alert, value, 0) {
+                Some(Transition::Fire) => {
+                    alert.armed = false;
+                    fired.push(price);
+                }
+                Some(Transition::Rearm) => alert.armed = true,
+                None => {}
+            }
+        }
+        fired
+    }
+
+    #[test]
+    fn test_hysteresis_limits_refiring() {
+        let wobble = [
+            1699.0, 1700.5, 1699.5, 1700.2, 1698.0, 1701.0, 1689.0, 1702.0,
+        ];
+        let once = alert(AlertKind::PriceAbove, 1700.0, Rearm::Once);
+        assert_eq!(fired_at(once, &wobble), [1700.5]);
+        // Without a band every wobble across the threshold fires
+        let bare = alert(
+            AlertKind::PriceAbove,
+            1700.0,
+            Rearm::Recurring { hysteresis: 0.0 },
+        );
+        assert_eq!(fired_at(bare, &wobble), [1700.5, 1700.2, 1701.0, 1702.0]);
+        let banded = alert(
+            AlertKind::PriceAbove,
+            1700.0,
+            Rearm::Recurring { hysteresis: 5.0 },
+        );
+        assert_eq!(fired_at(banded, &wobble), [1700.5, 1702.0]);
+        let below = alert(
+            AlertKind::ChangeBelow,
+            -1.0,
+            Rearm::Recurring { hysteresis: 0.5 },
+        );
+        assert_eq!(
+            fired_at(below, &[1660.0, 1670.0, 1655.0, 1675.0, 1655.0]),
+            [1660.0, 1655.0]
+        );
+    }
+
+    #[test]
+    fn test_snooze_delays_firing() {
+        let mut snoozed = alert(AlertKind::PriceBelow, 1650.0, Rearm::Once);
+        snoozed.snoozed_until = Some(100);
+        assert_eq!(transition(&snoozed, 1640.0, 99), None);
+        assert_eq!(transition(&snoozed, 1640.0, 100), Some(Transition::Fire));
+
+        assert_eq!(Snooze::Minutes(30).until(1_000).unwrap(), 2_800);
+        assert!(Snooze::Minutes(0).until(0).is_err());
+        // Friday 2024-07-05 15:30 Shanghai snoozes to Monday's open
+        assert_eq!(
+            Snooze::NextSession.until(1_720_164_600).unwrap(),
+            1_720_402_200
+        );
+    }
+
+    #[test]
+    fn test_windowed_values() {
+        // A quote a minute, volume picking up in the last five minutes
+        let samples: Vec<Sample> = (0..=20)
+            .map(|minute| Sample {
+                timestamp: 1_000 + minute * 60,
+                price: 100.0 + minute as f64 * 0.1,
+                volume: if minute <= 15 {
+                    minute as f64 * 1_000.0
+                } else {
+                    15_000.0 + (minute - 15) as f64 * 4_000.0
+                },
+            })
+            .collect();
+        let moved = move_pct(&samples, 10).unwrap();
+        assert!((moved - (102.0 / 101.0 - 1.0) * 100.0).abs() < 1e-9);
+        assert_eq!(move_pct(&samples, 30), None);
+        // 20 000 shares in the last five minutes against 5 000 before
+        assert_eq!(volume_ratio(&samples, 5), Some(4.0));
+        assert_eq!(volume_ratio(&samples, 15), None);
+
+        let history = QuoteHistory::default();
+        let mut tick = quote(1690.0);
+        tick.volume = 5_000.0;
+        history.record(&[tick.clone()]);
+        tick.timestamp = 60;
+        tick.volume = 100.0;
+        history.record(&[tick]);
+        // Falling volume starts a new day
+        assert_eq!(history.samples("SH600519").len(), 1);
+
+        let closes = [10.0, 11.0, 12.0, 13.0];
+        assert_eq!(ma_distance(&closes, 2, 12.5), Some(0.0));
+        assert_eq!(ma_distance(&closes, 5, 12.5), None);
+        let cross = Alert {
+            window: Some(3),
+            ..alert(AlertKind::MaCrossBelow, 0.0, Rearm::Once)
+        };
+        let below = observed(&cross, &quote(11.0), &[], &closes).unwrap();
+        assert_eq!(transition(&cross, below, 0), Some(Transition::Fire));
+    }
+}
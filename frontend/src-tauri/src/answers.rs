@@ -0,0 +1,206 @@
+//! Provenance of AI-generated answers and summaries.
+//!
+//! Every answer is stored with the material it was grounded on: document
+//! passages (with the quoted text the model saw), news items and data values
+//! such as prices, each numbered as it was presented to the model and marked
+//! when the answer cites it. `get_answer_sources` returns that record so a
+//! claim can be checked against its source instead of taken on trust.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::utils::{read_from_file, write_to_file};
+
+/// Answers kept on disk; the oldest are dropped beyond this
+const MAX_ANSWERS: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Grounding {
+    /// A passage of a research document
+    Chunk {
+        document_id: String,
+        chunk_index: usize,
+        title: String,
+        quote: String,
+        start_ms: Option<u64>,
+    },
+    News {
+        news_id: String,
+        title: String,
+        url: String,
+        /// Unix seconds
+        published_at: i64,
+    },
+    /// A number the model was given, e.g. a closing price
+    Data {
+        label: String,
+        value: f64,
+        symbol: Option<String>,
+        as_of: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    /// Number the source was presented to the model under, `[n]` in the answer
+    pub index: usize,
+    /// Whether the answer cites it
+    pub cited: bool,
+    #[serde(flatten)]
+    pub grounding: Grounding,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Answer {
+    pub id: String,
+    /// What produced the answer, e.g. `research_question`
+    pub kind: String,
+    pub prompt: String,
+    pub text: String,
+    pub model: String,
+    pub symbol: Option<String>,
+    pub sources: Vec<Source>,
+    pub created_at: String,
+}
+
+/// Source numbers cited as `[n]` (or `[1, 3]`) in an answer
+pub fn cited_indices(text: &str) -> BTreeSet<usize> {
+    let mut cited = BTreeSet::new();
+    for part in text.split('[').skip(1) {
+        let Some((inside, _)) = part.split_once(']') else {
+            continue;
+        };
+        for number in inside.split([',', '，']) {
+            if let Ok(n) = number.trim().parse() {
+                cited.insert(n);
+            }
+        }
+    }
+    cited
+}
+
+/// Number groundings from 1 and mark the ones `text` cites
+pub fn number_sources(groundings: Vec<Grounding>, text: &str) -> Vec<Source> {
+    let cited = cited_indices(text);
+    groundings
+        .into_iter()
+        .enumerate()
+        .map(|(i, grounding)| Source {
+            index: i + 1,
+            cited: cited.contains(&(i + 1)),
+            grounding,
+        })
+        .collect()
+}
+
+pub struct AnswerStore {
+    path: PathBuf,
+    answers: RwLock<Vec<Answer>>,
+}
+
+impl AnswerStore {
+    pub fn load(path: PathBuf) -> Self {
+        let answers = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            answers: RwLock::new(answers),
+        }
+    }
+
+    pub fn record(&self, answer: Answer) -> Result<(), String> {
+        let mut answers = self.answers.write().unwrap();
+        answers.push(answer);
+        if answers.len() > MAX_ANSWERS {
+            let excess = answers.len() - MAX_ANSWERS;
+            answers.drain(..excess);
+        }
+        let content = serde_json::to_string(&*answers)
+            .map_err(|e| format!("Failed to serialize answers: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save answers: {}", e))
+    }
+
+    pub fn get(&self, id: &str) -> Option<Answer> {
+        self.answers
+            .read()
+            .unwrap()
+            .iter()
+            .find(|a| a.id == id)
+            .cloned()
+    }
+}
+
+/// What an answer was grounded on, cited sources included
+#[tauri::command]
+pub fn get_answer_sources(
+    store: State<'_, AnswerStore>,
+    answer_id: String,
+) -> Result<Vec<Source>, String> {
+    store
+        .get(&answer_id)
+        .map(|answer| answer.sources)
+        .ok_or_else(|| format!("Answer not found: {}", answer_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources_recorded_with_citations() {
+        assert_eq!(
+            cited_indices("营收增长 [1]，毛利率承压 [2, 4]。[注] [x]"),
+            BTreeSet::from([1, 2, 4])
+        );
+
+        let groundings = vec![
+            Grounding::Chunk {
+                document_id: "doc1".to_string(),
+                chunk_index: 3,
+                title: "2024年年度报告".to_string(),
+                quote: "营业收入同比增长15%".to_string(),
+                start_ms: None,
+            },
+            Grounding::Data {
+                label: "收盘价".to_string(),
+                value: 1688.0,
+                symbol: Some("SH600519".to_string()),
+                as_of: "2025-06-03".to_string(),
+            },
+        ];
+        let sources = number_sources(groundings, "收入增长15% [1]");
+        assert_eq!(
+            sources
+                .iter()
+                .map(|s| (s.index, s.cited))
+                .collect::<Vec<_>>(),
+            vec![(1, true), (2, false)]
+        );
+
+        let path = std::env::temp_dir().join(format!("ssi-answers-{}.json", std::process::id()));
+        let store = AnswerStore::load(path.clone());
+        store
+            .record(Answer {
+                id: "answer1".to_string(),
+                kind: "research_question".to_string(),
+                prompt: "收入怎么样".to_string(),
+                text: "收入增长15% [1]".to_string(),
+                model: "qwen2.5:7b".to_string(),
+                symbol: Some("SH600519".to_string()),
+                sources,
+                created_at: "2025-06-03 08:00:00 UTC".to_string(),
+            })
+            .unwrap();
+        let reloaded = AnswerStore::load(path.clone()).get("answer1").unwrap();
+        assert_eq!(reloaded.sources.len(), 2);
+        assert!(AnswerStore::load(path.clone()).get("missing").is_none());
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,214 @@
+//! Theme and appearance.
+//!
+//! The user picks light, dark or "follow the system" plus an accent colour
+//! and the colour convention for price moves: red for up and green for down
+//! as on mainland exchanges, or the reverse as in most other markets. The OS
+//! theme is tracked from window events, and every change to what the UI
+//! should show is emitted as `theme-changed` with the resolved
+//! [`ThemeInfo`]. Rust-side rendering (reports, chart images) reads the same
+//! colours from [`AppearanceSettings`] so generated artifacts match the app.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State, Theme};
+
+use crate::settings::SettingsStore;
+
+const RED: &str = "#f23645";
+const GREEN: &str = "#089981";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeMode {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+/// Which colour marks a rising price
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorConvention {
+    /// 红涨绿跌, as on mainland exchanges
+    #[default]
+    RedUp,
+    GreenUp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppearanceSettings {
+    pub theme: ThemeMode,
+    /// `#rrggbb`
+    pub accent_color: String,
+    pub color_convention: ColorConvention,
+}
+
+impl Default for AppearanceSettings {
+    fn default() -> Self {
+        Self {
+            theme: ThemeMode::System,
+            accent_color: "#1677ff".to_string(),
+            color_convention: ColorConvention::RedUp,
+        }
+    }
+}
+
+impl AppearanceSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let hex = self.accent_color.strip_prefix('#').unwrap_or("");
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "Invalid accent color {}: expected #rrggbb",
+                self.accent_color
+            ));
+        }
+        Ok(())
+    }
+
+    /// Colour of rising prices
+    pub fn up_color(&self) -> &'static str {
+        match self.color_convention {
+            ColorConvention::RedUp => RED,
+            ColorConvention::GreenUp => GREEN,
+        }
+    }
+
+    /// Colour of falling prices
+    pub fn down_color(&self) -> &'static str {
+        match self.color_convention {
+            ColorConvention::RedUp => GREEN,
+            ColorConvention::GreenUp => RED,
+        }
+    }
+
+    /// Whether to render dark, given the OS theme
+    pub fn dark(&self, system_dark: bool) -> bool {
+        match self.theme {
+            ThemeMode::System => system_dark,
+            ThemeMode::Light => false,
+            ThemeMode::Dark => true,
+        }
+    }
+}
+
+/// The OS theme, as last reported by a window
+pub struct SystemTheme {
+    dark: AtomicBool,
+}
+
+impl SystemTheme {
+    pub fn new(theme: Option<Theme>) -> Self {
+        Self {
+            dark: AtomicBool::new(theme == Some(Theme::Dark)),
+        }
+    }
+
+    pub fn dark(&self) -> bool {
+        self.dark.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThemeInfo {
+    #[serde(flatten)]
+    pub settings: AppearanceSettings,
+    pub system_dark: bool,
+    /// What the UI should show
+    pub dark: bool,
+    pub up_color: String,
+    pub down_color: String,
+}
+
+impl ThemeInfo {
+    fn resolve(settings: AppearanceSettings, system_dark: bool) -> Self {
+        Self {
+            dark: settings.dark(system_dark),
+            up_color: settings.up_color().to_string(),
+            down_color: settings.down_color().to_string(),
+            settings,
+            system_dark,
+        }
+    }
+}
+
+fn emit_theme(app: &AppHandle, info: &ThemeInfo) {
+    if let Err(e) = app.emit("theme-changed", info.clone()) {
+        warn!("Failed to emit theme-changed event: {}", e);
+    }
+}
+
+/// Record an OS theme change reported by a window event
+pub fn on_system_theme(app: &AppHandle, theme: Theme) {
+    let dark = theme == Theme::Dark;
+    if app
+        .state::<SystemTheme>()
+        .dark
+        .swap(dark, Ordering::Relaxed)
+        == dark
+    {
+        return;
+    }
+    let settings = app.state::<SettingsStore>().get().appearance;
+    info!(
+        "System theme changed to {}",
+        if dark { "dark" } else { "light" }
+    );
+    // Only a UI that follows the system needs redrawing
+    if settings.theme == ThemeMode::System {
+        emit_theme(app, &ThemeInfo::resolve(settings, dark));
+    }
+}
+
+#[tauri::command]
+pub fn get_appearance(
+    settings: State<'_, SettingsStore>,
+    system: State<'_, SystemTheme>,
+) -> Result<ThemeInfo, String> {
+    Ok(ThemeInfo::resolve(settings.get().appearance, system.dark()))
+}
+
+/// Save appearance overrides and broadcast the resolved theme
+#[tauri::command]
+pub fn set_appearance(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    system: State<'_, SystemTheme>,
+    appearance: AppearanceSettings,
+) -> Result<ThemeInfo, String> {
+    let mut updated = settings.get();
+    updated.appearance = appearance.clone();
+    settings.set(updated)?;
+    let info = ThemeInfo::resolve(appearance, system.dark());
+    emit_theme(&app, &info);
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_resolution_and_colors() {
+        let mut settings = AppearanceSettings::default();
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.up_color(), RED);
+        assert!(ThemeInfo::resolve(settings.clone(), true).dark);
+        assert!(!ThemeInfo::resolve(settings.clone(), false).dark);
+
+        settings.theme = ThemeMode::Light;
+        settings.color_convention = ColorConvention::GreenUp;
+        let info = ThemeInfo::resolve(settings.clone(), true);
+        assert!(!info.dark);
+        assert_eq!(
+            (info.up_color.as_str(), info.down_color.as_str()),
+            (GREEN, RED)
+        );
+
+        settings.accent_color = "blue".to_string();
+        assert!(settings.validate().is_err());
+    }
+}
@@ -0,0 +1,451 @@
+//! Scheduled snapshots archived for compliance.
+//!
+//! Advisory users must be able to show what they saw at a given time. Each
+//! [`ArchiveSchedule`] asks for a snapshot every so many minutes, optionally
+//! only while the A-share market is in session: a PNG of the dashboard or a
+//! PDF of the portfolio. The webview renders them, so the main window gets an
+//! `archive-capture` event and hands the file back with
+//! [`submit_archive_snapshot`]; requests it doesn't answer within
+//! [`CAPTURE_TIMEOUT`] lapse.
+//!
+//! Files go into a folder per month under the archive folder, each with a
+//! `.sha256` sidecar in `sha256sum` format, and are listed in a hash-chained
+//! `manifest.jsonl` in the archive folder, so a removed or altered record
+//! shows. Snapshots older than [`ArchiveSettings::retention_days`] are
+//! deleted, and the deletion recorded, after each new one.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Local, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::audit::{AuditAction, AuditLog, AuditTrail};
+use crate::calendar;
+use crate::models::Market;
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::utils::{ensure_dir_exists, generate_id, get_app_data_dir};
+
+const MANIFEST: &str = "manifest.jsonl";
+/// Time between checks of the schedules
+const SCHEDULE_CHECK: Duration = Duration::from_secs(30);
+/// How long the webview has to answer a capture request
+const CAPTURE_TIMEOUT: i64 = 5 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotKind {
+    /// PNG of the dashboard
+    Dashboard,
+    /// PDF of the portfolio
+    Portfolio,
+}
+
+impl SnapshotKind {
+    fn extension(self) -> &'static str {
+        match self {
+            SnapshotKind::Dashboard => "png",
+            SnapshotKind::Portfolio => "pdf",
+        }
+    }
+
+    /// Whether `data` starts the way the kind's file format does
+    fn matches(self, data: &[u8]) -> bool {
+        match self {
+            SnapshotKind::Dashboard => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+            SnapshotKind::Portfolio => data.starts_with(b"%PDF-"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveSchedule {
+    pub kind: SnapshotKind,
+    pub every_minutes: u32,
+    /// Only while the A-share market is in session
+    #[serde(default)]
+    pub session_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    pub schedules: Vec<ArchiveSchedule>,
+    /// Where snapshots are kept; `archive` in the app data dir when unset
+    pub folder: Option<PathBuf>,
+    /// Days snapshots are kept; 0 keeps them forever
+    pub retention_days: u32,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self {
+            schedules: Vec::new(),
+            folder: None,
+            retention_days: 365,
+        }
+    }
+}
+
+impl ArchiveSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .schedules
+            .iter()
+            .any(|s| !(1..=7 * 24 * 60).contains(&s.every_minutes))
+        {
+            return Err("Snapshots must be scheduled 1 minute to 7 days apart".to_string());
+        }
+        if self
+            .folder
+            .as_ref()
+            .is_some_and(|folder| !folder.is_absolute())
+        {
+            return Err("The archive folder must be an absolute path".to_string());
+        }
+        Ok(())
+    }
+
+    fn folder(&self) -> Result<PathBuf, String> {
+        self.folder
+            .clone()
+            .or_else(|| get_app_data_dir().map(|dir| dir.join("archive")))
+            .ok_or_else(|| "Failed to resolve the archive folder".to_string())
+    }
+}
+
+/// A snapshot as listed in the manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedSnapshot {
+    pub kind: SnapshotKind,
+    /// Path relative to the archive folder
+    pub file: String,
+    pub sha256: String,
+    pub bytes: u64,
+    /// Unix seconds the capture was requested at
+    pub captured_at: i64,
+}
+
+/// Payload of the `archive-capture` event
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureRequest {
+    pub request_id: String,
+    pub kind: SnapshotKind,
+}
+
+#[derive(Default)]
+pub struct Archive {
+    /// Capture requests awaiting their file, with the time they were made
+    pending: Mutex<HashMap<String, (SnapshotKind, i64)>>,
+    /// Unix seconds of the last request of each kind
+    last_requested: Mutex<HashMap<SnapshotKind, i64>>,
+    /// Held while writing, so manifest entries chain one after another
+    writing: Mutex<()>,
+}
+
+impl Archive {
+    fn request(&self, app: &AppHandle, kind: SnapshotKind, now: i64) -> Result<String, String> {
+        let request_id = generate_id("capture");
+        {
+            let mut pending = self.pending.lock().unwrap();
+            pending.retain(|_, (_, at)| now - *at < CAPTURE_TIMEOUT);
+            pending.insert(request_id.clone(), (kind, now));
+        }
+        let request = CaptureRequest {
+            request_id: request_id.clone(),
+            kind,
+        };
+        app.emit_to("main", "archive-capture", request)
+            .map_err(|e| format!("Failed to request a snapshot: {}", e))?;
+        Ok(request_id)
+    }
+
+    fn take(&self, request_id: &str, now: i64) -> Option<(SnapshotKind, i64)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(request_id)
+            .filter(|(_, at)| now - *at < CAPTURE_TIMEOUT)
+    }
+}
+
+/// Kinds due now, at most one request per kind however many schedules name it
+fn due(
+    schedules: &[ArchiveSchedule],
+    last: &HashMap<SnapshotKind, i64>,
+    now: i64,
+    in_session: bool,
+) -> Vec<SnapshotKind> {
+    let mut kinds = Vec::new();
+    for schedule in schedules {
+        let elapsed = last.get(&schedule.kind).map_or(true, |at| {
+            now - at >= i64::from(schedule.every_minutes) * 60
+        });
+        if elapsed && (in_session || !schedule.session_only) && !kinds.contains(&schedule.kind) {
+            kinds.push(schedule.kind);
+        }
+    }
+    kinds
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn manifest(folder: &Path) -> AuditLog {
+    AuditLog::load(folder.join(MANIFEST))
+}
+
+/// Write a snapshot with its checksum sidecar and list it in the manifest
+fn store(
+    folder: &Path,
+    kind: SnapshotKind,
+    captured_at: i64,
+    data: &[u8],
+) -> Result<ArchivedSnapshot, String> {
+    let local = DateTime::<Utc>::from_timestamp(captured_at, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local);
+    let month = local.format("%Y-%m").to_string();
+    let name = format!(
+        "{}-{:?}.{}",
+        local.format("%Y%m%d-%H%M%S"),
+        kind,
+        kind.extension()
+    )
+    .to_lowercase();
+    let dir = folder.join(&month);
+    ensure_dir_exists(&dir).map_err(|e| format!("Failed to create archive folder: {}", e))?;
+    let sha256 = sha256_hex(data);
+    std::fs::write(dir.join(&name), data)
+        .and_then(|_| {
+            std::fs::write(
+                dir.join(format!("{}.sha256", name)),
+                format!("{}  {}\n", sha256, name),
+            )
+        })
+        .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+    let snapshot = ArchivedSnapshot {
+        kind,
+        file: format!("{}/{}", month, name),
+        sha256,
+        bytes: data.len() as u64,
+        captured_at,
+    };
+    manifest(folder).record(
+        &crate::audit::current_user(),
+        AuditAction::Create,
+        format!("snapshot:{}", snapshot.file),
+        None,
+        Some(&snapshot),
+    )?;
+    Ok(snapshot)
+}
+
+/// Snapshots in the manifest that are still on disk
+fn archived(folder: &Path) -> Vec<ArchivedSnapshot> {
+    let mut snapshots: Vec<ArchivedSnapshot> = Vec::new();
+    for entry in manifest(folder).trail(Some("snapshot")).entries {
+        match entry.action {
+            AuditAction::Create => {
+                snapshots.extend(entry.after.and_then(|v| serde_json::from_value(v).ok()))
+            }
+            AuditAction::Delete => {
+                snapshots.retain(|s| entry.entity != format!("snapshot:{}", s.file))
+            }
+            _ => {}
+        }
+    }
+    snapshots
+}
+
+/// Delete snapshots older than the retention, returning how many went
+fn expire(folder: &Path, retention_days: u32, now: i64) -> Result<usize, String> {
+    if retention_days == 0 {
+        return Ok(0);
+    }
+    let cutoff = now - i64::from(retention_days) * 86_400;
+    let expired: Vec<ArchivedSnapshot> = archived(folder)
+        .into_iter()
+        .filter(|s| s.captured_at < cutoff)
+        .collect();
+    let log = manifest(folder);
+    for snapshot in &expired {
+        let path = folder.join(&snapshot.file);
+        for file in [
+            path.clone(),
+            PathBuf::from(format!("{}.sha256", path.display())),
+        ] {
+            if let Err(e) = std::fs::remove_file(&file) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(format!("Failed to delete {}: {}", file.display(), e));
+                }
+            }
+        }
+        log.record(
+            "retention",
+            AuditAction::Delete,
+            format!("snapshot:{}", snapshot.file),
+            Some(snapshot),
+            None,
+        )?;
+    }
+    Ok(expired.len())
+}
+
+/// Request the snapshots that are due, for as long as the app runs
+pub fn start_archive_schedule(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SCHEDULE_CHECK).await;
+            let schedules = handle.state::<SettingsStore>().get().archive.schedules;
+            if schedules.is_empty() {
+                continue;
+            }
+            let now = Utc::now().timestamp();
+            let archive = handle.state::<Archive>();
+            let in_session = sessions::in_session(Market::Cn, now)
+                && calendar::is_trading_day(sessions::trading_day(Market::Cn, now));
+            let kinds = due(
+                &schedules,
+                &archive.last_requested.lock().unwrap(),
+                now,
+                in_session,
+            );
+            for kind in kinds {
+                archive.last_requested.lock().unwrap().insert(kind, now);
+                if let Err(e) = archive.request(&handle, kind, now) {
+                    warn!("{}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Ask the main window for a snapshot now, outside the schedule
+#[tauri::command]
+pub fn capture_archive_snapshot(
+    app: AppHandle,
+    archive: State<'_, Archive>,
+    kind: SnapshotKind,
+) -> Result<String, String> {
+    archive.request(&app, kind, Utc::now().timestamp())
+}
+
+/// Archive the file rendered for a capture request, base64-encoded
+#[tauri::command]
+pub fn submit_archive_snapshot(
+    archive: State<'_, Archive>,
+    settings: State<'_, SettingsStore>,
+    request_id: String,
+    data: String,
+) -> Result<ArchivedSnapshot, String> {
+    let now = Utc::now().timestamp();
+    let (kind, requested_at) = archive
+        .take(&request_id, now)
+        .ok_or_else(|| format!("Unknown or lapsed capture request: {}", request_id))?;
+    let data = BASE64
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid snapshot data: {}", e))?;
+    if !kind.matches(&data) {
+        return Err(format!("Snapshot is not a {} file", kind.extension()));
+    }
+    let settings = settings.get().archive;
+    let folder = settings.folder()?;
+    let _writing = archive.writing.lock().unwrap();
+    let snapshot = store(&folder, kind, requested_at, &data)?;
+    info!("Archived snapshot {}", snapshot.file);
+    match expire(&folder, settings.retention_days, now) {
+        Ok(0) => {}
+        Ok(expired) => info!("Deleted {} snapshots past retention", expired),
+        Err(e) => warn!("{}", e),
+    }
+    Ok(snapshot)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveReport {
+    pub snapshots: Vec<ArchivedSnapshot>,
+    /// Snapshots missing from disk or whose contents no longer match
+    pub damaged: Vec<String>,
+    /// Whether the manifest's hash chain is unbroken
+    pub manifest_intact: bool,
+}
+
+/// Archived snapshots, each checked against its recorded checksum
+#[tauri::command]
+pub async fn verify_archive(settings: State<'_, SettingsStore>) -> Result<ArchiveReport, String> {
+    let folder = settings.get().archive.folder()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let AuditTrail { intact, .. } = manifest(&folder).trail(None);
+        let snapshots = archived(&folder);
+        let damaged = snapshots
+            .iter()
+            .filter(|s| {
+                std::fs::read(folder.join(&s.file))
+                    .map(|data| sha256_hex(&data) != s.sha256)
+                    .unwrap_or(true)
+            })
+            .map(|s| s.file.clone())
+            .collect();
+        ArchiveReport {
+            snapshots,
+            damaged,
+            manifest_intact: intact,
+        }
+    })
+    .await
+    .map_err(|e| format!("Failed to verify archive: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_and_retention() {
+        let schedules = [
+            ArchiveSchedule {
+                kind: SnapshotKind::Dashboard,
+                every_minutes: 15,
+                session_only: true,
+            },
+            ArchiveSchedule {
+                kind: SnapshotKind::Portfolio,
+                every_minutes: 60,
+                session_only: false,
+            },
+        ];
+        let last = HashMap::from([(SnapshotKind::Portfolio, 1_000)]);
+        assert_eq!(due(&schedules, &last, 2_000, false), []);
+        assert_eq!(
+            due(&schedules, &last, 4_600, true),
+            [SnapshotKind::Dashboard, SnapshotKind::Portfolio]
+        );
+
+        let folder = std::env::temp_dir().join(format!("ssi-archive-{}", std::process::id()));
+        std::fs::remove_dir_all(&folder).ok();
+        let png = b"\x89PNG\r\n\x1a\nrest";
+        assert!(SnapshotKind::Dashboard.matches(png));
+        assert!(!SnapshotKind::Portfolio.matches(png));
+        let old = store(&folder, SnapshotKind::Dashboard, 1_700_000_000, png).unwrap();
+        let new = store(&folder, SnapshotKind::Dashboard, 1_720_000_000, png).unwrap();
+        let sidecar = std::fs::read_to_string(folder.join(format!("{}.sha256", new.file))).unwrap();
+        assert!(sidecar.starts_with(&new.sha256));
+
+        assert_eq!(expire(&folder, 30, 1_720_000_000).unwrap(), 1);
+        assert!(!folder.join(&old.file).exists());
+        assert_eq!(archived(&folder), [new]);
+        assert!(manifest(&folder).trail(None).intact);
+        std::fs::remove_dir_all(&folder).ok();
+    }
+}
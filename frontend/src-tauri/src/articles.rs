@@ -0,0 +1,347 @@
+//! Full-text news articles and their offline cache.
+//!
+//! Opening a news item fetches the article page once, extracts the readable
+//! body with a readability-style scorer (paragraph text rewarded, link-heavy
+//! and boilerplate blocks penalized) and caches the result on disk, so it can
+//! be read offline and handed to summarization without fetching again.
+
+use std::path::PathBuf;
+
+use log::{info, warn};
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::entity_linking::{self, AliasStore};
+use crate::faults::FaultInjector;
+use crate::instruments::InstrumentMaster;
+use crate::news::NewsStore;
+use crate::politeness::PolicyEngine;
+use crate::utils::{get_timestamp, read_from_file, write_to_file};
+
+/// Paragraphs shorter than this are treated as captions or bylines
+const MIN_PARAGRAPH_CHARS: usize = 10;
+
+const POSITIVE_HINTS: &[&str] = &["article", "content", "body", "text", "post", "main", "txt"];
+const NEGATIVE_HINTS: &[&str] = &[
+    "comment",
+    "footer",
+    "sidebar",
+    "side",
+    "nav",
+    "menu",
+    "ad",
+    "share",
+    "related",
+    "recommend",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Article {
+    pub news_id: String,
+    pub url: String,
+    pub title: String,
+    pub paragraphs: Vec<String>,
+    pub images: Vec<String>,
+    pub fetched_at: String,
+}
+
+impl Article {
+    /// Body text with paragraphs separated by blank lines
+    pub fn text(&self) -> String {
+        self.paragraphs.join("\n\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Readable {
+    pub title: String,
+    pub paragraphs: Vec<String>,
+    pub images: Vec<String>,
+}
+
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).expect("valid selector")
+}
+
+fn normalized_text(element: ElementRef) -> String {
+    element
+        .text()
+        .collect::<Vec<_>>()
+        .join("")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether an element or one of its ancestors is page chrome rather than content
+fn in_boilerplate(element: ElementRef) -> bool {
+    element
+        .ancestors()
+        .chain(std::iter::once(*element))
+        .filter_map(ElementRef::wrap)
+        .any(|e| {
+            matches!(
+                e.value().name(),
+                "script" | "style" | "nav" | "footer" | "aside" | "header" | "form" | "noscript"
+            )
+        })
+}
+
+fn class_weight(element: ElementRef) -> f64 {
+    let value = element.value();
+    let hints = format!(
+        "{} {}",
+        value.attr("class").unwrap_or_default(),
+        value.id().unwrap_or_default()
+    )
+    .to_lowercase();
+    let tokens: Vec<&str> = hints
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let has = |list: &[&str]| tokens.iter().any(|t| list.iter().any(|h| t.contains(h)));
+    let mut weight = 0.0;
+    if has(POSITIVE_HINTS) {
+        weight += 25.0;
+    }
+    if tokens.iter().any(|t| NEGATIVE_HINTS.contains(t)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+fn link_density(element: ElementRef, text_len: usize) -> f64 {
+    if text_len == 0 {
+        return 1.0;
+    }
+    let link_len: usize = element
+        .select(&selector("a"))
+        .map(|a| normalized_text(a).chars().count())
+        .sum();
+    link_len as f64 / text_len as f64
+}
+
+/// Extract the title, body paragraphs and images of an article page
+pub fn extract_readable(html: &str, base_url: &str) -> Readable {
+    let document = Html::parse_document(html);
+
+    let title = document
+        .select(&selector("meta[property='og:title']"))
+        .find_map(|m| m.value().attr("content").map(str::to_string))
+        .or_else(|| document.select(&selector("h1")).next().map(normalized_text))
+        .or_else(|| {
+            document
+                .select(&selector("title"))
+                .next()
+                .map(normalized_text)
+        })
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    // Score each paragraph's parent (and, at half weight, grandparent) by its text
+    let mut scores: Vec<(ElementRef, f64)> = Vec::new();
+    for paragraph in document.select(&selector("p")) {
+        if in_boilerplate(paragraph) {
+            continue;
+        }
+        let text = normalized_text(paragraph);
+        let len = text.chars().count();
+        if len < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let commas = text.matches([',', '，', '。', '；']).count() as f64;
+        let score = 1.0 + commas + (len as f64 / 100.0).min(3.0);
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (depth, ancestor) in ancestors.enumerate() {
+            let share = if depth == 0 { score } else { score / 2.0 };
+            match scores.iter_mut().find(|(e, _)| *e == ancestor) {
+                Some((_, total)) => *total += share,
+                None => scores.push((ancestor, class_weight(ancestor) + share)),
+            }
+        }
+    }
+
+    let best = scores
+        .into_iter()
+        .map(|(element, score)| {
+            let len = normalized_text(element).chars().count();
+            (element, score * (1.0 - link_density(element, len)))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(element, _)| element);
+    let Some(content) = best else {
+        return Readable {
+            title,
+            paragraphs: Vec::new(),
+            images: Vec::new(),
+        };
+    };
+
+    let paragraphs = content
+        .select(&selector("p"))
+        .filter(|p| !in_boilerplate(*p))
+        .map(normalized_text)
+        .filter(|text| text.chars().count() >= MIN_PARAGRAPH_CHARS)
+        .collect();
+    let base = url::Url::parse(base_url).ok();
+    let images = content
+        .select(&selector("img"))
+        .filter_map(|img| {
+            let value = img.value();
+            value.attr("data-src").or_else(|| value.attr("src"))
+        })
+        .filter_map(|src| match &base {
+            Some(base) => base.join(src).ok().map(|u| u.to_string()),
+            None => Some(src.to_string()),
+        })
+        .filter(|src| src.starts_with("http"))
+        .collect();
+    Readable {
+        title,
+        paragraphs,
+        images,
+    }
+}
+
+pub struct ArticleCache {
+    dir: PathBuf,
+}
+
+impl ArticleCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, news_id: &str) -> PathBuf {
+        let safe: String = news_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{}.json", safe))
+    }
+
+    pub fn get(&self, news_id: &str) -> Option<Article> {
+        read_from_file(&self.path(news_id))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    pub fn put(&self, article: &Article) -> Result<(), String> {
+        let content = serde_json::to_string(article)
+            .map_err(|e| format!("Failed to serialize article: {}", e))?;
+        write_to_file(&self.path(&article.news_id), &content)
+            .map_err(|e| format!("Failed to cache article: {}", e))
+    }
+}
+
+/// Readable content of a news item, from the cache or fetched and extracted on first open
+pub async fn article_content(app: &AppHandle, news_id: &str) -> Result<Article, String> {
+    let cache = app.state::<ArticleCache>();
+    if let Some(article) = cache.get(news_id) {
+        return Ok(article);
+    }
+    let item = app
+        .state::<NewsStore>()
+        .get(news_id)
+        .ok_or_else(|| format!("News item not found: {}", news_id))?;
+
+    let policies = app.state::<PolicyEngine>();
+    let html = app
+        .state::<FaultInjector>()
+        .wrap(&item.source, async {
+            policies
+                .get(&item.source, &item.url)
+                .await?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read article: {}", e))
+        })
+        .await?;
+    let readable = extract_readable(&html, &item.url);
+    if readable.paragraphs.is_empty() {
+        return Err(format!("No readable content found at {}", item.url));
+    }
+
+    let article = Article {
+        news_id: item.id,
+        url: item.url,
+        title: if readable.title.is_empty() {
+            item.title
+        } else {
+            readable.title
+        },
+        paragraphs: readable.paragraphs,
+        images: readable.images,
+        fetched_at: get_timestamp(),
+    };
+    let linker =
+        entity_linking::linker(&app.state::<InstrumentMaster>(), &app.state::<AliasStore>());
+    let symbols = linker.link(&format!("{}\n{}", article.title, article.text()));
+    if let Err(e) = app
+        .state::<NewsStore>()
+        .add_symbols(&article.news_id, symbols)
+    {
+        warn!("{}", e);
+    }
+    cache.put(&article)?;
+    info!("Cached article {}", article.news_id);
+    Ok(article)
+}
+
+/// Full readable text and images of a news item, cached for offline reading
+#[tauri::command]
+pub async fn get_article_content(app: AppHandle, news_id: String) -> Result<Article, String> {
+    article_content(&app, &news_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_readable_picks_article_body() {
+        let html = r#"<html><head><title>站点</title>
+            <meta property="og:title" content="央行宣布降准0.5个百分点"></head>
+            <body>
+              <nav><p>首页，行情，资讯，数据中心，个股，基金</p></nav>
+              <div class="sidebar"><p><a href="/a">热门文章，点击查看更多精彩内容</a></p></div>
+              <div class="article-body">
+                <p>中国人民银行决定于近日下调金融机构存款准备金率0.5个百分点，释放长期资金约1万亿元。</p>
+                <img src="/img/chart.png">
+                <p>此次降准是全面降准，旨在保持流动性合理充裕，支持实体经济发展。</p>
+                <p>短</p>
+              </div>
+              <footer><p>版权所有，未经许可不得转载，违者必究。</p></footer>
+            </body></html>"#;
+        let readable = extract_readable(html, "https://finance.eastmoney.com/a/1.html");
+        assert_eq!(readable.title, "央行宣布降准0.5个百分点");
+        assert_eq!(readable.paragraphs.len(), 2);
+        assert!(readable.paragraphs[0].starts_with("中国人民银行"));
+        assert_eq!(
+            readable.images,
+            ["https://finance.eastmoney.com/img/chart.png"]
+        );
+
+        let dir = std::env::temp_dir().join(format!("ssi-articles-{}", std::process::id()));
+        let cache = ArticleCache::new(dir.clone());
+        let article = Article {
+            news_id: "eastmoney-1".to_string(),
+            url: "https://finance.eastmoney.com/a/1.html".to_string(),
+            title: readable.title,
+            paragraphs: readable.paragraphs,
+            images: readable.images,
+            fetched_at: String::new(),
+        };
+        cache.put(&article).unwrap();
+        assert_eq!(cache.get("eastmoney-1"), Some(article));
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,233 @@
+//! Append-only audit trail of portfolio changes.
+//!
+//! Every change to holdings, transactions and broker snapshots is appended
+//! to `portfolio_audit.jsonl` with who made it, when, and the entity before
+//! and after. The file is never rewritten: each entry carries the SHA-256 of
+//! the previous one, so an edited or deleted line breaks the chain and the
+//! trail reports itself as no longer intact. The same log format keeps the
+//! originals of AI output rewritten by the advice guardrail.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::portfolio::PortfolioStore;
+use crate::privacy::Privacy;
+use crate::utils::{get_timestamp, read_from_file};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Import,
+    Sync,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub at: String,
+    /// The OS user, or the integration that made the change
+    pub actor: String,
+    pub action: AuditAction,
+    /// `kind:id`, e.g. `transaction:txn-18c2f1a3b4c5d6e7`
+    pub entity: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn digest(&self) -> String {
+        let unsigned = AuditEntry {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let bytes = serde_json::to_vec(&unsigned).unwrap_or_default();
+        hex::encode(Sha256::digest(bytes))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditTrail {
+    pub entries: Vec<AuditEntry>,
+    /// False if any line of the log was altered, removed or reordered
+    pub intact: bool,
+}
+
+/// The person using the app, as far as the OS can tell
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "local".to_string())
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    /// Sequence number and hash of the last entry
+    head: Mutex<(u64, String)>,
+}
+
+impl AuditLog {
+    pub fn load(path: PathBuf) -> Self {
+        let head = Self::read(&path)
+            .last()
+            .map(|e| (e.seq, e.hash.clone()))
+            .unwrap_or_default();
+        Self {
+            path,
+            head: Mutex::new(head),
+        }
+    }
+
+    fn read(path: &Path) -> Vec<AuditEntry> {
+        read_from_file(path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Append an entry; `before` and `after` are the entity's state around the change
+    pub fn record<T: Serialize>(
+        &self,
+        actor: &str,
+        action: AuditAction,
+        entity: String,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Result<(), String> {
+        let to_value = |value: Option<&T>| {
+            value
+                .map(serde_json::to_value)
+                .transpose()
+                .map_err(|e| format!("Failed to serialize audit entry: {}", e))
+        };
+        let mut head = self.head.lock().unwrap();
+        let mut entry = AuditEntry {
+            seq: head.0 + 1,
+            at: get_timestamp(),
+            actor: actor.to_string(),
+            action,
+            entity,
+            before: to_value(before)?,
+            after: to_value(after)?,
+            prev_hash: head.1.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.digest();
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|e| format!("Failed to write audit trail: {}", e))?;
+        *head = (entry.seq, entry.hash);
+        Ok(())
+    }
+
+    /// Entries for an entity (`transaction:txn-…`) or a whole kind (`transaction`)
+    pub fn trail(&self, entity: Option<&str>) -> AuditTrail {
+        let _head = self.head.lock().unwrap();
+        let entries = Self::read(&self.path);
+        let mut prev = (0, String::new());
+        let mut intact = true;
+        for entry in &entries {
+            intact &= entry.seq == prev.0 + 1
+                && entry.prev_hash == prev.1
+                && entry.hash == entry.digest();
+            prev = (entry.seq, entry.hash.clone());
+        }
+        let entries = entries
+            .into_iter()
+            .filter(|e| {
+                entity.map_or(true, |entity| {
+                    e.entity == entity || e.entity.starts_with(&format!("{}:", entity))
+                })
+            })
+            .collect();
+        AuditTrail { entries, intact }
+    }
+}
+
+/// Change history of a portfolio entity, or of all entities when none is given
+#[tauri::command]
+pub fn get_audit_trail(
+    store: State<'_, PortfolioStore>,
+    privacy: State<'_, Privacy>,
+    entity: Option<String>,
+) -> Result<AuditTrail, String> {
+    privacy.apply(store.audit().trail(entity.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_chain_detects_tampering() {
+        let path = std::env::temp_dir().join(format!("ssi-audit-{}.jsonl", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let log = AuditLog::load(path.clone());
+        log.record(
+            "alice",
+            AuditAction::Create,
+            "transaction:a".to_string(),
+            None,
+            Some(&1),
+        )
+        .unwrap();
+        log.record(
+            "alice",
+            AuditAction::Update,
+            "transaction:a".to_string(),
+            Some(&1),
+            Some(&2),
+        )
+        .unwrap();
+        log.record::<i32>(
+            "sync:ibkr",
+            AuditAction::Sync,
+            "broker_account:ibkr/U1".to_string(),
+            None,
+            None,
+        )
+        .unwrap();
+
+        // A reopened log continues the chain
+        let log = AuditLog::load(path.clone());
+        log.record(
+            "alice",
+            AuditAction::Delete,
+            "transaction:a".to_string(),
+            Some(&2),
+            None,
+        )
+        .unwrap();
+        let trail = log.trail(Some("transaction"));
+        assert!(trail.intact);
+        assert_eq!(trail.entries.len(), 3);
+        assert_eq!(trail.entries[1].before, Some(Value::from(1)));
+        assert_eq!(log.trail(None).entries.len(), 4);
+
+        let content = read_from_file(&path).unwrap();
+        std::fs::write(&path, content.replace("\"after\":2", "\"after\":3")).unwrap();
+        assert!(!log.trail(None).intact);
+        std::fs::remove_file(path).ok();
+    }
+}
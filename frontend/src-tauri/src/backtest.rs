@@ -0,0 +1,892 @@
+//! Bar-by-bar backtest engine.
+//!
+//! Strategies decide on the close of a bar and their orders fill at the open
+//! of the next tradable bar, priced through the active [`CostModel`]. On
+//! intraday intervals only bars inside exchange sessions are simulated, so a
+//! signal on the last bar before the lunch break fills when the afternoon
+//! session opens. China A-share positions follow T+1: shares bought on a
+//! trading day cannot be sold until the next one, and a blocked exit is
+//! retried at the next opportunity.
+//!
+//! Portfolio runs apply one strategy to a basket of symbols sharing a single
+//! cash balance. Each symbol's signal is scaled by a per-position weight cap
+//! (scaled down further when the caps would exceed full investment), and
+//! single-symbol runs are a portfolio of one. The basket may instead be an
+//! index with recorded constituents: every symbol that was a member during
+//! the run is simulated, but only held while a member, so a constituent is
+//! sold on the day it leaves the index and not bought before it joins.
+
+use chrono::NaiveDate;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::backtest_runs::RunStore;
+use crate::columnar::{ColumnarStore, MappedBars};
+use crate::costs::{CostModel, Side};
+use crate::executor::{Priority, ResourceClass};
+use crate::models::{Bar, Market};
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::strategy::StrategySpec;
+use crate::tasks::{spawn_task_with, CancellationToken, TaskContext, TaskHandle};
+use crate::universe::{Membership, UniverseStore};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestConfig {
+    pub symbol: String,
+    /// Bar interval, e.g. `1d` or `1m`
+    pub interval: String,
+    pub market: Market,
+    /// Unix seconds, inclusive
+    pub start: Option<i64>,
+    /// Unix seconds, exclusive
+    pub end: Option<i64>,
+    pub initial_cash: f64,
+    pub strategy: StrategySpec,
+    /// Overrides the cost model from settings, or the market's preset
+    #[serde(default)]
+    pub cost_model: Option<CostModel>,
+    /// Saved strategy the run is recorded under
+    #[serde(default)]
+    pub strategy_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Rebalance {
+    /// Trade a symbol only when its signal changes
+    #[default]
+    OnSignal,
+    /// Also restore every position to its target weight every `every_bars` bars
+    Periodic { every_bars: usize },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioConfig {
+    /// Left empty when `index` is set
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    /// Index whose point-in-time members are traded instead of `symbols`
+    #[serde(default)]
+    pub index: Option<String>,
+    pub interval: String,
+    pub market: Market,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub initial_cash: f64,
+    /// Applied to every symbol independently
+    pub strategy: StrategySpec,
+    /// Largest fraction of equity one symbol may hold; defaults to an equal split
+    #[serde(default)]
+    pub max_position_weight: Option<f64>,
+    #[serde(default)]
+    pub rebalance: Rebalance,
+    #[serde(default)]
+    pub cost_model: Option<CostModel>,
+    #[serde(default)]
+    pub strategy_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trade {
+    pub symbol: String,
+    pub timestamp: i64,
+    pub side: Side,
+    pub quantity: f64,
+    pub price: f64,
+    pub costs: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EquityPoint {
+    pub timestamp: i64,
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BacktestMetrics {
+    pub total_return: f64,
+    pub max_drawdown: f64,
+    pub trades: usize,
+    pub total_costs: f64,
+    /// Sell orders cut short because the shares were bought the same trading day
+    pub settlement_blocked: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BacktestResult {
+    pub symbol: String,
+    pub equity_curve: Vec<EquityPoint>,
+    pub trades: Vec<Trade>,
+    pub metrics: BacktestMetrics,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SymbolContribution {
+    pub symbol: String,
+    /// Realized plus unrealized profit net of costs
+    pub pnl: f64,
+    /// Share of the portfolio return, as a fraction of initial cash
+    pub contribution: f64,
+    pub trades: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioResult {
+    pub equity_curve: Vec<EquityPoint>,
+    pub trades: Vec<Trade>,
+    pub metrics: BacktestMetrics,
+    pub contributions: Vec<SymbolContribution>,
+}
+
+/// Intervals measured in minutes (`1m`, `5m`, `60m`) are simulated per session
+pub fn is_intraday(interval: &str) -> bool {
+    interval
+        .strip_suffix('m')
+        .is_some_and(|n| n.parse::<u32>().is_ok())
+}
+
+/// Smallest tradable quantity
+pub fn board_lot(market: Market) -> f64 {
+    match market {
+        Market::Cn => 100.0,
+        Market::Hk | Market::Us => 1.0,
+    }
+}
+
+/// Whether shares bought today can only be sold on the next trading day
+pub fn is_t_plus_one(market: Market) -> bool {
+    market == Market::Cn
+}
+
+#[derive(Debug, Default)]
+struct Position {
+    quantity: f64,
+    /// Shares settled and allowed to be sold today
+    sellable: f64,
+    last_price: f64,
+    /// Net cash paid out (negative) or received for this symbol
+    cash_flow: f64,
+    trades: usize,
+}
+
+struct Portfolio<'a> {
+    market: Market,
+    costs: &'a CostModel,
+    cash: f64,
+    positions: Vec<Position>,
+    trades: Vec<Trade>,
+    settlement_blocked: usize,
+}
+
+impl Portfolio<'_> {
+    fn equity(&self) -> f64 {
+        self.cash
+            + self
+                .positions
+                .iter()
+                .map(|p| p.quantity * p.last_price)
+                .sum::<f64>()
+    }
+
+    fn execute(&mut self, k: usize, symbol: &str, timestamp: i64, side: Side, quantity: f64) {
+        let position = &mut self.positions[k];
+        let fill = self.costs.fill(side, position.last_price, quantity);
+        self.cash += fill.cash_flow;
+        position.cash_flow += fill.cash_flow;
+        position.trades += 1;
+        match side {
+            Side::Buy => {
+                position.quantity += quantity;
+                if !is_t_plus_one(self.market) {
+                    position.sellable += quantity;
+                }
+            }
+            Side::Sell => {
+                position.quantity -= quantity;
+                position.sellable -= quantity;
+            }
+        }
+        self.trades.push(Trade {
+            symbol: symbol.to_string(),
+            timestamp,
+            side,
+            quantity,
+            price: fill.price,
+            costs: fill.total_costs(),
+        });
+    }
+
+    /// Trade the given symbols toward their target weights at their current
+    /// prices, sells first so they fund the buys. Returns the symbols whose
+    /// exits were held back by settlement rules.
+    fn rebalance(
+        &mut self,
+        symbols: &[String],
+        timestamp: i64,
+        orders: &[(usize, f64)],
+    ) -> Vec<usize> {
+        let lot = board_lot(self.market);
+        let equity = self.equity();
+        let desired: Vec<(usize, f64)> = orders
+            .iter()
+            .map(|&(k, weight)| {
+                let price = self.positions[k].last_price;
+                (k, (weight * equity / price / lot).floor() * lot)
+            })
+            .collect();
+
+        let mut blocked = Vec::new();
+        for &(k, target) in &desired {
+            let position = &self.positions[k];
+            if target >= position.quantity {
+                continue;
+            }
+            let wanted = position.quantity - target;
+            let sell = wanted.min(position.sellable);
+            if sell > 0.0 {
+                self.execute(k, &symbols[k], timestamp, Side::Sell, sell);
+            }
+            if sell < wanted {
+                self.settlement_blocked += 1;
+                blocked.push(k);
+            }
+        }
+        for &(k, target) in &desired {
+            let position = &self.positions[k];
+            if target <= position.quantity {
+                continue;
+            }
+            let price = position.last_price;
+            let mut buy = target - position.quantity;
+            let wanted = self.costs.fill(Side::Buy, price, buy);
+            if self.cash + wanted.cash_flow < 0.0 {
+                // Fees shrink with the order, so reserving the full order's
+                // leaves enough for all but minimum-commission rounding
+                let lots = ((self.cash - wanted.total_costs()) / (wanted.price * lot)).floor();
+                buy = buy.min(lots.max(0.0) * lot);
+                if buy > 0.0 && self.cash + self.costs.fill(Side::Buy, price, buy).cash_flow < 0.0 {
+                    buy -= lot;
+                }
+            }
+            if buy > 0.0 {
+                self.execute(k, &symbols[k], timestamp, Side::Buy, buy);
+            }
+        }
+        blocked
+    }
+}
+
+/// Weight each symbol may hold given every symbol's current signal
+fn target_weights(signals: &[f64], max_weight: f64) -> Vec<f64> {
+    let weights: Vec<f64> = signals
+        .iter()
+        .map(|s| s.clamp(0.0, 1.0) * max_weight)
+        .collect();
+    let total: f64 = weights.iter().sum();
+    if total > 1.0 {
+        weights.iter().map(|w| w / total).collect()
+    } else {
+        weights
+    }
+}
+
+fn session_bars(config: &PortfolioConfig, bars: &[Bar]) -> Vec<Bar> {
+    if is_intraday(&config.interval) {
+        bars.iter()
+            .filter(|b| sessions::in_session(config.market, b.timestamp))
+            .copied()
+            .collect()
+    } else {
+        bars.to_vec()
+    }
+}
+
+/// Run a backtest over `bars`, which must be sorted by timestamp
+pub fn simulate(
+    config: &BacktestConfig,
+    bars: &[Bar],
+    costs: &CostModel,
+    token: &CancellationToken,
+) -> Result<BacktestResult, String> {
+    let portfolio = PortfolioConfig {
+        symbols: vec![config.symbol.clone()],
+        index: None,
+        interval: config.interval.clone(),
+        market: config.market,
+        start: config.start,
+        end: config.end,
+        initial_cash: config.initial_cash,
+        strategy: config.strategy.clone(),
+        max_position_weight: Some(1.0),
+        rebalance: Rebalance::OnSignal,
+        cost_model: None,
+        strategy_id: None,
+    };
+    let result = simulate_portfolio(&portfolio, &[bars.to_vec()], None, costs, token)?;
+    Ok(BacktestResult {
+        symbol: config.symbol.clone(),
+        equity_curve: result.equity_curve,
+        trades: result.trades,
+        metrics: result.metrics,
+    })
+}
+
+/// Run a shared-capital backtest; `series[k]` holds the sorted bars of
+/// `config.symbols[k]`, and `membership[k]`, for index runs, when it was a
+/// member
+pub fn simulate_portfolio(
+    config: &PortfolioConfig,
+    series: &[Vec<Bar>],
+    membership: Option<&[Vec<Membership>]>,
+    costs: &CostModel,
+    token: &CancellationToken,
+) -> Result<PortfolioResult, String> {
+    if config.initial_cash <= 0.0 {
+        return Err("Initial cash must be positive".to_string());
+    }
+    if config.symbols.is_empty() || config.symbols.len() != series.len() {
+        return Err("Portfolio backtest needs bars for every symbol".to_string());
+    }
+    if membership.is_some_and(|m| m.len() != series.len()) {
+        return Err("Index backtest needs the membership of every symbol".to_string());
+    }
+    let is_member = |k: usize, day: NaiveDate| {
+        membership.map_or(true, |m| m[k].iter().any(|interval| interval.contains(day)))
+    };
+
+    let series: Vec<Vec<Bar>> = series.iter().map(|b| session_bars(config, b)).collect();
+    let mut timeline: Vec<i64> = series.iter().flatten().map(|b| b.timestamp).collect();
+    timeline.sort_unstable();
+    timeline.dedup();
+
+    // An equal split among the most symbols held at once
+    let slots = match membership {
+        None => config.symbols.len(),
+        Some(_) => {
+            let mut days: Vec<NaiveDate> = timeline
+                .iter()
+                .map(|&ts| sessions::trading_day(config.market, ts))
+                .collect();
+            days.dedup();
+            days.iter()
+                .map(|&day| (0..series.len()).filter(|&k| is_member(k, day)).count())
+                .max()
+                .unwrap_or(0)
+                .max(1)
+        }
+    };
+    let max_weight = config.max_position_weight.unwrap_or(1.0 / slots as f64);
+    if !(max_weight > 0.0 && max_weight <= 1.0) {
+        return Err(format!("Invalid maximum position weight: {}", max_weight));
+    }
+
+    let mut strategies = Vec::with_capacity(series.len());
+    for bars in &series {
+        let mut strategy = config.strategy.build()?;
+        strategy.prepare(bars);
+        strategies.push(strategy);
+    }
+
+    let n = series.len();
+    let mut portfolio = Portfolio {
+        market: config.market,
+        costs,
+        cash: config.initial_cash,
+        positions: (0..n).map(|_| Position::default()).collect(),
+        trades: Vec::new(),
+        settlement_blocked: 0,
+    };
+    let mut cursors = vec![0usize; n];
+    let mut signals = vec![0.0; n];
+    let mut pending = vec![false; n];
+    let mut equity_curve = Vec::with_capacity(timeline.len());
+    let mut current_day = None;
+
+    for (step, &timestamp) in timeline.iter().enumerate() {
+        if step % 4096 == 0 {
+            token.checkpoint()?;
+        }
+        let day = sessions::trading_day(config.market, timestamp);
+        if current_day != Some(day) {
+            current_day = Some(day);
+            for position in &mut portfolio.positions {
+                position.sellable = position.quantity;
+            }
+        }
+        if let Rebalance::Periodic { every_bars } = config.rebalance {
+            if step > 0 && every_bars > 0 && step % every_bars == 0 {
+                pending.iter_mut().for_each(|p| *p = true);
+            }
+        }
+
+        // Symbols with a bar at this timestamp, and its index in their series
+        let active: Vec<(usize, usize)> = (0..n)
+            .filter(|&k| series[k].get(cursors[k]).map(|b| b.timestamp) == Some(timestamp))
+            .map(|k| (k, cursors[k]))
+            .collect();
+
+        // A symbol that has left the index is sold at the open
+        for &(k, _) in &active {
+            if signals[k] != 0.0 && !is_member(k, day) {
+                signals[k] = 0.0;
+                pending[k] = true;
+            }
+        }
+
+        let weights = target_weights(&signals, max_weight);
+        let mut orders = Vec::new();
+        for &(k, i) in &active {
+            portfolio.positions[k].last_price = series[k][i].open;
+            if pending[k] {
+                pending[k] = false;
+                orders.push((k, weights[k]));
+            }
+        }
+        for k in portfolio.rebalance(&config.symbols, timestamp, &orders) {
+            pending[k] = true;
+        }
+
+        for &(k, i) in &active {
+            portfolio.positions[k].last_price = series[k][i].close;
+            if let Some(target) = strategies[k].target(i) {
+                let target = if is_member(k, day) { target } else { 0.0 };
+                if target != signals[k] {
+                    signals[k] = target;
+                    pending[k] = true;
+                }
+            }
+            cursors[k] += 1;
+        }
+        equity_curve.push(EquityPoint {
+            timestamp,
+            equity: portfolio.equity(),
+        });
+    }
+
+    let contributions = config
+        .symbols
+        .iter()
+        .zip(&portfolio.positions)
+        .map(|(symbol, position)| {
+            let pnl = position.cash_flow + position.quantity * position.last_price;
+            SymbolContribution {
+                symbol: symbol.clone(),
+                pnl,
+                contribution: pnl / config.initial_cash,
+                trades: position.trades,
+            }
+        })
+        .collect();
+    let metrics = compute_metrics(config.initial_cash, &equity_curve, &portfolio);
+    Ok(PortfolioResult {
+        equity_curve,
+        trades: portfolio.trades,
+        metrics,
+        contributions,
+    })
+}
+
+fn compute_metrics(
+    initial_cash: f64,
+    curve: &[EquityPoint],
+    portfolio: &Portfolio,
+) -> BacktestMetrics {
+    let final_equity = curve.last().map_or(initial_cash, |p| p.equity);
+    let mut peak = initial_cash;
+    let mut max_drawdown: f64 = 0.0;
+    for point in curve {
+        peak = peak.max(point.equity);
+        max_drawdown = max_drawdown.max((peak - point.equity) / peak);
+    }
+    BacktestMetrics {
+        total_return: final_equity / initial_cash - 1.0,
+        max_drawdown,
+        trades: portfolio.trades.len(),
+        total_costs: portfolio.trades.iter().map(|t| t.costs).sum(),
+        settlement_blocked: portfolio.settlement_blocked,
+    }
+}
+
+/// Load the bars of a symbol within `[start, end)`, honoring snapshot mode
+fn load_bars(
+    mapped: &MappedBars,
+    until: Option<i64>,
+    start: Option<i64>,
+    end: Option<i64>,
+) -> Vec<Bar> {
+    let rows = mapped.rows_until(until);
+    let range = mapped.range(start.unwrap_or(i64::MIN), end.unwrap_or(i64::MAX));
+    mapped.to_bars(range.start.min(rows)..range.end.min(rows))
+}
+
+/// Backtest a strategy over stored bars
+#[tauri::command]
+pub fn run_backtest(
+    app: AppHandle,
+    store: State<'_, ColumnarStore>,
+    settings: State<'_, SettingsStore>,
+    config: BacktestConfig,
+) -> Result<TaskHandle, String> {
+    config.strategy.build()?;
+    let mapped = store.open(&config.symbol, &config.interval)?;
+    let costs = config
+        .cost_model
+        .clone()
+        .unwrap_or_else(|| CostModel::for_market(settings.get().cost_model, config.market));
+
+    let handle = app.clone();
+    let task_id = spawn_task_with(
+        &app,
+        "backtest",
+        ResourceClass::Cpu,
+        Priority::Normal,
+        move |ctx: TaskContext| {
+            let until = handle.state::<SnapshotClock>().frozen_at();
+            let bars = load_bars(&mapped, until, config.start, config.end);
+            let result = simulate(&config, &bars, &costs, &ctx.token)?;
+            let recorded = handle.state::<RunStore>().record(
+                config.strategy_id.clone(),
+                &config.strategy,
+                vec![config.symbol.clone()],
+                &config.interval,
+                (config.start, config.end),
+                &result.metrics,
+            );
+            if let Err(e) = recorded {
+                warn!("Failed to record backtest run: {}", e);
+            }
+            Ok(result)
+        },
+    );
+    Ok(TaskHandle { task_id })
+}
+
+/// Backtest a strategy over a basket of symbols sharing one account
+#[tauri::command]
+pub fn run_portfolio_backtest(
+    app: AppHandle,
+    store: State<'_, ColumnarStore>,
+    settings: State<'_, SettingsStore>,
+    universe: State<'_, UniverseStore>,
+    mut config: PortfolioConfig,
+) -> Result<TaskHandle, String> {
+    config.strategy.build()?;
+    let membership = match &config.index {
+        Some(_) if !config.symbols.is_empty() => {
+            return Err("Give either symbols or an index, not both".to_string());
+        }
+        Some(index) => {
+            let day = |ts: i64| sessions::trading_day(config.market, ts);
+            let memberships =
+                universe.memberships(index, config.start.map(day), config.end.map(day))?;
+            if memberships.is_empty() {
+                return Err(format!("{} had no members during the backtest", index));
+            }
+            let (symbols, membership) = memberships.into_iter().unzip();
+            config.symbols = symbols;
+            Some(membership)
+        }
+        None => None,
+    };
+    let mapped = config
+        .symbols
+        .iter()
+        .map(|symbol| store.open(symbol, &config.interval))
+        .collect::<Result<Vec<_>, _>>()?;
+    let costs = config
+        .cost_model
+        .clone()
+        .unwrap_or_else(|| CostModel::for_market(settings.get().cost_model, config.market));
+
+    let handle = app.clone();
+    let task_id = spawn_task_with(
+        &app,
+        "portfolio_backtest",
+        ResourceClass::Cpu,
+        Priority::Normal,
+        move |ctx: TaskContext| {
+            let until = handle.state::<SnapshotClock>().frozen_at();
+            let series: Vec<Vec<Bar>> = mapped
+                .iter()
+                .map(|bars| load_bars(bars, until, config.start, config.end))
+                .collect();
+            let result =
+                simulate_portfolio(&config, &series, membership.as_deref(), &costs, &ctx.token)?;
+            let recorded = handle.state::<RunStore>().record(
+                config.strategy_id.clone(),
+                &config.strategy,
+                config.symbols.clone(),
+                &config.interval,
+                (config.start, config.end),
+                &result.metrics,
+            );
+            if let Err(e) = recorded {
+                warn!("Failed to record backtest run: {}", e);
+            }
+            Ok(result)
+        },
+    );
+    Ok(TaskHandle { task_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sessions::timezone;
+    use crate::testing::{assert_golden, round, sample_bars};
+    use chrono::TimeZone;
+
+    fn minute_bar(day: u32, h: u32, m: u32, price: f64) -> Bar {
+        let timestamp = timezone(Market::Cn)
+            .with_ymd_and_hms(2024, 7, day, h, m, 0)
+            .unwrap()
+            .timestamp();
+        Bar {
+            timestamp,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 1000.0,
+        }
+    }
+
+    fn config(market: Market) -> BacktestConfig {
+        BacktestConfig {
+            symbol: "SH600000".to_string(),
+            interval: "1m".to_string(),
+            market,
+            start: None,
+            end: None,
+            initial_cash: 100_000.0,
+            strategy: StrategySpec::MaCross { fast: 1, slow: 2 },
+            cost_model: None,
+            strategy_id: None,
+        }
+    }
+
+    #[test]
+    fn test_load_bars_excludes_end() {
+        let path = std::env::temp_dir().join(format!("ssi-backtest-{}.col", std::process::id()));
+        let bars = sample_bars();
+        crate::columnar::write_columnar(&path, &bars).unwrap();
+        let mapped = MappedBars::open(&path).unwrap();
+        let (start, end) = (bars[2].timestamp, bars[5].timestamp);
+
+        let loaded = load_bars(&mapped, None, Some(start), Some(end));
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded[0].timestamp, start);
+        assert!(loaded.iter().all(|bar| bar.timestamp < end));
+        // The snapshot cut-off still applies inside the window
+        let frozen = load_bars(&mapped, Some(start), Some(start), Some(end));
+        assert_eq!(frozen.len(), 1);
+        assert!(load_bars(&mapped, None, Some(end), Some(start)).is_empty());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_interval_classification() {
+        assert!(is_intraday("1m"));
+        assert!(is_intraday("15m"));
+        assert!(!is_intraday("1d"));
+        assert!(!is_intraday("m"));
+    }
+
+    #[test]
+    fn test_ma_cross_golden() {
+        let config = BacktestConfig {
+            interval: "1d".to_string(),
+            strategy: StrategySpec::MaCross { fast: 5, slow: 20 },
+            ..config(Market::Cn)
+        };
+        let token = CancellationToken::default();
+        let mut result = simulate(
+            &config,
+            &sample_bars(),
+            &CostModel::preset(Market::Cn),
+            &token,
+        )
+        .unwrap();
+
+        for point in &mut result.equity_curve {
+            point.equity = round(point.equity);
+        }
+        for trade in &mut result.trades {
+            trade.price = round(trade.price);
+            trade.costs = round(trade.costs);
+        }
+        let metrics = &mut result.metrics;
+        metrics.total_return = round(metrics.total_return);
+        metrics.max_drawdown = round(metrics.max_drawdown);
+        metrics.total_costs = round(metrics.total_costs);
+        assert_golden("backtest_ma_cross", &result);
+    }
+
+    #[test]
+    fn test_lunch_break_fill_and_t_plus_one() {
+        // Price rises into the lunch break, so the cross fires on the 11:29 bar
+        // and the buy fills on the 13:00 open; bars during the break are skipped.
+        // The drop at 13:02 triggers an exit that T+1 blocks until the next day.
+        let bars = vec![
+            minute_bar(1, 11, 28, 10.0),
+            minute_bar(1, 11, 29, 11.0),
+            minute_bar(1, 12, 0, 50.0),
+            minute_bar(1, 13, 0, 12.0),
+            minute_bar(1, 13, 1, 11.0),
+            minute_bar(1, 13, 2, 10.0),
+            minute_bar(2, 9, 30, 9.0),
+            minute_bar(2, 9, 31, 8.0),
+        ];
+        let token = CancellationToken::default();
+        let result = simulate(&config(Market::Cn), &bars, &CostModel::zero(), &token).unwrap();
+
+        assert_eq!(result.equity_curve.len(), 7);
+        assert_eq!(result.trades.len(), 2);
+        let (buy, sell) = (&result.trades[0], &result.trades[1]);
+        assert_eq!(buy.side, Side::Buy);
+        assert_eq!(buy.price, 12.0);
+        assert_eq!(buy.quantity % 100.0, 0.0);
+        assert_eq!(sell.side, Side::Sell);
+        assert_eq!(sell.quantity, buy.quantity);
+        assert_eq!(sell.timestamp, bars[6].timestamp);
+        assert_eq!(result.metrics.settlement_blocked, 1);
+
+        // The same signals settle immediately in Hong Kong
+        let result = simulate(&config(Market::Hk), &bars, &CostModel::zero(), &token).unwrap();
+        assert_eq!(result.metrics.settlement_blocked, 0);
+    }
+
+    #[test]
+    fn test_portfolio_shares_capital() {
+        let rising: Vec<Bar> = (0..6)
+            .map(|i| minute_bar(1, 10, i, 10.0 + i as f64))
+            .collect();
+        let falling: Vec<Bar> = (0..6)
+            .map(|i| minute_bar(1, 10, i, 20.0 - i as f64))
+            .collect();
+        let mut rising_late = rising.clone();
+        rising_late.iter_mut().for_each(|b| b.open *= 2.0);
+
+        let config = PortfolioConfig {
+            symbols: vec!["SH600000".to_string(), "SZ000001".to_string()],
+            index: None,
+            interval: "1m".to_string(),
+            market: Market::Hk,
+            start: None,
+            end: None,
+            initial_cash: 100_000.0,
+            strategy: StrategySpec::MaCross { fast: 1, slow: 2 },
+            max_position_weight: None,
+            rebalance: Rebalance::OnSignal,
+            cost_model: None,
+            strategy_id: None,
+        };
+        let token = CancellationToken::default();
+        let result = simulate_portfolio(
+            &config,
+            &[rising, falling],
+            None,
+            &CostModel::zero(),
+            &token,
+        )
+        .unwrap();
+
+        // Only the rising symbol is bought, capped at half the equity
+        assert!(result.trades.iter().all(|t| t.symbol == "SH600000"));
+        let bought = &result.trades[0];
+        assert!(bought.quantity * bought.price <= 50_000.0);
+        assert!(bought.quantity * bought.price > 49_000.0);
+
+        let contributed: f64 = result.contributions.iter().map(|c| c.contribution).sum();
+        assert!((contributed - result.metrics.total_return).abs() < 1e-9);
+        assert_eq!(result.contributions[1].pnl, 0.0);
+
+        assert!(
+            simulate_portfolio(&config, &[rising_late], None, &CostModel::zero(), &token).is_err()
+        );
+    }
+
+    #[test]
+    fn test_index_members_drop_out() {
+        let day = |d: u32| NaiveDate::from_ymd_opt(2024, 7, d).unwrap();
+        let daily = |d: u32, price: f64| Bar {
+            timestamp: timezone(Market::Hk)
+                .with_ymd_and_hms(2024, 7, d, 16, 0, 0)
+                .unwrap()
+                .timestamp(),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 1000.0,
+        };
+        // Both rise all along; SZ000001 joins on the 3rd, SH600000 leaves on the 5th
+        let rising: Vec<Bar> = (1..=8).map(|d| daily(d, 10.0 + d as f64)).collect();
+        let membership = vec![
+            vec![Membership {
+                symbol: "SH600000".to_string(),
+                added: day(1),
+                removed: Some(day(5)),
+            }],
+            vec![Membership {
+                symbol: "SZ000001".to_string(),
+                added: day(3),
+                removed: None,
+            }],
+        ];
+        let config = PortfolioConfig {
+            symbols: vec!["SH600000".to_string(), "SZ000001".to_string()],
+            index: Some("000300".to_string()),
+            interval: "1d".to_string(),
+            market: Market::Hk,
+            start: None,
+            end: None,
+            initial_cash: 100_000.0,
+            strategy: StrategySpec::MaCross { fast: 1, slow: 2 },
+            max_position_weight: None,
+            rebalance: Rebalance::OnSignal,
+            cost_model: None,
+            strategy_id: None,
+        };
+        let token = CancellationToken::default();
+        let series = [rising.clone(), rising.clone()];
+        let result = simulate_portfolio(
+            &config,
+            &series,
+            Some(&membership),
+            &CostModel::zero(),
+            &token,
+        )
+        .unwrap();
+
+        let trades = |symbol: &str| -> Vec<(Side, i64)> {
+            result
+                .trades
+                .iter()
+                .filter(|t| t.symbol == symbol)
+                .map(|t| (t.side, t.timestamp))
+                .collect()
+        };
+        // Bought on the 3rd open after the cross, sold at the open of the 5th
+        assert_eq!(
+            trades("SH600000"),
+            [
+                (Side::Buy, rising[2].timestamp),
+                (Side::Sell, rising[4].timestamp)
+            ]
+        );
+        // Its signal was up from the 2nd too, but it's only bought once a member
+        assert_eq!(trades("SZ000001"), [(Side::Buy, rising[3].timestamp)]);
+        assert!(simulate_portfolio(
+            &config,
+            &series,
+            Some(&membership[..1]),
+            &CostModel::zero(),
+            &token
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! History of completed backtest runs.
+//!
+//! Every finished backtest is recorded with its strategy spec, a stable hash
+//! of that spec, the data window and its metrics, so a strategy's results can
+//! be tracked and compared as its parameters are tweaked.
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::backtest::BacktestMetrics;
+use crate::fields::select;
+use crate::strategy::StrategySpec;
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BacktestRun {
+    pub id: String,
+    pub strategy_id: Option<String>,
+    pub spec_hash: String,
+    pub spec: StrategySpec,
+    pub symbols: Vec<String>,
+    pub interval: String,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub metrics: BacktestMetrics,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricDeltas {
+    total_return: f64,
+    max_drawdown: f64,
+    total_costs: f64,
+    trades: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunComparison {
+    runs: Vec<BacktestRun>,
+    /// Spec parameters whose values differ between the runs
+    changed_parameters: Vec<String>,
+    /// Metric changes of each run relative to the first
+    deltas: Vec<MetricDeltas>,
+}
+
+/// Short, stable hash identifying a strategy spec
+pub fn spec_hash(spec: &StrategySpec) -> String {
+    let bytes = serde_json::to_vec(spec).unwrap_or_default();
+    hex::encode(&Sha256::digest(bytes)[..8])
+}
+
+pub struct RunStore {
+    path: PathBuf,
+    runs: RwLock<Vec<BacktestRun>>,
+}
+
+impl RunStore {
+    pub fn load(path: PathBuf) -> Self {
+        let runs = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            runs: RwLock::new(runs),
+        }
+    }
+
+    pub fn record(
+        &self,
+        strategy_id: Option<String>,
+        spec: &StrategySpec,
+        symbols: Vec<String>,
+        interval: &str,
+        window: (Option<i64>, Option<i64>),
+        metrics: &BacktestMetrics,
+    ) -> Result<BacktestRun, String> {
+        let run = BacktestRun {
+            id: generate_id("run"),
+            strategy_id,
+            spec_hash: spec_hash(spec),
+            spec: spec.clone(),
+            symbols,
+            interval: interval.to_string(),
+            start: window.0,
+            end: window.1,
+            metrics: metrics.clone(),
+            created_at: get_timestamp(),
+        };
+        let mut runs = self.runs.write().unwrap();
+        runs.push(run.clone());
+        let content = serde_json::to_string(&*runs)
+            .map_err(|e| format!("Failed to serialize backtest runs: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save backtest runs: {}", e))?;
+        Ok(run)
+    }
+
+    /// Runs of one strategy, oldest first; `None` lists every run
+    pub fn list(&self, strategy_id: Option<&str>) -> Vec<BacktestRun> {
+        self.runs
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|r| strategy_id.map_or(true, |id| r.strategy_id.as_deref() == Some(id)))
+            .cloned()
+            .collect()
+    }
+
+    pub fn compare(&self, ids: &[String]) -> Result<RunComparison, String> {
+        let runs = {
+            let all = self.runs.read().unwrap();
+            ids.iter()
+                .map(|id| {
+                    all.iter()
+                        .find(|r| &r.id == id)
+                        .cloned()
+                        .ok_or_else(|| format!("Backtest run not found: {}", id))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let Some(base) = runs.first() else {
+            return Err("No runs to compare".to_string());
+        };
+
+        let deltas = runs
+            .iter()
+            .map(|r| MetricDeltas {
+                total_return: r.metrics.total_return - base.metrics.total_return,
+                max_drawdown: r.metrics.max_drawdown - base.metrics.max_drawdown,
+                total_costs: r.metrics.total_costs - base.metrics.total_costs,
+                trades: r.metrics.trades as i64 - base.metrics.trades as i64,
+            })
+            .collect();
+        Ok(RunComparison {
+            changed_parameters: changed_parameters(&runs),
+            deltas,
+            runs,
+        })
+    }
+}
+
+fn changed_parameters(runs: &[BacktestRun]) -> Vec<String> {
+    let specs: Vec<Value> = runs
+        .iter()
+        .map(|r| serde_json::to_value(&r.spec).unwrap_or_default())
+        .collect();
+    let keys: BTreeSet<&String> = specs
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|o| o.keys())
+        .collect();
+    keys.into_iter()
+        .filter(|key| specs.iter().any(|s| s.get(key) != specs[0].get(key)))
+        .cloned()
+        .collect()
+}
+
+/// Backtest runs recorded for a saved strategy, oldest first
+#[tauri::command]
+pub fn list_backtest_runs(
+    store: State<'_, RunStore>,
+    strategy_id: Option<String>,
+    fields: Option<Vec<String>>,
+) -> Result<Value, String> {
+    select(&store.list(strategy_id.as_deref()), fields.as_deref())
+}
+
+/// Compare runs side by side relative to the first id
+#[tauri::command]
+pub fn compare_runs(store: State<'_, RunStore>, ids: Vec<String>) -> Result<RunComparison, String> {
+    store.compare(&ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(total_return: f64, trades: usize) -> BacktestMetrics {
+        BacktestMetrics {
+            total_return,
+            max_drawdown: 0.1,
+            trades,
+            total_costs: 0.0,
+            settlement_blocked: 0,
+        }
+    }
+
+    #[test]
+    fn test_record_and_compare() {
+        let path = std::env::temp_dir().join(format!("ssi-runs-{}.json", std::process::id()));
+        let store = RunStore::load(path.clone());
+        let record = |spec: &StrategySpec, m: BacktestMetrics| {
+            store
+                .record(
+                    Some("strategy-a".to_string()),
+                    spec,
+                    vec!["SH600000".to_string()],
+                    "1d",
+                    (None, None),
+                    &m,
+                )
+                .unwrap()
+        };
+        let first = record(
+            &StrategySpec::MaCross { fast: 5, slow: 20 },
+            metrics(0.1, 4),
+        );
+        let second = record(
+            &StrategySpec::MaCross { fast: 5, slow: 30 },
+            metrics(0.15, 2),
+        );
+        store
+            .record(
+                None,
+                &first.spec,
+                vec![],
+                "1d",
+                (None, None),
+                &metrics(0.0, 0),
+            )
+            .unwrap();
+
+        assert_eq!(
+            first.spec_hash,
+            spec_hash(&StrategySpec::MaCross { fast: 5, slow: 20 })
+        );
+        assert_ne!(first.spec_hash, second.spec_hash);
+        assert_eq!(
+            RunStore::load(path.clone()).list(Some("strategy-a")).len(),
+            2
+        );
+        assert_eq!(store.list(None).len(), 3);
+
+        let comparison = store
+            .compare(&[first.id.clone(), second.id.clone()])
+            .unwrap();
+        assert_eq!(comparison.changed_parameters, ["slow"]);
+        assert!((comparison.deltas[1].total_return - 0.05).abs() < 1e-12);
+        assert_eq!(comparison.deltas[1].trades, -2);
+        assert!(store.compare(&["run-missing".to_string()]).is_err());
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,322 @@
+//! Read-only broker integration.
+//!
+//! Positions and cash balances are pulled on demand from the IBKR Client
+//! Portal Gateway, which the user runs locally and logs into themselves. The
+//! client only ever issues GET requests to the gateway's portfolio
+//! endpoints; there is no code path that can place, modify or cancel an
+//! order. Synced accounts are stored as snapshots in the
+//! [`PortfolioStore`](crate::portfolio::PortfolioStore), separate from
+//! manual holdings.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::portfolio::{BrokerAccount, CashBalance, Holding, PortfolioStore};
+use crate::privacy::Privacy;
+use crate::settings::SettingsStore;
+use crate::utils::get_timestamp;
+
+pub const IBKR: &str = "ibkr";
+
+/// Only these endpoints are ever requested
+const READ_ONLY_PREFIX: &str = "/portfolio/";
+
+/// The gateway returns at most this many positions per page
+const POSITIONS_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrokerSettings {
+    /// Base URL of the IBKR Client Portal Gateway; must be on this machine
+    pub ibkr_gateway: String,
+}
+
+impl Default for BrokerSettings {
+    fn default() -> Self {
+        Self {
+            ibkr_gateway: "https://localhost:5000".to_string(),
+        }
+    }
+}
+
+impl BrokerSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let url = url::Url::parse(&self.ibkr_gateway)
+            .map_err(|e| format!("Invalid gateway URL {}: {}", self.ibkr_gateway, e))?;
+        if !is_loopback(&url) {
+            return Err(format!(
+                "The broker gateway must run on this machine, got {}",
+                self.ibkr_gateway
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn is_loopback(url: &url::Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IbkrAccount {
+    #[serde(rename = "accountId", alias = "id")]
+    account_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IbkrPosition {
+    #[serde(default)]
+    conid: i64,
+    #[serde(default)]
+    ticker: Option<String>,
+    #[serde(default)]
+    contract_desc: Option<String>,
+    #[serde(default)]
+    listing_exchange: Option<String>,
+    position: f64,
+    #[serde(default)]
+    avg_price: Option<f64>,
+    #[serde(default)]
+    avg_cost: f64,
+    #[serde(default)]
+    mkt_price: Option<f64>,
+    #[serde(default)]
+    currency: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IbkrLedgerEntry {
+    #[serde(default)]
+    cashbalance: f64,
+    #[serde(default)]
+    stockmarketvalue: f64,
+    #[serde(default)]
+    netliquidationvalue: f64,
+}
+
+/// App symbol for a gateway position: A-shares via Stock Connect, HK and others by ticker
+fn map_symbol(ticker: &str, exchange: &str) -> String {
+    let ticker = ticker.trim().to_uppercase();
+    match exchange {
+        "SEHKNTL" => format!("SH{}", ticker),
+        "SEHKSZSE" => format!("SZ{}", ticker),
+        "SEHK" => format!("HK{:0>5}", ticker),
+        _ => ticker,
+    }
+}
+
+fn to_holding(position: IbkrPosition, now: &str) -> Holding {
+    let ticker = position
+        .ticker
+        .or(position.contract_desc)
+        .unwrap_or_else(|| position.conid.to_string());
+    Holding {
+        id: format!("{}-{}", IBKR, position.conid),
+        symbol: map_symbol(&ticker, position.listing_exchange.as_deref().unwrap_or("")),
+        quantity: position.position,
+        cost_price: position.avg_price.unwrap_or(position.avg_cost),
+        currency: position.currency,
+        market_price: position.mkt_price,
+        note: String::new(),
+        updated_at: now.to_string(),
+    }
+}
+
+/// Balances per currency; the gateway's "BASE" entry is an aggregate and is skipped
+fn to_balances(ledger: BTreeMap<String, IbkrLedgerEntry>) -> Vec<CashBalance> {
+    ledger
+        .into_iter()
+        .filter(|(currency, _)| currency != "BASE")
+        .map(|(currency, entry)| CashBalance {
+            currency,
+            cash: entry.cashbalance,
+            market_value: entry.stockmarketvalue,
+            net_liquidation: entry.netliquidationvalue,
+        })
+        .collect()
+}
+
+fn check_read_only(path: &str) -> Result<(), String> {
+    if path.starts_with(READ_ONLY_PREFIX) && !path.contains("..") {
+        Ok(())
+    } else {
+        Err(format!("Refusing non-portfolio broker request: {}", path))
+    }
+}
+
+/// GET-only client for the gateway's portfolio endpoints
+struct IbkrClient {
+    base: String,
+    client: reqwest::Client,
+}
+
+impl IbkrClient {
+    fn new(gateway: &str) -> Result<Self, String> {
+        let url = url::Url::parse(gateway)
+            .map_err(|e| format!("Invalid gateway URL {}: {}", gateway, e))?;
+        // The gateway serves a self-signed certificate; that is only accepted on loopback
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(is_loopback(&url))
+            .no_proxy()
+            .timeout(Duration::from_secs(15))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        Ok(Self {
+            base: format!("{}/v1/api", gateway.trim_end_matches('/')),
+            client,
+        })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        check_read_only(path)?;
+        let response = self
+            .client
+            .get(format!("{}{}", self.base, path))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the IBKR gateway: {}", e))?;
+        match response.status().as_u16() {
+            200 => {}
+            401 => {
+                return Err(
+                    "The IBKR gateway is not logged in; log in to it and sync again".to_string(),
+                )
+            }
+            status => {
+                return Err(format!(
+                    "IBKR gateway returned HTTP {} for {}",
+                    status, path
+                ))
+            }
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse IBKR response for {}: {}", path, e))
+    }
+
+    async fn account(&self, account_id: &str) -> Result<BrokerAccount, String> {
+        let now = get_timestamp();
+        let mut positions = Vec::new();
+        for page in 0.. {
+            let batch: Vec<IbkrPosition> = self
+                .get(&format!("/portfolio/{}/positions/{}", account_id, page))
+                .await?;
+            let last = batch.len() < POSITIONS_PAGE_SIZE;
+            positions.extend(
+                batch
+                    .into_iter()
+                    .filter(|p| p.position != 0.0)
+                    .map(|p| to_holding(p, &now)),
+            );
+            if last {
+                break;
+            }
+        }
+        let ledger = self
+            .get(&format!("/portfolio/{}/ledger", account_id))
+            .await?;
+        Ok(BrokerAccount {
+            broker: IBKR.to_string(),
+            account_id: account_id.to_string(),
+            positions,
+            balances: to_balances(ledger),
+            synced_at: now,
+        })
+    }
+}
+
+/// Pull positions and balances of every account from a broker into the portfolio
+#[tauri::command]
+pub async fn sync_broker_positions(
+    app: AppHandle,
+    broker: Option<String>,
+) -> Result<Vec<BrokerAccount>, String> {
+    let broker = broker.unwrap_or_else(|| IBKR.to_string());
+    if broker != IBKR {
+        return Err(format!("Unsupported broker: {}", broker));
+    }
+    let gateway = app.state::<SettingsStore>().get().brokers.ibkr_gateway;
+    let client = IbkrClient::new(&gateway)?;
+    // The gateway requires the account list before any other portfolio call
+    let accounts: Vec<IbkrAccount> = client.get("/portfolio/accounts").await?;
+    let mut synced = Vec::new();
+    for account in accounts {
+        let account = client.account(&account.account_id).await?;
+        app.state::<PortfolioStore>()
+            .replace_broker_account(&format!("sync:{}", broker), account.clone())?;
+        synced.push(account);
+    }
+    info!("Synced {} {} account(s)", synced.len(), broker);
+    let synced = app.state::<Privacy>().apply(synced)?;
+    if let Err(e) = app.emit("portfolio-synced", synced.clone()) {
+        warn!("Failed to emit portfolio-synced event: {}", e);
+    }
+    Ok(synced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions_and_ledger_parsing() {
+        let positions: Vec<IbkrPosition> = serde_json::from_str(
+            r#"[
+                {"acctId": "U1", "conid": 265598, "contractDesc": "AAPL", "ticker": "AAPL",
+                 "listingExchange": "NASDAQ", "position": 10.0, "avgCost": 150.5,
+                 "avgPrice": 150.5, "mktPrice": 180.25, "currency": "USD"},
+                {"conid": 1, "ticker": "700", "listingExchange": "SEHK", "position": 200,
+                 "avgCost": 320.0, "currency": "HKD"},
+                {"conid": 2, "ticker": "600519", "listingExchange": "SEHKNTL",
+                 "position": 100, "avgCost": 1650.0, "currency": "CNH"}
+            ]"#,
+        )
+        .unwrap();
+        let holdings: Vec<Holding> = positions.into_iter().map(|p| to_holding(p, "t")).collect();
+        assert_eq!(holdings[0].symbol, "AAPL");
+        assert_eq!(holdings[0].id, "ibkr-265598");
+        assert_eq!(holdings[0].market_price, Some(180.25));
+        assert_eq!(holdings[1].symbol, "HK00700");
+        assert_eq!(holdings[1].cost_price, 320.0);
+        assert_eq!(holdings[2].symbol, "SH600519");
+        assert_eq!(map_symbol("000001", "SEHKSZSE"), "SZ000001");
+
+        let ledger: BTreeMap<String, IbkrLedgerEntry> = serde_json::from_str(
+            r#"{"BASE": {"cashbalance": 1.0}, "USD": {"cashbalance": 500.0,
+                "stockmarketvalue": 1802.5, "netliquidationvalue": 2302.5}}"#,
+        )
+        .unwrap();
+        let balances = to_balances(ledger);
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].currency, "USD");
+        assert_eq!(balances[0].net_liquidation, 2302.5);
+    }
+
+    #[test]
+    fn test_gateway_must_be_local_and_read_only() {
+        assert!(BrokerSettings::default().validate().is_ok());
+        let remote = BrokerSettings {
+            ibkr_gateway: "https://gateway.example.com:5000".to_string(),
+        };
+        assert!(remote.validate().is_err());
+        let ip = BrokerSettings {
+            ibkr_gateway: "https://127.0.0.1:5000".to_string(),
+        };
+        assert!(ip.validate().is_ok());
+
+        assert!(check_read_only("/portfolio/U1/ledger").is_ok());
+        assert!(check_read_only("/iserver/account/U1/orders").is_err());
+        assert!(check_read_only("/portfolio/../iserver/account/U1/orders").is_err());
+    }
+}
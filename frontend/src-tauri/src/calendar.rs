@@ -0,0 +1,560 @@
+//! Trading calendars and the A-share market status banner.
+//!
+//! Exchange closures are the holiday schedules published each December by
+//! the Shanghai and Shenzhen exchanges, kept here as calendar-day ranges;
+//! weekends are closed regardless. Hong Kong and New York closures are kept
+//! as plain full-day lists, since only the A-share banner names holidays,
+//! along with their half days: Hong Kong trades only the morning session on
+//! the eves of Christmas, New Year and the Lunar New Year, and New York
+//! closes at 13:00 around Independence Day, Thanksgiving and Christmas. Dates
+//! past [`COVERED_THROUGH`] are treated as full weekday trading days until the
+//! table is extended. The banner turns the
+//! calendar and the session times in [`sessions`](crate::sessions) into a
+//! localized status line, so the frontend does not duplicate calendar logic.
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Market;
+use crate::sessions::{session_segments, timezone};
+
+/// Last year the closure table covers
+pub const COVERED_THROUGH: i32 = 2026;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Holiday {
+    NewYear,
+    SpringFestival,
+    Qingming,
+    LabourDay,
+    DragonBoat,
+    MidAutumn,
+    NationalDay,
+    /// National Day and Mid-Autumn Festival falling together
+    NationalDayMidAutumn,
+}
+
+type Ymd = (i32, u32, u32);
+
+/// Exchange closures as inclusive `(first, last)` calendar days
+const CLOSURES: &[(Holiday, Ymd, Ymd)] = &[
+    (Holiday::NewYear, (2024, 1, 1), (2024, 1, 1)),
+    (Holiday::SpringFestival, (2024, 2, 9), (2024, 2, 18)),
+    (Holiday::Qingming, (2024, 4, 4), (2024, 4, 6)),
+    (Holiday::LabourDay, (2024, 5, 1), (2024, 5, 5)),
+    (Holiday::DragonBoat, (2024, 6, 10), (2024, 6, 10)),
+    (Holiday::MidAutumn, (2024, 9, 15), (2024, 9, 17)),
+    (Holiday::NationalDay, (2024, 10, 1), (2024, 10, 7)),
+    (Holiday::NewYear, (2025, 1, 1), (2025, 1, 1)),
+    (Holiday::SpringFestival, (2025, 1, 28), (2025, 2, 4)),
+    (Holiday::Qingming, (2025, 4, 4), (2025, 4, 6)),
+    (Holiday::LabourDay, (2025, 5, 1), (2025, 5, 5)),
+    (Holiday::DragonBoat, (2025, 5, 31), (2025, 6, 2)),
+    (Holiday::NationalDayMidAutumn, (2025, 10, 1), (2025, 10, 8)),
+    (Holiday::NewYear, (2026, 1, 1), (2026, 1, 3)),
+    (Holiday::SpringFestival, (2026, 2, 15), (2026, 2, 23)),
+    (Holiday::Qingming, (2026, 4, 4), (2026, 4, 6)),
+    (Holiday::LabourDay, (2026, 5, 1), (2026, 5, 5)),
+    (Holiday::DragonBoat, (2026, 6, 19), (2026, 6, 21)),
+    (Holiday::MidAutumn, (2026, 9, 25), (2026, 9, 27)),
+    (Holiday::NationalDay, (2026, 10, 1), (2026, 10, 7)),
+];
+
+/// Full-day closures of the Hong Kong exchange on weekdays
+const HK_CLOSURES: &[Ymd] = &[
+    (2024, 1, 1),
+    (2024, 2, 12),
+    (2024, 2, 13),
+    (2024, 3, 29),
+    (2024, 4, 1),
+    (2024, 4, 4),
+    (2024, 5, 1),
+    (2024, 5, 15),
+    (2024, 6, 10),
+    (2024, 7, 1),
+    (2024, 9, 18),
+    (2024, 10, 1),
+    (2024, 10, 11),
+    (2024, 12, 25),
+    (2024, 12, 26),
+    (2025, 1, 1),
+    (2025, 1, 29),
+    (2025, 1, 30),
+    (2025, 1, 31),
+    (2025, 4, 4),
+    (2025, 4, 18),
+    (2025, 4, 21),
+    (2025, 5, 1),
+    (2025, 5, 5),
+    (2025, 7, 1),
+    (2025, 10, 1),
+    (2025, 10, 7),
+    (2025, 10, 29),
+    (2025, 12, 25),
+    (2025, 12, 26),
+    (2026, 1, 1),
+    (2026, 2, 17),
+    (2026, 2, 18),
+    (2026, 2, 19),
+    (2026, 4, 3),
+    (2026, 4, 6),
+    (2026, 4, 7),
+    (2026, 5, 1),
+    (2026, 5, 25),
+    (2026, 6, 19),
+    (2026, 7, 1),
+    (2026, 10, 1),
+    (2026, 10, 19),
+    (2026, 12, 25),
+];
+
+/// Full-day closures of the New York exchanges on weekdays
+const US_CLOSURES: &[Ymd] = &[
+    (2024, 1, 1),
+    (2024, 1, 15),
+    (2024, 2, 19),
+    (2024, 3, 29),
+    (2024, 5, 27),
+    (2024, 6, 19),
+    (2024, 7, 4),
+    (2024, 9, 2),
+    (2024, 11, 28),
+    (2024, 12, 25),
+    (2025, 1, 1),
+    (2025, 1, 9),
+    (2025, 1, 20),
+    (2025, 2, 17),
+    (2025, 4, 18),
+    (2025, 5, 26),
+    (2025, 6, 19),
+    (2025, 7, 4),
+    (2025, 9, 1),
+    (2025, 11, 27),
+    (2025, 12, 25),
+    (2026, 1, 1),
+    (2026, 1, 19),
+    (2026, 2, 16),
+    (2026, 4, 3),
+    (2026, 5, 25),
+    (2026, 6, 19),
+    (2026, 7, 3),
+    (2026, 9, 7),
+    (2026, 11, 26),
+    (2026, 12, 25),
+];
+
+/// Weekdays the Hong Kong exchange trades the morning session only
+const HK_HALF_DAYS: &[Ymd] = &[
+    (2024, 2, 9),
+    (2024, 12, 24),
+    (2024, 12, 31),
+    (2025, 1, 28),
+    (2025, 12, 24),
+    (2025, 12, 31),
+    (2026, 2, 16),
+    (2026, 12, 24),
+    (2026, 12, 31),
+];
+
+/// Weekdays the New York exchanges close at 13:00
+const US_HALF_DAYS: &[Ymd] = &[
+    (2024, 7, 3),
+    (2024, 11, 29),
+    (2024, 12, 24),
+    (2025, 7, 3),
+    (2025, 11, 28),
+    (2025, 12, 24),
+    (2026, 11, 27),
+    (2026, 12, 24),
+];
+
+fn date((y, m, d): Ymd) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap_or_default()
+}
+
+/// The holiday closure a day falls in, if any
+pub fn holiday_on(day: NaiveDate) -> Option<Holiday> {
+    CLOSURES
+        .iter()
+        .find(|(_, first, last)| date(*first) <= day && day <= date(*last))
+        .map(|(holiday, _, _)| *holiday)
+}
+
+pub fn is_trading_day(day: NaiveDate) -> bool {
+    !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) && holiday_on(day).is_none()
+}
+
+/// Whether the exchanges of `market` trade on `day`
+pub fn is_market_trading_day(market: Market, day: NaiveDate) -> bool {
+    let closures = match market {
+        Market::Cn => return is_trading_day(day),
+        Market::Hk => HK_CLOSURES,
+        Market::Us => US_CLOSURES,
+    };
+    !matches!(day.weekday(), Weekday::Sat | Weekday::Sun)
+        && !closures.iter().any(|ymd| date(*ymd) == day)
+}
+
+/// Local minute `market` closes at on `day` when it closes early
+pub fn early_close(market: Market, day: NaiveDate) -> Option<u32> {
+    let (half_days, close) = match market {
+        Market::Cn => return None,
+        Market::Hk => (HK_HALF_DAYS, 12 * 60),
+        Market::Us => (US_HALF_DAYS, 13 * 60),
+    };
+    half_days
+        .iter()
+        .any(|ymd| date(*ymd) == day)
+        .then_some(close)
+}
+
+/// Session segments of `market` on `day`, cut short on half days
+pub fn sessions_on(market: Market, day: NaiveDate) -> Vec<(u32, u32)> {
+    let segments = session_segments(market).iter().copied();
+    match early_close(market, day) {
+        Some(close) => segments
+            .filter(|&(start, _)| start < close)
+            .map(|(start, end)| (start, end.min(close)))
+            .collect(),
+        None => segments.collect(),
+    }
+}
+
+/// First trading day after `day`
+pub fn next_trading_day(day: NaiveDate) -> NaiveDate {
+    day.iter_days()
+        .skip(1)
+        .find(|d| is_trading_day(*d))
+        .unwrap_or(day)
+}
+
+/// Start of the first trading session after `now`: today's open if it is
+/// still ahead, otherwise the next trading day's
+pub fn next_open(now: DateTime<Tz>) -> DateTime<Tz> {
+    let today = now.date_naive();
+    let open = session_segments(Market::Cn)[0].0;
+    let day = if is_trading_day(today) && now.hour() * 60 + now.minute() < open {
+        today
+    } else {
+        next_trading_day(today)
+    };
+    let local = day.and_hms_opt(open / 60, open % 60, 0).unwrap_or_default();
+    now.timezone()
+        .from_local_datetime(&local)
+        .single()
+        .unwrap_or(now)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    Zh,
+    En,
+}
+
+impl Locale {
+    pub fn parse(tag: Option<&str>) -> Self {
+        match tag {
+            Some(tag) if !tag.to_ascii_lowercase().starts_with("zh") => Locale::En,
+            _ => Locale::Zh,
+        }
+    }
+}
+
+impl Holiday {
+    pub fn name(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Holiday::NewYear, Locale::Zh) => "元旦",
+            (Holiday::NewYear, Locale::En) => "New Year's Day",
+            (Holiday::SpringFestival, Locale::Zh) => "春节",
+            (Holiday::SpringFestival, Locale::En) => "Spring Festival",
+            (Holiday::Qingming, Locale::Zh) => "清明节",
+            (Holiday::Qingming, Locale::En) => "Qingming Festival",
+            (Holiday::LabourDay, Locale::Zh) => "劳动节",
+            (Holiday::LabourDay, Locale::En) => "Labour Day",
+            (Holiday::DragonBoat, Locale::Zh) => "端午节",
+            (Holiday::DragonBoat, Locale::En) => "Dragon Boat Festival",
+            (Holiday::MidAutumn, Locale::Zh) => "中秋节",
+            (Holiday::MidAutumn, Locale::En) => "Mid-Autumn Festival",
+            (Holiday::NationalDay, Locale::Zh) => "国庆节",
+            (Holiday::NationalDay, Locale::En) => "National Day",
+            (Holiday::NationalDayMidAutumn, Locale::Zh) => "国庆节、中秋节",
+            (Holiday::NationalDayMidAutumn, Locale::En) => "National Day and Mid-Autumn Festival",
+        }
+    }
+
+    /// Seasonal greeting; none for Qingming, a day of remembrance
+    pub fn greeting(self, locale: Locale) -> Option<&'static str> {
+        Some(match (self, locale) {
+            (Holiday::Qingming, _) => return None,
+            (Holiday::NewYear, Locale::Zh) => "新年快乐",
+            (Holiday::NewYear, Locale::En) => "Happy New Year",
+            (Holiday::SpringFestival, Locale::Zh) => "新春快乐",
+            (Holiday::SpringFestival, Locale::En) => "Happy Chinese New Year",
+            (Holiday::LabourDay, Locale::Zh) => "劳动节快乐",
+            (Holiday::LabourDay, Locale::En) => "Happy Labour Day",
+            (Holiday::DragonBoat, Locale::Zh) => "端午安康",
+            (Holiday::DragonBoat, Locale::En) => "Happy Dragon Boat Festival",
+            (Holiday::MidAutumn, Locale::Zh) => "中秋快乐",
+            (Holiday::MidAutumn, Locale::En) => "Happy Mid-Autumn Festival",
+            (Holiday::NationalDay, Locale::Zh) => "国庆快乐",
+            (Holiday::NationalDay, Locale::En) => "Happy National Day",
+            (Holiday::NationalDayMidAutumn, Locale::Zh) => "国庆中秋双节快乐",
+            (Holiday::NationalDayMidAutumn, Locale::En) => {
+                "Happy National Day and Mid-Autumn Festival"
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketState {
+    PreOpen,
+    Open,
+    LunchBreak,
+    Closed,
+    Weekend,
+    Holiday,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionBanner {
+    pub market: Market,
+    pub state: MarketState,
+    /// Ready-made status line, e.g. "沪深休市：中秋节，9月18日恢复交易"
+    pub message: String,
+    pub greeting: Option<String>,
+    pub holiday: Option<Holiday>,
+    /// Next trading day, `YYYY-MM-DD`, when the market is shut for the day
+    pub resumes_on: Option<String>,
+    /// Whether the closure table covers today; holidays are unknown past it
+    pub calendar_covered: bool,
+}
+
+fn clock(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn month_day(day: NaiveDate, locale: Locale) -> String {
+    match locale {
+        Locale::Zh => format!("{}月{}日", day.month(), day.day()),
+        Locale::En => day.format("%b %-d").to_string(),
+    }
+}
+
+/// Holiday closure between `from` and the next trading day, if any
+fn closure_before_next_open(from: NaiveDate, next: NaiveDate) -> Option<Holiday> {
+    from.iter_days()
+        .take_while(|d| *d < next)
+        .find_map(holiday_on)
+}
+
+/// The A-share status banner at `now`
+pub fn banner(now: DateTime<Tz>, locale: Locale) -> SessionBanner {
+    let today = now.date_naive();
+    let minute = now.hour() * 60 + now.minute();
+    let segments = session_segments(Market::Cn);
+    let (open, close) = (segments[0].0, segments[segments.len() - 1].1);
+    let next = next_trading_day(today);
+    let market = match locale {
+        Locale::Zh => "沪深",
+        Locale::En => "SSE/SZSE",
+    };
+    let resumes = month_day(next, locale);
+    let greeting = holiday_on(today).and_then(|h| h.greeting(locale).map(str::to_string));
+
+    let (state, holiday, message) = if !is_trading_day(today) {
+        match closure_before_next_open(today, next) {
+            Some(holiday) => (
+                MarketState::Holiday,
+                Some(holiday),
+                match locale {
+                    Locale::Zh => format!(
+                        "{}休市：{}，{}恢复交易",
+                        market,
+                        holiday.name(locale),
+                        resumes
+                    ),
+                    Locale::En => format!(
+                        "{} closed for {}; trading resumes {}",
+                        market,
+                        holiday.name(locale),
+                        resumes
+                    ),
+                },
+            ),
+            None => (
+                MarketState::Weekend,
+                None,
+                match locale {
+                    Locale::Zh => format!("{}休市：周末，{}恢复交易", market, resumes),
+                    Locale::En => format!(
+                        "{} closed for the weekend; trading resumes {}",
+                        market, resumes
+                    ),
+                },
+            ),
+        }
+    } else if minute < open {
+        let message = match locale {
+            Locale::Zh => format!("{}待开盘，{}开始交易", market, clock(open)),
+            Locale::En => format!("{} opens at {}", market, clock(open)),
+        };
+        (MarketState::PreOpen, None, message)
+    } else if let Some(&(_, end)) = segments.iter().find(|(s, e)| *s <= minute && minute < *e) {
+        let message = match locale {
+            Locale::Zh => format!(
+                "{}交易中，{}{}",
+                market,
+                clock(end),
+                if end == close {
+                    "收盘"
+                } else {
+                    "午间休市"
+                }
+            ),
+            Locale::En => format!(
+                "{} trading, {} at {}",
+                market,
+                if end == close {
+                    "closes"
+                } else {
+                    "lunch break"
+                },
+                clock(end)
+            ),
+        };
+        (MarketState::Open, None, message)
+    } else if minute < close {
+        let resume = segments
+            .iter()
+            .find(|(s, _)| *s > minute)
+            .map_or(close, |(s, _)| *s);
+        let message = match locale {
+            Locale::Zh => format!("{}午间休市，{}恢复交易", market, clock(resume)),
+            Locale::En => format!(
+                "{} lunch break, trading resumes at {}",
+                market,
+                clock(resume)
+            ),
+        };
+        (MarketState::LunchBreak, None, message)
+    } else {
+        let tomorrow = today.succ_opt().unwrap_or(today);
+        let holiday = closure_before_next_open(tomorrow, next);
+        let message = match (holiday, locale) {
+            (Some(h), Locale::Zh) => format!(
+                "{}已收盘，{}休市，{}恢复交易",
+                market,
+                h.name(locale),
+                resumes
+            ),
+            (Some(h), Locale::En) => format!(
+                "{} closed; shut for {}, trading resumes {}",
+                market,
+                h.name(locale),
+                resumes
+            ),
+            (None, Locale::Zh) => format!("{}已收盘，下一交易日{}", market, resumes),
+            (None, Locale::En) => format!("{} closed; next trading day {}", market, resumes),
+        };
+        (MarketState::Closed, holiday, message)
+    };
+
+    let shut = !matches!(
+        state,
+        MarketState::PreOpen | MarketState::Open | MarketState::LunchBreak
+    );
+    SessionBanner {
+        market: Market::Cn,
+        state,
+        message,
+        greeting,
+        holiday,
+        resumes_on: shut.then(|| next.to_string()),
+        calendar_covered: today.year() <= COVERED_THROUGH,
+    }
+}
+
+/// Localized A-share status line; `locale` is a BCP 47 tag, Chinese by default
+#[tauri::command]
+pub fn get_session_status_banner(locale: Option<String>) -> Result<SessionBanner, String> {
+    let now = Utc::now().with_timezone(&timezone(Market::Cn));
+    Ok(banner(now, Locale::parse(locale.as_deref())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Tz> {
+        timezone(Market::Cn)
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_holiday_and_weekend_banners() {
+        let mid_autumn = banner(at(2024, 9, 16, 10, 0), Locale::Zh);
+        assert_eq!(mid_autumn.state, MarketState::Holiday);
+        assert_eq!(mid_autumn.message, "沪深休市：中秋节，9月18日恢复交易");
+        assert_eq!(mid_autumn.greeting.as_deref(), Some("中秋快乐"));
+        assert_eq!(mid_autumn.resumes_on.as_deref(), Some("2024-09-18"));
+
+        // A weekend running into a holiday reports the holiday
+        let en = banner(at(2024, 9, 14, 10, 0), Locale::En);
+        assert_eq!(
+            en.message,
+            "SSE/SZSE closed for Mid-Autumn Festival; trading resumes Sep 18"
+        );
+
+        let weekend = banner(at(2024, 7, 6, 10, 0), Locale::Zh);
+        assert_eq!(weekend.state, MarketState::Weekend);
+        assert_eq!(weekend.message, "沪深休市：周末，7月8日恢复交易");
+        assert!(weekend.greeting.is_none());
+
+        assert_eq!(next_open(at(2024, 9, 16, 10, 0)), at(2024, 9, 18, 9, 30));
+        assert_eq!(next_open(at(2024, 7, 1, 9, 0)), at(2024, 7, 1, 9, 30));
+        assert_eq!(next_open(at(2024, 7, 1, 9, 30)), at(2024, 7, 2, 9, 30));
+    }
+
+    #[test]
+    fn test_intraday_banners() {
+        assert_eq!(
+            banner(at(2024, 7, 1, 9, 0), Locale::Zh).message,
+            "沪深待开盘，09:30开始交易"
+        );
+        assert_eq!(
+            banner(at(2024, 7, 1, 10, 0), Locale::Zh).message,
+            "沪深交易中，11:30午间休市"
+        );
+        assert_eq!(
+            banner(at(2024, 7, 1, 12, 0), Locale::Zh).state,
+            MarketState::LunchBreak
+        );
+        assert_eq!(
+            banner(at(2024, 7, 1, 14, 0), Locale::Zh).message,
+            "沪深交易中，15:00收盘"
+        );
+        assert_eq!(
+            banner(at(2024, 7, 1, 16, 0), Locale::Zh).message,
+            "沪深已收盘，下一交易日7月2日"
+        );
+        // Closing ahead of the National Day week
+        let eve = banner(at(2024, 9, 30, 16, 0), Locale::Zh);
+        assert_eq!(eve.message, "沪深已收盘，国庆节休市，10月8日恢复交易");
+        assert_eq!(eve.holiday, Some(Holiday::NationalDay));
+        assert_eq!(next_trading_day(date((2025, 9, 30))), date((2025, 10, 9)));
+
+        // Each market keeps its own holidays
+        assert!(!is_market_trading_day(Market::Hk, date((2024, 7, 1))));
+        assert!(is_market_trading_day(Market::Cn, date((2024, 7, 1))));
+        assert!(!is_market_trading_day(Market::Us, date((2024, 7, 4))));
+        assert!(is_market_trading_day(Market::Us, date((2024, 10, 1))));
+        assert_eq!(sessions_on(Market::Hk, date((2024, 12, 24))), [(570, 720)]);
+        assert_eq!(sessions_on(Market::Us, date((2024, 11, 29))), [(570, 780)]);
+        assert_eq!(sessions_on(Market::Us, date((2024, 11, 27))), [(570, 960)]);
+    }
+}
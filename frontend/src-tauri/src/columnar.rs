@@ -0,0 +1,357 @@
+//! Memory-mapped columnar storage for large historical bar series.
+//!
+//! Each symbol/interval pair is stored as a single file laid out as:
+//! a 16-byte header (`magic`, `version`, `rows`) followed by six
+//! little-endian columns of `rows` 8-byte values each (timestamp, open,
+//! high, low, close, volume). Readers map the file and borrow the columns
+//! directly as slices, so hot paths never deserialize individual rows.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use log::info;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::models::Bar;
+use crate::snapshot::SnapshotClock;
+use crate::utils::{ensure_dir_exists, format_file_size};
+
+const MAGIC: &[u8; 4] = b"SSIC";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+const COLUMNS: usize = 6;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnarInfo {
+    symbol: String,
+    interval: String,
+    rows: usize,
+    first_timestamp: Option<i64>,
+    last_timestamp: Option<i64>,
+    file_size: String,
+}
+
+/// A read-only, memory-mapped view over one columnar bar file
+pub struct MappedBars {
+    mmap: Mmap,
+    rows: usize,
+}
+
+impl MappedBars {
+    /// Map a columnar file and validate its header
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        // SAFETY: files are only ever replaced via atomic rename, never
+        // truncated in place, so the mapping stays valid while it is held.
+        let mmap = unsafe { Mmap::map(&file) }
+            .map_err(|e| format!("Failed to map {:?}: {}", path, e))?;
+
+        if mmap.len() < HEADER_LEN || &mmap[0..4] != MAGIC {
+            return Err(format!("Invalid columnar file: {:?}", path));
+        }
+        let version = u32::from_le_bytes(mmap[4..8].try_into().unwrap());
+        if version != VERSION {
+            return Err(format!("Unsupported columnar version {} in {:?}", version, path));
+        }
+        let rows = u64::from_le_bytes(mmap[8..16].try_into().unwrap());
+        // A corrupt row count must not overflow into a length that matches
+        let len = usize::try_from(rows)
+            .ok()
+            .and_then(|rows| rows.checked_mul(COLUMNS * 8))
+            .and_then(|columns| columns.checked_add(HEADER_LEN))
+            .ok_or_else(|| format!("Invalid row count {} in {:?}", rows, path))?;
+        if mmap.len() != len {
+            return Err(format!("Truncated columnar file: {:?}", path));
+        }
+        let rows = rows as usize;
+
+        Ok(Self { mmap, rows })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    fn column_bytes(&self, index: usize) -> &[u8] {
+        let start = HEADER_LEN + index * self.rows * 8;
+        &self.mmap[start..start + self.rows * 8]
+    }
+
+    fn f64_column(&self, index: usize) -> &[f64] {
+        // SAFETY: the header is 8 bytes aligned and mappings are page
+        // aligned, so every column starts on an 8-byte boundary.
+        let (prefix, values, suffix) = unsafe { self.column_bytes(index).align_to::<f64>() };
+        debug_assert!(prefix.is_empty() && suffix.is_empty());
+        values
+    }
+
+    pub fn timestamps(&self) -> &[i64] {
+        // SAFETY: see `f64_column`.
+        let (prefix, values, suffix) = unsafe { self.column_bytes(0).align_to::<i64>() };
+        debug_assert!(prefix.is_empty() && suffix.is_empty());
+        values
+    }
+
+    pub fn opens(&self) -> &[f64] {
+        self.f64_column(1)
+    }
+
+    pub fn highs(&self) -> &[f64] {
+        self.f64_column(2)
+    }
+
+    pub fn lows(&self) -> &[f64] {
+        self.f64_column(3)
+    }
+
+    pub fn closes(&self) -> &[f64] {
+        self.f64_column(4)
+    }
+
+    pub fn volumes(&self) -> &[f64] {
+        self.f64_column(5)
+    }
+
+    /// Index range of bars whose timestamp falls within `[start, end)`
+    pub fn range(&self, start: i64, end: i64) -> Range<usize> {
+        let ts = self.timestamps();
+        let lo = ts.partition_point(|&t| t < start);
+        let hi = ts.partition_point(|&t| t < end);
+        lo..hi.max(lo)
+    }
+
+    /// Number of leading bars at or before `until` (all bars if `None`)
+    pub fn rows_until(&self, until: Option<i64>) -> usize {
+        match until {
+            Some(ts) => self.timestamps().partition_point(|&t| t <= ts),
+            None => self.rows,
+        }
+    }
+
+    /// Materialize a single bar
+    pub fn bar(&self, index: usize) -> Bar {
+        Bar {
+            timestamp: self.timestamps()[index],
+            open: self.opens()[index],
+            high: self.highs()[index],
+            low: self.lows()[index],
+            close: self.closes()[index],
+            volume: self.volumes()[index],
+        }
+    }
+
+    /// Materialize a range of bars, for callers that need owned rows
+    pub fn to_bars(&self, range: Range<usize>) -> Vec<Bar> {
+        range.map(|i| self.bar(i)).collect()
+    }
+}
+
+/// Write bars to a columnar file, replacing any existing file atomically
+pub fn write_columnar(path: &Path, bars: &[Bar]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        ensure_dir_exists(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    let mut buf = Vec::with_capacity(HEADER_LEN + bars.len() * COLUMNS * 8);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&(bars.len() as u64).to_le_bytes());
+    for bar in bars {
+        buf.extend_from_slice(&bar.timestamp.to_le_bytes());
+    }
+    for column in [
+        |b: &Bar| b.open,
+        |b: &Bar| b.high,
+        |b: &Bar| b.low,
+        |b: &Bar| b.close,
+        |b: &Bar| b.volume,
+    ] {
+        for bar in bars {
+            buf.extend_from_slice(&column(bar).to_le_bytes());
+        }
+    }
+
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp).map_err(|e| format!("Failed to create {:?}: {}", tmp, e))?;
+    file.write_all(&buf)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write {:?}: {}", tmp, e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {:?}: {}", path, e))?;
+    Ok(())
+}
+
+/// Store of columnar files with a cache of open mappings
+pub struct ColumnarStore {
+    root: PathBuf,
+    mapped: Mutex<HashMap<String, Arc<MappedBars>>>,
+}
+
+impl ColumnarStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            mapped: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn key(symbol: &str, interval: &str) -> String {
+        format!("{}_{}", symbol.to_uppercase(), interval)
+    }
+
+    fn path_for(&self, symbol: &str, interval: &str) -> PathBuf {
+        self.root.join(format!("{}.col", Self::key(symbol, interval)))
+    }
+
+    /// Open (or reuse) the mapping for a symbol/interval
+    pub fn open(&self, symbol: &str, interval: &str) -> Result<Arc<MappedBars>, String> {
+        let key = Self::key(symbol, interval);
+        let mut mapped = self.mapped.lock().unwrap();
+        if let Some(bars) = mapped.get(&key) {
+            return Ok(bars.clone());
+        }
+        let bars = Arc::new(MappedBars::open(&self.path_for(symbol, interval))?);
+        mapped.insert(key, bars.clone());
+        Ok(bars)
+    }
+
+    /// Replace the stored series for a symbol/interval
+    pub fn write(&self, symbol: &str, interval: &str, bars: &[Bar]) -> Result<(), String> {
+        write_columnar(&self.path_for(symbol, interval), bars)?;
+        self.mapped.lock().unwrap().remove(&Self::key(symbol, interval));
+        info!("Wrote {} columnar bars for {} ({})", bars.len(), symbol, interval);
+        Ok(())
+    }
+
+    /// Open mappings and their total mapped bytes
+    pub fn mapped(&self) -> (usize, usize) {
+        let mapped = self.mapped.lock().unwrap();
+        let bytes = mapped.values().map(|bars| bars.mmap.len()).sum();
+        (mapped.len(), bytes)
+    }
+
+    /// Unmap files no one else holds, returning how many were released
+    pub fn release_unused(&self) -> usize {
+        let mut mapped = self.mapped.lock().unwrap();
+        let before = mapped.len();
+        mapped.retain(|_, bars| Arc::strong_count(bars) > 1);
+        before - mapped.len()
+    }
+
+    pub fn exists(&self, symbol: &str, interval: &str) -> bool {
+        self.path_for(symbol, interval).exists()
+    }
+
+    /// Every stored symbol/interval pair, sorted
+    pub fn series(&self) -> Vec<(String, String)> {
+        let mut series: Vec<(String, String)> = fs::read_dir(&self.root)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| {
+                        let name = entry.ok()?.file_name().into_string().ok()?;
+                        let (symbol, interval) = name.strip_suffix(".col")?.rsplit_once('_')?;
+                        Some((symbol.to_string(), interval.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        series.sort();
+        series
+    }
+
+    /// When the stored series was last written
+    pub fn modified(&self, symbol: &str, interval: &str) -> Option<SystemTime> {
+        fs::metadata(self.path_for(symbol, interval))
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+/// Describe the columnar dataset stored for a symbol/interval
+#[tauri::command]
+pub fn get_columnar_info(
+    store: State<'_, ColumnarStore>,
+    clock: State<'_, SnapshotClock>,
+    symbol: String,
+    interval: String,
+) -> Result<ColumnarInfo, String> {
+    let bars = store.open(&symbol, &interval)?;
+    let rows = bars.rows_until(clock.frozen_at());
+    let ts = &bars.timestamps()[..rows];
+    let size = fs::metadata(store.path_for(&symbol, &interval))
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    Ok(ColumnarInfo {
+        symbol,
+        interval,
+        rows,
+        first_timestamp: ts.first().copied(),
+        last_timestamp: ts.last().copied(),
+        file_size: format_file_size(size),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(n: i64) -> Vec<Bar> {
+        (0..n)
+            .map(|i| Bar {
+                timestamp: 1_700_000_000 + i * 60,
+                open: i as f64,
+                high: i as f64 + 1.0,
+                low: i as f64 - 1.0,
+                close: i as f64 + 0.5,
+                volume: 100.0 * i as f64,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_roundtrip_and_range() {
+        let dir = std::env::temp_dir().join(format!("ssi-columnar-{}", std::process::id()));
+        let store = ColumnarStore::new(dir.clone());
+        let bars = sample(100);
+        store.write("sh600000", "1m", &bars).unwrap();
+
+        let mapped = store.open("SH600000", "1m").unwrap();
+        assert_eq!(mapped.len(), 100);
+        assert_eq!(mapped.closes()[10], 10.5);
+        assert_eq!(mapped.bar(42), bars[42]);
+
+        let range = mapped.range(bars[10].timestamp, bars[20].timestamp);
+        assert_eq!(range, 10..20);
+        assert_eq!(mapped.range(0, 1), 0..0);
+        assert_eq!(mapped.rows_until(Some(bars[9].timestamp)), 10);
+        assert_eq!(mapped.rows_until(None), 100);
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_rejects_corrupt_row_count() {
+        let path = std::env::temp_dir().join(format!("ssi-columnar-{}.col", std::process::id()));
+        write_columnar(&path, &sample(3)).unwrap();
+        let mut content = fs::read(&path).unwrap();
+        // Wraps around to the real length without checked arithmetic
+        let wrapping = 3 + (1u64 << 60);
+        for rows in [4, u64::MAX, wrapping] {
+            content[8..16].copy_from_slice(&rows.to_le_bytes());
+            fs::write(&path, &content).unwrap();
+            assert!(MappedBars::open(&path).is_err());
+        }
+        fs::remove_file(path).ok();
+    }
+}
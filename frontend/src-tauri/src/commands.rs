@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 use tauri::{Manager, Window};
 use std::process::Command;
 use log::info;
+use sysinfo::{ProcessRefreshKind, System};
+
+use crate::utils::format_file_size;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppInfo {
@@ -13,8 +16,18 @@ pub struct AppInfo {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SystemInfo {
     os: String,
+    os_version: Option<String>,
     arch: String,
+    /// Installed RAM, human readable
     memory: String,
+    total_memory: u64,
+    available_memory: u64,
+    cpu_model: String,
+    /// Logical cores
+    cpu_cores: usize,
+    physical_cores: Option<usize>,
+    /// Resident memory of this app's main process
+    app_memory: Option<u64>,
 }
 
 /// Get application information
@@ -102,21 +115,35 @@ pub fn show_in_folder(path: String) -> Result<(), String> {
 /// Get system information
 #[tauri::command]
 pub fn get_system_info() -> Result<SystemInfo, String> {
+    let mut sys = System::new();
+    sys.refresh_memory();
+    sys.refresh_cpu();
+
+    let app_memory = sysinfo::get_current_pid().ok().and_then(|pid| {
+        sys.refresh_process_specifics(pid, ProcessRefreshKind::new().with_memory());
+        sys.process(pid).map(|process| process.memory())
+    });
+    let cpu_model = sys
+        .cpus()
+        .first()
+        .map(|cpu| cpu.brand().trim().to_string())
+        .filter(|brand| !brand.is_empty())
+        .unwrap_or_else(|| "Unknown".to_string());
+
     Ok(SystemInfo {
         os: std::env::consts::OS.to_string(),
+        os_version: System::long_os_version(),
         arch: std::env::consts::ARCH.to_string(),
-        memory: "Unknown".to_string(), // TODO: Implement memory detection
+        memory: format_file_size(sys.total_memory()),
+        total_memory: sys.total_memory(),
+        available_memory: sys.available_memory(),
+        cpu_model,
+        cpu_cores: sys.cpus().len(),
+        physical_cores: sys.physical_core_count(),
+        app_memory,
     })
 }
 
-/// Check for application updates
-#[tauri::command]
-pub async fn check_for_updates() -> Result<bool, String> {
-    info!("Checking for application updates...");
-    // TODO: Implement update checking logic
-    Ok(false) // No updates available for now
-}
-
 /// Restart the application
 #[tauri::command]
 pub fn restart_app(app: tauri::AppHandle) -> Result<(), String> {
@@ -131,12 +158,4 @@ pub fn minimize_to_tray(window: Window) -> Result<(), String> {
     info!("Minimizing window to system tray");
     window.minimize().map_err(|e| format!("Failed to minimize window: {}", e))?;
     Ok(())
-}
-
-/// Show system notification
-#[tauri::command]
-pub fn show_notification(title: String, body: String) -> Result<(), String> {
-    info!("Showing notification: {} - {}", title, body);
-    // TODO: Implement notification system
-    Ok(())
 }
\ No newline at end of file
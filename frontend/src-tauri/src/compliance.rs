@@ -0,0 +1,283 @@
+//! Compliance policies for managed deployments.
+//!
+//! An administrator can install a policy file that switches off whole
+//! subsystems, such as sending data to external AI providers, delivering
+//! webhooks or syncing with a broker. The file is read once at startup from
+//! the system-wide location (`/etc/smart-stock-insider/policies.json`,
+//! `/Library/Application Support/SmartStockInsider/policies.json` or
+//! `%ProgramData%\SmartStockInsider\policies.json`), which users cannot
+//! edit. `SMART_STOCK_INSIDER_POLICY` names a policy to use only where no
+//! system policy is installed, so it cannot weaken one. Commands of a disabled subsystem are rejected by the command
+//! middleware; background work checks [`CompliancePolicy::allows`]. The
+//! policy also sets how AI output that reads like personal trading advice is
+//! handled (see [`AdviceGuardrail`]). A policy file that exists but cannot be
+//! read disables everything rather than nothing.
+
+use std::path::PathBuf;
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::utils::read_from_file;
+
+pub const POLICY_ENV: &str = "SMART_STOCK_INSIDER_POLICY";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    /// Requests to hosted AI models
+    ExternalAi,
+    /// Alert delivery to webhooks and chat services
+    Webhooks,
+    /// Reading positions from a broker
+    BrokerSync,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 3] = [
+        Subsystem::ExternalAi,
+        Subsystem::Webhooks,
+        Subsystem::BrokerSync,
+    ];
+
+    /// Commands that belong to the subsystem
+    pub fn commands(self) -> &'static [&'static str] {
+        match self {
+            Subsystem::ExternalAi => &["set_ai_api_key"],
+            Subsystem::Webhooks => &["set_delivery_secret", "test_delivery_channel"],
+            Subsystem::BrokerSync => &["sync_broker_positions"],
+        }
+    }
+
+    fn of_command(command: &str) -> Option<Subsystem> {
+        Subsystem::ALL
+            .into_iter()
+            .find(|s| s.commands().contains(&command))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdviceMode {
+    /// Drop the advice sentences and keep the analysis, with a disclaimer
+    #[default]
+    Rewrite,
+    /// Replace the whole output with a notice and the disclaimer
+    Withhold,
+}
+
+/// Handling of AI output resembling individualized investment advice
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdviceGuardrail {
+    pub mode: AdviceMode,
+    /// Appended to filtered output; the built-in disclaimer when empty
+    pub disclaimer: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PolicyFile {
+    /// Shown to users, e.g. the organisation that set the policy
+    pub name: String,
+    pub disabled: Vec<Subsystem>,
+    /// Why, shown with every rejection
+    pub reason: String,
+    pub advice: AdviceGuardrail,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompliancePolicy {
+    /// File the policy came from; none when no policy is installed
+    pub source: Option<PathBuf>,
+    pub policy: PolicyFile,
+}
+
+fn system_policy_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        let base = std::env::var("ProgramData").unwrap_or_else(|_| r"C:\ProgramData".to_string());
+        PathBuf::from(base)
+            .join("SmartStockInsider")
+            .join("policies.json")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/SmartStockInsider/policies.json")
+    } else {
+        PathBuf::from("/etc/smart-stock-insider/policies.json")
+    }
+}
+
+impl CompliancePolicy {
+    pub fn load() -> Self {
+        Self::resolve(
+            system_policy_path(),
+            std::env::var_os(POLICY_ENV).map(PathBuf::from),
+        )
+    }
+
+    /// The system policy if one is installed, else the `override_path` one
+    fn resolve(system: PathBuf, override_path: Option<PathBuf>) -> Self {
+        let path = if system.exists() {
+            if let Some(ignored) = override_path {
+                warn!(
+                    "Ignoring {}={:?}, the system compliance policy applies",
+                    POLICY_ENV, ignored
+                );
+            }
+            system
+        } else {
+            match override_path {
+                Some(path) if path.exists() => path,
+                _ => return Self::default(),
+            }
+        };
+        let policy = Self::from_file(path);
+        info!(
+            "Compliance policy from {:?} disables {:?}",
+            policy.source, policy.policy.disabled
+        );
+        policy
+    }
+
+    fn from_file(path: PathBuf) -> Self {
+        let policy = read_from_file(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                error!(
+                    "Unreadable compliance policy {}, disabling all subsystems: {}",
+                    path.display(),
+                    e
+                );
+                PolicyFile {
+                    name: "Unreadable policy file".to_string(),
+                    disabled: Subsystem::ALL.to_vec(),
+                    reason: format!("The policy file {} could not be read", path.display()),
+                    advice: AdviceGuardrail {
+                        mode: AdviceMode::Withhold,
+                        disclaimer: String::new(),
+                    },
+                }
+            });
+        Self {
+            source: Some(path),
+            policy,
+        }
+    }
+
+    pub fn allows(&self, subsystem: Subsystem) -> bool {
+        !self.policy.disabled.contains(&subsystem)
+    }
+
+    /// Reject commands and work that belong to a disabled subsystem
+    pub fn check(&self, subsystem: Subsystem) -> Result<(), String> {
+        if self.allows(subsystem) {
+            return Ok(());
+        }
+        let mut message = format!("{:?} is disabled by policy", subsystem);
+        if !self.policy.name.is_empty() {
+            message.push_str(&format!(" \"{}\"", self.policy.name));
+        }
+        if !self.policy.reason.is_empty() {
+            message.push_str(&format!(": {}", self.policy.reason));
+        }
+        Err(message)
+    }
+
+    pub fn admit(&self, command: &str) -> Result<(), String> {
+        match Subsystem::of_command(command) {
+            Some(subsystem) => self.check(subsystem),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubsystemStatus {
+    pub subsystem: Subsystem,
+    pub enabled: bool,
+    pub commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivePolicies {
+    pub source: Option<PathBuf>,
+    pub name: String,
+    pub reason: String,
+    pub subsystems: Vec<SubsystemStatus>,
+    pub advice: AdviceGuardrail,
+}
+
+/// The installed compliance policy and which subsystems it leaves enabled
+#[tauri::command]
+pub fn get_active_policies(policy: State<'_, CompliancePolicy>) -> Result<ActivePolicies, String> {
+    Ok(ActivePolicies {
+        source: policy.source.clone(),
+        name: policy.policy.name.clone(),
+        reason: policy.policy.reason.clone(),
+        subsystems: Subsystem::ALL
+            .into_iter()
+            .map(|subsystem| SubsystemStatus {
+                subsystem,
+                enabled: policy.allows(subsystem),
+                commands: subsystem.commands().iter().map(|c| c.to_string()).collect(),
+            })
+            .collect(),
+        advice: policy.policy.advice.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_file_disables_subsystems() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("ssi-policy-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"name": "Acme", "disabled": ["broker_sync"], "reason": "No broker connectivity",
+                "advice": {"mode": "withhold"}}"#,
+        )
+        .unwrap();
+        let policy = CompliancePolicy::from_file(path.clone());
+        assert!(policy.allows(Subsystem::ExternalAi));
+        let rejected = policy.admit("sync_broker_positions").unwrap_err();
+        assert!(rejected.contains("Acme") && rejected.contains("No broker connectivity"));
+        assert!(policy.admit("get_portfolio").is_ok());
+        assert!(policy.admit("test_delivery_channel").is_ok());
+        assert_eq!(policy.policy.advice.mode, AdviceMode::Withhold);
+
+        // A broken file fails closed
+        std::fs::write(&path, "{ not json").unwrap();
+        let broken = CompliancePolicy::from_file(path.clone());
+        assert!(Subsystem::ALL.iter().all(|s| !broken.allows(*s)));
+        assert!(broken.admit("test_delivery_channel").is_err());
+        assert!(CompliancePolicy::default().allows(Subsystem::Webhooks));
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_env_override_cannot_weaken_system_policy() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let system = dir.join(format!("ssi-policy-system-{}.json", id));
+        let permissive = dir.join(format!("ssi-policy-user-{}.json", id));
+        std::fs::write(&system, r#"{"disabled": ["external_ai", "webhooks"]}"#).unwrap();
+        std::fs::write(&permissive, r#"{"disabled": []}"#).unwrap();
+
+        let policy = CompliancePolicy::resolve(system.clone(), Some(permissive.clone()));
+        assert_eq!(policy.source.as_ref(), Some(&system));
+        assert!(!policy.allows(Subsystem::ExternalAi));
+        assert!(policy.admit("test_delivery_channel").is_err());
+
+        // Without a system policy the override is honoured
+        std::fs::remove_file(&system).unwrap();
+        let policy = CompliancePolicy::resolve(system.clone(), Some(permissive.clone()));
+        assert_eq!(policy.source, Some(permissive.clone()));
+        assert!(policy.allows(Subsystem::ExternalAi));
+        assert!(CompliancePolicy::resolve(system, None).source.is_none());
+        std::fs::remove_file(permissive).ok();
+    }
+}
@@ -0,0 +1,231 @@
+//! Transaction cost models shared by backtests and paper trading.
+//!
+//! A [`CostModel`] prices a fill: slippage moves the execution price against
+//! the order, then commission, taxes (A-share and HK stamp duty, US SEC fee)
+//! and exchange fees are charged on the resulting notional. The active model
+//! lives in settings; presets give realistic defaults per market, and stand
+//! in for the settings model until the user changes it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::Market;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CostModel {
+    /// Broker commission on notional, in basis points
+    pub commission_bps: f64,
+    /// Broker commission per share
+    pub commission_per_share: f64,
+    /// Minimum commission per fill
+    pub min_commission: f64,
+    /// Tax charged on buys, in basis points
+    pub buy_tax_bps: f64,
+    /// Tax charged on sells, in basis points
+    pub sell_tax_bps: f64,
+    /// Exchange and transfer fees on both sides, in basis points
+    pub exchange_fee_bps: f64,
+    /// Fixed adverse price move, in basis points
+    pub slippage_bps: f64,
+    /// Quoted bid/ask spread; half of it is paid on every fill
+    pub spread_bps: f64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self::preset(Market::Cn)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FillCost {
+    /// Price after slippage
+    pub price: f64,
+    pub notional: f64,
+    pub commission: f64,
+    pub taxes: f64,
+    pub fees: f64,
+    /// Cash change of the account: negative for buys
+    pub cash_flow: f64,
+}
+
+impl FillCost {
+    pub fn total_costs(&self) -> f64 {
+        self.commission + self.taxes + self.fees
+    }
+}
+
+impl CostModel {
+    /// The model to charge in `market` given the one in settings: that one
+    /// if the user changed it, else the market's preset
+    pub fn for_market(configured: CostModel, market: Market) -> Self {
+        if configured == CostModel::default() {
+            Self::preset(market)
+        } else {
+            configured
+        }
+    }
+
+    /// Typical retail costs for a market
+    pub fn preset(market: Market) -> Self {
+        match market {
+            Market::Cn => Self {
+                commission_bps: 2.5,
+                commission_per_share: 0.0,
+                min_commission: 5.0,
+                buy_tax_bps: 0.0,
+                sell_tax_bps: 5.0,
+                exchange_fee_bps: 0.1,
+                slippage_bps: 2.0,
+                spread_bps: 0.0,
+            },
+            Market::Hk => Self {
+                commission_bps: 3.0,
+                commission_per_share: 0.0,
+                min_commission: 18.0,
+                buy_tax_bps: 10.0,
+                sell_tax_bps: 10.0,
+                exchange_fee_bps: 0.855,
+                slippage_bps: 5.0,
+                spread_bps: 0.0,
+            },
+            Market::Us => Self {
+                commission_bps: 0.0,
+                commission_per_share: 0.005,
+                min_commission: 1.0,
+                buy_tax_bps: 0.0,
+                sell_tax_bps: 0.278,
+                exchange_fee_bps: 0.0,
+                slippage_bps: 1.0,
+                spread_bps: 0.0,
+            },
+        }
+    }
+
+    /// A model that charges nothing, for frictionless comparisons
+    pub fn zero() -> Self {
+        Self {
+            commission_bps: 0.0,
+            commission_per_share: 0.0,
+            min_commission: 0.0,
+            buy_tax_bps: 0.0,
+            sell_tax_bps: 0.0,
+            exchange_fee_bps: 0.0,
+            slippage_bps: 0.0,
+            spread_bps: 0.0,
+        }
+    }
+
+    /// Execution price for an order at `price` after slippage and half-spread
+    pub fn execution_price(&self, side: Side, price: f64) -> f64 {
+        let adverse = (self.slippage_bps + self.spread_bps / 2.0) / 10_000.0;
+        match side {
+            Side::Buy => price * (1.0 + adverse),
+            Side::Sell => price * (1.0 - adverse),
+        }
+    }
+
+    /// Price a fill of `quantity` shares at a reference `price`
+    pub fn fill(&self, side: Side, price: f64, quantity: f64) -> FillCost {
+        let price = self.execution_price(side, price);
+        let notional = price * quantity.abs();
+        if notional == 0.0 {
+            return FillCost {
+                price,
+                notional,
+                commission: 0.0,
+                taxes: 0.0,
+                fees: 0.0,
+                cash_flow: 0.0,
+            };
+        }
+
+        let commission = (notional * self.commission_bps / 10_000.0
+            + quantity.abs() * self.commission_per_share)
+            .max(self.min_commission);
+        let tax_bps = match side {
+            Side::Buy => self.buy_tax_bps,
+            Side::Sell => self.sell_tax_bps,
+        };
+        let taxes = notional * tax_bps / 10_000.0;
+        let fees = notional * self.exchange_fee_bps / 10_000.0;
+        let costs = commission + taxes + fees;
+        let cash_flow = match side {
+            Side::Buy => -(notional + costs),
+            Side::Sell => notional - costs,
+        };
+        FillCost {
+            price,
+            notional,
+            commission,
+            taxes,
+            fees,
+            cash_flow,
+        }
+    }
+}
+
+/// Cost model presets for every supported market
+#[tauri::command]
+pub fn get_cost_presets() -> Result<Vec<(Market, CostModel)>, String> {
+    Ok([Market::Cn, Market::Hk, Market::Us]
+        .into_iter()
+        .map(|market| (market, CostModel::preset(market)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_share_costs() {
+        let model = CostModel {
+            slippage_bps: 0.0,
+            ..CostModel::preset(Market::Cn)
+        };
+
+        // 1000 shares at 10.00: commission 2.5 bps would be 2.50, so the 5.00 minimum applies
+        let buy = model.fill(Side::Buy, 10.0, 1000.0);
+        assert_eq!(buy.commission, 5.0);
+        assert_eq!(buy.taxes, 0.0);
+        assert!((buy.fees - 0.1).abs() < 1e-9);
+        assert!((buy.cash_flow + 10_005.1).abs() < 1e-9);
+
+        // Stamp duty only on the sell side
+        let sell = model.fill(Side::Sell, 100.0, 1000.0);
+        assert!((sell.commission - 25.0).abs() < 1e-9);
+        assert!((sell.taxes - 50.0).abs() < 1e-9);
+        assert!((sell.cash_flow - (100_000.0 - 25.0 - 50.0 - 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_slippage_moves_price_against_the_order() {
+        let model = CostModel {
+            slippage_bps: 5.0,
+            spread_bps: 10.0,
+            ..CostModel::zero()
+        };
+        assert!((model.execution_price(Side::Buy, 100.0) - 100.1).abs() < 1e-9);
+        assert!((model.execution_price(Side::Sell, 100.0) - 99.9).abs() < 1e-9);
+        assert_eq!(model.fill(Side::Buy, 100.0, 0.0).cash_flow, 0.0);
+    }
+
+    #[test]
+    fn test_default_settings_model_follows_the_market() {
+        let us = CostModel::for_market(CostModel::default(), Market::Us);
+        assert_eq!(us, CostModel::preset(Market::Us));
+        let custom = CostModel {
+            commission_bps: 1.0,
+            ..CostModel::default()
+        };
+        assert_eq!(CostModel::for_market(custom.clone(), Market::Hk), custom);
+    }
+}
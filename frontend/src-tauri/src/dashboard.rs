@@ -0,0 +1,357 @@
+//! Data for user-configured dashboard tiles.
+//!
+//! A [`TileSpec`] names a metric, the symbols it covers (one, a list, a
+//! watchlist, or an index's members), a transform over the last `window`
+//! daily bars, and optionally an order and a limit, e.g. "20-day return of
+//! the CSI 300 members, best 10 first". [`get_dashboard_tile`] resolves any
+//! spec, so a new kind of widget needs no command of its own.
+//!
+//! Like the screener, tiles read only cached daily bars and make no requests.
+//! The latest quote stands in for today's bar, or is added as one, so tiles
+//! move with the market; while the snapshot clock is frozen they stop at it
+//! and quotes are left out. Symbols without cached bars are listed as
+//! missing rather than failing the tile.
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::columnar::ColumnarStore;
+use crate::db::{Database, Watchlists};
+use crate::instruments::InstrumentMaster;
+use crate::kline::{series_interval, Adjust, KlinePeriod};
+use crate::models::Market;
+use crate::quotes::{Quote, QuoteFeed};
+use crate::sessions;
+use crate::snapshot::SnapshotClock;
+use crate::symbols::normalize_symbol;
+use crate::universe::UniverseStore;
+
+/// Most bars a window may span
+const MAX_WINDOW: usize = 250;
+/// Most symbols one tile may cover
+const MAX_SYMBOLS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Close,
+    /// Percent change against the previous close
+    ChangePct,
+    Volume,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TileSource {
+    Symbol {
+        symbol: String,
+    },
+    Symbols {
+        symbols: Vec<String>,
+    },
+    Watchlist {
+        id: String,
+    },
+    /// Today's members of an index with recorded constituents
+    Index {
+        index: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// The latest value
+    #[default]
+    Latest,
+    /// The last `window` values, oldest first, for sparklines
+    Series,
+    /// Percent change across the window
+    Change,
+    Mean,
+    Min,
+    Max,
+    /// Sample standard deviation across the window
+    Stdev,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+fn default_window() -> usize {
+    20
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileSpec {
+    pub metric: Metric,
+    pub source: TileSource,
+    #[serde(default)]
+    pub transform: Transform,
+    /// Bars the transform looks back over
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// Order of the rows by value; source order when unset
+    #[serde(default)]
+    pub sort: Option<SortOrder>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl TileSpec {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_WINDOW).contains(&self.window) {
+            return Err(format!("Tile window must be 1 to {} bars", MAX_WINDOW));
+        }
+        if self.transform == Transform::Change && self.window < 2 {
+            return Err("A change needs a window of at least 2 bars".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TileRow {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub value: Option<f64>,
+    /// Values behind a `series` transform
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub series: Vec<f64>,
+    /// Unix seconds of the latest bar or quote used
+    pub as_of: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TileData {
+    pub rows: Vec<TileRow>,
+    /// Symbols with no cached daily bars
+    pub missing: Vec<String>,
+    /// Mean of the row values, e.g. a universe's average return
+    pub mean: Option<f64>,
+}
+
+/// A symbol's daily closes and volumes with their timestamps
+struct Daily {
+    timestamps: Vec<i64>,
+    closes: Vec<f64>,
+    volumes: Vec<f64>,
+}
+
+impl Daily {
+    /// Take a quote as today's bar, replacing the cached one for the same day
+    fn overlay(&mut self, market: Market, quote: &Quote) {
+        let Some(&last) = self.timestamps.last() else {
+            return;
+        };
+        if quote.timestamp <= last || quote.price <= 0.0 {
+            return;
+        }
+        if sessions::trading_day(market, quote.timestamp) == sessions::trading_day(market, last) {
+            self.timestamps.pop();
+            self.closes.pop();
+            self.volumes.pop();
+        }
+        self.timestamps.push(quote.timestamp);
+        self.closes.push(quote.price);
+        self.volumes.push(quote.volume);
+    }
+
+    fn values(&self, metric: Metric) -> Vec<f64> {
+        match metric {
+            Metric::Close => self.closes.clone(),
+            Metric::Volume => self.volumes.clone(),
+            Metric::ChangePct => self
+                .closes
+                .windows(2)
+                .map(|pair| (pair[1] / pair[0] - 1.0) * 100.0)
+                .collect(),
+        }
+    }
+}
+
+/// Apply a transform to the last `window` values
+fn transform(values: &[f64], transform: Transform, window: usize) -> (Option<f64>, Vec<f64>) {
+    let tail = &values[values.len().saturating_sub(window)..];
+    let n = tail.len() as f64;
+    let value = match transform {
+        Transform::Latest | Transform::Series => tail.last().copied(),
+        Transform::Change => match (tail.first(), tail.last()) {
+            (Some(first), Some(last)) if tail.len() >= 2 && *first != 0.0 => {
+                Some((last / first - 1.0) * 100.0)
+            }
+            _ => None,
+        },
+        Transform::Mean => (!tail.is_empty()).then(|| tail.iter().sum::<f64>() / n),
+        Transform::Min => tail.iter().copied().reduce(f64::min),
+        Transform::Max => tail.iter().copied().reduce(f64::max),
+        Transform::Stdev => (tail.len() >= 2).then(|| {
+            let mean = tail.iter().sum::<f64>() / n;
+            (tail.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        }),
+    };
+    let series = if transform == Transform::Series {
+        tail.to_vec()
+    } else {
+        Vec::new()
+    };
+    (value.filter(|v| v.is_finite()), series)
+}
+
+/// Order rows by value, those without one last, and cut them to `limit`
+fn rank(rows: &mut Vec<TileRow>, sort: Option<SortOrder>, limit: Option<usize>) {
+    if let Some(order) = sort {
+        rows.sort_by(|a, b| match (a.value, b.value) {
+            (Some(x), Some(y)) if order == SortOrder::Asc => x.total_cmp(&y),
+            (Some(x), Some(y)) => y.total_cmp(&x),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+    }
+    if let Some(limit) = limit {
+        rows.truncate(limit);
+    }
+}
+
+fn symbols(app: &AppHandle, source: &TileSource) -> Result<Vec<String>, String> {
+    let symbols = match source {
+        TileSource::Symbol { symbol } => vec![symbol.clone()],
+        TileSource::Symbols { symbols } => symbols.clone(),
+        TileSource::Watchlist { id } => {
+            let db = app.state::<Database>();
+            let conn = db.conn()?;
+            Watchlists(&conn)
+                .get(id)?
+                .items
+                .into_iter()
+                .map(|item| item.symbol)
+                .collect()
+        }
+        TileSource::Index { index } => app
+            .state::<UniverseStore>()
+            .universe(index, Local::now().date_naive())?,
+    };
+    let mut normalized = Vec::with_capacity(symbols.len());
+    for symbol in &symbols {
+        let symbol =
+            normalize_symbol(symbol).ok_or_else(|| format!("Invalid symbol: {}", symbol))?;
+        if !normalized.contains(&symbol) {
+            normalized.push(symbol);
+        }
+    }
+    if normalized.len() > MAX_SYMBOLS {
+        return Err(format!("A tile may cover at most {} symbols", MAX_SYMBOLS));
+    }
+    Ok(normalized)
+}
+
+fn daily(app: &AppHandle, symbol: &str, until: Option<i64>) -> Result<Option<Daily>, String> {
+    let store = app.state::<ColumnarStore>();
+    let interval = series_interval(KlinePeriod::Daily, Adjust::None);
+    if !store.exists(symbol, &interval) {
+        return Ok(None);
+    }
+    let bars = store.open(symbol, &interval)?;
+    let rows = bars.rows_until(until);
+    if rows == 0 {
+        return Ok(None);
+    }
+    let mut daily = Daily {
+        timestamps: bars.timestamps()[..rows].to_vec(),
+        closes: bars.closes()[..rows].to_vec(),
+        volumes: bars.volumes()[..rows].to_vec(),
+    };
+    if until.is_none() {
+        if let Some(quote) = app.state::<QuoteFeed>().get(symbol) {
+            daily.overlay(Market::of(symbol).unwrap_or(Market::Cn), &quote);
+        }
+    }
+    Ok(Some(daily))
+}
+
+fn resolve(app: &AppHandle, spec: &TileSpec) -> Result<TileData, String> {
+    spec.validate()?;
+    let until = app.state::<SnapshotClock>().frozen_at();
+    let names = app.state::<InstrumentMaster>();
+    let mut rows = Vec::new();
+    let mut missing = Vec::new();
+    for symbol in symbols(app, &spec.source)? {
+        let Some(daily) = daily(app, &symbol, until)? else {
+            missing.push(symbol);
+            continue;
+        };
+        let (value, series) = transform(&daily.values(spec.metric), spec.transform, spec.window);
+        rows.push(TileRow {
+            name: names.name(&symbol),
+            symbol,
+            value,
+            series,
+            as_of: daily.timestamps.last().copied().unwrap_or_default(),
+        });
+    }
+    let values: Vec<f64> = rows.iter().filter_map(|row| row.value).collect();
+    let mean = (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64);
+    rank(&mut rows, spec.sort, spec.limit);
+    Ok(TileData {
+        rows,
+        missing,
+        mean,
+    })
+}
+
+/// Resolve a dashboard tile from cached bars and live quotes
+#[tauri::command]
+pub async fn get_dashboard_tile(app: AppHandle, spec: TileSpec) -> Result<TileData, String> {
+    tauri::async_runtime::spawn_blocking(move || resolve(&app, &spec))
+        .await
+        .map_err(|e| format!("Failed to resolve dashboard tile: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transforms_and_ranking() {
+        let closes = [10.0, 11.0, 12.1, 11.0, 12.0];
+        assert_eq!(
+            transform(&closes, Transform::Latest, 20),
+            (Some(12.0), vec![])
+        );
+        let (change, _) = transform(&closes, Transform::Change, 3);
+        assert!((change.unwrap() - (12.0 / 12.1 - 1.0) * 100.0).abs() < 1e-9);
+        assert_eq!(transform(&closes, Transform::Max, 2).0, Some(12.0));
+        assert_eq!(
+            transform(&closes, Transform::Series, 2),
+            (Some(12.0), vec![11.0, 12.0])
+        );
+        assert_eq!(transform(&closes[..1], Transform::Stdev, 5).0, None);
+
+        let row = |symbol: &str, value| TileRow {
+            symbol: symbol.to_string(),
+            name: None,
+            value,
+            series: Vec::new(),
+            as_of: 0,
+        };
+        let mut rows = vec![
+            row("SH600519", Some(1.5)),
+            row("SZ000858", None),
+            row("SZ300750", Some(4.0)),
+        ];
+        rank(&mut rows, Some(SortOrder::Desc), Some(2));
+        let ranked: Vec<&str> = rows.iter().map(|r| r.symbol.as_str()).collect();
+        assert_eq!(ranked, ["SZ300750", "SH600519"]);
+
+        let spec: TileSpec = serde_json::from_str(
+            r#"{"metric":"close","source":{"kind":"index","index":"000300"},"transform":"change"}"#,
+        )
+        .unwrap();
+        assert_eq!(spec.window, 20);
+        assert!(spec.validate().is_ok());
+    }
+}
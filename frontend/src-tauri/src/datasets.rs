@@ -0,0 +1,354 @@
+//! Self-description of the data stored on this machine.
+//!
+//! `describe_datasets` lists every local dataset with the symbols it covers,
+//! its date range, bar intervals, record count, last update and where the
+//! data came from. The data-coverage view renders it directly, and the AI
+//! layer hands it to models as the description of what they can ask about;
+//! `table` names the matching table of the [SQL console](crate::sql_console)
+//! where there is one.
+
+use std::collections::BTreeSet;
+use std::time::SystemTime;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+
+use crate::backtest_runs::{BacktestRun, RunStore};
+use crate::columnar::ColumnarStore;
+use crate::documents::{DocumentStore, DocumentSummary};
+use crate::fields::select;
+use crate::instruments::InstrumentMaster;
+use crate::news::{NewsItem, NewsStore};
+use crate::portfolio::{Portfolio, PortfolioStore};
+use crate::snapshot::SnapshotClock;
+use crate::universe::UniverseStore;
+
+/// Coverage of one stored bar series
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesCoverage {
+    pub symbol: String,
+    pub interval: String,
+    pub rows: usize,
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatasetInfo {
+    pub name: String,
+    pub description: String,
+    /// Provider or origin of the data
+    pub source: String,
+    /// SQL console table holding the data, if any
+    pub table: Option<String>,
+    pub records: usize,
+    pub symbols: Vec<String>,
+    pub intervals: Vec<String>,
+    /// First and last day covered, `YYYY-MM-DD`
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub last_updated: Option<String>,
+    /// Per-series coverage, for bar data
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub series: Vec<SeriesCoverage>,
+}
+
+fn day(ts: i64) -> Option<String> {
+    DateTime::from_timestamp(ts, 0).map(|t| t.format("%Y-%m-%d").to_string())
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
+}
+
+/// Bar series in the columnar store, cut off at the snapshot clock if frozen
+pub fn describe_bars(store: &ColumnarStore, until: Option<i64>) -> DatasetInfo {
+    let mut info = DatasetInfo {
+        name: "bars".to_string(),
+        description: "OHLCV price bars per symbol and interval".to_string(),
+        source: "columnar store".to_string(),
+        ..Default::default()
+    };
+    let mut symbols = BTreeSet::new();
+    let mut intervals = BTreeSet::new();
+    let mut updated = None;
+    for (symbol, interval) in store.series() {
+        let Ok(bars) = store.open(&symbol, &interval) else {
+            continue;
+        };
+        let rows = bars.rows_until(until);
+        let ts = &bars.timestamps()[..rows];
+        info.records += rows;
+        updated = updated.max(store.modified(&symbol, &interval));
+        symbols.insert(symbol.clone());
+        intervals.insert(interval.clone());
+        info.series.push(SeriesCoverage {
+            symbol,
+            interval,
+            rows,
+            start: ts.first().and_then(|t| day(*t)),
+            end: ts.last().and_then(|t| day(*t)),
+        });
+    }
+    info.start = info.series.iter().filter_map(|s| s.start.clone()).min();
+    info.end = info.series.iter().filter_map(|s| s.end.clone()).max();
+    info.symbols = symbols.into_iter().collect();
+    info.intervals = intervals.into_iter().collect();
+    info.last_updated = updated.map(format_time);
+    info
+}
+
+pub fn describe_news(items: &[NewsItem]) -> DatasetInfo {
+    let sources: BTreeSet<&str> = items.iter().map(|n| n.source.as_str()).collect();
+    let symbols: BTreeSet<&String> = items.iter().flat_map(|n| &n.symbols).collect();
+    DatasetInfo {
+        name: "news".to_string(),
+        description: "Fast news items with linked symbols and watch tags".to_string(),
+        source: sources.into_iter().collect::<Vec<_>>().join(", "),
+        table: Some("news".to_string()),
+        records: items.len(),
+        symbols: symbols.into_iter().cloned().collect(),
+        start: items.iter().map(|n| n.published_at).min().and_then(day),
+        end: items.iter().map(|n| n.published_at).max().and_then(day),
+        last_updated: items.iter().map(|n| n.fetched_at.clone()).max(),
+        ..Default::default()
+    }
+}
+
+pub fn describe_portfolio(portfolio: &Portfolio) -> Vec<DatasetInfo> {
+    let holdings: BTreeSet<&String> = portfolio
+        .manual
+        .iter()
+        .map(|h| &h.symbol)
+        .chain(
+            portfolio
+                .broker_accounts
+                .iter()
+                .flat_map(|a| a.positions.iter().map(|p| &p.symbol)),
+        )
+        .collect();
+    let brokers: BTreeSet<&str> = portfolio
+        .broker_accounts
+        .iter()
+        .map(|a| a.broker.as_str())
+        .collect();
+    let mut source = vec!["manual"];
+    source.extend(brokers);
+    let traded: BTreeSet<&String> = portfolio
+        .transactions
+        .iter()
+        .map(|t| &t.entry.symbol)
+        .filter(|s| !s.is_empty())
+        .collect();
+    vec![
+        DatasetInfo {
+            name: "holdings".to_string(),
+            description: "Manual holdings and positions synced from brokers".to_string(),
+            source: source.join(", "),
+            table: Some("holdings".to_string()),
+            records: portfolio.manual.len()
+                + portfolio
+                    .broker_accounts
+                    .iter()
+                    .map(|a| a.positions.len())
+                    .sum::<usize>(),
+            symbols: holdings.into_iter().cloned().collect(),
+            last_updated: portfolio
+                .manual
+                .iter()
+                .map(|h| h.updated_at.clone())
+                .chain(
+                    portfolio
+                        .broker_accounts
+                        .iter()
+                        .map(|a| a.synced_at.clone()),
+                )
+                .max(),
+            ..Default::default()
+        },
+        DatasetInfo {
+            name: "transactions".to_string(),
+            description: "Trades, cash movements, dividends and fees".to_string(),
+            source: "manual and imported".to_string(),
+            table: Some("transactions".to_string()),
+            records: portfolio.transactions.len(),
+            symbols: traded.into_iter().cloned().collect(),
+            start: portfolio
+                .transactions
+                .iter()
+                .map(|t| t.entry.date.clone())
+                .min(),
+            end: portfolio
+                .transactions
+                .iter()
+                .map(|t| t.entry.date.clone())
+                .max(),
+            last_updated: portfolio
+                .transactions
+                .iter()
+                .map(|t| t.recorded_at.clone())
+                .max(),
+            ..Default::default()
+        },
+    ]
+}
+
+pub fn describe_backtest_runs(runs: &[BacktestRun]) -> DatasetInfo {
+    let symbols: BTreeSet<&String> = runs.iter().flat_map(|r| &r.symbols).collect();
+    let intervals: BTreeSet<&String> = runs.iter().map(|r| &r.interval).collect();
+    DatasetInfo {
+        name: "backtest_runs".to_string(),
+        description: "Recorded backtest runs with their headline metrics".to_string(),
+        source: "backtester".to_string(),
+        table: Some("backtest_runs".to_string()),
+        records: runs.len(),
+        symbols: symbols.into_iter().cloned().collect(),
+        intervals: intervals.into_iter().cloned().collect(),
+        start: runs.iter().filter_map(|r| r.start).min().and_then(day),
+        end: runs.iter().filter_map(|r| r.end).max().and_then(day),
+        last_updated: runs.iter().map(|r| r.created_at.clone()).max(),
+        ..Default::default()
+    }
+}
+
+pub fn describe_documents(documents: &[DocumentSummary]) -> DatasetInfo {
+    let symbols: BTreeSet<&String> = documents.iter().filter_map(|d| d.symbol.as_ref()).collect();
+    DatasetInfo {
+        name: "research_documents".to_string(),
+        description: "Imported reports, transcripts and OCR'd statements".to_string(),
+        source: "user imports".to_string(),
+        records: documents.len(),
+        symbols: symbols.into_iter().cloned().collect(),
+        last_updated: documents.iter().map(|d| d.created_at.clone()).max(),
+        ..Default::default()
+    }
+}
+
+fn describe_reference(app: &AppHandle) -> Vec<DatasetInfo> {
+    let master = app.state::<InstrumentMaster>();
+    let instruments = master.instruments();
+    let mut datasets = vec![DatasetInfo {
+        name: "instruments".to_string(),
+        description: "Listed A-share instruments and their names".to_string(),
+        source: "eastmoney".to_string(),
+        records: instruments.len(),
+        symbols: instruments.into_iter().map(|i| i.symbol).collect(),
+        last_updated: master.refreshed_on().map(|d| d.to_string()),
+        ..Default::default()
+    }];
+    for index in app.state::<UniverseStore>().coverage() {
+        datasets.push(DatasetInfo {
+            name: format!("index_constituents:{}", index.index),
+            description: format!("Point-in-time constituents of {}", index.index),
+            source: "recorded snapshots".to_string(),
+            records: index.symbols.len(),
+            symbols: index.symbols,
+            start: index.first_snapshot.map(|d: NaiveDate| d.to_string()),
+            end: index.last_snapshot.map(|d| d.to_string()),
+            last_updated: index.last_snapshot.map(|d| d.to_string()),
+            ..Default::default()
+        });
+    }
+    datasets
+}
+
+/// Metadata about every locally stored dataset
+#[tauri::command]
+pub fn describe_datasets(
+    app: AppHandle,
+    store: State<'_, ColumnarStore>,
+    clock: State<'_, SnapshotClock>,
+    fields: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let until = clock.frozen_at();
+    let mut datasets = vec![describe_bars(&store, until)];
+    datasets.extend(describe_portfolio(&app.state::<PortfolioStore>().get()));
+    datasets.push(describe_news(
+        &app.state::<NewsStore>().latest(until, usize::MAX),
+    ));
+    datasets.push(describe_documents(&app.state::<DocumentStore>().list(None)));
+    datasets.push(describe_backtest_runs(&app.state::<RunStore>().list(None)));
+    datasets.extend(describe_reference(&app));
+    select(&datasets, fields.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Bar;
+
+    fn bars(start: i64, n: i64) -> Vec<Bar> {
+        (0..n)
+            .map(|i| Bar {
+                timestamp: start + i * 86_400,
+                open: 1.0,
+                high: 1.0,
+                low: 1.0,
+                close: 1.0,
+                volume: 1.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bars_coverage() {
+        let dir = std::env::temp_dir().join(format!("ssi-datasets-{}", std::process::id()));
+        let store = ColumnarStore::new(dir.clone());
+        // 2024-01-02 and 2024-03-01 (UTC)
+        store
+            .write("SH600519", "1d", &bars(1_704_153_600, 10))
+            .unwrap();
+        store
+            .write("SZ000001", "1d", &bars(1_709_251_200, 5))
+            .unwrap();
+        store
+            .write("SH600519", "1m", &bars(1_709_251_200, 3))
+            .unwrap();
+
+        let info = describe_bars(&store, None);
+        assert_eq!(info.records, 18);
+        assert_eq!(info.symbols, vec!["SH600519", "SZ000001"]);
+        assert_eq!(info.intervals, vec!["1d", "1m"]);
+        assert_eq!(info.start.as_deref(), Some("2024-01-02"));
+        assert_eq!(info.end.as_deref(), Some("2024-03-05"));
+        assert_eq!(info.series.len(), 3);
+        assert!(info.last_updated.is_some());
+
+        // A frozen snapshot clock hides later bars
+        let frozen = describe_bars(&store, Some(1_704_153_600 + 86_400));
+        assert_eq!(frozen.records, 2);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_news_coverage() {
+        let item = |id: &str, published_at: i64, symbols: &[&str]| NewsItem {
+            id: id.to_string(),
+            source: "eastmoney".to_string(),
+            title: String::new(),
+            summary: String::new(),
+            url: String::new(),
+            published_at,
+            fetched_at: format!("2024-03-0{} 00:00:00 UTC", id),
+            tags: Vec::new(),
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+        };
+        let info = describe_news(&[
+            item("1", 1_704_153_600, &["SH600519"]),
+            item("2", 1_709_251_200, &["SH600519", "SZ000001"]),
+        ]);
+        assert_eq!(info.records, 2);
+        assert_eq!(info.symbols, vec!["SH600519", "SZ000001"]);
+        assert_eq!(info.start.as_deref(), Some("2024-01-02"));
+        assert_eq!(info.end.as_deref(), Some("2024-03-01"));
+        assert_eq!(
+            info.last_updated.as_deref(),
+            Some("2024-03-02 00:00:00 UTC")
+        );
+        assert_eq!(info.table.as_deref(), Some("news"));
+    }
+}
@@ -0,0 +1,2576 @@
+//! SQLite database for user data: watchlists, portfolios, alerts, notes,
+//! formulas, portfolio alerts, explanations of fired triggers and the
+//! notification center, along with the cache of downloaded financial
+//! statements.
+//!
+//! Everything lives in one `smart-stock-insider.db` file in the app data
+//! dir. Connections are opened in WAL mode with foreign keys on and kept in
+//! a small pool, so concurrent commands don't reopen the file or block each
+//! other while reading. The schema is built by numbered migrations, each
+//! applied once inside a transaction and recorded in `schema_migrations`;
+//! a database from an older version is brought up to date when it opens.
+//! Repositories wrap a borrowed connection and map rows to typed records.
+//! Deleted watchlists, alerts and notes are only marked with `deleted_at`
+//! and can be restored (see [`undo`](crate::undo)) until the retention
+//! window passes, after which the next delete of their kind purges them.
+//! Records older versions kept in JSON files are imported once, after which
+//! the file is renamed so it isn't read again.
+
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::explain::Explanation;
+use crate::financials::FinancialStatements;
+use crate::formulas::Formula;
+use crate::merge::Mergeable;
+use crate::models::Market;
+use crate::notifications::{
+    Notification, NotificationFilter, NotificationState, StoredNotification,
+};
+use crate::polling::RefreshProfile;
+use crate::portfolio::PortfolioStore;
+use crate::portfolio_alerts::PortfolioAlert;
+use crate::settings::SettingsStore;
+use crate::symbols::symbol_key;
+use crate::undo::{retention_cutoff, UndoAction, UndoLog};
+use crate::utils::{ensure_dir_exists, generate_id, get_timestamp, read_from_file};
+
+pub const DB_FILE: &str = "smart-stock-insider.db";
+/// Idle connections kept open for reuse
+const MAX_IDLE: usize = 4;
+/// Notifications kept in the center; the oldest beyond this are dropped
+const MAX_NOTIFICATIONS: usize = 1000;
+
+struct Migration {
+    version: i64,
+    name: &'static str,
+    sql: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        sql: "
+CREATE TABLE watchlists (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    position INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE watchlist_items (
+    watchlist_id TEXT NOT NULL REFERENCES watchlists(id) ON DELETE CASCADE,
+    symbol TEXT NOT NULL,
+    group_name TEXT NOT NULL DEFAULT '',
+    position INTEGER NOT NULL,
+    added_at TEXT NOT NULL,
+    PRIMARY KEY (watchlist_id, symbol)
+);
+CREATE TABLE portfolios (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    base_currency TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE alerts (
+    id TEXT PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    threshold REAL NOT NULL,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    note TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL,
+    triggered_at TEXT
+);
+CREATE INDEX alerts_symbol ON alerts(symbol);
+CREATE TABLE notes (
+    id TEXT PRIMARY KEY,
+    symbol TEXT,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE INDEX notes_symbol ON notes(symbol);
+",
+    },
+    Migration {
+        version: 2,
+        name: "trigger explanations",
+        sql: "
+CREATE TABLE trigger_explanations (
+    event_id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    source_id TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    conditions TEXT NOT NULL,
+    as_of INTEGER NOT NULL,
+    created_at TEXT NOT NULL
+);
+",
+    },
+    Migration {
+        version: 3,
+        name: "notification center",
+        sql: "
+CREATE TABLE notifications (
+    id TEXT PRIMARY KEY,
+    category TEXT NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    actions TEXT NOT NULL,
+    state TEXT NOT NULL DEFAULT 'unread',
+    created_at TEXT NOT NULL,
+    read_at TEXT
+);
+CREATE INDEX notifications_state ON notifications(state, created_at);
+",
+    },
+    Migration {
+        version: 4,
+        name: "alert snooze and re-arm",
+        sql: "
+ALTER TABLE alerts ADD COLUMN rearm TEXT NOT NULL DEFAULT '{\"mode\":\"once\"}';
+ALTER TABLE alerts ADD COLUMN armed INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE alerts ADD COLUMN snoozed_until INTEGER;
+",
+    },
+    Migration {
+        version: 5,
+        name: "watchlist refresh profiles",
+        sql: "
+ALTER TABLE watchlists ADD COLUMN market TEXT;
+ALTER TABLE watchlists ADD COLUMN refresh_profile TEXT NOT NULL DEFAULT 'standard';
+",
+    },
+    Migration {
+        version: 6,
+        name: "alert windows and trigger history",
+        sql: "
+ALTER TABLE alerts ADD COLUMN window_len INTEGER;
+CREATE TABLE alert_triggers (
+    id TEXT PRIMARY KEY,
+    alert_id TEXT NOT NULL REFERENCES alerts(id) ON DELETE CASCADE,
+    symbol TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    threshold REAL NOT NULL,
+    value REAL NOT NULL,
+    price REAL NOT NULL,
+    fired_at TEXT NOT NULL
+);
+CREATE INDEX alert_triggers_alert ON alert_triggers(alert_id, fired_at);
+",
+    },
+    Migration {
+        version: 7,
+        name: "alert delivery channels",
+        sql: "
+ALTER TABLE alerts ADD COLUMN channels TEXT NOT NULL DEFAULT '[]';
+",
+    },
+    Migration {
+        version: 8,
+        name: "formulas, portfolio alerts and financial statements",
+        sql: "
+CREATE TABLE formulas (
+    name TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    source TEXT NOT NULL
+);
+CREATE TABLE portfolio_alerts (
+    id TEXT PRIMARY KEY,
+    condition TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    armed INTEGER NOT NULL DEFAULT 1,
+    peak REAL,
+    channels TEXT NOT NULL DEFAULT '[]',
+    note TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL,
+    triggered_at TEXT
+);
+CREATE TABLE financial_statements (
+    symbol TEXT PRIMARY KEY,
+    statements TEXT NOT NULL,
+    fetched_at INTEGER NOT NULL
+);
+",
+    },
+    Migration {
+        version: 9,
+        name: "soft deletion",
+        sql: "
+ALTER TABLE watchlists ADD COLUMN deleted_at TEXT;
+ALTER TABLE alerts ADD COLUMN deleted_at TEXT;
+ALTER TABLE notes ADD COLUMN deleted_at TEXT;
+",
+    },
+];
+
+fn failed(what: &str) -> impl FnOnce(rusqlite::Error) -> String + '_ {
+    move |e| format!("Failed to {}: {}", what, e)
+}
+
+/// A unit enum variant as its serde name, for text columns
+fn enum_text<T: Serialize>(value: T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(text)) => text,
+        _ => String::new(),
+    }
+}
+
+fn text_enum<T: DeserializeOwned>(column: usize, text: String) -> rusqlite::Result<T> {
+    serde_json::from_value(serde_json::Value::String(text)).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+/// Apply migrations the database hasn't seen, returning its schema version
+pub fn migrate(conn: &mut Connection) -> Result<i64, String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+    )
+    .map_err(failed("create migrations table"))?;
+    let applied: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(failed("read schema version"))?;
+    let mut current = applied;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > applied) {
+        let tx = conn.transaction().map_err(failed("start migration"))?;
+        tx.execute_batch(migration.sql)
+            .map_err(|e| format!("Migration {} failed: {}", migration.version, e))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.name, get_timestamp()],
+        )
+        .map_err(failed("record migration"))?;
+        tx.commit().map_err(failed("commit migration"))?;
+        info!(
+            "Applied database migration {}: {}",
+            migration.version, migration.name
+        );
+        current = migration.version;
+    }
+    Ok(current)
+}
+
+pub struct Database {
+    path: PathBuf,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl Database {
+    /// Open the database file, creating and migrating it as needed
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            ensure_dir_exists(dir)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+        let mut conn = connect(&path)?;
+        migrate(&mut conn)?;
+        Ok(Self {
+            path,
+            idle: Mutex::new(vec![conn]),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A connection from the pool, returned to it when dropped
+    pub fn conn(&self) -> Result<PooledConnection<'_>, String> {
+        let conn = match self.idle.lock().unwrap().pop() {
+            Some(conn) => conn,
+            None => connect(&self.path)?,
+        };
+        Ok(PooledConnection {
+            db: self,
+            conn: Some(conn),
+        })
+    }
+
+    /// Import the formulas, portfolio alerts and financial statements older
+    /// versions kept in JSON files under `data_dir`; a file that fails to
+    /// import is left in place and tried again on the next start
+    pub fn import_legacy(&self, data_dir: &Path) -> Result<(), String> {
+        let conn = self.conn()?;
+        let results = [
+            import_file(
+                &conn,
+                &data_dir.join("formulas.json"),
+                |conn, formulas: BTreeMap<String, Formula>| {
+                    formulas
+                        .values()
+                        .try_for_each(|formula| SavedFormulas(conn).save(formula))
+                },
+            ),
+            import_file(
+                &conn,
+                &data_dir.join("portfolio_alerts.json"),
+                |conn, alerts: Vec<PortfolioAlert>| {
+                    alerts
+                        .iter()
+                        .try_for_each(|alert| PortfolioAlerts(conn).create(alert).map(drop))
+                },
+            ),
+            import_file(
+                &conn,
+                &data_dir.join("financials.json"),
+                |conn, cached: BTreeMap<String, FinancialStatements>| {
+                    cached
+                        .values()
+                        .try_for_each(|statements| Financials(conn).save(statements))
+                },
+            ),
+        ];
+        for e in results.into_iter().filter_map(Result::err) {
+            warn!("{}", e);
+        }
+        Ok(())
+    }
+}
+
+/// Hand the records of a JSON file to `import` in one transaction, then
+/// rename the file so they are imported once
+fn import_file<T: DeserializeOwned>(
+    conn: &Connection,
+    path: &Path,
+    import: impl FnOnce(&Connection, T) -> Result<(), String>,
+) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let content = read_from_file(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let records =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))?;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(failed("start import"))?;
+    import(&tx, records).map_err(|e| format!("Failed to import {:?}: {}", path, e))?;
+    tx.commit().map_err(failed("commit import"))?;
+    let mut imported = path.as_os_str().to_owned();
+    imported.push(".imported");
+    std::fs::rename(path, &imported)
+        .map_err(|e| format!("Failed to rename imported {:?}: {}", path, e))?;
+    info!("Imported {:?} into the database", path);
+    Ok(())
+}
+
+fn connect(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(failed("open database"))?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA foreign_keys = ON;
+         PRAGMA busy_timeout = 5000;",
+    )
+    .map_err(failed("configure database connection"))?;
+    Ok(conn)
+}
+
+pub struct PooledConnection<'a> {
+    db: &'a Database,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        let mut idle = self.db.idle.lock().unwrap();
+        if idle.len() < MAX_IDLE {
+            idle.extend(self.conn.take());
+        }
+    }
+}
+
+/// Turn "no row changed" into a not-found error
+fn expect_changed(changed: usize, what: &str, id: &str) -> Result<(), String> {
+    if changed == 0 {
+        Err(format!("{} not found: {}", what, id))
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that a new ordering lists exactly the current members
+fn same_members(current: &[String], ordered: &[String], what: &str) -> Result<(), String> {
+    let mut expected = current.to_vec();
+    let mut given = ordered.to_vec();
+    expected.sort();
+    given.sort();
+    if expected != given {
+        return Err(format!(
+            "The new order must list every {} exactly once",
+            what
+        ));
+    }
+    Ok(())
+}
+
+fn required(value: &str, what: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("{} is required", what));
+    }
+    Ok(value.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchlistItem {
+    pub symbol: String,
+    /// Group within the watchlist; empty when ungrouped
+    pub group: String,
+    pub position: i64,
+    pub added_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Watchlist {
+    pub id: String,
+    pub name: String,
+    pub position: i64,
+    pub items: Vec<WatchlistItem>,
+    /// Market whose sessions time the refresh profile; A-shares when unset
+    pub market: Option<Market>,
+    pub refresh_profile: RefreshProfile,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Mergeable for Watchlist {
+    const KIND: &'static str = "watchlist";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn modified_at(&self) -> &str {
+        &self.updated_at
+    }
+
+    /// Names are unique, so a list of the same name is the same list
+    fn fingerprint(&self) -> String {
+        self.name.clone()
+    }
+}
+
+pub struct Watchlists<'a>(pub &'a Connection);
+
+impl Watchlists<'_> {
+    fn items(&self, id: &str) -> Result<Vec<WatchlistItem>, String> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT symbol, group_name, position, added_at FROM watchlist_items
+                 WHERE watchlist_id = ?1 ORDER BY position",
+            )
+            .map_err(failed("read watchlist"))?;
+        let items = statement
+            .query_map([id], |row| {
+                Ok(WatchlistItem {
+                    symbol: row.get(0)?,
+                    group: row.get(1)?,
+                    position: row.get(2)?,
+                    added_at: row.get(3)?,
+                })
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(failed("read watchlist"))?;
+        Ok(items)
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Watchlist> {
+        Ok(Watchlist {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            position: row.get(2)?,
+            items: Vec::new(),
+            market: row
+                .get::<_, Option<String>>(5)?
+                .map(|market| text_enum(5, market))
+                .transpose()?,
+            refresh_profile: text_enum(6, row.get(6)?)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<Watchlist>, String> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT id, name, position, created_at, updated_at, market, refresh_profile
+                 FROM watchlists WHERE deleted_at IS NULL ORDER BY position",
+            )
+            .map_err(failed("list watchlists"))?;
+        let watchlists: Vec<Watchlist> = statement
+            .query_map([], Self::from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list watchlists"))?;
+        watchlists
+            .into_iter()
+            .map(|mut watchlist| {
+                watchlist.items = self.items(&watchlist.id)?;
+                Ok(watchlist)
+            })
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Result<Watchlist, String> {
+        let mut watchlist = self
+            .0
+            .query_row(
+                "SELECT id, name, position, created_at, updated_at, market, refresh_profile
+                 FROM watchlists WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                Self::from_row,
+            )
+            .optional()
+            .map_err(failed("read watchlist"))?
+            .ok_or_else(|| format!("Watchlist not found: {}", id))?;
+        watchlist.items = self.items(id)?;
+        Ok(watchlist)
+    }
+
+    /// Purge a trashed watchlist holding `name`, which a live one now takes
+    fn free_name(&self, name: &str) -> Result<(), String> {
+        self.0
+            .execute(
+                "DELETE FROM watchlists WHERE name = ?1 AND deleted_at IS NOT NULL",
+                [name],
+            )
+            .map_err(failed("purge watchlist"))?;
+        Ok(())
+    }
+
+    pub fn create(&self, name: &str) -> Result<Watchlist, String> {
+        let name = required(name, "Watchlist name")?;
+        self.free_name(&name)?;
+        let id = generate_id("watchlist");
+        let now = get_timestamp();
+        self.0
+            .execute(
+                "INSERT INTO watchlists (id, name, position, created_at, updated_at)
+                 VALUES (?1, ?2, (SELECT COALESCE(MAX(position), -1) + 1 FROM watchlists), ?3, ?3)",
+                params![id, name, now],
+            )
+            .map_err(failed("create watchlist"))?;
+        self.get(&id)
+    }
+
+    /// Write a watchlist from elsewhere as it is, ids and timestamps
+    /// included; a new one goes last, a trashed one is restored
+    pub fn upsert(&self, watchlist: &Watchlist) -> Result<Watchlist, String> {
+        self.free_name(&watchlist.name)?;
+        let tx = self
+            .0
+            .unchecked_transaction()
+            .map_err(failed("import watchlist"))?;
+        tx.execute(
+            "INSERT INTO watchlists
+                 (id, name, position, created_at, updated_at, market, refresh_profile)
+             VALUES (?1, ?2, (SELECT COALESCE(MAX(position), -1) + 1 FROM watchlists),
+                 ?3, ?4, ?5, ?6)
+             ON CONFLICT(id) DO UPDATE SET name = ?2, updated_at = ?4, market = ?5,
+                 refresh_profile = ?6, deleted_at = NULL",
+            params![
+                watchlist.id,
+                watchlist.name,
+                watchlist.created_at,
+                watchlist.updated_at,
+                watchlist.market.map(enum_text),
+                enum_text(watchlist.refresh_profile)
+            ],
+        )
+        .map_err(failed("import watchlist"))?;
+        tx.execute(
+            "DELETE FROM watchlist_items WHERE watchlist_id = ?1",
+            [&watchlist.id],
+        )
+        .map_err(failed("import watchlist"))?;
+        for item in &watchlist.items {
+            tx.execute(
+                "INSERT INTO watchlist_items (watchlist_id, symbol, group_name, position, added_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    watchlist.id,
+                    item.symbol,
+                    item.group,
+                    item.position,
+                    item.added_at
+                ],
+            )
+            .map_err(failed("import watchlist"))?;
+        }
+        tx.commit().map_err(failed("import watchlist"))?;
+        self.get(&watchlist.id)
+    }
+
+    pub fn rename(&self, id: &str, name: &str) -> Result<Watchlist, String> {
+        let name = required(name, "Watchlist name")?;
+        self.free_name(&name)?;
+        let changed = self
+            .0
+            .execute(
+                "UPDATE watchlists SET name = ?2, updated_at = ?3
+                 WHERE id = ?1 AND deleted_at IS NULL",
+                params![id, name, get_timestamp()],
+            )
+            .map_err(failed("rename watchlist"))?;
+        expect_changed(changed, "Watchlist", id)?;
+        self.get(id)
+    }
+
+    pub fn set_refresh(
+        &self,
+        id: &str,
+        market: Option<Market>,
+        profile: RefreshProfile,
+    ) -> Result<Watchlist, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE watchlists SET market = ?2, refresh_profile = ?3, updated_at = ?4
+                 WHERE id = ?1 AND deleted_at IS NULL",
+                params![
+                    id,
+                    market.map(enum_text),
+                    enum_text(profile),
+                    get_timestamp()
+                ],
+            )
+            .map_err(failed("update watchlist refresh"))?;
+        expect_changed(changed, "Watchlist", id)?;
+        self.get(id)
+    }
+
+    /// Move a watchlist to the trash, returning it as it was
+    pub fn delete(&self, id: &str) -> Result<Watchlist, String> {
+        let watchlist = self.get(id)?;
+        self.0
+            .execute(
+                "DELETE FROM watchlists WHERE deleted_at < ?1",
+                [retention_cutoff()],
+            )
+            .map_err(failed("purge watchlists"))?;
+        self.0
+            .execute(
+                "UPDATE watchlists SET deleted_at = ?2 WHERE id = ?1",
+                params![id, get_timestamp()],
+            )
+            .map_err(failed("delete watchlist"))?;
+        Ok(watchlist)
+    }
+
+    /// Bring a watchlist back from the trash, items included
+    pub fn restore(&self, id: &str) -> Result<Watchlist, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE watchlists SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                [id],
+            )
+            .map_err(failed("restore watchlist"))?;
+        expect_changed(changed, "Trashed watchlist", id)?;
+        self.get(id)
+    }
+
+    /// Put watchlists in the given order, as after a drag and drop
+    pub fn reorder(&self, ids: &[String]) -> Result<Vec<Watchlist>, String> {
+        let current: Vec<String> = self.list()?.into_iter().map(|w| w.id).collect();
+        same_members(&current, ids, "watchlist")?;
+        let tx = self
+            .0
+            .unchecked_transaction()
+            .map_err(failed("reorder watchlists"))?;
+        for (position, id) in ids.iter().enumerate() {
+            tx.execute(
+                "UPDATE watchlists SET position = ?2 WHERE id = ?1",
+                params![id, position as i64],
+            )
+            .map_err(failed("reorder watchlists"))?;
+        }
+        tx.commit().map_err(failed("reorder watchlists"))?;
+        self.list()
+    }
+
+    /// Rewrite item positions to follow `symbols` and touch the watchlist
+    fn write_order(&self, id: &str, symbols: &[String]) -> Result<Watchlist, String> {
+        let tx = self
+            .0
+            .unchecked_transaction()
+            .map_err(failed("update watchlist"))?;
+        for (position, symbol) in symbols.iter().enumerate() {
+            tx.execute(
+                "UPDATE watchlist_items SET position = ?3 WHERE watchlist_id = ?1 AND symbol = ?2",
+                params![id, symbol, position as i64],
+            )
+            .map_err(failed("update watchlist"))?;
+        }
+        tx.execute(
+            "UPDATE watchlists SET updated_at = ?2 WHERE id = ?1",
+            params![id, get_timestamp()],
+        )
+        .map_err(failed("update watchlist"))?;
+        tx.commit().map_err(failed("update watchlist"))?;
+        self.get(id)
+    }
+
+    /// Append a symbol, optionally into a group; market suffixes such as
+    /// `00700.HK` are stored in canonical form
+    pub fn add_symbol(&self, id: &str, symbol: &str, group: &str) -> Result<Watchlist, String> {
+        let watchlist = self.get(id)?;
+        let symbol = required(symbol, "Symbol")?;
+        let symbol = symbol_key(&symbol);
+        if watchlist.items.iter().any(|item| item.symbol == symbol) {
+            return Err(format!("{} is already in {}", symbol, watchlist.name));
+        }
+        self.0
+            .execute(
+                "INSERT INTO watchlist_items (watchlist_id, symbol, group_name, position, added_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    id,
+                    symbol,
+                    group.trim(),
+                    watchlist.items.len() as i64,
+                    get_timestamp()
+                ],
+            )
+            .map_err(failed("add symbol"))?;
+        let order: Vec<String> = watchlist
+            .items
+            .into_iter()
+            .map(|item| item.symbol)
+            .chain([symbol])
+            .collect();
+        self.write_order(id, &order)
+    }
+
+    pub fn remove_symbol(&self, id: &str, symbol: &str) -> Result<Watchlist, String> {
+        let symbol = symbol_key(symbol);
+        let changed = self
+            .0
+            .execute(
+                "DELETE FROM watchlist_items WHERE watchlist_id = ?1 AND symbol = ?2",
+                params![id, symbol],
+            )
+            .map_err(failed("remove symbol"))?;
+        expect_changed(changed, "Watchlist symbol", &symbol)?;
+        let order: Vec<String> = self
+            .items(id)?
+            .into_iter()
+            .map(|item| item.symbol)
+            .collect();
+        self.write_order(id, &order)
+    }
+
+    /// Put the symbols in the given order; it must list each exactly once
+    pub fn reorder_symbols(&self, id: &str, symbols: &[String]) -> Result<Watchlist, String> {
+        let symbols: Vec<String> = symbols.iter().map(|s| symbol_key(s)).collect();
+        let current: Vec<String> = self
+            .get(id)?
+            .items
+            .into_iter()
+            .map(|item| item.symbol)
+            .collect();
+        same_members(&current, &symbols, "symbol")?;
+        self.write_order(id, &symbols)
+    }
+
+    /// Move a symbol into a group, at `index` among that group's symbols or at its end
+    pub fn move_to_group(
+        &self,
+        id: &str,
+        symbol: &str,
+        group: &str,
+        index: Option<usize>,
+    ) -> Result<Watchlist, String> {
+        let symbol = symbol_key(symbol);
+        let group = group.trim();
+        let mut items = self.get(id)?.items;
+        let from = items
+            .iter()
+            .position(|item| item.symbol == symbol)
+            .ok_or_else(|| format!("Watchlist symbol not found: {}", symbol))?;
+        let moved = items.remove(from);
+        let members: Vec<usize> = items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.group == group)
+            .map(|(i, _)| i)
+            .collect();
+        let to = match index.and_then(|n| members.get(n)) {
+            Some(&i) => i,
+            None => members.last().map_or(items.len(), |&i| i + 1),
+        };
+        items.insert(to, moved);
+        self.0
+            .execute(
+                "UPDATE watchlist_items SET group_name = ?3 WHERE watchlist_id = ?1 AND symbol = ?2",
+                params![id, symbol, group],
+            )
+            .map_err(failed("move symbol"))?;
+        let order: Vec<String> = items.into_iter().map(|item| item.symbol).collect();
+        self.write_order(id, &order)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioRecord {
+    pub id: String,
+    pub name: String,
+    /// Currency totals are reported in
+    pub base_currency: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+pub struct Portfolios<'a>(pub &'a Connection);
+
+impl Portfolios<'_> {
+    fn from_row(row: &Row) -> rusqlite::Result<PortfolioRecord> {
+        Ok(PortfolioRecord {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            base_currency: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<PortfolioRecord>, String> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT id, name, base_currency, created_at, updated_at FROM portfolios
+                 ORDER BY created_at, id",
+            )
+            .map_err(failed("list portfolios"))?;
+        let portfolios = statement
+            .query_map([], Self::from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list portfolios"))?;
+        Ok(portfolios)
+    }
+
+    pub fn get(&self, id: &str) -> Result<PortfolioRecord, String> {
+        self.0
+            .query_row(
+                "SELECT id, name, base_currency, created_at, updated_at FROM portfolios
+                 WHERE id = ?1",
+                [id],
+                Self::from_row,
+            )
+            .optional()
+            .map_err(failed("read portfolio"))?
+            .ok_or_else(|| format!("Portfolio not found: {}", id))
+    }
+
+    pub fn create(&self, name: &str, base_currency: &str) -> Result<PortfolioRecord, String> {
+        let name = required(name, "Portfolio name")?;
+        let currency = required(base_currency, "Base currency")?.to_uppercase();
+        let id = generate_id("portfolio");
+        let now = get_timestamp();
+        self.0
+            .execute(
+                "INSERT INTO portfolios (id, name, base_currency, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?4)",
+                params![id, name, currency, now],
+            )
+            .map_err(failed("create portfolio"))?;
+        self.get(&id)
+    }
+
+    pub fn rename(&self, id: &str, name: &str) -> Result<PortfolioRecord, String> {
+        let name = required(name, "Portfolio name")?;
+        let changed = self
+            .0
+            .execute(
+                "UPDATE portfolios SET name = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, name, get_timestamp()],
+            )
+            .map_err(failed("rename portfolio"))?;
+        expect_changed(changed, "Portfolio", id)?;
+        self.get(id)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let changed = self
+            .0
+            .execute("DELETE FROM portfolios WHERE id = ?1", [id])
+            .map_err(failed("delete portfolio"))?;
+        expect_changed(changed, "Portfolio", id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    PriceAbove,
+    PriceBelow,
+    /// Daily change in percent at or above the threshold
+    ChangeAbove,
+    /// Daily change in percent at or below the threshold
+    ChangeBelow,
+    /// Change in percent over the last `window` minutes at or above the threshold
+    MoveAbove,
+    /// Change in percent over the last `window` minutes at or below the threshold
+    MoveBelow,
+    /// Volume of the last `window` minutes at least the threshold times
+    /// that of the windows before it
+    VolumeSpike,
+    /// Price at least the threshold in percent above its `window`-day average
+    MaCrossAbove,
+    /// Price at most the threshold in percent off its `window`-day average
+    MaCrossBelow,
+}
+
+impl AlertKind {
+    const ALL: [AlertKind; 9] = [
+        AlertKind::PriceAbove,
+        AlertKind::PriceBelow,
+        AlertKind::ChangeAbove,
+        AlertKind::ChangeBelow,
+        AlertKind::MoveAbove,
+        AlertKind::MoveBelow,
+        AlertKind::VolumeSpike,
+        AlertKind::MaCrossAbove,
+        AlertKind::MaCrossBelow,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            AlertKind::PriceAbove => "price_above",
+            AlertKind::PriceBelow => "price_below",
+            AlertKind::ChangeAbove => "change_above",
+            AlertKind::ChangeBelow => "change_below",
+            AlertKind::MoveAbove => "move_above",
+            AlertKind::MoveBelow => "move_below",
+            AlertKind::VolumeSpike => "volume_spike",
+            AlertKind::MaCrossAbove => "ma_cross_above",
+            AlertKind::MaCrossBelow => "ma_cross_below",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    /// Allowed window, in minutes or days, of kinds that take one
+    pub fn window_range(self) -> Option<(u32, u32)> {
+        match self {
+            AlertKind::MoveAbove | AlertKind::MoveBelow | AlertKind::VolumeSpike => Some((1, 120)),
+            AlertKind::MaCrossAbove | AlertKind::MaCrossBelow => Some((2, 250)),
+            _ => None,
+        }
+    }
+}
+
+/// When a fired alert may fire again
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Rearm {
+    /// Fires once; re-enabling or snoozing arms it again
+    #[default]
+    Once,
+    /// Re-arms once the value has moved back past the threshold by
+    /// `hysteresis`, in the threshold's units
+    Recurring { hysteresis: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: String,
+    pub symbol: String,
+    pub kind: AlertKind,
+    pub threshold: f64,
+    pub enabled: bool,
+    pub note: String,
+    pub created_at: String,
+    pub triggered_at: Option<String>,
+    pub rearm: Rearm,
+    /// Whether the alert can fire; cleared when it fires
+    pub armed: bool,
+    /// Unix seconds until which the alert stays quiet
+    pub snoozed_until: Option<i64>,
+    /// Minutes or days the kind looks back over, for kinds that take one
+    pub window: Option<u32>,
+    /// Delivery channels the alert also goes to, by name
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewAlert {
+    pub symbol: String,
+    pub kind: AlertKind,
+    pub threshold: f64,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub rearm: Rearm,
+    #[serde(default)]
+    pub window: Option<u32>,
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+/// Check a window against the alert kind, dropping one the kind doesn't take
+fn alert_window(kind: AlertKind, window: Option<u32>) -> Result<Option<u32>, String> {
+    let Some((min, max)) = kind.window_range() else {
+        return Ok(None);
+    };
+    match window {
+        Some(window) if (min..=max).contains(&window) => Ok(Some(window)),
+        _ => Err(format!("Alert window must be between {} and {}", min, max)),
+    }
+}
+
+fn channels_json(channels: &[String]) -> Result<String, String> {
+    serde_json::to_string(channels)
+        .map_err(|e| format!("Failed to serialize delivery channels: {}", e))
+}
+
+/// One firing of an alert
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertTrigger {
+    pub id: String,
+    pub alert_id: String,
+    pub symbol: String,
+    pub kind: AlertKind,
+    pub threshold: f64,
+    /// Value the threshold was compared with
+    pub value: f64,
+    pub price: f64,
+    pub fired_at: String,
+}
+
+fn rearm_json(rearm: Rearm) -> Result<String, String> {
+    if let Rearm::Recurring { hysteresis } = rearm {
+        if !hysteresis.is_finite() || hysteresis < 0.0 {
+            return Err("Alert hysteresis must be a non-negative number".to_string());
+        }
+    }
+    serde_json::to_string(&rearm).map_err(|e| format!("Failed to serialize re-arm rule: {}", e))
+}
+
+pub struct Alerts<'a>(pub &'a Connection);
+
+impl Alerts<'_> {
+    const COLUMNS: &'static str = "id, symbol, kind, threshold, enabled, note, created_at, \
+         triggered_at, rearm, armed, snoozed_until, window_len, channels";
+
+    fn from_row(row: &Row) -> rusqlite::Result<Alert> {
+        let kind: String = row.get(2)?;
+        let rearm: String = row.get(8)?;
+        let channels: String = row.get(12)?;
+        Ok(Alert {
+            id: row.get(0)?,
+            symbol: row.get(1)?,
+            kind: AlertKind::parse(&kind).ok_or_else(|| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    rusqlite::types::Type::Text,
+                    format!("unknown alert kind {}", kind).into(),
+                )
+            })?,
+            threshold: row.get(3)?,
+            enabled: row.get(4)?,
+            note: row.get(5)?,
+            created_at: row.get(6)?,
+            triggered_at: row.get(7)?,
+            rearm: serde_json::from_str(&rearm).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(8, rusqlite::types::Type::Text, e.into())
+            })?,
+            armed: row.get(9)?,
+            snoozed_until: row.get(10)?,
+            window: row.get(11)?,
+            channels: serde_json::from_str(&channels).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(12, rusqlite::types::Type::Text, e.into())
+            })?,
+        })
+    }
+
+    /// Alerts, optionally only those on one symbol
+    pub fn list(&self, symbol: Option<&str>) -> Result<Vec<Alert>, String> {
+        let mut statement = self
+            .0
+            .prepare(&format!(
+                "SELECT {} FROM alerts WHERE deleted_at IS NULL AND (?1 IS NULL OR symbol = ?1)
+                 ORDER BY created_at, id",
+                Self::COLUMNS
+            ))
+            .map_err(failed("list alerts"))?;
+        let alerts = statement
+            .query_map([symbol], Self::from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list alerts"))?;
+        Ok(alerts)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Alert, String> {
+        self.0
+            .query_row(
+                &format!(
+                    "SELECT {} FROM alerts WHERE id = ?1 AND deleted_at IS NULL",
+                    Self::COLUMNS
+                ),
+                [id],
+                Self::from_row,
+            )
+            .optional()
+            .map_err(failed("read alert"))?
+            .ok_or_else(|| format!("Alert not found: {}", id))
+    }
+
+    pub fn create(&self, alert: NewAlert) -> Result<Alert, String> {
+        let symbol = required(&alert.symbol, "Symbol")?.to_uppercase();
+        if !alert.threshold.is_finite() {
+            return Err("Alert threshold must be a number".to_string());
+        }
+        let rearm = rearm_json(alert.rearm)?;
+        let window = alert_window(alert.kind, alert.window)?;
+        let channels = channels_json(&alert.channels)?;
+        let id = generate_id("alert");
+        self.0
+            .execute(
+                "INSERT INTO alerts
+                 (id, symbol, kind, threshold, enabled, note, created_at, rearm, window_len, channels)
+                 VALUES (?1, ?2, ?3, ?4, 1, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    id,
+                    symbol,
+                    alert.kind.as_str(),
+                    alert.threshold,
+                    alert.note.trim(),
+                    get_timestamp(),
+                    rearm,
+                    window,
+                    channels
+                ],
+            )
+            .map_err(failed("create alert"))?;
+        self.get(&id)
+    }
+
+    /// Enable or disable an alert; enabling also arms it and ends any snooze
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Result<Alert, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE alerts SET enabled = ?2,
+                 armed = CASE WHEN ?2 THEN 1 ELSE armed END,
+                 snoozed_until = CASE WHEN ?2 THEN NULL ELSE snoozed_until END
+                 WHERE id = ?1",
+                params![id, enabled],
+            )
+            .map_err(failed("update alert"))?;
+        expect_changed(changed, "Alert", id)?;
+        self.get(id)
+    }
+
+    pub fn set_rearm(&self, id: &str, rearm: Rearm) -> Result<Alert, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE alerts SET rearm = ?2 WHERE id = ?1",
+                params![id, rearm_json(rearm)?],
+            )
+            .map_err(failed("update alert"))?;
+        expect_changed(changed, "Alert", id)?;
+        self.get(id)
+    }
+
+    /// Route an alert to delivery channels, replacing its current ones
+    pub fn set_channels(&self, id: &str, channels: &[String]) -> Result<Alert, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE alerts SET channels = ?2 WHERE id = ?1",
+                params![id, channels_json(channels)?],
+            )
+            .map_err(failed("update alert"))?;
+        expect_changed(changed, "Alert", id)?;
+        self.get(id)
+    }
+
+    /// Record that an alert fired at `value` and `price`, disarming it
+    pub fn fire(&self, id: &str, value: f64, price: f64) -> Result<Alert, String> {
+        let now = get_timestamp();
+        let changed = self
+            .0
+            .execute(
+                "UPDATE alerts SET armed = 0, triggered_at = ?2 WHERE id = ?1",
+                params![id, now],
+            )
+            .map_err(failed("update alert"))?;
+        expect_changed(changed, "Alert", id)?;
+        self.0
+            .execute(
+                "INSERT INTO alert_triggers
+                 (id, alert_id, symbol, kind, threshold, value, price, fired_at)
+                 SELECT ?2, id, symbol, kind, threshold, ?3, ?4, ?5 FROM alerts WHERE id = ?1",
+                params![id, generate_id("trigger"), value, price, now],
+            )
+            .map_err(failed("record alert trigger"))?;
+        self.get(id)
+    }
+
+    /// Firings, newest first, optionally only those of one alert
+    pub fn triggers(
+        &self,
+        alert_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<AlertTrigger>, String> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT id, alert_id, symbol, kind, threshold, value, price, fired_at
+                 FROM alert_triggers WHERE (?1 IS NULL OR alert_id = ?1)
+                     AND alert_id IN (SELECT id FROM alerts WHERE deleted_at IS NULL)
+                 ORDER BY fired_at DESC, rowid DESC LIMIT ?2",
+            )
+            .map_err(failed("list alert triggers"))?;
+        let triggers = statement
+            .query_map(params![alert_id, limit as i64], |row| {
+                let kind: String = row.get(3)?;
+                Ok(AlertTrigger {
+                    id: row.get(0)?,
+                    alert_id: row.get(1)?,
+                    symbol: row.get(2)?,
+                    kind: AlertKind::parse(&kind).ok_or_else(|| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            3,
+                            rusqlite::types::Type::Text,
+                            format!("unknown alert kind {}", kind).into(),
+                        )
+                    })?,
+                    threshold: row.get(4)?,
+                    value: row.get(5)?,
+                    price: row.get(6)?,
+                    fired_at: row.get(7)?,
+                })
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list alert triggers"))?;
+        Ok(triggers)
+    }
+
+    pub fn arm(&self, id: &str) -> Result<Alert, String> {
+        let changed = self
+            .0
+            .execute("UPDATE alerts SET armed = 1 WHERE id = ?1", [id])
+            .map_err(failed("update alert"))?;
+        expect_changed(changed, "Alert", id)?;
+        self.get(id)
+    }
+
+    /// Keep an alert quiet until `until`, then let it fire again if its condition holds
+    pub fn snooze(&self, id: &str, until: i64) -> Result<Alert, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE alerts SET armed = 1, snoozed_until = ?2 WHERE id = ?1",
+                params![id, until],
+            )
+            .map_err(failed("update alert"))?;
+        expect_changed(changed, "Alert", id)?;
+        self.get(id)
+    }
+
+    /// Move an alert and its trigger history to the trash, returning it as it was
+    pub fn delete(&self, id: &str) -> Result<Alert, String> {
+        let alert = self.get(id)?;
+        self.0
+            .execute(
+                "DELETE FROM alerts WHERE deleted_at < ?1",
+                [retention_cutoff()],
+            )
+            .map_err(failed("purge alerts"))?;
+        self.0
+            .execute(
+                "UPDATE alerts SET deleted_at = ?2 WHERE id = ?1",
+                params![id, get_timestamp()],
+            )
+            .map_err(failed("delete alert"))?;
+        Ok(alert)
+    }
+
+    pub fn restore(&self, id: &str) -> Result<Alert, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE alerts SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                [id],
+            )
+            .map_err(failed("restore alert"))?;
+        expect_changed(changed, "Trashed alert", id)?;
+        self.get(id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub id: String,
+    /// Symbol the note is about; general notes have none
+    pub symbol: Option<String>,
+    pub title: String,
+    pub body: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Mergeable for Note {
+    const KIND: &'static str = "note";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn modified_at(&self) -> &str {
+        &self.updated_at
+    }
+
+    fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}",
+            self.symbol.as_deref().unwrap_or_default(),
+            self.title,
+            self.body
+        )
+    }
+}
+
+pub struct Notes<'a>(pub &'a Connection);
+
+impl Notes<'_> {
+    fn from_row(row: &Row) -> rusqlite::Result<Note> {
+        Ok(Note {
+            id: row.get(0)?,
+            symbol: row.get(1)?,
+            title: row.get(2)?,
+            body: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    }
+
+    /// Notes, most recently edited first, optionally only those on one symbol
+    pub fn list(&self, symbol: Option<&str>) -> Result<Vec<Note>, String> {
+        let mut statement = self
+            .0
+            .prepare(
+                "SELECT id, symbol, title, body, created_at, updated_at FROM notes
+                 WHERE deleted_at IS NULL AND (?1 IS NULL OR symbol = ?1)
+                 ORDER BY updated_at DESC, id DESC",
+            )
+            .map_err(failed("list notes"))?;
+        let notes = statement
+            .query_map([symbol], Self::from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list notes"))?;
+        Ok(notes)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Note, String> {
+        self.0
+            .query_row(
+                "SELECT id, symbol, title, body, created_at, updated_at FROM notes
+                 WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                Self::from_row,
+            )
+            .optional()
+            .map_err(failed("read note"))?
+            .ok_or_else(|| format!("Note not found: {}", id))
+    }
+
+    pub fn create(&self, symbol: Option<&str>, title: &str, body: &str) -> Result<Note, String> {
+        let symbol = symbol
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_uppercase);
+        let id = generate_id("note");
+        let now = get_timestamp();
+        self.0
+            .execute(
+                "INSERT INTO notes (id, symbol, title, body, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                params![id, symbol, title.trim(), body, now],
+            )
+            .map_err(failed("create note"))?;
+        self.get(&id)
+    }
+
+    /// Write a note from elsewhere as it is, id and timestamps included; a
+    /// trashed one is restored
+    pub fn upsert(&self, note: &Note) -> Result<Note, String> {
+        self.0
+            .execute(
+                "INSERT INTO notes (id, symbol, title, body, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(id) DO UPDATE SET symbol = ?2, title = ?3, body = ?4,
+                     updated_at = ?6, deleted_at = NULL",
+                params![
+                    note.id,
+                    note.symbol,
+                    note.title,
+                    note.body,
+                    note.created_at,
+                    note.updated_at
+                ],
+            )
+            .map_err(failed("import note"))?;
+        self.get(&note.id)
+    }
+
+    pub fn update(&self, id: &str, title: &str, body: &str) -> Result<Note, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE notes SET title = ?2, body = ?3, updated_at = ?4
+                 WHERE id = ?1 AND deleted_at IS NULL",
+                params![id, title.trim(), body, get_timestamp()],
+            )
+            .map_err(failed("update note"))?;
+        expect_changed(changed, "Note", id)?;
+        self.get(id)
+    }
+
+    /// Move a note to the trash, returning it as it was
+    pub fn delete(&self, id: &str) -> Result<Note, String> {
+        let note = self.get(id)?;
+        self.0
+            .execute(
+                "DELETE FROM notes WHERE deleted_at < ?1",
+                [retention_cutoff()],
+            )
+            .map_err(failed("purge notes"))?;
+        self.0
+            .execute(
+                "UPDATE notes SET deleted_at = ?2 WHERE id = ?1",
+                params![id, get_timestamp()],
+            )
+            .map_err(failed("delete note"))?;
+        Ok(note)
+    }
+
+    pub fn restore(&self, id: &str) -> Result<Note, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE notes SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                [id],
+            )
+            .map_err(failed("restore note"))?;
+        expect_changed(changed, "Trashed note", id)?;
+        self.get(id)
+    }
+}
+
+pub struct Explanations<'a>(pub &'a Connection);
+
+impl Explanations<'_> {
+    pub fn record(&self, explanation: &Explanation) -> Result<(), String> {
+        let kind = serde_json::to_value(explanation.kind)
+            .map_err(|e| format!("Failed to serialize trigger kind: {}", e))?;
+        let conditions = serde_json::to_string(&explanation.conditions)
+            .map_err(|e| format!("Failed to serialize trigger conditions: {}", e))?;
+        self.0
+            .execute(
+                "INSERT OR REPLACE INTO trigger_explanations
+                 (event_id, kind, source_id, symbol, outcome, conditions, as_of, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    explanation.event_id,
+                    kind.as_str(),
+                    explanation.source_id,
+                    explanation.symbol,
+                    explanation.outcome,
+                    conditions,
+                    explanation.as_of,
+                    explanation.created_at
+                ],
+            )
+            .map_err(failed("record trigger explanation"))?;
+        Ok(())
+    }
+
+    pub fn get(&self, event_id: &str) -> Result<Explanation, String> {
+        let row = self
+            .0
+            .query_row(
+                "SELECT kind, source_id, symbol, outcome, conditions, as_of, created_at
+                 FROM trigger_explanations WHERE event_id = ?1",
+                [event_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, i64>(5)?,
+                        row.get::<_, String>(6)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(failed("read trigger explanation"))?
+            .ok_or_else(|| format!("No explanation for event: {}", event_id))?;
+        let (kind, source_id, symbol, outcome, conditions, as_of, created_at) = row;
+        Ok(Explanation {
+            event_id: event_id.to_string(),
+            kind: serde_json::from_value(serde_json::Value::String(kind))
+                .map_err(|e| format!("Failed to parse trigger kind: {}", e))?,
+            source_id,
+            symbol,
+            outcome,
+            conditions: serde_json::from_str(&conditions)
+                .map_err(|e| format!("Failed to parse trigger conditions: {}", e))?,
+            as_of,
+            created_at,
+        })
+    }
+}
+
+pub struct Notifications<'a>(pub &'a Connection);
+
+impl Notifications<'_> {
+    const COLUMNS: &'static str = "id, category, title, body, actions, state, created_at, read_at";
+
+    fn from_row(row: &Row) -> rusqlite::Result<StoredNotification> {
+        let actions: String = row.get(4)?;
+        Ok(StoredNotification {
+            id: row.get(0)?,
+            category: text_enum(1, row.get(1)?)?,
+            title: row.get(2)?,
+            body: row.get(3)?,
+            actions: serde_json::from_str(&actions).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, e.into())
+            })?,
+            state: text_enum(5, row.get(5)?)?,
+            created_at: row.get(6)?,
+            read_at: row.get(7)?,
+        })
+    }
+
+    /// Store a new unread notification, dropping the oldest beyond the cap
+    pub fn record(
+        &self,
+        id: &str,
+        notification: &Notification,
+    ) -> Result<StoredNotification, String> {
+        let actions = serde_json::to_string(&notification.actions)
+            .map_err(|e| format!("Failed to serialize notification actions: {}", e))?;
+        self.0
+            .execute(
+                "INSERT INTO notifications (id, category, title, body, actions, state, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 'unread', ?6)",
+                params![
+                    id,
+                    enum_text(notification.category),
+                    notification.title,
+                    notification.body,
+                    actions,
+                    get_timestamp()
+                ],
+            )
+            .map_err(failed("record notification"))?;
+        self.0
+            .execute(
+                "DELETE FROM notifications WHERE id NOT IN
+                 (SELECT id FROM notifications ORDER BY created_at DESC, id DESC LIMIT ?1)",
+                [MAX_NOTIFICATIONS as i64],
+            )
+            .map_err(failed("prune notifications"))?;
+        self.get(id)
+    }
+
+    pub fn get(&self, id: &str) -> Result<StoredNotification, String> {
+        self.0
+            .query_row(
+                &format!("SELECT {} FROM notifications WHERE id = ?1", Self::COLUMNS),
+                [id],
+                Self::from_row,
+            )
+            .optional()
+            .map_err(failed("read notification"))?
+            .ok_or_else(|| format!("Notification not found: {}", id))
+    }
+
+    /// Newest first; without a state filter archived notifications are left out
+    pub fn list(&self, filter: &NotificationFilter) -> Result<Vec<StoredNotification>, String> {
+        let state = filter.state.map(enum_text);
+        let category = filter.category.map(enum_text);
+        let mut statement = self
+            .0
+            .prepare(&format!(
+                "SELECT {} FROM notifications
+                 WHERE (?1 IS NULL AND state != 'archived' OR state = ?1)
+                   AND (?2 IS NULL OR category = ?2)
+                 ORDER BY created_at DESC, id DESC LIMIT ?3",
+                Self::COLUMNS
+            ))
+            .map_err(failed("list notifications"))?;
+        let limit = filter
+            .limit
+            .unwrap_or(MAX_NOTIFICATIONS)
+            .min(MAX_NOTIFICATIONS);
+        let notifications = statement
+            .query_map(params![state, category, limit as i64], Self::from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list notifications"))?;
+        Ok(notifications)
+    }
+
+    /// Move notifications to `state`, returning how many changed
+    pub fn set_state(&self, ids: &[String], state: NotificationState) -> Result<usize, String> {
+        let sql = match state {
+            NotificationState::Unread => {
+                "UPDATE notifications SET state = 'unread', read_at = NULL
+                 WHERE id = ?1 AND state != 'unread'"
+            }
+            NotificationState::Read => {
+                "UPDATE notifications SET state = 'read', read_at = ?2
+                 WHERE id = ?1 AND state = 'unread'"
+            }
+            NotificationState::Archived => {
+                "UPDATE notifications SET state = 'archived', read_at = COALESCE(read_at, ?2)
+                 WHERE id = ?1 AND state != 'archived'"
+            }
+        };
+        let now = get_timestamp();
+        let mut changed = 0;
+        for id in ids {
+            changed += if state == NotificationState::Unread {
+                self.0.execute(sql, [id])
+            } else {
+                self.0.execute(sql, params![id, now])
+            }
+            .map_err(failed("update notification"))?;
+        }
+        Ok(changed)
+    }
+
+    /// Delete notifications for good, the given ones or else every one that
+    /// has been read, returning how many went
+    pub fn clear(&self, ids: Option<&[String]>) -> Result<usize, String> {
+        let Some(ids) = ids else {
+            return self
+                .0
+                .execute("DELETE FROM notifications WHERE state != 'unread'", [])
+                .map_err(failed("clear notifications"));
+        };
+        let mut cleared = 0;
+        for id in ids {
+            cleared += self
+                .0
+                .execute("DELETE FROM notifications WHERE id = ?1", [id])
+                .map_err(failed("clear notifications"))?;
+        }
+        Ok(cleared)
+    }
+
+    pub fn unread_count(&self) -> Result<usize, String> {
+        self.0
+            .query_row(
+                "SELECT COUNT(*) FROM notifications WHERE state = 'unread'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count as usize)
+            .map_err(failed("count unread notifications"))
+    }
+}
+
+/// Saved formulas, by their unique name
+pub struct SavedFormulas<'a>(pub &'a Connection);
+
+impl SavedFormulas<'_> {
+    /// Every formula, by name
+    pub fn list(&self) -> Result<Vec<Formula>, String> {
+        let mut statement = self
+            .0
+            .prepare("SELECT name, kind, source FROM formulas ORDER BY name")
+            .map_err(failed("list formulas"))?;
+        let formulas = statement
+            .query_map([], |row| {
+                Ok(Formula {
+                    name: row.get(0)?,
+                    kind: text_enum(1, row.get(1)?)?,
+                    source: row.get(2)?,
+                })
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list formulas"))?;
+        Ok(formulas)
+    }
+
+    /// Save a formula, replacing one of the same name
+    pub fn save(&self, formula: &Formula) -> Result<(), String> {
+        self.0
+            .execute(
+                "INSERT INTO formulas (name, kind, source) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name) DO UPDATE SET kind = ?2, source = ?3",
+                params![formula.name, enum_text(formula.kind), formula.source],
+            )
+            .map_err(failed("save formula"))?;
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let changed = self
+            .0
+            .execute("DELETE FROM formulas WHERE name = ?1", [name])
+            .map_err(failed("delete formula"))?;
+        expect_changed(changed, "Formula", name)
+    }
+}
+
+pub struct PortfolioAlerts<'a>(pub &'a Connection);
+
+impl PortfolioAlerts<'_> {
+    const COLUMNS: &'static str = "id, condition, currency, enabled, armed, peak, channels, \
+         note, created_at, triggered_at";
+
+    fn from_row(row: &Row) -> rusqlite::Result<PortfolioAlert> {
+        let condition: String = row.get(1)?;
+        let channels: String = row.get(6)?;
+        Ok(PortfolioAlert {
+            id: row.get(0)?,
+            condition: serde_json::from_str(&condition).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+            })?,
+            currency: row.get(2)?,
+            enabled: row.get(3)?,
+            armed: row.get(4)?,
+            peak: row.get(5)?,
+            channels: serde_json::from_str(&channels).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, e.into())
+            })?,
+            note: row.get(7)?,
+            created_at: row.get(8)?,
+            triggered_at: row.get(9)?,
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<PortfolioAlert>, String> {
+        let mut statement = self
+            .0
+            .prepare(&format!(
+                "SELECT {} FROM portfolio_alerts ORDER BY created_at, id",
+                Self::COLUMNS
+            ))
+            .map_err(failed("list portfolio alerts"))?;
+        let alerts = statement
+            .query_map([], Self::from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(failed("list portfolio alerts"))?;
+        Ok(alerts)
+    }
+
+    pub fn get(&self, id: &str) -> Result<PortfolioAlert, String> {
+        self.0
+            .query_row(
+                &format!(
+                    "SELECT {} FROM portfolio_alerts WHERE id = ?1",
+                    Self::COLUMNS
+                ),
+                [id],
+                Self::from_row,
+            )
+            .optional()
+            .map_err(failed("read portfolio alert"))?
+            .ok_or_else(|| format!("Portfolio alert not found: {}", id))
+    }
+
+    pub fn create(&self, alert: &PortfolioAlert) -> Result<PortfolioAlert, String> {
+        let condition = serde_json::to_string(&alert.condition)
+            .map_err(|e| format!("Failed to serialize portfolio condition: {}", e))?;
+        self.0
+            .execute(
+                "INSERT INTO portfolio_alerts
+                 (id, condition, currency, enabled, armed, peak, channels, note, created_at,
+                  triggered_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    alert.id,
+                    condition,
+                    alert.currency,
+                    alert.enabled,
+                    alert.armed,
+                    alert.peak,
+                    channels_json(&alert.channels)?,
+                    alert.note,
+                    alert.created_at,
+                    alert.triggered_at
+                ],
+            )
+            .map_err(failed("create portfolio alert"))?;
+        self.get(&alert.id)
+    }
+
+    /// Enable or disable an alert; enabling a disabled one also re-arms it
+    /// and resets its peak
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Result<PortfolioAlert, String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE portfolio_alerts SET
+                 armed = CASE WHEN ?2 AND NOT enabled THEN 1 ELSE armed END,
+                 peak = CASE WHEN ?2 AND NOT enabled THEN NULL ELSE peak END,
+                 enabled = ?2
+                 WHERE id = ?1",
+                params![id, enabled],
+            )
+            .map_err(failed("update portfolio alert"))?;
+        expect_changed(changed, "Portfolio alert", id)?;
+        self.get(id)
+    }
+
+    /// Store what evaluating an alert changed: whether it is armed, its
+    /// peak and when it last fired
+    pub fn set_state(&self, alert: &PortfolioAlert) -> Result<(), String> {
+        let changed = self
+            .0
+            .execute(
+                "UPDATE portfolio_alerts SET armed = ?2, peak = ?3, triggered_at = ?4
+                 WHERE id = ?1",
+                params![alert.id, alert.armed, alert.peak, alert.triggered_at],
+            )
+            .map_err(failed("update portfolio alert"))?;
+        expect_changed(changed, "Portfolio alert", &alert.id)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let changed = self
+            .0
+            .execute("DELETE FROM portfolio_alerts WHERE id = ?1", [id])
+            .map_err(failed("delete portfolio alert"))?;
+        expect_changed(changed, "Portfolio alert", id)
+    }
+}
+
+/// Downloaded financial statements, one set per symbol
+pub struct Financials<'a>(pub &'a Connection);
+
+impl Financials<'_> {
+    /// Statements of a symbol fetched at or after `since`, in Unix seconds
+    fn fetched_since(
+        &self,
+        symbol: &str,
+        since: i64,
+    ) -> Result<Option<FinancialStatements>, String> {
+        let statements: Option<String> = self
+            .0
+            .query_row(
+                "SELECT statements FROM financial_statements
+                 WHERE symbol = ?1 AND fetched_at >= ?2",
+                params![symbol, since],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(failed("read financial statements"))?;
+        statements
+            .map(|statements| {
+                serde_json::from_str(&statements)
+                    .map_err(|e| format!("Failed to parse financial statements: {}", e))
+            })
+            .transpose()
+    }
+
+    /// Cached statements of a symbol, however old
+    pub fn cached(&self, symbol: &str) -> Result<Option<FinancialStatements>, String> {
+        self.fetched_since(symbol, i64::MIN)
+    }
+
+    /// Cached statements of a symbol, unless fetched `max_age` or more before `now`
+    pub fn fresh(
+        &self,
+        symbol: &str,
+        now: i64,
+        max_age: Duration,
+    ) -> Result<Option<FinancialStatements>, String> {
+        self.fetched_since(symbol, now - max_age.as_secs() as i64 + 1)
+    }
+
+    /// Cache a symbol's statements, replacing any it had
+    pub fn save(&self, statements: &FinancialStatements) -> Result<(), String> {
+        let content = serde_json::to_string(statements)
+            .map_err(|e| format!("Failed to serialize financial statements: {}", e))?;
+        self.0
+            .execute(
+                "INSERT INTO financial_statements (symbol, statements, fetched_at)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT(symbol) DO UPDATE SET statements = ?2, fetched_at = ?3",
+                params![statements.symbol, content, statements.fetched_at],
+            )
+            .map_err(failed("save financial statements"))?;
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub fn list_watchlists(db: State<'_, Database>) -> Result<Vec<Watchlist>, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).list()
+}
+
+#[tauri::command]
+pub fn create_watchlist(db: State<'_, Database>, name: String) -> Result<Watchlist, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).create(&name)
+}
+
+#[tauri::command]
+pub fn rename_watchlist(
+    db: State<'_, Database>,
+    watchlist_id: String,
+    name: String,
+) -> Result<Watchlist, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).rename(&watchlist_id, &name)
+}
+
+#[tauri::command]
+pub fn delete_watchlist(
+    db: State<'_, Database>,
+    undo: State<'_, UndoLog>,
+    watchlist_id: String,
+) -> Result<(), String> {
+    let conn = db.conn()?;
+    let watchlist = Watchlists(&conn).delete(&watchlist_id)?;
+    undo.push(
+        format!("watchlist:{}", watchlist_id),
+        format!("Delete watchlist {}", watchlist.name),
+        UndoAction::Delete,
+    )
+}
+
+/// Set the market a watchlist focuses on and how eagerly its quotes refresh
+#[tauri::command]
+pub fn set_watchlist_refresh(
+    db: State<'_, Database>,
+    watchlist_id: String,
+    market: Option<Market>,
+    profile: RefreshProfile,
+) -> Result<Watchlist, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).set_refresh(&watchlist_id, market, profile)
+}
+
+/// Put watchlists in a new order
+#[tauri::command]
+pub fn reorder_watchlists(
+    db: State<'_, Database>,
+    watchlist_ids: Vec<String>,
+) -> Result<Vec<Watchlist>, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).reorder(&watchlist_ids)
+}
+
+/// Add a symbol to the end of a watchlist, optionally into a group
+#[tauri::command]
+pub fn add_symbol(
+    db: State<'_, Database>,
+    watchlist_id: String,
+    symbol: String,
+    group: Option<String>,
+) -> Result<Watchlist, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).add_symbol(&watchlist_id, &symbol, group.as_deref().unwrap_or(""))
+}
+
+#[tauri::command]
+pub fn remove_symbol(
+    db: State<'_, Database>,
+    watchlist_id: String,
+    symbol: String,
+) -> Result<Watchlist, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).remove_symbol(&watchlist_id, &symbol)
+}
+
+/// Persist the order of a watchlist's symbols after a drag and drop
+#[tauri::command]
+pub fn reorder_symbols(
+    db: State<'_, Database>,
+    watchlist_id: String,
+    symbols: Vec<String>,
+) -> Result<Watchlist, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).reorder_symbols(&watchlist_id, &symbols)
+}
+
+/// Move a symbol into a group (empty for ungrouped), at `index` within it
+#[tauri::command]
+pub fn move_symbol_to_group(
+    db: State<'_, Database>,
+    watchlist_id: String,
+    symbol: String,
+    group: String,
+    index: Option<usize>,
+) -> Result<Watchlist, String> {
+    let conn = db.conn()?;
+    Watchlists(&conn).move_to_group(&watchlist_id, &symbol, &group, index)
+}
+
+#[tauri::command]
+pub fn list_portfolios(db: State<'_, Database>) -> Result<Vec<PortfolioRecord>, String> {
+    let conn = db.conn()?;
+    Portfolios(&conn).list()
+}
+
+#[tauri::command]
+pub fn create_portfolio(
+    db: State<'_, Database>,
+    name: String,
+    base_currency: String,
+) -> Result<PortfolioRecord, String> {
+    let conn = db.conn()?;
+    Portfolios(&conn).create(&name, &base_currency)
+}
+
+#[tauri::command]
+pub fn rename_portfolio(
+    db: State<'_, Database>,
+    portfolio_id: String,
+    name: String,
+) -> Result<PortfolioRecord, String> {
+    let conn = db.conn()?;
+    Portfolios(&conn).rename(&portfolio_id, &name)
+}
+
+/// Delete a named portfolio once no ledger entry is booked to it
+#[tauri::command]
+pub fn delete_portfolio(
+    db: State<'_, Database>,
+    store: State<'_, PortfolioStore>,
+    portfolio_id: String,
+) -> Result<(), String> {
+    let booked = store
+        .get()
+        .transactions
+        .iter()
+        .filter(|t| t.entry.portfolio_id.as_deref() == Some(portfolio_id.as_str()))
+        .count();
+    if booked > 0 {
+        return Err(format!(
+            "Portfolio still has {} transactions; move or delete them first",
+            booked
+        ));
+    }
+    let conn = db.conn()?;
+    Portfolios(&conn).delete(&portfolio_id)
+}
+
+#[tauri::command]
+pub fn list_alerts(db: State<'_, Database>, symbol: Option<String>) -> Result<Vec<Alert>, String> {
+    let symbol = symbol.map(|s| s.trim().to_uppercase());
+    let conn = db.conn()?;
+    Alerts(&conn).list(symbol.as_deref())
+}
+
+#[tauri::command]
+pub fn create_alert(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    alert: NewAlert,
+) -> Result<Alert, String> {
+    settings.get().delivery.check_routes(&alert.channels)?;
+    let conn = db.conn()?;
+    Alerts(&conn).create(alert)
+}
+
+#[tauri::command]
+pub fn set_alert_enabled(
+    db: State<'_, Database>,
+    alert_id: String,
+    enabled: bool,
+) -> Result<Alert, String> {
+    let conn = db.conn()?;
+    Alerts(&conn).set_enabled(&alert_id, enabled)
+}
+
+#[tauri::command]
+pub fn delete_alert(
+    db: State<'_, Database>,
+    undo: State<'_, UndoLog>,
+    alert_id: String,
+) -> Result<(), String> {
+    let conn = db.conn()?;
+    let alert = Alerts(&conn).delete(&alert_id)?;
+    undo.push(
+        format!("alert:{}", alert_id),
+        format!("Delete {} alert on {}", alert.kind.as_str(), alert.symbol),
+        UndoAction::Delete,
+    )
+}
+
+/// Trigger history, newest first, of one alert or all of them
+#[tauri::command]
+pub fn list_alert_triggers(
+    db: State<'_, Database>,
+    alert_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<AlertTrigger>, String> {
+    let conn = db.conn()?;
+    Alerts(&conn).triggers(alert_id.as_deref(), limit.unwrap_or(100))
+}
+
+#[tauri::command]
+pub fn list_notes(db: State<'_, Database>, symbol: Option<String>) -> Result<Vec<Note>, String> {
+    let symbol = symbol.map(|s| s.trim().to_uppercase());
+    let conn = db.conn()?;
+    Notes(&conn).list(symbol.as_deref())
+}
+
+#[tauri::command]
+pub fn create_note(
+    db: State<'_, Database>,
+    symbol: Option<String>,
+    title: String,
+    body: String,
+) -> Result<Note, String> {
+    let conn = db.conn()?;
+    Notes(&conn).create(symbol.as_deref(), &title, &body)
+}
+
+#[tauri::command]
+pub fn update_note(
+    db: State<'_, Database>,
+    undo: State<'_, UndoLog>,
+    note_id: String,
+    title: String,
+    body: String,
+) -> Result<Note, String> {
+    let conn = db.conn()?;
+    let notes = Notes(&conn);
+    let before = notes.get(&note_id)?;
+    let updated = notes.update(&note_id, &title, &body)?;
+    let before =
+        serde_json::to_value(&before).map_err(|e| format!("Failed to serialize note: {}", e))?;
+    undo.push(
+        format!("note:{}", note_id),
+        format!("Edit note {}", updated.title),
+        UndoAction::Update { before },
+    )?;
+    Ok(updated)
+}
+
+#[tauri::command]
+pub fn delete_note(
+    db: State<'_, Database>,
+    undo: State<'_, UndoLog>,
+    note_id: String,
+) -> Result<(), String> {
+    let conn = db.conn()?;
+    let note = Notes(&conn).delete(&note_id)?;
+    undo.push(
+        format!("note:{}", note_id),
+        format!("Delete note {}", note.title),
+        UndoAction::Delete,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::NotificationCategory;
+
+    fn temp_db(name: &str) -> (Database, PathBuf) {
+        let path = std::env::temp_dir().join(format!("ssi-db-{}-{}.db", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        (Database::open(path.clone()).unwrap(), path)
+    }
+
+    fn remove(path: PathBuf) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            std::fs::remove_file(file).ok();
+        }
+    }
+
+    #[test]
+    fn test_migrations_run_once_and_pool_reuses() {
+        let (db, path) = temp_db("migrate");
+        {
+            let mut conn = db.conn().unwrap();
+            assert_eq!(migrate(&mut conn).unwrap(), 9);
+            let applied: i64 = conn
+                .query_row("SELECT COUNT(*) FROM schema_migrations", [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(applied, 9);
+            // A second connection while the first is out
+            let other = db.conn().unwrap();
+            assert!(Notes(&other).list(None).unwrap().is_empty());
+        }
+        assert_eq!(db.idle.lock().unwrap().len(), 2);
+        drop(db);
+        let reopened = Database::open(path.clone()).unwrap();
+        assert!(Watchlists(&reopened.conn().unwrap())
+            .list()
+            .unwrap()
+            .is_empty());
+        remove(path);
+    }
+
+    #[test]
+    fn test_watchlist_ordering_and_groups() {
+        let (db, path) = temp_db("watchlists");
+        let conn = db.conn().unwrap();
+        let watchlists = Watchlists(&conn);
+        let list = watchlists.create("自选").unwrap();
+        let other = watchlists.create("观察").unwrap();
+        for (symbol, group) in [
+            ("SH600519", "白酒"),
+            ("SZ000858", "白酒"),
+            ("SZ300750", ""),
+            ("SH601318", "金融"),
+        ] {
+            watchlists.add_symbol(&list.id, symbol, group).unwrap();
+        }
+        assert!(watchlists.add_symbol(&list.id, "sh600519", "").is_err());
+        let symbols = |w: Watchlist| -> Vec<(String, String)> {
+            w.items.into_iter().map(|i| (i.symbol, i.group)).collect()
+        };
+
+        // Drag SH601318 into 白酒 between the two liquor stocks
+        let moved = watchlists
+            .move_to_group(&list.id, "SH601318", "白酒", Some(1))
+            .unwrap();
+        assert_eq!(
+            symbols(moved),
+            [
+                ("SH600519".to_string(), "白酒".to_string()),
+                ("SH601318".to_string(), "白酒".to_string()),
+                ("SZ000858".to_string(), "白酒".to_string()),
+                ("SZ300750".to_string(), String::new()),
+            ]
+        );
+        let order: Vec<String> = ["SZ300750", "SZ000858", "SH600519", "SH601318"]
+            .map(String::from)
+            .to_vec();
+        let reordered = watchlists.reorder_symbols(&list.id, &order).unwrap();
+        assert_eq!(
+            reordered
+                .items
+                .iter()
+                .map(|i| i.position)
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert!(watchlists.reorder_symbols(&list.id, &order[..3]).is_err());
+
+        let removed = watchlists.remove_symbol(&list.id, "SZ000858").unwrap();
+        assert_eq!(
+            removed
+                .items
+                .iter()
+                .map(|i| (i.symbol.as_str(), i.position))
+                .collect::<Vec<_>>(),
+            [("SZ300750", 0), ("SH600519", 1), ("SH601318", 2)]
+        );
+        assert!(watchlists.remove_symbol(&list.id, "SZ000858").is_err());
+
+        let lists = watchlists
+            .reorder(&[other.id.clone(), list.id.clone()])
+            .unwrap();
+        assert_eq!(lists[0].id, other.id);
+
+        assert_eq!(lists[0].refresh_profile, RefreshProfile::Standard);
+        let eod = watchlists
+            .set_refresh(&other.id, Some(Market::Us), RefreshProfile::EndOfDay)
+            .unwrap();
+        assert_eq!(
+            (eod.market, eod.refresh_profile),
+            (Some(Market::Us), RefreshProfile::EndOfDay)
+        );
+        let cleared = watchlists
+            .set_refresh(&other.id, None, RefreshProfile::Aggressive)
+            .unwrap();
+        assert_eq!(cleared.market, None);
+        drop(conn);
+        remove(path);
+    }
+
+    #[test]
+    fn test_legacy_files_import_once() {
+        let (db, path) = temp_db("legacy");
+        let dir = std::env::temp_dir().join(format!("ssi-db-legacy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("formulas.json"),
+            r#"{"动量": {"name": "动量", "kind": "column", "source": "close[-1] / close[-20]"}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("portfolio_alerts.json"),
+            r#"[{"id": "portfolio-alert-1", "condition": {"kind": "drawdown", "pct": 8.0},
+                "currency": "CNY", "enabled": true, "armed": false, "peak": 310000.0,
+                "created_at": "2024-03-01 09:30:00"}]"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("financials.json"), "not json").unwrap();
+
+        db.import_legacy(&dir).unwrap();
+        let conn = db.conn().unwrap();
+        assert_eq!(SavedFormulas(&conn).list().unwrap()[0].name, "动量");
+        let alerts = PortfolioAlerts(&conn);
+        let alert = alerts.get("portfolio-alert-1").unwrap();
+        assert_eq!((alert.armed, alert.peak), (false, Some(310_000.0)));
+        assert!(dir.join("formulas.json.imported").exists());
+        // A file that fails to import stays for the next start
+        assert!(dir.join("financials.json").exists());
+        db.import_legacy(&dir).unwrap();
+        assert_eq!(SavedFormulas(&conn).list().unwrap().len(), 1);
+
+        // Re-enabling re-arms and forgets the peak; staying enabled doesn't
+        alerts.set_enabled(&alert.id, true).unwrap();
+        assert!(!alerts.get(&alert.id).unwrap().armed);
+        alerts.set_enabled(&alert.id, false).unwrap();
+        let enabled = alerts.set_enabled(&alert.id, true).unwrap();
+        assert_eq!((enabled.armed, enabled.peak), (true, None));
+        alerts.delete(&alert.id).unwrap();
+        assert!(alerts.delete(&alert.id).is_err());
+        drop(conn);
+        std::fs::remove_dir_all(dir).ok();
+        remove(path);
+    }
+
+    #[test]
+    fn test_repositories() {
+        let (db, path) = temp_db("repos");
+        let conn = db.conn().unwrap();
+
+        let watchlists = Watchlists(&conn);
+        let core = watchlists.create("核心持仓").unwrap();
+        let tech = watchlists.create("科技").unwrap();
+        assert_eq!((core.position, tech.position), (0, 1));
+        assert!(watchlists.create("科技").is_err());
+        watchlists.add_symbol(&core.id, "sh600519", "白酒").unwrap();
+        assert_eq!(watchlists.get(&core.id).unwrap().items[0].group, "白酒");
+        // A trashed watchlist keeps its items until it is restored
+        assert_eq!(watchlists.delete(&core.id).unwrap().items.len(), 1);
+        assert!(watchlists.get(&core.id).is_err());
+        assert_eq!(watchlists.list().unwrap().len(), 1);
+        assert!(watchlists.delete(&core.id).is_err());
+        let restored = watchlists.restore(&core.id).unwrap();
+        assert_eq!(restored.items[0].symbol, "SH600519");
+        assert!(watchlists.restore(&core.id).is_err());
+        // and gives up its name to a new list, which purges it with its items
+        watchlists.delete(&core.id).unwrap();
+        watchlists.create("核心持仓").unwrap();
+        assert!(watchlists.restore(&core.id).is_err());
+        let orphans: i64 = conn
+            .query_row("SELECT COUNT(*) FROM watchlist_items", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(orphans, 0);
+
+        let portfolio = Portfolios(&conn).create("港股账户", "hkd").unwrap();
+        assert_eq!(portfolio.base_currency, "HKD");
+
+        let alerts = Alerts(&conn);
+        let alert = alerts
+            .create(NewAlert {
+                symbol: "sz000001".to_string(),
+                kind: AlertKind::PriceBelow,
+                threshold: 9.5,
+                note: String::new(),
+                rearm: Rearm::default(),
+                window: Some(5),
+                channels: vec!["phone".to_string()],
+            })
+            .unwrap();
+        assert_eq!(alert.symbol, "SZ000001");
+        assert_eq!(alert.channels, ["phone"]);
+        assert!(alerts
+            .set_channels(&alert.id, &[])
+            .unwrap()
+            .channels
+            .is_empty());
+        // Price alerts take no window
+        assert_eq!(alert.window, None);
+        assert!(!alerts.set_enabled(&alert.id, false).unwrap().enabled);
+        assert_eq!(alerts.list(Some("SZ000001")).unwrap().len(), 1);
+        assert!(alerts.list(Some("SH600519")).unwrap().is_empty());
+        let spike = NewAlert {
+            symbol: "SH600519".to_string(),
+            kind: AlertKind::VolumeSpike,
+            threshold: 3.0,
+            note: String::new(),
+            rearm: Rearm::default(),
+            window: None,
+            channels: Vec::new(),
+        };
+        assert!(alerts.create(spike.clone()).is_err());
+        let spike = alerts
+            .create(NewAlert {
+                window: Some(5),
+                ..spike
+            })
+            .unwrap();
+        assert!(!alerts.fire(&spike.id, 4.2, 1688.0).unwrap().armed);
+        let triggers = alerts.triggers(Some(&spike.id), 10).unwrap();
+        assert_eq!(
+            (triggers.len(), triggers[0].kind, triggers[0].value),
+            (1, AlertKind::VolumeSpike, 4.2)
+        );
+        assert!(alerts.triggers(Some(&alert.id), 10).unwrap().is_empty());
+        // History goes to the trash with its alert, and comes back with it
+        alerts.delete(&spike.id).unwrap();
+        assert!(alerts.triggers(None, 10).unwrap().is_empty());
+        assert_eq!(alerts.list(Some("SH600519")).unwrap().len(), 0);
+        alerts.restore(&spike.id).unwrap();
+        assert_eq!(alerts.triggers(None, 10).unwrap().len(), 1);
+
+        let notes = Notes(&conn);
+        let note = notes.create(Some("sh600519"), "调研", "渠道库存").unwrap();
+        notes.create(None, "宏观", "").unwrap();
+        assert_eq!(notes.list(Some("SH600519")).unwrap().len(), 1);
+        assert_eq!(notes.list(None).unwrap().len(), 2);
+        assert_eq!(
+            notes
+                .update(&note.id, "调研纪要", "渠道库存偏高")
+                .unwrap()
+                .title,
+            "调研纪要"
+        );
+        assert_eq!(notes.delete(&note.id).unwrap().title, "调研纪要");
+        assert_eq!(notes.list(None).unwrap().len(), 1);
+        assert!(notes.update(&note.id, "调研", "").is_err());
+        assert_eq!(notes.restore(&note.id).unwrap().body, "渠道库存偏高");
+        // Trash past the retention window is purged by the next delete
+        conn.execute(
+            "UPDATE notes SET deleted_at = '2000-01-01 00:00:00 UTC' WHERE id != ?1",
+            [&note.id],
+        )
+        .unwrap();
+        notes.delete(&note.id).unwrap();
+        let kept: i64 = conn
+            .query_row("SELECT COUNT(*) FROM notes", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(kept, 1);
+
+        let explanation = Explanation {
+            event_id: "signal-1".to_string(),
+            kind: crate::explain::TriggerKind::StrategySignal,
+            source_id: "strategy-1".to_string(),
+            symbol: "SH600519".to_string(),
+            outcome: "buy".to_string(),
+            conditions: vec![crate::explain::Condition::new(
+                "RSI(14)".to_string(),
+                27.5,
+                crate::explain::Comparison::Below,
+                30.0,
+            )],
+            as_of: 1_717_400_000,
+            created_at: "2025-06-03 08:00:00 UTC".to_string(),
+        };
+        Explanations(&conn).record(&explanation).unwrap();
+        assert_eq!(Explanations(&conn).get("signal-1").unwrap(), explanation);
+        assert!(Explanations(&conn).get("signal-2").is_err());
+        drop(conn);
+        remove(path);
+    }
+
+    #[test]
+    fn test_notification_center_states() {
+        let (db, path) = temp_db("notifications");
+        let conn = db.conn().unwrap();
+        let center = Notifications(&conn);
+        let notification = |category, title: &str| Notification {
+            category,
+            title: title.to_string(),
+            body: String::new(),
+            actions: Vec::new(),
+        };
+        let alert = center
+            .record(
+                "n1",
+                &notification(NotificationCategory::PriceAlert, "SH600519 突破 1700"),
+            )
+            .unwrap();
+        let task = center
+            .record(
+                "n2",
+                &notification(NotificationCategory::Task, "任务完成: backtest"),
+            )
+            .unwrap();
+        assert_eq!(alert.state, NotificationState::Unread);
+        assert_eq!(center.unread_count().unwrap(), 2);
+
+        let ids = vec![alert.id.clone(), "missing".to_string()];
+        assert_eq!(center.set_state(&ids, NotificationState::Read).unwrap(), 1);
+        assert_eq!(center.set_state(&ids, NotificationState::Read).unwrap(), 0);
+        assert!(center.get(&alert.id).unwrap().read_at.is_some());
+        center
+            .set_state(std::slice::from_ref(&task.id), NotificationState::Archived)
+            .unwrap();
+
+        let visible = center.list(&NotificationFilter::default()).unwrap();
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].id, alert.id);
+        let archived = NotificationFilter {
+            state: Some(NotificationState::Archived),
+            ..Default::default()
+        };
+        assert_eq!(center.list(&archived).unwrap()[0].id, task.id);
+        let tasks = NotificationFilter {
+            category: Some(NotificationCategory::Task),
+            ..Default::default()
+        };
+        assert!(center.list(&tasks).unwrap().is_empty());
+        assert_eq!(center.unread_count().unwrap(), 0);
+
+        let news = center
+            .record("n3", &notification(NotificationCategory::News, "快讯"))
+            .unwrap();
+        assert_eq!(center.clear(None).unwrap(), 2);
+        assert_eq!(
+            center.get(&news.id).unwrap().state,
+            NotificationState::Unread
+        );
+        assert_eq!(
+            center.clear(Some(std::slice::from_ref(&news.id))).unwrap(),
+            1
+        );
+        drop(conn);
+        remove(path);
+    }
+}
@@ -0,0 +1,459 @@
+//! Delivery of fired alerts beyond the desktop.
+//!
+//! Channels are configured in [`DeliverySettings`] by name, and each alert
+//! lists the channels it goes to. A webhook gets the alert as JSON, signed
+//! with `X-Signature-256` when a secret is set; email goes out over SMTP,
+//! with implicit TLS or STARTTLS; Server酱 and Telegram bots push it to a
+//! phone. Passwords, send keys and bot tokens are kept out of settings in
+//! an encrypted file, by channel name.
+//!
+//! Sends run in the background and a failing channel is only logged, so a
+//! slow mail server never holds up alert evaluation. Errors leave out
+//! request URLs, which carry the Server酱 key and the bot token. Nothing is
+//! sent while a compliance policy disables [`Subsystem::Webhooks`].
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use tauri::{AppHandle, Manager, State};
+
+use crate::compliance::{CompliancePolicy, Subsystem};
+use crate::secure_store::EncryptedFile;
+use crate::settings::SettingsStore;
+use crate::utils::get_timestamp;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Where a channel sends to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChannelTarget {
+    Webhook {
+        url: String,
+    },
+    Email {
+        host: String,
+        port: u16,
+        /// STARTTLS on a plain connection, rather than implicit TLS
+        starttls: bool,
+        username: String,
+        from: String,
+        to: Vec<String>,
+    },
+    /// Server酱 Turbo, by send key
+    ServerChan,
+    Telegram {
+        chat_id: String,
+    },
+}
+
+impl ChannelTarget {
+    /// Whether the channel can't send without its secret; a webhook's only
+    /// signs
+    fn needs_secret(&self) -> bool {
+        !matches!(self, ChannelTarget::Webhook { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryChannel {
+    /// Unique; alerts route by it
+    pub name: String,
+    #[serde(flatten)]
+    pub target: ChannelTarget,
+}
+
+fn mailbox(address: &str) -> Result<Mailbox, String> {
+    address
+        .parse()
+        .map_err(|e| format!("Invalid email address {}: {}", address, e))
+}
+
+impl DeliveryChannel {
+    fn validate(&self) -> Result<(), String> {
+        match &self.target {
+            ChannelTarget::Webhook { url } => {
+                let parsed = url::Url::parse(url)
+                    .map_err(|e| format!("Invalid webhook URL for {}: {}", self.name, e))?;
+                if !matches!(parsed.scheme(), "http" | "https") {
+                    return Err(format!("Webhook {} must use http or https", self.name));
+                }
+            }
+            ChannelTarget::Email {
+                host,
+                port,
+                from,
+                to,
+                ..
+            } => {
+                if host.trim().is_empty() || *port == 0 {
+                    return Err(format!("Email channel {} needs an SMTP server", self.name));
+                }
+                if to.is_empty() {
+                    return Err(format!("Email channel {} needs a recipient", self.name));
+                }
+                mailbox(from)?;
+                to.iter()
+                    .try_for_each(|address| mailbox(address).map(drop))?;
+            }
+            ChannelTarget::ServerChan => {}
+            ChannelTarget::Telegram { chat_id } => {
+                if chat_id.trim().is_empty() {
+                    return Err(format!("Telegram channel {} needs a chat id", self.name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeliverySettings {
+    pub channels: Vec<DeliveryChannel>,
+}
+
+impl DeliverySettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, channel) in self.channels.iter().enumerate() {
+            if channel.name.trim().is_empty() {
+                return Err("Delivery channels need a name".to_string());
+            }
+            if self.channels[..i].iter().any(|c| c.name == channel.name) {
+                return Err(format!("Duplicate delivery channel name: {}", channel.name));
+            }
+            channel.validate()?;
+        }
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Option<&DeliveryChannel> {
+        self.channels.iter().find(|c| c.name == name)
+    }
+
+    /// Check that an alert routes only to configured channels
+    pub fn check_routes(&self, names: &[String]) -> Result<(), String> {
+        match names.iter().find(|name| self.find(name).is_none()) {
+            Some(name) => Err(format!("Unknown delivery channel: {}", name)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Channel secrets by channel name
+pub struct DeliveryVault {
+    file: EncryptedFile,
+    secrets: Mutex<HashMap<String, String>>,
+}
+
+impl DeliveryVault {
+    pub fn load(path: PathBuf, key_path: PathBuf) -> Self {
+        let file = EncryptedFile::new(path, &key_path);
+        let secrets = file.read();
+        Self {
+            file,
+            secrets: Mutex::new(secrets),
+        }
+    }
+
+    fn get(&self, channel: &str) -> Option<String> {
+        self.secrets.lock().unwrap().get(channel).cloned()
+    }
+
+    fn set(&self, channel: &str, secret: Option<String>) -> Result<(), String> {
+        let mut secrets = self.secrets.lock().unwrap();
+        match secret {
+            Some(secret) => secrets.insert(channel.to_string(), secret),
+            None => secrets.remove(channel),
+        };
+        self.file.write(&*secrets, "delivery channel secrets")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertMessage {
+    pub title: String,
+    pub body: String,
+}
+
+/// `X-Signature-256` of a webhook payload
+fn signature(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(payload);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn http_error(channel: &str) -> impl FnOnce(reqwest::Error) -> String + '_ {
+    move |e| format!("Failed to deliver to {}: {}", channel, e.without_url())
+}
+
+async fn send_email(
+    channel: &DeliveryChannel,
+    password: &str,
+    message: &AlertMessage,
+) -> Result<(), String> {
+    let ChannelTarget::Email {
+        host,
+        port,
+        starttls,
+        username,
+        from,
+        to,
+    } = &channel.target
+    else {
+        return Ok(());
+    };
+    let failed =
+        |e: &dyn std::fmt::Display| format!("Failed to deliver to {}: {}", channel.name, e);
+    let mut builder = lettre::Message::builder()
+        .from(mailbox(from)?)
+        .subject(&message.title)
+        .header(ContentType::TEXT_PLAIN);
+    for address in to {
+        builder = builder.to(mailbox(address)?);
+    }
+    let email = builder.body(message.body.clone()).map_err(|e| failed(&e))?;
+    let relay = if *starttls {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+    };
+    let transport = relay
+        .map_err(|e| failed(&e))?
+        .port(*port)
+        .credentials(Credentials::new(username.clone(), password.to_string()))
+        .timeout(Some(SEND_TIMEOUT))
+        .build();
+    transport.send(email).await.map_err(|e| failed(&e))?;
+    Ok(())
+}
+
+async fn send(
+    channel: &DeliveryChannel,
+    secret: Option<&str>,
+    message: &AlertMessage,
+) -> Result<(), String> {
+    let secret = match secret {
+        None if channel.target.needs_secret() => {
+            return Err(format!("No secret set for channel {}", channel.name));
+        }
+        secret => secret,
+    };
+    let client = reqwest::Client::builder()
+        .timeout(SEND_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    let request = match &channel.target {
+        ChannelTarget::Webhook { url } => {
+            let payload = serde_json::to_vec(&json!({
+                "title": message.title,
+                "body": message.body,
+                "sent_at": get_timestamp(),
+            }))
+            .map_err(|e| format!("Failed to serialize alert: {}", e))?;
+            let mut request = client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json");
+            if let Some(secret) = secret {
+                request = request.header("X-Signature-256", signature(secret, &payload));
+            }
+            request.body(payload)
+        }
+        ChannelTarget::Email { .. } => {
+            return send_email(channel, secret.unwrap_or_default(), message).await;
+        }
+        ChannelTarget::ServerChan => client
+            .post(format!(
+                "https://sctapi.ftqq.com/{}.send",
+                secret.unwrap_or_default()
+            ))
+            .form(&[("title", &message.title), ("desp", &message.body)]),
+        ChannelTarget::Telegram { chat_id } => client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                secret.unwrap_or_default()
+            ))
+            .json(&json!({
+                "chat_id": chat_id,
+                "text": format!("{}\n{}", message.title, message.body),
+            })),
+    };
+    let response = request
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(http_error(&channel.name))?;
+    if channel.target == ChannelTarget::ServerChan {
+        // Server酱 answers 200 with the failure in `code`
+        let reply: serde_json::Value = response.json().await.map_err(http_error(&channel.name))?;
+        if reply["code"].as_i64() != Some(0) {
+            return Err(format!(
+                "Failed to deliver to {}: {}",
+                channel.name,
+                reply["message"].as_str().unwrap_or("unknown error")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Send a fired alert to the channels it routes to, in the background
+pub fn deliver(app: &AppHandle, channels: &[String], message: AlertMessage) {
+    if channels.is_empty() {
+        return;
+    }
+    if let Err(e) = app.state::<CompliancePolicy>().check(Subsystem::Webhooks) {
+        warn!("Not delivering \"{}\": {}", message.title, e);
+        return;
+    }
+    let settings = app.state::<SettingsStore>().get().delivery;
+    let vault = app.state::<DeliveryVault>();
+    for name in channels {
+        let Some(channel) = settings.find(name).cloned() else {
+            warn!("Alert routed to unknown delivery channel {}", name);
+            continue;
+        };
+        let secret = vault.get(name);
+        let message = message.clone();
+        tauri::async_runtime::spawn(async move {
+            match send(&channel, secret.as_deref(), &message).await {
+                Ok(()) => info!("Delivered \"{}\" to {}", message.title, channel.name),
+                Err(e) => warn!("{}", e),
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStatus {
+    #[serde(flatten)]
+    pub channel: DeliveryChannel,
+    pub has_secret: bool,
+}
+
+#[tauri::command]
+pub fn list_delivery_channels(
+    settings: State<'_, SettingsStore>,
+    vault: State<'_, DeliveryVault>,
+) -> Result<Vec<ChannelStatus>, String> {
+    Ok(settings
+        .get()
+        .delivery
+        .channels
+        .into_iter()
+        .map(|channel| ChannelStatus {
+            has_secret: vault.get(&channel.name).is_some(),
+            channel,
+        })
+        .collect())
+}
+
+/// Store a channel's password, send key, bot token or webhook signing secret
+#[tauri::command]
+pub fn set_delivery_secret(
+    settings: State<'_, SettingsStore>,
+    vault: State<'_, DeliveryVault>,
+    channel: String,
+    secret: String,
+) -> Result<(), String> {
+    if settings.get().delivery.find(&channel).is_none() {
+        return Err(format!("Unknown delivery channel: {}", channel));
+    }
+    let secret = secret.trim().to_string();
+    if secret.is_empty() {
+        return Err("Secret is required".to_string());
+    }
+    vault.set(&channel, Some(secret))
+}
+
+#[tauri::command]
+pub fn remove_delivery_secret(
+    vault: State<'_, DeliveryVault>,
+    channel: String,
+) -> Result<(), String> {
+    vault.set(&channel, None)
+}
+
+/// Send a test message through a channel, waiting for the outcome
+#[tauri::command]
+pub async fn test_delivery_channel(app: AppHandle, channel: String) -> Result<(), String> {
+    app.state::<CompliancePolicy>().check(Subsystem::Webhooks)?;
+    let settings = app.state::<SettingsStore>().get().delivery;
+    let target = settings
+        .find(&channel)
+        .cloned()
+        .ok_or_else(|| format!("Unknown delivery channel: {}", channel))?;
+    let secret = app.state::<DeliveryVault>().get(&channel);
+    let message = AlertMessage {
+        title: "智股通测试消息".to_string(),
+        body: format!("渠道 {} 已可接收提醒", channel),
+    };
+    send(&target, secret.as_deref(), &message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_settings() {
+        let channel = |name: &str, target| DeliveryChannel {
+            name: name.to_string(),
+            target,
+        };
+        let email = ChannelTarget::Email {
+            host: "smtp.qq.com".to_string(),
+            port: 465,
+            starttls: false,
+            username: "alerts@qq.com".to_string(),
+            from: "智股通 <alerts@qq.com>".to_string(),
+            to: vec!["me@example.com".to_string()],
+        };
+        let mut settings = DeliverySettings {
+            channels: vec![
+                channel("mail", email),
+                channel("phone", ChannelTarget::ServerChan),
+            ],
+        };
+        assert!(settings.validate().is_ok());
+        assert!(settings.check_routes(&["phone".to_string()]).is_ok());
+        assert!(settings.check_routes(&["pager".to_string()]).is_err());
+        assert!(ChannelTarget::ServerChan.needs_secret());
+
+        settings.channels.push(channel(
+            "hook",
+            ChannelTarget::Webhook {
+                url: "ftp://example.com".to_string(),
+            },
+        ));
+        assert!(settings.validate().is_err());
+        settings.channels[2] = channel("phone", ChannelTarget::ServerChan);
+        assert!(settings.validate().is_err());
+
+        let stored: DeliveryChannel =
+            serde_json::from_str(r#"{"name":"tg","kind":"telegram","chat_id":"42"}"#).unwrap();
+        assert_eq!(
+            stored.target,
+            ChannelTarget::Telegram {
+                chat_id: "42".to_string()
+            }
+        );
+        // The signature GitHub-style receivers check, HMAC-SHA256 of the body
+        assert_eq!(
+            signature("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
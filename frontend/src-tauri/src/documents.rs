@@ -0,0 +1,385 @@
+//! Research documents: transcripts and other long-form text.
+//!
+//! Documents are stored as ordered chunks of a few hundred characters, each
+//! keeping its position in the source media where there is one. Chunks are
+//! the unit of keyword search and of retrieval for AI answers, so a hit can
+//! point back to the exact passage (or minute of a call) it came from.
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+use crate::fields::select;
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+/// Target chunk length in characters
+pub const CHUNK_CHARS: usize = 400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Transcript,
+    Article,
+    Note,
+    /// Exchange filings, e.g. from a news backfill
+    Announcement,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub text: String,
+    /// Offsets into the source media, for transcripts
+    pub start_ms: Option<u64>,
+    pub end_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResearchDocument {
+    pub id: String,
+    pub kind: DocumentKind,
+    pub title: String,
+    pub symbol: Option<String>,
+    /// File path or URL the document was imported from
+    pub source: String,
+    pub chunks: Vec<Chunk>,
+    pub created_at: String,
+}
+
+/// A document without its chunks, for listings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSummary {
+    pub id: String,
+    pub kind: DocumentKind,
+    pub title: String,
+    pub symbol: Option<String>,
+    pub source: String,
+    pub chunks: usize,
+    pub created_at: String,
+}
+
+/// One chunk with the document it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Passage {
+    pub document_id: String,
+    pub chunk_index: usize,
+    pub title: String,
+    pub symbol: Option<String>,
+    pub text: String,
+    pub start_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub document_id: String,
+    pub title: String,
+    pub chunk_index: usize,
+    pub text: String,
+    pub start_ms: Option<u64>,
+    pub score: usize,
+}
+
+impl ResearchDocument {
+    pub fn new(
+        kind: DocumentKind,
+        title: &str,
+        symbol: Option<String>,
+        source: &str,
+        chunks: Vec<Chunk>,
+    ) -> Self {
+        Self {
+            id: generate_id("doc"),
+            kind,
+            title: title.to_string(),
+            symbol: symbol.map(|s| s.trim().to_uppercase()),
+            source: source.to_string(),
+            chunks,
+            created_at: get_timestamp(),
+        }
+    }
+
+    pub fn summary(&self) -> DocumentSummary {
+        DocumentSummary {
+            id: self.id.clone(),
+            kind: self.kind,
+            title: self.title.clone(),
+            symbol: self.symbol.clone(),
+            source: self.source.clone(),
+            chunks: self.chunks.len(),
+            created_at: self.created_at.clone(),
+        }
+    }
+}
+
+/// Merge consecutive timed segments into chunks of about `CHUNK_CHARS`
+pub fn chunk_segments(segments: &[(String, u64, u64)]) -> Vec<Chunk> {
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut current: Option<Chunk> = None;
+    for (text, start, end) in segments {
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let chunk = current.get_or_insert_with(|| Chunk {
+            text: String::new(),
+            start_ms: Some(*start),
+            end_ms: Some(*end),
+        });
+        if !chunk.text.is_empty() {
+            chunk.text.push(' ');
+        }
+        chunk.text.push_str(text);
+        chunk.end_ms = Some(*end);
+        if chunk.text.chars().count() >= CHUNK_CHARS {
+            chunks.extend(current.take());
+        }
+    }
+    chunks.extend(current);
+    chunks
+}
+
+/// Split plain text into chunks of about `CHUNK_CHARS` at line and sentence ends
+pub fn chunk_text(text: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for sentence in text.split_inclusive(['\n', '。', '！', '？']) {
+        current.push_str(sentence.trim());
+        if current.chars().count() >= CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+        .into_iter()
+        .map(|text| Chunk {
+            text,
+            start_ms: None,
+            end_ms: None,
+        })
+        .collect()
+}
+
+pub struct DocumentStore {
+    path: PathBuf,
+    documents: RwLock<Vec<ResearchDocument>>,
+}
+
+impl DocumentStore {
+    pub fn load(path: PathBuf) -> Self {
+        let documents = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            documents: RwLock::new(documents),
+        }
+    }
+
+    pub fn add(
+        &self,
+        kind: DocumentKind,
+        title: &str,
+        symbol: Option<String>,
+        source: &str,
+        chunks: Vec<Chunk>,
+    ) -> Result<ResearchDocument, String> {
+        let document = ResearchDocument::new(kind, title, symbol, source, chunks);
+        self.add_all(vec![document.clone()])?;
+        Ok(document)
+    }
+
+    /// Add several documents with a single write
+    pub fn add_all(&self, new: Vec<ResearchDocument>) -> Result<(), String> {
+        if new.is_empty() {
+            return Ok(());
+        }
+        let mut documents = self.documents.write().unwrap();
+        documents.extend(new);
+        let content = serde_json::to_string(&*documents)
+            .map_err(|e| format!("Failed to serialize documents: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save documents: {}", e))?;
+        Ok(())
+    }
+
+    /// Whether a document imported from `source` is already stored
+    pub fn has_source(&self, source: &str) -> bool {
+        self.documents
+            .read()
+            .unwrap()
+            .iter()
+            .any(|d| d.source == source)
+    }
+
+    pub fn get(&self, id: &str) -> Option<ResearchDocument> {
+        self.documents
+            .read()
+            .unwrap()
+            .iter()
+            .find(|d| d.id == id)
+            .cloned()
+    }
+
+    pub fn list(&self, symbol: Option<&str>) -> Vec<DocumentSummary> {
+        self.documents
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|d| symbol.map_or(true, |s| d.symbol.as_deref() == Some(s)))
+            .map(ResearchDocument::summary)
+            .collect()
+    }
+
+    /// Every chunk of every document, in storage order
+    pub fn passages(&self) -> Vec<Passage> {
+        self.documents
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|d| {
+                d.chunks.iter().enumerate().map(|(i, chunk)| Passage {
+                    document_id: d.id.clone(),
+                    chunk_index: i,
+                    title: d.title.clone(),
+                    symbol: d.symbol.clone(),
+                    text: chunk.text.clone(),
+                    start_ms: chunk.start_ms,
+                })
+            })
+            .collect()
+    }
+
+    /// Chunks containing every whitespace-separated term of the query, best first
+    pub fn search(&self, query: &str, symbol: Option<&str>, limit: usize) -> Vec<SearchHit> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let documents = self.documents.read().unwrap();
+        let mut hits: Vec<SearchHit> = documents
+            .iter()
+            .filter(|d| symbol.map_or(true, |s| d.symbol.as_deref() == Some(s)))
+            .flat_map(|d| {
+                let terms = &terms;
+                d.chunks.iter().enumerate().filter_map(move |(i, chunk)| {
+                    let text = chunk.text.to_lowercase();
+                    let counts: Vec<usize> = terms
+                        .iter()
+                        .map(|t| text.matches(t.as_str()).count())
+                        .collect();
+                    if counts.contains(&0) {
+                        return None;
+                    }
+                    Some(SearchHit {
+                        document_id: d.id.clone(),
+                        title: d.title.clone(),
+                        chunk_index: i,
+                        text: chunk.text.clone(),
+                        start_ms: chunk.start_ms,
+                        score: counts.iter().sum(),
+                    })
+                })
+            })
+            .collect();
+        hits.sort_by_key(|h| std::cmp::Reverse(h.score));
+        hits.truncate(limit);
+        hits
+    }
+}
+
+/// Imported research documents, optionally for one symbol
+#[tauri::command]
+pub fn list_documents(
+    store: State<'_, DocumentStore>,
+    symbol: Option<String>,
+) -> Result<Vec<DocumentSummary>, String> {
+    Ok(store.list(symbol.as_deref()))
+}
+
+#[tauri::command]
+pub fn get_document(
+    store: State<'_, DocumentStore>,
+    id: String,
+    fields: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let document = store
+        .get(&id)
+        .ok_or_else(|| format!("Document not found: {}", id))?;
+    select(&document, fields.as_deref())
+}
+
+/// Keyword search over document passages
+#[tauri::command]
+pub fn search_documents(
+    store: State<'_, DocumentStore>,
+    query: String,
+    symbol: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    Ok(store.search(&query, symbol.as_deref(), limit.unwrap_or(20)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunking_and_search() {
+        let long = "营收".repeat(CHUNK_CHARS / 2);
+        let segments = vec![
+            ("各位投资者好".to_string(), 0, 2_000),
+            (long, 2_000, 60_000),
+            ("毛利率 margin 提升".to_string(), 60_000, 63_000),
+            ("  ".to_string(), 63_000, 64_000),
+        ];
+        let chunks = chunk_segments(&segments);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].start_ms, Some(0));
+        assert_eq!(chunks[0].end_ms, Some(60_000));
+        assert_eq!(chunks[1].start_ms, Some(60_000));
+
+        let path = std::env::temp_dir().join(format!("ssi-docs-{}.json", std::process::id()));
+        let store = DocumentStore::load(path.clone());
+        let doc = store
+            .add(
+                DocumentKind::Transcript,
+                "业绩说明会",
+                Some("sh600519".to_string()),
+                "call.mp3",
+                chunks,
+            )
+            .unwrap();
+        assert_eq!(doc.symbol.as_deref(), Some("SH600519"));
+
+        let hits = store.search("毛利率 MARGIN", None, 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk_index, 1);
+        assert_eq!(hits[0].start_ms, Some(60_000));
+        assert!(store.search("毛利率 净利润", None, 10).is_empty());
+        assert!(store.search("营收", Some("SZ000001"), 10).is_empty());
+        assert_eq!(
+            DocumentStore::load(path.clone())
+                .list(Some("SH600519"))
+                .len(),
+            1
+        );
+        assert!(store.has_source("call.mp3"));
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_chunk_text_at_sentence_ends() {
+        let sentence = format!("{}。", "利润".repeat(CHUNK_CHARS / 4));
+        let text = format!("关于年度报告的公告\n{}{}{}", sentence, sentence, sentence);
+        let chunks = chunk_text(&text);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].text.starts_with("关于年度报告的公告"));
+        assert!(chunks.iter().all(|c| c.text.ends_with('。')));
+        assert!(chunk_text("  \n ").is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Provider response validation and drift detection.
+//!
+//! Providers change their APIs without notice. Responses are checked against
+//! a small field schema before they are parsed; a response with missing or
+//! retyped fields is rejected and recorded as a drift incident with a sample
+//! of the payload, instead of flowing through as zeros and empty lists.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::utils::{get_timestamp, read_from_file, write_to_file};
+
+/// Incidents kept in the log
+const MAX_INCIDENTS: usize = 200;
+
+/// Characters of the offending payload kept with an incident
+const SAMPLE_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    String,
+    Number,
+    Bool,
+    Object,
+    Array,
+    NonEmptyArray,
+}
+
+impl FieldKind {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            FieldKind::String => value.is_string(),
+            FieldKind::Number => value.is_number(),
+            FieldKind::Bool => value.is_boolean(),
+            FieldKind::Object => value.is_object(),
+            FieldKind::Array => value.is_array(),
+            FieldKind::NonEmptyArray => value.as_array().is_some_and(|a| !a.is_empty()),
+        }
+    }
+}
+
+/// An expected field; `[]` after a segment applies the rest of the path to every element,
+/// e.g. `data.diff[].f12`
+pub struct Field {
+    pub path: &'static str,
+    pub kind: FieldKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    pub path: String,
+    pub expected: FieldKind,
+    /// JSON type found instead, or `missing`
+    pub found: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftIncident {
+    pub provider: String,
+    pub endpoint: String,
+    pub violations: Vec<Violation>,
+    pub sample: String,
+    pub detected_at: String,
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn check(
+    value: &Value,
+    segments: &[&str],
+    prefix: String,
+    field: &Field,
+    out: &mut Vec<Violation>,
+) {
+    let Some((segment, rest)) = segments.split_first() else {
+        if !field.kind.matches(value) {
+            out.push(Violation {
+                path: prefix,
+                expected: field.kind,
+                found: type_name(value).to_string(),
+            });
+        }
+        return;
+    };
+    let (key, each) = match segment.strip_suffix("[]") {
+        Some(key) => (key, true),
+        None => (*segment, false),
+    };
+    let path = if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    };
+    let Some(child) = value.get(key).filter(|v| !v.is_null()) else {
+        out.push(Violation {
+            path,
+            expected: if each { FieldKind::Array } else { field.kind },
+            found: "missing".to_string(),
+        });
+        return;
+    };
+    if !each {
+        return check(child, rest, path, field, out);
+    }
+    let Some(items) = child.as_array() else {
+        out.push(Violation {
+            path,
+            expected: FieldKind::Array,
+            found: type_name(child).to_string(),
+        });
+        return;
+    };
+    for (i, item) in items.iter().enumerate() {
+        let before = out.len();
+        check(item, rest, format!("{}[{}]", path, i), field, out);
+        // One bad element is enough to describe the drift
+        if out.len() > before {
+            break;
+        }
+    }
+}
+
+/// Check a response against its expected fields
+pub fn validate(value: &Value, schema: &[Field]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for field in schema {
+        let segments: Vec<&str> = field.path.split('.').collect();
+        check(value, &segments, String::new(), field, &mut violations);
+    }
+    violations
+}
+
+fn sample(body: &str) -> String {
+    match body.char_indices().nth(SAMPLE_CHARS) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+pub struct DriftLog {
+    path: PathBuf,
+    incidents: Mutex<VecDeque<DriftIncident>>,
+}
+
+impl DriftLog {
+    pub fn load(path: PathBuf) -> Self {
+        let incidents = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            incidents: Mutex::new(incidents),
+        }
+    }
+
+    fn record(&self, incident: DriftIncident) {
+        let mut incidents = self.incidents.lock().unwrap();
+        incidents.push_back(incident);
+        while incidents.len() > MAX_INCIDENTS {
+            incidents.pop_front();
+        }
+        let saved = serde_json::to_string(&*incidents)
+            .map_err(|e| e.to_string())
+            .and_then(|content| write_to_file(&self.path, &content).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            warn!("Failed to save provider drift log: {}", e);
+        }
+    }
+
+    /// Parse and validate a provider response, recording an incident when it has drifted
+    pub fn check_response(
+        &self,
+        app: &AppHandle,
+        provider: &str,
+        endpoint: &str,
+        body: &str,
+        schema: &[Field],
+    ) -> Result<Value, String> {
+        let (value, violations) = match serde_json::from_str::<Value>(body) {
+            Ok(value) => {
+                let violations = validate(&value, schema);
+                (Some(value), violations)
+            }
+            Err(e) => (
+                None,
+                vec![Violation {
+                    path: String::new(),
+                    expected: FieldKind::Object,
+                    found: format!("invalid JSON ({})", e),
+                }],
+            ),
+        };
+        match value {
+            Some(value) if violations.is_empty() => Ok(value),
+            _ => {
+                warn!(
+                    "Provider drift detected for {} {}: {:?}",
+                    provider, endpoint, violations
+                );
+                let incident = DriftIncident {
+                    provider: provider.to_string(),
+                    endpoint: endpoint.to_string(),
+                    violations,
+                    sample: sample(body),
+                    detected_at: get_timestamp(),
+                };
+                if let Err(e) = app.emit("provider-drift", incident.clone()) {
+                    warn!("Failed to emit provider-drift event: {}", e);
+                }
+                self.record(incident);
+                Err(format!(
+                    "{} returned an unexpected response from {}",
+                    provider, endpoint
+                ))
+            }
+        }
+    }
+
+    pub fn incidents(&self) -> Vec<DriftIncident> {
+        self.incidents
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        self.incidents.lock().unwrap().clear();
+        write_to_file(&self.path, "[]").map_err(|e| format!("Failed to clear drift log: {}", e))
+    }
+}
+
+/// Recorded provider drift incidents, newest first
+#[tauri::command]
+pub fn get_provider_drift(log: State<'_, DriftLog>) -> Result<Vec<DriftIncident>, String> {
+    Ok(log.incidents())
+}
+
+/// Forget recorded drift incidents
+#[tauri::command]
+pub fn clear_provider_drift(log: State<'_, DriftLog>) -> Result<(), String> {
+    log.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCHEMA: &[Field] = &[
+        Field {
+            path: "data.diff",
+            kind: FieldKind::NonEmptyArray,
+        },
+        Field {
+            path: "data.diff[].f12",
+            kind: FieldKind::String,
+        },
+        Field {
+            path: "data.diff[].f13",
+            kind: FieldKind::Number,
+        },
+    ];
+
+    #[test]
+    fn test_validate_detects_drift() {
+        let good = json!({"data": {"diff": [{"f12": "600000", "f13": 1}]}});
+        assert!(validate(&good, SCHEMA).is_empty());
+
+        let retyped =
+            json!({"data": {"diff": [{"f12": "600000", "f13": 1}, {"f12": 600001, "f13": 1}]}});
+        assert_eq!(
+            validate(&retyped, SCHEMA),
+            vec![Violation {
+                path: "data.diff[1].f12".to_string(),
+                expected: FieldKind::String,
+                found: "number".to_string(),
+            }]
+        );
+
+        let emptied = json!({"data": null});
+        let violations = validate(&emptied, SCHEMA);
+        assert_eq!(violations.len(), 3);
+        assert!(violations.iter().all(|v| v.found == "missing"));
+
+        let empty_list = json!({"data": {"diff": []}});
+        assert_eq!(validate(&empty_list, SCHEMA).len(), 1);
+    }
+}
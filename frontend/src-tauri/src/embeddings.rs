@@ -0,0 +1,709 @@
+//! Embedding index over research document chunks, for semantic search.
+//!
+//! Chunks are embedded by a local Ollama-compatible model server. Vectors
+//! from different models live in different spaces, so the index serves
+//! exactly one model at a time and queries are always embedded with that
+//! model, whatever the settings currently select. Switching models is a
+//! migration: the new model's vectors are built next to the live ones,
+//! batch by batch with progress and checkpoints, and replace them in one
+//! step once every chunk is covered, so search keeps working on the old
+//! model until then and an interrupted migration picks up where it stopped.
+//! Compaction drops vectors whose chunk is gone or whose text changed and
+//! runs after every rebuild and weekly at startup.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use chrono::{Local, NaiveDate};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::documents::{DocumentStore, Passage};
+use crate::executor::{Priority, ResourceClass};
+use crate::settings::SettingsStore;
+use crate::tasks::{spawn_task_with, TaskContext, TaskHandle};
+use crate::utils::{get_timestamp, read_from_file, write_to_file};
+
+/// Chunks sent to the model server per request
+const BATCH_SIZE: usize = 32;
+
+/// Days between automatic compactions
+const COMPACT_INTERVAL_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingSettings {
+    /// Base URL of the model server
+    pub endpoint: String,
+    /// Model new indexes are built with
+    pub model: String,
+}
+
+impl Default for EmbeddingSettings {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://127.0.0.1:11434".to_string(),
+            model: "bge-m3".to_string(),
+        }
+    }
+}
+
+impl EmbeddingSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        url::Url::parse(&self.endpoint)
+            .map_err(|e| format!("Invalid embedding endpoint {}: {}", self.endpoint, e))?;
+        if self.model.trim().is_empty() {
+            return Err("Embedding model is required".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Embedding {
+    document_id: String,
+    chunk_index: usize,
+    /// Hash of the chunk text the vector was computed from
+    text_hash: String,
+    vector: Vec<f32>,
+}
+
+impl Embedding {
+    fn key(&self) -> (&str, usize) {
+        (&self.document_id, self.chunk_index)
+    }
+}
+
+/// Vectors of the model being migrated to, not yet searchable
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Migration {
+    model: String,
+    started_at: String,
+    entries: Vec<Embedding>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexData {
+    /// Model every live vector comes from
+    model: Option<String>,
+    entries: Vec<Embedding>,
+    migration: Option<Migration>,
+    compacted_on: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationStatus {
+    pub model: String,
+    pub started_at: String,
+    pub embedded: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingStatus {
+    /// Model search runs on; none before the first build
+    pub model: Option<String>,
+    /// Model the settings select for the next rebuild
+    pub configured_model: String,
+    pub embedded: usize,
+    /// Chunks without an up-to-date vector from the live model
+    pub pending: usize,
+    pub migration: Option<MigrationStatus>,
+    pub compacted_on: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionReport {
+    /// Vectors of chunks that no longer exist
+    pub orphans: usize,
+    /// Vectors of chunks whose text changed since, and duplicates
+    pub stale: usize,
+    pub kept: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticHit {
+    pub document_id: String,
+    pub title: String,
+    pub chunk_index: usize,
+    pub text: String,
+    pub start_ms: Option<u64>,
+    /// Cosine similarity to the query
+    pub similarity: f32,
+}
+
+fn text_hash(text: &str) -> String {
+    hex::encode(&Sha256::digest(text.as_bytes())[..16])
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 {
+        0.0
+    } else {
+        dot / denominator
+    }
+}
+
+/// Passages whose text has no vector among `entries`
+fn uncovered<'a>(entries: &[Embedding], passages: &'a [Passage]) -> Vec<&'a Passage> {
+    let covered: HashSet<(&str, usize, &str)> = entries
+        .iter()
+        .map(|e| (e.document_id.as_str(), e.chunk_index, e.text_hash.as_str()))
+        .collect();
+    passages
+        .iter()
+        .filter(|p| {
+            !covered.contains(&(
+                p.document_id.as_str(),
+                p.chunk_index,
+                text_hash(&p.text).as_str(),
+            ))
+        })
+        .collect()
+}
+
+/// Keep one current vector per live chunk
+fn compact_entries(
+    entries: &mut Vec<Embedding>,
+    current: &HashMap<(&str, usize), String>,
+    report: &mut CompactionReport,
+) {
+    let mut seen = HashSet::new();
+    let mut kept = Vec::with_capacity(entries.len());
+    // Newest first, so a re-embedded chunk keeps its latest vector
+    for entry in entries.drain(..).rev() {
+        match current.get(&entry.key()) {
+            None => report.orphans += 1,
+            Some(hash) if *hash != entry.text_hash => report.stale += 1,
+            Some(_) if !seen.insert((entry.document_id.clone(), entry.chunk_index)) => {
+                report.stale += 1
+            }
+            Some(_) => kept.push(entry),
+        }
+    }
+    kept.reverse();
+    *entries = kept;
+}
+
+pub struct EmbeddingIndex {
+    path: PathBuf,
+    data: Mutex<IndexData>,
+    /// Set while a rebuild task runs
+    rebuilding: AtomicBool,
+}
+
+impl EmbeddingIndex {
+    pub fn load(path: PathBuf) -> Self {
+        let data = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            data: Mutex::new(data),
+            rebuilding: AtomicBool::new(false),
+        }
+    }
+
+    fn save(&self, data: &IndexData) -> Result<(), String> {
+        let content = serde_json::to_string(data)
+            .map_err(|e| format!("Failed to serialize embeddings: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save embeddings: {}", e))
+    }
+
+    pub fn model(&self) -> Option<String> {
+        self.data.lock().unwrap().model.clone()
+    }
+
+    pub fn status(&self, passages: &[Passage], configured_model: &str) -> EmbeddingStatus {
+        let data = self.data.lock().unwrap();
+        EmbeddingStatus {
+            model: data.model.clone(),
+            configured_model: configured_model.to_string(),
+            embedded: data.entries.len(),
+            pending: uncovered(&data.entries, passages).len(),
+            migration: data.migration.as_ref().map(|m| MigrationStatus {
+                model: m.model.clone(),
+                started_at: m.started_at.clone(),
+                embedded: m.entries.len(),
+                total: passages.len(),
+            }),
+            compacted_on: data.compacted_on,
+        }
+    }
+
+    /// Prepare to embed with `model`, starting a migration when it is not the live model
+    fn begin(&self, model: &str) -> Result<(), String> {
+        let mut data = self.data.lock().unwrap();
+        if data.model.as_deref() == Some(model) {
+            return Ok(());
+        }
+        if data.model.is_none() && data.entries.is_empty() {
+            // Nothing is searchable yet, so there is nothing to keep serving
+            data.model = Some(model.to_string());
+            data.migration = None;
+        } else if data.migration.as_ref().map(|m| m.model.as_str()) != Some(model) {
+            info!(
+                "Starting embedding migration from {} to {}",
+                data.model.as_deref().unwrap_or("none"),
+                model
+            );
+            data.migration = Some(Migration {
+                model: model.to_string(),
+                started_at: get_timestamp(),
+                entries: Vec::new(),
+            });
+        }
+        self.save(&data)
+    }
+
+    /// Passages `model` still has to embed
+    fn pending(&self, model: &str, passages: &[Passage]) -> Vec<Passage> {
+        let data = self.data.lock().unwrap();
+        let entries = match &data.migration {
+            Some(migration) if migration.model == model => &migration.entries,
+            _ if data.model.as_deref() == Some(model) => &data.entries,
+            _ => return passages.to_vec(),
+        };
+        uncovered(entries, passages).into_iter().cloned().collect()
+    }
+
+    /// Add a batch of vectors computed with `model`
+    fn store(&self, model: &str, batch: Vec<Embedding>) -> Result<(), String> {
+        let mut data = self.data.lock().unwrap();
+        let live = data.model.as_deref() == Some(model);
+        match &mut data.migration {
+            Some(migration) if migration.model == model => migration.entries.extend(batch),
+            _ if live => data.entries.extend(batch),
+            _ => return Err(format!("No embedding build in progress for {}", model)),
+        }
+        self.save(&data)
+    }
+
+    /// Make a completed migration live
+    fn finish(&self, model: &str) -> Result<(), String> {
+        let mut data = self.data.lock().unwrap();
+        if data.migration.as_ref().map(|m| m.model.as_str()) != Some(model) {
+            return Ok(());
+        }
+        let migration = data.migration.take().unwrap_or_default();
+        info!(
+            "Embedding index switched from {} to {}",
+            data.model.as_deref().unwrap_or("none"),
+            model
+        );
+        data.model = Some(migration.model);
+        data.entries = migration.entries;
+        self.save(&data)
+    }
+
+    /// Drop vectors of deleted or changed chunks, live and migrating alike
+    pub fn compact(
+        &self,
+        passages: &[Passage],
+        today: NaiveDate,
+    ) -> Result<CompactionReport, String> {
+        let hashes: Vec<String> = passages.iter().map(|p| text_hash(&p.text)).collect();
+        let current: HashMap<(&str, usize), String> = passages
+            .iter()
+            .zip(hashes)
+            .map(|(p, hash)| ((p.document_id.as_str(), p.chunk_index), hash))
+            .collect();
+        let mut report = CompactionReport::default();
+        let mut data = self.data.lock().unwrap();
+        compact_entries(&mut data.entries, &current, &mut report);
+        if let Some(migration) = &mut data.migration {
+            compact_entries(&mut migration.entries, &current, &mut report);
+        }
+        report.kept = data.entries.len();
+        data.compacted_on = Some(today);
+        self.save(&data)?;
+        Ok(report)
+    }
+
+    /// Live vectors most similar to `query`, best first
+    fn search(
+        &self,
+        query: &[f32],
+        allowed: impl Fn(&str) -> bool,
+        limit: usize,
+    ) -> Vec<(String, usize, f32)> {
+        let data = self.data.lock().unwrap();
+        let mut scored: Vec<(String, usize, f32)> = data
+            .entries
+            .iter()
+            .filter(|e| e.vector.len() == query.len() && allowed(&e.document_id))
+            .map(|e| {
+                (
+                    e.document_id.clone(),
+                    e.chunk_index,
+                    cosine(query, &e.vector),
+                )
+            })
+            .collect();
+        scored.sort_by(|a, b| b.2.total_cmp(&a.2));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// Embed texts with `model` on the configured server
+async fn embed(
+    settings: &EmbeddingSettings,
+    model: &str,
+    input: &[String],
+) -> Result<Vec<Vec<f32>>, String> {
+    let url = format!("{}/api/embed", settings.endpoint.trim_end_matches('/'));
+    let response: EmbedResponse = reqwest::Client::new()
+        .post(&url)
+        .json(&json!({ "model": model, "input": input }))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to reach embedding model {}: {}", model, e))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse embeddings: {}", e))?;
+    if response.embeddings.len() != input.len() {
+        return Err(format!(
+            "Embedding model {} returned {} vectors for {} texts",
+            model,
+            response.embeddings.len(),
+            input.len()
+        ));
+    }
+    Ok(response.embeddings)
+}
+
+/// Clears the rebuilding flag when a rebuild ends, however it ends
+struct RebuildGuard {
+    app: AppHandle,
+}
+
+impl Drop for RebuildGuard {
+    fn drop(&mut self) {
+        self.app
+            .state::<EmbeddingIndex>()
+            .rebuilding
+            .store(false, Ordering::Relaxed);
+    }
+}
+
+fn rebuild(ctx: &TaskContext, app: &AppHandle, model: &str) -> Result<EmbeddingStatus, String> {
+    let settings = app.state::<SettingsStore>();
+    let index = app.state::<EmbeddingIndex>();
+    let passages = app.state::<DocumentStore>().passages();
+    let endpoint = settings.get().embeddings;
+
+    index.begin(model)?;
+    let pending = index.pending(model, &passages);
+    let total = pending.len() as u64;
+    let mut done = 0u64;
+    ctx.report("embed", done, total);
+    for batch in pending.chunks(BATCH_SIZE) {
+        ctx.checkpoint()?;
+        let texts: Vec<String> = batch.iter().map(|p| p.text.clone()).collect();
+        let vectors = tauri::async_runtime::block_on(embed(&endpoint, model, &texts))?;
+        let embeddings = batch
+            .iter()
+            .zip(vectors)
+            .map(|(passage, vector)| Embedding {
+                document_id: passage.document_id.clone(),
+                chunk_index: passage.chunk_index,
+                text_hash: text_hash(&passage.text),
+                vector,
+            })
+            .collect();
+        index.store(model, embeddings)?;
+        done += batch.len() as u64;
+        ctx.report("embed", done, total);
+    }
+    index.finish(model)?;
+
+    let mut updated = settings.get();
+    if updated.embeddings.model != model {
+        updated.embeddings.model = model.to_string();
+        settings.set(updated)?;
+    }
+    let report = index.compact(&passages, Local::now().date_naive())?;
+    info!(
+        "Embeddings rebuilt with {}: {} embedded, {} orphaned and {} stale vectors dropped",
+        model, done, report.orphans, report.stale
+    );
+    Ok(index.status(&passages, model))
+}
+
+/// Compact the index at startup when the last compaction is a week old
+pub fn compact_if_due(app: &AppHandle) {
+    let index = app.state::<EmbeddingIndex>();
+    let today = Local::now().date_naive();
+    let due = match index.data.lock().unwrap().compacted_on {
+        Some(date) => (today - date).num_days() >= COMPACT_INTERVAL_DAYS,
+        None => true,
+    };
+    let configured = app.state::<SettingsStore>().get().embeddings.model;
+    if let Some(model) = index.model().filter(|m| *m != configured) {
+        warn!(
+            "Embedding index uses {} but settings select {}; rebuild embeddings to migrate",
+            model, configured
+        );
+    }
+    if !due {
+        return;
+    }
+    let handle = app.clone();
+    spawn_task_with(
+        app,
+        "embedding_compaction",
+        ResourceClass::Disk,
+        Priority::Background,
+        move |_ctx: TaskContext| {
+            let passages = handle.state::<DocumentStore>().passages();
+            let report = handle
+                .state::<EmbeddingIndex>()
+                .compact(&passages, Local::now().date_naive())?;
+            info!(
+                "Embedding index compacted: {} orphaned and {} stale vectors dropped",
+                report.orphans, report.stale
+            );
+            Ok(report)
+        },
+    );
+}
+
+/// Embed newly added chunks in the background with the live model; does
+/// nothing before the first rebuild, during a rebuild, or while settings
+/// select another model that a rebuild has yet to migrate to
+pub fn embed_new(app: &AppHandle) {
+    let index = app.state::<EmbeddingIndex>();
+    let Some(model) = index.model() else {
+        return;
+    };
+    if app.state::<SettingsStore>().get().embeddings.model != model
+        || index.rebuilding.swap(true, Ordering::Relaxed)
+    {
+        return;
+    }
+    let handle = app.clone();
+    spawn_task_with(
+        app,
+        "embedding_rebuild",
+        ResourceClass::Cpu,
+        Priority::Background,
+        move |ctx: TaskContext| {
+            let _guard = RebuildGuard {
+                app: handle.clone(),
+            };
+            rebuild(&ctx, &handle, &model)
+        },
+    );
+}
+
+/// Embed chunks missing from the index, migrating every vector when `model` differs from the live model
+#[tauri::command]
+pub fn rebuild_embeddings(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    index: State<'_, EmbeddingIndex>,
+    model: Option<String>,
+) -> Result<TaskHandle, String> {
+    let model = model
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| settings.get().embeddings.model);
+    if index.rebuilding.swap(true, Ordering::Relaxed) {
+        return Err("An embedding rebuild is already running".to_string());
+    }
+    let handle = app.clone();
+    let task_id = spawn_task_with(
+        &app,
+        "embedding_rebuild",
+        ResourceClass::Cpu,
+        Priority::Background,
+        move |ctx: TaskContext| {
+            let _guard = RebuildGuard {
+                app: handle.clone(),
+            };
+            rebuild(&ctx, &handle, &model)
+        },
+    );
+    Ok(TaskHandle { task_id })
+}
+
+#[tauri::command]
+pub fn get_embedding_status(
+    settings: State<'_, SettingsStore>,
+    index: State<'_, EmbeddingIndex>,
+    documents: State<'_, DocumentStore>,
+) -> Result<EmbeddingStatus, String> {
+    Ok(index.status(&documents.passages(), &settings.get().embeddings.model))
+}
+
+/// Passages closest in meaning to the query, optionally for one symbol
+pub async fn search(
+    app: &AppHandle,
+    query: &str,
+    symbol: Option<&str>,
+    limit: usize,
+) -> Result<Vec<SemanticHit>, String> {
+    let index = app.state::<EmbeddingIndex>();
+    // Queries must be embedded with the model the vectors came from
+    let model = index
+        .model()
+        .ok_or("No embeddings yet; rebuild embeddings first")?;
+    let settings = app.state::<SettingsStore>().get().embeddings;
+    let vector = embed(&settings, &model, &[query.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+
+    let symbol = symbol.map(|s| s.trim().to_uppercase());
+    let passages = app.state::<DocumentStore>().passages();
+    let allowed: HashSet<&str> = passages
+        .iter()
+        .filter(|p| symbol.is_none() || p.symbol == symbol)
+        .map(|p| p.document_id.as_str())
+        .collect();
+    let by_key: HashMap<(&str, usize), &Passage> = passages
+        .iter()
+        .map(|p| ((p.document_id.as_str(), p.chunk_index), p))
+        .collect();
+    Ok(index
+        .search(&vector, |id| allowed.contains(id), limit)
+        .into_iter()
+        .filter_map(|(document_id, chunk_index, similarity)| {
+            let passage = by_key.get(&(document_id.as_str(), chunk_index))?;
+            Some(SemanticHit {
+                document_id: passage.document_id.clone(),
+                title: passage.title.clone(),
+                chunk_index,
+                text: passage.text.clone(),
+                start_ms: passage.start_ms,
+                similarity,
+            })
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn semantic_search(
+    app: AppHandle,
+    query: String,
+    symbol: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<SemanticHit>, String> {
+    search(&app, &query, symbol.as_deref(), limit.unwrap_or(20)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passage(document_id: &str, chunk_index: usize, text: &str) -> Passage {
+        Passage {
+            document_id: document_id.to_string(),
+            chunk_index,
+            title: "公告".to_string(),
+            symbol: None,
+            text: text.to_string(),
+            start_ms: None,
+        }
+    }
+
+    fn embedded(passages: &[Passage], vector: Vec<f32>) -> Vec<Embedding> {
+        passages
+            .iter()
+            .map(|p| Embedding {
+                document_id: p.document_id.clone(),
+                chunk_index: p.chunk_index,
+                text_hash: text_hash(&p.text),
+                vector: vector.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_migration_keeps_serving_old_model() {
+        let path = std::env::temp_dir().join(format!("ssi-embeddings-{}.json", std::process::id()));
+        let index = EmbeddingIndex::load(path.clone());
+        let passages = vec![passage("d1", 0, "营收增长"), passage("d1", 1, "毛利率下降")];
+
+        index.begin("small").unwrap();
+        index
+            .store("small", embedded(&passages, vec![1.0, 0.0]))
+            .unwrap();
+        index.finish("small").unwrap();
+        assert!(index.pending("small", &passages).is_empty());
+
+        // Half-way through a migration search still uses the old vectors
+        index.begin("large").unwrap();
+        assert_eq!(index.pending("large", &passages).len(), 2);
+        index
+            .store("large", embedded(&passages[..1], vec![0.0, 1.0, 0.0]))
+            .unwrap();
+        assert_eq!(index.model().as_deref(), Some("small"));
+        assert_eq!(index.search(&[1.0, 0.0], |_| true, 10).len(), 2);
+
+        // An interrupted migration resumes with what is left
+        let index = EmbeddingIndex::load(path.clone());
+        index.begin("large").unwrap();
+        let pending = index.pending("large", &passages);
+        assert_eq!(pending, passages[1..].to_vec());
+        index
+            .store("large", embedded(&pending, vec![0.0, 0.0, 1.0]))
+            .unwrap();
+        index.finish("large").unwrap();
+        assert_eq!(index.model().as_deref(), Some("large"));
+        let hits = index.search(&[0.0, 1.0, 0.0], |_| true, 10);
+        assert_eq!((hits[0].1, hits[0].2), (0, 1.0));
+        assert!(index.search(&[1.0, 0.0], |_| true, 10).is_empty());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_compaction_drops_orphans_and_stale() {
+        let path =
+            std::env::temp_dir().join(format!("ssi-embed-compact-{}.json", std::process::id()));
+        let index = EmbeddingIndex::load(path.clone());
+        let old = vec![
+            passage("d1", 0, "营收增长"),
+            passage("d1", 1, "毛利率下降"),
+            passage("gone", 0, "已删除"),
+        ];
+        index.begin("small").unwrap();
+        index.store("small", embedded(&old, vec![1.0])).unwrap();
+        index
+            .store("small", embedded(&old[..1], vec![2.0]))
+            .unwrap();
+
+        let current = vec![passage("d1", 0, "营收增长"), passage("d1", 1, "毛利率回升")];
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let report = index.compact(&current, today).unwrap();
+        assert_eq!(
+            report,
+            CompactionReport {
+                orphans: 1,
+                stale: 2,
+                kept: 1
+            }
+        );
+        assert_eq!(index.search(&[1.0], |_| true, 10).len(), 1);
+        assert_eq!(index.pending("small", &current), current[1..].to_vec());
+        let status = index.status(&current, "small");
+        assert_eq!((status.pending, status.compacted_on), (1, Some(today)));
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,251 @@
+//! Linking company mentions in news to instruments.
+//!
+//! Articles often name a company without its ticker. Every instrument
+//! contributes its listed name plus a few derived short forms (without
+//! ST markers or a trailing share-class letter), well-known nicknames such as
+//! 宁王 for CATL ship built in, and users can add their own aliases. All names
+//! are compiled into one leftmost-longest Aho-Corasick automaton so 平安银行
+//! links to the bank and not to 中国平安; bare six-digit codes are linked as
+//! well.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::instruments::{Instrument, InstrumentMaster};
+use crate::utils::{read_from_file, write_to_file};
+
+/// Names shorter than this match too much ordinary text
+const MIN_ALIAS_CHARS: usize = 2;
+
+/// Common nicknames shipped with the app; user aliases take precedence
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("茅台", "SH600519"),
+    ("宁王", "SZ300750"),
+    ("宁德时代", "SZ300750"),
+    ("招行", "SH600036"),
+    ("工行", "SH601398"),
+    ("建行", "SH601939"),
+    ("中石油", "SH601857"),
+    ("中石化", "SH600028"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolAlias {
+    pub alias: String,
+    pub symbol: String,
+}
+
+/// Short forms of a listed name: without ST markers and without a trailing A/B share class
+fn derived_names(instrument: &Instrument) -> Vec<String> {
+    let mut names = vec![instrument.name.clone()];
+    let stripped = if instrument.is_special_treatment() {
+        instrument.name.trim_start_matches(['*', 'S', 'T'])
+    } else {
+        &instrument.name
+    };
+    let stripped = stripped
+        .strip_suffix(['A', 'B'])
+        .filter(|rest| rest.chars().count() >= MIN_ALIAS_CHARS)
+        .unwrap_or(stripped);
+    if stripped != instrument.name {
+        names.push(stripped.to_string());
+    }
+    names
+}
+
+pub struct EntityLinker {
+    automaton: Option<AhoCorasick>,
+    /// Symbol of each automaton pattern
+    targets: Vec<String>,
+    /// Six-digit code to symbol
+    codes: BTreeMap<String, String>,
+}
+
+impl EntityLinker {
+    pub fn build(instruments: &[Instrument], aliases: &[SymbolAlias]) -> Self {
+        let mut names: BTreeMap<String, String> = BTreeMap::new();
+        for instrument in instruments {
+            for name in derived_names(instrument) {
+                names
+                    .entry(name)
+                    .or_insert_with(|| instrument.symbol.clone());
+            }
+        }
+        for (alias, symbol) in BUILTIN_ALIASES {
+            names.insert(alias.to_string(), symbol.to_string());
+        }
+        for alias in aliases {
+            names.insert(alias.alias.clone(), alias.symbol.clone());
+        }
+        names.retain(|name, _| name.chars().count() >= MIN_ALIAS_CHARS);
+
+        let (patterns, targets): (Vec<String>, Vec<String>) = names.into_iter().unzip();
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(&patterns)
+            .ok();
+        let codes = instruments
+            .iter()
+            .filter_map(|i| {
+                let code = i.symbol.get(i.symbol.len().saturating_sub(6)..)?;
+                Some((code.to_string(), i.symbol.clone()))
+            })
+            .collect();
+        Self {
+            automaton,
+            targets,
+            codes,
+        }
+    }
+
+    /// Symbols mentioned in the text, by name, alias or code
+    pub fn link(&self, text: &str) -> Vec<String> {
+        let mut symbols = BTreeSet::new();
+        if let Some(automaton) = &self.automaton {
+            for found in automaton.find_iter(text) {
+                symbols.insert(self.targets[found.pattern().as_usize()].clone());
+            }
+        }
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            if !chars[i].is_ascii_digit() {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i - start == 6 {
+                let code: String = chars[start..i].iter().collect();
+                if let Some(symbol) = self.codes.get(&code) {
+                    symbols.insert(symbol.clone());
+                }
+            }
+        }
+        symbols.into_iter().collect()
+    }
+}
+
+pub struct AliasStore {
+    path: PathBuf,
+    aliases: RwLock<Vec<SymbolAlias>>,
+}
+
+impl AliasStore {
+    pub fn load(path: PathBuf) -> Self {
+        let aliases = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            aliases: RwLock::new(aliases),
+        }
+    }
+
+    fn save(&self, aliases: &[SymbolAlias]) -> Result<(), String> {
+        let content = serde_json::to_string(aliases)
+            .map_err(|e| format!("Failed to serialize symbol aliases: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save symbol aliases: {}", e))
+    }
+
+    pub fn set(&self, alias: &str, symbol: &str) -> Result<(), String> {
+        let alias = alias.trim();
+        if alias.chars().count() < MIN_ALIAS_CHARS {
+            return Err(format!(
+                "Alias must be at least {} characters",
+                MIN_ALIAS_CHARS
+            ));
+        }
+        let mut aliases = self.aliases.write().unwrap();
+        aliases.retain(|a| a.alias != alias);
+        aliases.push(SymbolAlias {
+            alias: alias.to_string(),
+            symbol: symbol.trim().to_uppercase(),
+        });
+        self.save(&aliases)
+    }
+
+    pub fn remove(&self, alias: &str) -> Result<(), String> {
+        let mut aliases = self.aliases.write().unwrap();
+        aliases.retain(|a| a.alias != alias);
+        self.save(&aliases)
+    }
+
+    pub fn list(&self) -> Vec<SymbolAlias> {
+        self.aliases.read().unwrap().clone()
+    }
+}
+
+/// Linker over the current instrument master and alias list
+pub fn linker(master: &InstrumentMaster, aliases: &AliasStore) -> EntityLinker {
+    EntityLinker::build(&master.instruments(), &aliases.list())
+}
+
+/// Link news mentions of a name to a symbol
+#[tauri::command]
+pub fn add_symbol_alias(
+    aliases: State<'_, AliasStore>,
+    alias: String,
+    symbol: String,
+) -> Result<(), String> {
+    aliases.set(&alias, &symbol)
+}
+
+#[tauri::command]
+pub fn remove_symbol_alias(aliases: State<'_, AliasStore>, alias: String) -> Result<(), String> {
+    aliases.remove(&alias)
+}
+
+#[tauri::command]
+pub fn list_symbol_aliases(aliases: State<'_, AliasStore>) -> Result<Vec<SymbolAlias>, String> {
+    Ok(aliases.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instrument(symbol: &str, name: &str) -> Instrument {
+        Instrument {
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_links_names_aliases_and_codes() {
+        let instruments = vec![
+            instrument("SH601318", "中国平安"),
+            instrument("SZ000001", "平安银行"),
+            instrument("SH600519", "贵州茅台"),
+            instrument("SZ000002", "万科A"),
+            instrument("SH600666", "*ST奥瑞"),
+        ];
+        let aliases = vec![SymbolAlias {
+            alias: "平银".to_string(),
+            symbol: "SZ000001".to_string(),
+        }];
+        let linker = EntityLinker::build(&instruments, &aliases);
+
+        assert_eq!(linker.link("平安银行一季度净利润增长"), ["SZ000001"]);
+        assert_eq!(
+            linker.link("中国平安与平银同日公告，茅台提价"),
+            ["SH600519", "SH601318", "SZ000001"]
+        );
+        assert_eq!(
+            linker.link("万科召开股东大会，奥瑞申请撤销风险警示"),
+            ["SH600666", "SZ000002"]
+        );
+        assert_eq!(linker.link("600519盘中拉升，1600519不是代码"), ["SH600519"]);
+        assert!(linker.link("今日两市成交额破万亿").is_empty());
+    }
+}
@@ -0,0 +1,325 @@
+//! Priority-aware admission control for mixed workloads.
+//!
+//! Work is classified by the resource it mostly consumes (network, disk,
+//! CPU) and each class has a bounded number of concurrent slots. Waiters are
+//! admitted highest priority first, and background work may never occupy the
+//! slots reserved for interactive requests, so a long backfill cannot make the
+//! quote for the visible chart wait. Quote and chart downloads a view is
+//! waiting on are admitted as interactive, quote polling as normal and
+//! warm-ups and scheduled refreshes as background work.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceClass {
+    Network,
+    Disk,
+    Cpu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Background,
+    Normal,
+    Interactive,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassStats {
+    class: ResourceClass,
+    limit: usize,
+    reserved_interactive: usize,
+    running: usize,
+    queued: usize,
+}
+
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    grant: oneshot::Sender<()>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.seq == other.seq
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    // Higher priority first, then FIFO within a priority
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct ClassState {
+    limit: usize,
+    reserved_interactive: usize,
+    running: usize,
+    seq: u64,
+    waiters: BinaryHeap<Waiter>,
+}
+
+impl ClassState {
+    fn new(limit: usize, reserved_interactive: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            reserved_interactive: reserved_interactive.min(limit.saturating_sub(1)),
+            running: 0,
+            seq: 0,
+            waiters: BinaryHeap::new(),
+        }
+    }
+
+    fn capacity_for(&self, priority: Priority) -> usize {
+        match priority {
+            Priority::Interactive => self.limit,
+            _ => self.limit - self.reserved_interactive,
+        }
+    }
+
+    fn can_admit(&self, priority: Priority) -> bool {
+        self.running < self.capacity_for(priority)
+            && self.waiters.peek().map_or(true, |w| w.priority <= priority)
+    }
+
+    /// Hand free slots to queued waiters, best first
+    fn dispatch(&mut self) {
+        while let Some(top) = self.waiters.peek() {
+            if self.running >= self.capacity_for(top.priority) {
+                break;
+            }
+            let waiter = self.waiters.pop().unwrap();
+            // A dropped receiver means the waiter gave up; skip it
+            if waiter.grant.send(()).is_ok() {
+                self.running += 1;
+            }
+        }
+    }
+}
+
+/// Slot held while a unit of work runs; released on drop
+pub struct Permit {
+    state: Arc<Mutex<ClassState>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        release(&self.state);
+    }
+}
+
+fn release(state: &Mutex<ClassState>) {
+    let mut state = state.lock().unwrap();
+    state.running -= 1;
+    state.dispatch();
+}
+
+/// A queued request; gives its slot back if abandoned after being granted
+struct Pending {
+    state: Arc<Mutex<ClassState>>,
+    rx: Option<oneshot::Receiver<()>>,
+    granted: bool,
+}
+
+impl Pending {
+    fn into_permit(mut self) -> Permit {
+        self.granted = true;
+        Permit {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if let (false, Some(rx)) = (self.granted, self.rx.as_mut()) {
+            rx.close();
+            if rx.try_recv().is_ok() {
+                release(&self.state);
+            }
+        }
+    }
+}
+
+pub struct Executor {
+    classes: HashMap<ResourceClass, Arc<Mutex<ClassState>>>,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        let cpus = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Self::with_limits(&[
+            (ResourceClass::Network, 8, 2),
+            (ResourceClass::Disk, 4, 1),
+            (ResourceClass::Cpu, cpus, 1),
+        ])
+    }
+}
+
+impl Executor {
+    /// Build an executor from `(class, limit, reserved_for_interactive)`
+    pub fn with_limits(limits: &[(ResourceClass, usize, usize)]) -> Self {
+        Self {
+            classes: limits
+                .iter()
+                .map(|&(class, limit, reserved)| {
+                    (
+                        class,
+                        Arc::new(Mutex::new(ClassState::new(limit, reserved))),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn class(&self, class: ResourceClass) -> Arc<Mutex<ClassState>> {
+        self.classes[&class].clone()
+    }
+
+    /// Try to take a slot immediately, or enqueue a waiter
+    fn admit_or_enqueue(
+        &self,
+        class: ResourceClass,
+        priority: Priority,
+    ) -> Result<Permit, Pending> {
+        let state = self.class(class);
+        let mut guard = state.lock().unwrap();
+        if guard.can_admit(priority) {
+            guard.running += 1;
+            drop(guard);
+            return Ok(Permit { state });
+        }
+        let (tx, rx) = oneshot::channel();
+        guard.seq += 1;
+        let seq = guard.seq;
+        guard.waiters.push(Waiter {
+            priority,
+            seq,
+            grant: tx,
+        });
+        drop(guard);
+        Err(Pending {
+            state,
+            rx: Some(rx),
+            granted: false,
+        })
+    }
+
+    /// Wait for a slot in `class`
+    pub async fn acquire(&self, class: ResourceClass, priority: Priority) -> Permit {
+        match self.admit_or_enqueue(class, priority) {
+            Ok(permit) => permit,
+            Err(mut pending) => {
+                // The sender lives in the queue until granted, so this only
+                // completes once the slot has been counted for us
+                if let Some(rx) = pending.rx.as_mut() {
+                    let _ = rx.await;
+                }
+                pending.into_permit()
+            }
+        }
+    }
+
+    /// Blocking variant of [`Executor::acquire`] for `spawn_blocking` jobs
+    pub fn acquire_blocking(&self, class: ResourceClass, priority: Priority) -> Permit {
+        match self.admit_or_enqueue(class, priority) {
+            Ok(permit) => permit,
+            Err(mut pending) => {
+                if let Some(rx) = pending.rx.take() {
+                    let _ = rx.blocking_recv();
+                }
+                pending.into_permit()
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Vec<ClassStats> {
+        let mut stats: Vec<ClassStats> = self
+            .classes
+            .iter()
+            .map(|(class, state)| {
+                let state = state.lock().unwrap();
+                ClassStats {
+                    class: *class,
+                    limit: state.limit,
+                    reserved_interactive: state.reserved_interactive,
+                    running: state.running,
+                    queued: state.waiters.len(),
+                }
+            })
+            .collect();
+        stats.sort_by_key(|s| s.class as u8);
+        stats
+    }
+}
+
+/// Current slot usage per resource class
+#[tauri::command]
+pub fn get_executor_stats(executor: State<'_, Executor>) -> Result<Vec<ClassStats>, String> {
+    Ok(executor.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_cannot_take_reserved_slot() {
+        let executor = Executor::with_limits(&[(ResourceClass::Network, 2, 1)]);
+        let _bg = executor.acquire_blocking(ResourceClass::Network, Priority::Background);
+        assert!(executor
+            .admit_or_enqueue(ResourceClass::Network, Priority::Background)
+            .is_err());
+        assert!(executor
+            .admit_or_enqueue(ResourceClass::Network, Priority::Interactive)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_interactive_waiter_served_first() {
+        let executor = Arc::new(Executor::with_limits(&[(ResourceClass::Cpu, 1, 0)]));
+        let held = executor.acquire(ResourceClass::Cpu, Priority::Normal).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for priority in [Priority::Background, Priority::Interactive] {
+            let (executor, order) = (executor.clone(), order.clone());
+            handles.push(tokio::spawn(async move {
+                let _permit = executor.acquire(ResourceClass::Cpu, priority).await;
+                order.lock().unwrap().push(priority);
+            }));
+            tokio::task::yield_now().await;
+        }
+        while executor.stats()[0].queued < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        drop(held);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![Priority::Interactive, Priority::Background]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Explanations of why a trigger fired.
+//!
+//! When a monitored strategy signals, the conditions it evaluated are kept
+//! with the actual computed values and the thresholds they were compared
+//! against, and whether each passed, instead of a bare "condition met". The
+//! explanation is stored in the database under the event's id, which the
+//! event itself carries, and `explain_trigger` returns it.
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::{Database, Explanations};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Above,
+    Below,
+}
+
+impl Comparison {
+    fn holds(self, actual: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Above => actual > threshold,
+            Comparison::Below => actual < threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    /// What was measured, e.g. `SMA(5)` or `RSI(14)`
+    pub metric: String,
+    pub actual: f64,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    /// Name of the threshold when it is itself computed, e.g. `SMA(20)`
+    pub threshold_metric: Option<String>,
+    pub passed: bool,
+}
+
+impl Condition {
+    pub fn new(metric: String, actual: f64, comparison: Comparison, threshold: f64) -> Self {
+        Self {
+            metric,
+            actual,
+            comparison,
+            threshold,
+            threshold_metric: None,
+            passed: comparison.holds(actual, threshold),
+        }
+    }
+
+    /// Name the computed value the metric was compared against
+    pub fn against(mut self, threshold_metric: String) -> Self {
+        self.threshold_metric = Some(threshold_metric);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerKind {
+    StrategySignal,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Explanation {
+    pub event_id: String,
+    pub kind: TriggerKind,
+    /// Strategy that fired
+    pub source_id: String,
+    pub symbol: String,
+    /// What the trigger did, e.g. `buy`
+    pub outcome: String,
+    pub conditions: Vec<Condition>,
+    /// Timestamp of the bar evaluated
+    pub as_of: i64,
+    pub created_at: String,
+}
+
+/// Conditions behind a fired trigger, with their computed values and thresholds
+#[tauri::command]
+pub fn explain_trigger(db: State<'_, Database>, event_id: String) -> Result<Explanation, String> {
+    let conn = db.conn()?;
+    Explanations(&conn).get(&event_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditions_record_values() {
+        let cross = Condition::new("SMA(5)".to_string(), 10.4, Comparison::Above, 10.1)
+            .against("SMA(20)".to_string());
+        assert!(cross.passed);
+        assert_eq!(cross.threshold_metric.as_deref(), Some("SMA(20)"));
+        assert!(!Condition::new("RSI(14)".to_string(), 30.0, Comparison::Below, 30.0).passed);
+    }
+}
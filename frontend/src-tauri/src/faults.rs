@@ -0,0 +1,214 @@
+//! Debug-only fault injection for data providers.
+//!
+//! Provider fetches are wrapped in [`FaultInjector::wrap`]; when a fault is
+//! registered for the provider (or for `*`), a matching fraction of requests
+//! fail with an error, a timeout, a rate-limit response, or a truncated JSON
+//! body, so retry, failover and offline paths can be exercised on demand.
+//! Release builds never inject anything.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// How long an injected timeout stalls before failing
+const INJECTED_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Matches every provider
+const ANY_PROVIDER: &str = "*";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultKind {
+    Error,
+    Timeout,
+    MalformedJson,
+    RateLimit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultRule {
+    provider: String,
+    kind: FaultKind,
+    /// Fraction of requests affected, 0.0 – 1.0
+    rate: f64,
+    injected: u64,
+}
+
+#[derive(Default)]
+pub struct FaultInjector {
+    rules: Mutex<HashMap<String, FaultRule>>,
+}
+
+impl FaultInjector {
+    pub fn set(&self, provider: &str, kind: FaultKind, rate: f64) {
+        self.rules.lock().unwrap().insert(
+            provider.to_string(),
+            FaultRule {
+                provider: provider.to_string(),
+                kind,
+                rate: rate.clamp(0.0, 1.0),
+                injected: 0,
+            },
+        );
+    }
+
+    pub fn clear(&self, provider: Option<&str>) {
+        let mut rules = self.rules.lock().unwrap();
+        match provider {
+            Some(provider) => {
+                rules.remove(provider);
+            }
+            None => rules.clear(),
+        }
+    }
+
+    pub fn rules(&self) -> Vec<FaultRule> {
+        let mut rules: Vec<FaultRule> = self.rules.lock().unwrap().values().cloned().collect();
+        rules.sort_by(|a, b| a.provider.cmp(&b.provider));
+        rules
+    }
+
+    /// Decide whether the next request to `provider` should fail
+    pub fn roll(&self, provider: &str) -> Option<FaultKind> {
+        if !cfg!(debug_assertions) {
+            return None;
+        }
+        let mut rules = self.rules.lock().unwrap();
+        let key = if rules.contains_key(provider) {
+            provider
+        } else {
+            ANY_PROVIDER
+        };
+        let rule = rules.get_mut(key)?;
+        if rand::thread_rng().gen_bool(rule.rate) {
+            rule.injected += 1;
+            Some(rule.kind)
+        } else {
+            None
+        }
+    }
+
+    /// Run a provider fetch, substituting an injected fault when one fires
+    pub async fn wrap<F>(&self, provider: &str, fetch: F) -> Result<String, String>
+    where
+        F: Future<Output = Result<String, String>>,
+    {
+        let Some(kind) = self.roll(provider) else {
+            return fetch.await;
+        };
+        warn!("Injecting {:?} fault into {}", kind, provider);
+        match kind {
+            FaultKind::Error => Err(format!("Injected error from {}", provider)),
+            FaultKind::RateLimit => {
+                Err(format!("{} returned HTTP 429 Too Many Requests", provider))
+            }
+            FaultKind::Timeout => {
+                tokio::time::sleep(INJECTED_TIMEOUT).await;
+                Err(format!("Request to {} timed out", provider))
+            }
+            FaultKind::MalformedJson => fetch.await.map(|body| truncate_body(&body)),
+        }
+    }
+}
+
+/// Cut a body in half (on a char boundary) so it no longer parses
+fn truncate_body(body: &str) -> String {
+    let mut end = body.len() / 2;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\u{FFFD}", &body[..end])
+}
+
+fn ensure_debug_build() -> Result<(), String> {
+    if cfg!(debug_assertions) {
+        Ok(())
+    } else {
+        Err("Fault injection is only available in debug builds".to_string())
+    }
+}
+
+/// Make a fraction of requests to `provider` (or `*`) fail with `kind`
+#[tauri::command]
+pub fn inject_fault(
+    injector: State<'_, FaultInjector>,
+    provider: String,
+    kind: FaultKind,
+    rate: f64,
+) -> Result<(), String> {
+    ensure_debug_build()?;
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(format!("Fault rate must be between 0 and 1, got {}", rate));
+    }
+    info!(
+        "Injecting {:?} faults into {} at rate {}",
+        kind, provider, rate
+    );
+    injector.set(&provider, kind, rate);
+    Ok(())
+}
+
+/// Remove injected faults for one provider, or all when omitted
+#[tauri::command]
+pub fn clear_faults(
+    injector: State<'_, FaultInjector>,
+    provider: Option<String>,
+) -> Result<(), String> {
+    ensure_debug_build()?;
+    injector.clear(provider.as_deref());
+    Ok(())
+}
+
+/// List active fault rules with their injection counts
+#[tauri::command]
+pub fn list_faults(injector: State<'_, FaultInjector>) -> Result<Vec<FaultRule>, String> {
+    ensure_debug_build()?;
+    Ok(injector.rules())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    async fn test_wrap_injects_matching_faults() {
+        let injector = FaultInjector::default();
+        let ok = || async { Ok::<_, String>(r#"{"price": 12.5}"#.to_string()) };
+
+        assert!(injector.wrap("sina", ok()).await.is_ok());
+
+        injector.set("sina", FaultKind::RateLimit, 1.0);
+        assert!(injector
+            .wrap("sina", ok())
+            .await
+            .unwrap_err()
+            .contains("429"));
+        assert!(injector.wrap("tencent", ok()).await.is_ok());
+
+        injector.set("*", FaultKind::MalformedJson, 1.0);
+        let body = injector.wrap("tencent", ok()).await.unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&body).is_err());
+
+        injector.clear(None);
+        assert!(injector.rules().is_empty());
+        assert_eq!(injector.roll("sina"), None);
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[tokio::test]
+    async fn test_release_builds_never_inject() {
+        let injector = FaultInjector::default();
+        injector.set("*", FaultKind::Error, 1.0);
+        assert_eq!(injector.roll("sina"), None);
+        let body = injector.wrap("sina", async { Ok("{}".to_string()) }).await;
+        assert_eq!(body, Ok("{}".to_string()));
+        assert!(ensure_debug_build().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Field selection for commands that return large composite objects.
+//!
+//! Such commands take an optional `fields` list of dotted paths, e.g.
+//! `["manual.symbol", "manual.quantity", "transactions"]`, and return only
+//! those parts of the response. Arrays are traversed element-wise, so
+//! `positions.symbol` picks the symbol of every position, and a path that
+//! ends on an object keeps the whole subtree. Paths naming fields a response
+//! does not have are ignored. Without `fields` the full object is returned.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Default)]
+struct Selection {
+    /// Keep the whole subtree
+    all: bool,
+    children: BTreeMap<String, Selection>,
+}
+
+impl Selection {
+    fn parse(fields: &[String]) -> Result<Self, String> {
+        let mut root = Selection::default();
+        for field in fields {
+            let mut node = &mut root;
+            for part in field.split('.') {
+                let part = part.trim();
+                if part.is_empty() {
+                    return Err(format!("Invalid field path: \"{}\"", field));
+                }
+                node = node.children.entry(part.to_string()).or_default();
+            }
+            node.all = true;
+        }
+        Ok(root)
+    }
+
+    fn prune(&self, value: Value) -> Value {
+        if self.all {
+            return value;
+        }
+        match value {
+            Value::Array(items) => Value::Array(items.into_iter().map(|i| self.prune(i)).collect()),
+            Value::Object(mut map) => {
+                let mut kept = Map::new();
+                for (key, child) in &self.children {
+                    if let Some(field) = map.remove(key) {
+                        kept.insert(key.clone(), child.prune(field));
+                    }
+                }
+                Value::Object(kept)
+            }
+            other => other,
+        }
+    }
+}
+
+/// A response reduced to the requested fields, or whole when none are given
+pub fn select<T: Serialize>(response: &T, fields: Option<&[String]>) -> Result<Value, String> {
+    let value = serde_json::to_value(response)
+        .map_err(|e| format!("Failed to serialize response: {}", e))?;
+    match fields {
+        Some(fields) if !fields.is_empty() => Ok(Selection::parse(fields)?.prune(value)),
+        _ => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_nested_paths() {
+        let response = json!({
+            "manual": [
+                {"id": "h1", "symbol": "SH600519", "quantity": 100.0, "note": "long"},
+                {"id": "h2", "symbol": "SZ000001", "quantity": 200.0, "note": ""}
+            ],
+            "broker_accounts": [{"broker": "ibkr", "positions": [{"symbol": "AAPL"}]}],
+            "transactions": [{"id": "t1"}]
+        });
+        let fields = vec![
+            "manual.symbol".to_string(),
+            "manual.quantity".to_string(),
+            "transactions".to_string(),
+            "missing.field".to_string(),
+        ];
+        let selected = select(&response, Some(&fields)).unwrap();
+        assert_eq!(
+            selected,
+            json!({
+                "manual": [
+                    {"symbol": "SH600519", "quantity": 100.0},
+                    {"symbol": "SZ000001", "quantity": 200.0}
+                ],
+                "transactions": [{"id": "t1"}]
+            })
+        );
+
+        assert_eq!(select(&response, None).unwrap(), response);
+        assert_eq!(select(&response, Some(&[])).unwrap(), response);
+        assert!(select(&response, Some(&["manual..symbol".to_string()])).is_err());
+    }
+}
@@ -0,0 +1,627 @@
+//! Financial statements for the 财务分析 tab.
+//!
+//! Income statements, balance sheets and cash-flow statements of an A-share
+//! company come from Eastmoney's F10 data center, one request per
+//! statement, and are normalized into typed rows tagged with their reporting
+//! period. Figures are in yuan and, as A-share reports are filed,
+//! cumulative from the start of the fiscal year: a Q3 income statement
+//! covers January to September. The statements of a symbol are cached in
+//! the database and served from the cache for the fundamentals polling
+//! interval, since they only change when a new report is published. Cash
+//! dividends, from the same data center, are cached with them for the
+//! valuation metrics. In snapshot mode only the cache is read, cut back to
+//! the reports public and dividends gone ex by the snapshot day.
+
+use chrono::{Datelike, Duration, Local, NaiveDate, Utc};
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+
+use crate::db::{Database, Financials};
+use crate::drift::{DriftLog, Field, FieldKind};
+use crate::faults::FaultInjector;
+use crate::models::Market;
+use crate::politeness::PolicyEngine;
+use crate::polling::DataClass;
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::symbols::symbol_key;
+
+const PROVIDER: &str = "eastmoney";
+
+const DATA_URL: &str = "https://datacenter.eastmoney.com/securities/api/data/get";
+
+const DIVIDEND_URL: &str = "https://datacenter-web.eastmoney.com/api/data/v1/get";
+
+/// Reports fetched per statement, newest first: eleven years of quarters,
+/// enough for trailing figures across a ten-year valuation lookback
+const PERIODS: usize = 44;
+
+/// Which part of a report a period closes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeriodKind {
+    Q1,
+    /// Half-year report
+    Interim,
+    Q3,
+    Annual,
+}
+
+impl PeriodKind {
+    fn of(end: NaiveDate) -> Option<Self> {
+        match end.month() {
+            3 => Some(PeriodKind::Q1),
+            6 => Some(PeriodKind::Interim),
+            9 => Some(PeriodKind::Q3),
+            12 => Some(PeriodKind::Annual),
+            _ => None,
+        }
+    }
+}
+
+/// The period a statement covers and when it was published
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportingPeriod {
+    pub end: NaiveDate,
+    pub fiscal_year: i32,
+    pub kind: PeriodKind,
+    /// Date the report was published, when known
+    pub published: Option<NaiveDate>,
+}
+
+impl ReportingPeriod {
+    /// Date the report was public by: its publication date, or else the
+    /// deadline for filing it
+    pub fn public_from(&self) -> NaiveDate {
+        let deadline = match self.kind {
+            PeriodKind::Q1 | PeriodKind::Q3 => 31,
+            PeriodKind::Interim => 62,
+            PeriodKind::Annual => 120,
+        };
+        self.published
+            .unwrap_or(self.end + Duration::days(deadline))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IncomeStatement {
+    pub period: ReportingPeriod,
+    pub revenue: Option<f64>,
+    pub operating_cost: Option<f64>,
+    pub operating_profit: Option<f64>,
+    pub total_profit: Option<f64>,
+    pub net_profit: Option<f64>,
+    /// Net profit attributable to shareholders of the parent
+    pub parent_net_profit: Option<f64>,
+    pub basic_eps: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceSheet {
+    pub period: ReportingPeriod,
+    pub total_assets: Option<f64>,
+    pub total_liabilities: Option<f64>,
+    pub total_equity: Option<f64>,
+    pub parent_equity: Option<f64>,
+    pub cash: Option<f64>,
+    pub current_assets: Option<f64>,
+    pub current_liabilities: Option<f64>,
+    pub inventory: Option<f64>,
+    pub receivables: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CashFlowStatement {
+    pub period: ReportingPeriod,
+    pub operating: Option<f64>,
+    pub investing: Option<f64>,
+    pub financing: Option<f64>,
+    /// Net change in cash and cash equivalents
+    pub net_change: Option<f64>,
+    /// Cash paid for fixed, intangible and other long-term assets
+    pub capex: Option<f64>,
+}
+
+/// A cash dividend paid out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dividend {
+    pub ex_date: NaiveDate,
+    /// Before tax, in yuan
+    pub cash_per_share: f64,
+}
+
+/// All three statements of a symbol, newest period first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FinancialStatements {
+    pub symbol: String,
+    pub income: Vec<IncomeStatement>,
+    pub balance: Vec<BalanceSheet>,
+    pub cash_flow: Vec<CashFlowStatement>,
+    /// Newest first; missing from caches written before dividends were kept
+    #[serde(default)]
+    pub dividends: Vec<Dividend>,
+    pub fetched_on: NaiveDate,
+    /// Unix seconds; zero in caches written before it was kept
+    #[serde(default)]
+    pub fetched_at: i64,
+}
+
+impl FinancialStatements {
+    /// The statements as they stood on `day`: reports public by then and
+    /// dividends gone ex by then
+    pub fn as_of(mut self, day: NaiveDate) -> Self {
+        self.income.retain(|s| s.period.public_from() <= day);
+        self.balance.retain(|s| s.period.public_from() <= day);
+        self.cash_flow.retain(|s| s.period.public_from() <= day);
+        self.dividends.retain(|d| d.ex_date <= day);
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct DataResponse<T> {
+    result: DataResult<T>,
+}
+
+#[derive(Deserialize)]
+struct DataResult<T> {
+    data: Vec<T>,
+}
+
+/// Fields every statement row carries
+#[derive(Deserialize)]
+struct PeriodFields {
+    #[serde(rename = "REPORT_DATE")]
+    report_date: String,
+    #[serde(rename = "NOTICE_DATE")]
+    notice_date: Option<String>,
+}
+
+impl PeriodFields {
+    fn period(&self) -> Option<ReportingPeriod> {
+        let end = parse_date(&self.report_date)?;
+        Some(ReportingPeriod {
+            end,
+            fiscal_year: end.year(),
+            kind: PeriodKind::of(end)?,
+            published: self.notice_date.as_deref().and_then(parse_date),
+        })
+    }
+}
+
+/// `2023-12-31 00:00:00` to its date
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct IncomeRow {
+    #[serde(flatten)]
+    period: PeriodFields,
+    total_operate_income: Option<f64>,
+    total_operate_cost: Option<f64>,
+    operate_profit: Option<f64>,
+    total_profit: Option<f64>,
+    netprofit: Option<f64>,
+    parent_netprofit: Option<f64>,
+    basic_eps: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct BalanceRow {
+    #[serde(flatten)]
+    period: PeriodFields,
+    total_assets: Option<f64>,
+    total_liabilities: Option<f64>,
+    total_equity: Option<f64>,
+    total_parent_equity: Option<f64>,
+    monetaryfunds: Option<f64>,
+    total_current_assets: Option<f64>,
+    total_current_liab: Option<f64>,
+    inventory: Option<f64>,
+    accounts_rece: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct CashFlowRow {
+    #[serde(flatten)]
+    period: PeriodFields,
+    netcash_operate: Option<f64>,
+    netcash_invest: Option<f64>,
+    netcash_finance: Option<f64>,
+    cce_add: Option<f64>,
+    construct_long_asset: Option<f64>,
+}
+
+/// A dividend plan; only those carried out have an ex-dividend date
+#[derive(Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct DividendRow {
+    ex_dividend_date: Option<String>,
+    /// Per ten shares
+    pretax_bonus_rmb: Option<f64>,
+}
+
+const STATEMENT_SCHEMA: &[Field] = &[
+    Field {
+        path: "result.data",
+        kind: FieldKind::Array,
+    },
+    Field {
+        path: "result.data[].REPORT_DATE",
+        kind: FieldKind::String,
+    },
+];
+
+/// One statement in the data center: its report name and column set
+#[derive(Debug, Clone, Copy)]
+struct Statement {
+    report: &'static str,
+    columns: &'static str,
+    what: &'static str,
+}
+
+const INCOME: Statement = Statement {
+    report: "RPT_F10_FINANCE_GINCOME",
+    columns: "APP_F10_GINCOME",
+    what: "income statement",
+};
+const BALANCE: Statement = Statement {
+    report: "RPT_F10_FINANCE_GBALANCE",
+    columns: "APP_F10_GBALANCE",
+    what: "balance sheet",
+};
+const CASH_FLOW: Statement = Statement {
+    report: "RPT_F10_FINANCE_GCASHFLOW",
+    columns: "APP_F10_GCASHFLOW",
+    what: "cash-flow statement",
+};
+
+/// `result` is null rather than empty for a symbol that never paid out
+const DIVIDEND_SCHEMA: &[Field] = &[Field {
+    path: "success",
+    kind: FieldKind::Bool,
+}];
+
+/// `SH600519` to the data center's `600519.SH`
+fn secucode(symbol: &str) -> Result<String, String> {
+    if Market::of(symbol) != Some(Market::Cn) {
+        return Err(format!(
+            "Financial statements are only available for A-shares: {}",
+            symbol
+        ));
+    }
+    Ok(format!("{}.{}", &symbol[2..], &symbol[..2]))
+}
+
+async fn fetch_rows<T: DeserializeOwned>(
+    app: &AppHandle,
+    symbol: &str,
+    statement: Statement,
+) -> Result<Vec<T>, String> {
+    let url = format!(
+        "{}?type={}&sty={}&filter=(SECUCODE=%22{}%22)&p=1&ps={}&sr=-1&st=REPORT_DATE",
+        DATA_URL,
+        statement.report,
+        statement.columns,
+        secucode(symbol)?,
+        PERIODS
+    );
+    let policies = app.state::<PolicyEngine>();
+    let body = app
+        .state::<FaultInjector>()
+        .wrap(PROVIDER, async {
+            policies
+                .get(PROVIDER, &url)
+                .await?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", statement.what, e))
+        })
+        .await?;
+    let value = app.state::<DriftLog>().check_response(
+        app,
+        PROVIDER,
+        statement.what,
+        &body,
+        STATEMENT_SCHEMA,
+    )?;
+    parse_rows(value, statement.what)
+}
+
+async fn fetch_dividends(app: &AppHandle, symbol: &str) -> Result<Vec<DividendRow>, String> {
+    let code = secucode(symbol)?;
+    let url = format!(
+        "{}?reportName=RPT_SHAREBONUS_DET&columns=ALL&filter=(SECURITY_CODE=%22{}%22)&pageNumber=1&pageSize=100&sortColumns=EX_DIVIDEND_DATE&sortTypes=-1",
+        DIVIDEND_URL,
+        &code[..6]
+    );
+    let policies = app.state::<PolicyEngine>();
+    let body = app
+        .state::<FaultInjector>()
+        .wrap(PROVIDER, async {
+            policies
+                .get(PROVIDER, &url)
+                .await?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read dividends: {}", e))
+        })
+        .await?;
+    let value = app.state::<DriftLog>().check_response(
+        app,
+        PROVIDER,
+        "dividends",
+        &body,
+        DIVIDEND_SCHEMA,
+    )?;
+    if value.get("result").map_or(true, Value::is_null) {
+        return Ok(Vec::new());
+    }
+    parse_rows(value, "dividends")
+}
+
+fn parse_rows<T: DeserializeOwned>(value: Value, what: &str) -> Result<Vec<T>, String> {
+    let response: DataResponse<T> =
+        serde_json::from_value(value).map_err(|e| format!("Failed to parse {}: {}", what, e))?;
+    Ok(response.result.data)
+}
+
+fn income_statements(rows: Vec<IncomeRow>) -> Vec<IncomeStatement> {
+    rows.into_iter()
+        .filter_map(|row| {
+            Some(IncomeStatement {
+                period: row.period.period()?,
+                revenue: row.total_operate_income,
+                operating_cost: row.total_operate_cost,
+                operating_profit: row.operate_profit,
+                total_profit: row.total_profit,
+                net_profit: row.netprofit,
+                parent_net_profit: row.parent_netprofit,
+                basic_eps: row.basic_eps,
+            })
+        })
+        .collect()
+}
+
+fn balance_sheets(rows: Vec<BalanceRow>) -> Vec<BalanceSheet> {
+    rows.into_iter()
+        .filter_map(|row| {
+            Some(BalanceSheet {
+                period: row.period.period()?,
+                total_assets: row.total_assets,
+                total_liabilities: row.total_liabilities,
+                total_equity: row.total_equity,
+                parent_equity: row.total_parent_equity,
+                cash: row.monetaryfunds,
+                current_assets: row.total_current_assets,
+                current_liabilities: row.total_current_liab,
+                inventory: row.inventory,
+                receivables: row.accounts_rece,
+            })
+        })
+        .collect()
+}
+
+fn dividends(rows: Vec<DividendRow>) -> Vec<Dividend> {
+    rows.into_iter()
+        .filter_map(|row| {
+            Some(Dividend {
+                ex_date: parse_date(row.ex_dividend_date.as_deref()?)?,
+                cash_per_share: row.pretax_bonus_rmb.filter(|cash| *cash > 0.0)? / 10.0,
+            })
+        })
+        .collect()
+}
+
+fn cash_flow_statements(rows: Vec<CashFlowRow>) -> Vec<CashFlowStatement> {
+    rows.into_iter()
+        .filter_map(|row| {
+            Some(CashFlowStatement {
+                period: row.period.period()?,
+                operating: row.netcash_operate,
+                investing: row.netcash_invest,
+                financing: row.netcash_finance,
+                net_change: row.cce_add,
+                capex: row.construct_long_asset,
+            })
+        })
+        .collect()
+}
+
+/// Download all three statements of a symbol
+pub async fn fetch_statements(
+    app: &AppHandle,
+    symbol: &str,
+    today: NaiveDate,
+) -> Result<FinancialStatements, String> {
+    let (income, balance, cash_flow, dividend_rows) = tokio::try_join!(
+        fetch_rows(app, symbol, INCOME),
+        fetch_rows(app, symbol, BALANCE),
+        fetch_rows(app, symbol, CASH_FLOW),
+        fetch_dividends(app, symbol),
+    )?;
+    Ok(FinancialStatements {
+        symbol: symbol.to_string(),
+        income: income_statements(income),
+        balance: balance_sheets(balance),
+        cash_flow: cash_flow_statements(cash_flow),
+        dividends: dividends(dividend_rows),
+        fetched_on: today,
+        fetched_at: Utc::now().timestamp(),
+    })
+}
+
+/// Income statements, balance sheets and cash-flow statements of an A-share,
+/// from the cache unless stale or `refresh` is set
+#[tauri::command]
+pub async fn get_financial_statements(
+    app: AppHandle,
+    symbol: String,
+    refresh: Option<bool>,
+) -> Result<FinancialStatements, String> {
+    statements(&app, &symbol_key(&symbol), refresh.unwrap_or(false)).await
+}
+
+/// Statements of a symbol, from the cache unless stale or `refresh` is set
+pub async fn statements(
+    app: &AppHandle,
+    symbol: &str,
+    refresh: bool,
+) -> Result<FinancialStatements, String> {
+    let db = app.state::<Database>();
+    if let Some(frozen_at) = app.state::<SnapshotClock>().frozen_at() {
+        let conn = db.conn()?;
+        let cached = Financials(&conn).cached(symbol)?.ok_or_else(|| {
+            format!(
+                "No cached financial statements for {} in snapshot mode",
+                symbol
+            )
+        })?;
+        return Ok(cached.as_of(sessions::trading_day(Market::Cn, frozen_at)));
+    }
+    let today = Local::now().date_naive();
+    if !refresh {
+        let polling = app.state::<SettingsStore>().get().polling;
+        let max_age = polling.interval(DataClass::Fundamentals, false);
+        let conn = db.conn()?;
+        if let Some(cached) = Financials(&conn).fresh(symbol, Utc::now().timestamp(), max_age)? {
+            return Ok(cached);
+        }
+    }
+    let statements = fetch_statements(app, symbol, today).await?;
+    info!(
+        "Fetched financial statements of {}: {} periods",
+        symbol,
+        statements.income.len()
+    );
+    let conn = db.conn()?;
+    Financials(&conn).save(&statements)?;
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drift::validate;
+    use crate::testing::fixture;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_statement_rows() {
+        let value = serde_json::json!({
+            "result": {
+                "data": [
+                    {
+                        "REPORT_DATE": "2024-09-30 00:00:00",
+                        "NOTICE_DATE": "2024-10-26 00:00:00",
+                        "TOTAL_OPERATE_INCOME": 120_000_000_000.0,
+                        "NETPROFIT": 62_000_000_000.0,
+                        "PARENT_NETPROFIT": 60_800_000_000.0,
+                        "BASIC_EPS": 48.42,
+                        "OPERATE_PROFIT": null
+                    },
+                    { "REPORT_DATE": "2024-05-15 00:00:00" }
+                ]
+            }
+        });
+        let rows = parse_rows::<IncomeRow>(value, "income statement").unwrap();
+        let income = income_statements(rows);
+        // A row closing on no quarter end is dropped
+        assert_eq!(income.len(), 1);
+        let q3 = &income[0];
+        assert_eq!(q3.period.kind, PeriodKind::Q3);
+        assert_eq!(q3.period.fiscal_year, 2024);
+        assert_eq!(q3.period.published, NaiveDate::from_ymd_opt(2024, 10, 26));
+        assert_eq!(q3.basic_eps, Some(48.42));
+        assert_eq!(q3.operating_profit, None);
+
+        assert_eq!(secucode("SZ000001").unwrap(), "000001.SZ");
+        assert!(secucode("HK00700").is_err());
+    }
+
+    #[test]
+    fn test_parse_recorded_income_statements() {
+        let value: Value = serde_json::from_str(&fixture("eastmoney_income.json")).unwrap();
+        assert!(validate(&value, STATEMENT_SCHEMA).is_empty());
+        let rows = parse_rows::<IncomeRow>(value, "income statement").unwrap();
+        let income = income_statements(rows);
+        let kinds: Vec<PeriodKind> = income.iter().map(|s| s.period.kind).collect();
+        assert_eq!(kinds, [PeriodKind::Q1, PeriodKind::Annual, PeriodKind::Q3]);
+        let annual = &income[1];
+        assert_eq!(annual.period.fiscal_year, 2024);
+        assert_eq!(annual.period.published, NaiveDate::from_ymd_opt(2025, 4, 3));
+        assert_eq!(annual.revenue, Some(174_144_069_958.25));
+        assert_eq!(annual.parent_net_profit, Some(86_228_146_421.62));
+        assert_eq!(annual.basic_eps, Some(68.64));
+    }
+
+    #[test]
+    fn test_as_of_keeps_what_was_public() {
+        let value: Value = serde_json::from_str(&fixture("eastmoney_income.json")).unwrap();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let statements = FinancialStatements {
+            symbol: "SH600519".to_string(),
+            income: income_statements(parse_rows(value, "income statement").unwrap()),
+            balance: Vec::new(),
+            cash_flow: Vec::new(),
+            dividends: vec![
+                Dividend {
+                    ex_date: date(2025, 6, 26),
+                    cash_per_share: 27.673,
+                },
+                Dividend {
+                    ex_date: date(2024, 12, 20),
+                    cash_per_share: 23.882,
+                },
+            ],
+            fetched_on: date(2025, 7, 1),
+            fetched_at: 1_751_328_000,
+        };
+        // The Q1 report came out on April 30th
+        let then = statements.as_of(date(2025, 4, 15));
+        let kinds: Vec<PeriodKind> = then.income.iter().map(|s| s.period.kind).collect();
+        assert_eq!(kinds, [PeriodKind::Annual, PeriodKind::Q3]);
+        assert_eq!(then.dividends.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_expires_after_fundamentals_interval() {
+        let path = std::env::temp_dir().join(format!("ssi-financials-{}.db", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let db = Database::open(path.clone()).unwrap();
+        let conn = db.conn().unwrap();
+        let cache = Financials(&conn);
+        let fetched_at = 1_700_000_000;
+        cache
+            .save(&FinancialStatements {
+                symbol: "SH600519".to_string(),
+                income: Vec::new(),
+                balance: Vec::new(),
+                cash_flow: Vec::new(),
+                dividends: Vec::new(),
+                fetched_on: NaiveDate::from_ymd_opt(2023, 11, 14).unwrap(),
+                fetched_at,
+            })
+            .unwrap();
+        let hour = Duration::from_secs(3600);
+        let fresh = |now| cache.fresh("SH600519", now, hour).unwrap();
+        assert!(fresh(fetched_at + 3599).is_some());
+        assert!(fresh(fetched_at + 3600).is_none());
+        assert!(cache.cached("SH600519").unwrap().is_some());
+        assert!(cache.cached("SZ000001").unwrap().is_none());
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            std::fs::remove_file(file).ok();
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Fonts for Rust-side rendering (PDF reports, chart images).
+//!
+//! Installed fonts are scanned once with `fontdb` and each family is checked
+//! for Chinese glyphs by looking a few common characters up in its character
+//! map, since family names alone do not say whether a font can draw them.
+//! Renderers use [`RenderFonts`]: the user's chosen family if installed, and
+//! a CJK fallback picked from the user's choice, then the usual system CJK
+//! fonts of the OS, then any installed font that covers Chinese, so
+//! generated artifacts never fall back to tofu boxes while a usable font
+//! exists.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use fontdb::Database;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::settings::SettingsStore;
+
+/// Characters a font must have to count as covering Chinese
+const CJK_PROBE: &str = "中文股票涨跌";
+
+/// Default text families, most preferred first
+fn default_families() -> &'static [&'static str] {
+    if cfg!(target_os = "windows") {
+        &["Segoe UI", "Arial"]
+    } else if cfg!(target_os = "macos") {
+        &["Helvetica Neue", "Helvetica", "Arial"]
+    } else {
+        &["Noto Sans", "DejaVu Sans", "Liberation Sans"]
+    }
+}
+
+/// System CJK families, most preferred first
+fn cjk_families() -> &'static [&'static str] {
+    if cfg!(target_os = "windows") {
+        &["Microsoft YaHei", "DengXian", "SimHei", "SimSun"]
+    } else if cfg!(target_os = "macos") {
+        &[
+            "PingFang SC",
+            "Hiragino Sans GB",
+            "Heiti SC",
+            "STHeiti",
+            "Songti SC",
+        ]
+    } else {
+        &[
+            "Noto Sans CJK SC",
+            "Source Han Sans SC",
+            "WenQuanYi Micro Hei",
+            "WenQuanYi Zen Hei",
+            "Droid Sans Fallback",
+            "AR PL UMing CN",
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontSettings {
+    /// Family for text, by any of its names; the OS default when unset
+    pub family: Option<String>,
+    /// Family for Chinese text when `family` lacks the glyphs
+    pub cjk_family: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FontFamily {
+    pub family: String,
+    /// Every name the family goes by, localized ones included (e.g. 微软雅黑)
+    pub names: Vec<String>,
+    pub cjk: bool,
+    pub monospace: bool,
+    pub faces: usize,
+}
+
+impl FontFamily {
+    fn named(&self, name: &str) -> bool {
+        self.names
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RenderFonts {
+    pub family: Option<String>,
+    /// Fallback for Chinese text; the same as `family` when it covers Chinese
+    pub cjk_family: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+fn covers_cjk(data: &[u8], index: u32) -> bool {
+    ttf_parser::Face::parse(data, index)
+        .map(|face| CJK_PROBE.chars().all(|c| face.glyph_index(c).is_some()))
+        .unwrap_or(false)
+}
+
+fn scan(db: &Database) -> Vec<FontFamily> {
+    let mut families: BTreeMap<String, FontFamily> = BTreeMap::new();
+    for face in db.faces() {
+        let Some((family, _)) = face.families.first() else {
+            continue;
+        };
+        let cjk = db.with_face_data(face.id, covers_cjk).unwrap_or(false);
+        let entry = families
+            .entry(family.clone())
+            .or_insert_with(|| FontFamily {
+                family: family.clone(),
+                names: Vec::new(),
+                cjk: false,
+                monospace: false,
+                faces: 0,
+            });
+        entry.faces += 1;
+        entry.cjk |= cjk;
+        entry.monospace |= face.monospaced;
+        for (name, _) in &face.families {
+            if !entry.names.contains(name) {
+                entry.names.push(name.clone());
+            }
+        }
+    }
+    families.into_values().collect()
+}
+
+/// Pick the families renderers should use
+pub fn resolve(
+    settings: &FontSettings,
+    installed: &[FontFamily],
+    defaults: &[&str],
+    cjk_defaults: &[&str],
+) -> RenderFonts {
+    let mut warnings = Vec::new();
+    let find = |name: &str| installed.iter().find(|f| f.named(name));
+
+    let chosen = settings.family.as_deref().and_then(|name| {
+        let found = find(name);
+        if found.is_none() {
+            warnings.push(format!("Font {} is not installed", name));
+        }
+        found
+    });
+    let family = chosen
+        .or_else(|| defaults.iter().find_map(|name| find(name)))
+        .or_else(|| installed.iter().find(|f| !f.monospace));
+
+    let cjk = if let Some(family) = family.filter(|f| f.cjk) {
+        Some(family)
+    } else {
+        let chosen = settings.cjk_family.as_deref().and_then(|name| {
+            let found = find(name).filter(|f| f.cjk);
+            if found.is_none() {
+                warnings.push(format!(
+                    "Font {} is not installed or has no Chinese glyphs",
+                    name
+                ));
+            }
+            found
+        });
+        chosen
+            .or_else(|| {
+                cjk_defaults
+                    .iter()
+                    .find_map(|name| find(name).filter(|f| f.cjk))
+            })
+            .or_else(|| installed.iter().find(|f| f.cjk))
+    };
+    if cjk.is_none() {
+        warnings.push(
+            "No installed font has Chinese glyphs; Chinese text in reports will not render"
+                .to_string(),
+        );
+    }
+    RenderFonts {
+        family: family.map(|f| f.family.clone()),
+        cjk_family: cjk.map(|f| f.family.clone()),
+        warnings,
+    }
+}
+
+/// Installed font families, scanned on first use
+#[derive(Default)]
+pub struct FontCatalog {
+    families: Mutex<Option<Arc<Vec<FontFamily>>>>,
+}
+
+impl FontCatalog {
+    pub fn families(&self, refresh: bool) -> Arc<Vec<FontFamily>> {
+        let mut families = self.families.lock().unwrap();
+        if refresh || families.is_none() {
+            let mut db = Database::new();
+            db.load_system_fonts();
+            let scanned = scan(&db);
+            info!(
+                "Found {} font families, {} with Chinese glyphs",
+                scanned.len(),
+                scanned.iter().filter(|f| f.cjk).count()
+            );
+            *families = Some(Arc::new(scanned));
+        }
+        families.clone().unwrap_or_default()
+    }
+
+    /// Fonts renderers should use under the current settings
+    pub fn render_fonts(&self, settings: &FontSettings) -> RenderFonts {
+        let fonts = resolve(
+            settings,
+            &self.families(false),
+            default_families(),
+            cjk_families(),
+        );
+        for warning in &fonts.warnings {
+            warn!("{}", warning);
+        }
+        fonts
+    }
+}
+
+/// Installed font families; `refresh` rescans after fonts were installed
+#[tauri::command]
+pub async fn list_system_fonts(
+    app: AppHandle,
+    refresh: Option<bool>,
+) -> Result<Vec<FontFamily>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<FontCatalog>()
+            .families(refresh.unwrap_or(false))
+            .to_vec()
+    })
+    .await
+    .map_err(|e| format!("Font scan failed: {}", e))
+}
+
+/// The fonts reports and chart images will be rendered with
+#[tauri::command]
+pub async fn get_render_fonts(app: AppHandle) -> Result<RenderFonts, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = app.state::<SettingsStore>().get().fonts;
+        app.state::<FontCatalog>().render_fonts(&settings)
+    })
+    .await
+    .map_err(|e| format!("Font scan failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(names: &[&str], cjk: bool) -> FontFamily {
+        FontFamily {
+            family: names[0].to_string(),
+            names: names.iter().map(|n| n.to_string()).collect(),
+            cjk,
+            monospace: false,
+            faces: 1,
+        }
+    }
+
+    #[test]
+    fn test_cjk_fallback_resolution() {
+        let installed = vec![
+            family(&["DejaVu Sans"], false),
+            family(&["Microsoft YaHei", "微软雅黑"], true),
+            family(&["Some Pan-CJK"], true),
+        ];
+        let defaults = &["DejaVu Sans"];
+        let cjk_defaults = &["Microsoft YaHei"];
+
+        let fonts = resolve(&FontSettings::default(), &installed, defaults, cjk_defaults);
+        assert_eq!(fonts.family.as_deref(), Some("DejaVu Sans"));
+        assert_eq!(fonts.cjk_family.as_deref(), Some("Microsoft YaHei"));
+        assert!(fonts.warnings.is_empty());
+
+        // Localized names match, and a CJK text family needs no fallback
+        let settings = FontSettings {
+            family: Some("微软雅黑".to_string()),
+            cjk_family: None,
+        };
+        let fonts = resolve(&settings, &installed, defaults, cjk_defaults);
+        assert_eq!(fonts.cjk_family.as_deref(), Some("Microsoft YaHei"));
+
+        // Missing choices are reported and replaced
+        let settings = FontSettings {
+            family: Some("Comic Sans".to_string()),
+            cjk_family: Some("DejaVu Sans".to_string()),
+        };
+        let fonts = resolve(&settings, &installed, defaults, &[]);
+        assert_eq!(fonts.family.as_deref(), Some("DejaVu Sans"));
+        assert_eq!(fonts.cjk_family.as_deref(), Some("Microsoft YaHei"));
+        assert_eq!(fonts.warnings.len(), 2);
+
+        let latin_only = resolve(
+            &FontSettings::default(),
+            &installed[..1],
+            defaults,
+            cjk_defaults,
+        );
+        assert!(latin_only.cjk_family.is_none());
+        assert_eq!(latin_only.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,532 @@
+//! User formulas for custom indicators and watchlist columns.
+//!
+//! Formulas are [Rhai](https://rhai.rs) scripts run against a symbol's bars.
+//! The bar fields are in scope as arrays aligned with the bars (`open`,
+//! `high`, `low`, `close`, `volume`), along with series helpers: `sma`,
+//! `ema`, `rsi`, `highest`, `lowest`, `ref` (the series `n` bars back) and
+//! element-wise `add`, `sub`, `mul` and `div` taking series or numbers.
+//! Values not yet defined, such as a moving average's warm-up, are `()`.
+//! An indicator formula returns a series, drawn under the chart; a column
+//! formula returns a number, or a series whose last value is taken, shown
+//! against each symbol of a watchlist from its cached daily bars.
+//!
+//! Scripts are sandboxed: Rhai has no file, network or process access, and
+//! the engine here also refuses `eval`, discards `print` and caps the
+//! operations, call depth and collection sizes a run may use. Since one
+//! operation may be a series helper costing a pass over every bar, each run
+//! also has a wall-clock limit, so a runaway loop fails with an error
+//! instead of hanging a thread. Syntax errors are
+//! reported with their line and column when a formula is saved or checked,
+//! and runtime errors the same way when it runs.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rayon::prelude::*;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, ParseError, Position, Scope, AST, FLOAT, INT};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::columnar::ColumnarStore;
+use crate::db::{Database, SavedFormulas};
+use crate::executor::Priority;
+use crate::indicators::{self, Series};
+use crate::kline::{self, series_interval, Adjust, KlinePeriod};
+use crate::models::Bar;
+use crate::rolling;
+use crate::snapshot::SnapshotClock;
+use crate::symbols::symbol_key;
+
+/// Operations a single run may take
+const MAX_OPERATIONS: u64 = 5_000_000;
+/// Longest array a script may build, comfortably above a daily history
+const MAX_ARRAY_SIZE: usize = 200_000;
+/// Longest a single run may take
+const MAX_RUN_TIME: Duration = Duration::from_secs(2);
+/// Operations between two looks at the clock
+const CLOCK_CHECK_INTERVAL: u64 = 256;
+
+thread_local! {
+    /// When the run on this thread has to stop
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormulaKind {
+    Indicator,
+    Column,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Formula {
+    /// Unique; the indicator's label or the column's header
+    pub name: String,
+    pub kind: FormulaKind,
+    pub source: String,
+}
+
+/// A syntax or runtime error, located in the source when possible
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FormulaError {
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl FormulaError {
+    fn at(message: String, position: Position) -> Self {
+        Self {
+            message,
+            line: position.line(),
+            column: position.position(),
+        }
+    }
+
+    fn plain(message: String) -> Self {
+        Self {
+            message,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+impl From<ParseError> for FormulaError {
+    fn from(e: ParseError) -> Self {
+        let position = e.position();
+        Self::at(e.err_type().to_string(), position)
+    }
+}
+
+impl From<Box<EvalAltResult>> for FormulaError {
+    fn from(e: Box<EvalAltResult>) -> Self {
+        let position = e.position();
+        let mut e = *e;
+        e.clear_position();
+        let message = match &e {
+            EvalAltResult::ErrorTerminated(reason, _) => reason.to_string(),
+            e => e.to_string(),
+        };
+        Self::at(message, position)
+    }
+}
+
+impl std::fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)
+            }
+            (Some(line), None) => write!(f, "line {}: {}", line, self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Script values to numbers, `NaN` where undefined
+fn to_values(series: Array) -> Vec<f64> {
+    series
+        .iter()
+        .map(|v| number(v).unwrap_or(f64::NAN))
+        .collect()
+}
+
+fn number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|v| v as FLOAT))
+        .filter(|v| v.is_finite())
+}
+
+fn to_array(values: impl IntoIterator<Item = f64>) -> Array {
+    values
+        .into_iter()
+        .map(|v| {
+            if v.is_finite() {
+                Dynamic::from_float(v)
+            } else {
+                Dynamic::UNIT
+            }
+        })
+        .collect()
+}
+
+/// Apply a window function to the defined tail of a series, so the warm-up
+/// of a series derived from another doesn't spread into the result
+fn on_defined(series: Array, f: impl Fn(&[f64]) -> Vec<f64>) -> Array {
+    let values = to_values(series);
+    let start = values
+        .iter()
+        .position(|v| !v.is_nan())
+        .unwrap_or(values.len());
+    let mut out = vec![f64::NAN; start];
+    out.extend(f(&values[start..]));
+    to_array(out)
+}
+
+fn window(n: INT) -> usize {
+    n.max(0) as usize
+}
+
+/// Element-wise operation over series and numbers; a number applies to
+/// every bar
+fn zip_with(a: Dynamic, b: Dynamic, op: fn(f64, f64) -> f64) -> Array {
+    let length = |v: &Dynamic| v.read_lock::<Array>().map(|a| a.len());
+    let len = length(&a).or_else(|| length(&b)).unwrap_or(1);
+    let at = |v: &Dynamic, i: usize| -> f64 {
+        match v.read_lock::<Array>() {
+            Some(array) => array.get(i).and_then(number).unwrap_or(f64::NAN),
+            None => number(v).unwrap_or(f64::NAN),
+        }
+    };
+    to_array((0..len).map(|i| op(at(&a, i), at(&b, i))))
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(10_000)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(1_000)
+        .on_progress(|operations| {
+            if operations % CLOCK_CHECK_INTERVAL != 0 {
+                return None;
+            }
+            let expired = DEADLINE
+                .with(Cell::get)
+                .is_some_and(|deadline| Instant::now() >= deadline);
+            expired.then(|| {
+                Dynamic::from(format!(
+                    "The formula ran longer than {} seconds",
+                    MAX_RUN_TIME.as_secs()
+                ))
+            })
+        })
+        .on_print(|_| {})
+        .on_debug(|_, _, _| {});
+    engine.disable_symbol("eval");
+
+    engine
+        .register_fn("sma", |series: Array, n: INT| {
+            on_defined(series, |v| rolling::rolling_mean(v, window(n)))
+        })
+        .register_fn("ema", |series: Array, n: INT| {
+            on_defined(series, |v| rolling::ema(v, window(n)))
+        })
+        .register_fn("rsi", |series: Array, n: INT| {
+            on_defined(series, |v| {
+                let rsi = indicators::rsi(v, window(n));
+                rsi.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect()
+            })
+        })
+        .register_fn("highest", |series: Array, n: INT| {
+            on_defined(series, |v| rolling::rolling_max(v, window(n)))
+        })
+        .register_fn("lowest", |series: Array, n: INT| {
+            on_defined(series, |v| rolling::rolling_min(v, window(n)))
+        })
+        .register_fn("ref", |series: Array, n: INT| {
+            let n = window(n);
+            let mut shifted: Array = vec![Dynamic::UNIT; n.min(series.len())];
+            shifted.extend(series.iter().take(series.len().saturating_sub(n)).cloned());
+            shifted
+        });
+    for (name, op) in [
+        ("add", (|a, b| a + b) as fn(f64, f64) -> f64),
+        ("sub", |a, b| a - b),
+        ("mul", |a, b| a * b),
+        ("div", |a, b| if b == 0.0 { f64::NAN } else { a / b }),
+    ] {
+        engine.register_fn(name, move |a: Dynamic, b: Dynamic| zip_with(a, b, op));
+    }
+    engine
+}
+
+fn scope(bars: &[Bar]) -> Scope<'static> {
+    let column = |value: fn(&Bar) -> f64| -> Array { to_array(bars.iter().map(value)) };
+    let mut scope = Scope::new();
+    scope
+        .push_constant("open", column(|b| b.open))
+        .push_constant("high", column(|b| b.high))
+        .push_constant("low", column(|b| b.low))
+        .push_constant("close", column(|b| b.close))
+        .push_constant("volume", column(|b| b.volume));
+    scope
+}
+
+/// The sandboxed engine formulas run on; the formulas themselves are kept
+/// in the database
+pub struct Formulas {
+    engine: Engine,
+}
+
+impl Default for Formulas {
+    fn default() -> Self {
+        Self { engine: engine() }
+    }
+}
+
+impl Formulas {
+    pub fn compile(&self, source: &str) -> Result<AST, FormulaError> {
+        Ok(self.engine.compile(source)?)
+    }
+
+    /// Run a formula over `bars` within [`MAX_RUN_TIME`]
+    fn eval(&self, ast: &AST, bars: &[Bar]) -> Result<Dynamic, FormulaError> {
+        DEADLINE.with(|deadline| deadline.set(Some(Instant::now() + MAX_RUN_TIME)));
+        let result = self.engine.eval_ast_with_scope(&mut scope(bars), ast);
+        DEADLINE.with(|deadline| deadline.set(None));
+        Ok(result?)
+    }
+
+    /// Series of an indicator formula, aligned with `bars`
+    pub fn indicator(&self, ast: &AST, bars: &[Bar]) -> Result<Series, FormulaError> {
+        let result = self.eval(ast, bars)?;
+        let Some(series) = result.read_lock::<Array>().map(|a| a.clone()) else {
+            return Err(FormulaError::plain(format!(
+                "An indicator must return a series, not {}",
+                result.type_name()
+            )));
+        };
+        if series.len() != bars.len() {
+            return Err(FormulaError::plain(format!(
+                "The formula returned {} values for {} bars",
+                series.len(),
+                bars.len()
+            )));
+        }
+        Ok(series.iter().map(number).collect())
+    }
+
+    /// Value of a column formula on the last of `bars`
+    pub fn column(&self, ast: &AST, bars: &[Bar]) -> Result<Option<f64>, FormulaError> {
+        let result = self.eval(ast, bars)?;
+        if let Some(series) = result.read_lock::<Array>() {
+            return Ok(series.last().and_then(number));
+        }
+        if result.is_unit() {
+            return Ok(None);
+        }
+        number(&result).map(Some).ok_or_else(|| {
+            FormulaError::plain(format!(
+                "A column must return a number, not {}",
+                result.type_name()
+            ))
+        })
+    }
+}
+
+#[tauri::command]
+pub fn list_formulas(db: State<'_, Database>) -> Result<Vec<Formula>, String> {
+    let conn = db.conn()?;
+    SavedFormulas(&conn).list()
+}
+
+/// Syntax errors of a formula being edited; `None` when it compiles
+#[tauri::command]
+pub fn check_formula(
+    formulas: State<'_, Formulas>,
+    source: String,
+) -> Result<Option<FormulaError>, String> {
+    Ok(formulas.compile(&source).err())
+}
+
+/// Save a formula, replacing one of the same name; refused if it doesn't
+/// compile
+#[tauri::command]
+pub fn save_formula(
+    formulas: State<'_, Formulas>,
+    db: State<'_, Database>,
+    formula: Formula,
+) -> Result<(), String> {
+    if formula.name.trim().is_empty() {
+        return Err("Formula name must not be empty".to_string());
+    }
+    formulas
+        .compile(&formula.source)
+        .map_err(|e| format!("Formula {} doesn't compile: {}", formula.name, e))?;
+    let conn = db.conn()?;
+    SavedFormulas(&conn).save(&formula)
+}
+
+#[tauri::command]
+pub fn delete_formula(db: State<'_, Database>, name: String) -> Result<(), String> {
+    let conn = db.conn()?;
+    SavedFormulas(&conn).delete(&name)?;
+    info!("Deleted formula {}", name);
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct FormulaSeries {
+    pub timestamps: Vec<i64>,
+    pub values: Series,
+}
+
+/// Run an indicator formula over a symbol's candles, as served by `get_kline`
+#[tauri::command]
+pub async fn compute_formula(
+    app: AppHandle,
+    symbol: String,
+    period: KlinePeriod,
+    adjust: Option<Adjust>,
+    source: String,
+) -> Result<FormulaSeries, String> {
+    let adjust = adjust.unwrap_or_default();
+    let bars = kline::candles(&app, &symbol, period, adjust, Priority::Interactive).await?;
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let formulas = handle.state::<Formulas>();
+        let values = formulas
+            .compile(&source)
+            .and_then(|ast| formulas.indicator(&ast, &bars))
+            .map_err(|e| e.to_string())?;
+        Ok(FormulaSeries {
+            timestamps: bars.iter().map(|b| b.timestamp).collect(),
+            values,
+        })
+    })
+    .await
+    .map_err(|e| format!("Failed to run formula: {}", e))?
+}
+
+/// Column formula values by symbol and formula name, from cached daily bars;
+/// a formula failing on a symbol leaves its cell empty
+#[tauri::command]
+pub async fn compute_formula_columns(
+    app: AppHandle,
+    symbols: Vec<String>,
+) -> Result<BTreeMap<String, BTreeMap<String, Option<f64>>>, String> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let formulas = handle.state::<Formulas>();
+        let store = handle.state::<ColumnarStore>();
+        let until = handle.state::<SnapshotClock>().frozen_at();
+        let interval = series_interval(KlinePeriod::Daily, Adjust::None);
+        let db = handle.state::<Database>();
+        let saved = SavedFormulas(&db.conn()?).list()?;
+        let columns: Vec<(String, AST)> = saved
+            .into_iter()
+            .filter(|formula| formula.kind == FormulaKind::Column)
+            .filter_map(|formula| match formulas.compile(&formula.source) {
+                Ok(ast) => Some((formula.name, ast)),
+                Err(e) => {
+                    warn!("Skipped column {}: {}", formula.name, e);
+                    None
+                }
+            })
+            .collect();
+        Ok(symbols
+            .par_iter()
+            .map(|symbol| {
+                let symbol = symbol_key(symbol);
+                let bars = store
+                    .open(&symbol, &interval)
+                    .map(|bars| bars.to_bars(0..bars.rows_until(until)))
+                    .unwrap_or_default();
+                let values = columns
+                    .iter()
+                    .map(|(name, ast)| {
+                        let value = if bars.is_empty() {
+                            None
+                        } else {
+                            formulas.column(ast, &bars).unwrap_or_else(|e| {
+                                warn!("Column {} failed on {}: {}", name, symbol, e);
+                                None
+                            })
+                        };
+                        (name.clone(), value)
+                    })
+                    .collect();
+                (symbol, values)
+            })
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("Failed to compute formula columns: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(closes: &[f64]) -> Vec<Bar> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Bar {
+                timestamp: i as i64 * 86_400,
+                open: close - 0.5,
+                high: close + 1.0,
+                low: close - 1.0,
+                close: *close,
+                volume: 1000.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_run_formulas() {
+        let formulas = Formulas::default();
+        let bars = bars(&[10.0, 11.0, 12.0, 13.0, 14.0]);
+        let run = |source: &str| {
+            let ast = formulas.compile(source).unwrap();
+            formulas.indicator(&ast, &bars)
+        };
+
+        assert_eq!(
+            run("sma(close, 3)").unwrap(),
+            vec![None, None, Some(11.0), Some(12.0), Some(13.0)]
+        );
+        // The warm-up of the inner average doesn't spread into the outer one
+        assert_eq!(
+            run("sma(sma(close, 2), 2)").unwrap(),
+            vec![None, None, Some(11.0), Some(12.0), Some(13.0)]
+        );
+        assert_eq!(
+            run("sub(close, ref(close, 1))").unwrap(),
+            vec![None, Some(1.0), Some(1.0), Some(1.0), Some(1.0)]
+        );
+        assert_eq!(run("mul(close, 2)").unwrap()[4], Some(28.0));
+
+        let column = |source: &str| formulas.column(&formulas.compile(source).unwrap(), &bars);
+        assert_eq!(column("close[-1] / close[0]").unwrap(), Some(1.4));
+        assert_eq!(column("highest(high, 2)").unwrap(), Some(15.0));
+
+        let error = formulas.compile("let x = ;\nclose").unwrap_err();
+        assert_eq!((error.line, error.column), (Some(1), Some(9)));
+        assert!(run("1 + 1").unwrap_err().message.contains("series"));
+        assert!(run("[1.0]")
+            .unwrap_err()
+            .message
+            .contains("1 values for 5 bars"));
+        // Runaway scripts stop at the operation limit instead of hanging
+        assert!(run("loop {}").is_err());
+        assert!(formulas.compile("eval(\"1\")").is_err());
+    }
+
+    #[test]
+    fn test_slow_native_calls_hit_the_time_limit() {
+        let formulas = Formulas::default();
+        let closes: Vec<f64> = (0..2000).map(|i| 10.0 + (i % 50) as f64).collect();
+        let bars = bars(&closes);
+        // Well under the operation limit, but each call walks every bar
+        let source = "let s = (); for i in 0..1000000 { s = sma(close, 250); } s";
+        let started = Instant::now();
+        let error = formulas
+            .indicator(&formulas.compile(source).unwrap(), &bars)
+            .unwrap_err();
+        assert!(error.message.contains("ran longer than"));
+        assert!(started.elapsed() < MAX_RUN_TIME * 3);
+        // The limit is per run
+        let ast = formulas.compile("sma(close, 5)").unwrap();
+        assert!(formulas.indicator(&ast, &bars).is_ok());
+    }
+}
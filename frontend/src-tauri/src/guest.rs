@@ -0,0 +1,385 @@
+//! Read-only guest sessions.
+//!
+//! Launching with `--guest`, or with "start as guest" set in settings,
+//! opens a session in which only the commands listed in
+//! [`READ_ONLY_COMMANDS`] run; everything else is rejected before it reaches
+//! its handler by the command [`middleware`](crate::middleware), so a
+//! command added later is refused to guests until it is deliberately listed
+//! here. A guest
+//! session started from settings or `enter_guest_mode` ends with the
+//! passcode, if one is set; one started with `--guest` lasts until restart.
+//! After a few wrong passcodes each further attempt waits twice as long as
+//! the last, up to [`MAX_PASSCODE_DELAY`].
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::settings::SettingsStore;
+
+pub const GUEST_FLAG: &str = "--guest";
+
+/// Wrong passcodes allowed before further attempts are delayed
+const FREE_PASSCODE_ATTEMPTS: u32 = 3;
+/// Longest wait between passcode attempts
+const MAX_PASSCODE_DELAY: Duration = Duration::from_secs(300);
+
+/// Commands a guest may run: views, lookups and calculations that change no user data
+const READ_ONLY_COMMANDS: &[&str] = &[
+    "get_app_info",
+    "get_system_info",
+    "get_memory_breakdown",
+    "list_scheduled_jobs",
+    "get_providers",
+    "get_fundamentals",
+    "get_financial_statements",
+    "get_valuation",
+    "check_for_updates",
+    "minimize_to_tray",
+    "begin_drag",
+    "toggle_maximize",
+    "set_always_on_top",
+    "get_titlebar_hints",
+    "show_snap_layouts",
+    "show_notification",
+    "get_columnar_info",
+    "compute_indicators_batch",
+    "benchmark_indicators",
+    "run_screen",
+    "get_dashboard_tile",
+    "list_formulas",
+    "check_formula",
+    "compute_formula",
+    "compute_formula_columns",
+    "list_tasks",
+    "get_executor_stats",
+    "record_symbol_view",
+    "take_launch_action",
+    "get_preload_stats",
+    "get_snapshot_time",
+    "list_faults",
+    "get_universe_changes",
+    "get_universe",
+    "get_cost_presets",
+    "list_strategies",
+    "list_backtest_runs",
+    "compare_runs",
+    "list_strategy_monitors",
+    "get_signal_journal",
+    "acquire_symbol",
+    "release_symbol",
+    "watch_indicators",
+    "unwatch_indicators",
+    "list_subscriptions",
+    "get_provider_drift",
+    "get_provider_sessions",
+    "list_scrape_sources",
+    "scrape_page",
+    "get_source_policies",
+    "get_news",
+    "get_article_content",
+    "get_news_clusters",
+    "list_news_watches",
+    "get_watched_news",
+    "list_symbol_aliases",
+    "list_documents",
+    "get_document",
+    "search_documents",
+    "get_proxy_routes",
+    "test_source_connectivity",
+    "get_tls_diagnostics",
+    "get_provider_credentials",
+    "get_portfolio",
+    "list_transactions",
+    "get_portfolio_pnl",
+    "get_audit_trail",
+    "reconcile_account",
+    "run_readonly_query",
+    "describe_datasets",
+    "list_workspaces",
+    "load_workspace",
+    "get_appearance",
+    "list_system_fonts",
+    "get_render_fonts",
+    "get_session_status_banner",
+    "get_market_phases",
+    "list_news_backfills",
+    "get_embedding_status",
+    "semantic_search",
+    "get_answer_sources",
+    "get_ai_routing",
+    "get_notification_settings",
+    "list_notifications",
+    "get_unread_notification_count",
+    "get_ai_batch",
+    "list_ai_batches",
+    "list_watchlists",
+    "list_portfolios",
+    "list_alerts",
+    "list_portfolio_alerts",
+    "list_alert_triggers",
+    "list_delivery_channels",
+    "list_notes",
+    "list_watch_folders",
+    "explain_trigger",
+    "get_quotes",
+    "validate_symbol",
+    "search_symbols",
+    "get_quote_stream_state",
+    "get_session_presence",
+    "open_chart_window",
+    "toggle_ticker_bar",
+    "list_monitors",
+    "enter_presentation_mode",
+    "exit_presentation_mode",
+    "get_presentation_state",
+    "submit_archive_snapshot",
+    "verify_archive",
+    "get_window_zoom",
+    "get_kline",
+    "compute_indicators",
+    "get_watchlist_view",
+    "get_privacy_mode",
+    "get_active_policies",
+    "get_guest_mode",
+    "enter_guest_mode",
+    "exit_guest_mode",
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuestSettings {
+    /// Open every session as a guest
+    pub start_as_guest: bool,
+    /// `salt$sha256` of the passcode that ends a guest session, if any
+    pub passcode: Option<String>,
+}
+
+fn hash_passcode(salt: &str, passcode: &str) -> String {
+    hex::encode(Sha256::digest(format!("{}{}", salt, passcode)))
+}
+
+impl GuestSettings {
+    fn passcode_matches(&self, passcode: Option<&str>) -> bool {
+        match (&self.passcode, passcode) {
+            (None, _) => true,
+            (Some(stored), Some(passcode)) => stored
+                .split_once('$')
+                .is_some_and(|(salt, hash)| hash_passcode(salt, passcode) == hash),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PasscodeAttempts {
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+impl PasscodeAttempts {
+    fn check(&self, now: Instant) -> Result<(), String> {
+        match self.locked_until {
+            Some(until) if now < until => Err(format!(
+                "Too many wrong passcodes, try again in {} s",
+                (until - now).as_secs_f64().ceil()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lock out attempts for a delay that doubles with every failure past the free ones
+    fn record_failure(&mut self, now: Instant) {
+        self.failures += 1;
+        if self.failures >= FREE_PASSCODE_ATTEMPTS {
+            let doublings = (self.failures - FREE_PASSCODE_ATTEMPTS).min(16);
+            let delay = Duration::from_secs(1 << doublings).min(MAX_PASSCODE_DELAY);
+            self.locked_until = Some(now + delay);
+        }
+    }
+}
+
+pub struct GuestMode {
+    active: AtomicBool,
+    /// Started with `--guest`, which only a restart ends
+    pinned: bool,
+    attempts: Mutex<PasscodeAttempts>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuestStatus {
+    pub active: bool,
+    pub can_exit: bool,
+}
+
+impl GuestMode {
+    pub fn new(flag: bool, settings: &GuestSettings) -> Self {
+        let active = flag || settings.start_as_guest;
+        if active {
+            info!("Starting read-only guest session");
+        }
+        Self {
+            active: AtomicBool::new(active),
+            pinned: flag,
+            attempts: Mutex::new(PasscodeAttempts::default()),
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn admit(&self, command: &str) -> Result<(), String> {
+        if self.active() && !READ_ONLY_COMMANDS.contains(&command) {
+            return Err(format!(
+                "{} is not available in a read-only guest session",
+                command
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn emit_status(app: &AppHandle, status: &GuestStatus) {
+    if let Err(e) = app.emit("guest-mode-changed", status.clone()) {
+        warn!("Failed to emit guest-mode-changed event: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_guest_mode(guest: State<'_, GuestMode>) -> Result<GuestStatus, String> {
+    Ok(GuestStatus {
+        active: guest.active(),
+        can_exit: !guest.pinned,
+    })
+}
+
+/// Switch this session to read-only, e.g. before handing the screen over
+#[tauri::command]
+pub fn enter_guest_mode(app: AppHandle, guest: State<'_, GuestMode>) -> Result<(), String> {
+    guest.active.store(true, Ordering::Relaxed);
+    info!("Entered read-only guest session");
+    emit_status(
+        &app,
+        &GuestStatus {
+            active: true,
+            can_exit: !guest.pinned,
+        },
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub fn exit_guest_mode(
+    app: AppHandle,
+    guest: State<'_, GuestMode>,
+    settings: State<'_, SettingsStore>,
+    passcode: Option<String>,
+) -> Result<(), String> {
+    if guest.pinned {
+        return Err(format!(
+            "Restart the app without {} to leave guest mode",
+            GUEST_FLAG
+        ));
+    }
+    let mut attempts = guest.attempts.lock().unwrap();
+    let now = Instant::now();
+    attempts.check(now)?;
+    if !settings.get().guest.passcode_matches(passcode.as_deref()) {
+        attempts.record_failure(now);
+        warn!(
+            "Wrong passcode to leave guest mode ({} in a row)",
+            attempts.failures
+        );
+        return Err("Wrong passcode".to_string());
+    }
+    *attempts = PasscodeAttempts::default();
+    drop(attempts);
+    guest.active.store(false, Ordering::Relaxed);
+    info!("Left read-only guest session");
+    emit_status(
+        &app,
+        &GuestStatus {
+            active: false,
+            can_exit: true,
+        },
+    );
+    Ok(())
+}
+
+/// Set or clear the passcode that ends a guest session
+#[tauri::command]
+pub fn set_guest_passcode(
+    settings: State<'_, SettingsStore>,
+    passcode: Option<String>,
+) -> Result<(), String> {
+    let mut updated = settings.get();
+    updated.guest.passcode = passcode.filter(|p| !p.is_empty()).map(|passcode| {
+        let mut salt = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        let salt = hex::encode(salt);
+        format!("{}${}", salt, hash_passcode(&salt, &passcode))
+    });
+    settings.set(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guest_admits_only_read_only_commands() {
+        let host = GuestMode::new(false, &GuestSettings::default());
+        assert!(host.admit("remove_transaction").is_ok());
+
+        let guest = GuestMode::new(true, &GuestSettings::default());
+        assert!(guest.admit("get_portfolio").is_ok());
+        assert!(guest.admit("remove_transaction").is_err());
+        assert!(guest.admit("update_settings").is_err());
+        // Freezing the clock and fetching news change what everyone sees
+        assert!(guest.admit("set_snapshot_time").is_err());
+        assert!(guest.admit("refresh_news").is_err());
+        assert!(guest.admit("some_future_command").is_err());
+
+        let salt = "00ff";
+        let settings = GuestSettings {
+            start_as_guest: true,
+            passcode: Some(format!("{}${}", salt, hash_passcode(salt, "1234"))),
+        };
+        assert!(GuestMode::new(false, &settings).active());
+        assert!(settings.passcode_matches(Some("1234")));
+        assert!(!settings.passcode_matches(Some("0000")));
+        assert!(!settings.passcode_matches(None));
+        assert!(GuestSettings::default().passcode_matches(None));
+    }
+
+    #[test]
+    fn test_wrong_passcodes_back_off() {
+        let start = Instant::now();
+        let mut attempts = PasscodeAttempts::default();
+        for _ in 1..FREE_PASSCODE_ATTEMPTS {
+            attempts.record_failure(start);
+            assert!(attempts.check(start).is_ok());
+        }
+
+        attempts.record_failure(start);
+        assert!(attempts.check(start).is_err());
+        assert!(attempts.check(start + Duration::from_secs(1)).is_ok());
+
+        // Each further failure doubles the wait
+        attempts.record_failure(start);
+        assert!(attempts.check(start + Duration::from_secs(1)).is_err());
+        assert!(attempts.check(start + Duration::from_secs(2)).is_ok());
+
+        for _ in 0..40 {
+            attempts.record_failure(start);
+        }
+        assert!(attempts.check(start + MAX_PASSCODE_DELAY).is_ok());
+    }
+}
@@ -0,0 +1,229 @@
+//! Periodic memory housekeeping for long-running sessions.
+//!
+//! Every [`INTERVAL`] the app drops state nothing uses any more: the
+//! subscriptions of windows that are gone or have stayed hidden for
+//! [`COLD_AFTER`], the per-window view snapshots and indicator watches of
+//! windows that are gone, and columnar mappings no reader holds. Hidden
+//! windows learn of released symbols from `subscriptions-changed` and acquire
+//! them again when shown. Under memory pressure the indicator cache and the
+//! watchlist view snapshots are cleared too; both rebuild on demand.
+//! `get_memory_breakdown` reports what each subsystem holds.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use log::info;
+use serde::Serialize;
+use sysinfo::{ProcessRefreshKind, System};
+use tauri::{AppHandle, Manager, State};
+
+use crate::columnar::ColumnarStore;
+use crate::indicators::IndicatorCache;
+use crate::live_indicators::LiveIndicators;
+use crate::quotes::{Quote, QuoteFeed};
+use crate::subscriptions::{self, SubscriptionRegistry};
+use crate::watchlist_view::WatchlistViews;
+
+/// Time between housekeeping runs
+pub const INTERVAL: Duration = Duration::from_secs(300);
+/// How long a hidden window keeps its subscriptions
+pub const COLD_AFTER: Duration = Duration::from_secs(30 * 60);
+/// Process resident memory above which caches are cleared
+pub const PRESSURE_RSS_BYTES: u64 = 1 << 30;
+/// Fraction of system memory left available below which caches are cleared
+pub const PRESSURE_AVAILABLE_FRACTION: f64 = 0.1;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HousekeepingReport {
+    /// Unix milliseconds the run finished
+    pub at: i64,
+    pub under_pressure: bool,
+    pub released_subscriptions: usize,
+    pub released_mappings: usize,
+    pub cleared_indicator_sets: usize,
+    pub cleared_view_snapshots: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemMemory {
+    pub subsystem: &'static str,
+    pub entries: usize,
+    /// Estimated bytes held; mapped bytes for the columnar store
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryBreakdown {
+    /// Resident memory of the process, if the OS reports it
+    pub process_rss: Option<u64>,
+    pub subsystems: Vec<SubsystemMemory>,
+    pub last_housekeeping: Option<HousekeepingReport>,
+}
+
+/// Hidden-window tracking and the last run's report
+#[derive(Default)]
+pub struct Housekeeping {
+    hidden_since: Mutex<HashMap<String, Instant>>,
+    last: Mutex<Option<HousekeepingReport>>,
+}
+
+impl Housekeeping {
+    /// Record which windows are hidden and return the labels of those hidden
+    /// for at least [`COLD_AFTER`]
+    fn cold_windows(&self, windows: &[(String, bool)], now: Instant) -> HashSet<String> {
+        let mut hidden_since = self.hidden_since.lock().unwrap();
+        hidden_since.retain(|label, _| {
+            windows
+                .iter()
+                .any(|(window, visible)| window == label && !visible)
+        });
+        for (label, visible) in windows {
+            if !visible {
+                hidden_since.entry(label.clone()).or_insert(now);
+            }
+        }
+        hidden_since
+            .iter()
+            .filter(|(_, since)| now.duration_since(**since) >= COLD_AFTER)
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+}
+
+/// Resident memory of this process and the available fraction of system memory
+fn memory_status() -> (Option<u64>, Option<f64>) {
+    let mut sys = System::new();
+    sys.refresh_memory();
+    let rss = sysinfo::get_current_pid().ok().and_then(|pid| {
+        sys.refresh_process_specifics(pid, ProcessRefreshKind::new().with_memory());
+        sys.process(pid).map(|process| process.memory())
+    });
+    let available =
+        (sys.total_memory() > 0).then(|| sys.available_memory() as f64 / sys.total_memory() as f64);
+    (rss, available)
+}
+
+fn under_pressure(rss: Option<u64>, available: Option<f64>) -> bool {
+    rss.is_some_and(|rss| rss > PRESSURE_RSS_BYTES)
+        || available.is_some_and(|fraction| fraction < PRESSURE_AVAILABLE_FRACTION)
+}
+
+/// Run one housekeeping pass
+pub fn run(app: &AppHandle) -> HousekeepingReport {
+    let windows: Vec<(String, bool)> = app
+        .webview_windows()
+        .into_iter()
+        .map(|(label, window)| (label, window.is_visible().unwrap_or(true)))
+        .collect();
+    let open: HashSet<&str> = windows.iter().map(|(label, _)| label.as_str()).collect();
+    let housekeeping = app.state::<Housekeeping>();
+    let cold = housekeeping.cold_windows(&windows, Instant::now());
+
+    let changes = app
+        .state::<SubscriptionRegistry>()
+        .release_cold(|owner| !open.contains(owner) || cold.contains(owner));
+    let released_subscriptions = changes.len();
+    subscriptions::emit_changes(app, changes);
+
+    let views = app.state::<WatchlistViews>();
+    let live = app.state::<LiveIndicators>();
+    views.retain_windows(|label| open.contains(label));
+    live.retain_windows(|label| open.contains(label));
+    let released_mappings = app.state::<ColumnarStore>().release_unused();
+
+    let (rss, available) = memory_status();
+    let pressure = under_pressure(rss, available);
+    let (cleared_indicator_sets, cleared_view_snapshots) = if pressure {
+        (app.state::<IndicatorCache>().clear(), views.clear())
+    } else {
+        (0, 0)
+    };
+
+    let report = HousekeepingReport {
+        at: Utc::now().timestamp_millis(),
+        under_pressure: pressure,
+        released_subscriptions,
+        released_mappings,
+        cleared_indicator_sets,
+        cleared_view_snapshots,
+    };
+    if pressure || released_subscriptions > 0 {
+        info!("Memory housekeeping: {:?}", report);
+    }
+    *housekeeping.last.lock().unwrap() = Some(report.clone());
+    report
+}
+
+pub fn start_housekeeping(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(INTERVAL).await;
+            run(&handle);
+        }
+    });
+}
+
+/// Entries and estimated memory held by each in-memory subsystem
+#[tauri::command]
+pub fn get_memory_breakdown(
+    housekeeping: State<'_, Housekeeping>,
+    indicators: State<'_, IndicatorCache>,
+    columnar: State<'_, ColumnarStore>,
+    feed: State<'_, QuoteFeed>,
+    views: State<'_, WatchlistViews>,
+    live: State<'_, LiveIndicators>,
+    registry: State<'_, SubscriptionRegistry>,
+) -> Result<MemoryBreakdown, String> {
+    let subsystem = |subsystem, (entries, bytes)| SubsystemMemory {
+        subsystem,
+        entries,
+        bytes,
+    };
+    let quotes = feed.len();
+    Ok(MemoryBreakdown {
+        process_rss: memory_status().0,
+        subsystems: vec![
+            subsystem("indicator_cache", (indicators.len(), indicators.bytes())),
+            subsystem("columnar_mappings", columnar.mapped()),
+            subsystem("quotes", (quotes, quotes * std::mem::size_of::<Quote>())),
+            subsystem("watchlist_views", views.usage()),
+            subsystem("live_indicators", live.usage()),
+            subsystem("subscriptions", registry.usage()),
+        ],
+        last_housekeeping: housekeeping.last.lock().unwrap().clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cold_windows_and_pressure() {
+        let housekeeping = Housekeeping::default();
+        let start = Instant::now();
+        let windows = |popout_visible| {
+            vec![
+                ("main".to_string(), true),
+                ("popout".to_string(), popout_visible),
+            ]
+        };
+        assert!(housekeeping.cold_windows(&windows(false), start).is_empty());
+        let later = start + COLD_AFTER;
+        assert_eq!(
+            housekeeping.cold_windows(&windows(false), later),
+            HashSet::from(["popout".to_string()])
+        );
+        // Showing the window resets its clock
+        assert!(housekeeping.cold_windows(&windows(true), later).is_empty());
+        assert!(housekeeping.cold_windows(&windows(false), later).is_empty());
+
+        assert!(!under_pressure(Some(200 << 20), Some(0.5)));
+        assert!(under_pressure(Some(2 << 30), Some(0.5)));
+        assert!(under_pressure(None, Some(0.05)));
+        assert!(!under_pressure(None, None));
+    }
+}
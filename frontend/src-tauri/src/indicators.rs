@@ -0,0 +1,748 @@
+//! Technical indicator computation over candle series.
+//!
+//! Indicators are computed per symbol through an [`IndicatorContext`], which
+//! memoizes intermediate series (e.g. a 20-period SMA shared by `SMA(20)` and
+//! a later Bollinger band) so each window is only computed once. Batches of
+//! symbols are spread across the rayon thread pool.
+//!
+//! MACD, KDJ and BOLL produce several lines each, returned under
+//! `{label}.{line}` keys (`MACD12,26,9.DIF`, `KDJ9,3,3.J`, `BOLL20.UPPER`)
+//! and following the conventions of Chinese charting software: the MACD
+//! histogram is twice DIF minus DEA, and K and D start from 50.
+//! `compute_indicators` runs over the same candles `get_kline` serves, so
+//! every view of a symbol shows the same values.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::info;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::columnar::{ColumnarStore, MappedBars};
+use crate::executor::{Priority, ResourceClass};
+use crate::kline::{self, Adjust, KlinePeriod};
+use crate::models::Bar;
+use crate::rolling;
+use crate::snapshot::SnapshotClock;
+use crate::tasks::{spawn_task, spawn_task_with, CancellationToken, TaskContext, TaskHandle};
+
+/// Indicator requested by the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "period", rename_all = "UPPERCASE")]
+pub enum IndicatorSpec {
+    #[serde(alias = "MA")]
+    Sma(usize),
+    Ema(usize),
+    Rsi(usize),
+    /// Fast, slow and signal periods
+    Macd(usize, usize, usize),
+    /// RSV window and the K and D smoothing periods
+    Kdj(usize, usize, usize),
+    /// Bands [`BOLL_WIDTH`] standard deviations around the SMA
+    Boll(usize),
+}
+
+/// Standard deviations between the Bollinger middle and outer bands
+pub const BOLL_WIDTH: f64 = 2.0;
+
+impl IndicatorSpec {
+    pub fn label(&self) -> String {
+        match self {
+            IndicatorSpec::Sma(n) => format!("SMA{}", n),
+            IndicatorSpec::Ema(n) => format!("EMA{}", n),
+            IndicatorSpec::Rsi(n) => format!("RSI{}", n),
+            IndicatorSpec::Macd(fast, slow, signal) => format!("MACD{},{},{}", fast, slow, signal),
+            IndicatorSpec::Kdj(n, m1, m2) => format!("KDJ{},{},{}", n, m1, m2),
+            IndicatorSpec::Boll(n) => format!("BOLL{}", n),
+        }
+    }
+
+    /// Names of the lines the indicator produces; empty for a single line
+    pub fn lines(&self) -> &'static [&'static str] {
+        match self {
+            IndicatorSpec::Sma(_) | IndicatorSpec::Ema(_) | IndicatorSpec::Rsi(_) => &[""],
+            IndicatorSpec::Macd(..) => &["DIF", "DEA", "MACD"],
+            IndicatorSpec::Kdj(..) => &["K", "D", "J"],
+            IndicatorSpec::Boll(_) => &["MID", "UPPER", "LOWER"],
+        }
+    }
+
+    pub fn line_label(&self, line: &str) -> String {
+        if line.is_empty() {
+            self.label()
+        } else {
+            format!("{}.{}", self.label(), line)
+        }
+    }
+}
+
+/// An indicator series aligned with the input bars; `None` during warm-up
+pub type Series = Vec<Option<f64>>;
+
+/// Indicator set shown on charts by default
+pub const DEFAULT_SPECS: [IndicatorSpec; 7] = [
+    IndicatorSpec::Sma(5),
+    IndicatorSpec::Sma(10),
+    IndicatorSpec::Sma(20),
+    IndicatorSpec::Sma(60),
+    IndicatorSpec::Ema(12),
+    IndicatorSpec::Ema(26),
+    IndicatorSpec::Rsi(14),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SymbolIndicators {
+    symbol: String,
+    series: HashMap<String, Series>,
+}
+
+/// Most synthetic symbols a benchmark may generate
+pub const MAX_BENCHMARK_SYMBOLS: usize = 5000;
+
+/// Most bars per synthetic symbol a benchmark may generate
+pub const MAX_BENCHMARK_BARS: usize = 5000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndicatorBenchmark {
+    symbols: usize,
+    bars_per_symbol: usize,
+    threads: usize,
+    sequential_ms: f64,
+    parallel_ms: f64,
+    speedup: f64,
+}
+
+/// Simple moving average
+pub fn sma(values: &[f64], period: usize) -> Series {
+    rolling::rolling_mean(values, period)
+        .into_iter()
+        .map(|v| (!v.is_nan()).then_some(v))
+        .collect()
+}
+
+/// Exponential moving average, seeded with the SMA of the first window
+pub fn ema_seeded(values: &[f64], period: usize, seed: &Series) -> Series {
+    let mut out = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return out;
+    }
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut prev = seed[period - 1].unwrap_or(values[period - 1]);
+    out[period - 1] = Some(prev);
+    for i in period..values.len() {
+        prev = alpha * values[i] + (1.0 - alpha) * prev;
+        out[i] = Some(prev);
+    }
+    out
+}
+
+/// Relative strength index using Wilder smoothing
+pub fn rsi(values: &[f64], period: usize) -> Series {
+    let mut out = vec![None; values.len()];
+    if period == 0 || values.len() <= period {
+        return out;
+    }
+    let (mut gain, mut loss) = (0.0, 0.0);
+    for i in 1..=period {
+        let change = values[i] - values[i - 1];
+        if change > 0.0 {
+            gain += change;
+        } else {
+            loss -= change;
+        }
+    }
+    gain /= period as f64;
+    loss /= period as f64;
+    out[period] = Some(rsi_value(gain, loss));
+    for i in period + 1..values.len() {
+        let change = values[i] - values[i - 1];
+        gain = (gain * (period - 1) as f64 + change.max(0.0)) / period as f64;
+        loss = (loss * (period - 1) as f64 + (-change).max(0.0)) / period as f64;
+        out[i] = Some(rsi_value(gain, loss));
+    }
+    out
+}
+
+pub fn rsi_value(gain: f64, loss: f64) -> f64 {
+    if loss == 0.0 {
+        100.0
+    } else {
+        100.0 - 100.0 / (1.0 + gain / loss)
+    }
+}
+
+/// MACD lines from the fast and slow EMAs: DIF, its signal EMA (DEA) and
+/// the histogram
+pub fn macd(fast: &Series, slow: &Series, signal: usize) -> Vec<Series> {
+    let dif: Series = fast
+        .iter()
+        .zip(slow)
+        .map(|(fast, slow)| Some((*fast)? - (*slow)?))
+        .collect();
+    let mut dea = vec![None; dif.len()];
+    if let Some(start) = dif.iter().position(Option::is_some) {
+        let values: Vec<f64> = dif[start..].iter().map(|v| v.unwrap_or(0.0)).collect();
+        let seed = sma(&values, signal);
+        for (i, value) in ema_seeded(&values, signal, &seed).into_iter().enumerate() {
+            dea[start + i] = value;
+        }
+    }
+    let histogram = dif
+        .iter()
+        .zip(&dea)
+        .map(|(dif, dea)| Some(2.0 * ((*dif)? - (*dea)?)))
+        .collect();
+    vec![dif, dea, histogram]
+}
+
+/// Stochastic K, D and J over an `n`-bar RSV, smoothed over `m1` and `m2` bars
+pub fn kdj(
+    highs: &[f64],
+    lows: &[f64],
+    closes: &[f64],
+    n: usize,
+    m1: usize,
+    m2: usize,
+) -> Vec<Series> {
+    let mut lines = vec![vec![None; closes.len()]; 3];
+    if n == 0 || m1 == 0 || m2 == 0 || closes.len() < n {
+        return lines;
+    }
+    let highest = rolling::rolling_max(highs, n);
+    let lowest = rolling::rolling_min(lows, n);
+    let (mut k, mut d) = (50.0, 50.0);
+    for i in n - 1..closes.len() {
+        let range = highest[i] - lowest[i];
+        let rsv = if range > 0.0 {
+            (closes[i] - lowest[i]) / range * 100.0
+        } else {
+            50.0
+        };
+        k = ((m1 - 1) as f64 * k + rsv) / m1 as f64;
+        d = ((m2 - 1) as f64 * d + k) / m2 as f64;
+        lines[0][i] = Some(k);
+        lines[1][i] = Some(d);
+        lines[2][i] = Some(3.0 * k - 2.0 * d);
+    }
+    lines
+}
+
+/// Bollinger middle, upper and lower bands around an SMA of `period`
+pub fn bollinger(values: &[f64], middle: &Series, period: usize) -> Vec<Series> {
+    let std = rolling::rolling_std(values, period);
+    let band = |sign: f64| -> Series {
+        middle
+            .iter()
+            .zip(&std)
+            .map(|(mid, std)| Some((*mid)? + sign * BOLL_WIDTH * (!std.is_nan()).then_some(*std)?))
+            .collect()
+    };
+    vec![middle.clone(), band(1.0), band(-1.0)]
+}
+
+/// Per-symbol computation context that memoizes intermediate series
+pub struct IndicatorContext<'a> {
+    closes: &'a [f64],
+    highs: &'a [f64],
+    lows: &'a [f64],
+    /// Lines by indicator and line index
+    cache: HashMap<(IndicatorSpec, usize), Arc<Series>>,
+}
+
+impl<'a> IndicatorContext<'a> {
+    /// Context over closes alone, which stand in for highs and lows
+    pub fn new(closes: &'a [f64]) -> Self {
+        Self::with_range(closes, closes, closes)
+    }
+
+    pub fn with_range(closes: &'a [f64], highs: &'a [f64], lows: &'a [f64]) -> Self {
+        Self {
+            closes,
+            highs,
+            lows,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// The first line of an indicator
+    pub fn get(&mut self, spec: IndicatorSpec) -> Arc<Series> {
+        self.line(spec, 0)
+    }
+
+    pub fn line(&mut self, spec: IndicatorSpec, line: usize) -> Arc<Series> {
+        if let Some(series) = self.cache.get(&(spec, line)) {
+            return series.clone();
+        }
+        let lines = match spec {
+            IndicatorSpec::Sma(n) => vec![sma(self.closes, n)],
+            IndicatorSpec::Ema(n) => {
+                let seed = self.get(IndicatorSpec::Sma(n));
+                vec![ema_seeded(self.closes, n, &seed)]
+            }
+            IndicatorSpec::Rsi(n) => vec![rsi(self.closes, n)],
+            IndicatorSpec::Macd(fast, slow, signal) => {
+                let fast = self.get(IndicatorSpec::Ema(fast));
+                let slow = self.get(IndicatorSpec::Ema(slow));
+                macd(&fast, &slow, signal)
+            }
+            IndicatorSpec::Kdj(n, m1, m2) => kdj(self.highs, self.lows, self.closes, n, m1, m2),
+            IndicatorSpec::Boll(n) => {
+                let middle = self.get(IndicatorSpec::Sma(n));
+                bollinger(self.closes, &middle, n)
+            }
+        };
+        for (i, series) in lines.into_iter().enumerate() {
+            self.cache.insert((spec, i), Arc::new(series));
+        }
+        self.cache[&(spec, line)].clone()
+    }
+
+    /// Every line of an indicator under its output label
+    pub fn outputs(&mut self, spec: IndicatorSpec) -> Vec<(String, Series)> {
+        spec.lines()
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (spec.line_label(name), self.line(spec, i).as_ref().clone()))
+            .collect()
+    }
+
+    fn compute(&mut self, specs: &[IndicatorSpec]) -> HashMap<String, Series> {
+        specs.iter().flat_map(|spec| self.outputs(*spec)).collect()
+    }
+}
+
+fn compute_symbol(closes: &[f64], specs: &[IndicatorSpec]) -> HashMap<String, Series> {
+    IndicatorContext::new(closes).compute(specs)
+}
+
+struct CachedSeries {
+    rows: usize,
+    last_timestamp: Option<i64>,
+    series: HashMap<(IndicatorSpec, usize), Arc<Series>>,
+}
+
+/// Computed indicators per stored symbol/interval, reused until the
+/// underlying bars change
+#[derive(Default)]
+pub struct IndicatorCache {
+    entries: Mutex<HashMap<(String, String), CachedSeries>>,
+}
+
+impl IndicatorCache {
+    /// Indicators over the first `rows` stored bars
+    pub fn compute(
+        &self,
+        symbol: &str,
+        interval: &str,
+        bars: &MappedBars,
+        rows: usize,
+        specs: &[IndicatorSpec],
+    ) -> HashMap<String, Series> {
+        let key = (symbol.to_uppercase(), interval.to_string());
+        let rows = rows.min(bars.len());
+        let last_timestamp = bars.timestamps()[..rows].last().copied();
+
+        let mut cached: HashMap<(IndicatorSpec, usize), Arc<Series>> = HashMap::new();
+        if let Some(entry) = self.entries.lock().unwrap().get(&key) {
+            if entry.rows == rows && entry.last_timestamp == last_timestamp {
+                cached = entry.series.clone();
+            }
+        }
+
+        let mut ctx = IndicatorContext::with_range(
+            &bars.closes()[..rows],
+            &bars.highs()[..rows],
+            &bars.lows()[..rows],
+        );
+        ctx.cache = cached;
+        let result = ctx.compute(specs);
+
+        self.entries.lock().unwrap().insert(
+            key,
+            CachedSeries {
+                rows,
+                last_timestamp,
+                series: ctx.cache,
+            },
+        );
+        result
+    }
+
+    /// Number of symbol/interval pairs with cached indicators
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Estimated heap bytes held by cached series
+    pub fn bytes(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .flat_map(|entry| entry.series.values())
+            .map(|series| series.len() * std::mem::size_of::<Option<f64>>())
+            .sum()
+    }
+
+    /// Drop every cached series, returning how many symbol/interval pairs were cached
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let cleared = entries.len();
+        entries.clear();
+        entries.shrink_to_fit();
+        cleared
+    }
+}
+
+/// Compute the same indicator set for many close series in parallel
+pub fn compute_many(
+    series: &[Vec<f64>],
+    specs: &[IndicatorSpec],
+    token: &CancellationToken,
+) -> Result<Vec<HashMap<String, Series>>, String> {
+    series
+        .par_iter()
+        .map(|closes| {
+            token.checkpoint()?;
+            Ok(compute_symbol(closes, specs))
+        })
+        .collect()
+}
+
+/// Indicator series aligned with the candles they were computed over
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CandleIndicators {
+    pub timestamps: Vec<i64>,
+    pub series: HashMap<String, Series>,
+}
+
+/// Indicators over a run of candles
+pub fn candle_indicators(bars: &[Bar], specs: &[IndicatorSpec]) -> CandleIndicators {
+    let column = |value: fn(&Bar) -> f64| -> Vec<f64> { bars.iter().map(value).collect() };
+    let (closes, highs, lows) = (column(|b| b.close), column(|b| b.high), column(|b| b.low));
+    CandleIndicators {
+        timestamps: bars.iter().map(|b| b.timestamp).collect(),
+        series: IndicatorContext::with_range(&closes, &highs, &lows).compute(specs),
+    }
+}
+
+/// Compute indicators over a symbol's candles, as served by `get_kline`
+#[tauri::command]
+pub async fn compute_indicators(
+    app: AppHandle,
+    symbol: String,
+    period: KlinePeriod,
+    specs: Vec<IndicatorSpec>,
+    adjust: Option<Adjust>,
+) -> Result<CandleIndicators, String> {
+    let adjust = adjust.unwrap_or_default();
+    let bars = kline::candles(&app, &symbol, period, adjust, Priority::Interactive).await?;
+    tauri::async_runtime::spawn_blocking(move || candle_indicators(&bars, &specs))
+        .await
+        .map_err(|e| format!("Failed to compute indicators: {}", e))
+}
+
+/// Compute indicators for a batch of symbols from the columnar store.
+///
+/// Runs as a background task; results arrive with the `task-finished` event.
+#[tauri::command]
+pub fn compute_indicators_batch(
+    app: AppHandle,
+    store: State<'_, ColumnarStore>,
+    symbols: Vec<String>,
+    interval: String,
+    specs: Vec<IndicatorSpec>,
+) -> Result<TaskHandle, String> {
+    let mapped = symbols
+        .iter()
+        .map(|symbol| store.open(symbol, &interval))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let handle = app.clone();
+    let task_id = spawn_task_with(
+        &app,
+        "indicators",
+        ResourceClass::Cpu,
+        Priority::Normal,
+        move |ctx: TaskContext| {
+            let cache = handle.state::<IndicatorCache>();
+            let until = handle.state::<SnapshotClock>().frozen_at();
+            let total = symbols.len() as u64;
+            let done = AtomicU64::new(0);
+            symbols
+                .into_par_iter()
+                .zip(mapped.into_par_iter())
+                .map(|(symbol, bars)| {
+                    ctx.checkpoint()?;
+                    let rows = bars.rows_until(until);
+                    let series = cache.compute(&symbol, &interval, &bars, rows, &specs);
+                    ctx.report("compute", done.fetch_add(1, Ordering::Relaxed) + 1, total);
+                    Ok(SymbolIndicators { series, symbol })
+                })
+                .collect::<Result<Vec<_>, String>>()
+        },
+    );
+    Ok(TaskHandle { task_id })
+}
+
+/// Benchmark sizes, defaulted and checked against [`MAX_BENCHMARK_SYMBOLS`]
+/// and [`MAX_BENCHMARK_BARS`]
+fn benchmark_size(
+    symbols: Option<usize>,
+    bars_per_symbol: Option<usize>,
+) -> Result<(usize, usize), String> {
+    let symbols = symbols.unwrap_or(2000);
+    let bars = bars_per_symbol.unwrap_or(2500);
+    let sizes = [
+        ("symbols", symbols, MAX_BENCHMARK_SYMBOLS),
+        ("bars_per_symbol", bars, MAX_BENCHMARK_BARS),
+    ];
+    for (name, value, max) in sizes {
+        if !(1..=max).contains(&value) {
+            return Err(format!(
+                "Benchmark {} must be between 1 and {}, got {}",
+                name, max, value
+            ));
+        }
+    }
+    Ok((symbols, bars))
+}
+
+/// Measure sequential vs parallel indicator throughput on synthetic data.
+///
+/// Runs as a background task; results arrive with the `task-finished` event.
+#[tauri::command]
+pub fn benchmark_indicators(
+    app: AppHandle,
+    symbols: Option<usize>,
+    bars_per_symbol: Option<usize>,
+) -> Result<TaskHandle, String> {
+    let (symbols, bars_per_symbol) = benchmark_size(symbols, bars_per_symbol)?;
+
+    let task_id = spawn_task(&app, "benchmark_indicators", move |ctx: TaskContext| {
+        let series: Vec<Vec<f64>> = (0..symbols)
+            .map(|s| {
+                (0..bars_per_symbol)
+                    .map(|i| 10.0 + ((i * 7 + s * 13) % 97) as f64 * 0.1)
+                    .collect()
+            })
+            .collect();
+        let specs = [
+            IndicatorSpec::Sma(5),
+            IndicatorSpec::Sma(20),
+            IndicatorSpec::Sma(60),
+            IndicatorSpec::Ema(12),
+            IndicatorSpec::Ema(26),
+            IndicatorSpec::Rsi(14),
+        ];
+
+        let start = Instant::now();
+        let mut sequential = Vec::with_capacity(series.len());
+        for (i, closes) in series.iter().enumerate() {
+            ctx.checkpoint()?;
+            sequential.push(compute_symbol(closes, &specs));
+            ctx.report("sequential", i as u64 + 1, symbols as u64);
+        }
+        let sequential_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        let start = Instant::now();
+        ctx.report("parallel", 0, 1);
+        let parallel = compute_many(&series, &specs, &ctx.token)?;
+        ctx.report("parallel", 1, 1);
+        let parallel_ms = start.elapsed().as_secs_f64() * 1000.0;
+        debug_assert_eq!(sequential.len(), parallel.len());
+
+        let result = IndicatorBenchmark {
+            symbols,
+            bars_per_symbol,
+            threads: rayon::current_num_threads(),
+            sequential_ms,
+            parallel_ms,
+            speedup: sequential_ms / parallel_ms.max(f64::EPSILON),
+        };
+        info!("Indicator benchmark: {:?}", result);
+        Ok(result)
+    });
+    Ok(TaskHandle { task_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_golden, price_series, round_series, sample_closes};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_benchmark_size_is_bounded() {
+        assert_eq!(benchmark_size(None, None), Ok((2000, 2500)));
+        assert_eq!(
+            benchmark_size(Some(MAX_BENCHMARK_SYMBOLS), Some(1)),
+            Ok((MAX_BENCHMARK_SYMBOLS, 1))
+        );
+        assert!(benchmark_size(Some(MAX_BENCHMARK_SYMBOLS + 1), None).is_err());
+        assert!(benchmark_size(None, Some(usize::MAX)).is_err());
+        assert!(benchmark_size(Some(0), None).is_err());
+    }
+
+    #[test]
+    fn test_sma_and_ema() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(sma(&values, 3), vec![None, None, Some(2.0), Some(3.0), Some(4.0)]);
+
+        let mut ctx = IndicatorContext::new(&values);
+        let ema = ctx.get(IndicatorSpec::Ema(3));
+        assert_eq!(ema[2], Some(2.0));
+        assert_eq!(ema[3], Some(3.0));
+        // The seed SMA was memoized alongside the EMA
+        assert!(ctx.cache.contains_key(&(IndicatorSpec::Sma(3), 0)));
+    }
+
+    #[test]
+    fn test_cache_invalidated_when_bars_change() {
+        let dir = std::env::temp_dir().join(format!("ssi-indicators-{}", std::process::id()));
+        let store = ColumnarStore::new(dir.clone());
+        let cache = IndicatorCache::default();
+        let bar = |i: i64| Bar {
+            timestamp: i,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            close: i as f64,
+            volume: 0.0,
+        };
+
+        store
+            .write("SZ000001", "1d", &(0..5).map(bar).collect::<Vec<_>>())
+            .unwrap();
+        let bars = store.open("SZ000001", "1d").unwrap();
+        let first = cache.compute("SZ000001", "1d", &bars, 5, &[IndicatorSpec::Sma(2)]);
+        assert_eq!(first["SMA2"].len(), 5);
+
+        store
+            .write("SZ000001", "1d", &(0..6).map(bar).collect::<Vec<_>>())
+            .unwrap();
+        let bars = store.open("SZ000001", "1d").unwrap();
+        let second = cache.compute("SZ000001", "1d", &bars, 6, &[IndicatorSpec::Sma(2)]);
+        assert_eq!(second["SMA2"][5], Some(4.5));
+
+        // A snapshot view only sees the leading bars
+        let frozen = cache.compute("SZ000001", "1d", &bars, 3, &[IndicatorSpec::Sma(2)]);
+        assert_eq!(frozen["SMA2"].len(), 3);
+        assert_eq!(cache.len(), 1);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_multi_line_indicators() {
+        let specs: Vec<IndicatorSpec> = serde_json::from_str(
+            r#"[{"kind":"MA","period":5},{"kind":"MACD","period":[3,6,2]},
+                {"kind":"KDJ","period":[3,3,3]},{"kind":"BOLL","period":4}]"#,
+        )
+        .unwrap();
+        assert_eq!(specs[0], IndicatorSpec::Sma(5));
+        let closes: Vec<f64> = (0..12).map(|i| 10.0 + (i % 4) as f64).collect();
+        let highs: Vec<f64> = closes.iter().map(|c| c + 0.5).collect();
+        let lows: Vec<f64> = closes.iter().map(|c| c - 0.5).collect();
+        let series = IndicatorContext::with_range(&closes, &highs, &lows).compute(&specs);
+        let mut keys: Vec<&str> = series.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "BOLL4.LOWER",
+                "BOLL4.MID",
+                "BOLL4.UPPER",
+                "KDJ3,3,3.D",
+                "KDJ3,3,3.J",
+                "KDJ3,3,3.K",
+                "MACD3,6,2.DEA",
+                "MACD3,6,2.DIF",
+                "MACD3,6,2.MACD",
+                "SMA5"
+            ]
+        );
+        assert!(series.values().all(|s| s.len() == closes.len()));
+
+        // DIF starts with the slow EMA, DEA a signal period later
+        let dif = &series["MACD3,6,2.DIF"];
+        let dea = &series["MACD3,6,2.DEA"];
+        assert_eq!((dif[4], dif[5].is_some()), (None, true));
+        assert_eq!((dea[5], dea[6].is_some()), (None, true));
+        let histogram = series["MACD3,6,2.MACD"][8].unwrap();
+        assert!((histogram - 2.0 * (dif[8].unwrap() - dea[8].unwrap())).abs() < 1e-12);
+
+        // Window 10,11,12,13: mean 11.5, population std sqrt(1.25)
+        assert_eq!(series["BOLL4.MID"][3], Some(11.5));
+        let upper = series["BOLL4.UPPER"][3].unwrap();
+        assert!((upper - (11.5 + 2.0 * 1.25f64.sqrt())).abs() < 1e-9);
+        assert_eq!(series["BOLL4.LOWER"][2], None);
+
+        // Closing at the 3-bar high: RSV (13 - 10.5) / 3 = 83.3
+        let k = series["KDJ3,3,3.K"][3].unwrap();
+        let rsv = 2.5 / 3.0 * 100.0;
+        let first_k = (2.0 * 50.0 + (12.0 - 9.5) / 3.0 * 100.0) / 3.0;
+        assert!((k - (2.0 * first_k + rsv) / 3.0).abs() < 1e-9);
+        let (k, d, j) = (
+            series["KDJ3,3,3.K"][5].unwrap(),
+            series["KDJ3,3,3.D"][5].unwrap(),
+            series["KDJ3,3,3.J"][5].unwrap(),
+        );
+        assert!((j - (3.0 * k - 2.0 * d)).abs() < 1e-12);
+        assert_eq!(series["KDJ3,3,3.K"][1], None);
+    }
+
+    #[test]
+    fn test_rsi_bounds() {
+        let rising: Vec<f64> = (0..30).map(|i| i as f64).collect();
+        assert_eq!(rsi(&rising, 14)[20], Some(100.0));
+
+        let zigzag: Vec<f64> = (0..30).map(|i| if i % 2 == 0 { 10.0 } else { 11.0 }).collect();
+        let value = rsi(&zigzag, 14)[29].unwrap();
+        assert!(value > 0.0 && value < 100.0);
+    }
+
+    #[test]
+    fn test_default_indicators_golden() {
+        let closes = sample_closes();
+        let mut ctx = IndicatorContext::new(&closes);
+        let series: std::collections::BTreeMap<String, Series> = DEFAULT_SPECS
+            .iter()
+            .map(|spec| (spec.label(), round_series(&ctx.get(*spec))))
+            .collect();
+        assert_golden("default_indicators", &series);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_rsi_bounded(closes in price_series(2..300), period in 1usize..30) {
+            for value in rsi(&closes, period).into_iter().flatten() {
+                prop_assert!((0.0..=100.0).contains(&value));
+            }
+        }
+
+        #[test]
+        fn prop_moving_averages_within_window_range(closes in price_series(1..300), period in 1usize..60) {
+            let sma = sma(&closes, period);
+            let mut ctx = IndicatorContext::new(&closes);
+            let ema = ctx.get(IndicatorSpec::Ema(period));
+            let (lo, hi) = closes.iter().fold((f64::MAX, f64::MIN), |(lo, hi), &c| (lo.min(c), hi.max(c)));
+            for i in 0..closes.len() {
+                prop_assert_eq!(sma[i].is_some(), i + 1 >= period);
+                if let Some(v) = sma[i] {
+                    let window = &closes[i + 1 - period..=i];
+                    let min = window.iter().cloned().fold(f64::MAX, f64::min);
+                    let max = window.iter().cloned().fold(f64::MIN, f64::max);
+                    prop_assert!(v >= min - 1e-9 * max.abs() && v <= max + 1e-9 * max.abs());
+                }
+                if let Some(v) = ema[i] {
+                    prop_assert!(v >= lo - 1e-9 * hi && v <= hi + 1e-9 * hi);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,566 @@
+//! Instrument master (the list of tradable A-share symbols) and its change log.
+//!
+//! The master is refreshed weekly from Eastmoney's listing endpoint. Each
+//! refresh is diffed against the previous master to record new listings,
+//! delistings and renamings, including entering or leaving special
+//! treatment (ST / *ST), and the changes are pushed to the frontend through a
+//! `universe-changed` event. Delistings and renamings of symbols on a
+//! watchlist or held in the portfolio also raise a notification.
+//!
+//! A listing that would remove more than [`MAX_REMOVED_FRACTION`] of the
+//! master is refused rather than applied: a truncated or filtered response
+//! looks like a wave of delistings, which real listings never are.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{Local, NaiveDate};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{Database, Watchlists};
+use crate::drift::{DriftLog, Field, FieldKind};
+use crate::executor::{Priority, ResourceClass};
+use crate::faults::FaultInjector;
+use crate::notifications::{self, Notification, NotificationAction, NotificationCategory};
+use crate::politeness::PolicyEngine;
+use crate::portfolio::PortfolioStore;
+use crate::symbol_search::SymbolIndex;
+use crate::tasks::{spawn_task_with, TaskContext, TaskHandle};
+use crate::utils::{read_from_file, write_to_file};
+
+const PROVIDER: &str = "eastmoney";
+
+const LISTING_URL: &str = "https://push2.eastmoney.com/api/qt/clist/get?pn=1&pz=10000&po=1&np=1&fltt=2&invt=2&fid=f12&fs=m:0+t:6,m:0+t:80,m:1+t:2,m:1+t:23,m:0+t:81+s:2048&fields=f12,f13,f14";
+
+/// Days between automatic refreshes
+const REFRESH_INTERVAL_DAYS: i64 = 7;
+
+/// Largest share of the master one refresh may delist
+const MAX_REMOVED_FRACTION: f64 = 0.05;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instrument {
+    pub symbol: String,
+    pub name: String,
+}
+
+impl Instrument {
+    /// Whether the name carries a special-treatment marker (ST, *ST, SST…)
+    pub fn is_special_treatment(&self) -> bool {
+        self.name.contains("ST") && self.name.trim_start_matches(['*', 'S']).starts_with('T')
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChangeKind {
+    Listed,
+    Delisted,
+    Renamed { old_name: String },
+    EnteredSpecialTreatment { old_name: String },
+    LeftSpecialTreatment { old_name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UniverseChange {
+    pub date: NaiveDate,
+    pub symbol: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ChangeKind,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MasterData {
+    refreshed_on: Option<NaiveDate>,
+    instruments: BTreeMap<String, Instrument>,
+    changes: Vec<UniverseChange>,
+}
+
+/// Compute the changes between two masters
+pub fn diff_masters(
+    old: &BTreeMap<String, Instrument>,
+    new: &BTreeMap<String, Instrument>,
+    date: NaiveDate,
+) -> Vec<UniverseChange> {
+    let mut changes = Vec::new();
+    for (symbol, current) in new {
+        let kind = match old.get(symbol) {
+            None => ChangeKind::Listed,
+            Some(previous) if previous.name == current.name => continue,
+            Some(previous) => {
+                let old_name = previous.name.clone();
+                match (
+                    previous.is_special_treatment(),
+                    current.is_special_treatment(),
+                ) {
+                    (false, true) => ChangeKind::EnteredSpecialTreatment { old_name },
+                    (true, false) => ChangeKind::LeftSpecialTreatment { old_name },
+                    _ => ChangeKind::Renamed { old_name },
+                }
+            }
+        };
+        changes.push(UniverseChange {
+            date,
+            symbol: symbol.clone(),
+            name: current.name.clone(),
+            kind,
+        });
+    }
+    for (symbol, previous) in old {
+        if !new.contains_key(symbol) {
+            changes.push(UniverseChange {
+                date,
+                symbol: symbol.clone(),
+                name: previous.name.clone(),
+                kind: ChangeKind::Delisted,
+            });
+        }
+    }
+    changes
+}
+
+/// Refuse a new master that drops too much of the old one
+fn check_removals(
+    old: &BTreeMap<String, Instrument>,
+    new: &BTreeMap<String, Instrument>,
+) -> Result<(), String> {
+    let removed = old
+        .keys()
+        .filter(|symbol| !new.contains_key(*symbol))
+        .count();
+    if removed as f64 > old.len() as f64 * MAX_REMOVED_FRACTION {
+        return Err(format!(
+            "Refusing instrument listing that removes {} of {} symbols",
+            removed,
+            old.len()
+        ));
+    }
+    Ok(())
+}
+
+pub struct InstrumentMaster {
+    path: PathBuf,
+    data: Mutex<MasterData>,
+}
+
+impl InstrumentMaster {
+    pub fn load(path: PathBuf) -> Self {
+        let data = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+
+    pub fn needs_refresh(&self, today: NaiveDate) -> bool {
+        match self.data.lock().unwrap().refreshed_on {
+            Some(date) => (today - date).num_days() >= REFRESH_INTERVAL_DAYS,
+            None => true,
+        }
+    }
+
+    /// Replace the master, recording and returning the changes.
+    ///
+    /// The first refresh only seeds the master; listing every symbol as new
+    /// would bury real changes. A listing that removes too much of the master
+    /// is refused and leaves it as it was.
+    pub fn apply(
+        &self,
+        instruments: Vec<Instrument>,
+        date: NaiveDate,
+    ) -> Result<Vec<UniverseChange>, String> {
+        let new: BTreeMap<String, Instrument> = instruments
+            .into_iter()
+            .map(|i| (i.symbol.clone(), i))
+            .collect();
+
+        let mut data = self.data.lock().unwrap();
+        let changes = if data.refreshed_on.is_some() {
+            check_removals(&data.instruments, &new)?;
+            diff_masters(&data.instruments, &new, date)
+        } else {
+            Vec::new()
+        };
+        data.instruments = new;
+        data.refreshed_on = Some(date);
+        data.changes.extend(changes.iter().cloned());
+
+        let content = serde_json::to_string(&*data)
+            .map_err(|e| format!("Failed to serialize instrument master: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save instrument master: {}", e))?;
+        Ok(changes)
+    }
+
+    pub fn refreshed_on(&self) -> Option<NaiveDate> {
+        self.data.lock().unwrap().refreshed_on
+    }
+
+    pub fn instruments(&self) -> Vec<Instrument> {
+        self.data
+            .lock()
+            .unwrap()
+            .instruments
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn name(&self, symbol: &str) -> Option<String> {
+        let data = self.data.lock().unwrap();
+        data.instruments.get(symbol).map(|i| i.name.clone())
+    }
+
+    pub fn changes_between(&self, start: NaiveDate, end: NaiveDate) -> Vec<UniverseChange> {
+        self.data
+            .lock()
+            .unwrap()
+            .changes
+            .iter()
+            .filter(|c| c.date >= start && c.date <= end)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct ListingResponse {
+    data: ListingData,
+}
+
+#[derive(Deserialize)]
+struct ListingData {
+    diff: Vec<ListingRow>,
+}
+
+#[derive(Deserialize)]
+struct ListingRow {
+    f12: String,
+    f13: u8,
+    f14: String,
+}
+
+const LISTING_SCHEMA: &[Field] = &[
+    Field {
+        path: "data.diff",
+        kind: FieldKind::NonEmptyArray,
+    },
+    Field {
+        path: "data.diff[].f12",
+        kind: FieldKind::String,
+    },
+    Field {
+        path: "data.diff[].f13",
+        kind: FieldKind::Number,
+    },
+    Field {
+        path: "data.diff[].f14",
+        kind: FieldKind::String,
+    },
+];
+
+fn exchange_prefix(market: u8, code: &str) -> &'static str {
+    match market {
+        1 => "SH",
+        _ if code.starts_with(['4', '8', '9']) => "BJ",
+        _ => "SZ",
+    }
+}
+
+/// Download the current listing from Eastmoney
+pub async fn fetch_listing(app: &AppHandle) -> Result<Vec<Instrument>, String> {
+    let policies = app.state::<PolicyEngine>();
+    let body = app
+        .state::<FaultInjector>()
+        .wrap(PROVIDER, async {
+            policies
+                .get(PROVIDER, LISTING_URL)
+                .await
+                .map_err(|e| format!("Failed to fetch instrument listing: {}", e))?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read instrument listing: {}", e))
+        })
+        .await?;
+    let value = app.state::<DriftLog>().check_response(
+        app,
+        PROVIDER,
+        "instrument listing",
+        &body,
+        LISTING_SCHEMA,
+    )?;
+    let response: ListingResponse = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse instrument listing: {}", e))?;
+
+    Ok(response
+        .data
+        .diff
+        .into_iter()
+        .map(|row| Instrument {
+            symbol: format!("{}{}", exchange_prefix(row.f13, &row.f12), row.f12),
+            name: row.f14.replace(' ', ""),
+        })
+        .collect())
+}
+
+/// Symbols on a watchlist or held in the portfolio
+fn followed_symbols(app: &AppHandle) -> Result<BTreeSet<String>, String> {
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    let mut symbols: BTreeSet<String> = Watchlists(&conn)
+        .list()?
+        .into_iter()
+        .flat_map(|list| list.items)
+        .map(|item| item.symbol)
+        .collect();
+    let portfolio = app.state::<PortfolioStore>().get();
+    symbols.extend(
+        portfolio
+            .manual
+            .iter()
+            .chain(portfolio.broker_accounts.iter().flat_map(|a| &a.positions))
+            .map(|h| h.symbol.clone()),
+    );
+    Ok(symbols)
+}
+
+/// Notification for a delisting or renaming of a followed symbol
+fn change_notification(change: &UniverseChange) -> Option<Notification> {
+    let (title, body) = match &change.kind {
+        ChangeKind::Listed => return None,
+        ChangeKind::Delisted => (
+            format!("{} {} 已退市", change.symbol, change.name),
+            "该证券已不在上市列表中，请检查自选股和持仓".to_string(),
+        ),
+        ChangeKind::Renamed { old_name }
+        | ChangeKind::EnteredSpecialTreatment { old_name }
+        | ChangeKind::LeftSpecialTreatment { old_name } => (
+            format!("{} 更名为 {}", change.symbol, change.name),
+            format!("原名称：{}", old_name),
+        ),
+    };
+    let mut actions = Vec::new();
+    if change.kind != ChangeKind::Delisted {
+        actions.push(NotificationAction::open_chart(&change.symbol));
+    }
+    actions.push(NotificationAction::dismiss());
+    Some(Notification {
+        category: NotificationCategory::System,
+        title,
+        body,
+        actions,
+    })
+}
+
+fn notify_followed(app: &AppHandle, changes: &[UniverseChange]) -> Result<(), String> {
+    let followed = followed_symbols(app)?;
+    for change in changes.iter().filter(|c| followed.contains(&c.symbol)) {
+        if let Some(notification) = change_notification(change) {
+            if let Err(e) = notifications::notify(app, notification) {
+                warn!("{}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn start_refresh(app: &AppHandle) -> String {
+    let handle = app.clone();
+    spawn_task_with(
+        app,
+        "instrument_refresh",
+        ResourceClass::Network,
+        Priority::Background,
+        move |ctx: TaskContext| {
+            ctx.report("download", 0, 1);
+            let instruments = tauri::async_runtime::block_on(fetch_listing(&handle))?;
+            ctx.checkpoint()?;
+            ctx.report("download", 1, 1);
+
+            let today = Local::now().date_naive();
+            let master = handle.state::<InstrumentMaster>();
+            let changes = master.apply(instruments, today)?;
+            handle.state::<SymbolIndex>().rebuild(&master.instruments());
+            info!("Instrument master refreshed: {} changes", changes.len());
+            if !changes.is_empty() {
+                if let Err(e) = handle.emit("universe-changed", changes.clone()) {
+                    warn!("Failed to emit universe-changed event: {}", e);
+                }
+                if let Err(e) = notify_followed(&handle, &changes) {
+                    warn!("Failed to notify universe changes: {}", e);
+                }
+            }
+            Ok(changes)
+        },
+    )
+}
+
+/// Refresh the master at startup when the last refresh is a week old
+pub fn refresh_if_due(app: &AppHandle) {
+    if app
+        .state::<InstrumentMaster>()
+        .needs_refresh(Local::now().date_naive())
+    {
+        start_refresh(app);
+    }
+}
+
+/// Refresh the instrument master now
+#[tauri::command]
+pub fn refresh_instrument_master(app: AppHandle) -> Result<TaskHandle, String> {
+    Ok(TaskHandle {
+        task_id: start_refresh(&app),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DateRange {
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+/// Universe changes recorded within a date range (inclusive)
+#[tauri::command]
+pub fn get_universe_changes(
+    master: State<'_, InstrumentMaster>,
+    range: DateRange,
+) -> Result<Vec<UniverseChange>, String> {
+    if range.start > range.end {
+        return Err("Range start must not be after its end".to_string());
+    }
+    Ok(master.changes_between(range.start, range.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master(entries: &[(&str, &str)]) -> BTreeMap<String, Instrument> {
+        entries
+            .iter()
+            .map(|(symbol, name)| {
+                (
+                    symbol.to_string(),
+                    Instrument {
+                        symbol: symbol.to_string(),
+                        name: name.to_string(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_special_treatment_detection() {
+        let st = |name: &str| Instrument {
+            symbol: "SZ000001".to_string(),
+            name: name.to_string(),
+        };
+        assert!(st("ST康美").is_special_treatment());
+        assert!(st("*ST海润").is_special_treatment());
+        assert!(!st("贵州茅台").is_special_treatment());
+        assert!(!st("BEST科技").is_special_treatment());
+    }
+
+    #[test]
+    fn test_diff_masters() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 6).unwrap();
+        let old = master(&[
+            ("SH600519", "贵州茅台"),
+            ("SZ000001", "平安银行"),
+            ("SH600000", "浦发银行"),
+            ("SZ002002", "ST鸿达"),
+        ]);
+        let new = master(&[
+            ("SH600519", "贵州茅台"),
+            ("SZ000001", "*ST平安"),
+            ("SH688981", "中芯国际"),
+            ("SZ002002", "鸿达兴业"),
+        ]);
+
+        let changes = diff_masters(&old, &new, date);
+        let kinds: Vec<(&str, &ChangeKind)> = changes
+            .iter()
+            .map(|c| (c.symbol.as_str(), &c.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("SH688981", &ChangeKind::Listed),
+                (
+                    "SZ000001",
+                    &ChangeKind::EnteredSpecialTreatment {
+                        old_name: "平安银行".to_string()
+                    }
+                ),
+                (
+                    "SZ002002",
+                    &ChangeKind::LeftSpecialTreatment {
+                        old_name: "ST鸿达".to_string()
+                    }
+                ),
+                ("SH600000", &ChangeKind::Delisted),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_refuses_mass_removal() {
+        let old: BTreeMap<String, Instrument> = (0..100)
+            .map(|i| format!("SZ{:06}", i))
+            .map(|symbol| {
+                let instrument = Instrument {
+                    symbol: symbol.clone(),
+                    name: symbol.clone(),
+                };
+                (symbol, instrument)
+            })
+            .collect();
+        let keep = |n: usize| -> BTreeMap<String, Instrument> {
+            old.clone().into_iter().take(n).collect()
+        };
+        assert!(check_removals(&old, &keep(95)).is_ok());
+        assert!(check_removals(&old, &keep(94)).is_err());
+        assert!(check_removals(&old, &BTreeMap::new()).is_err());
+
+        let master = InstrumentMaster {
+            path: std::env::temp_dir().join("instruments-mass-removal.json"),
+            data: Mutex::new(MasterData {
+                refreshed_on: NaiveDate::from_ymd_opt(2024, 5, 6),
+                instruments: old.clone(),
+                changes: Vec::new(),
+            }),
+        };
+        let date = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        assert!(master.apply(Vec::new(), date).is_err());
+        assert_eq!(master.instruments().len(), 100);
+        assert_eq!(master.refreshed_on(), NaiveDate::from_ymd_opt(2024, 5, 6));
+    }
+
+    #[test]
+    fn test_change_notifications() {
+        let change = |kind| UniverseChange {
+            date: NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(),
+            symbol: "SZ000001".to_string(),
+            name: "*ST平安".to_string(),
+            kind,
+        };
+        assert!(change_notification(&change(ChangeKind::Listed)).is_none());
+        let delisted = change_notification(&change(ChangeKind::Delisted)).unwrap();
+        assert!(delisted.title.contains("退市"));
+        assert_eq!(delisted.actions.len(), 1);
+        let renamed = change_notification(&change(ChangeKind::EnteredSpecialTreatment {
+            old_name: "平安银行".to_string(),
+        }))
+        .unwrap();
+        assert!(renamed.body.contains("平安银行"));
+        assert_eq!(renamed.category, NotificationCategory::System);
+    }
+}
@@ -0,0 +1,675 @@
+//! Historical K-lines with local caching and adjusted prices.
+//!
+//! Daily, weekly and monthly candles come from Eastmoney's kline endpoint,
+//! unadjusted (不复权), forward adjusted (前复权) or backward adjusted
+//! (后复权), or from the next provider in the [`providers`] chain that has the
+//! period and adjustment when Eastmoney is down. They are cached in the [`ColumnarStore`] under one series per
+//! symbol, period and adjustment: `1d` holds unadjusted daily bars, the
+//! adjusted variants add a suffix (`1d-qfq`, `1w-hfq`). Weekly and monthly
+//! bars are stamped with their last trading day, as the provider dates them.
+//!
+//! A refresh only downloads the tail. It starts from the second-to-last
+//! cached bar, replacing the last one, which may have been incomplete. The
+//! first refetched bar is already complete and must match the cache. When it
+//! doesn't, a dividend or split has re-adjusted history, and the whole
+//! series is downloaded again. A series refreshed within the bar polling
+//! interval is served from the cache; when a snapshot is frozen nothing is
+//! fetched and bars after the snapshot are left out.
+//!
+//! Intraday charts are built from one cached `1m` series per symbol and
+//! adjustment. The 5, 15, 30 and 60-minute periods are [`resample`]d from it
+//! on request, so switching between them never touches the network. Minute
+//! bars are stamped with the minute they open, their buckets are aligned to
+//! the session openings at 09:30 and 13:00 so none spans the lunch break, and
+//! the opening auction folds into the first bar of the day. The provider only
+//! keeps the last few trading days of minute bars, so a refresh that can't
+//! reach back to the cache keeps the older cached minutes as they were.
+
+use std::time::{Duration, SystemTime};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+
+use crate::columnar::ColumnarStore;
+use crate::drift::{DriftLog, Field, FieldKind};
+use crate::executor::{Executor, Priority, ResourceClass};
+use crate::faults::FaultInjector;
+use crate::models::{Bar, Market};
+use crate::politeness::PolicyEngine;
+use crate::providers;
+use crate::quotes::provider_code;
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::symbols::symbol_key;
+
+const PROVIDER: &str = "eastmoney";
+
+const KLINE_URL: &str = "https://push2his.eastmoney.com/api/qt/stock/kline/get";
+
+/// Prices equal within this relative difference
+const PRICE_TOLERANCE: f64 = 1e-6;
+
+const KLINE_SCHEMA: &[Field] = &[
+    Field {
+        path: "data.klines",
+        kind: FieldKind::Array,
+    },
+    Field {
+        path: "data.klines[]",
+        kind: FieldKind::String,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KlinePeriod {
+    #[serde(rename = "1m")]
+    Minute1,
+    #[serde(rename = "5m")]
+    Minute5,
+    #[serde(rename = "15m")]
+    Minute15,
+    #[serde(rename = "30m")]
+    Minute30,
+    #[serde(rename = "60m")]
+    Minute60,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl KlinePeriod {
+    /// Interval name in the columnar store
+    pub fn interval(self) -> &'static str {
+        match self {
+            KlinePeriod::Minute1 => "1m",
+            KlinePeriod::Minute5 => "5m",
+            KlinePeriod::Minute15 => "15m",
+            KlinePeriod::Minute30 => "30m",
+            KlinePeriod::Minute60 => "60m",
+            KlinePeriod::Daily => "1d",
+            KlinePeriod::Weekly => "1w",
+            KlinePeriod::Monthly => "1mo",
+        }
+    }
+
+    /// Bar length of intraday periods
+    pub fn minutes(self) -> Option<u32> {
+        match self {
+            KlinePeriod::Minute1 => Some(1),
+            KlinePeriod::Minute5 => Some(5),
+            KlinePeriod::Minute15 => Some(15),
+            KlinePeriod::Minute30 => Some(30),
+            KlinePeriod::Minute60 => Some(60),
+            KlinePeriod::Daily | KlinePeriod::Weekly | KlinePeriod::Monthly => None,
+        }
+    }
+
+    /// Period downloaded and cached to serve this one
+    fn source(self) -> KlinePeriod {
+        match self.minutes() {
+            Some(_) => KlinePeriod::Minute1,
+            None => self,
+        }
+    }
+
+    fn klt(self) -> u32 {
+        match self {
+            KlinePeriod::Daily => 101,
+            KlinePeriod::Weekly => 102,
+            KlinePeriod::Monthly => 103,
+            minutes => minutes.minutes().unwrap_or(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Adjust {
+    /// 不复权
+    #[default]
+    None,
+    /// 前复权, anchored at the latest price
+    Forward,
+    /// 后复权, anchored at the listing price
+    Backward,
+}
+
+impl Adjust {
+    fn fqt(self) -> u32 {
+        match self {
+            Adjust::None => 0,
+            Adjust::Forward => 1,
+            Adjust::Backward => 2,
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Adjust::None => "",
+            Adjust::Forward => "-qfq",
+            Adjust::Backward => "-hfq",
+        }
+    }
+}
+
+/// Columnar interval holding `period` bars with `adjust`
+pub fn series_interval(period: KlinePeriod, adjust: Adjust) -> String {
+    format!("{}{}", period.interval(), adjust.suffix())
+}
+
+/// Eastmoney's `secid`: market prefix 1 for Shanghai, 0 for Shenzhen and
+/// Beijing, 116 for Hong Kong
+pub fn secid(symbol: &str) -> Option<String> {
+    let upper = symbol.trim().to_uppercase();
+    if Market::of(&upper) == Some(Market::Hk) {
+        return Some(format!("116.{}", &upper[2..]));
+    }
+    let code = provider_code(symbol)?;
+    let market = if code.starts_with("sh") { 1 } else { 0 };
+    Some(format!("{}.{}", market, &code[2..]))
+}
+
+fn local_timestamp(local: NaiveDateTime, market: Market) -> Option<i64> {
+    sessions::timezone(market)
+        .from_local_datetime(&local)
+        .single()
+        .map(|t| t.timestamp())
+}
+
+/// `2024-01-02,open,close,high,low,lots,amount`; minute rows read
+/// `2024-01-02 09:31` and are stamped with the end of their minute. Hong Kong
+/// rows count volume in shares rather than lots.
+fn parse_kline(row: &str, market: Market) -> Option<Bar> {
+    let fields: Vec<&str> = row.split(',').collect();
+    let number = |i: usize| fields.get(i)?.parse::<f64>().ok();
+    let stamp = fields.first()?;
+    let timestamp = match NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M") {
+        Ok(end) => local_timestamp(end, market)? - 60,
+        Err(_) => local_timestamp(
+            NaiveDate::parse_from_str(stamp, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)?,
+            market,
+        )?,
+    };
+    let lot = if market == Market::Cn { 100.0 } else { 1.0 };
+    Some(Bar {
+        timestamp,
+        open: number(1)?,
+        close: number(2)?,
+        high: number(3)?,
+        low: number(4)?,
+        volume: number(5)? * lot,
+    })
+}
+
+/// Start of the `minutes`-long bucket holding a bar opening at `ts`, aligned
+/// to the start of its session segment; `None` after the close
+pub fn bucket_start(ts: i64, minutes: u32, market: Market) -> Option<i64> {
+    let local = sessions::local_time(market, ts);
+    let minute = local.hour() * 60 + local.minute();
+    let &(start, _) = sessions::session_segments(market)
+        .iter()
+        .find(|(_, end)| minute < *end)?;
+    let offset = minute.saturating_sub(start) / minutes.max(1) * minutes.max(1);
+    let bucket =
+        local.date().and_hms_opt(0, 0, 0)? + chrono::Duration::minutes(i64::from(start + offset));
+    sessions::timezone(market)
+        .from_local_datetime(&bucket)
+        .single()
+        .map(|bucket| bucket.timestamp())
+}
+
+/// Start of the bar of `period` holding `ts` on `market`'s clock: the session
+/// bucket for minute periods, otherwise midnight of the day, the week's
+/// Monday or the month's first day
+pub fn period_start(period: KlinePeriod, market: Market, ts: i64) -> Option<i64> {
+    if let Some(minutes) = period.minutes() {
+        return bucket_start(ts, minutes, market);
+    }
+    let day = sessions::trading_day(market, ts);
+    let start = match period {
+        KlinePeriod::Weekly => {
+            day - chrono::Duration::days(i64::from(day.weekday().num_days_from_monday()))
+        }
+        KlinePeriod::Monthly => day.with_day(1)?,
+        _ => day,
+    };
+    local_timestamp(start.and_hms_opt(0, 0, 0)?, market)
+}
+
+/// Aggregate 1-minute bars into `minutes`-long bars aligned to the starts of
+/// the market's session segments. Bars before a segment opens, such as the
+/// opening auction, fold into its first bar; bars after the close are dropped.
+pub fn resample(bars: &[Bar], minutes: u32, market: Market) -> Vec<Bar> {
+    let mut resampled: Vec<Bar> = Vec::new();
+    for bar in bars {
+        let Some(bucket) = bucket_start(bar.timestamp, minutes, market) else {
+            continue;
+        };
+        match resampled.last_mut() {
+            Some(last) if last.timestamp == bucket => {
+                last.high = last.high.max(bar.high);
+                last.low = last.low.min(bar.low);
+                last.close = bar.close;
+                last.volume += bar.volume;
+            }
+            _ => resampled.push(Bar {
+                timestamp: bucket,
+                ..*bar
+            }),
+        }
+    }
+    resampled
+}
+
+fn same_prices(a: &Bar, b: &Bar) -> bool {
+    let close = |x: f64, y: f64| (x - y).abs() <= PRICE_TOLERANCE * x.abs().max(y.abs()).max(1.0);
+    a.timestamp == b.timestamp
+        && close(a.open, b.open)
+        && close(a.high, b.high)
+        && close(a.low, b.low)
+        && close(a.close, b.close)
+}
+
+/// Bar the tail refetch starts from, which is kept and checked against the refetch
+fn overlap_index(cached: &[Bar]) -> Option<usize> {
+    cached.len().checked_sub(2)
+}
+
+/// Append a tail fetched from the overlap bar's day; `None` when history was re-adjusted
+fn merge_tail(cached: &[Bar], tail: Vec<Bar>) -> Option<Vec<Bar>> {
+    let start = overlap_index(cached)?;
+    let overlap = tail
+        .iter()
+        .position(|bar| bar.timestamp == cached[start].timestamp)?;
+    if !same_prices(&cached[start], &tail[overlap]) {
+        return None;
+    }
+    let mut merged = cached[..start].to_vec();
+    merged.extend_from_slice(&tail[overlap..]);
+    Some(merged)
+}
+
+/// Bars from Eastmoney, from the bar at `from` when given
+pub async fn fetch_eastmoney(
+    app: &AppHandle,
+    symbol: &str,
+    period: KlinePeriod,
+    adjust: Adjust,
+    from: Option<i64>,
+) -> Result<Vec<Bar>, String> {
+    let secid = secid(symbol).ok_or_else(|| {
+        format!(
+            "K-lines are only available for A-shares and Hong Kong stocks: {}",
+            symbol
+        )
+    })?;
+    let market = Market::of(symbol).unwrap_or(Market::Cn);
+    let begin = match from {
+        Some(ts) => sessions::timezone(market)
+            .timestamp_opt(ts, 0)
+            .single()
+            .map(|t| t.format("%Y%m%d").to_string())
+            .unwrap_or_else(|| "0".to_string()),
+        None => "0".to_string(),
+    };
+    let url = format!(
+        "{}?secid={}&fields1=f1,f2,f3&fields2=f51,f52,f53,f54,f55,f56,f57&klt={}&fqt={}&beg={}&end=20500101",
+        KLINE_URL,
+        secid,
+        period.klt(),
+        adjust.fqt(),
+        begin
+    );
+    let policies = app.state::<PolicyEngine>();
+    let body = app
+        .state::<FaultInjector>()
+        .wrap(PROVIDER, async {
+            policies
+                .get(PROVIDER, &url)
+                .await?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read K-lines: {}", e))
+        })
+        .await?;
+    // Unknown symbols come back without data rather than with a changed schema
+    if serde_json::from_str::<Value>(&body).is_ok_and(|value| value["data"].is_null()) {
+        return Err(format!("No K-line data for {}", symbol));
+    }
+    let value =
+        app.state::<DriftLog>()
+            .check_response(app, PROVIDER, "kline", &body, KLINE_SCHEMA)?;
+    let rows = value["data"]["klines"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    Ok(rows
+        .iter()
+        .filter_map(|row| parse_kline(row.as_str()?, market))
+        .collect())
+}
+
+/// Cached bars brought up to date, re-downloading everything after a re-adjustment
+async fn refresh(
+    app: &AppHandle,
+    symbol: &str,
+    period: KlinePeriod,
+    adjust: Adjust,
+    cached: Vec<Bar>,
+) -> Result<Vec<Bar>, String> {
+    if let Some(start) = overlap_index(&cached) {
+        let tail =
+            providers::klines(app, symbol, period, adjust, Some(cached[start].timestamp)).await?;
+        if let Some(merged) = merge_tail(&cached, tail) {
+            return Ok(merged);
+        }
+        info!(
+            "{} {} history was re-adjusted; downloading it again",
+            symbol,
+            series_interval(period, adjust)
+        );
+    }
+    let bars = providers::klines(app, symbol, period, adjust, None).await?;
+    if period.minutes().is_none() {
+        return Ok(bars);
+    }
+    Ok(splice(cached, bars))
+}
+
+/// Keep the cached bars older than what the provider still has
+fn splice(cached: Vec<Bar>, bars: Vec<Bar>) -> Vec<Bar> {
+    let Some(first) = bars.first() else {
+        return cached;
+    };
+    let mut spliced: Vec<Bar> = cached
+        .into_iter()
+        .take_while(|bar| bar.timestamp < first.timestamp)
+        .collect();
+    spliced.extend(bars);
+    spliced
+}
+
+/// Bars of a downloaded period, from the cache or brought up to date by a
+/// download the executor admits at `priority`
+async fn series(
+    app: &AppHandle,
+    symbol: &str,
+    period: KlinePeriod,
+    adjust: Adjust,
+    priority: Priority,
+) -> Result<Vec<Bar>, String> {
+    let interval = series_interval(period, adjust);
+    let store = app.state::<ColumnarStore>();
+    let cached = if store.exists(symbol, &interval) {
+        let bars = store.open(symbol, &interval)?;
+        bars.to_bars(0..bars.len())
+    } else {
+        Vec::new()
+    };
+
+    if let Some(frozen_at) = app.state::<SnapshotClock>().frozen_at() {
+        return Ok(cached
+            .into_iter()
+            .filter(|bar| bar.timestamp <= frozen_at)
+            .collect());
+    }
+    let max_age = Duration::from_secs(app.state::<SettingsStore>().get().polling.bars_secs);
+    let fresh = store
+        .modified(symbol, &interval)
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < max_age);
+    if fresh && !cached.is_empty() {
+        return Ok(cached);
+    }
+
+    let bars = {
+        let _permit = app
+            .state::<Executor>()
+            .acquire(ResourceClass::Network, priority)
+            .await;
+        refresh(app, symbol, period, adjust, cached).await?
+    };
+    // Rewritten even when unchanged, to restart the freshness window
+    store.write(symbol, &interval, &bars)?;
+    Ok(bars)
+}
+
+/// Candles for a symbol, served from the local cache and refreshed from the provider
+#[tauri::command]
+pub async fn get_kline(
+    app: AppHandle,
+    symbol: String,
+    period: KlinePeriod,
+    adjust: Option<Adjust>,
+) -> Result<Vec<Bar>, String> {
+    let adjust = adjust.unwrap_or_default();
+    candles(&app, &symbol, period, adjust, Priority::Interactive).await
+}
+
+/// Candles of any period, resampling minute periods from the `1m` series;
+/// `priority` is [`Priority::Interactive`] for a view the user is waiting on
+pub async fn candles(
+    app: &AppHandle,
+    symbol: &str,
+    period: KlinePeriod,
+    adjust: Adjust,
+    priority: Priority,
+) -> Result<Vec<Bar>, String> {
+    let symbol = symbol_key(symbol);
+    let bars = series(app, &symbol, period.source(), adjust, priority).await?;
+    Ok(match period.minutes() {
+        Some(minutes) if minutes > 1 => {
+            resample(&bars, minutes, Market::of(&symbol).unwrap_or(Market::Cn))
+        }
+        _ => bars,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drift::validate;
+    use crate::testing::{fixture, minute_bars};
+    use proptest::prelude::*;
+
+    fn bar(day: u32, close: f64) -> Bar {
+        parse_kline(
+            &format!("2024-01-{:02},10.0,{},10.5,9.5,1200,0", day, close),
+            Market::Cn,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_intervals() {
+        let parsed = parse_kline(
+            "2024-01-02,1715.00,1685.01,1718.19,1678.10,32156,5473828200.00",
+            Market::Cn,
+        )
+        .unwrap();
+        assert_eq!(parsed.timestamp, 1_704_124_800);
+        assert_eq!(
+            (parsed.open, parsed.close, parsed.high, parsed.low),
+            (1715.0, 1685.01, 1718.19, 1678.1)
+        );
+        assert_eq!(parsed.volume, 3_215_600.0);
+        assert!(parse_kline("2024-01-02,-,,", Market::Cn).is_none());
+        // Hong Kong volume is already in shares
+        let hk = parse_kline("2024-01-02,300.0,302.0,305.0,298.0,15000000,0", Market::Hk).unwrap();
+        assert_eq!((hk.timestamp, hk.volume), (1_704_124_800, 15_000_000.0));
+
+        assert_eq!(secid("SH600519").as_deref(), Some("1.600519"));
+        assert_eq!(secid("sz000001").as_deref(), Some("0.000001"));
+        assert_eq!(secid("HK00700").as_deref(), Some("116.00700"));
+        assert!(secid("AAPL").is_none());
+        assert_eq!(series_interval(KlinePeriod::Daily, Adjust::None), "1d");
+        assert_eq!(
+            series_interval(KlinePeriod::Monthly, Adjust::Forward),
+            "1mo-qfq"
+        );
+    }
+
+    /// 1-minute bars on 2024-01-02 from the provider's `HH:MM` end stamps
+    fn minutes(stamps: &[&str]) -> Vec<Bar> {
+        stamps
+            .iter()
+            .enumerate()
+            .map(|(i, stamp)| {
+                let close = 10.0 + i as f64 / 10.0;
+                parse_kline(
+                    &format!(
+                        "2024-01-02 {},{},{},{},{},10,0",
+                        stamp,
+                        close - 0.05,
+                        close,
+                        close + 0.1,
+                        close - 0.1
+                    ),
+                    Market::Cn,
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resample_within_sessions() {
+        let open = 1_704_159_000; // 2024-01-02 09:30 Shanghai
+        let bars = minutes(&[
+            "09:30", "09:31", "09:32", "09:35", "09:36", "11:29", "11:30", "13:01", "13:02",
+            "15:00", "15:05",
+        ]);
+        // The auction print opens a minute before the session
+        assert_eq!(bars[0].timestamp, open - 60);
+        assert_eq!(bars[1].timestamp, open);
+
+        let five = resample(&bars, 5, Market::Cn);
+        let starts: Vec<i64> = five.iter().map(|b| (b.timestamp - open) / 60).collect();
+        assert_eq!(starts, [0, 5, 115, 210, 325]);
+        assert_eq!(
+            (five[0].open, five[0].close, five[0].high, five[0].low),
+            (9.95, 10.3, 10.4, 9.9)
+        );
+        assert_eq!(five[0].volume, 4_000.0);
+        assert_eq!(five[1].close, 10.4);
+
+        // Hourly bars break at 11:30 rather than straddling lunch
+        let hourly = resample(&bars, 60, Market::Cn);
+        let starts: Vec<i64> = hourly.iter().map(|b| (b.timestamp - open) / 60).collect();
+        assert_eq!(starts, [0, 60, 210, 270]);
+        assert_eq!(hourly[1].volume, 2_000.0);
+        assert_eq!(hourly[3].close, 10.9);
+        // Even unit buckets fold in the auction and drop after-hours trades
+        assert_eq!(resample(&bars, 1, Market::Cn).len(), bars.len() - 2);
+        assert_eq!(
+            series_interval(KlinePeriod::Minute15.source(), Adjust::None),
+            "1m"
+        );
+
+        // 2024-01-04 is a Thursday
+        let thursday = 1_704_331_800; // 09:30 Shanghai
+        let day = |d: i64| 1_704_124_800 + (d - 2) * 86_400;
+        assert_eq!(
+            period_start(KlinePeriod::Daily, Market::Cn, thursday),
+            Some(day(4))
+        );
+        assert_eq!(
+            period_start(KlinePeriod::Weekly, Market::Cn, thursday),
+            Some(day(1))
+        );
+        assert_eq!(
+            period_start(KlinePeriod::Monthly, Market::Cn, thursday),
+            Some(day(1))
+        );
+        assert_eq!(
+            period_start(KlinePeriod::Minute30, Market::Cn, thursday + 29 * 60),
+            Some(thursday)
+        );
+        assert_eq!(
+            period_start(KlinePeriod::Minute5, Market::Cn, day(4) + 20 * 3600),
+            None
+        );
+    }
+
+    #[test]
+    fn test_tail_merge_detects_readjustment() {
+        let cached = vec![bar(2, 10.0), bar(3, 10.2), bar(4, 10.1)];
+        // The last cached bar was still forming and has moved on
+        let tail = vec![bar(3, 10.2), bar(4, 10.3), bar(5, 10.4)];
+        let merged = merge_tail(&cached, tail).unwrap();
+        assert_eq!(
+            merged.iter().map(|b| b.close).collect::<Vec<_>>(),
+            [10.0, 10.2, 10.3, 10.4]
+        );
+
+        // A dividend shifted forward-adjusted history
+        let readjusted = vec![bar(3, 9.9), bar(4, 10.0)];
+        assert!(merge_tail(&cached, readjusted).is_none());
+        assert!(merge_tail(&cached[..1], vec![bar(2, 10.0)]).is_none());
+
+        // A whole refetched day finds the overlap minute inside it
+        let day = minutes(&["09:31", "09:32", "09:33", "09:34"]);
+        let merged = merge_tail(&day[..3], day.clone()).unwrap();
+        assert_eq!(merged, day);
+        assert_eq!(splice(day[..2].to_vec(), day[1..].to_vec()), day);
+    }
+
+    #[test]
+    fn test_parse_recorded_response() {
+        let value: Value = serde_json::from_str(&fixture("eastmoney_kline_daily.json")).unwrap();
+        assert!(validate(&value, KLINE_SCHEMA).is_empty());
+        let bars: Vec<Bar> = value["data"]["klines"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|row| parse_kline(row.as_str()?, Market::Cn))
+            .collect();
+        assert_eq!(bars.len(), 5);
+        let last = &bars[4];
+        // Midnight of 2025-06-03 in Shanghai
+        assert_eq!(last.timestamp, 1_748_880_000);
+        assert_eq!(
+            (last.open, last.close, last.high, last.low),
+            (1559.0, 1570.1, 1575.0, 1545.0)
+        );
+        assert_eq!(last.volume, 3_987_100.0);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_resample_keeps_ohlc_bounds(
+            bars in minute_bars(1..240),
+            minutes in 1u32..=60,
+        ) {
+            let resampled = resample(&bars, minutes, Market::Cn);
+            // Every bar opens before the close, so none is dropped
+            let volume = |bars: &[Bar]| bars.iter().map(|b| b.volume).sum::<f64>();
+            prop_assert_eq!(volume(&resampled), volume(&bars));
+            prop_assert_eq!(resampled[0].open, bars[0].open);
+            prop_assert_eq!(resampled.last().unwrap().close, bars.last().unwrap().close);
+            for pair in resampled.windows(2) {
+                prop_assert!(pair[0].timestamp < pair[1].timestamp);
+            }
+            for bucket in &resampled {
+                prop_assert!(bucket.low <= bucket.open.min(bucket.close));
+                prop_assert!(bucket.high >= bucket.open.max(bucket.close));
+                let members: Vec<&Bar> = bars
+                    .iter()
+                    .filter(|b| {
+                        bucket_start(b.timestamp, minutes, Market::Cn) == Some(bucket.timestamp)
+                    })
+                    .collect();
+                let high = members.iter().map(|b| b.high).fold(f64::MIN, f64::max);
+                let low = members.iter().map(|b| b.low).fold(f64::MAX, f64::min);
+                prop_assert_eq!((bucket.high, bucket.low), (high, low));
+            }
+        }
+    }
+}
@@ -0,0 +1,618 @@
+//! Incremental indicator updates for live charts.
+//!
+//! `watch_indicators` computes the full series once, like
+//! `compute_indicators`, and keeps a watch holding every indicator's
+//! recurrence state (EMA values, Wilder averages, K and D, the last window of
+//! prices) as of the last completed bar, next to the bar still forming. Each
+//! changed quote for the symbol updates the forming bar, or completes it and
+//! starts the next one when the quote falls in a new period, and the values
+//! of the forming bar are worked out from a copy of the state. Updating a
+//! chart therefore costs a step per indicator rather than a pass over years
+//! of history. The last values go out as compact `indicator-delta` events.
+//! Watches are stepped only while their symbol is subscribed at
+//! [`SubscriptionLevel::Chart`] or above, so charts hold such a subscription
+//! alongside their watch and a chart scrolled off screen stops costing
+//! anything; watches are dropped with their window.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use log::warn;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+
+use crate::executor::Priority;
+use crate::indicators::{self, rsi_value, CandleIndicators, IndicatorSpec, BOLL_WIDTH};
+use crate::kline::{self, period_start, Adjust, KlinePeriod};
+use crate::models::{Bar, Market};
+use crate::quotes::Quote;
+use crate::snapshot::SnapshotClock;
+use crate::subscriptions::{SubscriptionLevel, SubscriptionRegistry};
+use crate::symbols::symbol_key;
+
+/// The last `size` values seen
+#[derive(Debug, Clone)]
+struct Trailing {
+    size: usize,
+    values: VecDeque<f64>,
+}
+
+impl Trailing {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            values: VecDeque::with_capacity(size),
+        }
+    }
+
+    /// Add a value, returning whether the window is full
+    fn push(&mut self, value: f64) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        if self.values.len() == self.size {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+        self.values.len() == self.size
+    }
+
+    fn mean(&self) -> f64 {
+        self.values.iter().sum::<f64>() / self.values.len() as f64
+    }
+
+    fn std(&self) -> f64 {
+        let mean = self.mean();
+        let variance = self
+            .values
+            .iter()
+            .map(|v| (v - mean) * (v - mean))
+            .sum::<f64>()
+            / self.values.len() as f64;
+        variance.sqrt()
+    }
+
+    fn max(&self) -> f64 {
+        self.values.iter().copied().fold(f64::MIN, f64::max)
+    }
+
+    fn min(&self) -> f64 {
+        self.values.iter().copied().fold(f64::MAX, f64::min)
+    }
+}
+
+/// EMA seeded with the mean of its first window, as [`indicators::ema_seeded`]
+#[derive(Debug, Clone)]
+struct Ema {
+    period: usize,
+    seen: usize,
+    sum: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    fn new(period: usize) -> Self {
+        Self {
+            period,
+            seen: 0,
+            sum: 0.0,
+            value: None,
+        }
+    }
+
+    fn step(&mut self, value: f64) -> Option<f64> {
+        if self.period == 0 {
+            return None;
+        }
+        self.seen += 1;
+        self.value = match self.value {
+            Some(previous) => {
+                let alpha = 2.0 / (self.period as f64 + 1.0);
+                Some(alpha * value + (1.0 - alpha) * previous)
+            }
+            None => {
+                self.sum += value;
+                (self.seen == self.period).then(|| self.sum / self.period as f64)
+            }
+        };
+        self.value
+    }
+}
+
+/// Wilder-smoothed RSI, as [`indicators::rsi`]
+#[derive(Debug, Clone)]
+struct Rsi {
+    period: usize,
+    previous: Option<f64>,
+    changes: usize,
+    gain: f64,
+    loss: f64,
+}
+
+impl Rsi {
+    fn step(&mut self, value: f64) -> Option<f64> {
+        let previous = self.previous.replace(value)?;
+        if self.period == 0 {
+            return None;
+        }
+        let change = value - previous;
+        let period = self.period as f64;
+        self.changes += 1;
+        if self.changes <= self.period {
+            self.gain += change.max(0.0) / period;
+            self.loss += (-change).max(0.0) / period;
+            if self.changes < self.period {
+                return None;
+            }
+        } else {
+            self.gain = (self.gain * (period - 1.0) + change.max(0.0)) / period;
+            self.loss = (self.loss * (period - 1.0) + (-change).max(0.0)) / period;
+        }
+        Some(rsi_value(self.gain, self.loss))
+    }
+}
+
+/// Recurrence state of one indicator, advanced a bar at a time
+#[derive(Debug, Clone)]
+enum Stepper {
+    Sma(Trailing),
+    Ema(Ema),
+    Rsi(Rsi),
+    Macd {
+        fast: Ema,
+        slow: Ema,
+        signal: Ema,
+    },
+    Kdj {
+        highs: Trailing,
+        lows: Trailing,
+        m1: f64,
+        m2: f64,
+        k: f64,
+        d: f64,
+    },
+    Boll(Trailing),
+}
+
+impl Stepper {
+    fn new(spec: IndicatorSpec) -> Self {
+        match spec {
+            IndicatorSpec::Sma(n) => Stepper::Sma(Trailing::new(n)),
+            IndicatorSpec::Ema(n) => Stepper::Ema(Ema::new(n)),
+            IndicatorSpec::Rsi(n) => Stepper::Rsi(Rsi {
+                period: n,
+                previous: None,
+                changes: 0,
+                gain: 0.0,
+                loss: 0.0,
+            }),
+            IndicatorSpec::Macd(fast, slow, signal) => Stepper::Macd {
+                fast: Ema::new(fast),
+                slow: Ema::new(slow),
+                signal: Ema::new(signal),
+            },
+            IndicatorSpec::Kdj(n, m1, m2) => Stepper::Kdj {
+                highs: Trailing::new(n),
+                lows: Trailing::new(n),
+                m1: m1 as f64,
+                m2: m2 as f64,
+                k: 50.0,
+                d: 50.0,
+            },
+            IndicatorSpec::Boll(n) => Stepper::Boll(Trailing::new(n)),
+        }
+    }
+
+    /// Advance past `bar`, returning the indicator's lines at it
+    fn step(&mut self, bar: &Bar) -> Vec<Option<f64>> {
+        match self {
+            Stepper::Sma(window) => vec![window.push(bar.close).then(|| window.mean())],
+            Stepper::Ema(ema) => vec![ema.step(bar.close)],
+            Stepper::Rsi(rsi) => vec![rsi.step(bar.close)],
+            Stepper::Macd { fast, slow, signal } => {
+                let dif = fast.step(bar.close).zip(slow.step(bar.close));
+                let dif = dif.map(|(fast, slow)| fast - slow);
+                let dea = dif.and_then(|dif| signal.step(dif));
+                let histogram = dif.zip(dea).map(|(dif, dea)| 2.0 * (dif - dea));
+                vec![dif, dea, histogram]
+            }
+            Stepper::Kdj {
+                highs,
+                lows,
+                m1,
+                m2,
+                k,
+                d,
+            } => {
+                let full = highs.push(bar.high) & lows.push(bar.low);
+                if !full || *m1 <= 0.0 || *m2 <= 0.0 {
+                    return vec![None; 3];
+                }
+                let (highest, lowest) = (highs.max(), lows.min());
+                let rsv = if highest > lowest {
+                    (bar.close - lowest) / (highest - lowest) * 100.0
+                } else {
+                    50.0
+                };
+                *k = ((*m1 - 1.0) * *k + rsv) / *m1;
+                *d = ((*m2 - 1.0) * *d + *k) / *m2;
+                vec![Some(*k), Some(*d), Some(3.0 * *k - 2.0 * *d)]
+            }
+            Stepper::Boll(window) => {
+                if !window.push(bar.close) {
+                    return vec![None; 3];
+                }
+                let (mid, width) = (window.mean(), BOLL_WIDTH * window.std());
+                vec![Some(mid), Some(mid + width), Some(mid - width)]
+            }
+        }
+    }
+}
+
+/// Last indicator values of a watched chart
+#[derive(Debug, Clone, Serialize)]
+pub struct IndicatorDelta {
+    pub watch_id: u64,
+    pub symbol: String,
+    /// Timestamp of the forming bar the values belong to
+    pub timestamp: i64,
+    /// Whether the bar before it has just been completed
+    pub new_bar: bool,
+    pub values: HashMap<String, Option<f64>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndicatorWatch {
+    pub watch_id: u64,
+    pub indicators: CandleIndicators,
+}
+
+struct Watch {
+    owner: String,
+    symbol: String,
+    period: KlinePeriod,
+    specs: Vec<IndicatorSpec>,
+    /// State as of the last completed bar
+    steppers: Vec<Stepper>,
+    forming: Option<Bar>,
+}
+
+impl Watch {
+    fn new(
+        owner: &str,
+        symbol: &str,
+        period: KlinePeriod,
+        specs: Vec<IndicatorSpec>,
+        bars: &[Bar],
+    ) -> Self {
+        let mut steppers: Vec<Stepper> = specs.iter().map(|spec| Stepper::new(*spec)).collect();
+        let (forming, completed) = match bars.split_last() {
+            Some((last, completed)) => (Some(*last), completed),
+            None => (None, bars),
+        };
+        for bar in completed {
+            for stepper in &mut steppers {
+                stepper.step(bar);
+            }
+        }
+        Self {
+            owner: owner.to_string(),
+            symbol: symbol.to_string(),
+            period,
+            specs,
+            steppers,
+            forming,
+        }
+    }
+
+    /// Fold a quote into the forming bar, returning whether it started a new one;
+    /// `None` for a quote outside the bars, e.g. after the close or out of date
+    fn apply(&mut self, quote: &Quote) -> Option<bool> {
+        let market = Market::of(&self.symbol).unwrap_or(Market::Cn);
+        let start = period_start(self.period, market, quote.timestamp)?;
+        let intraday = self.period.minutes().is_some();
+        // Daily and longer bars are stamped with their latest trading day
+        let timestamp = if intraday {
+            start
+        } else {
+            period_start(KlinePeriod::Daily, market, quote.timestamp)?
+        };
+        if let Some(bar) = self.forming.as_mut() {
+            let current = period_start(self.period, market, bar.timestamp)?;
+            if start < current {
+                return None;
+            }
+            if start == current {
+                let (high, low) = if intraday {
+                    (quote.price, quote.price)
+                } else {
+                    (quote.high, quote.low)
+                };
+                bar.timestamp = timestamp;
+                bar.high = bar.high.max(high);
+                bar.low = bar.low.min(low);
+                bar.close = quote.price;
+                return Some(false);
+            }
+        }
+        if let Some(completed) = self.forming.take() {
+            for stepper in &mut self.steppers {
+                stepper.step(&completed);
+            }
+        }
+        self.forming = Some(if intraday {
+            Bar {
+                timestamp,
+                open: quote.price,
+                high: quote.price,
+                low: quote.price,
+                close: quote.price,
+                volume: 0.0,
+            }
+        } else {
+            Bar {
+                timestamp,
+                open: quote.open,
+                high: quote.high,
+                low: quote.low,
+                close: quote.price,
+                volume: quote.volume,
+            }
+        });
+        Some(true)
+    }
+
+    /// Indicator values at the forming bar, from a copy of the completed state
+    fn values(&self) -> HashMap<String, Option<f64>> {
+        let Some(bar) = self.forming else {
+            return HashMap::new();
+        };
+        self.specs
+            .iter()
+            .zip(&self.steppers)
+            .flat_map(|(spec, stepper)| {
+                let values = stepper.clone().step(&bar);
+                spec.lines()
+                    .iter()
+                    .zip(values)
+                    .map(|(line, value)| (spec.line_label(line), value))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct LiveIndicators {
+    next_id: AtomicU64,
+    watches: Mutex<HashMap<u64, Watch>>,
+}
+
+impl LiveIndicators {
+    /// Drop the watches of a destroyed window
+    pub fn release_window(&self, label: &str) {
+        self.watches
+            .lock()
+            .unwrap()
+            .retain(|_, watch| watch.owner != label);
+    }
+
+    /// Keep only the watches of windows for which `keep` holds
+    pub fn retain_windows(&self, keep: impl Fn(&str) -> bool) {
+        self.watches
+            .lock()
+            .unwrap()
+            .retain(|_, watch| keep(&watch.owner));
+    }
+
+    /// Watches and the estimated bytes of their windowed state
+    pub fn usage(&self) -> (usize, usize) {
+        let watches = self.watches.lock().unwrap();
+        let bytes = watches
+            .values()
+            .flat_map(|watch| &watch.specs)
+            .map(|spec| match *spec {
+                IndicatorSpec::Sma(n) | IndicatorSpec::Boll(n) => n * 8,
+                IndicatorSpec::Kdj(n, ..) => n * 16,
+                _ => 0,
+            } + std::mem::size_of::<Stepper>())
+            .sum();
+        (watches.len(), bytes)
+    }
+}
+
+/// Step the watches on freshly changed quotes and emit their new values
+pub fn on_quotes(app: &AppHandle, quotes: &[Quote]) {
+    if app.state::<SnapshotClock>().is_frozen() {
+        return;
+    }
+    let charted = app
+        .state::<SubscriptionRegistry>()
+        .symbols_at(SubscriptionLevel::Chart);
+    let live = app.state::<LiveIndicators>();
+    let deltas: Vec<IndicatorDelta> = {
+        let mut watches = live.watches.lock().unwrap();
+        watches
+            .iter_mut()
+            .filter(|(_, watch)| charted.binary_search(&watch.symbol).is_ok())
+            .filter_map(|(id, watch)| {
+                let quote = quotes.iter().find(|q| q.symbol == watch.symbol)?;
+                let new_bar = watch.apply(quote)?;
+                Some(IndicatorDelta {
+                    watch_id: *id,
+                    symbol: watch.symbol.clone(),
+                    timestamp: watch.forming?.timestamp,
+                    new_bar,
+                    values: watch.values(),
+                })
+            })
+            .collect()
+    };
+    for delta in deltas {
+        if let Err(e) = app.emit("indicator-delta", delta) {
+            warn!("Failed to emit indicator-delta event: {}", e);
+        }
+    }
+}
+
+/// Compute indicators over a symbol's candles and keep them updated from live quotes
+#[tauri::command]
+pub async fn watch_indicators(
+    window: Window,
+    app: AppHandle,
+    symbol: String,
+    period: KlinePeriod,
+    specs: Vec<IndicatorSpec>,
+    adjust: Option<Adjust>,
+) -> Result<IndicatorWatch, String> {
+    let symbol = symbol_key(&symbol);
+    let adjust = adjust.unwrap_or_default();
+    let bars = kline::candles(&app, &symbol, period, adjust, Priority::Interactive).await?;
+    let owner = window.label().to_string();
+    let (watch, indicators) = tauri::async_runtime::spawn_blocking(move || {
+        let indicators = indicators::candle_indicators(&bars, &specs);
+        (
+            Watch::new(&owner, &symbol, period, specs, &bars),
+            indicators,
+        )
+    })
+    .await
+    .map_err(|e| format!("Failed to compute indicators: {}", e))?;
+
+    let live = app.state::<LiveIndicators>();
+    let watch_id = live.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    live.watches.lock().unwrap().insert(watch_id, watch);
+    Ok(IndicatorWatch {
+        watch_id,
+        indicators,
+    })
+}
+
+/// Stop live updates of a watch taken with `watch_indicators`
+#[tauri::command]
+pub fn unwatch_indicators(live: State<'_, LiveIndicators>, watch_id: u64) -> Result<(), String> {
+    live.watches
+        .lock()
+        .unwrap()
+        .remove(&watch_id)
+        .map(|_| ())
+        .ok_or_else(|| format!("Indicator watch not found: {}", watch_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Currency;
+    use crate::testing::sample_closes;
+
+    const SPECS: [IndicatorSpec; 6] = [
+        IndicatorSpec::Sma(5),
+        IndicatorSpec::Ema(12),
+        IndicatorSpec::Rsi(14),
+        IndicatorSpec::Macd(12, 26, 9),
+        IndicatorSpec::Kdj(9, 3, 3),
+        IndicatorSpec::Boll(20),
+    ];
+
+    fn bars() -> Vec<Bar> {
+        sample_closes()
+            .into_iter()
+            .enumerate()
+            .map(|(i, close)| Bar {
+                timestamp: 1_704_124_800 + i as i64 * 86_400,
+                open: close,
+                high: close * 1.01,
+                low: close * 0.98,
+                close,
+                volume: 0.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_steps_match_full_computation() {
+        let bars = bars();
+        let full = indicators::candle_indicators(&bars, &SPECS);
+        let mut steppers: Vec<Stepper> = SPECS.iter().map(|spec| Stepper::new(*spec)).collect();
+        for (i, bar) in bars.iter().enumerate() {
+            for (spec, stepper) in SPECS.iter().zip(&mut steppers) {
+                for (line, value) in spec.lines().iter().zip(stepper.step(bar)) {
+                    let expected = full.series[&spec.line_label(line)][i];
+                    assert_eq!(
+                        value.is_some(),
+                        expected.is_some(),
+                        "{} at {}",
+                        spec.label(),
+                        i
+                    );
+                    if let (Some(value), Some(expected)) = (value, expected) {
+                        assert!(
+                            (value - expected).abs() < 1e-6 * expected.abs().max(1.0),
+                            "{}.{} at {}: {} vs {}",
+                            spec.label(),
+                            line,
+                            i,
+                            value,
+                            expected
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_quotes_update_forming_bar() {
+        let bars = bars();
+        let last = *bars.last().unwrap();
+        let mut watch = Watch::new(
+            "main",
+            "SH600519",
+            KlinePeriod::Daily,
+            SPECS.to_vec(),
+            &bars,
+        );
+        let quote = |timestamp: i64, price: f64| Quote {
+            symbol: "SH600519".to_string(),
+            name: String::new(),
+            price,
+            prev_close: last.close,
+            open: last.open,
+            high: price.max(last.high),
+            low: price.min(last.low),
+            volume: 0.0,
+            amount: 0.0,
+            change: 0.0,
+            change_pct: 0.0,
+            currency: Currency::Cny,
+            timestamp,
+            source: "tencent".to_string(),
+        };
+
+        // A tick later the same day reprices the last bar
+        let price = last.close * 1.02;
+        assert_eq!(
+            watch.apply(&quote(last.timestamp + 36_000, price)),
+            Some(false)
+        );
+        let mut repriced = bars.clone();
+        *repriced.last_mut().unwrap() = watch.forming.unwrap();
+        let full = indicators::candle_indicators(&repriced, &SPECS);
+        let values = watch.values();
+        assert_eq!(values.len(), full.series.len());
+        for (label, value) in &values {
+            let expected = full.series[label].last().copied().flatten();
+            assert!(
+                (value.unwrap() - expected.unwrap()).abs()
+                    < 1e-6 * expected.unwrap().abs().max(1.0)
+            );
+        }
+
+        // The next day completes it and starts a new bar
+        let next_day = last.timestamp + 86_400 + 36_000;
+        assert_eq!(watch.apply(&quote(next_day, price)), Some(true));
+        assert_eq!(watch.forming.unwrap().timestamp, last.timestamp + 86_400);
+        assert_eq!(watch.apply(&quote(last.timestamp, price)), None);
+    }
+}
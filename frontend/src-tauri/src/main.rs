@@ -1,12 +1,112 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::env;
-use log::{info, LevelFilter};
 use env_logger::Builder;
+use log::{info, warn, LevelFilter};
+use std::env;
+use tauri::Manager;
 
+mod ai;
+mod ai_batch;
+mod alerts;
+mod answers;
+mod appearance;
+mod archive;
+mod articles;
+mod audit;
+mod backtest;
+mod backtest_runs;
+mod brokers;
+mod calendar;
+mod columnar;
 mod commands;
+mod compliance;
+mod costs;
+mod dashboard;
+mod datasets;
+mod db;
+mod delivery;
+mod documents;
+mod drift;
+mod embeddings;
+mod entity_linking;
+mod executor;
+mod explain;
+mod faults;
+mod fields;
+mod financials;
+mod fonts;
+mod formulas;
+mod guest;
+mod housekeeping;
+mod indicators;
+mod instruments;
+mod kline;
+mod live_indicators;
+mod market_clock;
+mod menu_bar;
+mod merge;
+mod middleware;
+mod models;
+mod monitor;
+mod news;
+mod news_backfill;
+mod news_clusters;
+mod news_watch;
+mod note_editor;
+mod notifications;
+mod ocr;
+mod placement;
+mod politeness;
+mod polling;
+mod portfolio;
+mod portfolio_alerts;
+mod portfolio_pnl;
+mod power;
+mod preload;
+mod presence;
+mod presentation;
+mod privacy;
+mod profile;
+mod progress;
+mod provider_sessions;
+mod providers;
+mod proxy;
+mod quotes;
+mod recents;
+mod reconciliation;
+mod research;
+mod rolling;
+mod scheduler;
+mod screener;
+mod secure_store;
+mod sessions;
+mod settings;
+mod signing;
+mod snapshot;
+mod sql_console;
+mod strategy;
+mod strategy_file;
+mod streaming;
+mod subscriptions;
+mod symbol_search;
+mod symbols;
+mod tasks;
+#[cfg(test)]
+mod testing;
+mod titlebar;
+mod tls;
+mod transcription;
+mod undo;
+mod universe;
+mod updater;
 mod utils;
+mod valuation;
+mod watch_folders;
+mod watchlist_view;
+mod webview_fetch;
+mod workspace;
+mod zoom;
 
 use commands::*;
 
@@ -20,19 +120,406 @@ fn main() {
     dotenv::dotenv().ok();
 
     tauri::Builder::default()
+        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            recents::on_second_instance(app, &args);
+        }))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_window::init())
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(middleware::guard(tauri::generate_handler![
             get_app_info,
             open_external_url,
             show_in_folder,
             get_system_info,
-            check_for_updates,
+            updater::check_for_updates,
+            updater::install_update,
             restart_app,
             minimize_to_tray,
-            show_notification
-        ])
+            notifications::show_notification,
+            columnar::get_columnar_info,
+            indicators::compute_indicators,
+            indicators::compute_indicators_batch,
+            indicators::benchmark_indicators,
+            tasks::cancel_task,
+            tasks::list_tasks,
+            executor::get_executor_stats,
+            settings::get_settings,
+            settings::update_settings,
+            symbols::validate_symbol,
+            symbol_search::search_symbols,
+            preload::record_symbol_view,
+            recents::take_launch_action,
+            preload::get_preload_stats,
+            snapshot::set_snapshot_time,
+            snapshot::get_snapshot_time,
+            faults::inject_fault,
+            faults::clear_faults,
+            faults::list_faults,
+            instruments::refresh_instrument_master,
+            instruments::get_universe_changes,
+            universe::record_index_constituents,
+            universe::get_universe,
+            costs::get_cost_presets,
+            backtest::run_backtest,
+            backtest::run_portfolio_backtest,
+            strategy::save_strategy,
+            strategy::list_strategies,
+            strategy_file::export_strategy,
+            strategy_file::import_strategy,
+            backtest_runs::list_backtest_runs,
+            backtest_runs::compare_runs,
+            monitor::start_strategy_monitor,
+            monitor::stop_strategy_monitor,
+            monitor::list_strategy_monitors,
+            monitor::get_signal_journal,
+            subscriptions::acquire_symbol,
+            subscriptions::release_symbol,
+            subscriptions::list_subscriptions,
+            drift::get_provider_drift,
+            drift::clear_provider_drift,
+            provider_sessions::start_provider_login,
+            provider_sessions::get_provider_sessions,
+            provider_sessions::logout_provider,
+            webview_fetch::list_scrape_sources,
+            webview_fetch::scrape_page,
+            politeness::get_source_policies,
+            news::get_news,
+            news::refresh_news,
+            articles::get_article_content,
+            news_clusters::get_news_clusters,
+            news_watch::add_news_watch,
+            news_watch::remove_news_watch,
+            news_watch::list_news_watches,
+            news_watch::get_watched_news,
+            entity_linking::add_symbol_alias,
+            entity_linking::remove_symbol_alias,
+            entity_linking::list_symbol_aliases,
+            documents::list_documents,
+            documents::get_document,
+            documents::search_documents,
+            watch_folders::list_watch_folders,
+            watch_folders::add_watch_folder,
+            watch_folders::remove_watch_folder,
+            watch_folders::scan_watch_folders,
+            transcription::transcribe_media,
+            ocr::ocr_import,
+            proxy::get_proxy_routes,
+            proxy::set_source_proxy,
+            proxy::test_source_connectivity,
+            tls::get_tls_diagnostics,
+            signing::set_provider_credentials,
+            signing::clear_provider_credentials,
+            signing::get_provider_credentials,
+            portfolio::get_portfolio,
+            portfolio::add_manual_holding,
+            portfolio::remove_manual_holding,
+            brokers::sync_broker_positions,
+            portfolio::list_transactions,
+            portfolio::add_transaction,
+            portfolio::import_transactions,
+            portfolio::update_transaction,
+            portfolio::remove_transaction,
+            portfolio_pnl::get_portfolio_pnl,
+            audit::get_audit_trail,
+            reconciliation::reconcile_account,
+            undo::undo_last_action,
+            profile::export_profile,
+            profile::import_profile,
+            privacy::get_privacy_mode,
+            privacy::set_privacy_mode,
+            guest::get_guest_mode,
+            guest::enter_guest_mode,
+            guest::exit_guest_mode,
+            guest::set_guest_passcode,
+            compliance::get_active_policies,
+            sql_console::run_readonly_query,
+            datasets::describe_datasets,
+            workspace::list_workspaces,
+            workspace::save_workspace,
+            workspace::load_workspace,
+            workspace::delete_workspace,
+            appearance::get_appearance,
+            appearance::set_appearance,
+            fonts::list_system_fonts,
+            fonts::get_render_fonts,
+            calendar::get_session_status_banner,
+            market_clock::get_market_phases,
+            news_backfill::backfill_news,
+            news_backfill::list_news_backfills,
+            embeddings::rebuild_embeddings,
+            embeddings::get_embedding_status,
+            embeddings::semantic_search,
+            research::ask_research,
+            ai::get_ai_routing,
+            ai::set_ai_api_key,
+            ai::remove_ai_api_key,
+            answers::get_answer_sources,
+            notifications::get_notification_settings,
+            notifications::set_notification_muted,
+            notifications::set_mute_all,
+            notifications::set_quiet_hours,
+            notifications::snooze_symbol,
+            notifications::unsnooze_symbol,
+            notifications::list_notifications,
+            notifications::get_unread_notification_count,
+            notifications::mark_read,
+            notifications::mark_unread,
+            notifications::archive_notifications,
+            notifications::clear_notifications,
+            ai_batch::run_ai_batch,
+            ai_batch::resume_ai_batch,
+            ai_batch::get_ai_batch,
+            ai_batch::list_ai_batches,
+            db::list_watchlists,
+            db::create_watchlist,
+            db::rename_watchlist,
+            db::delete_watchlist,
+            db::reorder_watchlists,
+            db::add_symbol,
+            db::remove_symbol,
+            db::reorder_symbols,
+            db::move_symbol_to_group,
+            db::set_watchlist_refresh,
+            db::list_portfolios,
+            db::create_portfolio,
+            db::rename_portfolio,
+            db::delete_portfolio,
+            db::list_alerts,
+            db::create_alert,
+            db::set_alert_enabled,
+            db::delete_alert,
+            db::list_alert_triggers,
+            db::list_notes,
+            db::create_note,
+            db::update_note,
+            db::delete_note,
+            note_editor::edit_note_externally,
+            note_editor::resolve_note_edit_conflict,
+            note_editor::finish_note_edit,
+            explain::explain_trigger,
+            quotes::get_quotes,
+            streaming::get_quote_stream_state,
+            presence::get_session_presence,
+            kline::get_kline,
+            alerts::snooze_alert,
+            alerts::set_alert_rearm,
+            alerts::set_alert_channels,
+            portfolio_alerts::list_portfolio_alerts,
+            portfolio_alerts::add_portfolio_alert,
+            portfolio_alerts::set_portfolio_alert_enabled,
+            portfolio_alerts::delete_portfolio_alert,
+            delivery::list_delivery_channels,
+            delivery::set_delivery_secret,
+            delivery::remove_delivery_secret,
+            delivery::test_delivery_channel,
+            watchlist_view::get_watchlist_view,
+            dashboard::get_dashboard_tile,
+            live_indicators::watch_indicators,
+            live_indicators::unwatch_indicators,
+            housekeeping::get_memory_breakdown,
+            scheduler::list_scheduled_jobs,
+            scheduler::set_scheduled_job,
+            scheduler::run_scheduled_job,
+            providers::get_providers,
+            providers::get_fundamentals,
+            financials::get_financial_statements,
+            valuation::get_valuation,
+            screener::run_screen,
+            formulas::list_formulas,
+            formulas::check_formula,
+            formulas::save_formula,
+            formulas::delete_formula,
+            formulas::compute_formula,
+            formulas::compute_formula_columns,
+            titlebar::begin_drag,
+            titlebar::toggle_maximize,
+            titlebar::set_always_on_top,
+            titlebar::get_titlebar_hints,
+            titlebar::show_snap_layouts,
+            notifications::run_notification_action,
+            placement::open_chart_window,
+            placement::toggle_ticker_bar,
+            placement::list_monitors,
+            presentation::enter_presentation_mode,
+            presentation::exit_presentation_mode,
+            presentation::get_presentation_state,
+            archive::capture_archive_snapshot,
+            archive::submit_archive_snapshot,
+            archive::verify_archive,
+            zoom::set_window_zoom,
+            zoom::get_window_zoom
+        ]))
+        .manage(tasks::TaskManager::default())
+        .manage(executor::Executor::default())
+        .manage(indicators::IndicatorCache::default())
+        .manage(live_indicators::LiveIndicators::default())
+        .manage(housekeeping::Housekeeping::default())
+        .manage(preload::PreloadState::default())
+        .manage(snapshot::SnapshotClock::default())
+        .manage(faults::FaultInjector::default())
+        .manage(subscriptions::SubscriptionRegistry::default())
+        .manage(providers::ProviderHealth::default())
+        .manage(quotes::QuoteFeed::default())
+        .manage(watchlist_view::WatchlistViews::default())
+        .manage(streaming::QuoteStream::default())
+        .manage(presence::SessionPresence::default())
+        .manage(placement::MonitorLayout::default())
+        .manage(presentation::Presentation::default())
+        .manage(archive::Archive::default())
+        .manage(watch_folders::FolderWatcher::default())
+        .manage(note_editor::ExternalEdits::default())
+        .manage(scheduler::Scheduler::default())
+        .manage(market_clock::MarketClock::default())
+        .manage(alerts::QuoteHistory::default())
+        .manage(webview_fetch::WebviewFetcher::default())
+        .manage(politeness::PolicyEngine::default())
+        .manage(compliance::CompliancePolicy::load())
+        .manage(fonts::FontCatalog::default())
+        .on_page_load(|webview, payload| {
+            if payload.event() == tauri::webview::PageLoadEvent::Finished {
+                zoom::apply_zoom(webview);
+            }
+        })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::Destroyed => {
+                let registry = window.state::<subscriptions::SubscriptionRegistry>();
+                subscriptions::emit_changes(
+                    window.app_handle(),
+                    registry.release_owner(window.label()),
+                );
+                window
+                    .state::<watchlist_view::WatchlistViews>()
+                    .release_window(window.label());
+                window
+                    .state::<live_indicators::LiveIndicators>()
+                    .release_window(window.label());
+            }
+            tauri::WindowEvent::CloseRequested { api, .. }
+                if window.label() == "main" && menu_bar::keeps_running(window.app_handle()) =>
+            {
+                api.prevent_close();
+                if let Err(e) = window.hide() {
+                    warn!("Failed to hide main window: {}", e);
+                }
+            }
+            tauri::WindowEvent::ThemeChanged(theme) => {
+                appearance::on_system_theme(window.app_handle(), *theme);
+            }
+            _ => {}
+        })
         .setup(|app| {
+            let data_dir =
+                utils::get_app_data_dir().ok_or("Failed to resolve app data directory")?;
+            app.manage(columnar::ColumnarStore::new(data_dir.join("columnar")));
+            app.manage(settings::SettingsStore::load(
+                data_dir.join("settings.json"),
+            ));
+            let db = db::Database::open(data_dir.join(db::DB_FILE))?;
+            db.import_legacy(&data_dir)?;
+            app.manage(db);
+            let current = app.state::<settings::SettingsStore>().get();
+            if let Err(e) = app
+                .state::<politeness::PolicyEngine>()
+                .configure(&current.proxies, &current.tls)
+            {
+                warn!("Failed to apply network settings: {}", e);
+            }
+            app.manage(privacy::Privacy::new(current.privacy_mode));
+            let theme = app.get_webview_window("main").and_then(|w| w.theme().ok());
+            app.manage(appearance::SystemTheme::new(theme));
+            let guest_flag = env::args().any(|arg| arg == guest::GUEST_FLAG);
+            app.manage(guest::GuestMode::new(guest_flag, &current.guest));
+            app.manage(preload::UsageTracker::load(data_dir.join("usage.json")));
+            app.manage(recents::RecentSymbols::load(data_dir.join("recents.json")));
+            let args: Vec<String> = env::args().collect();
+            app.manage(recents::PendingLaunch::new(recents::parse_args(&args)));
+            app.manage(drift::DriftLog::load(data_dir.join("provider_drift.json")));
+            app.manage(provider_sessions::SessionVault::load(
+                data_dir.join("provider_sessions.enc"),
+                data_dir.join("session.key"),
+            ));
+            app.manage(signing::CredentialVault::load(
+                data_dir.join("provider_credentials.enc"),
+                data_dir.join("session.key"),
+            ));
+            app.manage(ai::AiKeyVault::load(
+                data_dir.join("ai_keys.enc"),
+                data_dir.join("session.key"),
+            ));
+            app.manage(delivery::DeliveryVault::load(
+                data_dir.join("delivery_secrets.enc"),
+                data_dir.join("session.key"),
+            ));
+            app.manage(ai::AdviceAudit(audit::AuditLog::load(
+                data_dir.join("ai_advice_audit.jsonl"),
+            )));
+            app.manage(instruments::InstrumentMaster::load(
+                data_dir.join("instruments.json"),
+            ));
+            let instruments = app.state::<instruments::InstrumentMaster>().instruments();
+            app.manage(symbol_search::SymbolIndex::new(&instruments));
+            app.manage(universe::UniverseStore::load(
+                data_dir.join("index_history.json"),
+            ));
+            app.manage(strategy::StrategyStore::load(
+                data_dir.join("strategies.json"),
+            ));
+            app.manage(backtest_runs::RunStore::load(
+                data_dir.join("backtest_runs.json"),
+            ));
+            app.manage(monitor::StrategyMonitor::load(
+                data_dir.join("monitors.json"),
+            ));
+            app.manage(news::NewsStore::load(data_dir.join("news.json")));
+            app.manage(entity_linking::AliasStore::load(
+                data_dir.join("symbol_aliases.json"),
+            ));
+            app.manage(news_watch::NewsWatcher::load(
+                data_dir.join("news_watches.json"),
+            ));
+            app.manage(documents::DocumentStore::load(
+                data_dir.join("research_documents.json"),
+            ));
+            app.manage(embeddings::EmbeddingIndex::load(
+                data_dir.join("embeddings.json"),
+            ));
+            app.manage(answers::AnswerStore::load(data_dir.join("ai_answers.json")));
+            app.manage(ai_batch::BatchStore::load(data_dir.join("ai_batches.json")));
+            app.manage(news_backfill::BackfillStore::load(
+                data_dir.join("news_backfill.json"),
+            ));
+            app.manage(formulas::Formulas::default());
+            app.manage(articles::ArticleCache::new(data_dir.join("articles")));
+            app.manage(undo::UndoLog::load(data_dir.join("undo_history.json")));
+            app.manage(portfolio::PortfolioStore::load(
+                data_dir.join("portfolio.json"),
+                data_dir.join("portfolio_audit.jsonl"),
+            ));
+            app.manage(strategy_file::SigningIdentity::load_or_create(
+                data_dir.join("signing.key"),
+            ));
+
+            preload::start_preload(app.handle());
+            instruments::refresh_if_due(app.handle());
+            monitor::resume_monitors(app.handle());
+            news::start_news_polling(app.handle());
+            market_clock::start_market_clock(app.handle());
+            quotes::start_quote_polling(app.handle());
+            streaming::start_quote_stream(app.handle());
+            presence::start_presence_monitor(app.handle());
+            housekeeping::start_housekeeping(app.handle());
+            news_backfill::resume_backfills(app.handle());
+            ai_batch::resume_batches(app.handle());
+            embeddings::compact_if_due(app.handle());
+            menu_bar::apply_dock_policy(app);
+            menu_bar::start_menu_bar(app.handle());
+            recents::start_recents(app.handle());
+            placement::start_monitor_watch(app.handle());
+            presentation::start_presentation_schedule(app.handle());
+            archive::start_archive_schedule(app.handle());
+            watch_folders::start_folder_watch(app.handle());
+            scheduler::start_scheduler(app.handle());
+            alerts::start_alert_watch(app.handle());
+
             info!("Application setup completed successfully");
             Ok(())
         })
@@ -46,4 +533,4 @@ fn init_logger() {
         .filter_level(LevelFilter::Info)
         .filter_module("tauri", LevelFilter::Warn)
         .init();
-}
\ No newline at end of file
+}
@@ -0,0 +1,220 @@
+//! The phase each market is in, and events when it changes.
+//!
+//! [`phase`] reads a market's trading calendar, half days included, and its
+//! session times into one of four phases: the pre-open (集合竞价 in Shanghai,
+//! Shenzhen and Hong Kong, pre-market trading in New York), the sessions,
+//! the lunch break between them, and closed. The clock checks every market
+//! each [`TICK`] and emits a `market-phase-changed` event when one moves on,
+//! so the frontend needn't keep its own session timers.
+//!
+//! The rest of the app follows the same phases: quotes poll at the session
+//! rate only while a market is open, every polled symbol is due again the
+//! moment a market changes phase rather than on its off-hours schedule, and
+//! price alerts let the indicative prices of the pre-open pass.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Timelike, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::calendar;
+use crate::models::Market;
+use crate::sessions;
+
+const TICK: Duration = Duration::from_secs(5);
+const MARKETS: [Market; 3] = [Market::Cn, Market::Hk, Market::Us];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketPhase {
+    /// The opening auction, or pre-market trading in New York
+    PreOpen,
+    Open,
+    LunchBreak,
+    /// After the close, and all day when the market doesn't trade
+    Closed,
+}
+
+/// Phase of `market` at Unix second `ts`
+pub fn phase(market: Market, ts: i64) -> MarketPhase {
+    let day = sessions::trading_day(market, ts);
+    if !calendar::is_market_trading_day(market, day) {
+        return MarketPhase::Closed;
+    }
+    let time = sessions::local_time(market, ts);
+    let minute = time.hour() * 60 + time.minute();
+    let segments = calendar::sessions_on(market, day);
+    let (Some(&(open, _)), Some(&(_, close))) = (segments.first(), segments.last()) else {
+        return MarketPhase::Closed;
+    };
+    if segments.iter().any(|&(s, e)| s <= minute && minute < e) {
+        MarketPhase::Open
+    } else if sessions::in_pre_market(market, ts) {
+        MarketPhase::PreOpen
+    } else if (open..close).contains(&minute) {
+        MarketPhase::LunchBreak
+    } else {
+        MarketPhase::Closed
+    }
+}
+
+/// Payload of the `market-phase-changed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseChange {
+    pub market: Market,
+    pub phase: MarketPhase,
+    pub previous: MarketPhase,
+    pub at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarketStatus {
+    pub market: Market,
+    pub phase: MarketPhase,
+    /// Local close today, `HH:MM`, when it comes early
+    pub early_close: Option<String>,
+}
+
+/// The last phase seen of each market
+#[derive(Default)]
+pub struct MarketClock {
+    phases: Mutex<HashMap<Market, MarketPhase>>,
+    /// Phase changes seen so far, for pollers to notice one happened
+    changes: AtomicU64,
+}
+
+impl MarketClock {
+    /// Phase of `market` as of the last tick, or now before the first
+    pub fn phase(&self, market: Market) -> MarketPhase {
+        self.phases
+            .lock()
+            .unwrap()
+            .get(&market)
+            .copied()
+            .unwrap_or_else(|| phase(market, Utc::now().timestamp()))
+    }
+
+    pub fn changes(&self) -> u64 {
+        self.changes.load(Ordering::Relaxed)
+    }
+
+    /// Move every market to its phase at `now`, returning those that changed
+    fn advance(&self, now: i64) -> Vec<PhaseChange> {
+        let mut phases = self.phases.lock().unwrap();
+        let mut changes = Vec::new();
+        for market in MARKETS {
+            let current = phase(market, now);
+            match phases.insert(market, current) {
+                Some(previous) if previous != current => changes.push(PhaseChange {
+                    market,
+                    phase: current,
+                    previous,
+                    at: now,
+                }),
+                _ => {}
+            }
+        }
+        self.changes
+            .fetch_add(changes.len() as u64, Ordering::Relaxed);
+        changes
+    }
+}
+
+/// Follow the markets' phases for as long as the app runs
+pub fn start_market_clock(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let changes = handle
+                .state::<MarketClock>()
+                .advance(Utc::now().timestamp());
+            for change in changes {
+                info!(
+                    "{:?} market moved from {:?} to {:?}",
+                    change.market, change.previous, change.phase
+                );
+                if let Err(e) = handle.emit("market-phase-changed", change) {
+                    warn!("Failed to emit market-phase-changed event: {}", e);
+                }
+            }
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+/// Current phase of every market
+#[tauri::command]
+pub fn get_market_phases(clock: State<'_, MarketClock>) -> Result<Vec<MarketStatus>, String> {
+    let now = Utc::now().timestamp();
+    Ok(MARKETS
+        .into_iter()
+        .map(|market| MarketStatus {
+            market,
+            phase: clock.phase(market),
+            early_close: calendar::early_close(market, sessions::trading_day(market, now))
+                .map(|minute| format!("{:02}:{:02}", minute / 60, minute % 60)),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(market: Market, (y, m, d): (i32, u32, u32), h: u32, min: u32) -> i64 {
+        sessions::timezone(market)
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn test_phases_and_changes() {
+        let monday = (2024, 7, 1);
+        let cn = |h, m| phase(Market::Cn, at(Market::Cn, monday, h, m));
+        assert_eq!(cn(9, 14), MarketPhase::Closed);
+        assert_eq!(cn(9, 15), MarketPhase::PreOpen);
+        assert_eq!(cn(9, 30), MarketPhase::Open);
+        assert_eq!(cn(11, 30), MarketPhase::LunchBreak);
+        assert_eq!(cn(13, 0), MarketPhase::Open);
+        assert_eq!(cn(15, 0), MarketPhase::Closed);
+        // Hong Kong is shut for HKSAR Establishment Day
+        assert_eq!(
+            phase(Market::Hk, at(Market::Hk, monday, 10, 0)),
+            MarketPhase::Closed
+        );
+        // Christmas Eve: Hong Kong trades the morning only, New York until 13:00
+        let eve = (2024, 12, 24);
+        assert_eq!(
+            phase(Market::Hk, at(Market::Hk, eve, 11, 0)),
+            MarketPhase::Open
+        );
+        assert_eq!(
+            phase(Market::Hk, at(Market::Hk, eve, 13, 30)),
+            MarketPhase::Closed
+        );
+        assert_eq!(
+            phase(Market::Us, at(Market::Us, eve, 12, 59)),
+            MarketPhase::Open
+        );
+        assert_eq!(
+            phase(Market::Us, at(Market::Us, eve, 13, 0)),
+            MarketPhase::Closed
+        );
+
+        let clock = MarketClock::default();
+        assert!(clock.advance(at(Market::Cn, monday, 9, 20)).is_empty());
+        let changes = clock.advance(at(Market::Cn, monday, 9, 30));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].market, Market::Cn);
+        assert_eq!(changes[0].previous, MarketPhase::PreOpen);
+        assert_eq!(clock.phase(Market::Cn), MarketPhase::Open);
+        assert_eq!(clock.changes(), 1);
+    }
+}
@@ -0,0 +1,425 @@
+//! macOS menu bar extra with mini quotes.
+//!
+//! A status item in the menu bar cycles through the top watchlist one quote
+//! at a time (`贵州茅台 1688.00 +0.48%`), switching every
+//! [`MenuBarSettings::rotate_secs`]. Its dropdown lists the watchlist with
+//! prices, the CFFEX stock index futures and a few quick actions, and is
+//! rebuilt every [`REFRESH`]. The watchlist is the one picked in settings or
+//! else the first.
+//!
+//! The status item lives apart from the dock icon and the main window: while
+//! it is on, closing the main window hides it instead of quitting, and the
+//! dock icon can be hidden so the app runs from the menu bar alone. The
+//! watchlist's symbols are held at quote level under [`OWNER`] so the quote
+//! poller keeps them fresh. Housekeeping drops subscriptions whose owner is no
+//! open window, so they are taken again whenever they go missing.
+//!
+//! Other platforms have no menu bar extra; its settings are kept but unused.
+
+// Only the macOS status item drives the quote and watchlist helpers
+#![cfg_attr(not(target_os = "macos"), allow(dead_code))]
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::db::{Database, Watchlist, Watchlists};
+use crate::politeness::PolicyEngine;
+use crate::quotes::{self, Quote, QuoteFeed};
+use crate::settings::SettingsStore;
+use crate::subscriptions::{self, SubscriptionLevel, SubscriptionRegistry};
+
+/// Subscription owner of the menu bar's symbols
+pub const OWNER: &str = "menu-bar";
+
+/// Time between rebuilds of the dropdown and refreshes of the futures
+pub const REFRESH: Duration = Duration::from_secs(30);
+
+/// Sina codes of the main continuous contracts and their labels
+const FUTURES: &[(&str, &str)] = &[
+    ("nf_IF0", "沪深300期货"),
+    ("nf_IH0", "上证50期货"),
+    ("nf_IC0", "中证500期货"),
+    ("nf_IM0", "中证1000期货"),
+];
+
+const FUTURES_URL: &str = "https://hq.sinajs.cn/list=";
+
+/// Menu bar text until a quote arrives
+const APP_TITLE: &str = "智股通";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MenuBarSettings {
+    pub enabled: bool,
+    /// Watchlist shown; the first watchlist when unset
+    pub watchlist_id: Option<String>,
+    /// Seconds each quote stays in the menu bar
+    pub rotate_secs: u64,
+    /// Watchlist symbols listed in the dropdown
+    pub max_items: usize,
+    /// Run from the menu bar alone, without a dock icon
+    pub hide_dock_icon: bool,
+}
+
+impl Default for MenuBarSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            watchlist_id: None,
+            rotate_secs: 5,
+            max_items: 8,
+            hide_dock_icon: false,
+        }
+    }
+}
+
+impl MenuBarSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(2..=60).contains(&self.rotate_secs) {
+            return Err(format!(
+                "Menu bar rotation must be between 2 and 60 seconds, got {}",
+                self.rotate_secs
+            ));
+        }
+        if !(1..=20).contains(&self.max_items) {
+            return Err(format!(
+                "Menu bar must list between 1 and 20 symbols, got {}",
+                self.max_items
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A quote as shown in the menu bar
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiniQuote {
+    pub label: String,
+    pub price: f64,
+    pub change_pct: f64,
+}
+
+impl MiniQuote {
+    fn from_quote(quote: &Quote) -> Self {
+        let label = if quote.name.is_empty() {
+            quote.symbol.clone()
+        } else {
+            quote.name.clone()
+        };
+        Self {
+            label,
+            price: quote.price,
+            change_pct: quote.change_pct,
+        }
+    }
+
+    /// `贵州茅台 1688.00 +0.48%`
+    pub fn line(&self) -> String {
+        format!("{} {:.2} {:+.2}%", self.label, self.price, self.change_pct)
+    }
+}
+
+/// `var hq_str_nf_IF0="open,high,low,price,volume,amount,open interest,…,prev settlement(14),…";`
+fn parse_sina_futures(body: &str) -> Vec<MiniQuote> {
+    let records = quotes::assignments(body, "var hq_str_");
+    FUTURES
+        .iter()
+        .filter_map(|(code, label)| {
+            let (_, value) = records
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(code))?;
+            let fields: Vec<&str> = value.split(',').collect();
+            let number = |i: usize| fields.get(i)?.parse::<f64>().ok();
+            let (price, settle) = (number(3)?, number(14)?);
+            if price <= 0.0 || settle <= 0.0 {
+                return None;
+            }
+            Some(MiniQuote {
+                label: label.to_string(),
+                price,
+                change_pct: (price / settle - 1.0) * 100.0,
+            })
+        })
+        .collect()
+}
+
+async fn fetch_futures(app: &AppHandle) -> Result<Vec<MiniQuote>, String> {
+    let codes: Vec<&str> = FUTURES.iter().map(|(code, _)| *code).collect();
+    let url = format!("{}{}", FUTURES_URL, codes.join(","));
+    let body = app
+        .state::<PolicyEngine>()
+        .get("sina", &url)
+        .await?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read index futures: {}", e))?;
+    Ok(parse_sina_futures(&body))
+}
+
+/// The watchlist the menu bar shows
+fn top_watchlist(app: &AppHandle, settings: &MenuBarSettings) -> Result<Option<Watchlist>, String> {
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    let lists = Watchlists(&conn).list()?;
+    let picked = settings
+        .watchlist_id
+        .as_ref()
+        .and_then(|id| lists.iter().find(|list| &list.id == id));
+    Ok(picked.or(lists.first()).cloned())
+}
+
+/// First `max` symbols of the watchlist
+fn shown_symbols(watchlist: Option<&Watchlist>, max: usize) -> Vec<String> {
+    watchlist
+        .map(|list| {
+            list.items
+                .iter()
+                .take(max)
+                .map(|item| item.symbol.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Hold exactly `symbols` at quote level, taking them again if housekeeping
+/// released them
+fn hold(app: &AppHandle, symbols: &[String]) {
+    let changes =
+        app.state::<SubscriptionRegistry>()
+            .hold(OWNER, symbols, SubscriptionLevel::Quote);
+    subscriptions::emit_changes(app, changes);
+}
+
+/// Each symbol with its menu bar quote, once one has been polled
+fn watchlist_quotes(app: &AppHandle, symbols: &[String]) -> Vec<(String, Option<MiniQuote>)> {
+    let feed = app.state::<QuoteFeed>();
+    symbols
+        .iter()
+        .map(|symbol| {
+            let quote = feed.get(symbol).map(|quote| MiniQuote::from_quote(&quote));
+            (symbol.clone(), quote)
+        })
+        .collect()
+}
+
+/// Menu bar text at the `tick`-th rotation step: one quote at a time, the
+/// app name until a quote has arrived
+fn rotating_title(quotes: &[MiniQuote], tick: usize) -> String {
+    match quotes.len() {
+        0 => APP_TITLE.to_string(),
+        len => quotes[tick % len].line(),
+    }
+}
+
+/// Whether closing the main window should only hide it
+pub fn keeps_running(app: &AppHandle) -> bool {
+    cfg!(target_os = "macos") && app.state::<SettingsStore>().get().menu_bar.enabled
+}
+
+#[cfg(target_os = "macos")]
+pub use macos::{apply_dock_policy, start_menu_bar};
+
+/// The menu bar extra is macOS only
+#[cfg(not(target_os = "macos"))]
+pub fn start_menu_bar(_app: &AppHandle) {}
+
+#[cfg(not(target_os = "macos"))]
+pub fn apply_dock_policy(_app: &mut tauri::App) {}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::time::Instant;
+
+    use log::warn;
+    use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tauri::tray::TrayIconBuilder;
+    use tauri::{Emitter, Wry};
+
+    use super::*;
+    use crate::notifications;
+
+    const TRAY_ID: &str = "menu-bar";
+    const SHOW: &str = "show";
+    const QUIT: &str = "quit";
+    const SYMBOL_PREFIX: &str = "symbol:";
+    /// Quick actions carried out by the frontend, as `(id, label)`
+    const FRONTEND_ACTIONS: &[(&str, &str)] =
+        &[("search", "搜索股票…"), ("notifications", "通知中心")];
+
+    /// Put up the menu bar extra and keep it current
+    pub fn start_menu_bar(app: &AppHandle) {
+        if !app.state::<SettingsStore>().get().menu_bar.enabled {
+            return;
+        }
+        if let Err(e) = create(app) {
+            warn!("Failed to create the menu bar extra: {}", e);
+            return;
+        }
+        let handle = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut futures = Vec::new();
+            let mut refreshed: Option<Instant> = None;
+            let mut tick = 0usize;
+            loop {
+                let settings = handle.state::<SettingsStore>().get().menu_bar;
+                let watchlist = top_watchlist(&handle, &settings).unwrap_or_else(|e| {
+                    warn!("Failed to read the menu bar watchlist: {}", e);
+                    None
+                });
+                let symbols = shown_symbols(watchlist.as_ref(), settings.max_items);
+                hold(&handle, &symbols);
+                let rows = watchlist_quotes(&handle, &symbols);
+                if refreshed.map_or(true, |at| at.elapsed() >= REFRESH) {
+                    match fetch_futures(&handle).await {
+                        Ok(latest) => futures = latest,
+                        Err(e) => warn!("Failed to refresh index futures: {}", e),
+                    }
+                    let name = watchlist
+                        .as_ref()
+                        .map_or("自选股", |list| list.name.as_str());
+                    set_menu(&handle, name, &rows, &futures);
+                    refreshed = Some(Instant::now());
+                }
+                let quotes: Vec<MiniQuote> =
+                    rows.into_iter().filter_map(|(_, quote)| quote).collect();
+                set_title(&handle, &rotating_title(&quotes, tick));
+                tick = tick.wrapping_add(1);
+                tokio::time::sleep(Duration::from_secs(settings.rotate_secs)).await;
+            }
+        });
+    }
+
+    /// Hide the dock icon when the app should run from the menu bar alone
+    pub fn apply_dock_policy(app: &mut tauri::App) {
+        let settings = app.state::<SettingsStore>().get().menu_bar;
+        if settings.enabled && settings.hide_dock_icon {
+            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+        }
+    }
+
+    fn create(app: &AppHandle) -> tauri::Result<()> {
+        let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+            .title(APP_TITLE)
+            .menu_on_left_click(true)
+            .on_menu_event(on_menu_event);
+        if let Some(icon) = app.default_window_icon() {
+            builder = builder.icon(icon.clone()).icon_as_template(true);
+        }
+        builder.build(app)?;
+        Ok(())
+    }
+
+    fn set_title(app: &AppHandle, title: &str) {
+        let Some(tray) = app.tray_by_id(TRAY_ID) else {
+            return;
+        };
+        if let Err(e) = tray.set_title(Some(title)) {
+            warn!("Failed to update the menu bar title: {}", e);
+        }
+    }
+
+    fn set_menu(
+        app: &AppHandle,
+        watchlist: &str,
+        rows: &[(String, Option<MiniQuote>)],
+        futures: &[MiniQuote],
+    ) {
+        let Some(tray) = app.tray_by_id(TRAY_ID) else {
+            return;
+        };
+        let menu = build_menu(app, watchlist, rows, futures);
+        if let Err(e) = menu.and_then(|menu| tray.set_menu(Some(menu))) {
+            warn!("Failed to update the menu bar menu: {}", e);
+        }
+    }
+
+    fn build_menu(
+        app: &AppHandle,
+        watchlist: &str,
+        rows: &[(String, Option<MiniQuote>)],
+        futures: &[MiniQuote],
+    ) -> tauri::Result<Menu<Wry>> {
+        let menu = Menu::new(app)?;
+        let label = |text: &str| MenuItem::new(app, text, false, None::<&str>);
+        let action = |id: &str, text: &str| MenuItem::with_id(app, id, text, true, None::<&str>);
+        menu.append(&label(watchlist)?)?;
+        for (symbol, quote) in rows {
+            let text = quote
+                .as_ref()
+                .map_or_else(|| symbol.clone(), MiniQuote::line);
+            menu.append(&action(&format!("{}{}", SYMBOL_PREFIX, symbol), &text)?)?;
+        }
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        menu.append(&label("股指期货")?)?;
+        for quote in futures {
+            menu.append(&label(&quote.line())?)?;
+        }
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        menu.append(&action(SHOW, "打开智股通")?)?;
+        for (id, text) in FRONTEND_ACTIONS {
+            menu.append(&action(id, text)?)?;
+        }
+        menu.append(&action(QUIT, "退出")?)?;
+        Ok(menu)
+    }
+
+    fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+        let id = event.id().as_ref();
+        match id {
+            SHOW => notifications::focus_main_window(app),
+            QUIT => app.exit(0),
+            _ => {
+                if let Some(symbol) = id.strip_prefix(SYMBOL_PREFIX) {
+                    notifications::open_chart(app, symbol);
+                } else if FRONTEND_ACTIONS.iter().any(|(action, _)| *action == id) {
+                    notifications::focus_main_window(app);
+                    if let Err(e) = app.emit("menu-bar-action", id) {
+                        warn!("Failed to emit menu-bar-action event: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_futures_and_rotation() {
+        let body = "var hq_str_nf_IF0=\"3871.800,3883.000,3847.000,3869.600,68254,79236934.180,145325.000,3869.600,0.000,4261.200,3486.600,0.000,0.000,3873.600,3850.000,150839.000\";\n\
+var hq_str_nf_IM0=\"\";\n";
+        let futures = parse_sina_futures(body);
+        assert_eq!(futures.len(), 1);
+        assert_eq!(futures[0].label, "沪深300期货");
+        // Change against the previous settlement
+        assert_eq!(futures[0].line(), "沪深300期货 3869.60 +0.51%");
+
+        let quotes = [
+            MiniQuote {
+                label: "贵州茅台".to_string(),
+                price: 1688.0,
+                change_pct: 0.476,
+            },
+            MiniQuote {
+                label: "平安银行".to_string(),
+                price: 11.3,
+                change_pct: -1.2,
+            },
+        ];
+        assert_eq!(rotating_title(&quotes, 0), "贵州茅台 1688.00 +0.48%");
+        assert_eq!(rotating_title(&quotes, 3), "平安银行 11.30 -1.20%");
+        assert_eq!(rotating_title(&[], 3), APP_TITLE);
+
+        assert!(MenuBarSettings::default().validate().is_ok());
+        assert!(MenuBarSettings {
+            rotate_secs: 0,
+            ..MenuBarSettings::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Merging user data from another machine or an imported profile.
+//!
+//! Records carry ULID-based ids, so the same record has the same id
+//! everywhere and two independently created records never collide. Merging
+//! matches incoming records to local ones by id and resolves differences
+//! with a fixed, deterministic policy that picks the same winner whichever
+//! side is merged into which:
+//!
+//! - a record only one side has is added, unless the other side holds a
+//!   record with identical content under another id (data copied between
+//!   machines before ids were shared), which is reported as a duplicate;
+//! - when both sides changed a record, the later modification wins, and
+//!   equal timestamps fall back to comparing the serialized records;
+//! - a deletion wins over a modification made before it, and loses to one
+//!   made after it.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::undo::Trashed;
+
+/// A user record that can be merged across machines
+pub trait Mergeable: Clone + PartialEq + Serialize {
+    /// Entity kind, as in audit trail entities
+    const KIND: &'static str;
+
+    fn id(&self) -> &str;
+    /// Last modification, as a `get_timestamp` string
+    fn modified_at(&self) -> &str;
+    /// Content identity ignoring ids and timestamps, for spotting duplicates
+    fn fingerprint(&self) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeSide {
+    Local,
+    Incoming,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergeConflict {
+    pub entity: String,
+    pub kept: MergeSide,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergeCounts {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+    pub duplicates: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// Counts per entity kind
+    pub counts: BTreeMap<String, MergeCounts>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// A change merging made to the local records, for the audit trail
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeChange<T> {
+    Added(T),
+    Replaced { before: T, after: T },
+    Deleted(T),
+}
+
+/// Whether `a` wins over `b`: later modification first, then the larger serialization
+fn newer<T: Mergeable>(a: &T, b: &T) -> bool {
+    match a.modified_at().cmp(b.modified_at()) {
+        std::cmp::Ordering::Equal => {
+            serde_json::to_string(a).unwrap_or_default()
+                > serde_json::to_string(b).unwrap_or_default()
+        }
+        ordering => ordering.is_gt(),
+    }
+}
+
+/// Merge incoming records and deletions into the local ones
+pub fn merge<T: Mergeable>(
+    local: &mut Vec<T>,
+    local_trash: &mut Vec<Trashed<T>>,
+    incoming: Vec<T>,
+    incoming_trash: Vec<Trashed<T>>,
+    report: &mut MergeReport,
+) -> Vec<MergeChange<T>> {
+    let mut changes = Vec::new();
+    let counts = report.counts.entry(T::KIND.to_string()).or_default();
+    let entity = |id: &str| format!("{}:{}", T::KIND, id);
+
+    for item in incoming {
+        if let Some(deleted) = local_trash.iter().find(|t| t.item.id() == item.id()) {
+            if deleted.deleted_at.as_str() >= item.modified_at() {
+                counts.unchanged += 1;
+                report.conflicts.push(MergeConflict {
+                    entity: entity(item.id()),
+                    kept: MergeSide::Local,
+                    reason: format!("Deleted here at {}", deleted.deleted_at),
+                });
+                continue;
+            }
+        }
+        if let Some(existing) = local.iter_mut().find(|l| l.id() == item.id()) {
+            if *existing == item {
+                counts.unchanged += 1;
+            } else if newer(&item, existing) {
+                counts.updated += 1;
+                report.conflicts.push(MergeConflict {
+                    entity: entity(item.id()),
+                    kept: MergeSide::Incoming,
+                    reason: format!("Modified there at {}", item.modified_at()),
+                });
+                let before = std::mem::replace(existing, item.clone());
+                changes.push(MergeChange::Replaced {
+                    before,
+                    after: item,
+                });
+            } else {
+                counts.unchanged += 1;
+                report.conflicts.push(MergeConflict {
+                    entity: entity(item.id()),
+                    kept: MergeSide::Local,
+                    reason: format!("Modified here at {}", existing.modified_at()),
+                });
+            }
+        } else if local.iter().any(|l| l.fingerprint() == item.fingerprint()) {
+            counts.duplicates += 1;
+        } else {
+            counts.added += 1;
+            local_trash.retain(|t| t.item.id() != item.id());
+            local.push(item.clone());
+            changes.push(MergeChange::Added(item));
+        }
+    }
+
+    for deleted in incoming_trash {
+        let Some(index) = local.iter().position(|l| l.id() == deleted.item.id()) else {
+            continue;
+        };
+        if deleted.deleted_at.as_str() >= local[index].modified_at() {
+            counts.deleted += 1;
+            let item = local.remove(index);
+            local_trash.push(Trashed {
+                item: item.clone(),
+                deleted_at: deleted.deleted_at,
+            });
+            changes.push(MergeChange::Deleted(item));
+        } else {
+            report.conflicts.push(MergeConflict {
+                entity: entity(deleted.item.id()),
+                kept: MergeSide::Local,
+                reason: format!(
+                    "Modified here at {}, after it was deleted there",
+                    local[index].modified_at()
+                ),
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    struct Note {
+        id: String,
+        text: String,
+        at: String,
+    }
+
+    impl Mergeable for Note {
+        const KIND: &'static str = "note";
+        fn id(&self) -> &str {
+            &self.id
+        }
+        fn modified_at(&self) -> &str {
+            &self.at
+        }
+        fn fingerprint(&self) -> String {
+            self.text.clone()
+        }
+    }
+
+    fn note(id: &str, text: &str, at: &str) -> Note {
+        Note {
+            id: id.to_string(),
+            text: text.to_string(),
+            at: format!("2026-03-0{} 00:00:00 UTC", at),
+        }
+    }
+
+    #[test]
+    fn test_merge_policy() {
+        let a = vec![
+            note("1", "kept", "1"),
+            note("2", "old", "1"),
+            note("4", "same", "1"),
+        ];
+        let a_trash = vec![Trashed {
+            item: note("5", "gone", "1"),
+            deleted_at: "2026-03-03 00:00:00 UTC".to_string(),
+        }];
+        let b = vec![
+            note("2", "new", "2"),
+            note("3", "fresh", "1"),
+            note("5", "gone", "1"),
+            note("6", "same", "2"),
+        ];
+
+        let mut local = a.clone();
+        let mut trash = a_trash.clone();
+        let mut report = MergeReport::default();
+        let changes = merge(&mut local, &mut trash, b.clone(), Vec::new(), &mut report);
+        let counts = &report.counts["note"];
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (1, 1, 1));
+        assert_eq!(changes.len(), 2);
+        let texts: Vec<&str> = local.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(texts, vec!["kept", "new", "same", "fresh"]);
+
+        // The other direction picks the same winners and applies the deletion
+        let mut other = b;
+        let mut other_trash = Vec::new();
+        merge(
+            &mut other,
+            &mut other_trash,
+            a,
+            a_trash,
+            &mut MergeReport::default(),
+        );
+        let mut ids: Vec<&str> = other.iter().map(|n| n.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["1", "2", "3", "6"]);
+        assert_eq!(other_trash.len(), 1);
+        assert_eq!(other.iter().find(|n| n.id == "2").unwrap().text, "new");
+    }
+}
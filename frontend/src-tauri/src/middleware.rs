@@ -0,0 +1,37 @@
+//! Checks every command passes before it reaches its handler.
+//!
+//! The generated invoke handler is wrapped so that guest sessions and
+//! compliance policies are enforced in one place, for every command, without
+//! each handler having to remember to check.
+
+use log::warn;
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime};
+
+use crate::compliance::CompliancePolicy;
+use crate::guest::GuestMode;
+
+/// Wrap the invoke handler with the guest and compliance checks
+pub fn guard<R: Runtime, F>(handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| {
+        let command = invoke.message.command().to_string();
+        let webview = invoke.message.webview();
+        let admitted = webview
+            .try_state::<CompliancePolicy>()
+            .map_or(Ok(()), |policy| policy.admit(&command))
+            .and_then(|_| {
+                webview
+                    .try_state::<GuestMode>()
+                    .map_or(Ok(()), |guest| guest.admit(&command))
+            });
+        if let Err(e) = admitted {
+            warn!("Rejected command {}: {}", command, e);
+            invoke.resolver.reject(e);
+            return true;
+        }
+        handler(invoke)
+    }
+}
@@ -0,0 +1,93 @@
+//! Shared market data models
+
+use serde::{Deserialize, Serialize};
+
+/// A single OHLCV bar
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bar {
+    /// Bar open time as a Unix timestamp in seconds
+    pub timestamp: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+/// Market a symbol trades on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Market {
+    /// Shanghai, Shenzhen and Beijing A-shares
+    Cn,
+    Hk,
+    Us,
+}
+
+/// Currency prices are quoted in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Cny,
+    Hkd,
+    Usd,
+}
+
+impl Market {
+    pub fn currency(self) -> Currency {
+        match self {
+            Market::Cn => Currency::Cny,
+            Market::Hk => Currency::Hkd,
+            Market::Us => Currency::Usd,
+        }
+    }
+
+    /// Market of a symbol in its canonical form: `SH600519`, `HK00700` or a
+    /// US ticker such as `AAPL` or `BRK.B`
+    pub fn of(symbol: &str) -> Option<Market> {
+        let digits =
+            |code: &str, len: usize| code.len() == len && code.chars().all(|c| c.is_ascii_digit());
+        if ["SH", "SZ", "BJ"].iter().any(|prefix| {
+            symbol
+                .strip_prefix(prefix)
+                .is_some_and(|code| digits(code, 6))
+        }) {
+            return Some(Market::Cn);
+        }
+        if symbol
+            .strip_prefix("HK")
+            .is_some_and(|code| digits(code, 5))
+        {
+            return Some(Market::Hk);
+        }
+        is_us_ticker(symbol).then_some(Market::Us)
+    }
+}
+
+/// One to five letters, optionally with a one-letter share class: `BRK.B`
+fn is_us_ticker(symbol: &str) -> bool {
+    let (root, class) = symbol.split_once('.').unwrap_or((symbol, ""));
+    (1..=5).contains(&root.len())
+        && root.chars().all(|c| c.is_ascii_uppercase())
+        && (class.is_empty() || (class.len() == 1 && class.chars().all(|c| c.is_ascii_uppercase())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_market_of_symbols() {
+        assert_eq!(Market::of("SH600519"), Some(Market::Cn));
+        assert_eq!(Market::of("HK00700"), Some(Market::Hk));
+        // Tickers that happen to start like an exchange prefix
+        assert_eq!(Market::of("SHOP"), Some(Market::Us));
+        assert_eq!(Market::of("HK"), Some(Market::Us));
+        assert_eq!(Market::Hk.currency(), Currency::Hkd);
+        assert_eq!(
+            serde_json::to_string(&Market::Us.currency()).unwrap(),
+            r#""USD""#
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! Live strategy monitoring.
+//!
+//! A monitored strategy is re-evaluated whenever new bars arrive in the
+//! store, checked at the bar polling interval. Entry and exit signals are
+//! never traded; they are emitted to the frontend as `strategy-signal` alerts
+//! and appended to a journal, each with the conditions behind it (see
+//! [`explain`](crate::explain)), while each monitor tracks the hypothetical
+//! return of following its signals at the signal bar's close. Monitors
+//! persist and resume at startup.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::columnar::ColumnarStore;
+use crate::costs::Side;
+use crate::db::{Database, Explanations};
+use crate::explain::{Condition, Explanation, TriggerKind};
+use crate::models::Bar;
+use crate::notifications::{self, Notification, NotificationCategory};
+use crate::polling::{jittered, DataClass};
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::strategy::{StrategySpec, StrategyStore};
+use crate::tasks::CancellationToken;
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+/// Bars handed to the strategy on each evaluation
+const LOOKBACK_BARS: usize = 1000;
+
+/// Targets at or above this fraction count as being in the market
+const LONG_THRESHOLD: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignalEvent {
+    /// Key of the signal's explanation; empty for signals journaled before ids existed
+    #[serde(default)]
+    pub id: String,
+    pub monitor_id: String,
+    pub strategy_id: String,
+    pub symbol: String,
+    /// Timestamp of the bar that produced the signal
+    pub timestamp: i64,
+    pub side: Side,
+    pub price: f64,
+    pub recorded_at: String,
+    /// What the strategy checked at the signal bar
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+impl SignalEvent {
+    fn explanation(&self) -> Explanation {
+        Explanation {
+            event_id: self.id.clone(),
+            kind: TriggerKind::StrategySignal,
+            source_id: self.strategy_id.clone(),
+            symbol: self.symbol.clone(),
+            outcome: match self.side {
+                Side::Buy => "buy",
+                Side::Sell => "sell",
+            }
+            .to_string(),
+            conditions: self.conditions.clone(),
+            as_of: self.timestamp,
+            created_at: self.recorded_at.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorState {
+    pub id: String,
+    pub strategy_id: String,
+    pub symbol: String,
+    pub interval: String,
+    spec: StrategySpec,
+    /// Last bar already evaluated; `None` until the first evaluation
+    last_evaluated: Option<i64>,
+    /// Entry price of the hypothetical open position
+    entry_price: Option<f64>,
+    /// Compounded return of closed hypothetical trades
+    realized_return: f64,
+    signals: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorSummary {
+    #[serde(flatten)]
+    state: MonitorState,
+    /// Realized return compounded with the open position marked at the last close
+    total_return: f64,
+    last_price: Option<f64>,
+}
+
+impl MonitorState {
+    /// Evaluate bars newer than the last evaluation and return the signals they produce.
+    ///
+    /// The first evaluation only establishes a baseline, so starting a monitor
+    /// never replays historical signals.
+    pub fn evaluate(&mut self, bars: &[Bar], now: &str) -> Result<Vec<SignalEvent>, String> {
+        let Some(last_bar) = bars.last() else {
+            return Ok(Vec::new());
+        };
+        let mut strategy = self.spec.build()?;
+        strategy.prepare(bars);
+        let Some(last_evaluated) = self.last_evaluated else {
+            // A position the strategy already holds at the baseline is
+            // joined at its close without a signal
+            if strategy
+                .target(bars.len() - 1)
+                .is_some_and(|target| target >= LONG_THRESHOLD)
+            {
+                self.entry_price = Some(last_bar.close);
+            }
+            self.last_evaluated = Some(last_bar.timestamp);
+            return Ok(Vec::new());
+        };
+
+        let mut events = Vec::new();
+        for (i, bar) in bars.iter().enumerate() {
+            if bar.timestamp <= last_evaluated {
+                continue;
+            }
+            let Some(target) = strategy.target(i) else {
+                continue;
+            };
+            let side = match (target >= LONG_THRESHOLD, self.entry_price) {
+                (true, None) => {
+                    self.entry_price = Some(bar.close);
+                    Side::Buy
+                }
+                (false, Some(entry)) => {
+                    self.realized_return = (1.0 + self.realized_return) * (bar.close / entry) - 1.0;
+                    self.entry_price = None;
+                    Side::Sell
+                }
+                _ => continue,
+            };
+            self.signals += 1;
+            events.push(SignalEvent {
+                id: generate_id("signal"),
+                monitor_id: self.id.clone(),
+                strategy_id: self.strategy_id.clone(),
+                symbol: self.symbol.clone(),
+                timestamp: bar.timestamp,
+                side,
+                price: bar.close,
+                recorded_at: now.to_string(),
+                conditions: strategy.conditions(i),
+            });
+        }
+        self.last_evaluated = Some(last_bar.timestamp);
+        Ok(events)
+    }
+
+    fn summary(&self, last_price: Option<f64>) -> MonitorSummary {
+        let open = match (self.entry_price, last_price) {
+            (Some(entry), Some(price)) => price / entry,
+            _ => 1.0,
+        };
+        MonitorSummary {
+            state: self.clone(),
+            total_return: (1.0 + self.realized_return) * open - 1.0,
+            last_price,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct MonitorData {
+    monitors: Vec<MonitorState>,
+    journal: Vec<SignalEvent>,
+}
+
+pub struct StrategyMonitor {
+    path: PathBuf,
+    data: Mutex<MonitorData>,
+    tokens: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl StrategyMonitor {
+    pub fn load(path: PathBuf) -> Self {
+        let data = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            data: Mutex::new(data),
+            tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn save(&self, data: &MonitorData) -> Result<(), String> {
+        let content = serde_json::to_string(data)
+            .map_err(|e| format!("Failed to serialize strategy monitors: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save strategy monitors: {}", e))
+    }
+
+    fn get(&self, id: &str) -> Option<MonitorState> {
+        let data = self.data.lock().unwrap();
+        data.monitors.iter().find(|m| m.id == id).cloned()
+    }
+
+    /// Store an evaluated monitor state and journal its signals
+    fn update(&self, state: MonitorState, events: &[SignalEvent]) -> Result<(), String> {
+        let mut data = self.data.lock().unwrap();
+        match data.monitors.iter_mut().find(|m| m.id == state.id) {
+            Some(existing) => *existing = state,
+            // Stopped while being evaluated
+            None => return Ok(()),
+        }
+        data.journal.extend_from_slice(events);
+        self.save(&data)
+    }
+}
+
+fn poll_monitor(app: &AppHandle, id: &str) -> Result<(), String> {
+    if app.state::<SnapshotClock>().is_frozen() {
+        return Ok(());
+    }
+    let monitors = app.state::<StrategyMonitor>();
+    let Some(mut state) = monitors.get(id) else {
+        return Ok(());
+    };
+    let store = app.state::<ColumnarStore>();
+    if !store.exists(&state.symbol, &state.interval) {
+        return Ok(());
+    }
+    let mapped = store.open(&state.symbol, &state.interval)?;
+    let bars = mapped.to_bars(mapped.len().saturating_sub(LOOKBACK_BARS)..mapped.len());
+    if bars.last().map(|b| b.timestamp) == state.last_evaluated {
+        return Ok(());
+    }
+
+    let events = state.evaluate(&bars, &get_timestamp())?;
+    monitors.update(state, &events)?;
+    if !events.is_empty() {
+        let db = app.state::<Database>();
+        let conn = db.conn()?;
+        for event in &events {
+            Explanations(&conn).record(&event.explanation())?;
+        }
+    }
+    for event in events {
+        info!(
+            "Strategy {} signalled {:?} {} at {}",
+            event.strategy_id, event.side, event.symbol, event.price
+        );
+        let side = match event.side {
+            Side::Buy => "买入",
+            Side::Sell => "卖出",
+        };
+        let notification = Notification {
+            category: NotificationCategory::StrategySignal,
+            title: format!("{} {}信号", event.symbol, side),
+            body: format!(
+                "策略 {} 在 {:.2} 发出{}信号",
+                event.strategy_id, event.price, side
+            ),
+            actions: Vec::new(),
+        };
+        if let Err(e) = notifications::notify(app, notification) {
+            warn!("{}", e);
+        }
+        if let Err(e) = app.emit("strategy-signal", event) {
+            warn!("Failed to emit strategy-signal event: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn spawn_monitor(app: &AppHandle, id: String) {
+    let token = CancellationToken::default();
+    app.state::<StrategyMonitor>()
+        .tokens
+        .lock()
+        .unwrap()
+        .insert(id.clone(), token.clone());
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if token.is_cancelled() {
+                break;
+            }
+            if let Err(e) = poll_monitor(&handle, &id) {
+                warn!("Strategy monitor {} failed: {}", id, e);
+            }
+            let polling = handle.state::<SettingsStore>().get().polling;
+            let delay = jittered(polling.interval(DataClass::Bars, true), polling.jitter);
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
+
+/// Resume monitors saved by a previous session
+pub fn resume_monitors(app: &AppHandle) {
+    let ids: Vec<String> = {
+        let monitors = app.state::<StrategyMonitor>();
+        let data = monitors.data.lock().unwrap();
+        data.monitors.iter().map(|m| m.id.clone()).collect()
+    };
+    for id in ids {
+        spawn_monitor(app, id);
+    }
+}
+
+/// Start monitoring a saved strategy on a symbol
+#[tauri::command]
+pub fn start_strategy_monitor(
+    app: AppHandle,
+    strategies: State<'_, StrategyStore>,
+    monitors: State<'_, StrategyMonitor>,
+    strategy_id: String,
+    symbol: String,
+    interval: String,
+) -> Result<MonitorSummary, String> {
+    let strategy = strategies
+        .get(&strategy_id)
+        .ok_or_else(|| format!("Strategy not found: {}", strategy_id))?;
+    let state = MonitorState {
+        id: generate_id("monitor"),
+        strategy_id,
+        symbol: symbol.to_uppercase(),
+        interval,
+        spec: strategy.spec,
+        last_evaluated: None,
+        entry_price: None,
+        realized_return: 0.0,
+        signals: 0,
+    };
+    {
+        let mut data = monitors.data.lock().unwrap();
+        data.monitors.push(state.clone());
+        monitors.save(&data)?;
+    }
+    info!(
+        "Monitoring strategy {} on {}",
+        state.strategy_id, state.symbol
+    );
+    spawn_monitor(&app, state.id.clone());
+    Ok(state.summary(None))
+}
+
+/// Stop a strategy monitor; its journal entries are kept
+#[tauri::command]
+pub fn stop_strategy_monitor(
+    monitors: State<'_, StrategyMonitor>,
+    monitor_id: String,
+) -> Result<(), String> {
+    if let Some(token) = monitors.tokens.lock().unwrap().remove(&monitor_id) {
+        token.cancel();
+    }
+    let mut data = monitors.data.lock().unwrap();
+    let before = data.monitors.len();
+    data.monitors.retain(|m| m.id != monitor_id);
+    if data.monitors.len() == before {
+        return Err(format!("Strategy monitor not found: {}", monitor_id));
+    }
+    monitors.save(&data)
+}
+
+/// Active monitors with the hypothetical performance of following their signals
+#[tauri::command]
+pub fn list_strategy_monitors(
+    monitors: State<'_, StrategyMonitor>,
+    store: State<'_, ColumnarStore>,
+) -> Result<Vec<MonitorSummary>, String> {
+    let states = monitors.data.lock().unwrap().monitors.clone();
+    Ok(states
+        .iter()
+        .map(|state| {
+            let last_price = store
+                .open(&state.symbol, &state.interval)
+                .ok()
+                .filter(|bars| !bars.is_empty())
+                .map(|bars| bars.bar(bars.len() - 1).close);
+            state.summary(last_price)
+        })
+        .collect())
+}
+
+/// Journaled signals, newest first, optionally for one strategy
+#[tauri::command]
+pub fn get_signal_journal(
+    monitors: State<'_, StrategyMonitor>,
+    strategy_id: Option<String>,
+) -> Result<Vec<SignalEvent>, String> {
+    let data = monitors.data.lock().unwrap();
+    Ok(data
+        .journal
+        .iter()
+        .rev()
+        .filter(|e| strategy_id.as_ref().map_or(true, |id| &e.strategy_id == id))
+        .cloned()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(closes: &[f64]) -> Vec<Bar> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| Bar {
+                timestamp: i as i64 * 60,
+                open: close,
+                high: close,
+                low: close,
+                close,
+                volume: 100.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_signals_only_after_baseline() {
+        let mut state = MonitorState {
+            id: "monitor-1".to_string(),
+            strategy_id: "strategy-1".to_string(),
+            symbol: "SH600000".to_string(),
+            interval: "1m".to_string(),
+            spec: StrategySpec::MaCross { fast: 1, slow: 2 },
+            last_evaluated: None,
+            entry_price: None,
+            realized_return: 0.0,
+            signals: 0,
+        };
+        let closes = [10.0, 9.0, 8.0, 10.0, 12.0, 11.0, 9.0];
+
+        // A cross already in the history is not replayed, but the position
+        // it opened is held from the baseline close
+        assert!(state.evaluate(&bars(&closes[..4]), "t").unwrap().is_empty());
+        assert_eq!(state.entry_price, Some(10.0));
+
+        let events = state.evaluate(&bars(&closes), "t").unwrap();
+        let sides: Vec<(i64, Side)> = events.iter().map(|e| (e.timestamp, e.side)).collect();
+        assert_eq!(sides, vec![(300, Side::Sell)]);
+        // The sell failed SMA(1) > SMA(2) at 11 against 11.5
+        let exit = &events[0].conditions[0];
+        assert_eq!(
+            (exit.actual, exit.threshold, exit.passed),
+            (11.0, 11.5, false)
+        );
+        assert_eq!(events[0].explanation().outcome, "sell");
+        assert!((state.realized_return - (11.0 / 10.0 - 1.0)).abs() < 1e-12);
+        assert!(state.evaluate(&bars(&closes), "t").unwrap().is_empty());
+        assert_eq!(state.summary(Some(9.0)).total_return, state.realized_return);
+    }
+}
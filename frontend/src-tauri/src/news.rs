@@ -0,0 +1,409 @@
+//! News feed.
+//!
+//! Headlines are pulled from Eastmoney's 7x24 fast-news list, or the next
+//! news provider in the [`providers`] chain, on the news polling interval,
+//! deduplicated by provider id and kept in a bounded store, newest first.
+//! New items are pushed to the frontend with a `news-updated` event. Symbols
+//! open in a detail view (subscribed at [`SubscriptionLevel::Detail`]) also
+//! have their latest announcements polled on the same interval. In snapshot
+//! mode nothing is fetched and only items published by the snapshot time are
+//! served.
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Asia::Shanghai;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::drift::{DriftLog, Field, FieldKind};
+use crate::entity_linking::{self, AliasStore};
+use crate::faults::FaultInjector;
+use crate::fields::select;
+use crate::instruments::InstrumentMaster;
+use crate::models::Market;
+use crate::news_backfill;
+use crate::news_watch;
+use crate::politeness::PolicyEngine;
+use crate::polling::DataClass;
+use crate::presence;
+use crate::providers;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::subscriptions::{SubscriptionLevel, SubscriptionRegistry};
+use crate::symbols::symbol_key;
+use crate::utils::{get_timestamp, read_from_file, write_to_file};
+
+const PROVIDER: &str = "eastmoney";
+
+const FAST_NEWS_URL: &str = "https://np-weblist.eastmoney.com/comm/web/getFastNewsList?client=web&biz=web_724&fastColumn=102&sortEnd=&pageSize=50&req_trace=ssi";
+
+/// Items kept in the store
+const MAX_ITEMS: usize = 5000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewsItem {
+    pub id: String,
+    pub source: String,
+    pub title: String,
+    pub summary: String,
+    /// Full article page
+    pub url: String,
+    /// Unix seconds
+    pub published_at: i64,
+    pub fetched_at: String,
+    /// Ids of the news watches the item matched
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Instruments the item mentions, by ticker, name or alias
+    #[serde(default)]
+    pub symbols: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct FastNewsResponse {
+    data: FastNewsData,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FastNewsData {
+    fast_news_list: Vec<FastNewsRow>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FastNewsRow {
+    code: String,
+    title: String,
+    summary: String,
+    show_time: String,
+}
+
+const FAST_NEWS_SCHEMA: &[Field] = &[
+    Field {
+        path: "data.fastNewsList",
+        kind: FieldKind::Array,
+    },
+    Field {
+        path: "data.fastNewsList[].code",
+        kind: FieldKind::String,
+    },
+    Field {
+        path: "data.fastNewsList[].title",
+        kind: FieldKind::String,
+    },
+    Field {
+        path: "data.fastNewsList[].showTime",
+        kind: FieldKind::String,
+    },
+];
+
+/// Parse a Beijing-time `YYYY-MM-DD HH:MM:SS` stamp into unix seconds
+fn parse_show_time(value: &str) -> Option<i64> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok()?;
+    Shanghai
+        .from_local_datetime(&naive)
+        .single()
+        .map(|t| t.timestamp())
+}
+
+/// Whether an item was out at `until`, or at all if `None`
+fn published_by(item: &NewsItem, until: Option<i64>) -> bool {
+    until.map_or(true, |until| item.published_at <= until)
+}
+
+pub struct NewsStore {
+    path: PathBuf,
+    items: RwLock<Vec<NewsItem>>,
+}
+
+impl NewsStore {
+    pub fn load(path: PathBuf) -> Self {
+        let items = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            items: RwLock::new(items),
+        }
+    }
+
+    /// Add items not seen before, returning the new ones
+    pub fn insert(&self, incoming: Vec<NewsItem>) -> Result<Vec<NewsItem>, String> {
+        let mut items = self.items.write().unwrap();
+        let fresh: Vec<NewsItem> = incoming
+            .into_iter()
+            .filter(|item| !items.iter().any(|i| i.id == item.id))
+            .collect();
+        if fresh.is_empty() {
+            return Ok(fresh);
+        }
+        items.extend(fresh.iter().cloned());
+        items.sort_by_key(|i| std::cmp::Reverse(i.published_at));
+        items.truncate(MAX_ITEMS);
+        let content = serde_json::to_string(&*items)
+            .map_err(|e| format!("Failed to serialize news: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save news: {}", e))?;
+        Ok(fresh)
+    }
+
+    pub fn get(&self, id: &str) -> Option<NewsItem> {
+        self.items
+            .read()
+            .unwrap()
+            .iter()
+            .find(|i| i.id == id)
+            .cloned()
+    }
+
+    /// Attach tags to an item, keeping existing ones
+    pub fn add_tags(&self, id: &str, tags: Vec<String>) -> Result<(), String> {
+        let mut items = self.items.write().unwrap();
+        let Some(item) = items.iter_mut().find(|i| i.id == id) else {
+            return Err(format!("News item not found: {}", id));
+        };
+        for tag in tags {
+            if !item.tags.contains(&tag) {
+                item.tags.push(tag);
+            }
+        }
+        let content = serde_json::to_string(&*items)
+            .map_err(|e| format!("Failed to serialize news: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save news: {}", e))
+    }
+
+    /// Attach linked symbols to an item, keeping existing ones
+    pub fn add_symbols(&self, id: &str, symbols: Vec<String>) -> Result<(), String> {
+        let mut items = self.items.write().unwrap();
+        let Some(item) = items.iter_mut().find(|i| i.id == id) else {
+            return Err(format!("News item not found: {}", id));
+        };
+        let before = item.symbols.len();
+        for symbol in symbols {
+            if !item.symbols.contains(&symbol) {
+                item.symbols.push(symbol);
+            }
+        }
+        if item.symbols.len() == before {
+            return Ok(());
+        }
+        item.symbols.sort();
+        let content = serde_json::to_string(&*items)
+            .map_err(|e| format!("Failed to serialize news: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save news: {}", e))
+    }
+
+    /// Items linked to a symbol and published at or before `until` (any
+    /// time if `None`), newest first
+    pub fn for_symbol(&self, symbol: &str, until: Option<i64>, limit: usize) -> Vec<NewsItem> {
+        self.items
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|i| published_by(i, until) && i.symbols.iter().any(|s| s == symbol))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Items carrying a tag, newest first
+    pub fn tagged(&self, tag: &str) -> Vec<NewsItem> {
+        self.items
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|i| i.tags.iter().any(|t| t == tag))
+            .cloned()
+            .collect()
+    }
+
+    /// Items published at or before `until` (any time if `None`), newest first
+    pub fn latest(&self, until: Option<i64>, limit: usize) -> Vec<NewsItem> {
+        self.items
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|i| published_by(i, until))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Download the latest fast-news headlines
+pub async fn fetch_fast_news(app: &AppHandle) -> Result<Vec<NewsItem>, String> {
+    let policies = app.state::<PolicyEngine>();
+    let body = app
+        .state::<FaultInjector>()
+        .wrap(PROVIDER, async {
+            policies
+                .get(PROVIDER, FAST_NEWS_URL)
+                .await?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read news list: {}", e))
+        })
+        .await?;
+    let value = app.state::<DriftLog>().check_response(
+        app,
+        PROVIDER,
+        "fast news",
+        &body,
+        FAST_NEWS_SCHEMA,
+    )?;
+    let response: FastNewsResponse =
+        serde_json::from_value(value).map_err(|e| format!("Failed to parse news list: {}", e))?;
+
+    let fetched_at = get_timestamp();
+    Ok(response
+        .data
+        .fast_news_list
+        .into_iter()
+        .filter_map(|row| {
+            Some(NewsItem {
+                id: format!("{}-{}", PROVIDER, row.code),
+                source: PROVIDER.to_string(),
+                url: format!("https://finance.eastmoney.com/a/{}.html", row.code),
+                published_at: parse_show_time(&row.show_time)?,
+                title: row.title,
+                summary: row.summary,
+                fetched_at: fetched_at.clone(),
+                tags: Vec::new(),
+                symbols: Vec::new(),
+            })
+        })
+        .collect())
+}
+
+/// Latest announcements of the symbols open in a detail view
+async fn detail_news(app: &AppHandle) -> Vec<NewsItem> {
+    let symbols = app
+        .state::<SubscriptionRegistry>()
+        .symbols_at(SubscriptionLevel::Detail);
+    let mut items = Vec::new();
+    for symbol in symbols {
+        match news_backfill::latest_announcements(app, &symbol).await {
+            Ok(announcements) => items.extend(announcements),
+            Err(e) => warn!("Announcement refresh for {} failed: {}", symbol, e),
+        }
+    }
+    items
+}
+
+async fn refresh(app: &AppHandle) -> Result<Vec<NewsItem>, String> {
+    // Nothing is fetched while the data is frozen at a snapshot
+    if app.state::<SnapshotClock>().is_frozen() {
+        return Ok(Vec::new());
+    }
+    let mut items = providers::news(app).await?;
+    items.extend(detail_news(app).await);
+    let linker =
+        entity_linking::linker(&app.state::<InstrumentMaster>(), &app.state::<AliasStore>());
+    for item in &mut items {
+        let linked = linker.link(&format!("{}\n{}", item.title, item.summary));
+        for symbol in linked {
+            if !item.symbols.contains(&symbol) {
+                item.symbols.push(symbol);
+            }
+        }
+    }
+    let fresh = app.state::<NewsStore>().insert(items)?;
+    news_watch::process(app, &fresh);
+    if !fresh.is_empty() {
+        info!("Fetched {} new news items", fresh.len());
+        if let Err(e) = app.emit("news-updated", fresh.clone()) {
+            warn!("Failed to emit news-updated event: {}", e);
+        }
+    }
+    Ok(fresh)
+}
+
+/// Poll the news feed on the configured interval, stretched outside sessions
+pub fn start_news_polling(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if !presence::paused(&handle) {
+                if let Err(e) = refresh(&handle).await {
+                    warn!("News refresh failed: {}", e);
+                }
+            }
+            let polling = handle.state::<SettingsStore>().get().polling;
+            let delay = polling.next_delay(DataClass::News, Market::Cn, Utc::now().timestamp());
+            presence::sleep_or_resume(&handle, delay).await;
+        }
+    });
+}
+
+/// Most recent news items, newest first, optionally only those linked to a symbol
+#[tauri::command]
+pub fn get_news(
+    store: State<'_, NewsStore>,
+    clock: State<'_, SnapshotClock>,
+    symbol: Option<String>,
+    limit: Option<usize>,
+    fields: Option<Vec<String>>,
+) -> Result<Value, String> {
+    let limit = limit.unwrap_or(100);
+    let until = clock.frozen_at();
+    let items = match symbol {
+        Some(symbol) => store.for_symbol(&symbol_key(&symbol), until, limit),
+        None => store.latest(until, limit),
+    };
+    select(&items, fields.as_deref())
+}
+
+/// Fetch the news feed now, returning items not seen before
+#[tauri::command]
+pub async fn refresh_news(app: AppHandle) -> Result<Vec<NewsItem>, String> {
+    refresh(&app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, published_at: i64) -> NewsItem {
+        NewsItem {
+            id: id.to_string(),
+            source: PROVIDER.to_string(),
+            title: id.to_string(),
+            summary: String::new(),
+            url: String::new(),
+            published_at,
+            fetched_at: String::new(),
+            tags: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_insert_deduplicates_and_orders() {
+        let path = std::env::temp_dir().join(format!("ssi-news-{}.json", std::process::id()));
+        let store = NewsStore::load(path.clone());
+        let fresh = store.insert(vec![item("a", 100), item("b", 300)]).unwrap();
+        assert_eq!(fresh.len(), 2);
+        let fresh = store.insert(vec![item("b", 300), item("c", 200)]).unwrap();
+        assert_eq!(fresh, vec![item("c", 200)]);
+
+        let reloaded = NewsStore::load(path.clone());
+        let ids = |until| -> Vec<String> {
+            reloaded
+                .latest(until, 10)
+                .into_iter()
+                .map(|i| i.id)
+                .collect()
+        };
+        assert_eq!(ids(None), ["b", "c", "a"]);
+        // Items after a snapshot time are hidden
+        assert_eq!(ids(Some(200)), ["c", "a"]);
+        assert_eq!(reloaded.get("c").unwrap().published_at, 200);
+        assert_eq!(parse_show_time("2024-03-01 09:30:00"), Some(1_709_256_600));
+        std::fs::remove_file(path).ok();
+    }
+}
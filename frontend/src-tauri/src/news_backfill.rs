@@ -0,0 +1,484 @@
+//! Historical announcement backfill for the research document store.
+//!
+//! A backfill walks Eastmoney's announcement archive for one symbol, newest
+//! first, page by page until it reaches announcements older than the
+//! requested number of years. Every page is ingested as research documents
+//! (title plus full text, chunked like any other document) so AI answers can
+//! cite years of filings, and the position reached is checkpointed to disk
+//! after each page. A backfill interrupted by cancellation, a network error
+//! or an app restart continues from its checkpoint instead of re-downloading
+//! what it already has; unfinished backfills are resumed at startup.
+//!
+//! The first page of the archive also serves as a symbol's own news feed:
+//! the news poller fetches it for symbols open in a detail view.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Asia::Shanghai;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::documents::{chunk_text, DocumentKind, DocumentStore, ResearchDocument};
+use crate::drift::{DriftLog, Field, FieldKind};
+use crate::executor::{Priority, ResourceClass};
+use crate::faults::FaultInjector;
+use crate::news::NewsItem;
+use crate::politeness::PolicyEngine;
+use crate::symbols::symbol_key;
+use crate::tasks::{spawn_task_with, TaskContext, TaskHandle};
+use crate::utils::{get_timestamp, read_from_file, write_to_file};
+
+const PROVIDER: &str = "eastmoney";
+
+const LIST_URL: &str = "https://np-anotice-stock.eastmoney.com/api/security/ann";
+const CONTENT_URL: &str = "https://np-cnotice-stock.eastmoney.com/api/content/ann";
+
+const PAGE_SIZE: usize = 50;
+
+/// Longest history a backfill may request
+const MAX_YEARS: u32 = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Backfill {
+    pub symbol: String,
+    pub years: u32,
+    /// Announcements published before this date are out of range
+    pub since: NaiveDate,
+    /// Archive page to fetch next
+    pub next_page: usize,
+    /// Documents ingested so far
+    pub documents: usize,
+    /// Announcements in the archive, as last reported by the provider
+    pub total: Option<usize>,
+    pub done: bool,
+    pub updated_at: String,
+}
+
+impl Backfill {
+    fn new(symbol: &str, years: u32, today: NaiveDate) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            years,
+            since: today - Duration::days(365 * years as i64),
+            next_page: 1,
+            documents: 0,
+            total: None,
+            done: false,
+            updated_at: get_timestamp(),
+        }
+    }
+
+    /// Pick up an existing backfill, or start over when it finished or covers less history
+    fn resume_or_new(
+        existing: Option<Backfill>,
+        symbol: &str,
+        years: u32,
+        today: NaiveDate,
+    ) -> Self {
+        match existing {
+            Some(backfill) if !backfill.done && backfill.years >= years => backfill,
+            Some(backfill) => Backfill {
+                documents: backfill.documents,
+                ..Backfill::new(symbol, years.max(backfill.years), today)
+            },
+            None => Backfill::new(symbol, years, today),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Announcement {
+    art_code: String,
+    title: String,
+    published: NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+struct ListResponse {
+    data: ListData,
+}
+
+#[derive(Deserialize)]
+struct ListData {
+    list: Vec<ListRow>,
+    total_hits: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ListRow {
+    art_code: String,
+    title: String,
+    notice_date: String,
+}
+
+const LIST_SCHEMA: &[Field] = &[
+    Field {
+        path: "data.list",
+        kind: FieldKind::Array,
+    },
+    Field {
+        path: "data.list[].art_code",
+        kind: FieldKind::String,
+    },
+    Field {
+        path: "data.list[].title",
+        kind: FieldKind::String,
+    },
+    Field {
+        path: "data.list[].notice_date",
+        kind: FieldKind::String,
+    },
+];
+
+/// `SH600519` style symbol to the bare code the archive is keyed by
+fn archive_code(symbol: &str) -> Result<String, String> {
+    let symbol = symbol.trim().to_uppercase();
+    let code = ["SH", "SZ", "BJ"]
+        .iter()
+        .find_map(|prefix| symbol.strip_prefix(prefix))
+        .filter(|code| code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()))
+        .ok_or_else(|| format!("Invalid A-share symbol: {}", symbol))?;
+    Ok(code.to_string())
+}
+
+fn detail_url(code: &str, art_code: &str) -> String {
+    format!(
+        "https://data.eastmoney.com/notices/detail/{}/{}.html",
+        code, art_code
+    )
+}
+
+fn parse_page(value: serde_json::Value) -> Result<(Vec<Announcement>, Option<usize>), String> {
+    let response: ListResponse = serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse announcement list: {}", e))?;
+    let announcements = response
+        .data
+        .list
+        .into_iter()
+        .filter_map(|row| {
+            let published = NaiveDateTime::parse_from_str(&row.notice_date, "%Y-%m-%d %H:%M:%S")
+                .map_err(|_| warn!("Skipping announcement {} with bad date", row.art_code))
+                .ok()?;
+            Some(Announcement {
+                art_code: row.art_code,
+                title: row.title.trim().to_string(),
+                published,
+            })
+        })
+        .collect();
+    Ok((announcements, response.data.total_hits))
+}
+
+async fn fetch_text(app: &AppHandle, url: &str, what: &str) -> Result<String, String> {
+    let policies = app.state::<PolicyEngine>();
+    app.state::<FaultInjector>()
+        .wrap(PROVIDER, async {
+            policies
+                .get(PROVIDER, url)
+                .await?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", what, e))
+        })
+        .await
+}
+
+async fn fetch_page(
+    app: &AppHandle,
+    code: &str,
+    page: usize,
+) -> Result<(Vec<Announcement>, Option<usize>), String> {
+    let url = format!(
+        "{}?sr=-1&page_size={}&page_index={}&ann_type=A&client_source=web&stock_list={}",
+        LIST_URL, PAGE_SIZE, page, code
+    );
+    let body = fetch_text(app, &url, "announcement list").await?;
+    let value = app.state::<DriftLog>().check_response(
+        app,
+        PROVIDER,
+        "announcement list",
+        &body,
+        LIST_SCHEMA,
+    )?;
+    parse_page(value)
+}
+
+/// A symbol's latest announcements as news items
+pub async fn latest_announcements(app: &AppHandle, symbol: &str) -> Result<Vec<NewsItem>, String> {
+    let code = archive_code(symbol)?;
+    let (announcements, _) = fetch_page(app, &code, 1).await?;
+    let fetched_at = get_timestamp();
+    Ok(announcements
+        .into_iter()
+        .filter_map(|announcement| {
+            Some(NewsItem {
+                id: format!("{}-ann-{}", PROVIDER, announcement.art_code),
+                source: PROVIDER.to_string(),
+                url: detail_url(&code, &announcement.art_code),
+                published_at: Shanghai
+                    .from_local_datetime(&announcement.published)
+                    .single()?
+                    .timestamp(),
+                title: announcement.title,
+                summary: String::new(),
+                fetched_at: fetched_at.clone(),
+                tags: Vec::new(),
+                symbols: vec![symbol_key(symbol)],
+            })
+        })
+        .collect())
+}
+
+/// Full text of an announcement
+async fn fetch_content(app: &AppHandle, art_code: &str) -> Result<String, String> {
+    let url = format!(
+        "{}?art_code={}&client_source=web&page_index=1",
+        CONTENT_URL, art_code
+    );
+    let body = fetch_text(app, &url, "announcement").await?;
+    let value: serde_json::Value =
+        serde_json::from_str(&body).map_err(|e| format!("Failed to parse announcement: {}", e))?;
+    value["data"]["notice_content"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("Announcement {} has no content", art_code))
+}
+
+pub struct BackfillStore {
+    path: PathBuf,
+    backfills: Mutex<BTreeMap<String, Backfill>>,
+    /// Symbols with a backfill task in flight
+    running: Mutex<HashSet<String>>,
+}
+
+impl BackfillStore {
+    pub fn load(path: PathBuf) -> Self {
+        let backfills = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            backfills: Mutex::new(backfills),
+            running: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<Backfill> {
+        self.backfills.lock().unwrap().get(symbol).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Backfill> {
+        self.backfills.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Record a checkpoint
+    pub fn save(&self, mut backfill: Backfill) -> Result<(), String> {
+        backfill.updated_at = get_timestamp();
+        let mut backfills = self.backfills.lock().unwrap();
+        backfills.insert(backfill.symbol.clone(), backfill);
+        let content = serde_json::to_string_pretty(&*backfills)
+            .map_err(|e| format!("Failed to serialize backfills: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save backfills: {}", e))
+    }
+}
+
+/// Clears a symbol's running flag when its task ends, however it ends
+struct RunningGuard {
+    app: AppHandle,
+    symbol: String,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.app
+            .state::<BackfillStore>()
+            .running
+            .lock()
+            .unwrap()
+            .remove(&self.symbol);
+    }
+}
+
+fn run(ctx: &TaskContext, app: &AppHandle, mut backfill: Backfill) -> Result<Backfill, String> {
+    let code = archive_code(&backfill.symbol)?;
+    let store = app.state::<BackfillStore>();
+    let documents = app.state::<DocumentStore>();
+    let since = backfill.since.and_hms_opt(0, 0, 0).unwrap_or_default();
+
+    while !backfill.done {
+        let (announcements, total) =
+            tauri::async_runtime::block_on(fetch_page(app, &code, backfill.next_page))?;
+        backfill.total = total.or(backfill.total);
+
+        let mut ingested = Vec::new();
+        let mut reached_end = announcements.len() < PAGE_SIZE;
+        for announcement in announcements {
+            if announcement.published < since {
+                reached_end = true;
+                break;
+            }
+            let source = detail_url(&code, &announcement.art_code);
+            if documents.has_source(&source) {
+                continue;
+            }
+            // A page is only checkpointed once all of it is stored
+            ctx.checkpoint()?;
+            let content =
+                tauri::async_runtime::block_on(fetch_content(app, &announcement.art_code))
+                    .unwrap_or_else(|e| {
+                        warn!("{}; keeping the title only", e);
+                        String::new()
+                    });
+            let text = format!("{}\n{}", announcement.title, content);
+            ingested.push(ResearchDocument::new(
+                DocumentKind::Announcement,
+                &announcement.title,
+                Some(backfill.symbol.clone()),
+                &source,
+                chunk_text(&text),
+            ));
+        }
+
+        backfill.documents += ingested.len();
+        documents.add_all(ingested)?;
+        backfill.next_page += 1;
+        backfill.done = reached_end;
+        store.save(backfill.clone())?;
+
+        let done = ((backfill.next_page - 1) * PAGE_SIZE) as u64;
+        let total = backfill.total.map(|t| t as u64).unwrap_or(done).max(done);
+        ctx.report("download", done, total);
+        ctx.checkpoint()?;
+    }
+    info!(
+        "Backfilled {} announcements for {} since {}",
+        backfill.documents, backfill.symbol, backfill.since
+    );
+    Ok(backfill)
+}
+
+fn start_backfill(app: &AppHandle, backfill: Backfill) -> Result<String, String> {
+    let symbol = backfill.symbol.clone();
+    if !app
+        .state::<BackfillStore>()
+        .running
+        .lock()
+        .unwrap()
+        .insert(symbol.clone())
+    {
+        return Err(format!("A backfill for {} is already running", symbol));
+    }
+    let handle = app.clone();
+    Ok(spawn_task_with(
+        app,
+        "news_backfill",
+        ResourceClass::Network,
+        Priority::Background,
+        move |ctx: TaskContext| {
+            let _guard = RunningGuard {
+                app: handle.clone(),
+                symbol,
+            };
+            run(&ctx, &handle, backfill)
+        },
+    ))
+}
+
+/// Continue backfills an earlier session left unfinished
+pub fn resume_backfills(app: &AppHandle) {
+    let pending: Vec<Backfill> = app
+        .state::<BackfillStore>()
+        .list()
+        .into_iter()
+        .filter(|b| !b.done)
+        .collect();
+    for backfill in pending {
+        info!(
+            "Resuming announcement backfill for {} at page {}",
+            backfill.symbol, backfill.next_page
+        );
+        if let Err(e) = start_backfill(app, backfill) {
+            warn!("Failed to resume backfill: {}", e);
+        }
+    }
+}
+
+/// Backfill `years` of announcements for a symbol into the research documents
+#[tauri::command]
+pub fn backfill_news(app: AppHandle, symbol: String, years: u32) -> Result<TaskHandle, String> {
+    if years == 0 || years > MAX_YEARS {
+        return Err(format!("Years must be between 1 and {}", MAX_YEARS));
+    }
+    let symbol = symbol_key(&symbol);
+    archive_code(&symbol)?;
+    let existing = app.state::<BackfillStore>().get(&symbol);
+    let backfill = Backfill::resume_or_new(existing, &symbol, years, Local::now().date_naive());
+    Ok(TaskHandle {
+        task_id: start_backfill(&app, backfill)?,
+    })
+}
+
+#[tauri::command]
+pub fn list_news_backfills(store: State<'_, BackfillStore>) -> Result<Vec<Backfill>, String> {
+    Ok(store.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_page_and_symbols() {
+        let value = json!({
+            "data": {
+                "list": [
+                    {"art_code": "AN202404011", "title": " 2023年年度报告 ", "notice_date": "2024-04-02 00:00:00"},
+                    {"art_code": "AN202404012", "title": "bad", "notice_date": "2024/04/02"}
+                ],
+                "total_hits": 812
+            }
+        });
+        let (announcements, total) = parse_page(value).unwrap();
+        assert_eq!(total, Some(812));
+        assert_eq!(announcements.len(), 1);
+        assert_eq!(announcements[0].title, "2023年年度报告");
+
+        assert_eq!(archive_code("sh600519").unwrap(), "600519");
+        assert!(archive_code("HK00700").is_err());
+        assert!(archive_code("SZ0001").is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let path = std::env::temp_dir().join(format!("ssi-backfill-{}.json", std::process::id()));
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let store = BackfillStore::load(path.clone());
+        let mut backfill = Backfill::new("SH600519", 5, today);
+        backfill.next_page = 7;
+        backfill.documents = 300;
+        store.save(backfill).unwrap();
+
+        // An interrupted backfill continues where it stopped
+        let reloaded = BackfillStore::load(path.clone()).get("SH600519");
+        let resumed = Backfill::resume_or_new(reloaded.clone(), "SH600519", 3, today);
+        assert_eq!(resumed.next_page, 7);
+        assert_eq!(resumed.since, NaiveDate::from_ymd_opt(2020, 6, 2).unwrap());
+
+        // Asking for more history restarts from the newest page, keeping the count
+        let longer = Backfill::resume_or_new(reloaded.clone(), "SH600519", 10, today);
+        assert_eq!((longer.next_page, longer.documents), (1, 300));
+
+        let mut finished = reloaded.unwrap();
+        finished.done = true;
+        let again = Backfill::resume_or_new(Some(finished), "SH600519", 5, today);
+        assert!(!again.done);
+        assert_eq!(again.next_page, 1);
+        std::fs::remove_file(path).ok();
+    }
+}
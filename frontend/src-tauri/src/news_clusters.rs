@@ -0,0 +1,245 @@
+//! Near-duplicate story clustering.
+//!
+//! The same event is usually carried by several outlets with slightly
+//! different wording. Each item's title and summary are reduced to character
+//! bigrams and a MinHash signature; items published within a day of each
+//! other whose estimated Jaccard similarity clears a threshold are folded into
+//! one story, which reports how many sources carried it and the mean
+//! headline sentiment of its items.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::news::{NewsItem, NewsStore};
+use crate::snapshot::SnapshotClock;
+
+/// Hash functions in a signature
+const SIGNATURE_LEN: usize = 64;
+
+/// Estimated Jaccard similarity at which two items are the same story
+const SIMILARITY_THRESHOLD: f64 = 0.5;
+
+/// Items further apart than this are never merged
+const CLUSTER_WINDOW_SECS: i64 = 24 * 3600;
+
+/// Items considered when building the clustered feed
+const CLUSTER_SCAN_LIMIT: usize = 1000;
+
+const POSITIVE_TERMS: &[&str] = &[
+    "上涨",
+    "大涨",
+    "涨停",
+    "增长",
+    "利好",
+    "突破",
+    "新高",
+    "盈利",
+    "扭亏",
+    "超预期",
+    "回购",
+    "增持",
+    "中标",
+    "获批",
+    "降准",
+    "降息",
+    "分红",
+];
+const NEGATIVE_TERMS: &[&str] = &[
+    "下跌", "大跌", "跌停", "亏损", "利空", "减持", "暴跌", "违规", "处罚", "退市", "下滑", "诉讼",
+    "新低", "爆雷", "立案", "预亏",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewsCluster {
+    /// Id of the earliest item, stable while the story grows
+    pub id: String,
+    pub title: String,
+    pub item_ids: Vec<String>,
+    pub sources: Vec<String>,
+    pub source_count: usize,
+    pub first_published_at: i64,
+    pub last_published_at: i64,
+    /// Mean headline sentiment of the items, from -1 to 1
+    pub sentiment: f64,
+}
+
+/// FNV-1a, stable across runs so signatures can be compared between sessions
+fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Character bigrams of the text with whitespace and punctuation removed
+fn shingles(text: &str) -> BTreeSet<String> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if chars.len() < 2 {
+        return chars.iter().map(|c| c.to_string()).collect();
+    }
+    chars.windows(2).map(|w| w.iter().collect()).collect()
+}
+
+pub fn minhash(text: &str) -> [u64; SIGNATURE_LEN] {
+    let mut signature = [u64::MAX; SIGNATURE_LEN];
+    for shingle in shingles(text) {
+        for (seed, slot) in signature.iter_mut().enumerate() {
+            *slot = (*slot).min(fnv1a(shingle.as_bytes(), seed as u64));
+        }
+    }
+    signature
+}
+
+/// Estimated Jaccard similarity of the texts behind two signatures
+pub fn similarity(a: &[u64; SIGNATURE_LEN], b: &[u64; SIGNATURE_LEN]) -> f64 {
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / SIGNATURE_LEN as f64
+}
+
+/// Lexicon score of a headline, from -1 (negative) to 1 (positive)
+pub fn headline_sentiment(text: &str) -> f64 {
+    let positive = POSITIVE_TERMS.iter().filter(|t| text.contains(*t)).count() as f64;
+    let negative = NEGATIVE_TERMS.iter().filter(|t| text.contains(*t)).count() as f64;
+    if positive + negative == 0.0 {
+        return 0.0;
+    }
+    (positive - negative) / (positive + negative)
+}
+
+struct Building {
+    items: Vec<NewsItem>,
+    signature: [u64; SIGNATURE_LEN],
+}
+
+impl Building {
+    fn into_cluster(mut self) -> NewsCluster {
+        self.items.sort_by_key(|i| i.published_at);
+        let first = &self.items[0];
+        let sources: BTreeSet<String> = self.items.iter().map(|i| i.source.clone()).collect();
+        let sentiment = self
+            .items
+            .iter()
+            .map(|i| headline_sentiment(&format!("{} {}", i.title, i.summary)))
+            .sum::<f64>()
+            / self.items.len() as f64;
+        NewsCluster {
+            id: first.id.clone(),
+            title: first.title.clone(),
+            item_ids: self.items.iter().map(|i| i.id.clone()).collect(),
+            source_count: sources.len(),
+            sources: sources.into_iter().collect(),
+            first_published_at: first.published_at,
+            last_published_at: self.items.last().map_or(0, |i| i.published_at),
+            sentiment,
+        }
+    }
+}
+
+/// Group near-duplicate items into stories, most recently updated first
+pub fn cluster(items: &[NewsItem]) -> Vec<NewsCluster> {
+    let mut ordered: Vec<&NewsItem> = items.iter().collect();
+    ordered.sort_by_key(|i| i.published_at);
+
+    let mut building: Vec<Building> = Vec::new();
+    for item in ordered {
+        let signature = minhash(&format!("{}{}", item.title, item.summary));
+        let matched = building.iter_mut().rev().find(|b| {
+            let latest = b.items.last().map_or(0, |i| i.published_at);
+            item.published_at - latest <= CLUSTER_WINDOW_SECS
+                && similarity(&b.signature, &signature) >= SIMILARITY_THRESHOLD
+        });
+        match matched {
+            Some(story) => story.items.push(item.clone()),
+            None => building.push(Building {
+                items: vec![item.clone()],
+                signature,
+            }),
+        }
+    }
+
+    let mut clusters: Vec<NewsCluster> = building.into_iter().map(Building::into_cluster).collect();
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.last_published_at));
+    clusters
+}
+
+/// Recent news grouped into stories, each listed once with its source count
+#[tauri::command]
+pub fn get_news_clusters(
+    store: State<'_, NewsStore>,
+    clock: State<'_, SnapshotClock>,
+    limit: Option<usize>,
+) -> Result<Vec<NewsCluster>, String> {
+    let mut clusters = cluster(&store.latest(clock.frozen_at(), CLUSTER_SCAN_LIMIT));
+    clusters.truncate(limit.unwrap_or(50));
+    Ok(clusters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, source: &str, title: &str, published_at: i64) -> NewsItem {
+        NewsItem {
+            id: id.to_string(),
+            source: source.to_string(),
+            title: title.to_string(),
+            summary: String::new(),
+            url: String::new(),
+            published_at,
+            fetched_at: String::new(),
+            tags: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_clusters_near_duplicates() {
+        let items = vec![
+            item(
+                "a",
+                "eastmoney",
+                "贵州茅台2024年净利润同比增长15%，超市场预期",
+                1000,
+            ),
+            item(
+                "b",
+                "cls",
+                "贵州茅台：2024年净利润同比增长15%，超预期",
+                1600,
+            ),
+            item(
+                "c",
+                "sina",
+                "贵州茅台2024年净利润同比增长15% 超市场预期",
+                2000,
+            ),
+            item("d", "cls", "央行宣布下调存款准备金率0.5个百分点", 1800),
+            item(
+                "e",
+                "sina",
+                "贵州茅台2024年净利润同比增长15%，超市场预期",
+                1000 + 3 * 86_400,
+            ),
+        ];
+        let clusters = cluster(&items);
+        assert_eq!(clusters.len(), 3);
+
+        let moutai = clusters.iter().find(|c| c.id == "a").unwrap();
+        assert_eq!(moutai.item_ids, ["a", "b", "c"]);
+        assert_eq!(moutai.source_count, 3);
+        assert!(moutai.sentiment > 0.0);
+        // Same headline days later is a new story
+        assert_eq!(clusters[0].id, "e");
+
+        assert_eq!(headline_sentiment("公司股东拟减持，股价大跌"), -1.0);
+        assert_eq!(headline_sentiment("今日无事"), 0.0);
+    }
+}
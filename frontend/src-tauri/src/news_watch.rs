@@ -0,0 +1,294 @@
+//! Keyword and entity watches on the news stream.
+//!
+//! Users register watches for companies, product lines, people or plain
+//! keywords, each with any number of aliases. Incoming items are segmented
+//! with jieba (watch terms are added to its dictionary so they stay whole)
+//! and a term matches only where it starts on a word boundary, so a watch on
+//! 安银 does not fire on 平安银行 while 茅台 still matches 茅台酒. Matching
+//! items are tagged with the watch id and announced through a
+//! `news-watch-hit` event.
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use jieba_rs::Jieba;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::news::{NewsItem, NewsStore};
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchKind {
+    Keyword,
+    Company,
+    Product,
+    Person,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewsWatch {
+    pub id: String,
+    pub label: String,
+    pub kind: WatchKind,
+    /// The name and its aliases; any one of them matching is a hit
+    pub terms: Vec<String>,
+    pub created_at: String,
+}
+
+/// Payload of the `news-watch-hit` event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchHit {
+    pub watch_id: String,
+    pub label: String,
+    pub news_id: String,
+    pub title: String,
+    pub matched_terms: Vec<String>,
+}
+
+/// Lowercase alphanumeric form used for comparison
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Segmented text joined back together, with the byte offset of every word start
+struct Segmented {
+    text: String,
+    boundaries: Vec<usize>,
+}
+
+fn segment(jieba: &Jieba, text: &str) -> Segmented {
+    let mut segmented = Segmented {
+        text: String::new(),
+        boundaries: Vec::new(),
+    };
+    for token in jieba.cut(text, true) {
+        let token = normalize(token);
+        if !token.is_empty() {
+            segmented.boundaries.push(segmented.text.len());
+            segmented.text.push_str(&token);
+        }
+    }
+    segmented
+}
+
+impl Segmented {
+    fn contains_word(&self, term: &str) -> bool {
+        !term.is_empty()
+            && self
+                .boundaries
+                .iter()
+                .any(|&start| self.text[start..].starts_with(term))
+    }
+}
+
+pub struct NewsWatcher {
+    path: PathBuf,
+    watches: RwLock<Vec<NewsWatch>>,
+    jieba: RwLock<Jieba>,
+}
+
+impl NewsWatcher {
+    pub fn load(path: PathBuf) -> Self {
+        let watches: Vec<NewsWatch> = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let mut jieba = Jieba::new();
+        for term in watches.iter().flat_map(|w| &w.terms) {
+            jieba.add_word(term, None, None);
+        }
+        Self {
+            path,
+            watches: RwLock::new(watches),
+            jieba: RwLock::new(jieba),
+        }
+    }
+
+    fn save(&self, watches: &[NewsWatch]) -> Result<(), String> {
+        let content = serde_json::to_string(watches)
+            .map_err(|e| format!("Failed to serialize news watches: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save news watches: {}", e))
+    }
+
+    pub fn add(
+        &self,
+        label: &str,
+        kind: WatchKind,
+        terms: Vec<String>,
+    ) -> Result<NewsWatch, String> {
+        let mut terms: Vec<String> = terms
+            .into_iter()
+            .chain(std::iter::once(label.to_string()))
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return Err("A news watch needs at least one term".to_string());
+        }
+        let watch = NewsWatch {
+            id: generate_id("watch"),
+            label: label.trim().to_string(),
+            kind,
+            terms,
+            created_at: get_timestamp(),
+        };
+        {
+            let mut jieba = self.jieba.write().unwrap();
+            for term in &watch.terms {
+                jieba.add_word(term, None, None);
+            }
+        }
+        let mut watches = self.watches.write().unwrap();
+        watches.push(watch.clone());
+        self.save(&watches)?;
+        Ok(watch)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let mut watches = self.watches.write().unwrap();
+        let before = watches.len();
+        watches.retain(|w| w.id != id);
+        if watches.len() == before {
+            return Err(format!("News watch not found: {}", id));
+        }
+        self.save(&watches)
+    }
+
+    pub fn list(&self) -> Vec<NewsWatch> {
+        self.watches.read().unwrap().clone()
+    }
+
+    /// Watches an item matches, with the terms that matched
+    pub fn matches(&self, item: &NewsItem) -> Vec<WatchHit> {
+        let jieba = self.jieba.read().unwrap();
+        let text = segment(&jieba, &format!("{}\n{}", item.title, item.summary));
+        self.watches
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|watch| {
+                let matched_terms: Vec<String> = watch
+                    .terms
+                    .iter()
+                    .filter(|term| text.contains_word(&normalize(term)))
+                    .cloned()
+                    .collect();
+                (!matched_terms.is_empty()).then(|| WatchHit {
+                    watch_id: watch.id.clone(),
+                    label: watch.label.clone(),
+                    news_id: item.id.clone(),
+                    title: item.title.clone(),
+                    matched_terms,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Match new items against the watches, tag them and raise an event per hit
+pub fn process(app: &AppHandle, items: &[NewsItem]) {
+    let watcher = app.state::<NewsWatcher>();
+    let store = app.state::<NewsStore>();
+    for item in items {
+        let hits = watcher.matches(item);
+        if hits.is_empty() {
+            continue;
+        }
+        let tags = hits.iter().map(|h| h.watch_id.clone()).collect();
+        if let Err(e) = store.add_tags(&item.id, tags) {
+            warn!("{}", e);
+        }
+        for hit in hits {
+            if let Err(e) = app.emit("news-watch-hit", hit) {
+                warn!("Failed to emit news-watch-hit event: {}", e);
+            }
+        }
+    }
+}
+
+/// Watch the news for a keyword, company, product or person and its aliases
+#[tauri::command]
+pub fn add_news_watch(
+    watcher: State<'_, NewsWatcher>,
+    label: String,
+    kind: WatchKind,
+    aliases: Vec<String>,
+) -> Result<NewsWatch, String> {
+    watcher.add(&label, kind, aliases)
+}
+
+#[tauri::command]
+pub fn remove_news_watch(watcher: State<'_, NewsWatcher>, id: String) -> Result<(), String> {
+    watcher.remove(&id)
+}
+
+#[tauri::command]
+pub fn list_news_watches(watcher: State<'_, NewsWatcher>) -> Result<Vec<NewsWatch>, String> {
+    Ok(watcher.list())
+}
+
+/// Items tagged by a watch, newest first
+#[tauri::command]
+pub fn get_watched_news(
+    store: State<'_, NewsStore>,
+    watch_id: String,
+) -> Result<Vec<NewsItem>, String> {
+    Ok(store.tagged(&watch_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str) -> NewsItem {
+        NewsItem {
+            id: "n1".to_string(),
+            source: "eastmoney".to_string(),
+            title: title.to_string(),
+            summary: String::new(),
+            url: String::new(),
+            published_at: 0,
+            fetched_at: String::new(),
+            tags: Vec::new(),
+            symbols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_watch_matching_respects_word_boundaries() {
+        let path = std::env::temp_dir().join(format!("ssi-watches-{}.json", std::process::id()));
+        let watcher = NewsWatcher::load(path.clone());
+        let citic = watcher
+            .add("中信", WatchKind::Company, vec!["CITIC".to_string()])
+            .unwrap();
+        let tesla = watcher
+            .add("特斯拉", WatchKind::Company, vec!["Model Y".to_string()])
+            .unwrap();
+
+        let hits = watcher.matches(&item("中信集团与citic资本宣布合作"));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].watch_id, citic.id);
+        assert_eq!(hits[0].matched_terms, ["CITIC", "中信"]);
+
+        let hits = watcher.matches(&item("新款Model Y本月交付"));
+        assert_eq!(hits[0].watch_id, tesla.id);
+
+        watcher.add("安银", WatchKind::Keyword, Vec::new()).unwrap();
+        assert!(watcher.matches(&item("平安银行发布年报")).is_empty());
+
+        let reloaded = NewsWatcher::load(path.clone());
+        assert_eq!(reloaded.list().len(), 3);
+        reloaded.remove(&citic.id).unwrap();
+        assert!(reloaded.remove(&citic.id).is_err());
+        std::fs::remove_file(path).ok();
+    }
+}
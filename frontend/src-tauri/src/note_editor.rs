@@ -0,0 +1,347 @@
+//! Editing notes in an external editor.
+//!
+//! [`edit_note_externally`] writes a note to a Markdown file in the temp
+//! folder, its title as the first heading, and opens it in the editor set in
+//! settings or the system's default for Markdown. The file is checked for
+//! saves every [`POLL_INTERVAL`] and each save is written back to the note,
+//! reaching the frontend as a `note-updated` event.
+//!
+//! Every sync remembers the text both sides agreed on. A save is only
+//! written back while the note in the app still has that text; when both
+//! sides have changed since, nothing is overwritten and a
+//! `note-edit-conflict` event asks the user to keep one version with
+//! [`resolve_note_edit_conflict`]. [`finish_note_edit`] syncs a last time and
+//! removes the file.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use log::{info, warn};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{Database, Note, Notes};
+use crate::settings::SettingsStore;
+use crate::utils::ensure_dir_exists;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A note's title and body as they appear in the file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Draft {
+    pub title: String,
+    pub body: String,
+}
+
+impl Draft {
+    fn of(note: &Note) -> Self {
+        Self {
+            title: note.title.clone(),
+            body: note.body.clone(),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        format!("# {}\n\n{}", self.title, self.body)
+    }
+
+    /// Read a file back; without a leading heading the title stays `title`
+    fn parse(content: &str, title: &str) -> Self {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let (first, rest) = content.split_once('\n').unwrap_or((content, ""));
+        match first.trim_end_matches('\r').strip_prefix("# ") {
+            Some(heading) => Self {
+                title: heading.trim().to_string(),
+                body: rest
+                    .strip_prefix("\r\n")
+                    .or_else(|| rest.strip_prefix('\n'))
+                    .unwrap_or(rest)
+                    .to_string(),
+            },
+            None => Self {
+                title: title.to_string(),
+                body: content.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sync {
+    /// Nothing in the file the note lacks
+    Unchanged,
+    /// The file has edits to write back
+    Apply,
+    /// The note and the file both changed since the last sync
+    Conflict,
+}
+
+fn reconcile(base: &Draft, stored: &Draft, file: &Draft) -> Sync {
+    if file == base || file == stored {
+        Sync::Unchanged
+    } else if stored == base {
+        Sync::Apply
+    } else {
+        Sync::Conflict
+    }
+}
+
+struct EditSession {
+    path: PathBuf,
+    /// Text the note and the file last agreed on
+    base: Draft,
+    modified: Option<SystemTime>,
+    conflict: bool,
+}
+
+#[derive(Default)]
+pub struct ExternalEdits {
+    sessions: Mutex<HashMap<String, EditSession>>,
+}
+
+/// Payload of the `note-edit-conflict` event
+#[derive(Debug, Clone, Serialize)]
+struct EditConflict {
+    note_id: String,
+    external: Draft,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalEdit {
+    pub note_id: String,
+    pub path: PathBuf,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn open_in_editor(editor: Option<&str>, path: &Path) -> Result<(), String> {
+    let mut command = match editor {
+        Some(program) => Command::new(program),
+        #[cfg(target_os = "windows")]
+        None => {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        #[cfg(target_os = "macos")]
+        None => Command::new("open"),
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        None => Command::new("xdg-open"),
+    };
+    command
+        .arg(path)
+        .spawn()
+        .map(drop)
+        .map_err(|e| format!("Failed to open editor: {}", e))
+}
+
+fn write_draft(path: &Path, draft: &Draft) -> Result<(), String> {
+    std::fs::write(path, draft.to_markdown())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Write a save back to the note if it can be without losing an edit
+fn sync(app: &AppHandle, note_id: &str) -> Result<Sync, String> {
+    let edits = app.state::<ExternalEdits>();
+    let mut sessions = edits.sessions.lock().unwrap();
+    let Some(session) = sessions.get_mut(note_id) else {
+        return Ok(Sync::Unchanged);
+    };
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    let notes = Notes(&conn);
+    let stored = Draft::of(&notes.get(note_id)?);
+    let content = std::fs::read_to_string(&session.path)
+        .map_err(|e| format!("Failed to read {}: {}", session.path.display(), e))?;
+    session.modified = modified(&session.path);
+    let file = Draft::parse(&content, &stored.title);
+    let outcome = reconcile(&session.base, &stored, &file);
+    match outcome {
+        Sync::Unchanged if file == stored => session.base = file,
+        Sync::Unchanged => {}
+        Sync::Apply => {
+            let note = notes.update(note_id, &file.title, &file.body)?;
+            session.base = Draft::of(&note);
+            session.conflict = false;
+            info!("Synced note {} from its external editor", note_id);
+            if let Err(e) = app.emit("note-updated", &note) {
+                warn!("Failed to emit note-updated event: {}", e);
+            }
+        }
+        Sync::Conflict if session.conflict => {}
+        Sync::Conflict => {
+            session.conflict = true;
+            warn!("Note {} changed in the app and its editor", note_id);
+            let conflict = EditConflict {
+                note_id: note_id.to_string(),
+                external: file,
+            };
+            if let Err(e) = app.emit("note-edit-conflict", conflict) {
+                warn!("Failed to emit note-edit-conflict event: {}", e);
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+/// Sync every save of the file until the session ends
+fn start_watch(app: &AppHandle, note_id: String) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let changed = {
+                let edits = handle.state::<ExternalEdits>();
+                let sessions = edits.sessions.lock().unwrap();
+                let Some(session) = sessions.get(&note_id) else {
+                    break;
+                };
+                modified(&session.path) != session.modified
+            };
+            if !changed {
+                continue;
+            }
+            if let Err(e) = sync(&handle, &note_id) {
+                // The note was deleted, or the file with it
+                warn!("Stopped external edit of note {}: {}", note_id, e);
+                handle
+                    .state::<ExternalEdits>()
+                    .sessions
+                    .lock()
+                    .unwrap()
+                    .remove(&note_id);
+                break;
+            }
+        }
+    });
+}
+
+/// Open a note in the external editor, syncing each save back to it
+#[tauri::command]
+pub fn edit_note_externally(
+    app: AppHandle,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    edits: State<'_, ExternalEdits>,
+    note_id: String,
+) -> Result<ExternalEdit, String> {
+    let editor = settings.get().external_editor;
+    let mut sessions = edits.sessions.lock().unwrap();
+    // Editing again reopens the file already being edited
+    if let Some(session) = sessions.get(&note_id) {
+        open_in_editor(editor.as_deref(), &session.path)?;
+        return Ok(ExternalEdit {
+            note_id,
+            path: session.path.clone(),
+        });
+    }
+    let note = {
+        let conn = db.conn()?;
+        Notes(&conn).get(&note_id)?
+    };
+    let dir = std::env::temp_dir().join("smart-stock-insider-notes");
+    ensure_dir_exists(&dir).map_err(|e| format!("Failed to create notes folder: {}", e))?;
+    let path = dir.join(format!("{}.md", note.id));
+    let base = Draft::of(&note);
+    write_draft(&path, &base)?;
+    sessions.insert(
+        note_id.clone(),
+        EditSession {
+            path: path.clone(),
+            base,
+            modified: modified(&path),
+            conflict: false,
+        },
+    );
+    drop(sessions);
+    start_watch(&app, note_id.clone());
+    open_in_editor(editor.as_deref(), &path)?;
+    info!("Editing note {} externally", note_id);
+    Ok(ExternalEdit { note_id, path })
+}
+
+/// Settle a conflict by keeping the editor's version or the app's
+#[tauri::command]
+pub fn resolve_note_edit_conflict(
+    app: AppHandle,
+    db: State<'_, Database>,
+    edits: State<'_, ExternalEdits>,
+    note_id: String,
+    keep_external: bool,
+) -> Result<Note, String> {
+    let mut sessions = edits.sessions.lock().unwrap();
+    let session = sessions
+        .get_mut(&note_id)
+        .ok_or_else(|| format!("Note is not being edited externally: {}", note_id))?;
+    let conn = db.conn()?;
+    let notes = Notes(&conn);
+    let mut note = notes.get(&note_id)?;
+    if keep_external {
+        let content = std::fs::read_to_string(&session.path)
+            .map_err(|e| format!("Failed to read {}: {}", session.path.display(), e))?;
+        let file = Draft::parse(&content, &note.title);
+        note = notes.update(&note_id, &file.title, &file.body)?;
+        if let Err(e) = app.emit("note-updated", &note) {
+            warn!("Failed to emit note-updated event: {}", e);
+        }
+    } else {
+        write_draft(&session.path, &Draft::of(&note))?;
+    }
+    session.base = Draft::of(&note);
+    session.modified = modified(&session.path);
+    session.conflict = false;
+    Ok(note)
+}
+
+/// Sync a last time, stop watching and remove the file
+#[tauri::command]
+pub fn finish_note_edit(
+    app: AppHandle,
+    edits: State<'_, ExternalEdits>,
+    note_id: String,
+) -> Result<(), String> {
+    if sync(&app, &note_id)? == Sync::Conflict {
+        return Err("Resolve the conflicting edits before finishing".to_string());
+    }
+    if let Some(session) = edits.sessions.lock().unwrap().remove(&note_id) {
+        std::fs::remove_file(&session.path).ok();
+        info!("Finished external edit of note {}", note_id);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_conflicts() {
+        let draft = |title: &str, body: &str| Draft {
+            title: title.to_string(),
+            body: body.to_string(),
+        };
+        let base = draft("茅台调研", "渠道库存偏低\n");
+        assert_eq!(Draft::parse(&base.to_markdown(), "x"), base);
+        assert_eq!(
+            Draft::parse("\u{feff}# 新标题\r\n\r\n正文", "x"),
+            draft("新标题", "正文")
+        );
+        assert_eq!(
+            Draft::parse("没有标题", "茅台调研"),
+            draft("茅台调研", "没有标题")
+        );
+
+        let edited = draft("茅台调研", "渠道库存偏低，批价回升\n");
+        let in_app = draft("茅台调研", "渠道库存偏低\n待跟进\n");
+        assert_eq!(reconcile(&base, &base, &base), Sync::Unchanged);
+        assert_eq!(reconcile(&base, &base, &edited), Sync::Apply);
+        assert_eq!(reconcile(&base, &in_app, &base), Sync::Unchanged);
+        assert_eq!(reconcile(&base, &in_app, &edited), Sync::Conflict);
+        assert_eq!(reconcile(&base, &edited, &edited), Sync::Unchanged);
+    }
+}
@@ -0,0 +1,746 @@
+//! Native desktop notifications and the in-app notification center.
+//!
+//! Notifications are shown through the platform notification service with
+//! optional action buttons. Each belongs to a category that the user can mute
+//! in settings, so a noisy source such as news can be silenced without losing
+//! price alerts. On Linux they go over D-Bus to the XDG notification server
+//! with an urgency per category: price alerts are critical and stay until
+//! acted on, news and task reports are low. Buttons are only added when the
+//! server advertises the `actions` capability. Where the platform reports interaction (toast activations on
+//! Windows, XDG notification servers on Linux), clicking a notification
+//! brings the main window to the front and a button press is routed through
+//! [`handle_action`]. Dismissing (`dismiss`), opening a chart
+//! (`open_chart:<symbol>`) and snoozing an alert
+//! (`snooze_alert:<alert id>:<minutes>`) are carried out in the backend, so an
+//! alert can be dealt with without switching to the app; any other action is
+//! emitted as a `notification-action` event. The notification center runs the
+//! same actions through `run_notification_action`.
+//!
+//! Besides muted categories, toasts are held back while everything is muted,
+//! during the quiet hours, and for a symbol snoozed for some hours, so
+//! overnight US-market alerts don't wake someone trading A-shares. Alerts
+//! held back aren't pushed to their delivery channels either.
+//!
+//! Every notification, muted or not, is also kept in the database as unread
+//! until the user reads or archives it, so dismissing a toast doesn't lose
+//! it, and stays as history across restarts until cleared. Events that
+//! don't warrant a toast, such as finished tasks, go only to the center. New
+//! entries reach the frontend as `notification-added` events.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Local, NaiveTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::alerts::{self, Snooze};
+use crate::db::{Database, Notifications};
+use crate::settings::SettingsStore;
+use crate::utils::generate_id;
+
+#[cfg(not(windows))]
+const APP_NAME: &str = "智股通";
+
+/// Action id the notification server reports for a click on the body
+const DEFAULT_ACTION: &str = "default";
+const DISMISS_ACTION: &str = "dismiss";
+const OPEN_CHART_PREFIX: &str = "open_chart:";
+const SNOOZE_ALERT_PREFIX: &str = "snooze_alert:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    PriceAlert,
+    StrategySignal,
+    News,
+    Task,
+    Update,
+    System,
+}
+
+/// Longest a symbol can be snoozed for
+const MAX_SNOOZE_HOURS: u32 = 7 * 24;
+
+/// Daily hours no toast is shown, in local time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// `HH:MM`
+    pub start: String,
+    /// `HH:MM`; before `start` for hours running past midnight
+    pub end: String,
+}
+
+impl QuietHours {
+    fn times(&self) -> Result<(NaiveTime, NaiveTime), String> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("Invalid quiet hours time: {}", time))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    pub fn covers(&self, time: NaiveTime) -> bool {
+        match self.times() {
+            Ok((start, end)) if start <= end => start <= time && time < end,
+            Ok((start, end)) => time >= start || time < end,
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Categories that are never shown on the desktop
+    pub muted: BTreeSet<NotificationCategory>,
+    /// Hold back every toast, whatever its category
+    pub mute_all: bool,
+    pub quiet_hours: Option<QuietHours>,
+    /// Snoozed symbols, to the Unix second they wake
+    pub snoozed_symbols: BTreeMap<String, i64>,
+}
+
+impl NotificationSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(quiet_hours) = &self.quiet_hours {
+            let (start, end) = quiet_hours.times()?;
+            if start == end {
+                return Err("Quiet hours must not start and end together".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_muted(&self, category: NotificationCategory) -> bool {
+        self.muted.contains(&category)
+    }
+
+    /// Whether everything is held back at local `time`
+    fn is_quiet(&self, time: NaiveTime) -> bool {
+        self.mute_all || self.quiet_hours.as_ref().is_some_and(|q| q.covers(time))
+    }
+
+    /// Whether a toast of `category` may be shown at local `time`
+    pub fn allows(&self, category: NotificationCategory, time: NaiveTime) -> bool {
+        !self.is_muted(category) && !self.is_quiet(time)
+    }
+
+    pub fn is_snoozed(&self, symbol: &str, now: i64) -> bool {
+        self.snoozed_symbols
+            .get(&symbol.to_uppercase())
+            .is_some_and(|until| *until > now)
+    }
+
+    /// Whether a fired alert on `symbol` may reach the desktop and its
+    /// delivery channels now; a muted category only keeps it off the desktop
+    pub fn allows_alert(&self, symbol: &str) -> bool {
+        !self.is_quiet(Local::now().naive_local().time())
+            && !self.is_snoozed(symbol, Utc::now().timestamp())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationAction {
+    pub id: String,
+    pub label: String,
+}
+
+impl NotificationAction {
+    /// Mark the notification read
+    pub fn dismiss() -> Self {
+        Self {
+            id: DISMISS_ACTION.to_string(),
+            label: "忽略".to_string(),
+        }
+    }
+
+    /// Bring up the main window on a symbol's chart
+    pub fn open_chart(symbol: &str) -> Self {
+        Self {
+            id: format!("{}{}", OPEN_CHART_PREFIX, symbol),
+            label: "打开图表".to_string(),
+        }
+    }
+
+    /// Snooze an alert for `minutes`
+    pub fn snooze_alert(alert_id: &str, minutes: u32, label: &str) -> Self {
+        Self {
+            id: format!("{}{}:{}", SNOOZE_ALERT_PREFIX, alert_id, minutes),
+            label: label.to_string(),
+        }
+    }
+}
+
+/// Where a notification action is carried out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ActionRoute<'a> {
+    /// A click on the notification body
+    Open,
+    /// Closed or expired without interaction
+    Closed,
+    Dismiss,
+    OpenChart(&'a str),
+    SnoozeAlert {
+        alert_id: &'a str,
+        minutes: u32,
+    },
+    /// Handled by the frontend
+    Frontend,
+}
+
+fn route(action: &str) -> ActionRoute<'_> {
+    if action == DEFAULT_ACTION {
+        return ActionRoute::Open;
+    }
+    if action == DISMISS_ACTION {
+        return ActionRoute::Dismiss;
+    }
+    // notify-rust reports closing as `__closed`
+    if action.starts_with("__") {
+        return ActionRoute::Closed;
+    }
+    if let Some(symbol) = action.strip_prefix(OPEN_CHART_PREFIX) {
+        return ActionRoute::OpenChart(symbol);
+    }
+    let snooze = action
+        .strip_prefix(SNOOZE_ALERT_PREFIX)
+        .and_then(|rest| rest.rsplit_once(':'))
+        .and_then(|(alert_id, minutes)| Some((alert_id, minutes.parse().ok()?)));
+    match snooze {
+        Some((alert_id, minutes)) => ActionRoute::SnoozeAlert { alert_id, minutes },
+        None => ActionRoute::Frontend,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub category: NotificationCategory,
+    pub title: String,
+    pub body: String,
+    #[serde(default)]
+    pub actions: Vec<NotificationAction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationState {
+    Unread,
+    Read,
+    Archived,
+}
+
+/// A notification as kept in the notification center
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredNotification {
+    pub id: String,
+    pub category: NotificationCategory,
+    pub title: String,
+    pub body: String,
+    pub actions: Vec<NotificationAction>,
+    pub state: NotificationState,
+    pub created_at: String,
+    pub read_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationFilter {
+    /// Only this state; by default everything but archived
+    pub state: Option<NotificationState>,
+    pub category: Option<NotificationCategory>,
+    pub limit: Option<usize>,
+}
+
+/// Payload of the `notification-action` event
+#[derive(Debug, Clone, Serialize)]
+struct ActionEvent {
+    notification_id: String,
+    category: NotificationCategory,
+    action: String,
+}
+
+/// Payload of the `open-chart` event
+#[derive(Debug, Clone, Serialize)]
+struct OpenChart {
+    symbol: String,
+}
+
+/// Bring the main window to the front, restoring it from the tray or taskbar
+pub fn focus_main_window(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    if let Err(e) = window
+        .unminimize()
+        .and_then(|_| window.show())
+        .and_then(|_| window.set_focus())
+    {
+        warn!("Failed to focus main window: {}", e);
+    }
+}
+
+/// Bring up the main window on a symbol's chart
+pub fn open_chart(app: &AppHandle, symbol: &str) {
+    focus_main_window(app);
+    let payload = OpenChart {
+        symbol: symbol.to_string(),
+    };
+    if let Err(e) = app.emit("open-chart", payload) {
+        warn!("Failed to emit open-chart event: {}", e);
+    }
+}
+
+fn mark_as(app: &AppHandle, id: &str, state: NotificationState) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    Notifications(&conn).set_state(&[id.to_string()], state)?;
+    Ok(())
+}
+
+/// Carry out an action pressed on a notification
+pub fn handle_action(
+    app: &AppHandle,
+    notification_id: &str,
+    category: NotificationCategory,
+    action: &str,
+) -> Result<(), String> {
+    match route(action) {
+        ActionRoute::Open => focus_main_window(app),
+        ActionRoute::Closed => {}
+        ActionRoute::Dismiss => mark_as(app, notification_id, NotificationState::Read)?,
+        ActionRoute::OpenChart(symbol) => {
+            open_chart(app, symbol);
+            mark_as(app, notification_id, NotificationState::Read)?;
+        }
+        ActionRoute::SnoozeAlert { alert_id, minutes } => {
+            alerts::apply_snooze(&app.state::<Database>(), alert_id, Snooze::Minutes(minutes))?;
+            mark_as(app, notification_id, NotificationState::Read)?;
+        }
+        ActionRoute::Frontend => {
+            focus_main_window(app);
+            let event = ActionEvent {
+                notification_id: notification_id.to_string(),
+                category,
+                action: action.to_string(),
+            };
+            if let Err(e) = app.emit("notification-action", event) {
+                warn!("Failed to emit notification-action event: {}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Show an actionable toast, routing its activation to [`handle_action`]
+#[cfg(windows)]
+fn show_native(app: &AppHandle, id: &str, notification: &Notification) -> Result<(), String> {
+    use tauri_winrt_notification::Toast;
+
+    // Unpackaged debug builds have no registered app id of their own
+    let app_id = if cfg!(debug_assertions) {
+        Toast::POWERSHELL_APP_ID.to_string()
+    } else {
+        app.config().identifier.clone()
+    };
+    let (first, rest) = notification
+        .body
+        .split_once('\n')
+        .unwrap_or((notification.body.as_str(), ""));
+    let mut toast = Toast::new(&app_id).title(&notification.title).text1(first);
+    if !rest.is_empty() {
+        toast = toast.text2(rest);
+    }
+    for action in &notification.actions {
+        toast = toast.add_button(&action.label, &action.id);
+    }
+    let app = app.clone();
+    let notification_id = id.to_string();
+    let category = notification.category;
+    toast
+        .on_activated(move |action| {
+            let action = action.unwrap_or_else(|| DEFAULT_ACTION.to_string());
+            if let Err(e) = handle_action(&app, &notification_id, category, &action) {
+                warn!("Failed to handle notification action {}: {}", action, e);
+            }
+            Ok(())
+        })
+        .show()
+        .map_err(|e| format!("Failed to show notification: {}", e))
+}
+
+/// Show a notification, routing its actions to [`handle_action`] where the
+/// notification server reports them
+#[cfg(not(windows))]
+fn show_native(app: &AppHandle, id: &str, notification: &Notification) -> Result<(), String> {
+    let mut native = notify_rust::Notification::new();
+    native
+        .appname(APP_NAME)
+        .summary(&notification.title)
+        .body(&notification.body);
+    #[cfg(target_os = "macos")]
+    for action in &notification.actions {
+        native.action(&action.id, &action.label);
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    xdg::decorate(&mut native, notification);
+    let handle = native
+        .show()
+        .map_err(|e| format!("Failed to show notification: {}", e))?;
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let app = app.clone();
+        let notification_id = id.to_string();
+        let category = notification.category;
+        // Blocks until the notification is clicked, dismissed or expires
+        std::thread::spawn(move || {
+            handle.wait_for_action(|action| {
+                if let Err(e) = handle_action(&app, &notification_id, category, action) {
+                    warn!("Failed to handle notification action {}: {}", action, e);
+                }
+            });
+        });
+    }
+    #[cfg(target_os = "macos")]
+    let _ = (app, id, handle);
+    Ok(())
+}
+
+/// What the XDG notification server on Linux is told about a notification
+#[cfg(all(unix, not(target_os = "macos")))]
+mod xdg {
+    use std::sync::OnceLock;
+
+    use notify_rust::{Hint, Urgency};
+
+    use super::{Notification, NotificationCategory, DEFAULT_ACTION};
+
+    /// Desktop entry the server takes the icon and app name from
+    const DESKTOP_ENTRY: &str = "smart-stock-insider";
+
+    fn urgency(category: NotificationCategory) -> Urgency {
+        match category {
+            NotificationCategory::PriceAlert => Urgency::Critical,
+            NotificationCategory::News | NotificationCategory::Task => Urgency::Low,
+            _ => Urgency::Normal,
+        }
+    }
+
+    /// Whether the server shows action buttons, asked once over D-Bus
+    fn supports_actions() -> bool {
+        static ACTIONS: OnceLock<bool> = OnceLock::new();
+        *ACTIONS.get_or_init(|| {
+            notify_rust::get_capabilities()
+                .map(|capabilities| capabilities.iter().any(|c| c == "actions"))
+                .unwrap_or(true)
+        })
+    }
+
+    /// Urgency, hints and, where the server has them, action buttons
+    pub fn decorate(native: &mut notify_rust::Notification, notification: &Notification) {
+        native
+            .urgency(urgency(notification.category))
+            .hint(Hint::DesktopEntry(DESKTOP_ENTRY.to_string()));
+        if notification.category == NotificationCategory::PriceAlert {
+            native.hint(Hint::Resident(true));
+        }
+        if supports_actions() {
+            for action in &notification.actions {
+                native.action(&action.id, &action.label);
+            }
+            native.action(DEFAULT_ACTION, "打开");
+        }
+    }
+}
+
+/// Keep a notification in the notification center without showing it
+pub fn record(app: &AppHandle, notification: &Notification) -> Result<StoredNotification, String> {
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    let stored = Notifications(&conn).record(&generate_id("notification"), notification)?;
+    if let Err(e) = app.emit("notification-added", &stored) {
+        warn!("Failed to emit notification-added event: {}", e);
+    }
+    Ok(stored)
+}
+
+/// Record a notification and show it unless muted or in the quiet hours,
+/// returning its id
+pub fn notify(app: &AppHandle, notification: Notification) -> Result<Option<String>, String> {
+    let id = record(app, &notification)?.id;
+    let settings = app.state::<SettingsStore>().get().notifications;
+    if !settings.allows(notification.category, Local::now().naive_local().time()) {
+        info!(
+            "Notification held back ({:?}): {}",
+            notification.category, notification.title
+        );
+        return Ok(None);
+    }
+
+    show_native(app, &id, &notification)?;
+    info!(
+        "Showing notification ({:?}): {}",
+        notification.category, notification.title
+    );
+    Ok(Some(id))
+}
+
+/// Show a desktop notification; returns `None` when it is held back
+#[tauri::command]
+pub fn show_notification(
+    app: AppHandle,
+    title: String,
+    body: String,
+    category: Option<NotificationCategory>,
+    actions: Option<Vec<NotificationAction>>,
+) -> Result<Option<String>, String> {
+    notify(
+        &app,
+        Notification {
+            category: category.unwrap_or(NotificationCategory::System),
+            title,
+            body,
+            actions: actions.unwrap_or_default(),
+        },
+    )
+}
+
+/// Run one of a notification's actions, as pressing its button would
+#[tauri::command]
+pub fn run_notification_action(
+    app: AppHandle,
+    notification_id: String,
+    action: String,
+) -> Result<(), String> {
+    let category = {
+        let db = app.state::<Database>();
+        let conn = db.conn()?;
+        Notifications(&conn).get(&notification_id)?.category
+    };
+    handle_action(&app, &notification_id, category, &action)
+}
+
+#[tauri::command]
+pub fn get_notification_settings(
+    settings: State<'_, SettingsStore>,
+) -> Result<NotificationSettings, String> {
+    Ok(settings.get().notifications)
+}
+
+/// Mute or unmute one category of desktop notifications
+#[tauri::command]
+pub fn set_notification_muted(
+    settings: State<'_, SettingsStore>,
+    category: NotificationCategory,
+    muted: bool,
+) -> Result<NotificationSettings, String> {
+    let mut updated = settings.get();
+    if muted {
+        updated.notifications.muted.insert(category);
+    } else {
+        updated.notifications.muted.remove(&category);
+    }
+    let notifications = updated.notifications.clone();
+    settings.set(updated)?;
+    info!("Notifications for {:?} muted: {}", category, muted);
+    Ok(notifications)
+}
+
+fn update_settings(
+    settings: &SettingsStore,
+    change: impl FnOnce(&mut NotificationSettings),
+) -> Result<NotificationSettings, String> {
+    let mut updated = settings.get();
+    change(&mut updated.notifications);
+    let now = Utc::now().timestamp();
+    updated
+        .notifications
+        .snoozed_symbols
+        .retain(|_, until| *until > now);
+    let notifications = updated.notifications.clone();
+    settings.set(updated)?;
+    Ok(notifications)
+}
+
+/// Hold back every toast, or stop doing so
+#[tauri::command]
+pub fn set_mute_all(
+    settings: State<'_, SettingsStore>,
+    muted: bool,
+) -> Result<NotificationSettings, String> {
+    let notifications = update_settings(&settings, |n| n.mute_all = muted)?;
+    info!("All notifications muted: {}", muted);
+    Ok(notifications)
+}
+
+/// Set the daily quiet hours, or clear them with `None`
+#[tauri::command]
+pub fn set_quiet_hours(
+    settings: State<'_, SettingsStore>,
+    quiet_hours: Option<QuietHours>,
+) -> Result<NotificationSettings, String> {
+    update_settings(&settings, |n| n.quiet_hours = quiet_hours)
+}
+
+/// Hold back alerts on a symbol for `hours`
+#[tauri::command]
+pub fn snooze_symbol(
+    settings: State<'_, SettingsStore>,
+    symbol: String,
+    hours: u32,
+) -> Result<NotificationSettings, String> {
+    let symbol = symbol.trim().to_uppercase();
+    if symbol.is_empty() {
+        return Err("Symbol is required".to_string());
+    }
+    if !(1..=MAX_SNOOZE_HOURS).contains(&hours) {
+        return Err(format!(
+            "Snooze must be between 1 and {} hours, got {}",
+            MAX_SNOOZE_HOURS, hours
+        ));
+    }
+    let until = Utc::now().timestamp() + i64::from(hours) * 3600;
+    let notifications = update_settings(&settings, |n| {
+        n.snoozed_symbols.insert(symbol.clone(), until);
+    })?;
+    info!("Alerts on {} snoozed for {} hours", symbol, hours);
+    Ok(notifications)
+}
+
+#[tauri::command]
+pub fn unsnooze_symbol(
+    settings: State<'_, SettingsStore>,
+    symbol: String,
+) -> Result<NotificationSettings, String> {
+    update_settings(&settings, |n| {
+        n.snoozed_symbols.remove(&symbol.trim().to_uppercase());
+    })
+}
+
+/// Notifications in the notification center, newest first
+#[tauri::command]
+pub fn list_notifications(
+    db: State<'_, Database>,
+    filter: Option<NotificationFilter>,
+) -> Result<Vec<StoredNotification>, String> {
+    let conn = db.conn()?;
+    Notifications(&conn).list(&filter.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_unread_notification_count(db: State<'_, Database>) -> Result<usize, String> {
+    let conn = db.conn()?;
+    Notifications(&conn).unread_count()
+}
+
+/// Mark unread notifications read, returning how many changed
+#[tauri::command]
+pub fn mark_read(db: State<'_, Database>, ids: Vec<String>) -> Result<usize, String> {
+    let conn = db.conn()?;
+    Notifications(&conn).set_state(&ids, NotificationState::Read)
+}
+
+#[tauri::command]
+pub fn mark_unread(db: State<'_, Database>, ids: Vec<String>) -> Result<usize, String> {
+    let conn = db.conn()?;
+    Notifications(&conn).set_state(&ids, NotificationState::Unread)
+}
+
+#[tauri::command]
+pub fn archive_notifications(db: State<'_, Database>, ids: Vec<String>) -> Result<usize, String> {
+    let conn = db.conn()?;
+    Notifications(&conn).set_state(&ids, NotificationState::Archived)
+}
+
+/// Delete notifications from the center, the given ones or else every read
+/// and archived one, returning how many went
+#[tauri::command]
+pub fn clear_notifications(
+    db: State<'_, Database>,
+    ids: Option<Vec<String>>,
+) -> Result<usize, String> {
+    let conn = db.conn()?;
+    let cleared = Notifications(&conn).clear(ids.as_deref())?;
+    info!("Cleared {} notifications", cleared);
+    Ok(cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_muted_categories_persist() {
+        let path = std::env::temp_dir().join(format!("ssi-notify-{}.json", std::process::id()));
+        let store = SettingsStore::load(path.clone());
+        assert!(!store
+            .get()
+            .notifications
+            .is_muted(NotificationCategory::News));
+
+        let mut updated = store.get();
+        updated
+            .notifications
+            .muted
+            .insert(NotificationCategory::News);
+        store.set(updated).unwrap();
+
+        let reloaded = SettingsStore::load(path.clone()).get().notifications;
+        assert!(reloaded.is_muted(NotificationCategory::News));
+        assert!(!reloaded.is_muted(NotificationCategory::PriceAlert));
+        assert_eq!(
+            serde_json::to_string(&reloaded).unwrap(),
+            r#"{"muted":["news"],"mute_all":false,"quiet_hours":null,"snoozed_symbols":{}}"#
+        );
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_quiet_hours_and_snoozes() {
+        let at = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        let mut settings = NotificationSettings {
+            quiet_hours: Some(QuietHours {
+                start: "23:00".to_string(),
+                end: "07:00".to_string(),
+            }),
+            ..Default::default()
+        };
+        assert!(!settings.allows(NotificationCategory::PriceAlert, at(23, 30)));
+        assert!(!settings.allows(NotificationCategory::PriceAlert, at(6, 59)));
+        assert!(settings.allows(NotificationCategory::PriceAlert, at(7, 0)));
+        settings.mute_all = true;
+        assert!(!settings.allows(NotificationCategory::System, at(12, 0)));
+
+        settings.snoozed_symbols.insert("AAPL".to_string(), 1_000);
+        assert!(settings.is_snoozed("aapl", 999));
+        assert!(!settings.is_snoozed("AAPL", 1_000));
+        assert!(!settings.is_snoozed("SH600519", 0));
+
+        settings.quiet_hours.as_mut().unwrap().end = "23:00".to_string();
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_action_routes() {
+        assert_eq!(route(DEFAULT_ACTION), ActionRoute::Open);
+        assert_eq!(route("__closed"), ActionRoute::Closed);
+        assert_eq!(
+            route(&NotificationAction::dismiss().id),
+            ActionRoute::Dismiss
+        );
+        assert_eq!(
+            route(&NotificationAction::open_chart("SH600519").id),
+            ActionRoute::OpenChart("SH600519")
+        );
+        assert_eq!(
+            route(&NotificationAction::snooze_alert("alert_01:x", 60, "暂停").id),
+            ActionRoute::SnoozeAlert {
+                alert_id: "alert_01:x",
+                minutes: 60
+            }
+        );
+        assert_eq!(route("snooze_alert:alert_01"), ActionRoute::Frontend);
+        assert_eq!(route("buy"), ActionRoute::Frontend);
+    }
+}
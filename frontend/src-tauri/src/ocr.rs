@@ -0,0 +1,466 @@
+//! Importing transactions and watchlists from screenshots.
+//!
+//! Users often only have a screenshot of a broker statement or a table on a
+//! web page. The image is run through a `tesseract` sidecar (Chinese and
+//! English models) and its word boxes are rebuilt into a table: words are
+//! grouped into lines by vertical overlap, neighbouring words into cells by
+//! horizontal gap, and cells into columns by overlapping x ranges across
+//! lines. The first all-text row is taken as the header; recognised header
+//! names (证券代码, 成交价格, …) map columns onto import fields.
+
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::costs::Side;
+use crate::utils::sidecar_path;
+
+/// Tesseract languages; chi_sim covers the Chinese headers and names
+const OCR_LANGUAGES: &str = "chi_sim+eng";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Word {
+    text: String,
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    conf: f64,
+}
+
+impl Word {
+    fn right(&self) -> i32 {
+        self.left + self.width
+    }
+
+    fn bottom(&self) -> i32 {
+        self.top + self.height
+    }
+
+    fn center_y(&self) -> i32 {
+        self.top + self.height / 2
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cell {
+    text: String,
+    left: i32,
+    right: i32,
+    conf: f64,
+}
+
+/// One row of an imported statement; fields the image had no column for stay empty
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OcrRecord {
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub date: Option<String>,
+    pub side: Option<Side>,
+    pub quantity: Option<f64>,
+    pub price: Option<f64>,
+    pub amount: Option<f64>,
+    /// Mean OCR confidence of the row's words, 0–100
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrImport {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub records: Vec<OcrRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImportField {
+    Symbol,
+    Name,
+    Date,
+    Side,
+    Quantity,
+    Price,
+    Amount,
+}
+
+/// Header keywords for each field, checked in order so 成交金额 is not taken for a price
+const HEADER_KEYWORDS: &[(&str, ImportField)] = &[
+    ("代码", ImportField::Symbol),
+    ("symbol", ImportField::Symbol),
+    ("ticker", ImportField::Symbol),
+    ("名称", ImportField::Name),
+    ("name", ImportField::Name),
+    ("日期", ImportField::Date),
+    ("时间", ImportField::Date),
+    ("date", ImportField::Date),
+    ("买卖", ImportField::Side),
+    ("方向", ImportField::Side),
+    ("操作", ImportField::Side),
+    ("side", ImportField::Side),
+    ("金额", ImportField::Amount),
+    ("amount", ImportField::Amount),
+    ("数量", ImportField::Quantity),
+    ("股数", ImportField::Quantity),
+    ("quantity", ImportField::Quantity),
+    ("qty", ImportField::Quantity),
+    ("价", ImportField::Price),
+    ("price", ImportField::Price),
+];
+
+/// Word boxes from tesseract's TSV output
+fn parse_tsv(tsv: &str) -> Vec<Word> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 12 || cols[0] != "5" {
+                return None;
+            }
+            let text = cols[11].trim();
+            if text.is_empty() {
+                return None;
+            }
+            Some(Word {
+                text: text.to_string(),
+                left: cols[6].parse().ok()?,
+                top: cols[7].parse().ok()?,
+                width: cols[8].parse().ok()?,
+                height: cols[9].parse().ok()?,
+                conf: cols[10].parse().unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+/// Words grouped into visual lines, each sorted left to right
+fn group_lines(mut words: Vec<Word>) -> Vec<Vec<Word>> {
+    words.sort_by_key(|w| w.top);
+    let mut lines: Vec<(i32, i32, Vec<Word>)> = Vec::new();
+    for word in words {
+        match lines
+            .iter_mut()
+            .find(|(top, bottom, _)| (*top..*bottom).contains(&word.center_y()))
+        {
+            Some((top, bottom, line)) => {
+                *top = (*top).min(word.top);
+                *bottom = (*bottom).max(word.bottom());
+                line.push(word);
+            }
+            None => lines.push((word.top, word.bottom(), vec![word])),
+        }
+    }
+    lines
+        .into_iter()
+        .map(|(_, _, mut line)| {
+            line.sort_by_key(|w| w.left);
+            line
+        })
+        .collect()
+}
+
+/// Join two fragments, with a space only between Latin letters or digits
+fn join_text(left: &mut String, right: &str) {
+    let needs_space = left
+        .chars()
+        .last()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        && right
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric());
+    if needs_space {
+        left.push(' ');
+    }
+    left.push_str(right);
+}
+
+/// Words of a line merged into cells; a gap wider than half the text height starts a new cell
+fn group_cells(line: &[Word]) -> Vec<Cell> {
+    let mut heights: Vec<i32> = line.iter().map(|w| w.height).collect();
+    heights.sort_unstable();
+    let gap = (heights.get(heights.len() / 2).copied().unwrap_or(0) / 2).max(1);
+
+    let mut cells: Vec<(Cell, usize)> = Vec::new();
+    for word in line {
+        match cells.last_mut() {
+            Some((cell, count)) if word.left - cell.right <= gap => {
+                join_text(&mut cell.text, &word.text);
+                cell.right = cell.right.max(word.right());
+                cell.conf += word.conf;
+                *count += 1;
+            }
+            _ => cells.push((
+                Cell {
+                    text: word.text.clone(),
+                    left: word.left,
+                    right: word.right(),
+                    conf: word.conf,
+                },
+                1,
+            )),
+        }
+    }
+    cells
+        .into_iter()
+        .map(|(mut cell, count)| {
+            cell.conf /= count as f64;
+            cell
+        })
+        .collect()
+}
+
+/// Column spans: the union of overlapping cell ranges across all lines
+fn column_spans(lines: &[Vec<Cell>]) -> Vec<(i32, i32)> {
+    let mut ranges: Vec<(i32, i32)> = lines.iter().flatten().map(|c| (c.left, c.right)).collect();
+    ranges.sort_unstable();
+    let mut spans: Vec<(i32, i32)> = Vec::new();
+    for (left, right) in ranges {
+        match spans.last_mut() {
+            Some(span) if left <= span.1 => span.1 = span.1.max(right),
+            _ => spans.push((left, right)),
+        }
+    }
+    spans
+}
+
+/// Cells laid out on the column grid, with the mean confidence of each row
+fn build_table(lines: &[Vec<Cell>]) -> Vec<(Vec<String>, f64)> {
+    let spans = column_spans(lines);
+    lines
+        .iter()
+        .map(|line| {
+            let mut row = vec![String::new(); spans.len()];
+            for cell in line {
+                let center = (cell.left + cell.right) / 2;
+                if let Some(column) = spans
+                    .iter()
+                    .position(|(left, right)| (*left..=*right).contains(&center))
+                {
+                    join_text(&mut row[column], &cell.text);
+                }
+            }
+            let confidence = line.iter().map(|c| c.conf).sum::<f64>() / line.len().max(1) as f64;
+            (row, confidence)
+        })
+        .collect()
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    let cleaned: String = text
+        .chars()
+        .filter(|c| !matches!(c, ',' | '，' | '¥' | '￥' | '$' | ' '))
+        .collect();
+    cleaned.parse().ok()
+}
+
+/// A six-digit A-share code in the text, with its exchange prefix
+fn parse_symbol(text: &str) -> Option<String> {
+    let digits: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.len() != 6 {
+        return None;
+    }
+    let prefix = match digits.as_bytes()[0] {
+        b'5' | b'6' | b'9' => "SH",
+        b'4' | b'8' => "BJ",
+        _ => "SZ",
+    };
+    Some(format!("{}{}", prefix, digits))
+}
+
+fn parse_side(text: &str) -> Option<Side> {
+    let text = text.to_lowercase();
+    if text.contains('买') || text.contains("buy") {
+        Some(Side::Buy)
+    } else if text.contains('卖') || text.contains("sell") {
+        Some(Side::Sell)
+    } else {
+        None
+    }
+}
+
+fn header_field(header: &str) -> Option<ImportField> {
+    let header = header.to_lowercase();
+    HEADER_KEYWORDS
+        .iter()
+        .find(|(keyword, _)| header.contains(keyword))
+        .map(|(_, field)| *field)
+}
+
+fn to_record(row: &[String], fields: &[Option<ImportField>], confidence: f64) -> OcrRecord {
+    let mut record = OcrRecord {
+        confidence,
+        ..OcrRecord::default()
+    };
+    for (text, field) in row.iter().zip(fields) {
+        if text.is_empty() {
+            continue;
+        }
+        match field {
+            Some(ImportField::Symbol) => record.symbol = parse_symbol(text),
+            Some(ImportField::Name) => record.name = Some(text.clone()),
+            Some(ImportField::Date) => record.date = Some(text.clone()),
+            Some(ImportField::Side) => record.side = parse_side(text),
+            Some(ImportField::Quantity) => record.quantity = parse_number(text),
+            Some(ImportField::Price) => record.price = parse_number(text),
+            Some(ImportField::Amount) => record.amount = parse_number(text),
+            None => {}
+        }
+    }
+    // Watchlist screenshots often have no header; take any code-like cell
+    if record.symbol.is_none() {
+        record.symbol = row.iter().find_map(|text| parse_symbol(text));
+    }
+    record
+}
+
+/// Rebuild a table from tesseract TSV output
+fn reconstruct(tsv: &str) -> OcrImport {
+    let lines: Vec<Vec<Cell>> = group_lines(parse_tsv(tsv))
+        .iter()
+        .map(|line| group_cells(line))
+        .collect();
+    let table = build_table(&lines);
+
+    // The header is the first row with several cells and no numbers; rows above it are titles
+    let header_index = table.iter().position(|(row, _)| {
+        row.iter().filter(|t| !t.is_empty()).count() >= 2
+            && row.iter().all(|t| parse_number(t).is_none())
+    });
+    let (headers, body) = match header_index {
+        Some(i) => (table[i].0.clone(), &table[i + 1..]),
+        None => (Vec::new(), &table[..]),
+    };
+    // Columns that only held dropped title text
+    let used: Vec<bool> = (0..headers.len().max(body.first().map_or(0, |(r, _)| r.len())))
+        .map(|column| {
+            headers.get(column).is_some_and(|h| !h.is_empty())
+                || body.iter().any(|(row, _)| !row[column].is_empty())
+        })
+        .collect();
+    let keep = |row: &[String]| -> Vec<String> {
+        row.iter()
+            .zip(&used)
+            .filter(|(_, used)| **used)
+            .map(|(text, _)| text.clone())
+            .collect()
+    };
+    let headers = keep(&headers);
+    let body: Vec<(Vec<String>, f64)> = body
+        .iter()
+        .map(|(row, confidence)| (keep(row), *confidence))
+        .collect();
+    let fields: Vec<Option<ImportField>> = headers.iter().map(|h| header_field(h)).collect();
+
+    let records = body
+        .iter()
+        .map(|(row, confidence)| to_record(row, &fields, *confidence))
+        .filter(|r| r.symbol.is_some() || r.name.is_some())
+        .collect();
+    OcrImport {
+        headers,
+        rows: body.into_iter().map(|(row, _)| row).collect(),
+        records,
+    }
+}
+
+fn run_tesseract(image_path: &Path) -> Result<String, String> {
+    let output = Command::new(sidecar_path("tesseract"))
+        .arg(image_path)
+        .args(["stdout", "-l", OCR_LANGUAGES, "--psm", "6", "tsv"])
+        .output()
+        .map_err(|e| format!("Failed to run tesseract: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// OCR a screenshot of a statement or table into rows for transaction or watchlist import
+#[tauri::command]
+pub async fn ocr_import(image_path: String) -> Result<OcrImport, String> {
+    let path = Path::new(&image_path).to_path_buf();
+    if !path.exists() {
+        return Err(format!("Image not found: {}", image_path));
+    }
+    let tsv = tauri::async_runtime::spawn_blocking(move || run_tesseract(&path))
+        .await
+        .map_err(|e| format!("OCR task failed: {}", e))??;
+    Ok(reconstruct(&tsv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tsv(words: &[(&str, i32, i32, i32)]) -> String {
+        let mut out = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext\n".to_string();
+        for (text, left, top, width) in words {
+            out.push_str(&format!(
+                "5\t1\t1\t1\t1\t1\t{}\t{}\t{}\t20\t90\t{}\n",
+                left, top, width, text
+            ));
+        }
+        out
+    }
+
+    #[test]
+    fn test_reconstructs_statement_table() {
+        let tsv = tsv(&[
+            ("交割单", 300, 0, 60),
+            ("成交", 0, 40, 40),
+            ("日期", 42, 40, 40),
+            ("证券代码", 200, 41, 80),
+            ("买卖", 400, 40, 40),
+            ("成交价格", 500, 42, 80),
+            ("数量", 700, 40, 40),
+            ("2024-05-06", 0, 80, 100),
+            ("600519", 200, 82, 70),
+            ("证券", 400, 80, 40),
+            ("买入", 442, 80, 40),
+            ("1,650.00", 500, 81, 80),
+            ("100", 700, 80, 30),
+            ("2024-05-07", 0, 120, 100),
+            ("000001", 200, 120, 70),
+            ("卖出", 400, 121, 40),
+            ("10.52", 510, 120, 50),
+            ("2000", 700, 120, 40),
+        ]);
+        let import = reconstruct(&tsv);
+        assert_eq!(
+            import.headers,
+            ["成交日期", "证券代码", "买卖", "成交价格", "数量"]
+        );
+        assert_eq!(import.rows.len(), 2);
+        assert_eq!(import.rows[0][2], "证券买入");
+
+        let first = &import.records[0];
+        assert_eq!(first.symbol.as_deref(), Some("SH600519"));
+        assert_eq!(first.date.as_deref(), Some("2024-05-06"));
+        assert_eq!(first.side, Some(Side::Buy));
+        assert_eq!(first.price, Some(1650.0));
+        assert_eq!(first.quantity, Some(100.0));
+        let second = &import.records[1];
+        assert_eq!(second.symbol.as_deref(), Some("SZ000001"));
+        assert_eq!(second.side, Some(Side::Sell));
+    }
+
+    #[test]
+    fn test_headerless_watchlist() {
+        let import = reconstruct(&tsv(&[
+            ("贵州茅台", 0, 0, 80),
+            ("600519", 200, 0, 70),
+            ("平安银行", 0, 40, 80),
+            ("000001", 200, 40, 70),
+        ]));
+        assert!(import.headers.is_empty());
+        let symbols: Vec<_> = import
+            .records
+            .iter()
+            .filter_map(|r| r.symbol.as_deref())
+            .collect();
+        assert_eq!(symbols, ["SH600519", "SZ000001"]);
+    }
+}
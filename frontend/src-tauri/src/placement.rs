@@ -0,0 +1,456 @@
+//! Window placement across monitors.
+//!
+//! Chart windows open on the monitor the main window is on, cascading from
+//! its top-left corner so several charts don't stack exactly. The ticker bar
+//! is a borderless always-on-top strip pinned to the top or bottom edge of
+//! the monitor picked in [`PlacementSettings`], or of the main window's
+//! monitor while that one isn't connected.
+//!
+//! Monitors come and go as laptops dock and undock, and the windows on a
+//! monitor that went away are left off-screen by the OS. Every
+//! [`MONITOR_CHECK`] the monitor layout is compared with the last one seen;
+//! when it changed, any window that is no longer visible is moved onto the
+//! monitor that overlaps it most, or else the primary, and the ticker bar is
+//! pinned again. Workspaces go through the same check when they are loaded,
+//! so geometry saved on another monitor setup never restores off-screen.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{
+    AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewUrl, WebviewWindow,
+    WebviewWindowBuilder,
+};
+
+use crate::settings::SettingsStore;
+
+/// Interval between checks of the monitor layout
+const MONITOR_CHECK: Duration = Duration::from_secs(3);
+
+pub const CHART_WINDOW_PREFIX: &str = "chart-";
+pub const TICKER_BAR_LABEL: &str = "ticker-bar";
+
+/// Logical size of a new chart window
+const CHART_SIZE: (f64, f64) = (960.0, 640.0);
+/// Logical offset between cascaded chart windows
+const CASCADE_STEP: i32 = 32;
+/// Chart windows cascade this many steps before starting over
+const CASCADE_STEPS: i32 = 8;
+/// Physical pixels of a window that must show on a monitor to count as visible
+const MIN_VISIBLE: i32 = 64;
+
+/// Monitor edge the ticker bar is pinned to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    #[default]
+    Top,
+    Bottom,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlacementSettings {
+    /// Monitor name the ticker bar is pinned to; the main window's when unset
+    pub ticker_monitor: Option<String>,
+    pub ticker_edge: Edge,
+    /// Logical height of the ticker bar
+    pub ticker_height: u32,
+}
+
+impl Default for PlacementSettings {
+    fn default() -> Self {
+        Self {
+            ticker_monitor: None,
+            ticker_edge: Edge::Top,
+            ticker_height: 32,
+        }
+    }
+}
+
+impl PlacementSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(16..=200).contains(&self.ticker_height) {
+            return Err("Ticker bar height must be between 16 and 200".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A rectangle in physical pixels of the virtual desktop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    /// Width and height of the overlap with `other`, zero when apart
+    fn overlap(&self, other: &Rect) -> (i32, i32) {
+        let width = self.right().min(other.right()) - self.x.max(other.x);
+        let height = self.bottom().min(other.bottom()) - self.y.max(other.y);
+        (width.max(0), height.max(0))
+    }
+
+    fn overlap_area(&self, other: &Rect) -> i64 {
+        let (width, height) = self.overlap(other);
+        width as i64 * height as i64
+    }
+
+    /// Moved, and shrunk if need be, to lie within `area`
+    fn clamped_into(&self, area: &Rect) -> Rect {
+        let width = self.width.min(area.width);
+        let height = self.height.min(area.height);
+        Rect {
+            x: self.x.clamp(area.x, area.right() - width as i32),
+            y: self.y.clamp(area.y, area.bottom() - height as i32),
+            width,
+            height,
+        }
+    }
+}
+
+/// A connected monitor as placement sees it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Screen {
+    pub name: Option<String>,
+    pub area: Rect,
+    pub scale_factor: f64,
+}
+
+impl Screen {
+    fn of(monitor: &Monitor) -> Self {
+        let position = monitor.position();
+        let size = monitor.size();
+        Self {
+            name: monitor.name().cloned(),
+            area: Rect {
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+            },
+            scale_factor: monitor.scale_factor(),
+        }
+    }
+}
+
+/// Whether enough of `rect` shows on some screen to grab it
+fn visible(rect: &Rect, screens: &[Screen]) -> bool {
+    screens.iter().any(|screen| {
+        let (width, height) = rect.overlap(&screen.area);
+        width >= MIN_VISIBLE && height >= MIN_VISIBLE
+    })
+}
+
+/// Where a window at `rect` belongs: unchanged when visible, otherwise on
+/// the screen it overlaps most, or the first screen
+pub fn rescue(rect: Rect, screens: &[Screen]) -> Rect {
+    if screens.is_empty() || visible(&rect, screens) {
+        return rect;
+    }
+    let target = screens
+        .iter()
+        .max_by_key(|screen| rect.overlap_area(&screen.area))
+        .filter(|screen| rect.overlap_area(&screen.area) > 0)
+        .unwrap_or(&screens[0]);
+    rect.clamped_into(&target.area)
+}
+
+/// Position of the `index`th chart window cascading over `anchor` on `screen`
+fn cascade(anchor: &Rect, screen: &Screen, index: usize, size: (u32, u32)) -> Rect {
+    let step =
+        (CASCADE_STEP as f64 * screen.scale_factor) as i32 * (index as i32 % CASCADE_STEPS + 1);
+    Rect {
+        x: anchor.x + step,
+        y: anchor.y + step,
+        width: size.0,
+        height: size.1,
+    }
+    .clamped_into(&screen.area)
+}
+
+/// Strip along `edge` of `screen`, `height` logical pixels tall
+fn ticker_strip(screen: &Screen, edge: Edge, height: u32) -> Rect {
+    let height = ((height as f64 * screen.scale_factor) as u32).min(screen.area.height);
+    let y = match edge {
+        Edge::Top => screen.area.y,
+        Edge::Bottom => screen.area.bottom() - height as i32,
+    };
+    Rect {
+        x: screen.area.x,
+        y,
+        width: screen.area.width,
+        height,
+    }
+}
+
+pub fn screens(app: &AppHandle) -> Vec<Screen> {
+    match app.available_monitors() {
+        Ok(monitors) => monitors.iter().map(Screen::of).collect(),
+        Err(e) => {
+            warn!("Failed to list monitors: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn window_rect(window: &WebviewWindow) -> Result<Rect, tauri::Error> {
+    let position = window.outer_position()?;
+    let size = window.outer_size()?;
+    Ok(Rect {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+pub fn set_rect(window: &WebviewWindow, rect: &Rect) -> Result<(), tauri::Error> {
+    window.set_position(PhysicalPosition {
+        x: rect.x,
+        y: rect.y,
+    })?;
+    window.set_size(PhysicalSize {
+        width: rect.width,
+        height: rect.height,
+    })
+}
+
+/// Screen the main window is on, or the first one
+fn main_screen(app: &AppHandle, screens: &[Screen]) -> Option<(Screen, Option<Rect>)> {
+    let main = app
+        .get_webview_window("main")
+        .and_then(|window| window_rect(&window).ok());
+    let screen = main
+        .and_then(|rect| {
+            screens
+                .iter()
+                .filter(|screen| rect.overlap_area(&screen.area) > 0)
+                .max_by_key(|screen| rect.overlap_area(&screen.area))
+        })
+        .or(screens.first())?;
+    Some((screen.clone(), main))
+}
+
+/// Move windows left off-screen back onto a connected monitor
+pub fn rescue_windows(app: &AppHandle) {
+    let screens = screens(app);
+    for (label, window) in app.webview_windows() {
+        if label == TICKER_BAR_LABEL || window.is_maximized().unwrap_or(false) {
+            continue;
+        }
+        let Ok(rect) = window_rect(&window) else {
+            continue;
+        };
+        let rescued = rescue(rect, &screens);
+        if rescued != rect {
+            info!("Moving window {} back on screen", label);
+            if let Err(e) = set_rect(&window, &rescued) {
+                warn!("Failed to move window {}: {}", label, e);
+            }
+        }
+    }
+}
+
+/// Pin the ticker bar, if open, to its monitor edge
+pub fn pin_ticker_bar(app: &AppHandle) {
+    let Some(window) = app.get_webview_window(TICKER_BAR_LABEL) else {
+        return;
+    };
+    let settings = app.state::<SettingsStore>().get().placement;
+    let screens = screens(app);
+    let screen = screens
+        .iter()
+        .find(|screen| settings.ticker_monitor.is_some() && screen.name == settings.ticker_monitor)
+        .cloned()
+        .or_else(|| main_screen(app, &screens).map(|(screen, _)| screen));
+    let Some(screen) = screen else {
+        return;
+    };
+    let strip = ticker_strip(&screen, settings.ticker_edge, settings.ticker_height);
+    if let Err(e) = set_rect(&window, &strip) {
+        warn!("Failed to pin the ticker bar: {}", e);
+    }
+}
+
+/// Last monitor layout seen, to notice hot-plugging
+#[derive(Default)]
+pub struct MonitorLayout(Mutex<Vec<Screen>>);
+
+/// Watch for monitors being connected, disconnected or rearranged
+pub fn start_monitor_watch(app: &AppHandle) {
+    let handle = app.clone();
+    *app.state::<MonitorLayout>().0.lock().unwrap() = screens(app);
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(MONITOR_CHECK).await;
+            let current = screens(&handle);
+            let layout = handle.state::<MonitorLayout>();
+            {
+                let mut last = layout.0.lock().unwrap();
+                if current.is_empty() || *last == current {
+                    continue;
+                }
+                *last = current.clone();
+            }
+            info!("Monitor layout changed: {} connected", current.len());
+            rescue_windows(&handle);
+            pin_ticker_bar(&handle);
+        }
+    });
+}
+
+/// Label of a symbol's chart window; labels can't hold the `.` of `BRK.B`
+fn chart_label(symbol: &str) -> String {
+    format!("{}{}", CHART_WINDOW_PREFIX, symbol.replace('.', "_"))
+}
+
+/// Open a symbol's chart in a window of its own on the main window's monitor
+#[tauri::command]
+pub fn open_chart_window(app: AppHandle, symbol: String) -> Result<(), String> {
+    let symbol = crate::symbols::symbol_key(&symbol);
+    let label = chart_label(&symbol);
+    if let Some(window) = app.get_webview_window(&label) {
+        return window
+            .set_focus()
+            .map_err(|e| format!("Failed to focus chart window: {}", e));
+    }
+    let open = app
+        .webview_windows()
+        .keys()
+        .filter(|label| label.starts_with(CHART_WINDOW_PREFIX))
+        .count();
+    let window = WebviewWindowBuilder::new(
+        &app,
+        label,
+        WebviewUrl::App(format!("chart/{}", symbol).into()),
+    )
+    .title(symbol.clone())
+    .inner_size(CHART_SIZE.0, CHART_SIZE.1)
+    .visible(false)
+    .build()
+    .map_err(|e| format!("Failed to open chart window: {}", e))?;
+
+    if let Some((screen, main)) = main_screen(&app, &screens(&app)) {
+        let size = window
+            .outer_size()
+            .map(|size| (size.width, size.height))
+            .unwrap_or_default();
+        let anchor = main.unwrap_or(screen.area);
+        if let Err(e) = set_rect(&window, &cascade(&anchor, &screen, open, size)) {
+            warn!("Failed to place chart window: {}", e);
+        }
+    }
+    window
+        .show()
+        .and_then(|_| window.set_focus())
+        .map_err(|e| format!("Failed to show chart window: {}", e))
+}
+
+/// Open the ticker bar pinned to its monitor edge, or close it when open
+#[tauri::command]
+pub fn toggle_ticker_bar(app: AppHandle) -> Result<bool, String> {
+    if let Some(window) = app.get_webview_window(TICKER_BAR_LABEL) {
+        window
+            .close()
+            .map_err(|e| format!("Failed to close the ticker bar: {}", e))?;
+        return Ok(false);
+    }
+    let window =
+        WebviewWindowBuilder::new(&app, TICKER_BAR_LABEL, WebviewUrl::App("ticker".into()))
+            .title("行情条")
+            .decorations(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .resizable(false)
+            .visible(false)
+            .build()
+            .map_err(|e| format!("Failed to open the ticker bar: {}", e))?;
+    pin_ticker_bar(&app);
+    window
+        .show()
+        .map_err(|e| format!("Failed to show the ticker bar: {}", e))?;
+    Ok(true)
+}
+
+/// Connected monitors, for picking the ticker bar's
+#[tauri::command]
+pub fn list_monitors(app: AppHandle) -> Result<Vec<Screen>, String> {
+    Ok(screens(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(x: i32, y: i32, width: u32, height: u32) -> Screen {
+        Screen {
+            name: None,
+            area: Rect {
+                x,
+                y,
+                width,
+                height,
+            },
+            scale_factor: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_rescue_and_pin() {
+        let laptop = screen(0, 0, 1920, 1080);
+        let external = screen(1920, 0, 2560, 1440);
+        let window = Rect {
+            x: 2400,
+            y: 200,
+            width: 1200,
+            height: 800,
+        };
+        // Visible while the external monitor is connected
+        assert_eq!(rescue(window, &[laptop.clone(), external]), window);
+        // Pulled back onto the laptop once it is unplugged
+        assert_eq!(
+            rescue(window, &[laptop.clone()]),
+            Rect {
+                x: 720,
+                y: 200,
+                width: 1200,
+                height: 800
+            }
+        );
+        // A sliver on screen is not enough to grab
+        let sliver = Rect {
+            x: 1900,
+            y: 100,
+            ..window
+        };
+        assert_eq!(rescue(sliver, &[laptop.clone()]).x, 720);
+
+        let bottom = ticker_strip(&laptop, Edge::Bottom, 32);
+        assert_eq!((bottom.y, bottom.width, bottom.height), (1048, 1920, 32));
+
+        let anchor = Rect {
+            x: 100,
+            y: 100,
+            width: 1400,
+            height: 900,
+        };
+        assert_eq!(cascade(&anchor, &laptop, 0, (960, 640)).x, 132);
+        // Cascading never walks a window off its monitor
+        let far = cascade(&anchor, &laptop, 7, (960, 640));
+        assert!(far.right() <= 1920 && far.bottom() <= 1080);
+    }
+}
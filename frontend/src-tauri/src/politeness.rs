@@ -0,0 +1,462 @@
+//! Per-source politeness policies enforced on every outgoing request.
+//!
+//! Each data source declares how often it may be hit, how many requests may
+//! be in flight, which endpoints may be called and the user agent to send.
+//! Requests go through the [`PolicyEngine`], which refuses sources without a
+//! policy and endpoints outside it, and spaces requests out by reserving the
+//! next free slot for the source, so a new provider or a runaway loop cannot
+//! hammer a site. Each source has its own HTTP client so it can be routed
+//! through its own proxy and TLS policy.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use log::info;
+use serde::Serialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::proxy::{ProxyRoute, ProxySettings};
+use crate::signing::{AuthScheme, CredentialVault};
+use crate::tls::{self, PinFailures, TlsSettings};
+
+/// Browser-like agent for sources that reject unknown clients
+const BROWSER_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0 Safari/537.36";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcePolicy {
+    pub id: &'static str,
+    /// Minimum spacing between two requests to the source
+    pub min_interval_ms: u64,
+    pub max_concurrent: usize,
+    /// URL prefixes (host and path, no scheme) the source may be called on
+    pub allowed_endpoints: &'static [&'static str],
+    pub user_agent: &'static str,
+    /// Referer sent with every request, for sources that refuse requests without one
+    pub referer: Option<&'static str>,
+    /// Terms or robots.txt constraints the policy was written against
+    pub notes: &'static str,
+    /// How requests are authenticated
+    pub auth: AuthScheme,
+}
+
+pub const SOURCE_POLICIES: &[SourcePolicy] = &[
+    SourcePolicy {
+        id: "eastmoney",
+        min_interval_ms: 500,
+        max_concurrent: 2,
+        allowed_endpoints: &[
+            "push2.eastmoney.com/api/qt/",
+            "push2his.eastmoney.com/api/qt/",
+            "emweb.securities.eastmoney.com/PC_HSF10/",
+            "np-weblist.eastmoney.com/comm/web/",
+            "finance.eastmoney.com/a/",
+            "np-anotice-stock.eastmoney.com/api/security/ann",
+            "np-cnotice-stock.eastmoney.com/api/content/ann",
+        ],
+        user_agent: BROWSER_USER_AGENT,
+        referer: None,
+        notes: "Public quote APIs; keep listing pulls to one per refresh",
+        auth: AuthScheme::None,
+    },
+    SourcePolicy {
+        id: "cls",
+        min_interval_ms: 5_000,
+        max_concurrent: 1,
+        allowed_endpoints: &["www.cls.cn/telegraph", "www.cls.cn/detail/"],
+        user_agent: BROWSER_USER_AGENT,
+        referer: None,
+        notes: "News pages only; robots.txt disallows /api for crawlers",
+        auth: AuthScheme::None,
+    },
+    SourcePolicy {
+        id: "xueqiu",
+        min_interval_ms: 2_000,
+        max_concurrent: 1,
+        allowed_endpoints: &["stock.xueqiu.com/v5/stock/", "xueqiu.com/"],
+        user_agent: BROWSER_USER_AGENT,
+        referer: None,
+        notes: "Logged-in session required; aggressive rate limiting",
+        auth: AuthScheme::None,
+    },
+    SourcePolicy {
+        id: "github",
+        min_interval_ms: 1_000,
+        max_concurrent: 1,
+        allowed_endpoints: &[
+            "api.github.com/repos/kevin12369/smart-stock-insider/releases",
+            "github.com/kevin12369/smart-stock-insider/releases/download/",
+            "objects.githubusercontent.com/",
+        ],
+        user_agent: BROWSER_USER_AGENT,
+        referer: None,
+        notes: "Update checks only; unauthenticated API is limited to 60 requests an hour",
+        auth: AuthScheme::None,
+    },
+    SourcePolicy {
+        id: "tencent",
+        min_interval_ms: 200,
+        max_concurrent: 2,
+        allowed_endpoints: &["qt.gtimg.cn/q="],
+        user_agent: BROWSER_USER_AGENT,
+        referer: None,
+        notes: "Batch quote endpoint; up to 60 symbols per request",
+        auth: AuthScheme::None,
+    },
+    SourcePolicy {
+        id: "sina",
+        min_interval_ms: 200,
+        max_concurrent: 2,
+        allowed_endpoints: &[
+            "hq.sinajs.cn/list=",
+            "hq.sinajs.cn/wskt?list=",
+            "money.finance.sina.com.cn/quotes_service/api/json_v2.php/CN_MarketData.getKLineData",
+            "feed.mix.sina.com.cn/api/roll/get",
+        ],
+        user_agent: BROWSER_USER_AGENT,
+        referer: Some("https://finance.sina.com.cn/"),
+        notes: "Batch quote endpoint, WebSocket feed, daily K-lines and roll news; rejects requests without a finance.sina.com.cn referer",
+        auth: AuthScheme::None,
+    },
+    SourcePolicy {
+        id: "yahoo",
+        min_interval_ms: 500,
+        max_concurrent: 1,
+        allowed_endpoints: &["query1.finance.yahoo.com/v8/finance/chart/"],
+        user_agent: BROWSER_USER_AGENT,
+        referer: None,
+        notes: "Chart endpoint only, one symbol per request; last-resort fallback",
+        auth: AuthScheme::None,
+    },
+];
+
+pub fn source_policy(id: &str) -> Result<&'static SourcePolicy, String> {
+    SOURCE_POLICIES
+        .iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| format!("No politeness policy for source: {}", id))
+}
+
+impl SourcePolicy {
+    pub fn allows(&self, url: &str) -> bool {
+        let Some(rest) = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+            .or_else(|| url.strip_prefix("wss://"))
+        else {
+            return false;
+        };
+        self.allowed_endpoints
+            .iter()
+            .any(|endpoint| rest.starts_with(endpoint))
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_millis(self.min_interval_ms)
+    }
+
+    /// Hosts of the allowed endpoints
+    pub fn hosts(&self) -> Vec<&'static str> {
+        let mut hosts: Vec<&'static str> = self
+            .allowed_endpoints
+            .iter()
+            .filter_map(|endpoint| endpoint.split('/').next())
+            .collect();
+        hosts.dedup();
+        hosts
+    }
+
+    /// Site root of the first endpoint, used for connectivity checks
+    pub fn probe_url(&self) -> String {
+        format!("https://{}/", self.hosts()[0])
+    }
+}
+
+pub struct PolicyEngine {
+    clients: RwLock<HashMap<&'static str, reqwest::Client>>,
+    /// Shared with the TLS verifiers of pinned clients
+    pin_failures: Arc<PinFailures>,
+    tls: RwLock<TlsSettings>,
+    permits: HashMap<&'static str, Semaphore>,
+    /// Earliest instant the next request to each source may start
+    next_slot: Mutex<HashMap<&'static str, Instant>>,
+}
+
+impl Default for PolicyEngine {
+    fn default() -> Self {
+        Self {
+            clients: RwLock::new(
+                SOURCE_POLICIES
+                    .iter()
+                    .map(|p| (p.id, reqwest::Client::new()))
+                    .collect(),
+            ),
+            permits: SOURCE_POLICIES
+                .iter()
+                .map(|p| (p.id, Semaphore::new(p.max_concurrent)))
+                .collect(),
+            next_slot: Mutex::new(HashMap::new()),
+            pin_failures: Arc::default(),
+            tls: RwLock::default(),
+        }
+    }
+}
+
+impl PolicyEngine {
+    /// Rebuild the per-source clients for new proxy routes or TLS policy
+    pub fn configure(&self, proxies: &ProxySettings, tls: &TlsSettings) -> Result<(), String> {
+        let clients = SOURCE_POLICIES
+            .iter()
+            .map(|p| {
+                let route = proxies.get(p.id).cloned().unwrap_or_default();
+                Ok((p.id, self.build_client(p, &route, tls)?))
+            })
+            .collect::<Result<HashMap<_, _>, String>>()?;
+        *self.clients.write().unwrap() = clients;
+        *self.tls.write().unwrap() = tls.clone();
+        Ok(())
+    }
+
+    fn build_client(
+        &self,
+        policy: &SourcePolicy,
+        route: &ProxyRoute,
+        tls: &TlsSettings,
+    ) -> Result<reqwest::Client, String> {
+        let mut builder = tls::apply(
+            route.client_builder()?,
+            tls,
+            &policy.hosts(),
+            &self.pin_failures,
+        );
+        if let Some(referer) = policy.referer {
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(
+                reqwest::header::REFERER,
+                reqwest::header::HeaderValue::from_static(referer),
+            );
+            builder = builder.default_headers(headers);
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))
+    }
+
+    pub fn pin_failures(&self) -> &PinFailures {
+        &self.pin_failures
+    }
+
+    /// TLS configuration for a socket to `source` opened outside the HTTP clients
+    pub fn stream_tls(&self, source: &str) -> Result<Arc<rustls::ClientConfig>, String> {
+        let policy = source_policy(source)?;
+        let tls = self.tls.read().unwrap();
+        Ok(Arc::new(tls::rustls_config(
+            &tls,
+            &policy.hosts(),
+            &self.pin_failures,
+        )))
+    }
+
+    /// A pin failure explains a connection error better than the TLS stack does
+    fn explain(&self, source: &str, url: &str, error: reqwest::Error) -> String {
+        let host = url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string));
+        match host.and_then(|host| self.pin_failures.last_for(&host)) {
+            Some(failure) if error.is_connect() => failure.describe(),
+            _ => format!("Failed to fetch from {}: {}", source, error),
+        }
+    }
+
+    fn client(&self, source: &str) -> reqwest::Client {
+        self.clients.read().unwrap()[source].clone()
+    }
+
+    /// Reserve the next request slot for a source, returning how long to wait for it
+    fn reserve(&self, policy: &SourcePolicy, now: Instant) -> Duration {
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let slot = next_slot
+            .get(policy.id)
+            .copied()
+            .filter(|slot| *slot > now)
+            .unwrap_or(now);
+        next_slot.insert(policy.id, slot + policy.interval());
+        slot - now
+    }
+
+    /// Wait until `source` may be called on `url`; hold the permit for the duration of the request
+    pub async fn admit(&self, source: &str, url: &str) -> Result<SemaphorePermit<'_>, String> {
+        let policy = source_policy(source)?;
+        if !policy.allows(url) {
+            return Err(format!(
+                "Endpoint not allowed by the {} policy: {}",
+                source, url
+            ));
+        }
+        self.wait_turn(policy).await
+    }
+
+    async fn wait_turn(&self, policy: &SourcePolicy) -> Result<SemaphorePermit<'_>, String> {
+        let permit = self.permits[policy.id]
+            .acquire()
+            .await
+            .map_err(|e| format!("Failed to acquire request slot: {}", e))?;
+        let wait = self.reserve(policy, Instant::now());
+        if !wait.is_zero() {
+            info!("Delaying {} request by {:?}", policy.id, wait);
+            tokio::time::sleep(wait).await;
+        }
+        Ok(permit)
+    }
+
+    /// GET `url` from `source` under its policy
+    pub async fn get(&self, source: &str, url: &str) -> Result<reqwest::Response, String> {
+        self.get_with_cookies(source, url, None).await
+    }
+
+    /// GET `url` from `source` under its policy, sending a login session's cookies
+    pub async fn get_with_cookies(
+        &self,
+        source: &str,
+        url: &str,
+        cookies: Option<&str>,
+    ) -> Result<reqwest::Response, String> {
+        let policy = source_policy(source)?;
+        if !matches!(policy.auth, AuthScheme::None) {
+            return Err(format!("Requests to {} must be signed", source));
+        }
+        let _permit = self.admit(source, url).await?;
+        let mut request = self
+            .client(source)
+            .get(url)
+            .header(reqwest::header::USER_AGENT, policy.user_agent);
+        if let Some(cookies) = cookies {
+            request = request.header(reqwest::header::COOKIE, cookies);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| self.explain(source, url, e))
+    }
+
+    /// Send a request to `source` under its policy, authenticated with its scheme.
+    ///
+    /// A rejected access token is dropped and the request retried once with a
+    /// fresh one.
+    pub async fn request(
+        &self,
+        source: &str,
+        method: reqwest::Method,
+        url: &str,
+        body: Option<Vec<u8>>,
+        credentials: &CredentialVault,
+    ) -> Result<reqwest::Response, String> {
+        let policy = source_policy(source)?;
+        let client = self.client(source);
+        let mut retried = false;
+        loop {
+            let token = credentials.prepare(self, policy).await?;
+            let _permit = self.admit(source, url).await?;
+            let mut builder = client
+                .request(method.clone(), url)
+                .header(reqwest::header::USER_AGENT, policy.user_agent);
+            if let Some(body) = &body {
+                builder = builder.body(body.clone());
+            }
+            let mut request = builder
+                .build()
+                .map_err(|e| format!("Failed to build request to {}: {}", source, e))?;
+            credentials.authorize(policy, &mut request, token.as_deref())?;
+            let response = client
+                .execute(request)
+                .await
+                .map_err(|e| self.explain(source, url, e))?;
+            let unauthorized = response.status() == reqwest::StatusCode::UNAUTHORIZED;
+            if unauthorized && token.is_some() && !retried {
+                credentials.invalidate_token(source);
+                retried = true;
+                continue;
+            }
+            return Ok(response);
+        }
+    }
+
+    /// POST a form to `source`, returning the body of a successful response
+    pub async fn post_form(
+        &self,
+        source: &str,
+        url: &str,
+        form: &[(&str, String)],
+    ) -> Result<String, String> {
+        let policy = source_policy(source)?;
+        let _permit = self.admit(source, url).await?;
+        self.client(source)
+            .post(url)
+            .header(reqwest::header::USER_AGENT, policy.user_agent)
+            .form(form)
+            .send()
+            .await
+            .map_err(|e| self.explain(source, url, e))?
+            .error_for_status()
+            .map_err(|e| format!("{} rejected the request: {}", source, e))?
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read response from {}: {}", source, e))
+    }
+
+    /// Reach the source's site root over `route`, or its configured route, under its pacing
+    pub async fn probe(
+        &self,
+        source: &str,
+        route: Option<&ProxyRoute>,
+    ) -> Result<(u16, Duration), String> {
+        let policy = source_policy(source)?;
+        let client = match route {
+            Some(route) => self.build_client(policy, route, &self.tls.read().unwrap())?,
+            None => self.client(source),
+        };
+        let _permit = self.wait_turn(policy).await?;
+        let started = Instant::now();
+        let url = policy.probe_url();
+        let response = client
+            .head(&url)
+            .header(reqwest::header::USER_AGENT, policy.user_agent)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| self.explain(source, &url, e))?;
+        Ok((response.status().as_u16(), started.elapsed()))
+    }
+}
+
+/// Politeness policies of every known source
+#[tauri::command]
+pub fn get_source_policies() -> Result<Vec<SourcePolicy>, String> {
+    Ok(SOURCE_POLICIES.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoints_and_pacing() {
+        let policy = source_policy("eastmoney").unwrap();
+        assert!(policy.allows("https://push2.eastmoney.com/api/qt/clist/get?pn=1"));
+        assert!(!policy.allows("https://push2.eastmoney.com/admin"));
+        assert!(!policy.allows("https://push2.eastmoney.com.evil.io/api/qt/"));
+        assert!(source_policy("unknown").is_err());
+
+        let engine = PolicyEngine::default();
+        let now = Instant::now();
+        assert_eq!(engine.reserve(policy, now), Duration::ZERO);
+        assert_eq!(engine.reserve(policy, now), Duration::from_millis(500));
+        assert_eq!(engine.reserve(policy, now), Duration::from_millis(1000));
+        // Slots in the past are not carried forward
+        assert_eq!(
+            engine.reserve(policy, now + Duration::from_secs(10)),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Refresh intervals per data class.
+//!
+//! Each class of data has its own interval (quotes also have a slower
+//! off-hours rate, and news is polled [`NEWS_OFF_HOURS_FACTOR`] times less
+//! often outside sessions), validated against sane bounds before settings
+//! are saved.
+//! Every delay is randomized by a jitter fraction so panels that start
+//! together drift apart instead of hitting providers in bursts.
+//!
+//! Whether a market is in session comes from the [`market_clock`], so
+//! holidays and the afternoons of half days poll at off-hours rates.
+//! Watchlists pick a [`RefreshProfile`] that bends the quote rates to what
+//! the list is for, on the session clock of the list's market: an aggressive
+//! list already refreshes at the session rate in the pre-market, a relaxed one
+//! refreshes at a fraction of the configured rates, and an end-of-day list
+//! isn't refreshed while the market trades.
+
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::market_clock::{self, MarketPhase};
+use crate::models::Market;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataClass {
+    Quotes,
+    Bars,
+    News,
+    Fundamentals,
+}
+
+/// How eagerly a watchlist's quotes are refreshed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshProfile {
+    /// The configured session and off-hours rates
+    #[default]
+    Standard,
+    /// The session rate from the start of the pre-market
+    Aggressive,
+    /// [`RELAXED_FACTOR`] times the standard intervals
+    Relaxed,
+    /// Nothing during the pre-market and sessions, off-hours rate after the close
+    EndOfDay,
+}
+
+/// How much slower relaxed watchlists refresh
+pub const RELAXED_FACTOR: u32 = 5;
+
+/// How much slower news is polled outside trading sessions, when little of
+/// it is published
+pub const NEWS_OFF_HOURS_FACTOR: u64 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PollingSettings {
+    /// Quote refresh while the market is in session
+    pub quotes_secs: u64,
+    /// Quote refresh outside trading sessions
+    pub quotes_off_hours_secs: u64,
+    pub bars_secs: u64,
+    pub news_secs: u64,
+    pub fundamentals_secs: u64,
+    /// Random spread applied to every delay, as a fraction of it
+    pub jitter: f64,
+    /// Stream quotes over the provider WebSocket, polling only what it doesn't carry
+    pub stream_quotes: bool,
+}
+
+impl Default for PollingSettings {
+    fn default() -> Self {
+        Self {
+            quotes_secs: 3,
+            quotes_off_hours_secs: 300,
+            bars_secs: 60,
+            news_secs: 600,
+            fundamentals_secs: 86_400,
+            jitter: 0.1,
+            stream_quotes: true,
+        }
+    }
+}
+
+const DAY_SECS: u64 = 86_400;
+
+impl PollingSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let bounds = [
+            ("quotes_secs", self.quotes_secs, 1, 3600),
+            (
+                "quotes_off_hours_secs",
+                self.quotes_off_hours_secs,
+                1,
+                DAY_SECS,
+            ),
+            ("bars_secs", self.bars_secs, 5, DAY_SECS),
+            ("news_secs", self.news_secs, 60, DAY_SECS),
+            (
+                "fundamentals_secs",
+                self.fundamentals_secs,
+                3600,
+                7 * DAY_SECS,
+            ),
+        ];
+        for (name, value, min, max) in bounds {
+            if !(min..=max).contains(&value) {
+                return Err(format!(
+                    "Polling interval {} must be between {} and {} seconds, got {}",
+                    name, min, max, value
+                ));
+            }
+        }
+        if !(0.0..=0.5).contains(&self.jitter) {
+            return Err(format!(
+                "Polling jitter must be between 0 and 0.5, got {}",
+                self.jitter
+            ));
+        }
+        Ok(())
+    }
+
+    /// Configured interval for a data class, before jitter
+    pub fn interval(&self, class: DataClass, in_session: bool) -> Duration {
+        let secs = match class {
+            DataClass::Quotes if in_session => self.quotes_secs,
+            DataClass::Quotes => self.quotes_off_hours_secs,
+            DataClass::Bars => self.bars_secs,
+            DataClass::News if in_session => self.news_secs,
+            DataClass::News => self.news_secs * NEWS_OFF_HOURS_FACTOR,
+            DataClass::Fundamentals => self.fundamentals_secs,
+        };
+        Duration::from_secs(secs)
+    }
+
+    /// Delay before the next refresh of `class` for a symbol on `market`
+    pub fn next_delay(&self, class: DataClass, market: Market, now: i64) -> Duration {
+        let in_session = market_clock::phase(market, now) == MarketPhase::Open;
+        jittered(self.interval(class, in_session), self.jitter)
+    }
+
+    /// Quote interval under a watchlist profile, before jitter; `None` while
+    /// the profile wants no refresh
+    pub fn quote_interval(
+        &self,
+        profile: RefreshProfile,
+        market: Market,
+        now: i64,
+    ) -> Option<Duration> {
+        let phase = market_clock::phase(market, now);
+        let in_session = phase == MarketPhase::Open;
+        let pre_market = phase == MarketPhase::PreOpen;
+        let standard = self.interval(DataClass::Quotes, in_session);
+        match profile {
+            RefreshProfile::Standard => Some(standard),
+            RefreshProfile::Aggressive => {
+                Some(self.interval(DataClass::Quotes, in_session || pre_market))
+            }
+            RefreshProfile::Relaxed => Some(standard * RELAXED_FACTOR),
+            RefreshProfile::EndOfDay => (!in_session && !pre_market).then_some(standard),
+        }
+    }
+
+    /// Quote interval of a symbol, the fastest among the `(profile, market)`
+    /// of the watchlists holding it; standard rates on the symbol's `market`
+    /// for a symbol in no watchlist
+    pub fn symbol_quote_interval(
+        &self,
+        lists: &[(RefreshProfile, Market)],
+        market: Market,
+        now: i64,
+    ) -> Option<Duration> {
+        if lists.is_empty() {
+            return self.quote_interval(RefreshProfile::Standard, market, now);
+        }
+        lists
+            .iter()
+            .filter_map(|&(profile, market)| self.quote_interval(profile, market, now))
+            .min()
+    }
+}
+
+/// Random factor in `1 ± jitter`
+pub fn jitter_factor(jitter: f64) -> f64 {
+    if jitter <= 0.0 {
+        return 1.0;
+    }
+    rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+}
+
+/// Spread `interval` uniformly over `±jitter` of its length
+pub fn jittered(interval: Duration, jitter: f64) -> Duration {
+    interval.mul_f64(jitter_factor(jitter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_and_jitter() {
+        let settings = PollingSettings::default();
+        assert!(settings.validate().is_ok());
+        assert!(PollingSettings {
+            quotes_secs: 0,
+            ..settings.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(PollingSettings {
+            jitter: 0.9,
+            ..settings.clone()
+        }
+        .validate()
+        .is_err());
+
+        assert_eq!(
+            settings.interval(DataClass::Quotes, true),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            settings.interval(DataClass::Quotes, false),
+            Duration::from_secs(300)
+        );
+        for _ in 0..100 {
+            let delay = jittered(Duration::from_secs(600), 0.1);
+            assert!(delay >= Duration::from_secs(540) && delay <= Duration::from_secs(660));
+        }
+    }
+
+    #[test]
+    fn test_news_polls_slower_off_hours() {
+        use crate::sessions;
+        use chrono::TimeZone;
+
+        let settings = PollingSettings {
+            jitter: 0.0,
+            ..PollingSettings::default()
+        };
+        let at = |h| {
+            sessions::timezone(Market::Cn)
+                .with_ymd_and_hms(2024, 7, 1, h, 0, 0)
+                .unwrap()
+                .timestamp()
+        };
+        let delay = |now| settings.next_delay(DataClass::News, Market::Cn, now);
+        assert_eq!(delay(at(10)), Duration::from_secs(600));
+        assert_eq!(delay(at(20)), Duration::from_secs(1800));
+    }
+
+    #[test]
+    fn test_refresh_profiles() {
+        use crate::sessions;
+        use chrono::TimeZone;
+
+        let settings = PollingSettings::default();
+        let at = |h, m| {
+            sessions::timezone(Market::Cn)
+                .with_ymd_and_hms(2024, 7, 1, h, m, 0)
+                .unwrap()
+                .timestamp()
+        };
+        let secs = |profile, now| {
+            settings
+                .quote_interval(profile, Market::Cn, now)
+                .map(|d| d.as_secs())
+        };
+        // Auction at 09:20, session at 10:00, evening at 20:00
+        let (auction, session, evening) = (at(9, 20), at(10, 0), at(20, 0));
+        assert_eq!(secs(RefreshProfile::Standard, auction), Some(300));
+        assert_eq!(secs(RefreshProfile::Standard, session), Some(3));
+        assert_eq!(secs(RefreshProfile::Aggressive, auction), Some(3));
+        assert_eq!(secs(RefreshProfile::Aggressive, evening), Some(300));
+        assert_eq!(secs(RefreshProfile::Relaxed, session), Some(15));
+        assert_eq!(secs(RefreshProfile::EndOfDay, auction), None);
+        assert_eq!(secs(RefreshProfile::EndOfDay, session), None);
+        assert_eq!(secs(RefreshProfile::EndOfDay, evening), Some(300));
+
+        let lists = [
+            (RefreshProfile::EndOfDay, Market::Cn),
+            (RefreshProfile::Relaxed, Market::Cn),
+        ];
+        let fastest = |lists: &[_], now| {
+            settings
+                .symbol_quote_interval(lists, Market::Cn, now)
+                .map(|d| d.as_secs())
+        };
+        assert_eq!(fastest(&lists, session), Some(15));
+        assert_eq!(fastest(&lists[..1], session), None);
+        assert_eq!(fastest(&[], session), Some(3));
+        // A US symbol in no list follows New York hours
+        assert_eq!(
+            settings
+                .symbol_quote_interval(&[], Market::Us, session)
+                .map(|d| d.as_secs()),
+            Some(300)
+        );
+        // Hong Kong is shut on 1 July while Shanghai trades
+        assert_eq!(
+            settings
+                .quote_interval(RefreshProfile::Standard, Market::Hk, session)
+                .map(|d| d.as_secs()),
+            Some(300)
+        );
+    }
+}
@@ -0,0 +1,797 @@
+//! Portfolio holdings: manual entries and positions synced from brokers.
+//!
+//! Manual holdings are what the user types in. Broker accounts are snapshots
+//! pulled on demand from a read-only broker integration and are replaced
+//! wholesale on every sync; they are never edited locally, so the two
+//! sources stay visibly separate. The manual transaction ledger records
+//! trades and cash movements per account so it can be reconciled against
+//! what the broker reports. Entries can also be booked to one of the named
+//! portfolios, whose positions and P/L are worked out from them in
+//! [`portfolio_pnl`](crate::portfolio_pnl). Deleted holdings and
+//! transactions go to the trash and can be restored until the retention
+//! window passes.
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::State;
+
+use crate::audit::{current_user, AuditAction, AuditLog};
+use crate::db::{Database, Portfolios};
+use crate::fields::select;
+use crate::merge::{merge, MergeChange, MergeReport, Mergeable};
+use crate::privacy::Privacy;
+use crate::undo::{purge_expired, Trashed, UndoAction, UndoLog};
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Holding {
+    pub id: String,
+    pub symbol: String,
+    pub quantity: f64,
+    /// Average cost per share, in the holding's currency
+    pub cost_price: f64,
+    pub currency: String,
+    /// Latest price reported by the broker; manual holdings have none
+    #[serde(default)]
+    pub market_price: Option<f64>,
+    #[serde(default)]
+    pub note: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CashBalance {
+    pub currency: String,
+    pub cash: f64,
+    pub market_value: f64,
+    pub net_liquidation: f64,
+}
+
+/// One broker account as of its last sync
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrokerAccount {
+    pub broker: String,
+    pub account_id: String,
+    pub positions: Vec<Holding>,
+    pub balances: Vec<CashBalance>,
+    pub synced_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionKind {
+    Buy,
+    Sell,
+    Deposit,
+    Withdrawal,
+    Dividend,
+    Fee,
+}
+
+impl TransactionKind {
+    pub fn is_trade(self) -> bool {
+        matches!(self, TransactionKind::Buy | TransactionKind::Sell)
+    }
+}
+
+/// A ledger entry as entered by the user, or suggested by reconciliation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionEntry {
+    pub account_id: String,
+    pub kind: TransactionKind,
+    /// Empty for cash movements
+    #[serde(default)]
+    pub symbol: String,
+    #[serde(default)]
+    pub quantity: f64,
+    #[serde(default)]
+    pub price: f64,
+    /// Cash moved by deposits, withdrawals, dividends and fees
+    #[serde(default)]
+    pub amount: f64,
+    /// Commission and taxes charged on top
+    #[serde(default)]
+    pub fee: f64,
+    pub currency: String,
+    /// Trade or value date, `YYYY-MM-DD`
+    pub date: String,
+    #[serde(default)]
+    pub note: String,
+    /// Named portfolio the entry is booked to, if any
+    #[serde(default)]
+    pub portfolio_id: Option<String>,
+}
+
+impl TransactionEntry {
+    pub fn validate(&self) -> Result<(), String> {
+        let finite = [self.quantity, self.price, self.amount, self.fee]
+            .iter()
+            .all(|v| v.is_finite() && *v >= 0.0);
+        if !finite {
+            return Err("Transaction amounts must be non-negative numbers".to_string());
+        }
+        if self.account_id.trim().is_empty() {
+            return Err("Transaction needs an account".to_string());
+        }
+        if chrono::NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").is_err() {
+            return Err(format!("Invalid transaction date: {}", self.date));
+        }
+        if self.kind.is_trade() {
+            if self.symbol.trim().is_empty() || self.quantity <= 0.0 {
+                return Err("Trades need a symbol and a positive quantity".to_string());
+            }
+        } else if self.amount <= 0.0 {
+            return Err("Cash movements need a positive amount".to_string());
+        }
+        Ok(())
+    }
+
+    /// Signed change in the account's cash balance
+    pub fn cash_effect(&self) -> f64 {
+        let gross = match self.kind {
+            TransactionKind::Buy => -self.quantity * self.price,
+            TransactionKind::Sell => self.quantity * self.price,
+            TransactionKind::Deposit | TransactionKind::Dividend => self.amount,
+            TransactionKind::Withdrawal | TransactionKind::Fee => -self.amount,
+        };
+        gross - self.fee
+    }
+
+    /// Signed change in the position held
+    pub fn quantity_effect(&self) -> f64 {
+        match self.kind {
+            TransactionKind::Buy => self.quantity,
+            TransactionKind::Sell => -self.quantity,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,
+    #[serde(flatten)]
+    pub entry: TransactionEntry,
+    pub recorded_at: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Portfolio {
+    pub manual: Vec<Holding>,
+    pub broker_accounts: Vec<BrokerAccount>,
+    #[serde(default)]
+    pub transactions: Vec<Transaction>,
+    #[serde(default)]
+    pub trash: PortfolioTrash,
+}
+
+impl Mergeable for Holding {
+    const KIND: &'static str = "holding";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn modified_at(&self) -> &str {
+        &self.updated_at
+    }
+
+    fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}|{}",
+            self.symbol, self.quantity, self.cost_price, self.currency
+        )
+    }
+}
+
+impl Mergeable for Transaction {
+    const KIND: &'static str = "transaction";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn modified_at(&self) -> &str {
+        &self.recorded_at
+    }
+
+    fn fingerprint(&self) -> String {
+        let e = &self.entry;
+        format!(
+            "{}|{:?}|{}|{}|{}|{}|{}|{}|{}|{}",
+            e.account_id,
+            e.kind,
+            e.symbol,
+            e.quantity,
+            e.price,
+            e.amount,
+            e.fee,
+            e.currency,
+            e.date,
+            e.portfolio_id.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioTrash {
+    pub holdings: Vec<Trashed<Holding>>,
+    pub transactions: Vec<Trashed<Transaction>>,
+}
+
+impl PortfolioTrash {
+    fn purge(&mut self) {
+        let purged = purge_expired(&mut self.holdings) + purge_expired(&mut self.transactions);
+        if purged > 0 {
+            info!("Purged {} expired portfolio entries from the trash", purged);
+        }
+    }
+}
+
+pub struct PortfolioStore {
+    path: PathBuf,
+    portfolio: RwLock<Portfolio>,
+    audit: AuditLog,
+}
+
+impl PortfolioStore {
+    pub fn load(path: PathBuf, audit_path: PathBuf) -> Self {
+        let mut portfolio: Portfolio = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        portfolio.trash.purge();
+        Self {
+            path,
+            portfolio: RwLock::new(portfolio),
+            audit: AuditLog::load(audit_path),
+        }
+    }
+
+    fn save(&self, portfolio: &Portfolio) -> Result<(), String> {
+        let content = serde_json::to_string(portfolio)
+            .map_err(|e| format!("Failed to serialize portfolio: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save portfolio: {}", e))
+    }
+
+    pub fn get(&self) -> Portfolio {
+        self.portfolio.read().unwrap().clone()
+    }
+
+    pub fn audit(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    pub fn add_manual(
+        &self,
+        actor: &str,
+        symbol: &str,
+        quantity: f64,
+        cost_price: f64,
+        currency: &str,
+        note: &str,
+    ) -> Result<Holding, String> {
+        let valid =
+            quantity.is_finite() && quantity > 0.0 && cost_price.is_finite() && cost_price >= 0.0;
+        if !valid {
+            return Err("Quantity must be positive and cost price non-negative".to_string());
+        }
+        let holding = Holding {
+            id: generate_id("holding"),
+            symbol: symbol.trim().to_uppercase(),
+            quantity,
+            cost_price,
+            currency: currency.trim().to_uppercase(),
+            market_price: None,
+            note: note.to_string(),
+            updated_at: get_timestamp(),
+        };
+        let mut portfolio = self.portfolio.write().unwrap();
+        // The audit entry is written first, so no change is ever saved unrecorded
+        self.audit.record(
+            actor,
+            AuditAction::Create,
+            format!("holding:{}", holding.id),
+            None,
+            Some(&holding),
+        )?;
+        portfolio.manual.push(holding.clone());
+        self.save(&portfolio)?;
+        Ok(holding)
+    }
+
+    /// Move a holding to the trash
+    pub fn remove_manual(&self, actor: &str, id: &str) -> Result<Holding, String> {
+        let mut portfolio = self.portfolio.write().unwrap();
+        let index = portfolio
+            .manual
+            .iter()
+            .position(|h| h.id == id)
+            .ok_or_else(|| format!("Holding not found: {}", id))?;
+        self.audit.record(
+            actor,
+            AuditAction::Delete,
+            format!("holding:{}", id),
+            Some(&portfolio.manual[index]),
+            None,
+        )?;
+        let holding = portfolio.manual.remove(index);
+        portfolio.trash.purge();
+        portfolio.trash.holdings.push(Trashed::new(holding.clone()));
+        self.save(&portfolio)?;
+        Ok(holding)
+    }
+
+    fn new_transaction(entry: TransactionEntry) -> Result<Transaction, String> {
+        entry.validate()?;
+        Ok(Transaction {
+            id: generate_id("txn"),
+            entry: TransactionEntry {
+                symbol: entry.symbol.trim().to_uppercase(),
+                currency: entry.currency.trim().to_uppercase(),
+                ..entry
+            },
+            recorded_at: get_timestamp(),
+        })
+    }
+
+    pub fn add_transaction(
+        &self,
+        actor: &str,
+        entry: TransactionEntry,
+    ) -> Result<Transaction, String> {
+        let transaction = Self::new_transaction(entry)?;
+        let mut portfolio = self.portfolio.write().unwrap();
+        self.audit.record(
+            actor,
+            AuditAction::Create,
+            format!("transaction:{}", transaction.id),
+            None,
+            Some(&transaction),
+        )?;
+        portfolio.transactions.push(transaction.clone());
+        self.save(&portfolio)?;
+        Ok(transaction)
+    }
+
+    /// Add a batch of transactions from an import; nothing is added if any entry is invalid
+    pub fn import_transactions(
+        &self,
+        actor: &str,
+        entries: Vec<TransactionEntry>,
+    ) -> Result<Vec<Transaction>, String> {
+        let transactions = entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                Self::new_transaction(entry).map_err(|e| format!("Row {}: {}", i + 1, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut portfolio = self.portfolio.write().unwrap();
+        for transaction in &transactions {
+            self.audit.record(
+                actor,
+                AuditAction::Import,
+                format!("transaction:{}", transaction.id),
+                None,
+                Some(transaction),
+            )?;
+        }
+        portfolio.transactions.extend(transactions.iter().cloned());
+        self.save(&portfolio)?;
+        Ok(transactions)
+    }
+
+    pub fn update_transaction(
+        &self,
+        actor: &str,
+        id: &str,
+        entry: TransactionEntry,
+    ) -> Result<Transaction, String> {
+        let updated = Self::new_transaction(entry)?;
+        let mut portfolio = self.portfolio.write().unwrap();
+        let existing = portfolio
+            .transactions
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| format!("Transaction not found: {}", id))?;
+        let updated = Transaction {
+            id: existing.id.clone(),
+            ..updated
+        };
+        self.audit.record(
+            actor,
+            AuditAction::Update,
+            format!("transaction:{}", id),
+            Some(&*existing),
+            Some(&updated),
+        )?;
+        *existing = updated.clone();
+        self.save(&portfolio)?;
+        Ok(updated)
+    }
+
+    /// Move a transaction to the trash
+    pub fn remove_transaction(&self, actor: &str, id: &str) -> Result<Transaction, String> {
+        let mut portfolio = self.portfolio.write().unwrap();
+        let index = portfolio
+            .transactions
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or_else(|| format!("Transaction not found: {}", id))?;
+        self.audit.record(
+            actor,
+            AuditAction::Delete,
+            format!("transaction:{}", id),
+            Some(&portfolio.transactions[index]),
+            None,
+        )?;
+        let transaction = portfolio.transactions.remove(index);
+        portfolio.trash.purge();
+        portfolio
+            .trash
+            .transactions
+            .push(Trashed::new(transaction.clone()));
+        self.save(&portfolio)?;
+        Ok(transaction)
+    }
+
+    /// Merge holdings and transactions from another copy of the portfolio.
+    ///
+    /// Broker snapshots are left alone: they belong to the machine that
+    /// synced them and are refreshed by syncing again.
+    pub fn merge(&self, actor: &str, incoming: Portfolio) -> Result<MergeReport, String> {
+        let mut portfolio = self.portfolio.write().unwrap();
+        let mut merged = portfolio.clone();
+        let mut report = MergeReport::default();
+        let holdings = merge(
+            &mut merged.manual,
+            &mut merged.trash.holdings,
+            incoming.manual,
+            incoming.trash.holdings,
+            &mut report,
+        );
+        let transactions = merge(
+            &mut merged.transactions,
+            &mut merged.trash.transactions,
+            incoming.transactions,
+            incoming.trash.transactions,
+            &mut report,
+        );
+        self.record_merge(actor, holdings)?;
+        self.record_merge(actor, transactions)?;
+        self.save(&merged)?;
+        *portfolio = merged;
+        Ok(report)
+    }
+
+    fn record_merge<T: Mergeable>(
+        &self,
+        actor: &str,
+        changes: Vec<MergeChange<T>>,
+    ) -> Result<(), String> {
+        for change in changes {
+            let (action, id, before, after) = match &change {
+                MergeChange::Added(item) => (AuditAction::Import, item.id(), None, Some(item)),
+                MergeChange::Replaced { before, after } => {
+                    (AuditAction::Update, after.id(), Some(before), Some(after))
+                }
+                MergeChange::Deleted(item) => (AuditAction::Delete, item.id(), Some(item), None),
+            };
+            self.audit
+                .record(actor, action, format!("{}:{}", T::KIND, id), before, after)?;
+        }
+        Ok(())
+    }
+
+    /// Undo a delete or edit of a holding or transaction
+    pub fn revert(
+        &self,
+        actor: &str,
+        kind: &str,
+        id: &str,
+        action: &UndoAction,
+    ) -> Result<(), String> {
+        match (kind, action) {
+            ("transaction", UndoAction::Update { before }) => {
+                let before: Transaction = serde_json::from_value(before.clone())
+                    .map_err(|e| format!("Failed to read previous transaction: {}", e))?;
+                self.update_transaction(actor, id, before.entry).map(|_| ())
+            }
+            ("transaction", UndoAction::Delete) => {
+                let mut portfolio = self.portfolio.write().unwrap();
+                let index = portfolio
+                    .trash
+                    .transactions
+                    .iter()
+                    .position(|t| t.item.id == id)
+                    .ok_or_else(|| format!("Transaction {} is no longer in the trash", id))?;
+                let transaction = portfolio.trash.transactions[index].item.clone();
+                self.audit.record(
+                    actor,
+                    AuditAction::Restore,
+                    format!("transaction:{}", id),
+                    None,
+                    Some(&transaction),
+                )?;
+                portfolio.trash.transactions.remove(index);
+                portfolio.transactions.push(transaction);
+                self.save(&portfolio)
+            }
+            ("holding", UndoAction::Delete) => {
+                let mut portfolio = self.portfolio.write().unwrap();
+                let index = portfolio
+                    .trash
+                    .holdings
+                    .iter()
+                    .position(|h| h.item.id == id)
+                    .ok_or_else(|| format!("Holding {} is no longer in the trash", id))?;
+                let holding = portfolio.trash.holdings[index].item.clone();
+                self.audit.record(
+                    actor,
+                    AuditAction::Restore,
+                    format!("holding:{}", id),
+                    None,
+                    Some(&holding),
+                )?;
+                portfolio.trash.holdings.remove(index);
+                portfolio.manual.push(holding);
+                self.save(&portfolio)
+            }
+            _ => Err(format!("Cannot undo this change to {} {}", kind, id)),
+        }
+    }
+
+    /// Replace the snapshot of a broker account with a fresh sync
+    pub fn replace_broker_account(
+        &self,
+        actor: &str,
+        account: BrokerAccount,
+    ) -> Result<(), String> {
+        let mut portfolio = self.portfolio.write().unwrap();
+        let index = portfolio
+            .broker_accounts
+            .iter()
+            .position(|a| a.broker == account.broker && a.account_id == account.account_id);
+        self.audit.record(
+            actor,
+            AuditAction::Sync,
+            format!("broker_account:{}/{}", account.broker, account.account_id),
+            index.map(|i| &portfolio.broker_accounts[i]),
+            Some(&account),
+        )?;
+        match index {
+            Some(i) => portfolio.broker_accounts[i] = account,
+            None => portfolio.broker_accounts.push(account),
+        }
+        self.save(&portfolio)
+    }
+}
+
+/// Manual holdings and synced broker accounts
+#[tauri::command]
+pub fn get_portfolio(
+    store: State<'_, PortfolioStore>,
+    privacy: State<'_, Privacy>,
+    fields: Option<Vec<String>>,
+) -> Result<Value, String> {
+    select(&privacy.apply(store.get())?, fields.as_deref())
+}
+
+#[tauri::command]
+pub fn add_manual_holding(
+    store: State<'_, PortfolioStore>,
+    privacy: State<'_, Privacy>,
+    symbol: String,
+    quantity: f64,
+    cost_price: f64,
+    currency: Option<String>,
+    note: Option<String>,
+) -> Result<Holding, String> {
+    privacy.check_editable()?;
+    store.add_manual(
+        &current_user(),
+        &symbol,
+        quantity,
+        cost_price,
+        currency.as_deref().unwrap_or("CNY"),
+        note.as_deref().unwrap_or_default(),
+    )
+}
+
+#[tauri::command]
+pub fn remove_manual_holding(
+    store: State<'_, PortfolioStore>,
+    privacy: State<'_, Privacy>,
+    undo: State<'_, UndoLog>,
+    id: String,
+) -> Result<(), String> {
+    privacy.check_editable()?;
+    let holding = store.remove_manual(&current_user(), &id)?;
+    undo.push(
+        format!("holding:{}", id),
+        format!("Delete holding {}", holding.symbol),
+        UndoAction::Delete,
+    )
+}
+
+/// Ledger transactions, optionally of one account or named portfolio,
+/// oldest first
+#[tauri::command]
+pub fn list_transactions(
+    store: State<'_, PortfolioStore>,
+    privacy: State<'_, Privacy>,
+    account_id: Option<String>,
+    portfolio_id: Option<String>,
+) -> Result<Vec<Transaction>, String> {
+    let mut transactions: Vec<Transaction> = store
+        .get()
+        .transactions
+        .into_iter()
+        .filter(|t| {
+            account_id
+                .as_ref()
+                .map_or(true, |a| &t.entry.account_id == a)
+        })
+        .filter(|t| portfolio_id.is_none() || t.entry.portfolio_id == portfolio_id)
+        .collect();
+    transactions.sort_by(|a, b| a.entry.date.cmp(&b.entry.date));
+    privacy.apply(transactions)
+}
+
+/// Check that entries booked to named portfolios name ones that exist
+fn check_portfolios(db: &Database, entries: &[TransactionEntry]) -> Result<(), String> {
+    let conn = db.conn()?;
+    let portfolios = Portfolios(&conn);
+    for id in entries.iter().filter_map(|e| e.portfolio_id.as_deref()) {
+        portfolios.get(id)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn add_transaction(
+    store: State<'_, PortfolioStore>,
+    db: State<'_, Database>,
+    privacy: State<'_, Privacy>,
+    entry: TransactionEntry,
+) -> Result<Transaction, String> {
+    privacy.check_editable()?;
+    check_portfolios(&db, std::slice::from_ref(&entry))?;
+    store.add_transaction(&current_user(), entry)
+}
+
+/// Record transactions read from a statement; `source` names it in the audit trail, e.g. `ocr`
+#[tauri::command]
+pub fn import_transactions(
+    store: State<'_, PortfolioStore>,
+    db: State<'_, Database>,
+    privacy: State<'_, Privacy>,
+    entries: Vec<TransactionEntry>,
+    source: String,
+) -> Result<Vec<Transaction>, String> {
+    privacy.check_editable()?;
+    check_portfolios(&db, &entries)?;
+    let actor = format!("{} via {}", current_user(), source);
+    store.import_transactions(&actor, entries)
+}
+
+#[tauri::command]
+pub fn update_transaction(
+    store: State<'_, PortfolioStore>,
+    db: State<'_, Database>,
+    privacy: State<'_, Privacy>,
+    undo: State<'_, UndoLog>,
+    id: String,
+    entry: TransactionEntry,
+) -> Result<Transaction, String> {
+    privacy.check_editable()?;
+    check_portfolios(&db, std::slice::from_ref(&entry))?;
+    let before = store
+        .get()
+        .transactions
+        .into_iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| format!("Transaction not found: {}", id))?;
+    let updated = store.update_transaction(&current_user(), &id, entry)?;
+    let before = serde_json::to_value(&before)
+        .map_err(|e| format!("Failed to serialize transaction: {}", e))?;
+    undo.push(
+        format!("transaction:{}", id),
+        format!("Edit transaction {}", updated.entry.symbol),
+        UndoAction::Update { before },
+    )?;
+    Ok(updated)
+}
+
+#[tauri::command]
+pub fn remove_transaction(
+    store: State<'_, PortfolioStore>,
+    privacy: State<'_, Privacy>,
+    undo: State<'_, UndoLog>,
+    id: String,
+) -> Result<(), String> {
+    privacy.check_editable()?;
+    let transaction = store.remove_transaction(&current_user(), &id)?;
+    undo.push(
+        format!("transaction:{}", id),
+        format!(
+            "Delete {:?} {} {}",
+            transaction.entry.kind, transaction.entry.symbol, transaction.entry.date
+        ),
+        UndoAction::Delete,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_and_broker_holdings_stay_separate() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("ssi-portfolio-{}.json", std::process::id()));
+        let audit = dir.join(format!("ssi-portfolio-audit-{}.jsonl", std::process::id()));
+        let store = PortfolioStore::load(path.clone(), audit.clone());
+        let manual = store
+            .add_manual("me", "sh600519", 100.0, 1650.0, "cny", "")
+            .unwrap();
+        assert_eq!(manual.symbol, "SH600519");
+        assert!(store
+            .add_manual("me", "SH600000", 0.0, 10.0, "CNY", "")
+            .is_err());
+
+        let account = |quantity: f64| BrokerAccount {
+            broker: "ibkr".to_string(),
+            account_id: "U1".to_string(),
+            positions: vec![Holding {
+                quantity,
+                id: "ibkr-1".to_string(),
+                market_price: Some(1700.0),
+                ..manual.clone()
+            }],
+            balances: Vec::new(),
+            synced_at: "t".to_string(),
+        };
+        store
+            .replace_broker_account("sync", account(100.0))
+            .unwrap();
+        store
+            .replace_broker_account("sync", account(200.0))
+            .unwrap();
+
+        let reloaded = PortfolioStore::load(path.clone(), audit.clone()).get();
+        assert_eq!(reloaded.manual, vec![manual.clone()]);
+        assert_eq!(reloaded.broker_accounts.len(), 1);
+        assert_eq!(reloaded.broker_accounts[0].positions[0].quantity, 200.0);
+
+        store.remove_manual("me", &manual.id).unwrap();
+        assert!(store.remove_manual("me", &manual.id).is_err());
+        assert_eq!(store.get().trash.holdings.len(), 1);
+        store
+            .revert("me", "holding", &manual.id, &UndoAction::Delete)
+            .unwrap();
+        assert_eq!(store.get().manual, vec![manual.clone()]);
+        assert!(store.get().trash.holdings.is_empty());
+        store.remove_manual("me", &manual.id).unwrap();
+
+        let trail = store.audit().trail(Some(&format!("holding:{}", manual.id)));
+        let actions: Vec<AuditAction> = trail.entries.iter().map(|e| e.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Create,
+                AuditAction::Delete,
+                AuditAction::Restore,
+                AuditAction::Delete
+            ]
+        );
+        assert_eq!(store.audit().trail(Some("broker_account")).entries.len(), 2);
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(audit).ok();
+    }
+}
@@ -0,0 +1,476 @@
+//! Alerts on the portfolio as a whole.
+//!
+//! Where a price alert watches one symbol, a [`PortfolioAlert`] watches the
+//! holdings together: the total value falling some percent below its peak,
+//! any one position growing past a weight, or cash running low. They are
+//! checked after every quote refresh, right after the price alerts, and fire
+//! through the same notification and delivery pipeline.
+//!
+//! Without exchange rates the portfolio is valued one currency at a time, so
+//! each alert covers the holdings and cash in its own currency. Positions are
+//! valued at the live quote, else the broker's last price, else cost, and the
+//! same symbol held manually and at a broker counts as one position. Cash is
+//! what brokers last reported; a cash alert waits until there is some. The
+//! drawdown peak is the highest value seen since the alert was created or
+//! re-enabled, so taking money out reads as a drawdown too.
+//!
+//! A fired alert re-arms once its value is back past the threshold by
+//! [`REARM_MARGIN`] of it, so a weight hovering at its limit fires once.
+
+use std::collections::BTreeMap;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{Database, PortfolioAlerts};
+use crate::delivery::{self, AlertMessage};
+use crate::notifications::{self, Notification, NotificationAction, NotificationCategory};
+use crate::portfolio::{Portfolio, PortfolioStore};
+use crate::privacy::Privacy;
+use crate::quotes::{Quote, QuoteFeed};
+use crate::settings::SettingsStore;
+use crate::utils::{generate_id, get_timestamp};
+
+/// Share of the threshold a value must clear by before an alert re-arms
+const REARM_MARGIN: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PortfolioCondition {
+    /// Total value `pct` percent or more below its peak
+    Drawdown { pct: f64 },
+    /// Any position at `pct` percent or more of the total value
+    PositionWeight { pct: f64 },
+    /// Cash at or below `amount`
+    CashBelow { amount: f64 },
+}
+
+impl PortfolioCondition {
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            PortfolioCondition::Drawdown { pct } | PortfolioCondition::PositionWeight { pct }
+                if !(pct > 0.0 && pct < 100.0) =>
+            {
+                Err(format!("Percent must be between 0 and 100, got {}", pct))
+            }
+            PortfolioCondition::CashBelow { amount } if !(amount.is_finite() && amount >= 0.0) => {
+                Err(format!(
+                    "Cash threshold must not be negative, got {}",
+                    amount
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn threshold(&self) -> f64 {
+        match *self {
+            PortfolioCondition::Drawdown { pct } | PortfolioCondition::PositionWeight { pct } => {
+                pct
+            }
+            PortfolioCondition::CashBelow { amount } => amount,
+        }
+    }
+
+    fn holds(&self, value: f64) -> bool {
+        match self {
+            PortfolioCondition::CashBelow { .. } => value <= self.threshold(),
+            _ => value >= self.threshold(),
+        }
+    }
+
+    fn cleared(&self, value: f64) -> bool {
+        let margin = self.threshold() * REARM_MARGIN;
+        match self {
+            PortfolioCondition::CashBelow { .. } => value > self.threshold() + margin,
+            _ => value < self.threshold() - margin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioAlert {
+    pub id: String,
+    pub condition: PortfolioCondition,
+    pub currency: String,
+    pub enabled: bool,
+    /// Cleared when the alert fires, set again once the condition clears
+    pub armed: bool,
+    /// Highest total value seen, for drawdowns
+    #[serde(default)]
+    pub peak: Option<f64>,
+    #[serde(default)]
+    pub channels: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub triggered_at: Option<String>,
+}
+
+/// Holdings and cash in one currency
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Valuation {
+    /// Market value by symbol
+    positions: BTreeMap<String, f64>,
+    /// Cash reported by brokers, if any reported this currency
+    cash: Option<f64>,
+}
+
+impl Valuation {
+    fn of(portfolio: &Portfolio, currency: &str, price: impl Fn(&str) -> Option<f64>) -> Self {
+        let mut valuation = Valuation::default();
+        let holdings = portfolio
+            .manual
+            .iter()
+            .chain(portfolio.broker_accounts.iter().flat_map(|a| &a.positions))
+            .filter(|h| h.currency == currency);
+        for holding in holdings {
+            let price = price(&holding.symbol)
+                .or(holding.market_price)
+                .unwrap_or(holding.cost_price);
+            *valuation
+                .positions
+                .entry(holding.symbol.clone())
+                .or_default() += holding.quantity * price;
+        }
+        for balance in portfolio.broker_accounts.iter().flat_map(|a| &a.balances) {
+            if balance.currency == currency {
+                *valuation.cash.get_or_insert(0.0) += balance.cash;
+            }
+        }
+        valuation
+    }
+
+    fn total(&self) -> f64 {
+        self.positions.values().sum::<f64>() + self.cash.unwrap_or(0.0)
+    }
+
+    /// The heaviest position and its weight in percent
+    fn heaviest(&self) -> Option<(&str, f64)> {
+        let total = self.total();
+        if total <= 0.0 {
+            return None;
+        }
+        self.positions
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(symbol, value)| (symbol.as_str(), value / total * 100.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+    Fire,
+    Rearm,
+}
+
+/// What an alert compares with its threshold, and the symbol it concerns
+fn observed(alert: &PortfolioAlert, valuation: &Valuation) -> Option<(f64, Option<String>)> {
+    match alert.condition {
+        PortfolioCondition::Drawdown { .. } => {
+            let (total, peak) = (valuation.total(), alert.peak?);
+            (peak > 0.0).then(|| ((1.0 - total / peak) * 100.0, None))
+        }
+        PortfolioCondition::PositionWeight { .. } => valuation
+            .heaviest()
+            .map(|(symbol, weight)| (weight, Some(symbol.to_string()))),
+        PortfolioCondition::CashBelow { .. } => valuation.cash.map(|cash| (cash, None)),
+    }
+}
+
+fn transition(alert: &PortfolioAlert, value: f64) -> Option<Transition> {
+    if !alert.enabled {
+        None
+    } else if alert.armed {
+        alert.condition.holds(value).then_some(Transition::Fire)
+    } else {
+        alert.condition.cleared(value).then_some(Transition::Rearm)
+    }
+}
+
+fn notification(
+    alert: &PortfolioAlert,
+    value: f64,
+    symbol: Option<&str>,
+    redact: bool,
+) -> Notification {
+    let title = match (alert.condition, symbol) {
+        (PortfolioCondition::Drawdown { .. }, _) => format!("组合回撤达 {:.2}%", value),
+        (PortfolioCondition::PositionWeight { .. }, Some(symbol)) => {
+            format!("{} 仓位占比达 {:.2}%", symbol, value)
+        }
+        (PortfolioCondition::PositionWeight { .. }, None) => {
+            format!("单一仓位占比达 {:.2}%", value)
+        }
+        (PortfolioCondition::CashBelow { .. }, _) => "现金余额不足".to_string(),
+    };
+    let mut body = match alert.condition {
+        PortfolioCondition::Drawdown { pct } => format!("超过 {:.2}% 回撤阈值", pct),
+        PortfolioCondition::PositionWeight { pct } => format!("超过 {:.2}% 仓位上限", pct),
+        // Amounts stay off screen in privacy mode
+        PortfolioCondition::CashBelow { .. } if redact => "低于设定阈值".to_string(),
+        PortfolioCondition::CashBelow { amount } => {
+            format!("现金 {:.2} {}，低于 {:.2}", value, alert.currency, amount)
+        }
+    };
+    if !alert.note.is_empty() {
+        body = format!("{}\n{}", body, alert.note);
+    }
+    let mut actions = Vec::new();
+    if let Some(symbol) = symbol {
+        actions.push(NotificationAction::open_chart(symbol));
+    }
+    actions.push(NotificationAction::dismiss());
+    Notification {
+        category: NotificationCategory::PriceAlert,
+        title,
+        body,
+        actions,
+    }
+}
+
+/// Fire and re-arm portfolio alerts against the latest quotes
+pub fn evaluate(app: &AppHandle, quotes: &[Quote]) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let conn = db.conn()?;
+    let alerts = PortfolioAlerts(&conn).list()?;
+    if quotes.is_empty() || !alerts.iter().any(|a| a.enabled) {
+        return Ok(());
+    }
+    let portfolio = app.state::<PortfolioStore>().get();
+    let feed = app.state::<QuoteFeed>();
+    let price = |symbol: &str| feed.get(symbol).map(|q| q.price).filter(|p| *p > 0.0);
+    let settings = app.state::<SettingsStore>().get();
+    let redact = app.state::<Privacy>().enabled();
+    let mut valuations: BTreeMap<String, Valuation> = BTreeMap::new();
+    let mut fired = Vec::new();
+    for mut alert in alerts.into_iter().filter(|a| a.enabled) {
+        let valuation = valuations
+            .entry(alert.currency.clone())
+            .or_insert_with(|| Valuation::of(&portfolio, &alert.currency, price));
+        let mut changed = false;
+        if matches!(alert.condition, PortfolioCondition::Drawdown { .. }) {
+            let total = valuation.total();
+            if alert.peak.map_or(true, |peak| total > peak) {
+                alert.peak = Some(total);
+                changed = true;
+            }
+        }
+        if let Some((value, symbol)) = observed(&alert, valuation) {
+            match transition(&alert, value) {
+                Some(Transition::Fire) => {
+                    alert.armed = false;
+                    alert.triggered_at = Some(get_timestamp());
+                    info!("Portfolio alert {} fired at {:.2}", alert.id, value);
+                    fired.push((alert.clone(), value, symbol));
+                    changed = true;
+                }
+                Some(Transition::Rearm) => {
+                    alert.armed = true;
+                    info!("Portfolio alert {} re-armed", alert.id);
+                    changed = true;
+                }
+                None => {}
+            }
+        }
+        if changed {
+            PortfolioAlerts(&conn).set_state(&alert)?;
+        }
+    }
+    drop(conn);
+    for (alert, value, symbol) in fired {
+        let notification = notification(&alert, value, symbol.as_deref(), redact);
+        let allowed = settings
+            .notifications
+            .allows_alert(symbol.as_deref().unwrap_or_default());
+        if !allowed {
+            info!(
+                "Portfolio alert {} held back by quiet hours or snooze",
+                alert.id
+            );
+            if let Err(e) = notifications::record(app, &notification) {
+                warn!("{}", e);
+            }
+            continue;
+        }
+        delivery::deliver(
+            app,
+            &alert.channels,
+            AlertMessage {
+                title: notification.title.clone(),
+                body: notification.body.clone(),
+            },
+        );
+        if let Err(e) = notifications::notify(app, notification) {
+            warn!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Symbols whose quotes enabled portfolio alerts depend on
+pub fn watched_symbols(app: &AppHandle) -> Vec<String> {
+    let db = app.state::<Database>();
+    let alerts = match db.conn().and_then(|conn| PortfolioAlerts(&conn).list()) {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            warn!("{}", e);
+            return Vec::new();
+        }
+    };
+    let currencies: Vec<&str> = alerts
+        .iter()
+        .filter(|a| a.enabled && !matches!(a.condition, PortfolioCondition::CashBelow { .. }))
+        .map(|a| a.currency.as_str())
+        .collect();
+    let portfolio = app.state::<PortfolioStore>().get();
+    let mut symbols: Vec<String> = portfolio
+        .manual
+        .iter()
+        .chain(portfolio.broker_accounts.iter().flat_map(|a| &a.positions))
+        .filter(|h| currencies.contains(&h.currency.as_str()))
+        .map(|h| h.symbol.clone())
+        .collect();
+    symbols.sort();
+    symbols.dedup();
+    symbols
+}
+
+#[tauri::command]
+pub fn list_portfolio_alerts(
+    db: State<'_, Database>,
+    privacy: State<'_, Privacy>,
+) -> Result<Vec<PortfolioAlert>, String> {
+    let conn = db.conn()?;
+    privacy.apply(PortfolioAlerts(&conn).list()?)
+}
+
+/// Watch the portfolio's holdings in `currency`, CNY by default
+#[tauri::command]
+pub fn add_portfolio_alert(
+    db: State<'_, Database>,
+    settings: State<'_, SettingsStore>,
+    privacy: State<'_, Privacy>,
+    condition: PortfolioCondition,
+    currency: Option<String>,
+    channels: Option<Vec<String>>,
+    note: Option<String>,
+) -> Result<PortfolioAlert, String> {
+    privacy.check_editable()?;
+    condition.validate()?;
+    let channels = channels.unwrap_or_default();
+    settings.get().delivery.check_routes(&channels)?;
+    let alert = PortfolioAlert {
+        id: generate_id("portfolio-alert"),
+        condition,
+        currency: currency.as_deref().unwrap_or("CNY").trim().to_uppercase(),
+        enabled: true,
+        armed: true,
+        peak: None,
+        channels,
+        note: note.unwrap_or_default(),
+        created_at: get_timestamp(),
+        triggered_at: None,
+    };
+    let conn = db.conn()?;
+    PortfolioAlerts(&conn).create(&alert)
+}
+
+/// Turn an alert on or off; turning it on re-arms it and resets its peak
+#[tauri::command]
+pub fn set_portfolio_alert_enabled(
+    db: State<'_, Database>,
+    alert_id: String,
+    enabled: bool,
+) -> Result<PortfolioAlert, String> {
+    let conn = db.conn()?;
+    PortfolioAlerts(&conn).set_enabled(&alert_id, enabled)
+}
+
+#[tauri::command]
+pub fn delete_portfolio_alert(db: State<'_, Database>, alert_id: String) -> Result<(), String> {
+    let conn = db.conn()?;
+    PortfolioAlerts(&conn).delete(&alert_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{BrokerAccount, CashBalance, Holding};
+
+    fn holding(symbol: &str, quantity: f64, cost_price: f64, currency: &str) -> Holding {
+        Holding {
+            id: format!("holding-{}", symbol),
+            symbol: symbol.to_string(),
+            quantity,
+            cost_price,
+            currency: currency.to_string(),
+            market_price: None,
+            note: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_portfolio_conditions() {
+        let portfolio = Portfolio {
+            manual: vec![
+                holding("SH600519", 100.0, 1500.0, "CNY"),
+                holding("AAPL", 10.0, 180.0, "USD"),
+            ],
+            broker_accounts: vec![BrokerAccount {
+                broker: "ibkr".to_string(),
+                account_id: "U1".to_string(),
+                positions: vec![holding("SH600519", 50.0, 1500.0, "CNY")],
+                balances: vec![CashBalance {
+                    currency: "CNY".to_string(),
+                    cash: 40_000.0,
+                    market_value: 0.0,
+                    net_liquidation: 0.0,
+                }],
+                synced_at: String::new(),
+            }],
+            ..Portfolio::default()
+        };
+        let quoted = |symbol: &str| (symbol == "SH600519").then_some(1600.0);
+        let valuation = Valuation::of(&portfolio, "CNY", quoted);
+        assert_eq!(valuation.positions["SH600519"], 240_000.0);
+        assert_eq!(valuation.total(), 280_000.0);
+        assert!(Valuation::of(&portfolio, "USD", quoted).cash.is_none());
+
+        let mut alert = PortfolioAlert {
+            id: "portfolio-alert-1".to_string(),
+            condition: PortfolioCondition::PositionWeight { pct: 80.0 },
+            currency: "CNY".to_string(),
+            enabled: true,
+            armed: true,
+            peak: None,
+            channels: Vec::new(),
+            note: String::new(),
+            created_at: String::new(),
+            triggered_at: None,
+        };
+        let (weight, symbol) = observed(&alert, &valuation).unwrap();
+        assert_eq!(symbol.as_deref(), Some("SH600519"));
+        assert_eq!(transition(&alert, weight), Some(Transition::Fire));
+        alert.armed = false;
+        // Re-arms only once the weight is back under 72%
+        assert_eq!(transition(&alert, 75.0), None);
+        assert_eq!(transition(&alert, 71.0), Some(Transition::Rearm));
+
+        alert.condition = PortfolioCondition::Drawdown { pct: 8.0 };
+        alert.armed = true;
+        alert.peak = Some(310_000.0);
+        let (drawdown, _) = observed(&alert, &valuation).unwrap();
+        assert!((drawdown - (1.0 - 280.0 / 310.0) * 100.0).abs() < 1e-9);
+        assert_eq!(transition(&alert, drawdown), Some(Transition::Fire));
+
+        alert.condition = PortfolioCondition::CashBelow { amount: 50_000.0 };
+        assert_eq!(observed(&alert, &valuation), Some((40_000.0, None)));
+        assert!(PortfolioCondition::Drawdown { pct: 120.0 }
+            .validate()
+            .is_err());
+    }
+}
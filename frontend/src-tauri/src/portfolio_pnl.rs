@@ -0,0 +1,367 @@
+//! Positions, cost basis and P/L from the transaction ledger.
+//!
+//! The ledger's trades are replayed in date order, all of them or those
+//! booked to one named portfolio. Cost basis is the weighted average, as
+//! A-share brokers report it: buys add their cost and fees to it, and a sell
+//! takes out its share at the average, realizing the proceeds less fees
+//! above it. Dividends and fees entered against a symbol count towards its
+//! realized P/L; those without one count towards the currency's total only.
+//! A sell larger than the position is realized without cost for the excess,
+//! and reported as a warning, since it usually means earlier buys are
+//! missing from the ledger.
+//!
+//! Open positions are marked at the latest quote, fetched for any symbol not
+//! polled yet; a position without a quote has no unrealized P/L. There are
+//! no exchange rates, so totals are kept per currency.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{Database, Portfolios};
+use crate::instruments::InstrumentMaster;
+use crate::portfolio::{PortfolioStore, Transaction, TransactionKind};
+use crate::privacy::Privacy;
+use crate::quotes::{self, QuoteFeed};
+
+/// Positions smaller than this are closed
+const QUANTITY_TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionPnl {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub currency: String,
+    /// Zero once the position is closed
+    pub quantity: f64,
+    pub average_cost: f64,
+    pub cost_basis: f64,
+    pub price: Option<f64>,
+    pub market_value: Option<f64>,
+    pub unrealized_pnl: Option<f64>,
+    /// Unrealized P/L against the cost basis, in percent
+    pub unrealized_pct: Option<f64>,
+    /// Sells above cost, dividends and fees, since the first trade
+    pub realized_pnl: f64,
+    pub dividends: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PnlTotal {
+    pub currency: String,
+    pub cost_basis: f64,
+    /// Of the positions with a quote
+    pub market_value: f64,
+    pub unrealized_pnl: f64,
+    pub realized_pnl: f64,
+    /// Open positions left out of the market value for want of a quote
+    pub unpriced: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioPnl {
+    pub portfolio_id: Option<String>,
+    pub positions: Vec<PositionPnl>,
+    pub totals: Vec<PnlTotal>,
+    pub warnings: Vec<String>,
+}
+
+/// A position replayed from the ledger, before it is marked to market
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Position {
+    currency: String,
+    quantity: f64,
+    cost: f64,
+    realized: f64,
+    dividends: f64,
+}
+
+impl Position {
+    fn average_cost(&self) -> f64 {
+        if self.quantity > QUANTITY_TOLERANCE {
+            self.cost / self.quantity
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Ledger {
+    positions: BTreeMap<String, Position>,
+    /// Dividends and fees without a symbol, by currency
+    other: BTreeMap<String, f64>,
+    warnings: Vec<String>,
+}
+
+fn replay(transactions: &[&Transaction]) -> Ledger {
+    let mut ordered = transactions.to_vec();
+    ordered.sort_by(|a, b| (&a.entry.date, &a.recorded_at).cmp(&(&b.entry.date, &b.recorded_at)));
+    let mut ledger = Ledger::default();
+    for transaction in ordered {
+        let e = &transaction.entry;
+        if e.symbol.is_empty() {
+            let income = match e.kind {
+                TransactionKind::Dividend => e.amount - e.fee,
+                TransactionKind::Fee => -e.amount - e.fee,
+                _ => continue,
+            };
+            *ledger.other.entry(e.currency.clone()).or_default() += income;
+            continue;
+        }
+        let position = ledger.positions.entry(e.symbol.clone()).or_default();
+        position.currency = e.currency.clone();
+        match e.kind {
+            TransactionKind::Buy => {
+                position.quantity += e.quantity;
+                position.cost += e.quantity * e.price + e.fee;
+            }
+            TransactionKind::Sell => {
+                let covered = e.quantity.min(position.quantity);
+                if e.quantity - covered > QUANTITY_TOLERANCE {
+                    ledger.warnings.push(format!(
+                        "Sell of {} {} on {} exceeds the {} held",
+                        e.quantity, e.symbol, e.date, position.quantity
+                    ));
+                }
+                let cost = position.average_cost() * covered;
+                position.realized += e.quantity * e.price - e.fee - cost;
+                position.cost -= cost;
+                position.quantity -= covered;
+                if position.quantity <= QUANTITY_TOLERANCE {
+                    position.quantity = 0.0;
+                    position.cost = 0.0;
+                }
+            }
+            TransactionKind::Dividend => {
+                position.realized += e.amount - e.fee;
+                position.dividends += e.amount;
+            }
+            TransactionKind::Fee => position.realized -= e.amount + e.fee,
+            TransactionKind::Deposit | TransactionKind::Withdrawal => {}
+        }
+    }
+    ledger
+}
+
+/// Mark replayed positions at `price` and total them per currency
+fn mark(
+    ledger: Ledger,
+    price: impl Fn(&str) -> Option<f64>,
+    name: impl Fn(&str) -> Option<String>,
+) -> (Vec<PositionPnl>, Vec<PnlTotal>) {
+    let mut totals: BTreeMap<String, PnlTotal> = BTreeMap::new();
+    let mut positions = Vec::new();
+    for (symbol, position) in ledger.positions {
+        let open = position.quantity > 0.0;
+        let price = price(&symbol).filter(|_| open);
+        let market_value = price.map(|p| p * position.quantity);
+        let unrealized = market_value.map(|v| v - position.cost);
+        let total = totals
+            .entry(position.currency.clone())
+            .or_insert_with(|| PnlTotal {
+                currency: position.currency.clone(),
+                ..PnlTotal::default()
+            });
+        total.cost_basis += position.cost;
+        total.realized_pnl += position.realized;
+        match (market_value, unrealized) {
+            (Some(value), Some(pnl)) => {
+                total.market_value += value;
+                total.unrealized_pnl += pnl;
+            }
+            _ if open => total.unpriced += 1,
+            _ => {}
+        }
+        positions.push(PositionPnl {
+            name: name(&symbol),
+            currency: position.currency.clone(),
+            quantity: position.quantity,
+            average_cost: position.average_cost(),
+            cost_basis: position.cost,
+            price,
+            market_value,
+            unrealized_pnl: unrealized,
+            unrealized_pct: unrealized
+                .filter(|_| position.cost > 0.0)
+                .map(|pnl| pnl / position.cost * 100.0),
+            realized_pnl: position.realized,
+            dividends: position.dividends,
+            symbol,
+        });
+    }
+    for (currency, income) in ledger.other {
+        totals
+            .entry(currency.clone())
+            .or_insert_with(|| PnlTotal {
+                currency,
+                ..PnlTotal::default()
+            })
+            .realized_pnl += income;
+    }
+    (positions, totals.into_values().collect())
+}
+
+/// Positions and P/L of a named portfolio, or of the whole ledger
+#[tauri::command]
+pub async fn get_portfolio_pnl(
+    app: AppHandle,
+    store: State<'_, PortfolioStore>,
+    db: State<'_, Database>,
+    privacy: State<'_, Privacy>,
+    portfolio_id: Option<String>,
+) -> Result<PortfolioPnl, String> {
+    if let Some(id) = &portfolio_id {
+        let conn = db.conn()?;
+        Portfolios(&conn).get(id)?;
+    }
+    let portfolio = store.get();
+    let booked: Vec<&Transaction> = portfolio
+        .transactions
+        .iter()
+        .filter(|t| portfolio_id.is_none() || t.entry.portfolio_id == portfolio_id)
+        .collect();
+    let ledger = replay(&booked);
+    let held: Vec<String> = ledger
+        .positions
+        .iter()
+        .filter(|(_, p)| p.quantity > 0.0)
+        .map(|(symbol, _)| symbol.clone())
+        .collect();
+    let quotes: BTreeMap<String, f64> = if held.is_empty() {
+        BTreeMap::new()
+    } else {
+        quotes::get_quotes(app.clone(), app.state::<QuoteFeed>(), held)
+            .await?
+            .into_iter()
+            .filter(|q| q.price > 0.0)
+            .map(|q| (q.symbol, q.price))
+            .collect()
+    };
+    let names = app.state::<InstrumentMaster>();
+    let warnings = ledger.warnings.clone();
+    let (positions, totals) = mark(
+        ledger,
+        |symbol| quotes.get(symbol).copied(),
+        |symbol| names.name(symbol),
+    );
+    privacy.apply(PortfolioPnl {
+        portfolio_id,
+        positions,
+        totals,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::TransactionEntry;
+    use proptest::prelude::*;
+
+    fn entry(kind: TransactionKind, symbol: &str, quantity: f64, price: f64) -> Transaction {
+        Transaction {
+            id: format!("txn-{:?}-{}", kind, quantity),
+            entry: TransactionEntry {
+                account_id: "A1".to_string(),
+                kind,
+                symbol: symbol.to_string(),
+                quantity,
+                price,
+                amount: 0.0,
+                fee: 5.0,
+                currency: "CNY".to_string(),
+                date: String::new(),
+                note: String::new(),
+                portfolio_id: None,
+            },
+            recorded_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_average_cost_and_pnl() {
+        let dated = |mut t: Transaction, date: &str| {
+            t.entry.date = date.to_string();
+            t
+        };
+        let mut dividend = entry(TransactionKind::Dividend, "SH600036", 0.0, 0.0);
+        dividend.entry.amount = 20.0;
+        dividend.entry.fee = 0.0;
+        let transactions = [
+            // Out of order on purpose; the replay goes by date
+            dated(
+                entry(TransactionKind::Sell, "SH600036", 150.0, 15.0),
+                "2024-03-01",
+            ),
+            dated(
+                entry(TransactionKind::Buy, "SH600036", 100.0, 10.0),
+                "2024-01-02",
+            ),
+            dated(
+                entry(TransactionKind::Buy, "SH600036", 100.0, 12.0),
+                "2024-02-01",
+            ),
+            dated(dividend, "2024-06-01"),
+            dated(
+                entry(TransactionKind::Sell, "SZ000001", 100.0, 11.0),
+                "2024-04-01",
+            ),
+        ];
+        let ledger = replay(&transactions.iter().collect::<Vec<_>>());
+        assert_eq!(ledger.warnings.len(), 1);
+        assert!(ledger.warnings[0].contains("SZ000001"));
+
+        let (positions, totals) = mark(ledger, |_| Some(14.0), |_| None);
+        let cmb = &positions[0];
+        assert_eq!(cmb.quantity, 50.0);
+        // (1005 + 1205) / 200 shares
+        assert!((cmb.average_cost - 11.05).abs() < 1e-9);
+        // 2250 - 5 - 1657.5 from the sell, and the dividend
+        assert!((cmb.realized_pnl - 607.5).abs() < 1e-9);
+        assert!((cmb.unrealized_pnl.unwrap() - 147.5).abs() < 1e-9);
+        // A closed position has no price or unrealized P/L
+        assert_eq!(positions[1].quantity, 0.0);
+        assert_eq!(positions[1].unrealized_pnl, None);
+        assert_eq!(totals.len(), 1);
+        assert!((totals[0].realized_pnl - (607.5 + 1095.0)).abs() < 1e-9);
+        assert!((totals[0].market_value - 700.0).abs() < 1e-9);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_pnl_matches_cash_flows(
+            trades in prop::collection::vec((any::<bool>(), 1u32..50, 1.0f64..200.0), 1..40),
+            price in 1.0f64..200.0,
+        ) {
+            let transactions: Vec<Transaction> = trades
+                .iter()
+                .enumerate()
+                .map(|(i, &(buy, lots, price))| {
+                    let kind = if buy { TransactionKind::Buy } else { TransactionKind::Sell };
+                    let mut t = entry(kind, "SH600036", f64::from(lots * 100), price);
+                    t.recorded_at = format!("{:03}", i);
+                    t
+                })
+                .collect();
+            // Whatever the cost basis, the P/L is the money in and out plus
+            // what is still held; over-sells are booked at their full size
+            let (mut cash, mut gross) = (0.0, 0.0);
+            for t in &transactions {
+                let value = t.entry.quantity * t.entry.price;
+                cash += match t.entry.kind {
+                    TransactionKind::Buy => -value,
+                    _ => value,
+                } - t.entry.fee;
+                gross += value + t.entry.fee;
+            }
+            let ledger = replay(&transactions.iter().collect::<Vec<_>>());
+            let (positions, _) = mark(ledger, |_| Some(price), |_| None);
+            let position = &positions[0];
+            let pnl = position.realized_pnl + position.unrealized_pnl.unwrap_or(0.0);
+            let expected = cash + position.quantity * price;
+            prop_assert!((pnl - expected).abs() <= 1e-9 * (gross + expected.abs()));
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Keeping the system awake while long tasks run.
+//!
+//! Backfills, backtests and the other tasks in [`INHIBITING_KINDS`] can run
+//! for an hour or more, and a laptop suspending halfway through loses the
+//! run. While one is running the app holds a suspend inhibit, and it drops the
+//! inhibit as soon as the task completes, fails or is cancelled. On Linux the
+//! inhibit is taken over the session bus from
+//! `org.freedesktop.PowerManagement.Inhibit`, and the cookie it returns is
+//! handed back on release. If no power manager answers, the task still runs
+//! and the system may suspend. Other platforms don't inhibit yet.
+//! `inhibit_suspend` in settings turns this off.
+
+use log::{info, warn};
+use tauri::{AppHandle, Manager};
+
+use crate::settings::SettingsStore;
+
+/// Task kinds long enough to keep the system awake for
+pub const INHIBITING_KINDS: &[&str] = &[
+    "backtest",
+    "portfolio_backtest",
+    "news_backfill",
+    "embedding_rebuild",
+    "ai_batch",
+    "transcription",
+];
+
+/// Whether a task of `kind` should hold a suspend inhibit
+pub fn inhibits(kind: &str) -> bool {
+    INHIBITING_KINDS.contains(&kind)
+}
+
+/// Reason shown by the power manager, e.g. in a "suspend anyway?" prompt
+fn reason(kind: &str) -> String {
+    let what = match kind {
+        "backtest" | "portfolio_backtest" => "回测",
+        "news_backfill" => "历史资讯回补",
+        "embedding_rebuild" => "向量索引重建",
+        "ai_batch" => "AI 批量任务",
+        "transcription" => "音视频转写",
+        _ => "后台任务",
+    };
+    format!("{}进行中", what)
+}
+
+/// A held inhibit, released when dropped
+pub struct SuspendInhibit {
+    task_id: String,
+    held: Option<platform::Cookie>,
+}
+
+impl SuspendInhibit {
+    /// Inhibit suspend for a running task when its kind calls for it and
+    /// settings allow it; `None` when no inhibit was taken
+    pub fn acquire(app: &AppHandle, task_id: &str, kind: &str) -> Option<Self> {
+        if !inhibits(kind) || !app.state::<SettingsStore>().get().inhibit_suspend {
+            return None;
+        }
+        let cookie = match platform::inhibit(&reason(kind)) {
+            Ok(cookie) => cookie?,
+            Err(e) => {
+                warn!("Failed to inhibit suspend for task {}: {}", task_id, e);
+                return None;
+            }
+        };
+        info!("Inhibited suspend for task {} ({})", task_id, kind);
+        Some(Self {
+            task_id: task_id.to_string(),
+            held: Some(cookie),
+        })
+    }
+}
+
+impl Drop for SuspendInhibit {
+    fn drop(&mut self) {
+        let Some(cookie) = self.held.take() else {
+            return;
+        };
+        match platform::release(cookie) {
+            Ok(()) => info!("Released suspend inhibit of task {}", self.task_id),
+            Err(e) => warn!(
+                "Failed to release suspend inhibit of task {}: {}",
+                self.task_id, e
+            ),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use zbus::blocking::Connection;
+
+    const SERVICE: &str = "org.freedesktop.PowerManagement";
+    const PATH: &str = "/org/freedesktop/PowerManagement/Inhibit";
+    const INTERFACE: &str = "org.freedesktop.PowerManagement.Inhibit";
+    /// Name the inhibit is registered under
+    const APP_NAME: &str = "智股通";
+
+    /// Inhibit cookie and the connection it was taken on; the power manager
+    /// also drops the inhibit if the connection closes
+    pub struct Cookie {
+        connection: Connection,
+        cookie: u32,
+    }
+
+    pub fn inhibit(reason: &str) -> Result<Option<Cookie>, String> {
+        let connection =
+            Connection::session().map_err(|e| format!("No D-Bus session bus: {}", e))?;
+        let reply = connection
+            .call_method(
+                Some(SERVICE),
+                PATH,
+                Some(INTERFACE),
+                "Inhibit",
+                &(APP_NAME, reason),
+            )
+            .map_err(|e| format!("Inhibit call failed: {}", e))?;
+        let cookie = reply
+            .body()
+            .deserialize::<u32>()
+            .map_err(|e| format!("Unexpected Inhibit reply: {}", e))?;
+        Ok(Some(Cookie { connection, cookie }))
+    }
+
+    pub fn release(held: Cookie) -> Result<(), String> {
+        held.connection
+            .call_method(
+                Some(SERVICE),
+                PATH,
+                Some(INTERFACE),
+                "UnInhibit",
+                &(held.cookie,),
+            )
+            .map(|_| ())
+            .map_err(|e| format!("UnInhibit call failed: {}", e))
+    }
+}
+
+/// No inhibit is taken outside Linux yet
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    pub enum Cookie {}
+
+    pub fn inhibit(_reason: &str) -> Result<Option<Cookie>, String> {
+        Ok(None)
+    }
+
+    pub fn release(held: Cookie) -> Result<(), String> {
+        match held {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_tasks_inhibit() {
+        assert!(inhibits("backtest"));
+        assert!(inhibits("news_backfill"));
+        assert!(!inhibits("preload"));
+        assert!(!inhibits("install_update"));
+        assert_eq!(reason("portfolio_backtest"), "回测进行中");
+    }
+}
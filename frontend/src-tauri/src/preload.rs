@@ -0,0 +1,257 @@
+//! Usage tracking and startup cache pre-warming.
+//!
+//! Symbol views are recorded with an exponentially decaying score. At
+//! startup the highest-scoring symbols have their quotes fetched, and their
+//! stored bars mapped and paged in and their default indicators computed in
+//! a background task, so the first chart the user opens is served from warm
+//! caches.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::columnar::ColumnarStore;
+use crate::executor::{Priority, ResourceClass};
+use crate::indicators::{IndicatorCache, DEFAULT_SPECS};
+use crate::quotes;
+use crate::recents;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::tasks::{spawn_task_with, TaskContext};
+use crate::utils::{get_timestamp, read_from_file, write_to_file};
+
+/// Interval pre-warmed for each symbol
+const PRELOAD_INTERVAL: &str = "1d";
+
+/// A view counts half as much after this many seconds
+const SCORE_HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 3600.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SymbolUsage {
+    views: u64,
+    score: f64,
+    last_viewed: i64,
+}
+
+impl SymbolUsage {
+    fn decayed_score(&self, now: i64) -> f64 {
+        let age = (now - self.last_viewed).max(0) as f64;
+        self.score * 0.5f64.powf(age / SCORE_HALF_LIFE_SECS)
+    }
+}
+
+/// Persistent per-symbol view counters
+pub struct UsageTracker {
+    path: PathBuf,
+    symbols: Mutex<HashMap<String, SymbolUsage>>,
+}
+
+impl UsageTracker {
+    pub fn load(path: PathBuf) -> Self {
+        let symbols = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            symbols: Mutex::new(symbols),
+        }
+    }
+
+    pub fn record_view(&self, symbol: &str, now: i64) -> Result<(), String> {
+        let mut symbols = self.symbols.lock().unwrap();
+        let usage = symbols.entry(symbol.to_uppercase()).or_default();
+        usage.score = usage.decayed_score(now) + 1.0;
+        usage.views += 1;
+        usage.last_viewed = now;
+
+        let content = serde_json::to_string(&*symbols)
+            .map_err(|e| format!("Failed to serialize usage data: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save usage data: {}", e))
+    }
+
+    /// Most viewed symbols, best first
+    pub fn top(&self, n: usize, now: i64) -> Vec<String> {
+        let symbols = self.symbols.lock().unwrap();
+        let mut ranked: Vec<(&String, f64)> = symbols
+            .iter()
+            .map(|(symbol, usage)| (symbol, usage.decayed_score(now)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        ranked.into_iter().take(n).map(|(s, _)| s.clone()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreloadStats {
+    enabled: bool,
+    running: bool,
+    last_run_at: Option<String>,
+    duration_ms: Option<u64>,
+    symbols: Vec<String>,
+    warmed: usize,
+    skipped: usize,
+    quotes_warmed: usize,
+    cached_indicator_sets: usize,
+}
+
+#[derive(Default)]
+pub struct PreloadState {
+    stats: Mutex<PreloadStats>,
+}
+
+/// Kick off the startup pre-warm if enabled in settings
+pub fn start_preload(app: &AppHandle) {
+    let settings = app.state::<SettingsStore>().get();
+    if !settings.preload_enabled {
+        info!("Cache preloading disabled in settings");
+        return;
+    }
+    let symbols = app
+        .state::<UsageTracker>()
+        .top(settings.preload_symbols, Utc::now().timestamp());
+    if symbols.is_empty() {
+        return;
+    }
+
+    app.state::<PreloadState>().stats.lock().unwrap().running = true;
+    // Quotes come from the network, alongside the disk-bound task below
+    let handle = app.clone();
+    let quoted = symbols.clone();
+    tauri::async_runtime::spawn(async move {
+        match quotes::warm(&handle, &quoted).await {
+            Ok(fetched) => {
+                let state = handle.state::<PreloadState>();
+                state.stats.lock().unwrap().quotes_warmed = fetched;
+            }
+            Err(e) => warn!("Failed to preload quotes: {}", e),
+        }
+    });
+    let handle = app.clone();
+    spawn_task_with(
+        app,
+        "preload",
+        ResourceClass::Disk,
+        Priority::Background,
+        move |ctx: TaskContext| {
+            let result = warm_symbols(&handle, &ctx, symbols);
+            let state = handle.state::<PreloadState>();
+            let mut stats = state.stats.lock().unwrap();
+            match &result {
+                Ok(finished) => {
+                    *stats = PreloadStats {
+                        quotes_warmed: stats.quotes_warmed,
+                        ..finished.clone()
+                    }
+                }
+                Err(_) => stats.running = false,
+            }
+            result
+        },
+    );
+}
+
+fn warm_symbols(
+    app: &AppHandle,
+    ctx: &TaskContext,
+    symbols: Vec<String>,
+) -> Result<PreloadStats, String> {
+    let started = Instant::now();
+    let store = app.state::<ColumnarStore>();
+    let cache = app.state::<IndicatorCache>();
+    let until = app.state::<SnapshotClock>().frozen_at();
+    let (mut warmed, mut skipped) = (0, 0);
+
+    for (i, symbol) in symbols.iter().enumerate() {
+        ctx.checkpoint()?;
+        if !store.exists(symbol, PRELOAD_INTERVAL) {
+            skipped += 1;
+            continue;
+        }
+        match store.open(symbol, PRELOAD_INTERVAL) {
+            Ok(bars) => {
+                let rows = bars.rows_until(until);
+                cache.compute(symbol, PRELOAD_INTERVAL, &bars, rows, &DEFAULT_SPECS);
+                warmed += 1;
+            }
+            Err(e) => {
+                warn!("Failed to preload {}: {}", symbol, e);
+                skipped += 1;
+            }
+        }
+        ctx.report("warm", i as u64 + 1, symbols.len() as u64);
+    }
+
+    info!(
+        "Cache preload finished: {} warmed, {} skipped",
+        warmed, skipped
+    );
+    Ok(PreloadStats {
+        enabled: true,
+        running: false,
+        last_run_at: Some(get_timestamp()),
+        duration_ms: Some(started.elapsed().as_millis() as u64),
+        symbols,
+        warmed,
+        skipped,
+        quotes_warmed: 0,
+        cached_indicator_sets: cache.len(),
+    })
+}
+
+/// Record that the user opened a symbol
+#[tauri::command]
+pub fn record_symbol_view(
+    app: AppHandle,
+    tracker: State<'_, UsageTracker>,
+    symbol: String,
+) -> Result<(), String> {
+    tracker.record_view(&symbol, Utc::now().timestamp())?;
+    recents::record(&app, &symbol);
+    Ok(())
+}
+
+/// Statistics about the last startup pre-warm
+#[tauri::command]
+pub fn get_preload_stats(
+    state: State<'_, PreloadState>,
+    settings: State<'_, SettingsStore>,
+    cache: State<'_, IndicatorCache>,
+) -> Result<PreloadStats, String> {
+    let mut stats = state.stats.lock().unwrap().clone();
+    stats.enabled = settings.get().preload_enabled;
+    stats.cached_indicator_sets = cache.len();
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_views_outrank_stale_ones() {
+        let path = std::env::temp_dir().join(format!("ssi-usage-{}.json", std::process::id()));
+        let tracker = UsageTracker::load(path.clone());
+        let now = 1_700_000_000;
+        let month = 30 * 24 * 3600;
+
+        for _ in 0..5 {
+            tracker.record_view("sh600519", now - month).unwrap();
+        }
+        tracker.record_view("sz000001", now).unwrap();
+        tracker.record_view("sz000001", now).unwrap();
+
+        assert_eq!(tracker.top(2, now), vec!["SZ000001", "SH600519"]);
+        assert_eq!(
+            UsageTracker::load(path.clone()).top(1, now),
+            vec!["SZ000001"]
+        );
+        std::fs::remove_file(path).ok();
+    }
+}
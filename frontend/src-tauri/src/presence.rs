@@ -0,0 +1,347 @@
+//! Screen lock and idle detection.
+//!
+//! An always-on workstation spends most nights with the screen locked, and
+//! quotes streamed or polled into a window nobody can see only use up
+//! bandwidth and provider quota. Every [`CHECK_INTERVAL`] the session is
+//! sampled: whether the screen is locked and how long since the last input.
+//! While it is locked, or idle for longer than
+//! [`PresenceSettings::idle_minutes`], the quote stream goes idle and quote
+//! and news polling stop. When the session becomes active again the pollers
+//! are woken at once and poll everything they skipped, so the screen is
+//! current within a round trip of unlocking. Changes reach the frontend as
+//! `session-presence` events.
+//!
+//! Windows reads the lock from whether the input desktop can be switched to
+//! and idle time from the last input tick; macOS reads both from the
+//! CoreGraphics session; Linux asks the `org.freedesktop.ScreenSaver`
+//! service on the session bus, whose idle time not every desktop provides.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::presentation;
+use crate::settings::SettingsStore;
+
+/// Interval between samples of the session state
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceSettings {
+    /// Pause streaming and polling while the screen is locked
+    pub pause_when_locked: bool,
+    /// Also pause after this many minutes without input; 0 never. Off by
+    /// default, since a chart watched without touching the mouse is in use
+    pub idle_minutes: u32,
+}
+
+impl Default for PresenceSettings {
+    fn default() -> Self {
+        Self {
+            pause_when_locked: true,
+            idle_minutes: 0,
+        }
+    }
+}
+
+impl PresenceSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.idle_minutes > 24 * 60 {
+            return Err("Idle pause must be at most a day".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    Active,
+    Idle,
+    Locked,
+}
+
+/// Presence from a sample; a platform that can't tell counts as active
+fn classify(locked: Option<bool>, idle_secs: Option<u64>, settings: &PresenceSettings) -> Presence {
+    if locked == Some(true) {
+        return Presence::Locked;
+    }
+    let idle_limit = u64::from(settings.idle_minutes) * 60;
+    match idle_secs {
+        Some(idle) if settings.idle_minutes > 0 && idle >= idle_limit => Presence::Idle,
+        _ => Presence::Active,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PresenceStatus {
+    pub presence: Presence,
+    /// Whether streaming and polling are paused for it
+    pub paused: bool,
+}
+
+pub struct SessionPresence {
+    status: Mutex<PresenceStatus>,
+    /// Wakes the pollers when a pause ends
+    resumed: Notify,
+}
+
+impl Default for SessionPresence {
+    fn default() -> Self {
+        Self {
+            status: Mutex::new(PresenceStatus {
+                presence: Presence::Active,
+                paused: false,
+            }),
+            resumed: Notify::new(),
+        }
+    }
+}
+
+impl SessionPresence {
+    pub fn paused(&self) -> bool {
+        self.status.lock().unwrap().paused
+    }
+
+    fn update(&self, app: &AppHandle, presence: Presence, settings: &PresenceSettings) {
+        let paused = match presence {
+            Presence::Active => false,
+            Presence::Idle => true,
+            Presence::Locked => settings.pause_when_locked,
+        };
+        let was_paused = {
+            let mut status = self.status.lock().unwrap();
+            if status.presence == presence && status.paused == paused {
+                return;
+            }
+            let was_paused = status.paused;
+            *status = PresenceStatus { presence, paused };
+            was_paused
+        };
+        info!("Session is now {:?}", presence);
+        if was_paused && !paused {
+            self.resumed.notify_waiters();
+        }
+        let status = PresenceStatus { presence, paused };
+        if let Err(e) = app.emit("session-presence", status) {
+            warn!("Failed to emit session-presence event: {}", e);
+        }
+    }
+}
+
+/// Whether streaming and polling should hold off
+pub fn paused(app: &AppHandle) -> bool {
+    app.state::<SessionPresence>().paused()
+}
+
+/// Sleep for `delay`, or until a pause ends if that comes first
+pub async fn sleep_or_resume(app: &AppHandle, delay: Duration) {
+    let presence = app.state::<SessionPresence>();
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => {}
+        _ = presence.resumed.notified() => {}
+    }
+}
+
+/// Sample the session state for as long as the app runs
+pub fn start_presence_monitor(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let sample = tauri::async_runtime::spawn_blocking(|| {
+                (platform::locked(), platform::idle_secs())
+            })
+            .await;
+            if let Ok((locked, idle_secs)) = sample {
+                let settings = handle.state::<SettingsStore>().get().presence;
+                // A wall screen is watched without anyone touching it
+                let idle_secs = idle_secs.filter(|_| !presentation::presenting(&handle));
+                let presence = classify(locked, idle_secs, &settings);
+                handle
+                    .state::<SessionPresence>()
+                    .update(&handle, presence, &settings);
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_session_presence(
+    presence: State<'_, SessionPresence>,
+) -> Result<PresenceStatus, String> {
+    Ok(presence.status.lock().unwrap().clone())
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::System::StationsAndDesktops::{
+        CloseDesktop, OpenInputDesktop, SwitchDesktop, DESKTOP_CONTROL_FLAGS, DESKTOP_SWITCHDESKTOP,
+    };
+    use windows::Win32::System::SystemInformation::GetTickCount;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    /// The secure desktop of the lock screen can't be switched away from
+    pub fn locked() -> Option<bool> {
+        unsafe {
+            let Ok(desktop) = OpenInputDesktop(
+                DESKTOP_CONTROL_FLAGS(0),
+                BOOL::from(false),
+                DESKTOP_SWITCHDESKTOP,
+            ) else {
+                return Some(true);
+            };
+            let switchable = SwitchDesktop(desktop).is_ok();
+            let _ = CloseDesktop(desktop);
+            Some(!switchable)
+        }
+    }
+
+    pub fn idle_secs() -> Option<u64> {
+        let mut info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        unsafe {
+            if !GetLastInputInfo(&mut info).as_bool() {
+                return None;
+            }
+            Some(u64::from(GetTickCount().wrapping_sub(info.dwTime)) / 1000)
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{c_char, c_void};
+    use std::ptr;
+
+    const UTF8_ENCODING: u32 = 0x0800_0100;
+    const COMBINED_SESSION_STATE: i32 = 0;
+    const ANY_INPUT_EVENT: u32 = !0;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSessionCopyCurrentDictionary() -> *const c_void;
+        fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            allocator: *const c_void,
+            string: *const c_char,
+            encoding: u32,
+        ) -> *const c_void;
+        fn CFDictionaryGetValue(dictionary: *const c_void, key: *const c_void) -> *const c_void;
+        fn CFBooleanGetValue(boolean: *const c_void) -> u8;
+        fn CFRelease(object: *const c_void);
+    }
+
+    /// The session dictionary carries `CGSSessionScreenIsLocked` only while locked
+    pub fn locked() -> Option<bool> {
+        unsafe {
+            let session = CGSessionCopyCurrentDictionary();
+            if session.is_null() {
+                return None;
+            }
+            let key = CFStringCreateWithCString(
+                ptr::null(),
+                b"CGSSessionScreenIsLocked\0".as_ptr().cast(),
+                UTF8_ENCODING,
+            );
+            let value = CFDictionaryGetValue(session, key);
+            let locked = !value.is_null() && CFBooleanGetValue(value) != 0;
+            CFRelease(key);
+            CFRelease(session);
+            Some(locked)
+        }
+    }
+
+    pub fn idle_secs() -> Option<u64> {
+        let secs = unsafe {
+            CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT)
+        };
+        secs.is_finite().then_some(secs as u64)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::sync::OnceLock;
+
+    use zbus::blocking::Connection;
+
+    const SERVICE: &str = "org.freedesktop.ScreenSaver";
+    const PATH: &str = "/org/freedesktop/ScreenSaver";
+
+    fn connection() -> Option<&'static Connection> {
+        static CONNECTION: OnceLock<Option<Connection>> = OnceLock::new();
+        CONNECTION
+            .get_or_init(|| Connection::session().ok())
+            .as_ref()
+    }
+
+    fn call(method: &str) -> Option<zbus::Message> {
+        connection()?
+            .call_method(Some(SERVICE), PATH, Some(SERVICE), method, &())
+            .ok()
+    }
+
+    /// The screensaver is active whenever the screen is locked
+    pub fn locked() -> Option<bool> {
+        call("GetActive")?.body().deserialize::<bool>().ok()
+    }
+
+    pub fn idle_secs() -> Option<u64> {
+        let reply = call("GetSessionIdleTime")?;
+        reply.body().deserialize::<u32>().ok().map(u64::from)
+    }
+}
+
+/// Other platforms can't tell, and always count as active
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+mod platform {
+    pub fn locked() -> Option<bool> {
+        None
+    }
+
+    pub fn idle_secs() -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_presence() {
+        let settings = PresenceSettings {
+            pause_when_locked: true,
+            idle_minutes: 30,
+        };
+        assert_eq!(classify(Some(true), Some(0), &settings), Presence::Locked);
+        assert_eq!(classify(Some(false), Some(60), &settings), Presence::Active);
+        assert_eq!(classify(None, Some(30 * 60), &settings), Presence::Idle);
+        assert_eq!(classify(None, None, &settings), Presence::Active);
+
+        let never_idle = PresenceSettings::default();
+        assert_eq!(
+            classify(Some(false), Some(86_400), &never_idle),
+            Presence::Active
+        );
+        assert!(PresenceSettings {
+            idle_minutes: 2000,
+            ..settings
+        }
+        .validate()
+        .is_err());
+    }
+}
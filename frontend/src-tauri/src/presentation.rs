@@ -0,0 +1,337 @@
+//! Presentation mode for wall screens.
+//!
+//! [`enter_presentation_mode`] opens a borderless fullscreen window on the
+//! chosen monitor that cycles through the views in [`PresentationSettings`],
+//! telling its page which one to show with a `presentation-view` event every
+//! [`PresentationSettings::dwell_secs`]. Escape on the page, or
+//! [`exit_presentation_mode`], closes it; closing the window any other way
+//! ends the rotation too.
+//!
+//! With a schedule set, the window opens as the schedule's hours begin and
+//! closes as they end, checked every [`SCHEDULE_CHECK`]. Only the edges act,
+//! so a presentation escaped during its hours stays closed until the next
+//! day's, and one opened by hand outside them isn't closed. While the window
+//! is open, idle time doesn't pause quotes, since nobody touches the input of
+//! a wall screen.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder};
+
+use crate::placement::{self, Screen};
+use crate::settings::SettingsStore;
+
+pub const PRESENTATION_LABEL: &str = "presentation";
+
+/// Interval between checks of the schedule
+const SCHEDULE_CHECK: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentationView {
+    Heatmap,
+    Watchlist,
+    News,
+}
+
+/// Hours a presentation runs by itself, in local time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresentationSchedule {
+    /// `HH:MM`
+    pub start: String,
+    /// `HH:MM`; before `start` for hours running past midnight
+    pub end: String,
+    /// ISO weekdays the hours start on, 1 for Monday; every day when empty
+    #[serde(default)]
+    pub weekdays: BTreeSet<u32>,
+}
+
+impl PresentationSchedule {
+    fn times(&self) -> Result<(NaiveTime, NaiveTime), String> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("Invalid schedule time: {}", time))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    fn starts_on(&self, weekday: u32) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&weekday)
+    }
+
+    /// Whether `now` falls within the scheduled hours
+    pub fn covers(&self, now: NaiveDateTime) -> bool {
+        let Ok((start, end)) = self.times() else {
+            return false;
+        };
+        let weekday = now.weekday().number_from_monday();
+        let time = now.time();
+        if start <= end {
+            return self.starts_on(weekday) && start <= time && time < end;
+        }
+        let yesterday = now.weekday().pred().number_from_monday();
+        (self.starts_on(weekday) && time >= start) || (self.starts_on(yesterday) && time < end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresentationSettings {
+    /// Monitor name to present on; the first connected when unset
+    pub display: Option<String>,
+    /// Views shown in turn
+    pub views: Vec<PresentationView>,
+    /// Seconds each view stays up
+    pub dwell_secs: u32,
+    pub schedule: Option<PresentationSchedule>,
+}
+
+impl Default for PresentationSettings {
+    fn default() -> Self {
+        Self {
+            display: None,
+            views: vec![
+                PresentationView::Heatmap,
+                PresentationView::Watchlist,
+                PresentationView::News,
+            ],
+            dwell_secs: 30,
+            schedule: None,
+        }
+    }
+}
+
+impl PresentationSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.views.is_empty() {
+            return Err("Presentation mode needs at least one view".to_string());
+        }
+        if !(5..=3600).contains(&self.dwell_secs) {
+            return Err("Presentation views must stay up 5 to 3600 seconds".to_string());
+        }
+        if let Some(schedule) = &self.schedule {
+            let (start, end) = schedule.times()?;
+            if start == end {
+                return Err("Presentation schedule must not start and end together".to_string());
+            }
+            if let Some(day) = schedule.weekdays.iter().find(|day| !(1..=7).contains(*day)) {
+                return Err(format!("Invalid weekday: {}", day));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Screen named `display`, or the first one
+fn pick_screen(screens: &[Screen], display: Option<&str>) -> Option<Screen> {
+    display
+        .and_then(|name| {
+            screens
+                .iter()
+                .find(|screen| screen.name.as_deref() == Some(name))
+        })
+        .or(screens.first())
+        .cloned()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PresentationState {
+    pub active: bool,
+    pub view: Option<PresentationView>,
+    pub display: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Presentation {
+    /// Bumped by every enter and exit, ending an earlier rotation
+    generation: AtomicU64,
+    /// Whether the schedule covered the last check
+    scheduled: AtomicBool,
+    view: Mutex<Option<PresentationView>>,
+    display: Mutex<Option<String>>,
+}
+
+/// Whether the presentation window is open
+pub fn presenting(app: &AppHandle) -> bool {
+    app.get_webview_window(PRESENTATION_LABEL).is_some()
+}
+
+/// Show each configured view in turn for as long as the session lasts
+fn start_rotation(app: &AppHandle, generation: u64) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let presentation = handle.state::<Presentation>();
+        let mut index = 0;
+        while presentation.generation.load(Ordering::SeqCst) == generation && presenting(&handle) {
+            let settings = handle.state::<SettingsStore>().get().presentation;
+            let view = settings.views[index % settings.views.len()];
+            *presentation.view.lock().unwrap() = Some(view);
+            if let Err(e) = handle.emit_to(PRESENTATION_LABEL, "presentation-view", view) {
+                warn!("Failed to switch presentation view: {}", e);
+            }
+            index += 1;
+            tokio::time::sleep(Duration::from_secs(u64::from(settings.dwell_secs))).await;
+        }
+    });
+}
+
+fn open(app: &AppHandle, display: Option<String>) -> Result<(), String> {
+    let settings = app.state::<SettingsStore>().get().presentation;
+    let display = display.or(settings.display);
+    let screen = pick_screen(&placement::screens(app), display.as_deref())
+        .ok_or_else(|| "No monitor to present on".to_string())?;
+    // Entering again moves the open presentation instead of starting another
+    let window = match app.get_webview_window(PRESENTATION_LABEL) {
+        Some(window) => {
+            window
+                .set_fullscreen(false)
+                .map_err(|e| format!("Failed to move presentation window: {}", e))?;
+            window
+        }
+        None => {
+            WebviewWindowBuilder::new(app, PRESENTATION_LABEL, WebviewUrl::App("present".into()))
+                .title("演示")
+                .decorations(false)
+                .skip_taskbar(true)
+                .visible(false)
+                .build()
+                .map_err(|e| format!("Failed to open presentation window: {}", e))?
+        }
+    };
+    // Fullscreen takes the monitor the window is on, so move it there first
+    placement::set_rect(&window, &screen.area)
+        .and_then(|_| window.set_fullscreen(true))
+        .and_then(|_| window.show())
+        .and_then(|_| window.set_focus())
+        .map_err(|e| format!("Failed to show presentation window: {}", e))?;
+
+    let presentation = app.state::<Presentation>();
+    *presentation.display.lock().unwrap() = screen.name.clone();
+    let generation = presentation.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    start_rotation(app, generation);
+    info!(
+        "Presenting on {}",
+        screen.name.as_deref().unwrap_or("the first monitor")
+    );
+    Ok(())
+}
+
+fn close(app: &AppHandle) -> Result<(), String> {
+    let presentation = app.state::<Presentation>();
+    presentation.generation.fetch_add(1, Ordering::SeqCst);
+    *presentation.view.lock().unwrap() = None;
+    *presentation.display.lock().unwrap() = None;
+    if let Some(window) = app.get_webview_window(PRESENTATION_LABEL) {
+        window
+            .close()
+            .map_err(|e| format!("Failed to close presentation window: {}", e))?;
+        info!("Left presentation mode");
+    }
+    Ok(())
+}
+
+/// Open and close the presentation as its scheduled hours begin and end
+pub fn start_presentation_schedule(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let schedule = handle.state::<SettingsStore>().get().presentation.schedule;
+            let covered = schedule.is_some_and(|s| s.covers(Local::now().naive_local()));
+            let was = handle
+                .state::<Presentation>()
+                .scheduled
+                .swap(covered, Ordering::SeqCst);
+            let result = match (was, covered) {
+                (false, true) if !presenting(&handle) => open(&handle, None),
+                (true, false) => close(&handle),
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                warn!("Scheduled presentation failed: {}", e);
+            }
+            tokio::time::sleep(SCHEDULE_CHECK).await;
+        }
+    });
+}
+
+/// Present the configured views fullscreen on `display`, or on the one in
+/// settings
+#[tauri::command]
+pub fn enter_presentation_mode(app: AppHandle, display: Option<String>) -> Result<(), String> {
+    open(&app, display)
+}
+
+#[tauri::command]
+pub fn exit_presentation_mode(app: AppHandle) -> Result<(), String> {
+    close(&app)
+}
+
+#[tauri::command]
+pub fn get_presentation_state(
+    app: AppHandle,
+    presentation: State<'_, Presentation>,
+) -> Result<PresentationState, String> {
+    let active = presenting(&app);
+    Ok(PresentationState {
+        active,
+        view: (*presentation.view.lock().unwrap()).filter(|_| active),
+        display: presentation
+            .display
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|_| active),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // 2025-06-02 is a Monday
+        NaiveDate::from_ymd_opt(2025, 6, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schedule() {
+        let office = PresentationSchedule {
+            start: "08:30".to_string(),
+            end: "18:00".to_string(),
+            weekdays: BTreeSet::from([1, 2, 3, 4, 5]),
+        };
+        assert!(office.covers(at(2, 8, 30)));
+        assert!(!office.covers(at(2, 18, 0)));
+        assert!(!office.covers(at(7, 10, 0)));
+
+        // Overnight hours belong to the day they start on
+        let night = PresentationSchedule {
+            start: "21:00".to_string(),
+            end: "02:00".to_string(),
+            weekdays: BTreeSet::from([5]),
+        };
+        assert!(night.covers(at(6, 23, 0)));
+        assert!(night.covers(at(7, 1, 59)));
+        assert!(!night.covers(at(8, 1, 0)));
+
+        let settings = PresentationSettings {
+            schedule: Some(PresentationSchedule {
+                weekdays: BTreeSet::from([8]),
+                ..office
+            }),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+        assert!(PresentationSettings::default().validate().is_ok());
+    }
+}
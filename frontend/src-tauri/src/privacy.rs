@@ -0,0 +1,178 @@
+//! Privacy mode for screen sharing.
+//!
+//! While enabled, portfolio responses have their absolute amounts (position
+//! sizes, cash, market values, P&L) multiplied by a secret factor drawn at
+//! launch. Prices, ratios and percentages are unchanged, so charts and
+//! weights still read correctly, but what is on screen no longer reveals
+//! what the account is worth. Redaction happens here rather than in the UI
+//! so nothing sensitive reaches the webview at all. Portfolio edits are
+//! refused meanwhile, since a form filled from scaled values would save
+//! them.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{info, warn};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::settings::SettingsStore;
+
+/// Fields holding absolute amounts, in any portfolio response
+const SENSITIVE_FIELDS: &[&str] = &[
+    "quantity",
+    "amount",
+    "fee",
+    "cash",
+    "market_value",
+    "net_liquidation",
+    "ledger_quantity",
+    "broker_quantity",
+    "ledger_cash",
+    "broker_cash",
+    "difference",
+    "pnl",
+    "realized_pnl",
+    "unrealized_pnl",
+    "cost_basis",
+    "peak",
+    "dividends",
+];
+
+pub struct Privacy {
+    enabled: AtomicBool,
+    factor: f64,
+}
+
+impl Privacy {
+    pub fn new(enabled: bool) -> Self {
+        // Far enough from 1 that scaled figures are never close to the real ones
+        let mut rng = rand::thread_rng();
+        let factor = if rng.gen_bool(0.5) {
+            rng.gen_range(0.1..0.5)
+        } else {
+            rng.gen_range(2.0..10.0)
+        };
+        Self {
+            enabled: AtomicBool::new(enabled),
+            factor,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// A response with its absolute amounts scaled when privacy mode is on
+    pub fn apply<T: Serialize + DeserializeOwned>(&self, response: T) -> Result<T, String> {
+        if !self.enabled() {
+            return Ok(response);
+        }
+        serde_json::to_value(&response)
+            .and_then(|mut value| {
+                scale(&mut value, self.factor);
+                serde_json::from_value(value)
+            })
+            .map_err(|e| format!("Failed to redact response: {}", e))
+    }
+
+    pub fn check_editable(&self) -> Result<(), String> {
+        if self.enabled() {
+            return Err("Turn off privacy mode to edit the portfolio".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn scale(value: &mut Value, factor: f64) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                match field {
+                    Value::Number(n) if SENSITIVE_FIELDS.contains(&key.as_str()) => {
+                        if let Some(scaled) = n
+                            .as_f64()
+                            .and_then(|v| serde_json::Number::from_f64(v * factor))
+                        {
+                            *n = scaled;
+                        }
+                    }
+                    _ => scale(field, factor),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| scale(item, factor)),
+        _ => {}
+    }
+}
+
+#[tauri::command]
+pub fn get_privacy_mode(privacy: State<'_, Privacy>) -> Result<bool, String> {
+    Ok(privacy.enabled())
+}
+
+/// Turn privacy mode on or off; the frontend reloads portfolio views on `privacy-mode-changed`
+#[tauri::command]
+pub fn set_privacy_mode(
+    app: AppHandle,
+    privacy: State<'_, Privacy>,
+    settings: State<'_, SettingsStore>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut updated = settings.get();
+    updated.privacy_mode = enabled;
+    settings.set(updated)?;
+    privacy.enabled.store(enabled, Ordering::Relaxed);
+    info!("Privacy mode {}", if enabled { "on" } else { "off" });
+    if let Err(e) = app.emit("privacy-mode-changed", enabled) {
+        warn!("Failed to emit privacy-mode-changed event: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{BrokerAccount, CashBalance, Holding};
+
+    #[test]
+    fn test_amounts_scaled_prices_kept() {
+        let account = BrokerAccount {
+            broker: "ibkr".to_string(),
+            account_id: "U1".to_string(),
+            positions: vec![Holding {
+                id: "h".to_string(),
+                symbol: "AAPL".to_string(),
+                quantity: 100.0,
+                cost_price: 150.0,
+                currency: "USD".to_string(),
+                market_price: Some(180.0),
+                note: String::new(),
+                updated_at: "t".to_string(),
+            }],
+            balances: vec![CashBalance {
+                currency: "USD".to_string(),
+                cash: 2_000.0,
+                market_value: 18_000.0,
+                net_liquidation: 20_000.0,
+            }],
+            synced_at: "t".to_string(),
+        };
+        let privacy = Privacy::new(false);
+        assert_eq!(privacy.apply(account.clone()).unwrap(), account);
+        assert!(privacy.check_editable().is_ok());
+
+        privacy.enabled.store(true, Ordering::Relaxed);
+        let shown = privacy.apply(account.clone()).unwrap();
+        let position = &shown.positions[0];
+        assert!((position.quantity - 100.0).abs() > 40.0);
+        assert_eq!(position.cost_price, 150.0);
+        assert_eq!(position.market_price, Some(180.0));
+        // Ratios survive scaling
+        let balance = &shown.balances[0];
+        assert!((balance.cash / balance.net_liquidation - 0.1).abs() < 1e-9);
+        assert!(privacy.check_editable().is_err());
+    }
+}
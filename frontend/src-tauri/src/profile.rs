@@ -0,0 +1,361 @@
+//! Profile export and import for moving user data between machines.
+//!
+//! A profile is a JSON file of the user's own records, deleted ones
+//! included so deletions carry over, their watchlists and notes, and their
+//! saved workspaces. Importing merges it into the local data
+//! (see [`merge`](crate::merge)) and reports what was added, updated,
+//! deleted, skipped as a duplicate or decided by the conflict policy.
+//! Watchlists and notes stay in the database's trash on the machine they
+//! were deleted on, so only their additions and edits carry over.
+
+use std::path::PathBuf;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::audit::current_user;
+use crate::db::{Database, Note, Notes, Watchlist, WatchlistItem, Watchlists};
+use crate::merge::{merge, MergeChange, MergeReport, Mergeable};
+use crate::portfolio::{Portfolio, PortfolioStore};
+use crate::privacy::Privacy;
+use crate::settings::SettingsStore;
+use crate::utils::{get_timestamp, read_from_file, write_to_file};
+use crate::workspace::Workspace;
+
+const PROFILE_FORMAT: &str = "smart-stock-insider-profile";
+const PROFILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub portfolio: Portfolio,
+    #[serde(default)]
+    pub watchlists: Vec<Watchlist>,
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub workspaces: Vec<Workspace>,
+}
+
+impl Profile {
+    fn parse(content: &str) -> Result<Self, String> {
+        let profile: Profile =
+            serde_json::from_str(content).map_err(|e| format!("Failed to read profile: {}", e))?;
+        if profile.format != PROFILE_FORMAT {
+            return Err("Not a Smart Stock Insider profile".to_string());
+        }
+        if profile.version > PROFILE_VERSION {
+            return Err(format!(
+                "Profile version {} is newer than this app supports; update the app first",
+                profile.version
+            ));
+        }
+        Ok(profile)
+    }
+}
+
+/// Records a merge added or replaced, which the database must now hold
+fn written<T>(changes: Vec<MergeChange<T>>) -> impl Iterator<Item = T> {
+    changes.into_iter().filter_map(|change| match change {
+        MergeChange::Added(item) | MergeChange::Replaced { after: item, .. } => Some(item),
+        MergeChange::Deleted(_) => None,
+    })
+}
+
+/// Add the symbols of incoming watchlists to local ones of the same name
+/// under another id, which would otherwise count as duplicates and lose
+/// them; returns the incoming watchlists left for [`merge`]
+fn fold_same_named(
+    watchlists: &Watchlists,
+    local: &mut [Watchlist],
+    incoming: Vec<Watchlist>,
+    report: &mut MergeReport,
+) -> Result<Vec<Watchlist>, String> {
+    let counts = report
+        .counts
+        .entry(Watchlist::KIND.to_string())
+        .or_default();
+    let mut rest = Vec::new();
+    for watchlist in incoming {
+        if local.iter().any(|l| l.id == watchlist.id) {
+            rest.push(watchlist);
+            continue;
+        }
+        let Some(existing) = local.iter_mut().find(|l| l.name == watchlist.name) else {
+            rest.push(watchlist);
+            continue;
+        };
+        let missing: Vec<WatchlistItem> = watchlist
+            .items
+            .into_iter()
+            .filter(|item| !existing.items.iter().any(|i| i.symbol == item.symbol))
+            .collect();
+        if missing.is_empty() {
+            counts.duplicates += 1;
+            continue;
+        }
+        for item in &missing {
+            watchlists.add_symbol(&existing.id, &item.symbol, &item.group)?;
+        }
+        *existing = watchlists.get(&existing.id)?;
+        counts.updated += 1;
+    }
+    Ok(rest)
+}
+
+/// Merge incoming watchlists and notes into the database
+fn merge_database(
+    db: &Database,
+    watchlists: Vec<Watchlist>,
+    notes: Vec<Note>,
+    report: &mut MergeReport,
+) -> Result<(), String> {
+    let conn = db.conn()?;
+    let mut local = Watchlists(&conn).list()?;
+    let mut watchlists = fold_same_named(&Watchlists(&conn), &mut local, watchlists, report)?;
+    // Positions order the lists of one machine, and don't travel
+    for watchlist in &mut watchlists {
+        if let Some(existing) = local.iter().find(|l| l.id == watchlist.id) {
+            watchlist.position = existing.position;
+        }
+    }
+    for watchlist in written(merge(
+        &mut local,
+        &mut Vec::new(),
+        watchlists,
+        Vec::new(),
+        report,
+    )) {
+        Watchlists(&conn).upsert(&watchlist)?;
+    }
+    let mut local = Notes(&conn).list(None)?;
+    for note in written(merge(
+        &mut local,
+        &mut Vec::new(),
+        notes,
+        Vec::new(),
+        report,
+    )) {
+        Notes(&conn).upsert(&note)?;
+    }
+    Ok(())
+}
+
+/// Write the user's holdings, transactions, watchlists, notes and workspaces
+/// to a profile file
+#[tauri::command]
+pub fn export_profile(
+    store: State<'_, PortfolioStore>,
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    path: String,
+) -> Result<(), String> {
+    let mut portfolio = store.get();
+    portfolio.broker_accounts.clear();
+    let conn = db.conn()?;
+    let profile = Profile {
+        format: PROFILE_FORMAT.to_string(),
+        version: PROFILE_VERSION,
+        exported_at: get_timestamp(),
+        portfolio,
+        watchlists: Watchlists(&conn).list()?,
+        notes: Notes(&conn).list(None)?,
+        workspaces: settings.get().workspaces,
+    };
+    let content = serde_json::to_string_pretty(&profile)
+        .map_err(|e| format!("Failed to serialize profile: {}", e))?;
+    write_to_file(&PathBuf::from(&path), &content)
+        .map_err(|e| format!("Failed to write profile: {}", e))?;
+    info!("Exported profile to {}", path);
+    Ok(())
+}
+
+/// Merge a profile exported on another machine into the local data
+#[tauri::command]
+pub fn import_profile(
+    store: State<'_, PortfolioStore>,
+    settings: State<'_, SettingsStore>,
+    db: State<'_, Database>,
+    privacy: State<'_, Privacy>,
+    path: String,
+) -> Result<MergeReport, String> {
+    privacy.check_editable()?;
+    let content = read_from_file(&PathBuf::from(&path))
+        .map_err(|e| format!("Failed to read profile: {}", e))?;
+    let profile = Profile::parse(&content)?;
+    let actor = format!("{} via profile {}", current_user(), profile.exported_at);
+    let mut report = store.merge(&actor, profile.portfolio)?;
+    merge_database(&db, profile.watchlists, profile.notes, &mut report)?;
+    let mut updated = settings.get();
+    merge(
+        &mut updated.workspaces,
+        &mut Vec::new(),
+        profile.workspaces,
+        Vec::new(),
+        &mut report,
+    );
+    settings.set(updated)?;
+    info!("Imported profile from {}: {:?}", path, report.counts);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_round_trip_merges_without_duplicates() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let (path, audit) = (
+            dir.join(format!("ssi-profile-a-{}.json", id)),
+            dir.join(format!("ssi-profile-a-{}.jsonl", id)),
+        );
+        let store = PortfolioStore::load(path.clone(), audit.clone());
+        store
+            .add_manual("me", "SH600519", 100.0, 1650.0, "CNY", "")
+            .unwrap();
+        let exported = Profile {
+            format: PROFILE_FORMAT.to_string(),
+            version: PROFILE_VERSION,
+            exported_at: get_timestamp(),
+            portfolio: store.get(),
+            watchlists: Vec::new(),
+            notes: Vec::new(),
+            workspaces: Vec::new(),
+        };
+        let content = serde_json::to_string(&exported).unwrap();
+        let profile = Profile::parse(&content).unwrap();
+
+        // Importing our own export changes nothing
+        let report = store.merge("me", profile.portfolio).unwrap();
+        assert_eq!(report.counts["holding"].unchanged, 1);
+        assert_eq!(report.counts["holding"].added, 0);
+        assert_eq!(store.get().manual.len(), 1);
+
+        assert!(Profile::parse(&content.replace(PROFILE_FORMAT, "other")).is_err());
+        assert!(Profile::parse(&content.replace("\"version\":1", "\"version\":9")).is_err());
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(audit).ok();
+    }
+
+    #[test]
+    fn test_watchlists_and_notes_merge_into_database() {
+        let open = |name: &str| {
+            let path = std::env::temp_dir().join(format!(
+                "ssi-profile-{}-{}.db",
+                name,
+                std::process::id()
+            ));
+            std::fs::remove_file(&path).ok();
+            (Database::open(path.clone()).unwrap(), path)
+        };
+        let (there, there_path) = open("there");
+        let (here, here_path) = open("here");
+        let (watchlists, notes) = {
+            let conn = there.conn().unwrap();
+            let list = Watchlists(&conn).create("半导体").unwrap();
+            Watchlists(&conn)
+                .add_symbol(&list.id, "SH688981", "")
+                .unwrap();
+            Watchlists(&conn).create("自选").unwrap();
+            Notes(&conn)
+                .create(Some("SH688981"), "产能", "关注扩产节奏")
+                .unwrap();
+            (
+                Watchlists(&conn).list().unwrap(),
+                Notes(&conn).list(None).unwrap(),
+            )
+        };
+        here.conn()
+            .map(|conn| Watchlists(&conn).create("自选").unwrap())
+            .unwrap();
+
+        let mut report = MergeReport::default();
+        merge_database(&here, watchlists.clone(), notes.clone(), &mut report).unwrap();
+        let counts = &report.counts["watchlist"];
+        // The other "自选" is the same list under another id
+        assert_eq!((counts.added, counts.duplicates), (1, 1));
+        assert_eq!(report.counts["note"].added, 1);
+        let conn = here.conn().unwrap();
+        let imported = Watchlists(&conn).get(&watchlists[0].id).unwrap();
+        assert_eq!(imported.items[0].symbol, "SH688981");
+        assert_eq!(Notes(&conn).get(&notes[0].id).unwrap(), notes[0]);
+
+        // Merging the same export again changes nothing
+        let mut again = MergeReport::default();
+        merge_database(&here, watchlists, notes, &mut again).unwrap();
+        let counts = &again.counts["watchlist"];
+        assert_eq!((counts.added, counts.updated), (0, 0));
+        assert_eq!(again.counts["note"].unchanged, 1);
+        assert_eq!(Watchlists(&conn).list().unwrap().len(), 2);
+        drop(conn);
+        for path in [there_path, here_path] {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut file = path.clone().into_os_string();
+                file.push(suffix);
+                std::fs::remove_file(file).ok();
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_named_watchlists_merge_their_symbols() {
+        let open = |name: &str| {
+            let path = std::env::temp_dir().join(format!(
+                "ssi-profile-{}-{}.db",
+                name,
+                std::process::id()
+            ));
+            std::fs::remove_file(&path).ok();
+            (Database::open(path.clone()).unwrap(), path)
+        };
+        let (there, there_path) = open("same-there");
+        let (here, here_path) = open("same-here");
+        let incoming = {
+            let conn = there.conn().unwrap();
+            let list = Watchlists(&conn).create("自选").unwrap();
+            Watchlists(&conn)
+                .add_symbol(&list.id, "SH600519", "白酒")
+                .unwrap();
+            Watchlists(&conn)
+                .add_symbol(&list.id, "SZ300750", "新能源")
+                .unwrap();
+            Watchlists(&conn).list().unwrap()
+        };
+        let local = {
+            let conn = here.conn().unwrap();
+            let list = Watchlists(&conn).create("自选").unwrap();
+            Watchlists(&conn)
+                .add_symbol(&list.id, "SH600519", "")
+                .unwrap()
+        };
+
+        let mut report = MergeReport::default();
+        merge_database(&here, incoming, Vec::new(), &mut report).unwrap();
+        let counts = &report.counts["watchlist"];
+        assert_eq!((counts.added, counts.updated, counts.duplicates), (0, 1, 0));
+        let conn = here.conn().unwrap();
+        let lists = Watchlists(&conn).list().unwrap();
+        assert_eq!(lists.len(), 1);
+        let items: Vec<(&str, &str)> = lists[0]
+            .items
+            .iter()
+            .map(|i| (i.symbol.as_str(), i.group.as_str()))
+            .collect();
+        // The local list keeps its id and its own grouping of shared symbols
+        assert_eq!(lists[0].id, local.id);
+        assert_eq!(items, [("SH600519", ""), ("SZ300750", "新能源")]);
+        drop(conn);
+        for path in [there_path, here_path] {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut file = path.clone().into_os_string();
+                file.push(suffix);
+                std::fs::remove_file(file).ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Unified `task-progress` event protocol.
+//!
+//! Every background job (backfill, updater, exports, AI jobs, sync) reports
+//! progress through a [`ProgressReporter`], so the frontend can render a single
+//! progress center keyed by task id instead of per-feature handling.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+/// Minimum interval between two events for the same stage
+const EMIT_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Payload of the `task-progress` event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskProgress {
+    pub task_id: String,
+    pub kind: String,
+    /// 0.0 – 100.0
+    pub percent: f64,
+    pub stage: String,
+    /// Estimated seconds remaining, once enough progress has been made
+    pub eta_secs: Option<u64>,
+    pub message: Option<String>,
+}
+
+struct ReporterState {
+    stage: String,
+    stage_started: Instant,
+    last_emit: Option<Instant>,
+}
+
+/// Emits throttled progress events for one task
+pub struct ProgressReporter {
+    app: AppHandle,
+    task_id: String,
+    kind: String,
+    state: Mutex<ReporterState>,
+}
+
+impl ProgressReporter {
+    pub fn new(app: AppHandle, task_id: &str, kind: &str) -> Self {
+        Self {
+            app,
+            task_id: task_id.to_string(),
+            kind: kind.to_string(),
+            state: Mutex::new(ReporterState {
+                stage: String::new(),
+                stage_started: Instant::now(),
+                last_emit: None,
+            }),
+        }
+    }
+
+    pub fn task_id(&self) -> &str {
+        &self.task_id
+    }
+
+    /// Report `done` of `total` units of work within `stage`.
+    ///
+    /// Events are throttled, except for stage changes and completion of a
+    /// stage, which are always emitted.
+    pub fn report(&self, stage: &str, done: u64, total: u64, message: Option<String>) {
+        let percent = if total == 0 {
+            100.0
+        } else {
+            (done.min(total) as f64 / total as f64) * 100.0
+        };
+
+        let progress = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let stage_changed = state.stage != stage;
+            if stage_changed {
+                state.stage = stage.to_string();
+                state.stage_started = now;
+            }
+            let throttled = state
+                .last_emit
+                .map(|last| now.duration_since(last) < EMIT_INTERVAL)
+                .unwrap_or(false);
+            if throttled && !stage_changed && done < total {
+                return;
+            }
+            state.last_emit = Some(now);
+
+            TaskProgress {
+                task_id: self.task_id.clone(),
+                kind: self.kind.clone(),
+                percent,
+                stage: stage.to_string(),
+                eta_secs: estimate_eta(now.duration_since(state.stage_started), percent),
+                message,
+            }
+        };
+
+        if let Err(e) = self.app.emit("task-progress", progress) {
+            warn!("Failed to emit task-progress event: {}", e);
+        }
+    }
+}
+
+/// Linear ETA from elapsed time and completion percentage
+pub fn estimate_eta(elapsed: Duration, percent: f64) -> Option<u64> {
+    if percent < 1.0 || elapsed < Duration::from_secs(1) {
+        return None;
+    }
+    if percent >= 100.0 {
+        return Some(0);
+    }
+    let remaining = elapsed.as_secs_f64() * (100.0 - percent) / percent;
+    Some(remaining.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_eta() {
+        assert_eq!(estimate_eta(Duration::from_secs(10), 0.5), None);
+        assert_eq!(estimate_eta(Duration::from_millis(500), 50.0), None);
+        assert_eq!(estimate_eta(Duration::from_secs(10), 25.0), Some(30));
+        assert_eq!(estimate_eta(Duration::from_secs(10), 100.0), Some(0));
+    }
+}
@@ -0,0 +1,482 @@
+//! Login sessions for providers that need cookies.
+//!
+//! Some sources only answer with a logged-in session (or a cookie that a
+//! captcha page hands out) and those cookies go stale every week or so. The
+//! vault keeps each provider's cookies encrypted at rest, tracks when they
+//! expire, and when a provider needs a fresh session it opens a managed login
+//! webview, waits for the required cookies to appear and stores them.
+//!
+//! Requests to these providers go through [`session_get`], which attaches the
+//! cookies and watches the answer: a 401 or 403, a redirect to a login page,
+//! or a body carrying the provider's own captcha page or "log in again" error
+//! marks the session rejected and raises `provider-login-required`, as does a
+//! request made without a usable session.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State, Url, WebviewUrl, WebviewWindowBuilder};
+
+use crate::politeness::PolicyEngine;
+use crate::secure_store::EncryptedFile;
+
+/// Longest time to wait for the user to finish logging in
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often the login webview is checked for the required cookies
+const LOGIN_POLL: Duration = Duration::from_secs(2);
+
+/// Sessions this close to expiry are reported as expiring
+const EXPIRY_WARNING_SECS: i64 = 24 * 3600;
+
+/// Path fragments of the pages providers redirect stale sessions to
+const LOGIN_PATHS: &[&str] = &["/login", "/signin", "passport."];
+
+/// A provider that needs a browser session
+pub struct SessionProvider {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub login_url: &'static str,
+    /// Cookies that must be present for the session to count as logged in
+    pub required_cookies: &'static [&'static str],
+    /// How long a captured session is trusted before asking again
+    pub max_age_secs: i64,
+    /// Fragments only found in what the provider answers a stale session
+    /// with, such as its "log in again" error or its captcha page's elements
+    pub rejection_markers: &'static [&'static str],
+}
+
+pub const SESSION_PROVIDERS: &[SessionProvider] = &[SessionProvider {
+    id: "xueqiu",
+    name: "雪球",
+    login_url: "https://xueqiu.com/",
+    required_cookies: &["xq_a_token", "u"],
+    max_age_secs: 7 * 24 * 3600,
+    // The "log in again" error code, and the Aliyun slider captcha the WAF
+    // serves in front of the API
+    rejection_markers: &[
+        "\"error_code\":\"400016\"",
+        "id=\"nc_1_wrapper\"",
+        "g.alicdn.com/AWSC/",
+    ],
+}];
+
+pub fn session_provider(id: &str) -> Result<&'static SessionProvider, String> {
+    SESSION_PROVIDERS
+        .iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| format!("Provider does not use login sessions: {}", id))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStatus {
+    Missing,
+    Valid,
+    Expiring,
+    Expired,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredSession {
+    cookies: Vec<(String, String)>,
+    captured_at: i64,
+    expires_at: i64,
+    /// Set when the provider rejected the session before it was due to expire
+    #[serde(default)]
+    rejected: bool,
+}
+
+/// What the UI sees of a session; cookie values never leave the vault
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub provider: String,
+    pub name: String,
+    pub status: SessionStatus,
+    pub captured_at: Option<i64>,
+    pub expires_at: Option<i64>,
+}
+
+/// Payload of the `provider-login-required` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginRequired {
+    pub provider: String,
+    pub name: String,
+    pub reason: String,
+}
+
+pub struct SessionVault {
+    file: EncryptedFile,
+    sessions: Mutex<HashMap<String, StoredSession>>,
+}
+
+impl SessionVault {
+    /// Open the encrypted vault at `path`, keyed by the key stored at `key_path`
+    pub fn load(path: PathBuf, key_path: PathBuf) -> Self {
+        let file = EncryptedFile::new(path, &key_path);
+        let sessions = file.read();
+        Self {
+            file,
+            sessions: Mutex::new(sessions),
+        }
+    }
+
+    fn save(&self, sessions: &HashMap<String, StoredSession>) -> Result<(), String> {
+        self.file.write(sessions, "sessions")
+    }
+
+    pub fn store(
+        &self,
+        provider: &SessionProvider,
+        cookies: Vec<(String, String)>,
+        now: i64,
+    ) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(
+            provider.id.to_string(),
+            StoredSession {
+                cookies,
+                captured_at: now,
+                expires_at: now + provider.max_age_secs,
+                rejected: false,
+            },
+        );
+        self.save(&sessions)
+    }
+
+    pub fn remove(&self, provider: &str) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.remove(provider);
+        self.save(&sessions)
+    }
+
+    /// Mark a session as refused by its provider so the next request asks for a login
+    pub fn reject(&self, provider: &str) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(provider) {
+            Some(session) => session.rejected = true,
+            None => return Ok(()),
+        }
+        self.save(&sessions)
+    }
+
+    /// Mark the session rejected when a response shows the provider refused
+    /// it, returning the login prompt to raise
+    pub fn check_response(
+        &self,
+        provider: &SessionProvider,
+        status: u16,
+        final_url: &str,
+        body: &str,
+    ) -> Result<Option<LoginRequired>, String> {
+        if !is_rejection(provider, status, final_url, body) {
+            return Ok(None);
+        }
+        self.reject(provider.id)?;
+        Ok(Some(login_prompt(provider, "session rejected by provider")))
+    }
+
+    pub fn status(&self, provider: &str, now: i64) -> SessionStatus {
+        match self.sessions.lock().unwrap().get(provider) {
+            None => SessionStatus::Missing,
+            Some(s) if s.rejected || s.expires_at <= now => SessionStatus::Expired,
+            Some(s) if s.expires_at - now <= EXPIRY_WARNING_SECS => SessionStatus::Expiring,
+            Some(_) => SessionStatus::Valid,
+        }
+    }
+
+    /// `Cookie` header value for a provider, if its session is still usable
+    pub fn cookie_header(&self, provider: &str, now: i64) -> Option<String> {
+        if matches!(
+            self.status(provider, now),
+            SessionStatus::Missing | SessionStatus::Expired
+        ) {
+            return None;
+        }
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(provider)?;
+        Some(
+            session
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    pub fn summaries(&self, now: i64) -> Vec<SessionSummary> {
+        SESSION_PROVIDERS
+            .iter()
+            .map(|provider| {
+                let stored = self.sessions.lock().unwrap().get(provider.id).cloned();
+                SessionSummary {
+                    provider: provider.id.to_string(),
+                    name: provider.name.to_string(),
+                    status: self.status(provider.id, now),
+                    captured_at: stored.as_ref().map(|s| s.captured_at),
+                    expires_at: stored.map(|s| s.expires_at),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Whether a response means `provider` no longer accepts the session
+fn is_rejection(provider: &SessionProvider, status: u16, final_url: &str, body: &str) -> bool {
+    let url = final_url.to_ascii_lowercase();
+    matches!(status, 401 | 403)
+        || LOGIN_PATHS.iter().any(|path| url.contains(path))
+        || provider
+            .rejection_markers
+            .iter()
+            .any(|marker| body.contains(marker))
+}
+
+fn login_prompt(provider: &SessionProvider, reason: &str) -> LoginRequired {
+    LoginRequired {
+        provider: provider.id.to_string(),
+        name: provider.name.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn emit_login_required(app: &AppHandle, payload: LoginRequired) {
+    warn!(
+        "Login required for {}: {}",
+        payload.provider, payload.reason
+    );
+    if let Err(e) = app.emit("provider-login-required", payload) {
+        warn!("Failed to emit provider-login-required event: {}", e);
+    }
+}
+
+fn login_required(app: &AppHandle, provider: &SessionProvider, reason: &str) {
+    emit_login_required(app, login_prompt(provider, reason));
+}
+
+/// Cookie header for a request to `provider`, prompting for a login when there is no usable session
+pub fn session_cookies(app: &AppHandle, provider: &str) -> Result<String, String> {
+    let provider = session_provider(provider)?;
+    let vault = app.state::<SessionVault>();
+    vault
+        .cookie_header(provider.id, Utc::now().timestamp())
+        .ok_or_else(|| {
+            login_required(app, provider, "no valid session");
+            format!("{} requires a login", provider.name)
+        })
+}
+
+/// Report that `provider` refused its session (login redirect, 401, captcha page)
+pub fn session_rejected(app: &AppHandle, provider: &str) {
+    let Ok(provider) = session_provider(provider) else {
+        return;
+    };
+    if let Err(e) = app.state::<SessionVault>().reject(provider.id) {
+        warn!("{}", e);
+    }
+    login_required(app, provider, "session rejected by provider");
+}
+
+/// GET `url` from a session provider with its cookies, returning the body.
+///
+/// Fails and prompts for a login when there is no usable session or the
+/// provider refuses the one sent.
+pub async fn session_get(app: &AppHandle, provider: &str, url: &str) -> Result<String, String> {
+    let cookies = session_cookies(app, provider)?;
+    let provider = session_provider(provider)?;
+    let response = app
+        .state::<PolicyEngine>()
+        .get_with_cookies(provider.id, url, Some(&cookies))
+        .await?;
+    let status = response.status();
+    let final_url = response.url().to_string();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response from {}: {}", provider.id, e))?;
+    let vault = app.state::<SessionVault>();
+    if let Some(prompt) = vault.check_response(provider, status.as_u16(), &final_url, &body)? {
+        emit_login_required(app, prompt);
+        return Err(format!("{} refused the login session", provider.name));
+    }
+    if !status.is_success() {
+        return Err(format!("{} rejected the request: {}", provider.id, status));
+    }
+    Ok(body)
+}
+
+fn login_label(provider: &str) -> String {
+    format!("login-{}", provider)
+}
+
+/// Open the provider's login page and capture its cookies once the user has logged in
+#[tauri::command]
+pub fn start_provider_login(app: AppHandle, provider: String) -> Result<(), String> {
+    let provider = session_provider(&provider)?;
+    let label = login_label(provider.id);
+    if let Some(window) = app.get_webview_window(&label) {
+        return window
+            .set_focus()
+            .map_err(|e| format!("Failed to focus login window: {}", e));
+    }
+
+    let url: Url = provider
+        .login_url
+        .parse()
+        .map_err(|e| format!("Invalid login URL: {}", e))?;
+    let window = WebviewWindowBuilder::new(&app, label.clone(), WebviewUrl::External(url.clone()))
+        .title(format!("登录 {}", provider.name))
+        .inner_size(480.0, 720.0)
+        .build()
+        .map_err(|e| format!("Failed to open login window: {}", e))?;
+    info!("Opened login window for {}", provider.id);
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let started = std::time::Instant::now();
+        while started.elapsed() < LOGIN_TIMEOUT {
+            tokio::time::sleep(LOGIN_POLL).await;
+            // The user closed the window without finishing
+            if handle.get_webview_window(&label).is_none() {
+                return;
+            }
+            let cookies: Vec<(String, String)> = match window.cookies_for_url(url.clone()) {
+                Ok(cookies) => cookies
+                    .iter()
+                    .map(|c| (c.name().to_string(), c.value().to_string()))
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to read login cookies: {}", e);
+                    continue;
+                }
+            };
+            let logged_in = provider
+                .required_cookies
+                .iter()
+                .all(|name| cookies.iter().any(|(n, v)| n == name && !v.is_empty()));
+            if !logged_in {
+                continue;
+            }
+
+            let vault = handle.state::<SessionVault>();
+            if let Err(e) = vault.store(provider, cookies, Utc::now().timestamp()) {
+                warn!("{}", e);
+                return;
+            }
+            info!("Captured login session for {}", provider.id);
+            if let Err(e) = handle.emit("provider-login-completed", provider.id) {
+                warn!("Failed to emit provider-login-completed event: {}", e);
+            }
+            if let Err(e) = window.close() {
+                warn!("Failed to close login window: {}", e);
+            }
+            return;
+        }
+        warn!("Login for {} timed out", provider.id);
+        window.close().ok();
+    });
+    Ok(())
+}
+
+/// Login session state of every provider that needs one
+#[tauri::command]
+pub fn get_provider_sessions(
+    vault: State<'_, SessionVault>,
+) -> Result<Vec<SessionSummary>, String> {
+    Ok(vault.summaries(Utc::now().timestamp()))
+}
+
+/// Forget a provider's stored cookies
+#[tauri::command]
+pub fn logout_provider(vault: State<'_, SessionVault>, provider: String) -> Result<(), String> {
+    session_provider(&provider)?;
+    vault.remove(&provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vault_round_trip_and_expiry() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("ssi-sessions-{}.enc", std::process::id()));
+        let key_path = dir.join(format!("ssi-session-{}.key", std::process::id()));
+        let provider = session_provider("xueqiu").unwrap();
+        let now = 1_700_000_000;
+
+        let vault = SessionVault::load(path.clone(), key_path.clone());
+        assert_eq!(vault.status("xueqiu", now), SessionStatus::Missing);
+        vault
+            .store(
+                provider,
+                vec![
+                    ("xq_a_token".to_string(), "secret-token".to_string()),
+                    ("u".to_string(), "42".to_string()),
+                ],
+                now,
+            )
+            .unwrap();
+
+        let stored = std::fs::read_to_string(&path).unwrap();
+        assert!(!stored.contains("secret-token"));
+
+        let reopened = SessionVault::load(path.clone(), key_path.clone());
+        assert_eq!(reopened.status("xueqiu", now), SessionStatus::Valid);
+        assert_eq!(
+            reopened.cookie_header("xueqiu", now).unwrap(),
+            "xq_a_token=secret-token; u=42"
+        );
+        let expiry = now + provider.max_age_secs;
+        assert_eq!(
+            reopened.status("xueqiu", expiry - 3600),
+            SessionStatus::Expiring
+        );
+        assert_eq!(reopened.status("xueqiu", expiry), SessionStatus::Expired);
+        assert!(reopened.cookie_header("xueqiu", expiry).is_none());
+
+        // An ordinary answer leaves the session alone, a login redirect rejects it
+        let quote = r#"{"data":{"quote":{"symbol":"SH600519"}},"error_code":0}"#;
+        let ok = reopened.check_response(
+            provider,
+            200,
+            "https://stock.xueqiu.com/v5/stock/quote.json",
+            quote,
+        );
+        assert!(ok.unwrap().is_none());
+        assert_eq!(reopened.status("xueqiu", now), SessionStatus::Valid);
+        let prompt = reopened
+            .check_response(provider, 200, "https://xueqiu.com/login?redirect=%2F", "")
+            .unwrap()
+            .unwrap();
+        assert_eq!(prompt.provider, "xueqiu");
+        assert_eq!(reopened.status("xueqiu", now), SessionStatus::Expired);
+        assert!(reopened.cookie_header("xueqiu", now).is_none());
+        let api = "https://stock.xueqiu.com/v5/stock/quote.json";
+        assert!(is_rejection(provider, 401, api, ""));
+        assert!(is_rejection(
+            provider,
+            400,
+            api,
+            r#"{"error_description":"遇到错误，请刷新页面或者重新登录帐号后再试","error_code":"400016"}"#
+        ));
+        let challenge = r#"<div id="nc_1_wrapper"></div><script src="https://g.alicdn.com/AWSC/AWSC/awsc.js"></script>"#;
+        assert!(is_rejection(provider, 200, api, challenge));
+        // Posts that merely talk about captchas are ordinary answers
+        let post = r#"{"data":{"text":"新版 captcha 验证码 体验如何？"},"error_code":0}"#;
+        assert!(!is_rejection(provider, 200, api, post));
+
+        // A different key cannot read the vault
+        std::fs::remove_file(&key_path).ok();
+        let foreign = SessionVault::load(path.clone(), key_path.clone());
+        assert_eq!(foreign.status("xueqiu", now), SessionStatus::Missing);
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(key_path).ok();
+    }
+}
@@ -0,0 +1,1154 @@
+//! Market data providers behind one trait, tried in the user's order.
+//!
+//! Each [`DataProvider`] serves some of quotes, K-lines, fundamentals and
+//! news, each for the markets it covers. A request goes down the providers in
+//! [`ProviderSettings::priority`] that can serve it for the symbol's market
+//! until one succeeds, so an outage of one source degrades
+//! to the next instead of blanking the app; providers left out of the
+//! priority list are never used. A provider failing [`TRIP_AFTER`] times in a
+//! row for a capability is passed over for [`COOLDOWN`], unless no other
+//! provider is left to try.
+//!
+//! Fallbacks don't carry everything the primary sources do: Sina and Yahoo
+//! only have unadjusted K-lines with a shorter history (Sina daily bars,
+//! Yahoo daily and 1-minute bars), and Yahoo quotes carry no turnover. Hong
+//! Kong and US symbols are quoted by Tencent and Yahoo; Hong Kong K-lines come
+//! from Eastmoney or Yahoo, US K-lines from Yahoo only.
+//!
+//! Xueqiu has fundamentals for all three markets but only answers a logged-in
+//! session, so it is left out of the default priority; its requests carry
+//! the cookies captured by [`provider_sessions`](crate::provider_sessions).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{TimeZone, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+
+use crate::drift::{DriftLog, Field, FieldKind};
+use crate::faults::FaultInjector;
+use crate::kline::{self, Adjust, KlinePeriod};
+use crate::models::{Bar, Market};
+use crate::news::{self, NewsItem};
+use crate::politeness::PolicyEngine;
+use crate::provider_sessions;
+use crate::quotes::{self, provider_code, tencent_code, Quote};
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::symbols::symbol_key;
+use crate::utils::get_timestamp;
+
+/// Consecutive failures after which a provider is passed over
+pub const TRIP_AFTER: u32 = 3;
+/// How long a tripped provider is passed over
+pub const COOLDOWN: Duration = Duration::from_secs(60);
+
+const EASTMONEY_STOCK_URL: &str = "https://push2.eastmoney.com/api/qt/stock/get";
+const SINA_KLINE_URL: &str =
+    "https://money.finance.sina.com.cn/quotes_service/api/json_v2.php/CN_MarketData.getKLineData";
+/// Sina's most recent bars per request
+const SINA_KLINE_LIMIT: usize = 1023;
+const SINA_NEWS_URL: &str =
+    "https://feed.mix.sina.com.cn/api/roll/get?pageid=155&lid=1686&num=50&page=1";
+const YAHOO_CHART_URL: &str = "https://query1.finance.yahoo.com/v8/finance/chart/";
+const XUEQIU_QUOTE_URL: &str = "https://stock.xueqiu.com/v5/stock/quote.json";
+
+const EASTMONEY_STOCK_SCHEMA: &[Field] = &[Field {
+    path: "data",
+    kind: FieldKind::Object,
+}];
+
+const XUEQIU_QUOTE_SCHEMA: &[Field] = &[Field {
+    path: "data.quote",
+    kind: FieldKind::Object,
+}];
+
+const SINA_NEWS_SCHEMA: &[Field] = &[
+    Field {
+        path: "result.data",
+        kind: FieldKind::Array,
+    },
+    Field {
+        path: "result.data[].title",
+        kind: FieldKind::String,
+    },
+    Field {
+        path: "result.data[].url",
+        kind: FieldKind::String,
+    },
+];
+
+const YAHOO_CHART_SCHEMA: &[Field] = &[
+    Field {
+        path: "chart.result",
+        kind: FieldKind::Array,
+    },
+    Field {
+        path: "chart.result[].meta",
+        kind: FieldKind::Object,
+    },
+];
+
+/// Future returned by provider requests
+pub type ProviderFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send + 'a>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderId {
+    Sina,
+    Eastmoney,
+    Tencent,
+    Yahoo,
+    Xueqiu,
+}
+
+impl ProviderId {
+    pub const ALL: [ProviderId; 5] = [
+        ProviderId::Sina,
+        ProviderId::Eastmoney,
+        ProviderId::Tencent,
+        ProviderId::Yahoo,
+        ProviderId::Xueqiu,
+    ];
+
+    /// Source id of the provider's politeness policy
+    pub fn source(self) -> &'static str {
+        match self {
+            ProviderId::Sina => "sina",
+            ProviderId::Eastmoney => "eastmoney",
+            ProviderId::Tencent => "tencent",
+            ProviderId::Yahoo => "yahoo",
+            ProviderId::Xueqiu => "xueqiu",
+        }
+    }
+
+    fn provider(self) -> &'static dyn DataProvider {
+        match self {
+            ProviderId::Sina => &Sina,
+            ProviderId::Eastmoney => &Eastmoney,
+            ProviderId::Tencent => &Tencent,
+            ProviderId::Yahoo => &Yahoo,
+            ProviderId::Xueqiu => &Xueqiu,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    Quotes,
+    Klines,
+    Fundamentals,
+    News,
+}
+
+impl Capability {
+    const ALL: [Capability; 4] = [
+        Capability::Quotes,
+        Capability::Klines,
+        Capability::Fundamentals,
+        Capability::News,
+    ];
+}
+
+/// Valuation snapshot of a symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fundamentals {
+    pub symbol: String,
+    pub pe_ttm: Option<f64>,
+    pub pb: Option<f64>,
+    /// In yuan
+    pub total_market_cap: Option<f64>,
+    /// In yuan
+    pub float_market_cap: Option<f64>,
+    pub total_shares: Option<f64>,
+    pub float_shares: Option<f64>,
+    pub source: String,
+}
+
+/// A source of market data. Requests for a capability the provider lacks
+/// fail; the chain only sends those its [`DataProvider::supports`] allows.
+pub trait DataProvider: Send + Sync {
+    fn id(&self) -> ProviderId;
+
+    fn supports(&self, capability: Capability) -> bool;
+
+    /// Markets the provider serves `capability` for
+    fn markets(&self, _capability: Capability) -> &'static [Market] {
+        &[Market::Cn]
+    }
+
+    /// Whether the provider has bars of `period` with `adjust`
+    fn supports_kline(&self, _period: KlinePeriod, _adjust: Adjust) -> bool {
+        self.supports(Capability::Klines)
+    }
+
+    /// Quotes of symbols of one market; symbols without a quote are left out
+    fn quotes<'a>(
+        &'a self,
+        _app: &'a AppHandle,
+        _symbols: &'a [String],
+    ) -> ProviderFuture<'a, Vec<Quote>> {
+        unsupported(self.id(), Capability::Quotes)
+    }
+
+    /// Bars of a symbol, from the bar at `from` when given
+    fn klines<'a>(
+        &'a self,
+        _app: &'a AppHandle,
+        _symbol: &'a str,
+        _period: KlinePeriod,
+        _adjust: Adjust,
+        _from: Option<i64>,
+    ) -> ProviderFuture<'a, Vec<Bar>> {
+        unsupported(self.id(), Capability::Klines)
+    }
+
+    fn fundamentals<'a>(
+        &'a self,
+        _app: &'a AppHandle,
+        _symbol: &'a str,
+    ) -> ProviderFuture<'a, Fundamentals> {
+        unsupported(self.id(), Capability::Fundamentals)
+    }
+
+    /// Latest market headlines
+    fn news<'a>(&'a self, _app: &'a AppHandle) -> ProviderFuture<'a, Vec<NewsItem>> {
+        unsupported(self.id(), Capability::News)
+    }
+}
+
+fn unsupported<'a, T: 'a>(id: ProviderId, capability: Capability) -> ProviderFuture<'a, T> {
+    Box::pin(async move { Err(format!("{} doesn't provide {:?}", id.source(), capability)) })
+}
+
+/// GET a provider endpoint through its politeness policy and fault injection
+async fn get_text(
+    app: &AppHandle,
+    id: ProviderId,
+    url: &str,
+    what: &str,
+) -> Result<String, String> {
+    let source = id.source();
+    let policies = app.state::<PolicyEngine>();
+    app.state::<FaultInjector>()
+        .wrap(source, async {
+            policies
+                .get(source, url)
+                .await?
+                .error_for_status()
+                .map_err(|e| format!("{} rejected the {} request: {}", source, what, e))?
+                .text()
+                .await
+                .map_err(|e| format!("Failed to read {} from {}: {}", what, source, e))
+        })
+        .await
+}
+
+fn a_share_code(symbol: &str) -> Result<String, String> {
+    provider_code(symbol).ok_or_else(|| format!("Not an A-share symbol: {}", symbol))
+}
+
+/// Whether `provider` serves `capability` for `market`
+fn covers(provider: &dyn DataProvider, capability: Capability, market: Market) -> bool {
+    provider.supports(capability) && provider.markets(capability).contains(&market)
+}
+
+fn market_of(symbol: &str) -> Result<Market, String> {
+    Market::of(symbol).ok_or_else(|| format!("Unknown market for symbol: {}", symbol))
+}
+
+/// Number that may come as a JSON number or string, `None` for placeholders like `-`
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+struct Tencent;
+
+impl DataProvider for Tencent {
+    fn id(&self) -> ProviderId {
+        ProviderId::Tencent
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(capability, Capability::Quotes | Capability::Fundamentals)
+    }
+
+    fn markets(&self, capability: Capability) -> &'static [Market] {
+        match capability {
+            Capability::Quotes => &[Market::Cn, Market::Hk, Market::Us],
+            _ => &[Market::Cn],
+        }
+    }
+
+    fn quotes<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbols: &'a [String],
+    ) -> ProviderFuture<'a, Vec<Quote>> {
+        Box::pin(async move {
+            let codes: Vec<String> = symbols.iter().filter_map(|s| tencent_code(s)).collect();
+            let url = format!("https://qt.gtimg.cn/q={}", codes.join(","));
+            let body = get_text(app, self.id(), &url, "quotes").await?;
+            Ok(quotes::parse_tencent(&body))
+        })
+    }
+
+    fn fundamentals<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbol: &'a str,
+    ) -> ProviderFuture<'a, Fundamentals> {
+        Box::pin(async move {
+            let url = format!("https://qt.gtimg.cn/q={}", a_share_code(symbol)?);
+            let body = get_text(app, self.id(), &url, "fundamentals").await?;
+            parse_tencent_fundamentals(&body)
+                .ok_or_else(|| format!("No fundamentals from tencent for {}", symbol))
+        })
+    }
+}
+
+/// Valuation fields of a Tencent quote: 39 PE(TTM), 44/45 float/total market
+/// cap in 亿, 46 PB, 72/73 float/total shares
+fn parse_tencent_fundamentals(body: &str) -> Option<Fundamentals> {
+    let (symbol, value) = quotes::assignments(body, "v_").into_iter().next()?;
+    let fields: Vec<&str> = value.split('~').collect();
+    if fields.len() < 47 {
+        return None;
+    }
+    let number = |i: usize| fields.get(i)?.parse::<f64>().ok().filter(|n| *n != 0.0);
+    Some(Fundamentals {
+        symbol,
+        pe_ttm: number(39),
+        pb: number(46),
+        total_market_cap: number(45).map(|cap| cap * 1e8),
+        float_market_cap: number(44).map(|cap| cap * 1e8),
+        total_shares: number(73),
+        float_shares: number(72),
+        source: ProviderId::Tencent.source().to_string(),
+    })
+}
+
+struct Sina;
+
+impl DataProvider for Sina {
+    fn id(&self) -> ProviderId {
+        ProviderId::Sina
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::Quotes | Capability::Klines | Capability::News
+        )
+    }
+
+    fn supports_kline(&self, period: KlinePeriod, adjust: Adjust) -> bool {
+        period == KlinePeriod::Daily && adjust == Adjust::None
+    }
+
+    fn quotes<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbols: &'a [String],
+    ) -> ProviderFuture<'a, Vec<Quote>> {
+        Box::pin(async move {
+            let codes: Vec<String> = symbols.iter().filter_map(|s| provider_code(s)).collect();
+            let url = format!("https://hq.sinajs.cn/list={}", codes.join(","));
+            let body = get_text(app, self.id(), &url, "quotes").await?;
+            Ok(quotes::parse_sina(&body))
+        })
+    }
+
+    fn klines<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbol: &'a str,
+        _period: KlinePeriod,
+        _adjust: Adjust,
+        from: Option<i64>,
+    ) -> ProviderFuture<'a, Vec<Bar>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}?symbol={}&scale=240&ma=no&datalen={}",
+                SINA_KLINE_URL,
+                a_share_code(symbol)?,
+                SINA_KLINE_LIMIT
+            );
+            let body = get_text(app, self.id(), &url, "K-lines").await?;
+            let bars = parse_sina_klines(&body)
+                .ok_or_else(|| format!("Failed to parse K-lines from sina for {}", symbol))?;
+            Ok(since(bars, from))
+        })
+    }
+
+    fn news<'a>(&'a self, app: &'a AppHandle) -> ProviderFuture<'a, Vec<NewsItem>> {
+        Box::pin(async move {
+            let body = get_text(app, self.id(), SINA_NEWS_URL, "news").await?;
+            let value = app.state::<DriftLog>().check_response(
+                app,
+                self.id().source(),
+                "roll news",
+                &body,
+                SINA_NEWS_SCHEMA,
+            )?;
+            Ok(parse_sina_news(&value))
+        })
+    }
+}
+
+/// `[{"day":"2024-01-02","open":"1700.0","high":…,"low":…,"close":…,"volume":"123456"}]`
+fn parse_sina_klines(body: &str) -> Option<Vec<Bar>> {
+    let rows: Vec<Value> = serde_json::from_str(body).ok()?;
+    Some(
+        rows.iter()
+            .filter_map(|row| {
+                let day = chrono::NaiveDate::parse_from_str(row["day"].as_str()?, "%Y-%m-%d")
+                    .ok()?
+                    .and_hms_opt(0, 0, 0)?;
+                Some(Bar {
+                    timestamp: local_midnight(day, Market::Cn)?,
+                    open: number(&row["open"])?,
+                    high: number(&row["high"])?,
+                    low: number(&row["low"])?,
+                    close: number(&row["close"])?,
+                    volume: number(&row["volume"])?,
+                })
+            })
+            .collect(),
+    )
+}
+
+fn parse_sina_news(value: &Value) -> Vec<NewsItem> {
+    let fetched_at = get_timestamp();
+    value["result"]["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|row| {
+            let url = row["url"].as_str()?;
+            let id = row["docid"].as_str().unwrap_or(url);
+            Some(NewsItem {
+                id: format!("sina-{}", id),
+                source: ProviderId::Sina.source().to_string(),
+                title: row["title"].as_str()?.to_string(),
+                summary: row["intro"].as_str().unwrap_or_default().to_string(),
+                url: url.to_string(),
+                published_at: number(&row["ctime"])? as i64,
+                fetched_at: fetched_at.clone(),
+                tags: Vec::new(),
+                symbols: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+struct Eastmoney;
+
+impl DataProvider for Eastmoney {
+    fn id(&self) -> ProviderId {
+        ProviderId::Eastmoney
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::Klines | Capability::Fundamentals | Capability::News
+        )
+    }
+
+    fn markets(&self, capability: Capability) -> &'static [Market] {
+        match capability {
+            Capability::Klines => &[Market::Cn, Market::Hk],
+            _ => &[Market::Cn],
+        }
+    }
+
+    fn klines<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbol: &'a str,
+        period: KlinePeriod,
+        adjust: Adjust,
+        from: Option<i64>,
+    ) -> ProviderFuture<'a, Vec<Bar>> {
+        Box::pin(kline::fetch_eastmoney(app, symbol, period, adjust, from))
+    }
+
+    fn fundamentals<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbol: &'a str,
+    ) -> ProviderFuture<'a, Fundamentals> {
+        Box::pin(async move {
+            let secid =
+                kline::secid(symbol).ok_or_else(|| format!("Not an A-share symbol: {}", symbol))?;
+            let url = format!(
+                "{}?secid={}&fields=f84,f85,f116,f117,f164,f167",
+                EASTMONEY_STOCK_URL, secid
+            );
+            let body = get_text(app, self.id(), &url, "fundamentals").await?;
+            let value = app.state::<DriftLog>().check_response(
+                app,
+                self.id().source(),
+                "stock",
+                &body,
+                EASTMONEY_STOCK_SCHEMA,
+            )?;
+            Ok(parse_eastmoney_fundamentals(symbol, &value["data"]))
+        })
+    }
+
+    fn news<'a>(&'a self, app: &'a AppHandle) -> ProviderFuture<'a, Vec<NewsItem>> {
+        Box::pin(news::fetch_fast_news(app))
+    }
+}
+
+/// `f164` PE(TTM) and `f167` PB are scaled by 100, market caps are in yuan
+fn parse_eastmoney_fundamentals(symbol: &str, data: &Value) -> Fundamentals {
+    let ratio = |key: &str| number(&data[key]).map(|n| n / 100.0);
+    Fundamentals {
+        symbol: symbol.to_string(),
+        pe_ttm: ratio("f164"),
+        pb: ratio("f167"),
+        total_market_cap: number(&data["f116"]),
+        float_market_cap: number(&data["f117"]),
+        total_shares: number(&data["f84"]),
+        float_shares: number(&data["f85"]),
+        source: ProviderId::Eastmoney.source().to_string(),
+    }
+}
+
+struct Yahoo;
+
+/// Yahoo's ticker: `600519.SS`, `000001.SZ`, `0700.HK`, `BRK-B`; Beijing
+/// listings aren't covered
+fn yahoo_symbol(symbol: &str) -> Result<String, String> {
+    match market_of(symbol)? {
+        Market::Cn => {
+            let code = a_share_code(symbol)?;
+            let suffix = match &code[..2] {
+                "sh" => "SS",
+                "sz" => "SZ",
+                _ => return Err(format!("yahoo doesn't cover {}", symbol)),
+            };
+            Ok(format!("{}.{}", &code[2..], suffix))
+        }
+        // Four digits unless the code needs five
+        Market::Hk => Ok(format!("{:0>4}.HK", symbol[2..].trim_start_matches('0'))),
+        Market::Us => Ok(symbol.replace('.', "-")),
+    }
+}
+
+impl DataProvider for Yahoo {
+    fn id(&self) -> ProviderId {
+        ProviderId::Yahoo
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(capability, Capability::Quotes | Capability::Klines)
+    }
+
+    fn markets(&self, _capability: Capability) -> &'static [Market] {
+        &[Market::Cn, Market::Hk, Market::Us]
+    }
+
+    fn supports_kline(&self, period: KlinePeriod, adjust: Adjust) -> bool {
+        matches!(period, KlinePeriod::Daily | KlinePeriod::Minute1) && adjust == Adjust::None
+    }
+
+    fn quotes<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbols: &'a [String],
+    ) -> ProviderFuture<'a, Vec<Quote>> {
+        Box::pin(async move {
+            let mut quotes = Vec::new();
+            for symbol in symbols {
+                let Ok(ticker) = yahoo_symbol(symbol) else {
+                    continue;
+                };
+                let url = format!("{}{}?range=1d&interval=1d", YAHOO_CHART_URL, ticker);
+                let chart = self.chart(app, &url).await?;
+                quotes.extend(parse_yahoo_quote(symbol, &chart));
+            }
+            Ok(quotes)
+        })
+    }
+
+    fn klines<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbol: &'a str,
+        period: KlinePeriod,
+        _adjust: Adjust,
+        from: Option<i64>,
+    ) -> ProviderFuture<'a, Vec<Bar>> {
+        Box::pin(async move {
+            let ticker = yahoo_symbol(symbol)?;
+            // Minute bars only reach back a few days
+            let url = match period {
+                KlinePeriod::Minute1 => {
+                    format!("{}{}?range=5d&interval=1m", YAHOO_CHART_URL, ticker)
+                }
+                _ => format!(
+                    "{}{}?period1={}&period2={}&interval=1d",
+                    YAHOO_CHART_URL,
+                    ticker,
+                    from.unwrap_or(0),
+                    Utc::now().timestamp()
+                ),
+            };
+            let chart = self.chart(app, &url).await?;
+            Ok(since(
+                parse_yahoo_bars(&chart, period, market_of(symbol)?),
+                from,
+            ))
+        })
+    }
+}
+
+impl Yahoo {
+    async fn chart(&self, app: &AppHandle, url: &str) -> Result<Value, String> {
+        let body = get_text(app, self.id(), url, "chart").await?;
+        let value = app.state::<DriftLog>().check_response(
+            app,
+            self.id().source(),
+            "chart",
+            &body,
+            YAHOO_CHART_SCHEMA,
+        )?;
+        Ok(value["chart"]["result"][0].clone())
+    }
+}
+
+fn parse_yahoo_quote(symbol: &str, chart: &Value) -> Option<Quote> {
+    let meta = &chart["meta"];
+    let price = number(&meta["regularMarketPrice"])?;
+    let prev_close = number(&meta["chartPreviousClose"])?;
+    if price <= 0.0 || prev_close <= 0.0 {
+        return None;
+    }
+    let first = |key: &str| number(&chart["indicators"]["quote"][0][key][0]);
+    Some(Quote {
+        symbol: symbol.trim().to_uppercase(),
+        name: meta["shortName"].as_str().unwrap_or_default().to_string(),
+        price,
+        prev_close,
+        open: first("open").unwrap_or(price),
+        high: number(&meta["regularMarketDayHigh"]).unwrap_or(price),
+        low: number(&meta["regularMarketDayLow"]).unwrap_or(price),
+        volume: number(&meta["regularMarketVolume"]).unwrap_or_default(),
+        amount: 0.0,
+        change: price - prev_close,
+        change_pct: (price / prev_close - 1.0) * 100.0,
+        currency: Market::of(&symbol.trim().to_uppercase())?.currency(),
+        timestamp: meta["regularMarketTime"].as_i64()?,
+        source: ProviderId::Yahoo.source().to_string(),
+    })
+}
+
+/// Chart bars stamped like Eastmoney's: daily bars at midnight on the
+/// market's clock, minute bars at the minute they open
+fn parse_yahoo_bars(chart: &Value, period: KlinePeriod, market: Market) -> Vec<Bar> {
+    let quote = &chart["indicators"]["quote"][0];
+    let column = |key: &str, i: usize| number(&quote[key][i]);
+    chart["timestamp"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .filter_map(|(i, ts)| {
+            let ts = ts.as_i64()?;
+            let timestamp = match period {
+                KlinePeriod::Minute1 => ts,
+                _ => {
+                    let day = sessions::trading_day(market, ts);
+                    local_midnight(day.and_hms_opt(0, 0, 0)?, market)?
+                }
+            };
+            Some(Bar {
+                timestamp,
+                open: column("open", i)?,
+                high: column("high", i)?,
+                low: column("low", i)?,
+                close: column("close", i)?,
+                volume: column("volume", i).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+struct Xueqiu;
+
+/// Xueqiu's code: `SH600519`, `00700` for Hong Kong, the ticker for US listings
+fn xueqiu_symbol(symbol: &str) -> Result<String, String> {
+    Ok(match market_of(symbol)? {
+        Market::Cn => symbol.to_string(),
+        Market::Hk => symbol[2..].to_string(),
+        Market::Us => symbol.to_string(),
+    })
+}
+
+impl DataProvider for Xueqiu {
+    fn id(&self) -> ProviderId {
+        ProviderId::Xueqiu
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        capability == Capability::Fundamentals
+    }
+
+    fn markets(&self, _capability: Capability) -> &'static [Market] {
+        &[Market::Cn, Market::Hk, Market::Us]
+    }
+
+    fn fundamentals<'a>(
+        &'a self,
+        app: &'a AppHandle,
+        symbol: &'a str,
+    ) -> ProviderFuture<'a, Fundamentals> {
+        Box::pin(async move {
+            let source = self.id().source();
+            let url = format!(
+                "{}?symbol={}&extend=detail",
+                XUEQIU_QUOTE_URL,
+                xueqiu_symbol(symbol)?
+            );
+            let body = app
+                .state::<FaultInjector>()
+                .wrap(source, provider_sessions::session_get(app, source, &url))
+                .await?;
+            let value = app.state::<DriftLog>().check_response(
+                app,
+                source,
+                "quote",
+                &body,
+                XUEQIU_QUOTE_SCHEMA,
+            )?;
+            Ok(parse_xueqiu_fundamentals(symbol, &value["data"]["quote"]))
+        })
+    }
+}
+
+/// Market caps are in the listing's currency, shares in units
+fn parse_xueqiu_fundamentals(symbol: &str, quote: &Value) -> Fundamentals {
+    Fundamentals {
+        symbol: symbol.to_string(),
+        pe_ttm: number(&quote["pe_ttm"]),
+        pb: number(&quote["pb"]),
+        total_market_cap: number(&quote["market_capital"]),
+        float_market_cap: number(&quote["float_market_capital"]),
+        total_shares: number(&quote["total_shares"]),
+        float_shares: number(&quote["float_shares"]),
+        source: ProviderId::Xueqiu.source().to_string(),
+    }
+}
+
+fn local_midnight(day: chrono::NaiveDateTime, market: Market) -> Option<i64> {
+    sessions::timezone(market)
+        .from_local_datetime(&day)
+        .single()
+        .map(|t| t.timestamp())
+}
+
+/// Bars from the one at `from`, for providers that can't start a download there
+fn since(bars: Vec<Bar>, from: Option<i64>) -> Vec<Bar> {
+    match from {
+        Some(from) => bars
+            .into_iter()
+            .filter(|bar| bar.timestamp >= from)
+            .collect(),
+        None => bars,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderSettings {
+    /// Providers in the order they are tried; those left out are disabled
+    pub priority: Vec<ProviderId>,
+}
+
+impl Default for ProviderSettings {
+    fn default() -> Self {
+        Self {
+            priority: vec![
+                ProviderId::Tencent,
+                ProviderId::Sina,
+                ProviderId::Eastmoney,
+                ProviderId::Yahoo,
+            ],
+        }
+    }
+}
+
+impl ProviderSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.priority.is_empty() {
+            return Err("At least one data provider must be enabled".to_string());
+        }
+        for (i, id) in self.priority.iter().enumerate() {
+            if self.priority[..i].contains(id) {
+                return Err(format!("Data provider {} is listed twice", id.source()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Health {
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    /// Unix seconds
+    last_success: Option<i64>,
+    tripped_until: Option<Instant>,
+}
+
+/// Recent outcomes of each provider and capability
+#[derive(Default)]
+pub struct ProviderHealth {
+    entries: Mutex<HashMap<(ProviderId, Capability), Health>>,
+}
+
+impl ProviderHealth {
+    fn record(&self, id: ProviderId, capability: Capability, error: Option<&str>, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        let health = entries.entry((id, capability)).or_default();
+        match error {
+            None => {
+                health.consecutive_failures = 0;
+                health.last_success = Some(Utc::now().timestamp());
+                health.tripped_until = None;
+            }
+            Some(e) => {
+                health.consecutive_failures += 1;
+                health.last_error = Some(e.to_string());
+                if health.consecutive_failures >= TRIP_AFTER {
+                    health.tripped_until = Some(now + COOLDOWN);
+                }
+            }
+        }
+    }
+
+    fn tripped(&self, id: ProviderId, capability: Capability, now: Instant) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(id, capability))
+            .and_then(|health| health.tripped_until)
+            .is_some_and(|until| now < until)
+    }
+
+    /// Providers to try in order: those able to serve the request, minus
+    /// tripped ones unless that would leave none
+    fn chain(
+        &self,
+        priority: &[ProviderId],
+        capability: Capability,
+        serves: impl Fn(&dyn DataProvider) -> bool,
+        now: Instant,
+    ) -> Vec<&'static dyn DataProvider> {
+        let able: Vec<&'static dyn DataProvider> = priority
+            .iter()
+            .map(|id| id.provider())
+            .filter(|provider| serves(*provider))
+            .collect();
+        let healthy: Vec<_> = able
+            .iter()
+            .copied()
+            .filter(|provider| !self.tripped(provider.id(), capability, now))
+            .collect();
+        if healthy.is_empty() {
+            able
+        } else {
+            healthy
+        }
+    }
+}
+
+/// Run a request down the provider chain until one provider serves it
+async fn first_ok<'a, T>(
+    app: &'a AppHandle,
+    capability: Capability,
+    serves: impl Fn(&dyn DataProvider) -> bool,
+    request: impl Fn(&'static dyn DataProvider) -> ProviderFuture<'a, T>,
+) -> Result<T, String> {
+    let priority = app.state::<SettingsStore>().get().providers.priority;
+    let health = app.state::<ProviderHealth>();
+    let chain = health.chain(&priority, capability, serves, Instant::now());
+    let mut errors = Vec::new();
+    for provider in chain {
+        match request(provider).await {
+            Ok(value) => {
+                health.record(provider.id(), capability, None, Instant::now());
+                return Ok(value);
+            }
+            Err(e) => {
+                warn!("{}; trying the next provider", e);
+                health.record(provider.id(), capability, Some(&e), Instant::now());
+                errors.push(e);
+            }
+        }
+    }
+    if errors.is_empty() {
+        return Err(format!("No enabled data provider serves {:?}", capability));
+    }
+    Err(format!("All data providers failed: {}", errors.join("; ")))
+}
+
+/// Quotes of symbols of one market from the first provider that answers
+pub async fn quotes(
+    app: &AppHandle,
+    market: Market,
+    symbols: &[String],
+) -> Result<Vec<Quote>, String> {
+    first_ok(
+        app,
+        Capability::Quotes,
+        |p| covers(p, Capability::Quotes, market),
+        |p| p.quotes(app, symbols),
+    )
+    .await
+}
+
+/// Bars of a symbol from the first provider that has the period and adjustment
+pub async fn klines(
+    app: &AppHandle,
+    symbol: &str,
+    period: KlinePeriod,
+    adjust: Adjust,
+    from: Option<i64>,
+) -> Result<Vec<Bar>, String> {
+    let market = market_of(symbol)?;
+    first_ok(
+        app,
+        Capability::Klines,
+        |p| covers(p, Capability::Klines, market) && p.supports_kline(period, adjust),
+        |p| p.klines(app, symbol, period, adjust, from),
+    )
+    .await
+}
+
+/// Latest headlines from the first news provider that answers
+pub async fn news(app: &AppHandle) -> Result<Vec<NewsItem>, String> {
+    first_ok(
+        app,
+        Capability::News,
+        |p| p.supports(Capability::News),
+        |p| p.news(app),
+    )
+    .await
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapabilityHealth {
+    pub capability: Capability,
+    pub markets: &'static [Market],
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// Unix seconds
+    pub last_success: Option<i64>,
+    /// Passed over until its cooldown ends
+    pub tripped: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderStatus {
+    pub id: ProviderId,
+    /// Position in the priority list; `None` when disabled
+    pub rank: Option<usize>,
+    pub capabilities: Vec<CapabilityHealth>,
+}
+
+/// Every provider with its rank, capabilities and recent health
+#[tauri::command]
+pub fn get_providers(
+    settings: State<'_, SettingsStore>,
+    health: State<'_, ProviderHealth>,
+) -> Result<Vec<ProviderStatus>, String> {
+    let priority = settings.get().providers.priority;
+    let now = Instant::now();
+    let entries = health.entries.lock().unwrap();
+    Ok(ProviderId::ALL
+        .iter()
+        .map(|&id| ProviderStatus {
+            id,
+            rank: priority.iter().position(|p| *p == id),
+            capabilities: Capability::ALL
+                .iter()
+                .filter(|capability| id.provider().supports(**capability))
+                .map(|&capability| {
+                    let entry = entries.get(&(id, capability)).cloned().unwrap_or_default();
+                    CapabilityHealth {
+                        capability,
+                        markets: id.provider().markets(capability),
+                        consecutive_failures: entry.consecutive_failures,
+                        last_error: entry.last_error,
+                        last_success: entry.last_success,
+                        tripped: entry.tripped_until.is_some_and(|until| now < until),
+                    }
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+/// Valuation snapshot of a symbol from the first provider that answers.
+/// Nothing is cached to answer from in snapshot mode, so it fails there
+#[tauri::command]
+pub async fn get_fundamentals(app: AppHandle, symbol: String) -> Result<Fundamentals, String> {
+    let symbol = symbol_key(&symbol);
+    if app.state::<SnapshotClock>().is_frozen() {
+        return Err(format!(
+            "Fundamentals of {} are not available in snapshot mode",
+            symbol
+        ));
+    }
+    let market = market_of(&symbol)?;
+    first_ok(
+        &app,
+        Capability::Fundamentals,
+        |p| covers(p, Capability::Fundamentals, market),
+        |p| p.fundamentals(&app, &symbol),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_order_and_cooldown() {
+        let health = ProviderHealth::default();
+        let priority = ProviderSettings::default().priority;
+        let now = Instant::now();
+        let ids = |chain: Vec<&'static dyn DataProvider>| {
+            chain.iter().map(|p| p.id()).collect::<Vec<_>>()
+        };
+        let daily = |p: &dyn DataProvider| p.supports_kline(KlinePeriod::Daily, Adjust::None);
+        assert_eq!(
+            ids(health.chain(&priority, Capability::Klines, daily, now)),
+            [ProviderId::Sina, ProviderId::Eastmoney, ProviderId::Yahoo]
+        );
+        let us_daily = |p: &dyn DataProvider| {
+            covers(p, Capability::Klines, Market::Us)
+                && p.supports_kline(KlinePeriod::Daily, Adjust::None)
+        };
+        assert_eq!(
+            ids(health.chain(&priority, Capability::Klines, us_daily, now)),
+            [ProviderId::Yahoo]
+        );
+        let forward = |p: &dyn DataProvider| p.supports_kline(KlinePeriod::Daily, Adjust::Forward);
+        assert_eq!(
+            ids(health.chain(&priority, Capability::Klines, forward, now)),
+            [ProviderId::Eastmoney]
+        );
+
+        let quotes = |p: &dyn DataProvider| p.supports(Capability::Quotes);
+        for _ in 0..TRIP_AFTER {
+            health.record(ProviderId::Tencent, Capability::Quotes, Some("down"), now);
+        }
+        assert_eq!(
+            ids(health.chain(&priority, Capability::Quotes, quotes, now)),
+            [ProviderId::Sina, ProviderId::Yahoo]
+        );
+        // A tripped provider is still tried when it's the only one left
+        let only = [ProviderId::Tencent];
+        assert_eq!(
+            ids(health.chain(&only, Capability::Quotes, quotes, now)),
+            [ProviderId::Tencent]
+        );
+        assert_eq!(
+            ids(health.chain(&priority, Capability::Quotes, quotes, now + COOLDOWN)),
+            [ProviderId::Tencent, ProviderId::Sina, ProviderId::Yahoo]
+        );
+
+        // Xueqiu needs a login, so it only serves when put in the priority
+        let hk_fundamentals =
+            |p: &dyn DataProvider| covers(p, Capability::Fundamentals, Market::Hk);
+        assert!(health
+            .chain(&priority, Capability::Fundamentals, hk_fundamentals, now)
+            .is_empty());
+        let with_xueqiu = [ProviderId::Tencent, ProviderId::Xueqiu];
+        assert_eq!(
+            ids(health.chain(&with_xueqiu, Capability::Fundamentals, hk_fundamentals, now)),
+            [ProviderId::Xueqiu]
+        );
+
+        assert!(ProviderSettings {
+            priority: vec![ProviderId::Sina, ProviderId::Sina],
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_fallback_responses() {
+        let chart: Value = serde_json::json!({
+            "meta": {
+                "regularMarketPrice": 1688.0,
+                "chartPreviousClose": 1680.0,
+                "regularMarketDayHigh": 1690.0,
+                "regularMarketDayLow": 1675.0,
+                "regularMarketVolume": 2345600,
+                "regularMarketTime": 1_748_934_003,
+                "shortName": "KWEICHOW MOUTAI"
+            },
+            // 09:30 on 2025-06-02 and 2025-06-03 in Shanghai
+            "timestamp": [1_748_827_800, 1_748_914_200],
+            "indicators": {"quote": [{
+                "open": [1670.0, 1685.0],
+                "high": [1682.0, 1690.0],
+                "low": [1665.0, 1675.0],
+                "close": [1680.0, 1688.0],
+                "volume": [3000000, 2345600]
+            }]}
+        });
+        let quote = parse_yahoo_quote("sh600519", &chart).unwrap();
+        assert_eq!(quote.symbol, "SH600519");
+        assert_eq!((quote.open, quote.change), (1670.0, 8.0));
+        let bars = parse_yahoo_bars(&chart, KlinePeriod::Daily, Market::Cn);
+        assert_eq!(bars.len(), 2);
+        // Stamped at Shanghai midnight
+        assert_eq!(bars[1].timestamp, 1_748_880_000);
+        assert_eq!(since(bars, Some(1_748_880_000)).len(), 1);
+        assert_eq!(yahoo_symbol("SZ000001").unwrap(), "000001.SZ");
+        assert!(yahoo_symbol("BJ430047").is_err());
+        assert_eq!(yahoo_symbol("HK00700").unwrap(), "0700.HK");
+        assert_eq!(yahoo_symbol("HK09988").unwrap(), "9988.HK");
+        assert_eq!(yahoo_symbol("BRK.B").unwrap(), "BRK-B");
+
+        let sina = r#"[{"day":"2025-06-03","open":"1685.000","high":"1690.000","low":"1675.000","close":"1688.000","volume":"2345600"}]"#;
+        let bars = parse_sina_klines(sina).unwrap();
+        assert_eq!((bars[0].timestamp, bars[0].close), (1_748_880_000, 1688.0));
+
+        let data = serde_json::json!({"f84": 1.256e9, "f116": 2.12e12, "f164": 2356, "f167": "-"});
+        let fundamentals = parse_eastmoney_fundamentals("SH600519", &data);
+        assert_eq!(fundamentals.pe_ttm, Some(23.56));
+        assert_eq!(fundamentals.pb, None);
+
+        let quote = serde_json::json!({"pe_ttm": 23.56, "pb": 8.1, "market_capital": 2.12e12, "float_shares": null});
+        let fundamentals = parse_xueqiu_fundamentals("HK00700", &quote);
+        assert_eq!(
+            (fundamentals.pe_ttm, fundamentals.pb),
+            (Some(23.56), Some(8.1))
+        );
+        assert_eq!(fundamentals.float_shares, None);
+        assert_eq!(xueqiu_symbol("HK00700").unwrap(), "00700");
+        assert_eq!(xueqiu_symbol("SH600519").unwrap(), "SH600519");
+    }
+}
@@ -0,0 +1,240 @@
+//! Per-source proxy routes.
+//!
+//! Each data source can go through the system proxy (the default, honouring
+//! `HTTP(S)_PROXY`), connect directly, or use its own HTTP or SOCKS5 proxy
+//! with optional credentials — for example a proxy for overseas sources and
+//! a direct line to domestic ones. Routes live in settings and are applied
+//! to the [`PolicyEngine`](crate::politeness::PolicyEngine), which keeps one
+//! HTTP client per source.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::politeness::{source_policy, PolicyEngine, SOURCE_POLICIES};
+use crate::settings::SettingsStore;
+
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
+/// Stands in for a stored password in routes sent to the frontend
+const PASSWORD_MASK: &str = "********";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ProxyRoute {
+    #[default]
+    System,
+    Direct,
+    Proxy {
+        /// `http://`, `https://`, `socks5://` or `socks5h://` (DNS through the proxy)
+        url: String,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+    },
+}
+
+/// Proxy route per source id; sources not listed use the system proxy
+pub type ProxySettings = BTreeMap<String, ProxyRoute>;
+
+impl ProxyRoute {
+    fn proxy(&self) -> Result<Option<reqwest::Proxy>, String> {
+        let ProxyRoute::Proxy {
+            url,
+            username,
+            password,
+        } = self
+        else {
+            return Ok(None);
+        };
+        let mut parsed =
+            url::Url::parse(url).map_err(|e| format!("Invalid proxy URL {}: {}", url, e))?;
+        if !PROXY_SCHEMES.contains(&parsed.scheme()) {
+            return Err(format!(
+                "Unsupported proxy scheme {}; use one of {}",
+                parsed.scheme(),
+                PROXY_SCHEMES.join(", ")
+            ));
+        }
+        let socks = parsed.scheme().starts_with("socks");
+        // SOCKS credentials are only read from the URL; HTTP proxies take a header
+        if let (true, Some(username)) = (socks, username) {
+            parsed
+                .set_username(username)
+                .and_then(|_| parsed.set_password(password.as_deref()))
+                .map_err(|_| format!("Invalid proxy URL {}", url))?;
+        }
+        let mut proxy = reqwest::Proxy::all(parsed.as_str())
+            .map_err(|e| format!("Invalid proxy URL {}: {}", url, e))?;
+        if let (false, Some(username)) = (socks, username) {
+            proxy = proxy.basic_auth(username, password.as_deref().unwrap_or(""));
+        }
+        Ok(Some(proxy))
+    }
+
+    /// Client builder that sends requests along this route
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder, String> {
+        Ok(match self.proxy()? {
+            Some(proxy) => reqwest::Client::builder().proxy(proxy),
+            None if *self == ProxyRoute::Direct => reqwest::Client::builder().no_proxy(),
+            None => reqwest::Client::builder(),
+        })
+    }
+
+    /// The route with its password masked, for display
+    pub fn redacted(&self) -> Self {
+        match self {
+            ProxyRoute::Proxy {
+                url,
+                username,
+                password,
+            } => ProxyRoute::Proxy {
+                url: url.clone(),
+                username: username.clone(),
+                password: password.as_ref().map(|_| PASSWORD_MASK.to_string()),
+            },
+            route => route.clone(),
+        }
+    }
+}
+
+/// Check that every route names a known source and builds a client
+pub fn validate(proxies: &ProxySettings) -> Result<(), String> {
+    for (source, route) in proxies {
+        source_policy(source)?;
+        route.proxy()?;
+    }
+    Ok(())
+}
+
+/// Result of a connectivity check against a source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivityCheck {
+    pub source: String,
+    pub route: ProxyRoute,
+    pub url: String,
+    pub reachable: bool,
+    pub status: Option<u16>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Proxy route of every source, passwords masked
+#[tauri::command]
+pub fn get_proxy_routes(
+    settings: State<'_, SettingsStore>,
+) -> Result<BTreeMap<String, ProxyRoute>, String> {
+    let proxies = settings.get().proxies;
+    Ok(SOURCE_POLICIES
+        .iter()
+        .map(|p| {
+            let route = proxies.get(p.id).cloned().unwrap_or_default();
+            (p.id.to_string(), route.redacted())
+        })
+        .collect())
+}
+
+/// Route one source through a proxy, directly or through the system proxy
+#[tauri::command]
+pub fn set_source_proxy(
+    settings: State<'_, SettingsStore>,
+    policies: State<'_, PolicyEngine>,
+    source: String,
+    route: ProxyRoute,
+) -> Result<(), String> {
+    let mut updated = settings.get();
+    let mut route = route;
+    // A masked password sent back unchanged keeps the stored one
+    if let ProxyRoute::Proxy { password, .. } = &mut route {
+        if password.as_deref() == Some(PASSWORD_MASK) {
+            *password = match updated.proxies.get(&source) {
+                Some(ProxyRoute::Proxy { password, .. }) => password.clone(),
+                _ => None,
+            };
+        }
+    }
+    if route == ProxyRoute::System {
+        updated.proxies.remove(&source);
+    } else {
+        updated.proxies.insert(source, route);
+    }
+    settings.set(updated)?;
+    let updated = settings.get();
+    policies.configure(&updated.proxies, &updated.tls)
+}
+
+/// Check that a source is reachable over its configured route, or over `route` before saving it
+#[tauri::command]
+pub async fn test_source_connectivity(
+    policies: State<'_, PolicyEngine>,
+    settings: State<'_, SettingsStore>,
+    source: String,
+    route: Option<ProxyRoute>,
+) -> Result<ConnectivityCheck, String> {
+    let policy = source_policy(&source)?;
+    let effective = route
+        .clone()
+        .or_else(|| settings.get().proxies.get(&source).cloned())
+        .unwrap_or_default();
+    let mut check = ConnectivityCheck {
+        source: source.clone(),
+        route: effective.redacted(),
+        url: policy.probe_url(),
+        reachable: false,
+        status: None,
+        latency_ms: None,
+        error: None,
+    };
+    match policies.probe(&source, route.as_ref()).await {
+        Ok((status, latency)) => {
+            check.reachable = true;
+            check.status = Some(status);
+            check.latency_ms = Some(latency.as_millis() as u64);
+        }
+        Err(e) => check.error = Some(e),
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(url: &str) -> ProxyRoute {
+        ProxyRoute::Proxy {
+            url: url.to_string(),
+            username: Some("user".to_string()),
+            password: Some("p@ss".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_proxy_routes() {
+        let route: ProxyRoute = serde_json::from_str(
+            r#"{"mode": "proxy", "url": "socks5h://127.0.0.1:1080", "username": "user"}"#,
+        )
+        .unwrap();
+        assert!(route.client_builder().is_ok());
+        assert!(ProxyRoute::Direct.client_builder().is_ok());
+        assert!(proxy("http://proxy.local:8080").client_builder().is_ok());
+        assert!(proxy("ftp://proxy.local").client_builder().is_err());
+        assert!(proxy("not a url").client_builder().is_err());
+
+        let mut proxies = ProxySettings::new();
+        proxies.insert("eastmoney".to_string(), ProxyRoute::Direct);
+        assert!(validate(&proxies).is_ok());
+        proxies.insert("bloomberg".to_string(), ProxyRoute::Direct);
+        assert!(validate(&proxies).is_err());
+
+        assert_eq!(
+            proxy("socks5://h:1").redacted(),
+            ProxyRoute::Proxy {
+                url: "socks5://h:1".to_string(),
+                username: Some("user".to_string()),
+                password: Some("********".to_string()),
+            }
+        );
+    }
+}
@@ -0,0 +1,624 @@
+//! Real-time A-share quotes pushed to the WebView.
+//!
+//! Every subscribed A-share symbol is polled in batches down the
+//! [`providers`] chain, Tencent's quote endpoint first by default, at the quote
+//! interval from the polling settings (faster in session, slower outside).
+//! A quote is only pushed when something traded since the last one for the
+//! symbol, so an idle market produces no `quotes-updated` events at all.
+//! The latest quote of each subscribed symbol is cached for `get_quotes`,
+//! and changed quotes are run through the [`alerts`] engine.
+//! Symbols carried by a connected quote stream (see [`crate::streaming`])
+//! are left out of polling.
+//!
+//! Each symbol is polled on its own schedule, at the fastest rate of the
+//! watchlists holding it under their refresh profiles; symbols due together
+//! share batches, and a symbol no list wants refreshed right now, like one
+//! only on an end-of-day list during the session, isn't polled at all. When
+//! a market changes phase every symbol is due again at once.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDateTime, TimeZone};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::alerts;
+use crate::db::{Database, Watchlists};
+use crate::executor::{Executor, Priority, ResourceClass};
+use crate::live_indicators;
+use crate::market_clock::MarketClock;
+use crate::models::{Currency, Market};
+use crate::polling::{jitter_factor, RefreshProfile};
+use crate::portfolio_alerts;
+use crate::presence;
+use crate::providers;
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::streaming::QuoteStream;
+use crate::subscriptions::{SubscriptionLevel, SubscriptionRegistry};
+use crate::symbols::symbol_key;
+
+/// Symbols per request
+const BATCH_SIZE: usize = 60;
+
+/// How long a quote fetched by [`warm`] is kept without a subscription
+const PRELOAD_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    pub symbol: String,
+    pub name: String,
+    pub price: f64,
+    pub prev_close: f64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    /// Shares traded today
+    pub volume: f64,
+    /// Turnover today, in the quote currency
+    pub amount: f64,
+    pub change: f64,
+    pub change_pct: f64,
+    #[serde(default)]
+    pub currency: Currency,
+    /// Exchange time of the quote, Unix seconds
+    pub timestamp: i64,
+    pub source: String,
+}
+
+impl Quote {
+    /// Whether anything traded between `previous` and this quote
+    fn differs_from(&self, previous: &Quote) -> bool {
+        self.price != previous.price
+            || self.volume != previous.volume
+            || self.amount != previous.amount
+            || self.high != previous.high
+            || self.low != previous.low
+    }
+}
+
+/// `SH600519` to the `sh600519` form A-share providers use; `None` for other markets
+pub fn provider_code(symbol: &str) -> Option<String> {
+    let symbol = symbol.trim().to_uppercase();
+    ["SH", "SZ", "BJ"]
+        .iter()
+        .find_map(|prefix| symbol.strip_prefix(prefix))
+        .filter(|code| code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()))?;
+    Some(symbol.to_lowercase())
+}
+
+/// Tencent's code for a symbol of any market: `sh600519`, `hk00700`, `usAAPL`
+pub fn tencent_code(symbol: &str) -> Option<String> {
+    match Market::of(symbol)? {
+        Market::Cn => provider_code(symbol),
+        Market::Hk => Some(symbol.to_lowercase()),
+        Market::Us => Some(format!("us{}", symbol)),
+    }
+}
+
+/// Each `name="fields"` assignment in a provider response
+pub fn assignments<'a>(body: &'a str, prefix: &str) -> Vec<(String, &'a str)> {
+    body.split(';')
+        .filter_map(|statement| {
+            let statement = statement.trim();
+            let (name, value) = statement.split_once('=')?;
+            let code = name.trim().strip_prefix(prefix)?;
+            Some((code.to_uppercase(), value.trim().trim_matches('"')))
+        })
+        .collect()
+}
+
+fn exchange_time(value: &str, format: &str, market: Market) -> Option<i64> {
+    let local = NaiveDateTime::parse_from_str(value, format).ok()?;
+    sessions::timezone(market)
+        .from_local_datetime(&local)
+        .single()
+        .map(|t| t.timestamp())
+}
+
+fn quote(
+    symbol: String,
+    name: &str,
+    numbers: [f64; 7],
+    timestamp: i64,
+    source: &str,
+) -> Option<Quote> {
+    let [price, prev_close, open, high, low, volume, amount] = numbers;
+    // Suspended or not yet traded
+    if price <= 0.0 || prev_close <= 0.0 {
+        return None;
+    }
+    let currency = Market::of(&symbol).unwrap_or(Market::Cn).currency();
+    Some(Quote {
+        symbol,
+        name: name.to_string(),
+        price,
+        prev_close,
+        open,
+        high,
+        low,
+        volume,
+        amount,
+        change: price - prev_close,
+        change_pct: (price / prev_close - 1.0) * 100.0,
+        currency,
+        timestamp,
+        source: source.to_string(),
+    })
+}
+
+/// `v_sh600519="1~贵州茅台~600519~price~prev~open~lots~…~yyyyMMddHHmmss~…~high~low~…~amount(万)~…";`
+///
+/// Hong Kong and US records (`v_hk00700`, `v_usAAPL`) have the same layout
+/// but count volume in shares, turnover in currency units, and write the time
+/// as `yyyy/MM/dd HH:mm:ss` and `yyyy-MM-dd HH:mm:ss`.
+pub fn parse_tencent(body: &str) -> Vec<Quote> {
+    assignments(body, "v_")
+        .into_iter()
+        .filter_map(|(code, value)| {
+            let symbol = match code.strip_prefix("US") {
+                Some(ticker) => ticker.to_string(),
+                None => code,
+            };
+            let market = Market::of(&symbol)?;
+            let (lot, turnover_unit, time_format) = match market {
+                Market::Cn => (100.0, 10_000.0, "%Y%m%d%H%M%S"),
+                Market::Hk => (1.0, 1.0, "%Y/%m/%d %H:%M:%S"),
+                Market::Us => (1.0, 1.0, "%Y-%m-%d %H:%M:%S"),
+            };
+            let fields: Vec<&str> = value.split('~').collect();
+            let number = |i: usize| fields.get(i)?.parse::<f64>().ok();
+            let numbers = [
+                number(3)?,
+                number(4)?,
+                number(5)?,
+                number(33)?,
+                number(34)?,
+                number(6)? * lot,
+                number(37)? * turnover_unit,
+            ];
+            let timestamp = exchange_time(fields.get(30)?, time_format, market)?;
+            quote(symbol, fields[1], numbers, timestamp, "tencent")
+        })
+        .collect()
+}
+
+/// `var hq_str_sh600519="贵州茅台,open,prev,price,high,low,bid,ask,shares,yuan,…,date,time,…";`
+///
+/// The WebSocket feed sends the same records one per line as `sh600519=…`.
+pub fn parse_sina(body: &str) -> Vec<Quote> {
+    body.split([';', '\n'])
+        .filter_map(|statement| {
+            let statement = statement.trim();
+            let statement = statement.strip_prefix("var ").unwrap_or(statement);
+            let (name, value) = statement.split_once('=')?;
+            let name = name.trim();
+            let code = name.strip_prefix("hq_str_").unwrap_or(name);
+            provider_code(code)?;
+            Some((code.to_uppercase(), value.trim().trim_matches('"')))
+        })
+        .filter_map(|(symbol, value)| {
+            let fields: Vec<&str> = value.split(',').collect();
+            let number = |i: usize| fields.get(i)?.parse::<f64>().ok();
+            let numbers = [
+                number(3)?,
+                number(2)?,
+                number(1)?,
+                number(4)?,
+                number(5)?,
+                number(8)?,
+                number(9)?,
+            ];
+            let time = format!("{} {}", fields.get(30)?, fields.get(31)?);
+            let timestamp = exchange_time(&time, "%Y-%m-%d %H:%M:%S", Market::Cn)?;
+            quote(symbol, fields[0], numbers, timestamp, "sina")
+        })
+        .collect()
+}
+
+/// Latest quote of each subscribed or recently preloaded symbol
+#[derive(Default)]
+pub struct QuoteFeed {
+    latest: Mutex<HashMap<String, Quote>>,
+    /// Expiry of each preloaded symbol's quote
+    preloaded: Mutex<HashMap<String, Instant>>,
+}
+
+impl QuoteFeed {
+    /// Store fresh quotes, returning those that changed, and forget symbols no longer watched
+    fn update(&self, quotes: Vec<Quote>, watched: &[String]) -> Vec<Quote> {
+        self.forget_unwatched(watched, Instant::now());
+        self.merge(quotes)
+    }
+
+    /// Drop quotes of symbols neither watched nor preloaded until after `now`
+    fn forget_unwatched(&self, watched: &[String], now: Instant) {
+        let mut preloaded = self.preloaded.lock().unwrap();
+        preloaded.retain(|_, until| *until > now);
+        self.latest
+            .lock()
+            .unwrap()
+            .retain(|symbol, _| watched.contains(symbol) || preloaded.contains_key(symbol));
+    }
+
+    /// Store quotes fetched ahead of any subscription, kept for [`PRELOAD_TTL`]
+    fn preload(&self, quotes: Vec<Quote>, now: Instant) {
+        self.preloaded.lock().unwrap().extend(
+            quotes
+                .iter()
+                .map(|quote| (quote.symbol.clone(), now + PRELOAD_TTL)),
+        );
+        self.merge(quotes);
+    }
+
+    /// Store fresh quotes, returning those that changed
+    fn merge(&self, quotes: Vec<Quote>) -> Vec<Quote> {
+        let mut latest = self.latest.lock().unwrap();
+        let mut changed = Vec::new();
+        for quote in quotes {
+            if latest
+                .get(&quote.symbol)
+                .map_or(true, |previous| quote.differs_from(previous))
+            {
+                changed.push(quote.clone());
+            }
+            latest.insert(quote.symbol.clone(), quote);
+        }
+        changed
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<Quote> {
+        self.latest.lock().unwrap().get(symbol).cloned()
+    }
+
+    /// Number of cached quotes
+    pub fn len(&self) -> usize {
+        self.latest.lock().unwrap().len()
+    }
+}
+
+/// Quotes for symbols of any market, batch by batch down the provider chain
+/// of each market, once the executor admits the fetch at `priority`
+async fn fetch(
+    app: &AppHandle,
+    symbols: &[String],
+    priority: Priority,
+) -> Result<Vec<Quote>, String> {
+    let _permit = app
+        .state::<Executor>()
+        .acquire(ResourceClass::Network, priority)
+        .await;
+    let mut quotes = Vec::new();
+    for market in [Market::Cn, Market::Hk, Market::Us] {
+        let of_market: Vec<String> = symbols
+            .iter()
+            .filter(|symbol| Market::of(symbol) == Some(market))
+            .cloned()
+            .collect();
+        for batch in of_market.chunks(BATCH_SIZE) {
+            quotes.extend(providers::quotes(app, market, batch).await?);
+        }
+    }
+    Ok(quotes)
+}
+
+/// Subscribed symbols the quote providers cover
+pub fn watched_symbols(app: &AppHandle) -> Vec<String> {
+    app.state::<SubscriptionRegistry>()
+        .symbols_at(SubscriptionLevel::Quote)
+        .into_iter()
+        .filter(|symbol| Market::of(symbol).is_some())
+        .collect()
+}
+
+/// Current quote interval of each watched symbol under its watchlists'
+/// refresh profiles; `None` while none of them wants it refreshed
+pub fn quote_intervals(app: &AppHandle, now: i64) -> Vec<(String, Option<Duration>)> {
+    let polling = app.state::<SettingsStore>().get().polling;
+    let db = app.state::<Database>();
+    let lists = db
+        .conn()
+        .and_then(|conn| Watchlists(&conn).list())
+        .unwrap_or_else(|e| {
+            warn!("Failed to read watchlist refresh profiles: {}", e);
+            Vec::new()
+        });
+    watched_symbols(app)
+        .into_iter()
+        .map(|symbol| {
+            let market = Market::of(&symbol).unwrap_or(Market::Cn);
+            // A list's market focus overrides the symbol's own session clock
+            let holding: Vec<(RefreshProfile, Market)> = lists
+                .iter()
+                .filter(|list| list.items.iter().any(|item| item.symbol == symbol))
+                .map(|list| (list.refresh_profile, list.market.unwrap_or(market)))
+                .collect();
+            let interval = polling.symbol_quote_interval(&holding, market, now);
+            (symbol, interval)
+        })
+        .collect()
+}
+
+fn emit_changed(app: &AppHandle, changed: Vec<Quote>) {
+    if changed.is_empty() {
+        return;
+    }
+    if let Err(e) = alerts::evaluate(app, &changed) {
+        warn!("Failed to evaluate alerts: {}", e);
+    }
+    if let Err(e) = portfolio_alerts::evaluate(app, &changed) {
+        warn!("Failed to evaluate portfolio alerts: {}", e);
+    }
+    live_indicators::on_quotes(app, &changed);
+    if let Err(e) = app.emit("quotes-updated", changed) {
+        warn!("Failed to emit quotes-updated event: {}", e);
+    }
+}
+
+/// Push streamed quotes that changed to the frontend
+pub fn publish(app: &AppHandle, quotes: Vec<Quote>) {
+    let changed = app.state::<QuoteFeed>().merge(quotes);
+    emit_changed(app, changed);
+}
+
+/// Poll the symbols that are due, keeping `schedule` to the next due time of
+/// every symbol polling is responsible for
+async fn refresh(
+    app: &AppHandle,
+    schedule: &mut HashMap<String, Instant>,
+    phase_changes: &mut u64,
+) -> Result<(), String> {
+    // Everything is due again once the freeze or the pause ends
+    if app.state::<SnapshotClock>().is_frozen() || presence::paused(app) {
+        schedule.clear();
+        return Ok(());
+    }
+    // and when a market opens, breaks or closes, so no symbol waits out an
+    // off-hours interval into the session
+    let changes = app.state::<MarketClock>().changes();
+    if changes != *phase_changes {
+        *phase_changes = changes;
+        schedule.clear();
+    }
+    let intervals = quote_intervals(app, chrono::Utc::now().timestamp());
+    let watched: Vec<String> = intervals.iter().map(|(symbol, _)| symbol.clone()).collect();
+    let stream = app.state::<QuoteStream>();
+    let polled: HashMap<String, Duration> = intervals
+        .into_iter()
+        .filter(|(symbol, _)| !stream.covers(symbol))
+        .filter_map(|(symbol, interval)| Some((symbol, interval?)))
+        .collect();
+    schedule.retain(|symbol, _| polled.contains_key(symbol));
+
+    let now = Instant::now();
+    let due: Vec<String> = polled
+        .keys()
+        .filter(|symbol| schedule.get(*symbol).map_or(true, |at| *at <= now))
+        .cloned()
+        .collect();
+    // One jitter for the round, so symbols polled together stay batched
+    let factor = jitter_factor(app.state::<SettingsStore>().get().polling.jitter);
+    for symbol in &due {
+        schedule.insert(symbol.clone(), now + polled[symbol].mul_f64(factor));
+    }
+    let quotes = if due.is_empty() {
+        Vec::new()
+    } else {
+        fetch(app, &due, Priority::Normal).await?
+    };
+    let changed = app.state::<QuoteFeed>().update(quotes, &watched);
+    emit_changed(app, changed);
+    Ok(())
+}
+
+/// Poll quotes of subscribed symbols as their refresh profiles make them due
+pub fn start_quote_polling(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut schedule = HashMap::new();
+        let mut phase_changes = handle.state::<MarketClock>().changes();
+        loop {
+            if let Err(e) = refresh(&handle, &mut schedule, &mut phase_changes).await {
+                warn!("Quote refresh failed: {}", e);
+            }
+            // Wake for the next due symbol, and at the session rate to pick up new ones
+            let polling = handle.state::<SettingsStore>().get().polling;
+            let session = Duration::from_secs(polling.quotes_secs);
+            let now = Instant::now();
+            let delay = schedule
+                .values()
+                .map(|at| at.saturating_duration_since(now))
+                .min()
+                .map_or(session, |next| next.min(session));
+            presence::sleep_or_resume(&handle, delay).await;
+        }
+    });
+}
+
+/// Fetch quotes of `symbols` the feed holds none of yet, so the first view
+/// of them opens on a cached quote even before it subscribes; returns how
+/// many were fetched. Nothing is fetched in snapshot mode
+pub async fn warm(app: &AppHandle, symbols: &[String]) -> Result<usize, String> {
+    if app.state::<SnapshotClock>().is_frozen() {
+        return Ok(0);
+    }
+    let feed = app.state::<QuoteFeed>();
+    let missing: Vec<String> = symbols
+        .iter()
+        .map(|s| symbol_key(s))
+        .filter(|symbol| Market::of(symbol).is_some() && feed.get(symbol).is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok(0);
+    }
+    let quotes = fetch(app, &missing, Priority::Background).await?;
+    let fetched = quotes.len();
+    feed.preload(quotes, Instant::now());
+    Ok(fetched)
+}
+
+/// Latest quotes of the given symbols, fetched now for any not polled yet;
+/// symbols may carry a market suffix such as `00700.HK`. In snapshot mode
+/// only cached quotes from before the snapshot time are returned
+#[tauri::command]
+pub async fn get_quotes(
+    app: AppHandle,
+    feed: State<'_, QuoteFeed>,
+    symbols: Vec<String>,
+) -> Result<Vec<Quote>, String> {
+    let symbols: Vec<String> = symbols.iter().map(|s| symbol_key(s)).collect();
+    if let Some(frozen_at) = app.state::<SnapshotClock>().frozen_at() {
+        return Ok(symbols
+            .iter()
+            .filter_map(|symbol| feed.get(symbol))
+            .filter(|quote| quote.timestamp <= frozen_at)
+            .collect());
+    }
+    let missing: Vec<String> = symbols
+        .iter()
+        .filter(|symbol| feed.get(symbol).is_none())
+        .cloned()
+        .collect();
+    let fetched: HashMap<String, Quote> = if missing.is_empty() {
+        HashMap::new()
+    } else {
+        fetch(&app, &missing, Priority::Interactive)
+            .await?
+            .into_iter()
+            .map(|quote| (quote.symbol.clone(), quote))
+            .collect()
+    };
+    Ok(symbols
+        .iter()
+        .filter_map(|symbol| feed.get(symbol).or_else(|| fetched.get(symbol).cloned()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+
+    const TENCENT: &str = "v_sh600519=\"1~贵州茅台~600519~1688.00~1680.00~1685.00~23456~12000~11456~1687.99~3~\
+~~~~~~~~~~~~~~~~~~~20250603150003~8.00~0.48~1690.00~1675.00~1688.00/23456/3950000000~23456~395000~0.19~\";\n\
+v_pv_none_match=\"1\";\n";
+
+    const SINA: &str =
+        "var hq_str_sz000001=\"平安银行,11.200,11.150,11.300,11.350,11.100,11.290,11.300,\
+98765400,1112345678.900,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,2025-06-03,15:00:03,00\";\n\
+var hq_str_sh000000=\"\";\n";
+
+    #[test]
+    fn test_parse_provider_batches() {
+        let quotes = parse_tencent(TENCENT);
+        assert_eq!(quotes.len(), 1);
+        let moutai = &quotes[0];
+        assert_eq!(moutai.symbol, "SH600519");
+        assert_eq!(
+            (moutai.price, moutai.high, moutai.low),
+            (1688.0, 1690.0, 1675.0)
+        );
+        assert_eq!(moutai.volume, 2_345_600.0);
+        assert_eq!(moutai.amount, 3_950_000_000.0);
+        // 15:00:03 in Shanghai
+        assert_eq!(moutai.timestamp, 1_748_934_003);
+
+        let quotes = parse_sina(SINA);
+        assert_eq!(quotes.len(), 1);
+        let pingan = &quotes[0];
+        assert_eq!(pingan.symbol, "SZ000001");
+        assert_eq!(
+            (pingan.open, pingan.prev_close, pingan.price),
+            (11.2, 11.15, 11.3)
+        );
+        assert!((pingan.change_pct - 1.3452914798206).abs() < 1e-9);
+        assert_eq!(pingan.timestamp, 1_748_934_003);
+        // The WebSocket feed's line format
+        let streamed = SINA.replace("var hq_str_", "").replace(['"', ';'], "");
+        assert_eq!(parse_sina(&streamed), quotes);
+
+        assert_eq!(provider_code("bj430047").as_deref(), Some("bj430047"));
+        assert!(provider_code("HK00700").is_none());
+    }
+
+    #[test]
+    fn test_parse_recorded_tencent_batch() {
+        let quotes = parse_tencent(&fixture("tencent_quotes.txt"));
+        let symbols: Vec<&str> = quotes.iter().map(|q| q.symbol.as_str()).collect();
+        assert_eq!(symbols, ["SH600519", "SZ000001", "HK00700"]);
+        let pingan = &quotes[1];
+        assert_eq!(
+            (pingan.price, pingan.prev_close, pingan.open),
+            (11.3, 11.15, 11.2)
+        );
+        assert_eq!((pingan.high, pingan.low), (11.35, 11.1));
+        assert_eq!(pingan.volume, 98_765_400.0);
+        assert!((pingan.amount - 1_112_345_700.0).abs() < 1e-3);
+        assert_eq!(quotes[2].currency, Currency::Hkd);
+        assert_eq!(quotes[2].timestamp, 1_748_938_090);
+    }
+
+    #[test]
+    fn test_parse_tencent_hk_and_us() {
+        let body = "v_hk00700=\"100~腾讯控股~00700~380.20~376.00~377.00~18234567.0~0~0~380.20~0~\
+~~~~~~~~~~~~~~~~~~~2025/06/03 16:08:10~4.20~1.12~382.00~375.40~380.20~18234567.0~6912345678.0~0~\";\n\
+v_usAAPL=\"200~苹果~AAPL.OQ~203.27~201.70~201.35~46381567~0~0~203.27~0~\
+~~~~~~~~~~~~~~~~~~~2025-06-03 16:00:00~1.57~0.78~203.77~200.96~203.27~46381567~9405050000~0~\";\n";
+        let quotes = parse_tencent(body);
+        assert_eq!(quotes.len(), 2);
+        let tencent = &quotes[0];
+        assert_eq!(tencent.symbol, "HK00700");
+        assert_eq!(tencent.currency, Currency::Hkd);
+        // Hong Kong volume is in shares, turnover in dollars
+        assert_eq!(tencent.volume, 18_234_567.0);
+        assert_eq!(tencent.amount, 6_912_345_678.0);
+        // 16:08:10 in Hong Kong
+        assert_eq!(tencent.timestamp, 1_748_938_090);
+        let apple = &quotes[1];
+        assert_eq!(apple.symbol, "AAPL");
+        assert_eq!(apple.currency, Currency::Usd);
+        // 16:00 in New York, on daylight time
+        assert_eq!(apple.timestamp, 1_748_980_800);
+
+        assert_eq!(tencent_code("HK00700").as_deref(), Some("hk00700"));
+        assert_eq!(tencent_code("AAPL").as_deref(), Some("usAAPL"));
+        assert_eq!(tencent_code("SH600519").as_deref(), Some("sh600519"));
+    }
+
+    #[test]
+    fn test_unchanged_ticks_are_dropped() {
+        let feed = QuoteFeed::default();
+        let watched = vec!["SH600519".to_string()];
+        let first = parse_tencent(TENCENT);
+        assert_eq!(feed.update(first.clone(), &watched).len(), 1);
+        // Same trades, later clock
+        let mut repeat = first[0].clone();
+        repeat.timestamp += 3;
+        assert!(feed.update(vec![repeat.clone()], &watched).is_empty());
+        let mut traded = repeat;
+        traded.volume += 100.0;
+        assert_eq!(feed.update(vec![traded], &watched).len(), 1);
+        // Unsubscribed symbols are forgotten
+        feed.update(Vec::new(), &[]);
+        assert!(feed.get("SH600519").is_none());
+    }
+
+    #[test]
+    fn test_preloaded_quotes_outlive_a_refresh() {
+        let feed = QuoteFeed::default();
+        let now = Instant::now();
+        feed.preload(parse_tencent(TENCENT), now);
+        // A polling round with nothing subscribed keeps the preloaded quote
+        feed.update(Vec::new(), &[]);
+        assert_eq!(feed.get("SH600519").unwrap().price, 1688.0);
+        feed.forget_unwatched(&[], now + PRELOAD_TTL / 2);
+        assert!(feed.get("SH600519").is_some());
+
+        feed.forget_unwatched(&[], now + PRELOAD_TTL);
+        assert!(feed.get("SH600519").is_none());
+    }
+}
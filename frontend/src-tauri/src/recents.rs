@@ -0,0 +1,454 @@
+//! Recent symbols and quick actions on the taskbar jump list and dock menu.
+//!
+//! Every chart view is recorded here as well as in the preload usage
+//! tracker, and the most recent symbols are offered from the OS surfaces
+//! next to a few quick actions. On Windows they go on the jump list: recent
+//! charts under their own category, quick actions as tasks. Each entry
+//! relaunches the app with [`OPEN_CHART_FLAG`] or [`ACTION_FLAG`], which the
+//! single-instance plugin hands to the instance already running; a cold
+//! start keeps the action until the frontend asks for it with
+//! `take_launch_action`. On macOS the same entries make up the dock menu,
+//! which AppKit asks the app delegate for each time it opens. Views made in
+//! a guest session aren't recorded.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::guest::GuestMode;
+use crate::instruments::InstrumentMaster;
+use crate::notifications;
+use crate::symbols::symbol_key;
+use crate::utils::{read_from_file, write_to_file};
+
+pub const OPEN_CHART_FLAG: &str = "--open-chart";
+pub const ACTION_FLAG: &str = "--quick-action";
+
+/// Recent symbols kept and shown
+const MAX_RECENTS: usize = 8;
+
+/// Actions offered next to the recent symbols, carried out by the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuickAction {
+    Search,
+    MorningScreen,
+}
+
+impl QuickAction {
+    const ALL: [QuickAction; 2] = [QuickAction::Search, QuickAction::MorningScreen];
+
+    fn id(self) -> &'static str {
+        match self {
+            QuickAction::Search => "search",
+            QuickAction::MorningScreen => "morning_screen",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            QuickAction::Search => "搜索股票",
+            QuickAction::MorningScreen => "运行早盘选股",
+        }
+    }
+
+    fn parse(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+}
+
+/// What a jump list or dock menu entry does
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum LaunchAction {
+    OpenChart(String),
+    Quick(QuickAction),
+}
+
+impl LaunchAction {
+    /// Command line that relaunches the app into this action
+    fn args(&self) -> String {
+        match self {
+            LaunchAction::OpenChart(symbol) => format!("{} {}", OPEN_CHART_FLAG, symbol),
+            LaunchAction::Quick(action) => format!("{} {}", ACTION_FLAG, action.id()),
+        }
+    }
+}
+
+/// An entry on the jump list or dock menu
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpEntry {
+    pub label: String,
+    pub action: LaunchAction,
+}
+
+/// Action asked for on a command line, if any
+pub fn parse_args(args: &[String]) -> Option<LaunchAction> {
+    let value = |flag: &str| {
+        let at = args.iter().position(|arg| arg == flag)?;
+        args.get(at + 1)
+    };
+    if let Some(symbol) = value(OPEN_CHART_FLAG) {
+        return Some(LaunchAction::OpenChart(symbol_key(symbol)));
+    }
+    value(ACTION_FLAG)
+        .and_then(|id| QuickAction::parse(id))
+        .map(LaunchAction::Quick)
+}
+
+/// Recently viewed symbols, most recent first
+pub struct RecentSymbols {
+    path: PathBuf,
+    symbols: Mutex<Vec<String>>,
+}
+
+impl RecentSymbols {
+    pub fn load(path: PathBuf) -> Self {
+        let symbols = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            symbols: Mutex::new(symbols),
+        }
+    }
+
+    /// Move a symbol to the front; false when it already was there
+    pub fn record(&self, symbol: &str) -> Result<bool, String> {
+        let symbol = symbol_key(symbol);
+        let mut symbols = self.symbols.lock().unwrap();
+        if symbols.first() == Some(&symbol) {
+            return Ok(false);
+        }
+        symbols.retain(|s| *s != symbol);
+        symbols.insert(0, symbol);
+        symbols.truncate(MAX_RECENTS);
+
+        let content = serde_json::to_string(&*symbols)
+            .map_err(|e| format!("Failed to serialize recent symbols: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save recent symbols: {}", e))?;
+        Ok(true)
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.symbols.lock().unwrap().clone()
+    }
+}
+
+/// Action the app was cold-started into, until the frontend takes it
+pub struct PendingLaunch(Mutex<Option<LaunchAction>>);
+
+impl PendingLaunch {
+    pub fn new(action: Option<LaunchAction>) -> Self {
+        Self(Mutex::new(action))
+    }
+}
+
+/// Recent chart entries, then quick actions
+fn entries(app: &AppHandle) -> (Vec<JumpEntry>, Vec<JumpEntry>) {
+    let master = app.state::<InstrumentMaster>();
+    let recents = app
+        .state::<RecentSymbols>()
+        .list()
+        .into_iter()
+        .map(|symbol| JumpEntry {
+            label: format!(
+                "打开{}图表",
+                master.name(&symbol).unwrap_or_else(|| symbol.clone())
+            ),
+            action: LaunchAction::OpenChart(symbol),
+        })
+        .collect();
+    let tasks = QuickAction::ALL
+        .into_iter()
+        .map(|action| JumpEntry {
+            label: action.label().to_string(),
+            action: LaunchAction::Quick(action),
+        })
+        .collect();
+    (recents, tasks)
+}
+
+/// Rebuild the jump list or dock menu from the current recents
+pub fn refresh(app: &AppHandle) {
+    let (recents, tasks) = entries(app);
+    platform::update(recents, tasks);
+}
+
+/// Record a chart view and bring the OS surfaces up to date
+pub fn record(app: &AppHandle, symbol: &str) {
+    if app.state::<GuestMode>().active() {
+        return;
+    }
+    match app.state::<RecentSymbols>().record(symbol) {
+        Ok(true) => refresh(app),
+        Ok(false) => {}
+        Err(e) => warn!("Failed to record recent symbol {}: {}", symbol, e),
+    }
+}
+
+/// Carry out an entry chosen from the jump list or dock menu
+pub fn run(app: &AppHandle, action: &LaunchAction) {
+    match action {
+        LaunchAction::OpenChart(symbol) => notifications::open_chart(app, symbol),
+        LaunchAction::Quick(action) => {
+            notifications::focus_main_window(app);
+            if let Err(e) = app.emit("quick-action", action.id()) {
+                warn!("Failed to emit quick-action event: {}", e);
+            }
+        }
+    }
+}
+
+/// Arguments passed to a second launch, forwarded by the single-instance plugin
+pub fn on_second_instance(app: &AppHandle, args: &[String]) {
+    match parse_args(args) {
+        Some(action) => run(app, &action),
+        None => notifications::focus_main_window(app),
+    }
+}
+
+/// Populate the jump list or dock menu at startup
+pub fn start_recents(app: &AppHandle) {
+    platform::install(app);
+    refresh(app);
+}
+
+/// Action the app was launched into from the jump list, once
+#[tauri::command]
+pub fn take_launch_action(
+    pending: State<'_, PendingLaunch>,
+) -> Result<Option<LaunchAction>, String> {
+    Ok(pending.0.lock().unwrap().take())
+}
+
+#[cfg(windows)]
+mod platform {
+    use log::warn;
+    use tauri::AppHandle;
+    use windows::core::{Interface, Result, HSTRING, PROPVARIANT};
+    use windows::Win32::Storage::EnhancedStorage::PKEY_Title;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER,
+        COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::Common::{IObjectArray, IObjectCollection};
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::UI::Shell::{
+        DestinationList, EnumerableObjectCollection, ICustomDestinationList, IShellLinkW, ShellLink,
+    };
+
+    use super::JumpEntry;
+
+    const RECENT_CATEGORY: &str = "最近查看";
+
+    pub fn install(_app: &AppHandle) {}
+
+    /// Replace the jump list; COM calls run on a thread of their own
+    pub fn update(recents: Vec<JumpEntry>, tasks: Vec<JumpEntry>) {
+        let exe = match std::env::current_exe() {
+            Ok(exe) => HSTRING::from(exe.as_os_str()),
+            Err(e) => {
+                warn!("Failed to update the jump list: {}", e);
+                return;
+            }
+        };
+        std::thread::spawn(move || {
+            let result = unsafe {
+                CoInitializeEx(None, COINIT_APARTMENTTHREADED)
+                    .ok()
+                    .and_then(|_| {
+                        let result = build(&exe, &recents, &tasks);
+                        CoUninitialize();
+                        result
+                    })
+            };
+            if let Err(e) = result {
+                warn!("Failed to update the jump list: {}", e);
+            }
+        });
+    }
+
+    unsafe fn build(exe: &HSTRING, recents: &[JumpEntry], tasks: &[JumpEntry]) -> Result<()> {
+        let list: ICustomDestinationList =
+            CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+        let mut min_slots = 0u32;
+        let _removed: IObjectArray = list.BeginList(&mut min_slots)?;
+        if !recents.is_empty() {
+            let category = collection(exe, recents)?;
+            list.AppendCategory(&HSTRING::from(RECENT_CATEGORY), &category)?;
+        }
+        list.AddUserTasks(&collection(exe, tasks)?)?;
+        list.CommitList()
+    }
+
+    unsafe fn collection(exe: &HSTRING, entries: &[JumpEntry]) -> Result<IObjectArray> {
+        let collection: IObjectCollection =
+            CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+        for entry in entries {
+            let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+            link.SetPath(exe)?;
+            link.SetArguments(&HSTRING::from(entry.action.args()))?;
+            link.SetIconLocation(exe, 0)?;
+            let properties: IPropertyStore = link.cast()?;
+            properties.SetValue(&PKEY_Title, &PROPVARIANT::from(entry.label.as_str()))?;
+            properties.Commit()?;
+            collection.AddObject(&link)?;
+        }
+        collection.cast()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::sync::{Mutex, OnceLock};
+
+    use log::warn;
+    use objc2::ffi::class_addMethod;
+    use objc2::rc::Retained;
+    use objc2::runtime::{AnyClass, AnyObject, Imp, Sel};
+    use objc2::sel;
+    use objc2_app_kit::{NSApplication, NSMenu, NSMenuItem};
+    use objc2_foundation::{MainThreadMarker, NSString};
+    use tauri::AppHandle;
+
+    use super::JumpEntry;
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+    /// Dock menu entries, `None` for the separator between recents and actions
+    static ENTRIES: Mutex<Vec<Option<JumpEntry>>> = Mutex::new(Vec::new());
+
+    /// Teach the app delegate `applicationDockMenu:` and the action its
+    /// items send; must run on the main thread
+    pub fn install(app: &AppHandle) {
+        let Some(mtm) = MainThreadMarker::new() else {
+            warn!("Dock menu not installed: not on the main thread");
+            return;
+        };
+        let Some(delegate) = NSApplication::sharedApplication(mtm).delegate() else {
+            warn!("Dock menu not installed: no application delegate");
+            return;
+        };
+        if APP.set(app.clone()).is_err() {
+            return;
+        }
+        let object: &AnyObject = (*delegate).as_ref();
+        let class = object.class() as *const AnyClass as *mut AnyClass;
+        unsafe {
+            let dock_menu: unsafe extern "C" fn(&AnyObject, Sel, *mut AnyObject) -> *mut NSMenu =
+                dock_menu;
+            let open_entry: unsafe extern "C" fn(&AnyObject, Sel, &NSMenuItem) = open_entry;
+            class_addMethod(
+                class,
+                sel!(applicationDockMenu:),
+                std::mem::transmute::<_, Imp>(dock_menu),
+                b"@@:@\0".as_ptr().cast(),
+            );
+            class_addMethod(
+                class,
+                sel!(openDockEntry:),
+                std::mem::transmute::<_, Imp>(open_entry),
+                b"v@:@\0".as_ptr().cast(),
+            );
+        }
+    }
+
+    pub fn update(recents: Vec<JumpEntry>, tasks: Vec<JumpEntry>) {
+        let mut entries: Vec<Option<JumpEntry>> = recents.into_iter().map(Some).collect();
+        if !entries.is_empty() {
+            entries.push(None);
+        }
+        entries.extend(tasks.into_iter().map(Some));
+        *ENTRIES.lock().unwrap() = entries;
+    }
+
+    /// Built fresh each time, so it always shows the current recents
+    unsafe extern "C" fn dock_menu(
+        this: &AnyObject,
+        _cmd: Sel,
+        _sender: *mut AnyObject,
+    ) -> *mut NSMenu {
+        let mtm = MainThreadMarker::new_unchecked();
+        let menu = NSMenu::new(mtm);
+        for (tag, entry) in ENTRIES.lock().unwrap().iter().enumerate() {
+            let item = match entry {
+                Some(entry) => {
+                    let item = NSMenuItem::initWithTitle_action_keyEquivalent(
+                        mtm.alloc(),
+                        &NSString::from_str(&entry.label),
+                        Some(sel!(openDockEntry:)),
+                        &NSString::from_str(""),
+                    );
+                    item.setTarget(Some(this));
+                    item.setTag(tag as isize);
+                    item
+                }
+                None => NSMenuItem::separatorItem(mtm),
+            };
+            menu.addItem(&item);
+        }
+        Retained::autorelease_return(menu)
+    }
+
+    unsafe extern "C" fn open_entry(_this: &AnyObject, _cmd: Sel, sender: &NSMenuItem) {
+        let entry = ENTRIES
+            .lock()
+            .unwrap()
+            .get(sender.tag() as usize)
+            .cloned()
+            .flatten();
+        if let (Some(app), Some(entry)) = (APP.get(), entry) {
+            super::run(app, &entry.action);
+        }
+    }
+}
+
+/// No jump list or dock menu elsewhere
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use tauri::AppHandle;
+
+    use super::JumpEntry;
+
+    pub fn install(_app: &AppHandle) {}
+
+    pub fn update(_recents: Vec<JumpEntry>, _tasks: Vec<JumpEntry>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recents_and_launch_args() {
+        let path = std::env::temp_dir().join(format!("ssi-recents-{}.json", std::process::id()));
+        let recents = RecentSymbols::load(path.clone());
+        assert!(recents.record("600519").unwrap());
+        assert!(recents.record("sz000001").unwrap());
+        assert!(!recents.record("SZ000001").unwrap());
+        assert!(recents.record("sh600519").unwrap());
+        assert_eq!(
+            RecentSymbols::load(path.clone()).list(),
+            vec!["SH600519", "SZ000001"]
+        );
+        std::fs::remove_file(path).ok();
+
+        let args = |line: &str| -> Vec<String> { line.split(' ').map(String::from).collect() };
+        let chart = LaunchAction::OpenChart("SH600519".to_string());
+        assert_eq!(
+            parse_args(&args(&format!("app.exe {}", chart.args()))),
+            Some(chart)
+        );
+        assert_eq!(
+            parse_args(&args("app.exe --quick-action morning_screen")),
+            Some(LaunchAction::Quick(QuickAction::MorningScreen))
+        );
+        assert_eq!(parse_args(&args("app.exe --quick-action trade")), None);
+        assert_eq!(parse_args(&args("app.exe --guest")), None);
+    }
+}
@@ -0,0 +1,330 @@
+//! Reconciliation of the manual ledger against broker snapshots.
+//!
+//! The ledger's positions and cash are rebuilt from its transactions and
+//! compared with the account's last broker sync. Differences are reported
+//! as trades missing on one side, quantity drift and cash mismatches, along
+//! with corrective ledger entries that would bring the two back in line.
+//! Suggestions are never applied automatically; the user reviews them and
+//! records the ones that are right with `add_transaction`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::portfolio::{
+    BrokerAccount, PortfolioStore, Transaction, TransactionEntry, TransactionKind,
+};
+use crate::privacy::Privacy;
+
+/// Quantities closer than this are considered equal
+const QUANTITY_TOLERANCE: f64 = 1e-6;
+/// Cash differences below one cent are rounding
+const CASH_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionDrift {
+    pub symbol: String,
+    pub ledger_quantity: f64,
+    pub broker_quantity: f64,
+    /// Broker minus ledger
+    pub difference: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CashDrift {
+    pub currency: String,
+    pub ledger_cash: f64,
+    pub broker_cash: f64,
+    /// Broker minus ledger
+    pub difference: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReconciliationReport {
+    pub account_id: String,
+    pub broker: String,
+    pub synced_at: String,
+    /// Positions only one side knows about
+    pub missing_trades: Vec<PositionDrift>,
+    /// Positions both sides hold in different sizes
+    pub quantity_drift: Vec<PositionDrift>,
+    pub cash_mismatches: Vec<CashDrift>,
+    /// Ledger entries that would clear every difference
+    pub suggested_entries: Vec<TransactionEntry>,
+    pub reconciled: bool,
+}
+
+#[derive(Default)]
+struct LedgerPosition {
+    quantity: f64,
+    bought: f64,
+    cost: f64,
+    currency: String,
+}
+
+impl LedgerPosition {
+    fn average_cost(&self) -> f64 {
+        if self.bought > 0.0 {
+            self.cost / self.bought
+        } else {
+            0.0
+        }
+    }
+}
+
+pub fn reconcile(
+    account: &BrokerAccount,
+    transactions: &[Transaction],
+    today: &str,
+) -> ReconciliationReport {
+    let mut positions: BTreeMap<String, LedgerPosition> = BTreeMap::new();
+    let mut cash: BTreeMap<String, f64> = BTreeMap::new();
+    for entry in transactions
+        .iter()
+        .map(|t| &t.entry)
+        .filter(|e| e.account_id == account.account_id)
+    {
+        *cash.entry(entry.currency.clone()).or_default() += entry.cash_effect();
+        if entry.kind.is_trade() {
+            let position = positions.entry(entry.symbol.clone()).or_default();
+            position.quantity += entry.quantity_effect();
+            position.currency = entry.currency.clone();
+            if entry.kind == TransactionKind::Buy {
+                position.bought += entry.quantity;
+                position.cost += entry.quantity * entry.price;
+            }
+        }
+    }
+
+    let broker: BTreeMap<&str, _> = account
+        .positions
+        .iter()
+        .map(|h| (h.symbol.as_str(), h))
+        .collect();
+    let symbols: BTreeSet<String> = positions
+        .keys()
+        .cloned()
+        .chain(broker.keys().map(|s| s.to_string()))
+        .collect();
+
+    let mut missing_trades = Vec::new();
+    let mut quantity_drift = Vec::new();
+    let mut suggested_entries = Vec::new();
+    for symbol in symbols {
+        let ledger = positions.get(&symbol);
+        let held = broker.get(symbol.as_str());
+        let ledger_quantity = ledger.map_or(0.0, |p| p.quantity);
+        let broker_quantity = held.map_or(0.0, |h| h.quantity);
+        let difference = broker_quantity - ledger_quantity;
+        if difference.abs() < QUANTITY_TOLERANCE {
+            continue;
+        }
+        let drift = PositionDrift {
+            symbol: symbol.clone(),
+            ledger_quantity,
+            broker_quantity,
+            difference,
+        };
+        if ledger_quantity.abs() < QUANTITY_TOLERANCE || broker_quantity.abs() < QUANTITY_TOLERANCE
+        {
+            missing_trades.push(drift);
+        } else {
+            quantity_drift.push(drift);
+        }
+        let (price, currency) = match (held, ledger) {
+            (Some(h), _) => (h.cost_price, h.currency.clone()),
+            (None, Some(p)) => (p.average_cost(), p.currency.clone()),
+            (None, None) => unreachable!("symbol comes from one of the two sides"),
+        };
+        suggested_entries.push(TransactionEntry {
+            account_id: account.account_id.clone(),
+            kind: if difference > 0.0 {
+                TransactionKind::Buy
+            } else {
+                TransactionKind::Sell
+            },
+            symbol,
+            quantity: difference.abs(),
+            price,
+            amount: 0.0,
+            fee: 0.0,
+            currency,
+            date: today.to_string(),
+            note: format!(
+                "Reconciliation with {} sync of {}",
+                account.broker, account.synced_at
+            ),
+            portfolio_id: None,
+        });
+    }
+
+    // Cash is compared after the suggested trades so the two corrections add up
+    for entry in &suggested_entries {
+        *cash.entry(entry.currency.clone()).or_default() += entry.cash_effect();
+    }
+    let currencies: BTreeSet<String> = cash
+        .keys()
+        .cloned()
+        .chain(account.balances.iter().map(|b| b.currency.clone()))
+        .collect();
+    let mut cash_mismatches = Vec::new();
+    for currency in currencies {
+        let ledger_cash = cash.get(&currency).copied().unwrap_or(0.0);
+        let broker_cash = account
+            .balances
+            .iter()
+            .find(|b| b.currency == currency)
+            .map_or(0.0, |b| b.cash);
+        let difference = broker_cash - ledger_cash;
+        if difference.abs() < CASH_TOLERANCE {
+            continue;
+        }
+        suggested_entries.push(TransactionEntry {
+            account_id: account.account_id.clone(),
+            kind: if difference > 0.0 {
+                TransactionKind::Deposit
+            } else {
+                TransactionKind::Withdrawal
+            },
+            symbol: String::new(),
+            quantity: 0.0,
+            price: 0.0,
+            amount: difference.abs(),
+            fee: 0.0,
+            currency: currency.clone(),
+            date: today.to_string(),
+            note: format!(
+                "Cash adjustment to {} sync of {}",
+                account.broker, account.synced_at
+            ),
+            portfolio_id: None,
+        });
+        cash_mismatches.push(CashDrift {
+            currency,
+            ledger_cash,
+            broker_cash,
+            difference,
+        });
+    }
+
+    ReconciliationReport {
+        account_id: account.account_id.clone(),
+        broker: account.broker.clone(),
+        synced_at: account.synced_at.clone(),
+        reconciled: suggested_entries.is_empty(),
+        missing_trades,
+        quantity_drift,
+        cash_mismatches,
+        suggested_entries,
+    }
+}
+
+/// Compare an account's manual ledger with its last broker sync
+#[tauri::command]
+pub fn reconcile_account(
+    store: State<'_, PortfolioStore>,
+    privacy: State<'_, Privacy>,
+    account_id: String,
+) -> Result<ReconciliationReport, String> {
+    let portfolio = store.get();
+    let account = portfolio
+        .broker_accounts
+        .iter()
+        .find(|a| a.account_id == account_id)
+        .ok_or_else(|| format!("Account {} has not been synced from a broker", account_id))?;
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    privacy.apply(reconcile(account, &portfolio.transactions, &today))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::{CashBalance, Holding};
+
+    fn trade(kind: TransactionKind, symbol: &str, quantity: f64, price: f64) -> Transaction {
+        Transaction {
+            id: format!("txn-{}-{}", symbol, quantity),
+            entry: TransactionEntry {
+                account_id: "U1".to_string(),
+                kind,
+                symbol: symbol.to_string(),
+                quantity,
+                price,
+                amount: 0.0,
+                fee: 0.0,
+                currency: "USD".to_string(),
+                date: "2026-01-05".to_string(),
+                note: String::new(),
+                portfolio_id: None,
+            },
+            recorded_at: "t".to_string(),
+        }
+    }
+
+    fn holding(symbol: &str, quantity: f64, cost_price: f64) -> Holding {
+        Holding {
+            id: format!("ibkr-{}", symbol),
+            symbol: symbol.to_string(),
+            quantity,
+            cost_price,
+            currency: "USD".to_string(),
+            market_price: None,
+            note: String::new(),
+            updated_at: "t".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_reconciliation_report() {
+        let mut deposit = trade(TransactionKind::Deposit, "", 0.0, 0.0);
+        deposit.entry.amount = 10_000.0;
+        let transactions = vec![
+            deposit,
+            trade(TransactionKind::Buy, "AAPL", 10.0, 150.0),
+            trade(TransactionKind::Buy, "MSFT", 5.0, 300.0),
+            trade(TransactionKind::Buy, "TSLA", 2.0, 200.0),
+        ];
+        // Broker: AAPL matches, MSFT has 3 more, TSLA was sold, NVDA is unknown to the ledger
+        let account = BrokerAccount {
+            broker: "ibkr".to_string(),
+            account_id: "U1".to_string(),
+            positions: vec![
+                holding("AAPL", 10.0, 150.0),
+                holding("MSFT", 8.0, 300.0),
+                holding("NVDA", 4.0, 100.0),
+            ],
+            balances: vec![CashBalance {
+                currency: "USD".to_string(),
+                cash: 6_000.0,
+                market_value: 0.0,
+                net_liquidation: 0.0,
+            }],
+            synced_at: "s".to_string(),
+        };
+
+        let report = reconcile(&account, &transactions, "2026-02-01");
+        assert!(!report.reconciled);
+        let missing: Vec<&str> = report
+            .missing_trades
+            .iter()
+            .map(|d| d.symbol.as_str())
+            .collect();
+        assert_eq!(missing, vec!["NVDA", "TSLA"]);
+        assert_eq!(report.quantity_drift.len(), 1);
+        assert_eq!(report.quantity_drift[0].difference, 3.0);
+
+        // Ledger cash 10000 - 1500 - 1500 - 400 = 6600; suggested MSFT buy, NVDA buy
+        // and TSLA sell move it to 6600 - 900 - 400 + 400 = 5700
+        assert_eq!(report.cash_mismatches.len(), 1);
+        assert!((report.cash_mismatches[0].difference - 300.0).abs() < 1e-9);
+
+        let mut applied = transactions.clone();
+        applied.extend(report.suggested_entries.iter().map(|entry| Transaction {
+            id: "fix".to_string(),
+            entry: entry.clone(),
+            recorded_at: "t".to_string(),
+        }));
+        assert!(reconcile(&account, &applied, "2026-02-01").reconciled);
+    }
+}
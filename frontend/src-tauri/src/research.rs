@@ -0,0 +1,232 @@
+//! Research questions answered from the user's own data.
+//!
+//! Before asking, the passages most relevant to the question are retrieved,
+//! semantically when the embedding index is built and by keyword otherwise,
+//! together with recent news and the latest prices of the symbol asked
+//! about. They are handed to the model as numbered sources it has to cite,
+//! and the answer is recorded with them in the [`AnswerStore`] so every
+//! claim can be traced back with `get_answer_sources`.
+
+use chrono::TimeZone;
+use chrono_tz::Asia::Shanghai;
+use log::{info, warn};
+use tauri::{AppHandle, Manager};
+
+use crate::ai::{self, AiTask};
+use crate::answers::{number_sources, Answer, AnswerStore, Grounding};
+use crate::columnar::ColumnarStore;
+use crate::documents::DocumentStore;
+use crate::embeddings;
+use crate::news::NewsStore;
+use crate::snapshot::SnapshotClock;
+use crate::utils::{generate_id, get_timestamp};
+
+/// Document passages given to the model per question
+const PASSAGE_LIMIT: usize = 6;
+
+/// Recent headlines given to the model per question
+const NEWS_LIMIT: usize = 5;
+
+const SYSTEM_PROMPT: &str = "你是一名证券研究助理。只根据提供的编号资料回答问题，\
+每个事实陈述后用 [编号] 标注出处；资料不足时直接说明，不要编造数据。";
+
+/// Latest close and daily change of a symbol from the local daily bars
+fn price_data(store: &ColumnarStore, symbol: &str) -> Vec<Grounding> {
+    let Ok(bars) = store.open(symbol, "1d") else {
+        return Vec::new();
+    };
+    let closes = bars.closes();
+    let Some(&close) = closes.last() else {
+        return Vec::new();
+    };
+    let as_of = Shanghai
+        .timestamp_opt(bars.timestamps()[closes.len() - 1], 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    let mut data = vec![Grounding::Data {
+        label: "收盘价".to_string(),
+        value: close,
+        symbol: Some(symbol.to_string()),
+        as_of: as_of.clone(),
+    }];
+    if let Some(&previous) = closes.len().checked_sub(2).and_then(|i| closes.get(i)) {
+        if previous > 0.0 {
+            data.push(Grounding::Data {
+                label: "日涨跌幅(%)".to_string(),
+                value: ((close / previous - 1.0) * 10_000.0).round() / 100.0,
+                symbol: Some(symbol.to_string()),
+                as_of,
+            });
+        }
+    }
+    data
+}
+
+/// Passages for the question, semantically when the index allows
+async fn passages(app: &AppHandle, question: &str, symbol: Option<&str>) -> Vec<Grounding> {
+    match embeddings::search(app, question, symbol, PASSAGE_LIMIT).await {
+        Ok(hits) if !hits.is_empty() => hits
+            .into_iter()
+            .map(|hit| Grounding::Chunk {
+                document_id: hit.document_id,
+                chunk_index: hit.chunk_index,
+                title: hit.title,
+                quote: hit.text,
+                start_ms: hit.start_ms,
+            })
+            .collect(),
+        result => {
+            if let Err(e) = result {
+                warn!("Semantic search unavailable, using keyword search: {}", e);
+            }
+            app.state::<DocumentStore>()
+                .search(question, symbol, PASSAGE_LIMIT)
+                .into_iter()
+                .map(|hit| Grounding::Chunk {
+                    document_id: hit.document_id,
+                    chunk_index: hit.chunk_index,
+                    title: hit.title,
+                    quote: hit.text,
+                    start_ms: hit.start_ms,
+                })
+                .collect()
+        }
+    }
+}
+
+/// The sources as numbered in the prompt
+fn render_sources(groundings: &[Grounding]) -> String {
+    groundings
+        .iter()
+        .enumerate()
+        .map(|(i, grounding)| {
+            let body = match grounding {
+                Grounding::Chunk { title, quote, .. } => format!("《{}》：{}", title, quote),
+                Grounding::News { title, .. } => format!("新闻：{}", title),
+                Grounding::Data {
+                    label,
+                    value,
+                    symbol,
+                    as_of,
+                } => format!(
+                    "{} {}（{}）：{}",
+                    symbol.as_deref().unwrap_or(""),
+                    label,
+                    as_of,
+                    value
+                ),
+            };
+            format!("[{}] {}", i + 1, body)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Answer a research question from the stored documents, news and prices, citing sources.
+/// `model` names a configured model to use instead of the routed one.
+#[tauri::command]
+pub async fn ask_research(
+    app: AppHandle,
+    question: String,
+    symbol: Option<String>,
+    model: Option<String>,
+) -> Result<Answer, String> {
+    let question = question.trim().to_string();
+    if question.is_empty() {
+        return Err("Question is required".to_string());
+    }
+    let symbol = symbol.map(|s| s.trim().to_uppercase());
+
+    let mut groundings = passages(&app, &question, symbol.as_deref()).await;
+    if let Some(symbol) = &symbol {
+        groundings.extend(
+            app.state::<NewsStore>()
+                .for_symbol(symbol, app.state::<SnapshotClock>().frozen_at(), NEWS_LIMIT)
+                .into_iter()
+                .map(|item| Grounding::News {
+                    news_id: item.id,
+                    title: item.title,
+                    url: item.url,
+                    published_at: item.published_at,
+                }),
+        );
+        groundings.extend(price_data(&app.state::<ColumnarStore>(), symbol));
+    }
+
+    let prompt = format!(
+        "资料：\n{}\n\n问题：{}",
+        render_sources(&groundings),
+        question
+    );
+    let completion = ai::complete(
+        &app,
+        AiTask::ResearchQuestion,
+        SYSTEM_PROMPT,
+        &prompt,
+        model.as_deref(),
+    )
+    .await?;
+    let text = completion.text;
+
+    let answer = Answer {
+        id: generate_id("answer"),
+        kind: "research_question".to_string(),
+        prompt: question,
+        sources: number_sources(groundings, &text),
+        text,
+        model: completion.model,
+        symbol,
+        created_at: get_timestamp(),
+    };
+    app.state::<AnswerStore>().record(answer.clone())?;
+    info!(
+        "Answered research question {} from {} sources",
+        answer.id,
+        answer.sources.len()
+    );
+    Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Bar;
+
+    #[test]
+    fn test_price_grounding_and_rendering() {
+        let root = std::env::temp_dir().join(format!("ssi-research-{}", std::process::id()));
+        let store = ColumnarStore::new(root.clone());
+        let bar = |timestamp: i64, close: f64| Bar {
+            timestamp,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+        };
+        // 2025-06-02 and 2025-06-03, 00:00 Beijing time
+        store
+            .write(
+                "SH600519",
+                "1d",
+                &[bar(1_748_793_600, 1600.0), bar(1_748_880_000, 1640.0)],
+            )
+            .unwrap();
+        let data = price_data(&store, "SH600519");
+        assert_eq!(
+            data[1],
+            Grounding::Data {
+                label: "日涨跌幅(%)".to_string(),
+                value: 2.5,
+                symbol: Some("SH600519".to_string()),
+                as_of: "2025-06-03".to_string(),
+            }
+        );
+        assert!(price_data(&store, "SZ000001").is_empty());
+
+        let rendered = render_sources(&data[..1]);
+        assert_eq!(rendered, "[1] SH600519 收盘价（2025-06-03）：1640");
+        std::fs::remove_dir_all(root).ok();
+    }
+}
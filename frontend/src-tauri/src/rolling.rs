@@ -0,0 +1,440 @@
+//! Rolling-window primitives used by indicator, screener and backtest code.
+//!
+//! Every function returns a vector aligned with its input, holding `NaN`
+//! until the first full window. Windows are evaluated from van Herk/Gil-Werman
+//! block scans: running aggregates from both ends of each `window`-sized
+//! block, so any window is the combination of one suffix and one prefix. That
+//! turns the per-element work into independent lane-wise operations, and for
+//! sums it bounds rounding error by the window contents instead of letting it
+//! drift with the whole history as global prefix sums would. With the
+//! `simd` feature enabled on x86_64, those lane-wise passes run on AVX when
+//! the CPU supports it; otherwise the scalar loops below are used.
+//!
+//! This module is self-contained so the criterion benches can include it.
+
+/// Rolling arithmetic mean
+pub fn rolling_mean(values: &[f64], window: usize) -> Vec<f64> {
+    let mut out = vec![f64::NAN; values.len()];
+    if window == 0 || values.len() < window {
+        return out;
+    }
+    let (suffix, prefix) = block_sums(values, window, |v| v);
+    kernels::window_mean(&suffix, &prefix, window, &mut out[window - 1..]);
+    out
+}
+
+/// Rolling population standard deviation
+pub fn rolling_std(values: &[f64], window: usize) -> Vec<f64> {
+    let mut out = vec![f64::NAN; values.len()];
+    if window == 0 || values.len() < window {
+        return out;
+    }
+    // Variance is shift-invariant; centering first limits cancellation in
+    // E[x^2] - E[x]^2
+    let shift = values.iter().sum::<f64>() / values.len() as f64;
+    let (suffix, prefix) = block_sums(values, window, |v| v - shift);
+    let (suffix_sq, prefix_sq) = block_sums(values, window, |v| (v - shift) * (v - shift));
+    kernels::window_std(
+        [&suffix, &prefix, &suffix_sq, &prefix_sq],
+        window,
+        &mut out[window - 1..],
+    );
+    out
+}
+
+/// Rolling maximum
+pub fn rolling_max(values: &[f64], window: usize) -> Vec<f64> {
+    rolling_extreme(values, window, f64::max, kernels::pairwise_max)
+}
+
+/// Rolling minimum
+pub fn rolling_min(values: &[f64], window: usize) -> Vec<f64> {
+    rolling_extreme(values, window, f64::min, kernels::pairwise_min)
+}
+
+/// Exponential moving average seeded with the mean of the first window
+pub fn ema(values: &[f64], period: usize) -> Vec<f64> {
+    let mut out = vec![f64::NAN; values.len()];
+    if period == 0 || values.len() < period {
+        return out;
+    }
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut prev = values[..period].iter().sum::<f64>() / period as f64;
+    out[period - 1] = prev;
+    for i in period..values.len() {
+        prev += alpha * (values[i] - prev);
+        out[i] = prev;
+    }
+    out
+}
+
+/// EMA over many series at once.
+///
+/// EMA is a serial recurrence within a series, so the vectorized path runs
+/// four equal-length series side by side in the SIMD lanes instead.
+pub fn ema_many(series: &[&[f64]], period: usize) -> Vec<Vec<f64>> {
+    let mut out = Vec::with_capacity(series.len());
+    for chunk in series.chunks(4) {
+        match kernels::ema_x4(chunk, period) {
+            Some(lanes) => out.extend(lanes),
+            None => out.extend(chunk.iter().map(|s| ema(s, period))),
+        }
+    }
+    out
+}
+
+/// Block scans of `f(v)`: returns `(suffix, prefix)` such that the sum over
+/// the window starting at `j` is `suffix[j] + prefix[j + window - 1]`
+fn block_sums(values: &[f64], window: usize, f: impl Fn(f64) -> f64) -> (Vec<f64>, Vec<f64>) {
+    let n = values.len();
+    let mut prefix: Vec<f64> = values.iter().map(|&v| f(v)).collect();
+    let mut suffix = prefix.clone();
+    for i in 1..n {
+        if i % window != 0 {
+            prefix[i] += prefix[i - 1];
+        }
+    }
+    for i in (0..n - 1).rev() {
+        if (i + 1) % window != 0 {
+            suffix[i] += suffix[i + 1];
+        }
+    }
+    // A window aligned with a block is covered by the suffix alone, and
+    // those windows end exactly on the last element of a block
+    for i in (window - 1..n).step_by(window) {
+        prefix[i] = 0.0;
+    }
+    let count = n - window + 1;
+    suffix.truncate(count);
+    prefix.drain(..window - 1);
+    (suffix, prefix)
+}
+
+fn rolling_extreme(
+    values: &[f64],
+    window: usize,
+    pick: fn(f64, f64) -> f64,
+    combine: fn(&[f64], &[f64], &mut [f64]),
+) -> Vec<f64> {
+    let n = values.len();
+    let mut out = vec![f64::NAN; n];
+    if window == 0 || n < window {
+        return out;
+    }
+
+    // Block-wise running extremes from the left (g) and from the right (h)
+    let mut g = values.to_vec();
+    let mut h = values.to_vec();
+    for i in 1..n {
+        if i % window != 0 {
+            g[i] = pick(g[i - 1], values[i]);
+        }
+    }
+    for i in (0..n - 1).rev() {
+        if (i + 1) % window != 0 {
+            h[i] = pick(h[i + 1], values[i]);
+        }
+    }
+
+    // The window [j, j + window) spans at most two blocks
+    let count = n - window + 1;
+    combine(&h[..count], &g[window - 1..], &mut out[window - 1..]);
+    out
+}
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+mod kernels {
+    pub use super::scalar::*;
+
+    pub fn ema_x4(_chunk: &[&[f64]], _period: usize) -> Option<Vec<Vec<f64>>> {
+        None
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod kernels {
+    use super::{avx, scalar};
+
+    pub fn window_mean(suffix: &[f64], prefix: &[f64], window: usize, out: &mut [f64]) {
+        if is_x86_feature_detected!("avx") {
+            // SAFETY: AVX support was checked at runtime.
+            unsafe { avx::window_mean(suffix, prefix, window, out) }
+        } else {
+            scalar::window_mean(suffix, prefix, window, out)
+        }
+    }
+
+    pub fn window_std(sums: [&[f64]; 4], window: usize, out: &mut [f64]) {
+        if is_x86_feature_detected!("avx") {
+            // SAFETY: AVX support was checked at runtime.
+            unsafe { avx::window_std(sums, window, out) }
+        } else {
+            scalar::window_std(sums, window, out)
+        }
+    }
+
+    pub fn pairwise_max(a: &[f64], b: &[f64], out: &mut [f64]) {
+        if is_x86_feature_detected!("avx") {
+            // SAFETY: AVX support was checked at runtime.
+            unsafe { avx::pairwise_max(a, b, out) }
+        } else {
+            scalar::pairwise_max(a, b, out)
+        }
+    }
+
+    pub fn pairwise_min(a: &[f64], b: &[f64], out: &mut [f64]) {
+        if is_x86_feature_detected!("avx") {
+            // SAFETY: AVX support was checked at runtime.
+            unsafe { avx::pairwise_min(a, b, out) }
+        } else {
+            scalar::pairwise_min(a, b, out)
+        }
+    }
+
+    pub fn ema_x4(chunk: &[&[f64]], period: usize) -> Option<Vec<Vec<f64>>> {
+        let len = chunk.first()?.len();
+        if chunk.len() != 4 || period == 0 || len < period || chunk.iter().any(|s| s.len() != len) {
+            return None;
+        }
+        if !is_x86_feature_detected!("avx") {
+            return None;
+        }
+        // SAFETY: AVX support was checked and all four lanes share a length.
+        Some(unsafe { avx::ema_x4([chunk[0], chunk[1], chunk[2], chunk[3]], period) })
+    }
+}
+
+#[cfg_attr(all(feature = "simd", target_arch = "x86_64"), allow(dead_code))]
+mod scalar {
+    pub fn window_mean(suffix: &[f64], prefix: &[f64], window: usize, out: &mut [f64]) {
+        let scale = 1.0 / window as f64;
+        for (i, o) in out.iter_mut().enumerate() {
+            *o = (suffix[i] + prefix[i]) * scale;
+        }
+    }
+
+    pub fn window_std(sums: [&[f64]; 4], window: usize, out: &mut [f64]) {
+        let [suffix, prefix, suffix_sq, prefix_sq] = sums;
+        let scale = 1.0 / window as f64;
+        for (i, o) in out.iter_mut().enumerate() {
+            let mean = (suffix[i] + prefix[i]) * scale;
+            let mean_sq = (suffix_sq[i] + prefix_sq[i]) * scale;
+            *o = (mean_sq - mean * mean).max(0.0).sqrt();
+        }
+    }
+
+    pub fn pairwise_max(a: &[f64], b: &[f64], out: &mut [f64]) {
+        for ((o, x), y) in out.iter_mut().zip(a).zip(b) {
+            *o = x.max(*y);
+        }
+    }
+
+    pub fn pairwise_min(a: &[f64], b: &[f64], out: &mut [f64]) {
+        for ((o, x), y) in out.iter_mut().zip(a).zip(b) {
+            *o = x.min(*y);
+        }
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod avx {
+    use std::arch::x86_64::*;
+
+    use super::scalar;
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn window_mean(suffix: &[f64], prefix: &[f64], window: usize, out: &mut [f64]) {
+        let n = out.len();
+        let scale = _mm256_set1_pd(1.0 / window as f64);
+        let mut i = 0;
+        while i + 4 <= n {
+            let s = _mm256_loadu_pd(suffix.as_ptr().add(i));
+            let p = _mm256_loadu_pd(prefix.as_ptr().add(i));
+            _mm256_storeu_pd(
+                out.as_mut_ptr().add(i),
+                _mm256_mul_pd(_mm256_add_pd(s, p), scale),
+            );
+            i += 4;
+        }
+        scalar::window_mean(&suffix[i..], &prefix[i..], window, &mut out[i..]);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn window_std(sums: [&[f64]; 4], window: usize, out: &mut [f64]) {
+        let [suffix, prefix, suffix_sq, prefix_sq] = sums;
+        let n = out.len();
+        let scale = _mm256_set1_pd(1.0 / window as f64);
+        let zero = _mm256_setzero_pd();
+        let mut i = 0;
+        while i + 4 <= n {
+            let sum = _mm256_add_pd(
+                _mm256_loadu_pd(suffix.as_ptr().add(i)),
+                _mm256_loadu_pd(prefix.as_ptr().add(i)),
+            );
+            let sum_sq = _mm256_add_pd(
+                _mm256_loadu_pd(suffix_sq.as_ptr().add(i)),
+                _mm256_loadu_pd(prefix_sq.as_ptr().add(i)),
+            );
+            let mean = _mm256_mul_pd(sum, scale);
+            let mean_sq = _mm256_mul_pd(sum_sq, scale);
+            let var = _mm256_max_pd(_mm256_sub_pd(mean_sq, _mm256_mul_pd(mean, mean)), zero);
+            _mm256_storeu_pd(out.as_mut_ptr().add(i), _mm256_sqrt_pd(var));
+            i += 4;
+        }
+        let tail = [&suffix[i..], &prefix[i..], &suffix_sq[i..], &prefix_sq[i..]];
+        scalar::window_std(tail, window, &mut out[i..]);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn pairwise_max(a: &[f64], b: &[f64], out: &mut [f64]) {
+        let n = out.len();
+        let mut i = 0;
+        while i + 4 <= n {
+            let x = _mm256_loadu_pd(a.as_ptr().add(i));
+            let y = _mm256_loadu_pd(b.as_ptr().add(i));
+            _mm256_storeu_pd(out.as_mut_ptr().add(i), _mm256_max_pd(x, y));
+            i += 4;
+        }
+        scalar::pairwise_max(&a[i..], &b[i..], &mut out[i..]);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn pairwise_min(a: &[f64], b: &[f64], out: &mut [f64]) {
+        let n = out.len();
+        let mut i = 0;
+        while i + 4 <= n {
+            let x = _mm256_loadu_pd(a.as_ptr().add(i));
+            let y = _mm256_loadu_pd(b.as_ptr().add(i));
+            _mm256_storeu_pd(out.as_mut_ptr().add(i), _mm256_min_pd(x, y));
+            i += 4;
+        }
+        scalar::pairwise_min(&a[i..], &b[i..], &mut out[i..]);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn ema_x4(lanes: [&[f64]; 4], period: usize) -> Vec<Vec<f64>> {
+        let len = lanes[0].len();
+        let mut out = vec![vec![f64::NAN; len]; 4];
+        let alpha = _mm256_set1_pd(2.0 / (period as f64 + 1.0));
+
+        let seed: [f64; 4] = std::array::from_fn(|l| lanes[l][..period].iter().sum::<f64>() / period as f64);
+        let mut prev = _mm256_loadu_pd(seed.as_ptr());
+        let mut buf = [0.0f64; 4];
+        _mm256_storeu_pd(buf.as_mut_ptr(), prev);
+        for l in 0..4 {
+            out[l][period - 1] = buf[l];
+        }
+
+        for i in period..len {
+            let x = _mm256_set_pd(lanes[3][i], lanes[2][i], lanes[1][i], lanes[0][i]);
+            prev = _mm256_add_pd(prev, _mm256_mul_pd(alpha, _mm256_sub_pd(x, prev)));
+            _mm256_storeu_pd(buf.as_mut_ptr(), prev);
+            for l in 0..4 {
+                out[l][i] = buf[l];
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(values: &[f64], window: usize, f: impl Fn(&[f64]) -> f64) -> Vec<f64> {
+        (0..values.len())
+            .map(|i| {
+                if i + 1 < window {
+                    f64::NAN
+                } else {
+                    f(&values[i + 1 - window..=i])
+                }
+            })
+            .collect()
+    }
+
+    fn assert_close(a: &[f64], b: &[f64], tolerance: f64) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x.is_nan() && y.is_nan()) || (x - y).abs() < tolerance, "{} != {}", x, y);
+        }
+    }
+
+    fn sample() -> Vec<f64> {
+        (0..103).map(|i| 10.0 + ((i * 37) % 23) as f64 * 0.37).collect()
+    }
+
+    #[test]
+    fn test_rolling_matches_naive() {
+        let values = sample();
+        for window in [1, 3, 7, 20] {
+            let mean = |w: &[f64]| w.iter().sum::<f64>() / w.len() as f64;
+            assert_close(&rolling_mean(&values, window), &naive(&values, window, mean), 1e-9);
+            assert_close(
+                &rolling_std(&values, window),
+                &naive(&values, window, |w| {
+                    let m = mean(w);
+                    (w.iter().map(|v| (v - m).powi(2)).sum::<f64>() / w.len() as f64).sqrt()
+                }),
+                // sqrt amplifies rounding when the true variance is ~0
+                1e-6,
+            );
+            assert_close(
+                &rolling_max(&values, window),
+                &naive(&values, window, |w| w.iter().cloned().fold(f64::MIN, f64::max)),
+                1e-9,
+            );
+            assert_close(
+                &rolling_min(&values, window),
+                &naive(&values, window, |w| w.iter().cloned().fold(f64::MAX, f64::min)),
+                1e-9,
+            );
+        }
+    }
+
+    #[test]
+    fn test_ema_many_matches_single() {
+        let a = sample();
+        let b: Vec<f64> = a.iter().map(|v| v * 2.0).collect();
+        let series: Vec<&[f64]> = vec![&a, &b, &a, &b, &a];
+        let many = ema_many(&series, 12);
+        assert_eq!(many.len(), 5);
+        for (s, e) in series.iter().zip(&many) {
+            assert_close(e, &ema(s, 12), 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_mean_recovers_after_outlier() {
+        // Prefix sums over the whole history would carry the outlier's
+        // rounding error into every later window
+        let mut values = vec![1e15];
+        values.extend(sample());
+        let mean = |w: &[f64]| w.iter().sum::<f64>() / w.len() as f64;
+        let expected = naive(&values, 5, mean);
+        assert_close(&rolling_mean(&values, 5)[5..], &expected[5..], 1e-9);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_rolling_invariants(
+            values in proptest::collection::vec(-1e6f64..1e6, 1..400),
+            window in 1usize..50,
+        ) {
+            let (mean, std) = (rolling_mean(&values, window), rolling_std(&values, window));
+            let (max, min) = (rolling_max(&values, window), rolling_min(&values, window));
+            // Floating-point error scales with the magnitude of the series
+            let scale = values.iter().fold(1.0f64, |m, v| m.max(v.abs()));
+            for i in 0..values.len() {
+                if i + 1 < window {
+                    proptest::prop_assert!(mean[i].is_nan() && max[i].is_nan());
+                    continue;
+                }
+                let tolerance = 1e-6 * scale;
+                proptest::prop_assert!(min[i] <= max[i]);
+                proptest::prop_assert!(mean[i] >= min[i] - tolerance && mean[i] <= max[i] + tolerance);
+                proptest::prop_assert!(std[i] >= 0.0 && std[i] <= (max[i] - min[i]) + tolerance);
+            }
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Background jobs run on cron schedules.
+//!
+//! Each [`Job`] has a five-field cron expression in local time (minute,
+//! hour, day of month, month, weekday with 0 for Sunday) and can be turned
+//! off or limited to A-share trading days; settings keep only the jobs the
+//! user changed, the rest run on their defaults. The scheduler checks every
+//! [`TICK`] and runs a job once its next time has passed, so a job due while
+//! the machine slept runs once on waking rather than once per missed time.
+//! [`run_scheduled_job`] runs one now, whatever its schedule.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use croner::Cron;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::calendar::{self, Locale};
+use crate::db::{Alerts, Database, Notifications, Watchlists};
+use crate::executor::Priority;
+use crate::housekeeping;
+use crate::kline::{self, Adjust, KlinePeriod};
+use crate::models::Market;
+use crate::notifications::{self, Notification, NotificationCategory};
+use crate::portfolio::PortfolioStore;
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::utils::{get_app_data_dir, get_timestamp, write_to_file};
+
+/// Time between checks of the schedules
+const TICK: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Job {
+    /// Bring daily bars of watched, held and alerted symbols up to date
+    EodRefresh,
+    /// Keep a copy of the portfolio as it stood that day
+    PortfolioSnapshot,
+    /// Release caches and subscriptions nothing uses
+    CachePruning,
+    /// Notify the day's market status, holdings and alerts
+    MorningBriefing,
+}
+
+impl Job {
+    pub const ALL: [Job; 4] = [
+        Job::EodRefresh,
+        Job::PortfolioSnapshot,
+        Job::CachePruning,
+        Job::MorningBriefing,
+    ];
+
+    fn default_schedule(self) -> JobSchedule {
+        let (cron, trading_days_only) = match self {
+            Job::EodRefresh => ("30 15 * * 1-5", true),
+            Job::PortfolioSnapshot => ("0 16 * * 1-5", true),
+            Job::CachePruning => ("0 3 * * *", false),
+            Job::MorningBriefing => ("45 8 * * 1-5", true),
+        };
+        JobSchedule {
+            cron: cron.to_string(),
+            enabled: true,
+            trading_days_only,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobSchedule {
+    pub cron: String,
+    pub enabled: bool,
+    /// Skip A-share non-trading days, whatever the expression says
+    #[serde(default)]
+    pub trading_days_only: bool,
+}
+
+fn parse_cron(expression: &str) -> Result<Cron, String> {
+    Cron::new(expression)
+        .parse()
+        .map_err(|e| format!("Invalid cron expression {}: {}", expression, e))
+}
+
+/// Next time after `now` a cron expression matches
+fn next_after(expression: &str, now: &DateTime<Local>) -> Option<DateTime<Local>> {
+    parse_cron(expression)
+        .ok()?
+        .find_next_occurrence(now, false)
+        .ok()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerSettings {
+    /// Jobs whose schedule differs from the default
+    pub jobs: BTreeMap<Job, JobSchedule>,
+}
+
+impl SchedulerSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        self.jobs
+            .values()
+            .try_for_each(|schedule| parse_cron(&schedule.cron).map(drop))
+    }
+
+    pub fn schedule(&self, job: Job) -> JobSchedule {
+        self.jobs
+            .get(&job)
+            .cloned()
+            .unwrap_or_else(|| job.default_schedule())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct JobState {
+    /// Expression `next` was worked out from
+    cron: String,
+    next: Option<DateTime<Local>>,
+    last_run_at: Option<String>,
+    last_result: Option<Result<String, String>>,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Mutex<HashMap<Job, JobState>>,
+    running: Mutex<HashSet<Job>>,
+}
+
+impl Scheduler {
+    /// Jobs whose time has passed, moving each on to its next time
+    fn due(&self, settings: &SchedulerSettings, now: DateTime<Local>) -> Vec<Job> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let mut due = Vec::new();
+        for job in Job::ALL {
+            let schedule = settings.schedule(job);
+            let state = jobs.entry(job).or_default();
+            if state.cron != schedule.cron || state.next.is_none() {
+                state.cron = schedule.cron.clone();
+                state.next = next_after(&schedule.cron, &now);
+                continue;
+            }
+            if state.next.is_some_and(|next| next <= now) {
+                state.next = next_after(&schedule.cron, &now);
+                let trading_day = calendar::is_trading_day(now.date_naive());
+                if schedule.enabled && (trading_day || !schedule.trading_days_only) {
+                    due.push(job);
+                }
+            }
+        }
+        due
+    }
+}
+
+/// Symbols on watchlists, held, or with an enabled alert
+fn tracked_symbols(app: &AppHandle) -> Result<Vec<String>, String> {
+    let mut symbols: Vec<String> = Vec::new();
+    {
+        let db = app.state::<Database>();
+        let conn = db.conn()?;
+        for watchlist in Watchlists(&conn).list()? {
+            symbols.extend(watchlist.items.into_iter().map(|item| item.symbol));
+        }
+        for alert in Alerts(&conn).list(None)? {
+            if alert.enabled {
+                symbols.push(alert.symbol);
+            }
+        }
+    }
+    let portfolio = app.state::<PortfolioStore>().get();
+    symbols.extend(portfolio.manual.into_iter().map(|h| h.symbol));
+    for account in portfolio.broker_accounts {
+        symbols.extend(account.positions.into_iter().map(|h| h.symbol));
+    }
+    symbols.sort();
+    symbols.dedup();
+    Ok(symbols)
+}
+
+async fn refresh_daily_bars(app: &AppHandle) -> Result<String, String> {
+    let symbols = tracked_symbols(app)?;
+    let mut failed = 0;
+    for symbol in &symbols {
+        if let Err(e) = kline::candles(
+            app,
+            symbol,
+            KlinePeriod::Daily,
+            Adjust::None,
+            Priority::Background,
+        )
+        .await
+        {
+            warn!("Failed to refresh daily bars of {}: {}", symbol, e);
+            failed += 1;
+        }
+    }
+    Ok(format!(
+        "Refreshed {} of {} symbols",
+        symbols.len() - failed,
+        symbols.len()
+    ))
+}
+
+fn snapshot_portfolio(app: &AppHandle) -> Result<String, String> {
+    let dir = get_app_data_dir()
+        .ok_or("Failed to resolve app data directory")?
+        .join("portfolio_snapshots");
+    let path = dir.join(format!("{}.json", Local::now().format("%Y-%m-%d")));
+    let content = serde_json::to_string_pretty(&app.state::<PortfolioStore>().get())
+        .map_err(|e| format!("Failed to serialize portfolio: {}", e))?;
+    write_to_file(&path, &content).map_err(|e| format!("Failed to save snapshot: {}", e))?;
+    Ok(format!("Saved {}", path.display()))
+}
+
+fn send_briefing(app: &AppHandle) -> Result<String, String> {
+    let now = Utc::now().with_timezone(&sessions::timezone(Market::Cn));
+    let banner = calendar::banner(now, Locale::Zh);
+    let (alerts, unread) = {
+        let db = app.state::<Database>();
+        let conn = db.conn()?;
+        let alerts = Alerts(&conn)
+            .list(None)?
+            .into_iter()
+            .filter(|alert| alert.enabled)
+            .count();
+        (alerts, Notifications(&conn).unread_count()?)
+    };
+    let portfolio = app.state::<PortfolioStore>().get();
+    let held = portfolio.manual.len()
+        + portfolio
+            .broker_accounts
+            .iter()
+            .map(|account| account.positions.len())
+            .sum::<usize>();
+    let mut lines = Vec::new();
+    lines.extend(banner.greeting);
+    lines.push(banner.message);
+    lines.push(format!("持仓 {} 只，启用提醒 {} 个", held, alerts));
+    if unread > 0 {
+        lines.push(format!("未读通知 {} 条", unread));
+    }
+    notifications::notify(
+        app,
+        Notification {
+            category: NotificationCategory::System,
+            title: format!("早盘简报 {}", now.format("%m月%d日")),
+            body: lines.join("\n"),
+            actions: Vec::new(),
+        },
+    )?;
+    Ok("Briefing sent".to_string())
+}
+
+async fn execute(app: &AppHandle, job: Job) -> Result<String, String> {
+    match job {
+        Job::EodRefresh => refresh_daily_bars(app).await,
+        Job::PortfolioSnapshot => snapshot_portfolio(app),
+        Job::CachePruning => {
+            let report = housekeeping::run(app);
+            Ok(format!(
+                "Released {} subscriptions and {} mappings",
+                report.released_subscriptions, report.released_mappings
+            ))
+        }
+        Job::MorningBriefing => send_briefing(app),
+    }
+}
+
+/// Run a job unless it is already running, recording its outcome
+async fn run(app: &AppHandle, job: Job) -> Result<String, String> {
+    let scheduler = app.state::<Scheduler>();
+    if !scheduler.running.lock().unwrap().insert(job) {
+        return Err(format!("{:?} is already running", job));
+    }
+    info!("Running scheduled job {:?}", job);
+    let result = execute(app, job).await;
+    scheduler.running.lock().unwrap().remove(&job);
+    match &result {
+        Ok(summary) => info!("Scheduled job {:?} finished: {}", job, summary),
+        Err(e) => warn!("Scheduled job {:?} failed: {}", job, e),
+    }
+    let mut jobs = scheduler.jobs.lock().unwrap();
+    let state = jobs.entry(job).or_default();
+    state.last_run_at = Some(get_timestamp());
+    state.last_result = Some(result.clone());
+    result
+}
+
+/// Run jobs as their schedules come due, for as long as the app runs
+pub fn start_scheduler(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let settings = handle.state::<SettingsStore>().get().scheduler;
+            for job in handle.state::<Scheduler>().due(&settings, Local::now()) {
+                let app = handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    run(&app, job).await.ok();
+                });
+            }
+            tokio::time::sleep(TICK).await;
+        }
+    });
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub job: Job,
+    #[serde(flatten)]
+    pub schedule: JobSchedule,
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub last_summary: Option<String>,
+    pub last_error: Option<String>,
+    pub running: bool,
+}
+
+#[tauri::command]
+pub fn list_scheduled_jobs(
+    settings: State<'_, SettingsStore>,
+    scheduler: State<'_, Scheduler>,
+) -> Result<Vec<JobStatus>, String> {
+    let settings = settings.get().scheduler;
+    let jobs = scheduler.jobs.lock().unwrap();
+    let running = scheduler.running.lock().unwrap();
+    let now = Local::now();
+    Ok(Job::ALL
+        .into_iter()
+        .map(|job| {
+            let schedule = settings.schedule(job);
+            let state = jobs.get(&job).cloned().unwrap_or_default();
+            let next = if state.cron == schedule.cron {
+                state.next
+            } else {
+                next_after(&schedule.cron, &now)
+            };
+            JobStatus {
+                job,
+                next_run_at: next
+                    .filter(|_| schedule.enabled)
+                    .map(|next| next.format("%Y-%m-%d %H:%M").to_string()),
+                last_run_at: state.last_run_at,
+                last_summary: state.last_result.clone().and_then(Result::ok),
+                last_error: state.last_result.and_then(Result::err),
+                running: running.contains(&job),
+                schedule,
+            }
+        })
+        .collect())
+}
+
+/// Change a job's cron expression, turn it on or off, or limit it to trading
+/// days; fields left out keep their current value
+#[tauri::command]
+pub fn set_scheduled_job(
+    settings: State<'_, SettingsStore>,
+    job: Job,
+    cron: Option<String>,
+    enabled: Option<bool>,
+    trading_days_only: Option<bool>,
+) -> Result<JobSchedule, String> {
+    let mut updated = settings.get();
+    let mut schedule = updated.scheduler.schedule(job);
+    if let Some(cron) = cron {
+        schedule.cron = cron.trim().to_string();
+    }
+    schedule.enabled = enabled.unwrap_or(schedule.enabled);
+    schedule.trading_days_only = trading_days_only.unwrap_or(schedule.trading_days_only);
+    if schedule == job.default_schedule() {
+        updated.scheduler.jobs.remove(&job);
+    } else {
+        updated.scheduler.jobs.insert(job, schedule.clone());
+    }
+    settings.set(updated)?;
+    info!("Scheduled job {:?} set to {:?}", job, schedule);
+    Ok(schedule)
+}
+
+/// Run a job now, returning its summary
+#[tauri::command]
+pub async fn run_scheduled_job(app: AppHandle, job: Job) -> Result<String, String> {
+    run(&app, job).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_due_jobs() {
+        let at = |d, h, m| Local.with_ymd_and_hms(2025, 6, d, h, m, 0).unwrap();
+        let mut settings = SchedulerSettings::default();
+        assert!(settings.validate().is_ok());
+
+        // The first check only works out when each job is next due
+        let scheduler = Scheduler::default();
+        assert!(scheduler.due(&settings, at(3, 8, 0)).is_empty());
+        assert_eq!(
+            scheduler.due(&settings, at(3, 8, 46)),
+            [Job::MorningBriefing]
+        );
+        assert!(scheduler.due(&settings, at(3, 8, 47)).is_empty());
+        // Waking after several missed times runs each job once
+        assert_eq!(scheduler.due(&settings, at(5, 9, 0)), Job::ALL);
+
+        // Monday 2 June 2025 was the Dragon Boat Festival holiday
+        let scheduler = Scheduler::default();
+        assert!(scheduler.due(&settings, at(1, 12, 0)).is_empty());
+        assert_eq!(scheduler.due(&settings, at(2, 9, 0)), [Job::CachePruning]);
+
+        settings.jobs.insert(
+            Job::CachePruning,
+            JobSchedule {
+                cron: "0 25 * * *".to_string(),
+                enabled: true,
+                trading_days_only: false,
+            },
+        );
+        assert!(settings.validate().is_err());
+    }
+}
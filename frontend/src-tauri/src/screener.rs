@@ -0,0 +1,601 @@
+//! Market screener.
+//!
+//! A screen is a filter expression over fields of a symbol's latest daily
+//! bar, its indicators and its valuation, such as
+//! `pe < 15 && roe > 0.12 && ma20 > ma60`. It runs as a background task
+//! across every symbol with daily bars in the columnar store, spread over the
+//! rayon pool, and reads only what is cached: bars from the columnar store
+//! and valuation figures from cached [financial
+//! statements](crate::financials), so screening the whole market makes no
+//! requests. A comparison involving a field a symbol has no data for is
+//! unknown, and only symbols the expression is definitely true for match.
+//! Each match is emitted as a `screener-match` event as soon as it is found;
+//! the task result lists them all.
+//!
+//! Fields are `open`, `high`, `low`, `close`, `volume`, `change` (percent
+//! against the previous close), `ma<n>`/`sma<n>`, `ema<n>`, `rsi<n>`, the
+//! MACD(12,26,9) lines `dif`, `dea` and `macd`, the KDJ(9,3,3) lines `k`,
+//! `d` and `j`, and `pe`, `pb`, `ps`, `dy` and `roe`, with yields and ROE as
+//! fractions. Expressions combine them with `+ - * /`, comparisons,
+//! `&&`, `||`, `!` and parentheses.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use log::{info, warn};
+use rayon::prelude::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::columnar::{ColumnarStore, MappedBars};
+use crate::db::{Database, Financials};
+use crate::executor::{Priority, ResourceClass};
+use crate::indicators::{IndicatorContext, IndicatorSpec};
+use crate::instruments::InstrumentMaster;
+use crate::kline::{series_interval, Adjust, KlinePeriod};
+use crate::models::Market;
+use crate::sessions;
+use crate::snapshot::SnapshotClock;
+use crate::tasks::{spawn_task_with, TaskContext, TaskHandle};
+use crate::valuation::{self, Snapshot};
+
+/// Longest indicator window a field may name
+const MAX_WINDOW: usize = 250;
+
+const MACD: IndicatorSpec = IndicatorSpec::Macd(12, 26, 9);
+const KDJ: IndicatorSpec = IndicatorSpec::Kdj(9, 3, 3);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+    Change,
+    /// A line of an indicator
+    Line(IndicatorSpec, usize),
+    Pe,
+    Pb,
+    Ps,
+    DividendYield,
+    Roe,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        let field = match name {
+            "open" => Field::Open,
+            "high" => Field::High,
+            "low" => Field::Low,
+            "close" => Field::Close,
+            "volume" => Field::Volume,
+            "change" => Field::Change,
+            "dif" => Field::Line(MACD, 0),
+            "dea" => Field::Line(MACD, 1),
+            "macd" => Field::Line(MACD, 2),
+            "k" => Field::Line(KDJ, 0),
+            "d" => Field::Line(KDJ, 1),
+            "j" => Field::Line(KDJ, 2),
+            "pe" => Field::Pe,
+            "pb" => Field::Pb,
+            "ps" => Field::Ps,
+            "dy" => Field::DividendYield,
+            "roe" => Field::Roe,
+            _ => {
+                let split = name.find(|c: char| c.is_ascii_digit())?;
+                let window: usize = name[split..].parse().ok()?;
+                if !(1..=MAX_WINDOW).contains(&window) {
+                    return None;
+                }
+                let spec = match &name[..split] {
+                    "ma" | "sma" => IndicatorSpec::Sma(window),
+                    "ema" => IndicatorSpec::Ema(window),
+                    "rsi" => IndicatorSpec::Rsi(window),
+                    _ => return None,
+                };
+                Field::Line(spec, 0)
+            }
+        };
+        Some(field)
+    }
+
+    fn fundamental(self) -> bool {
+        matches!(
+            self,
+            Field::Pe | Field::Pb | Field::Ps | Field::DividendYield | Field::Roe
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 16] = [
+    "&&", "||", "<=", ">=", "==", "!=", "<", ">", "!", "+", "-", "*", "/", "(", ")", "=",
+];
+
+/// Tokens of an expression with their character offsets
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let offset = source.len() - rest.len();
+        let c = rest.chars().next().unwrap_or_default();
+        let length = if c.is_ascii_digit() || c == '.' {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..length]
+                .parse()
+                .map_err(|_| format!("Invalid number at {}", offset))?;
+            tokens.push((Token::Number(number), offset));
+            length
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push((Token::Ident(rest[..length].to_lowercase()), offset));
+            length
+        } else {
+            let op = OPERATORS
+                .iter()
+                .copied()
+                .find(|op| rest.starts_with(op))
+                .ok_or_else(|| format!("Unexpected '{}' at {}", c, offset))?;
+            // A lone `=` is taken for the `==` it almost certainly means
+            tokens.push((Token::Op(if op == "=" { "==" } else { op }), offset));
+            op.len()
+        };
+        rest = &rest[length..];
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    And,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    /// Index into the screen's fields
+    Field(usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+fn truth(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl Expr {
+    /// Value of the expression, `None` when it depends on missing data;
+    /// true is any non-zero value
+    fn eval(&self, values: &[Option<f64>]) -> Option<f64> {
+        match self {
+            Expr::Number(n) => Some(*n),
+            Expr::Field(i) => values[*i],
+            Expr::Neg(inner) => inner.eval(values).map(|v| -v),
+            Expr::Not(inner) => inner.eval(values).map(|v| truth(v == 0.0)),
+            Expr::Binary(op, left, right) => {
+                let (l, r) = (left.eval(values), right.eval(values));
+                match op {
+                    // A known false side decides an `&&` even if the other is unknown
+                    BinOp::And if l == Some(0.0) || r == Some(0.0) => Some(0.0),
+                    BinOp::Or if l.is_some_and(|v| v != 0.0) || r.is_some_and(|v| v != 0.0) => {
+                        Some(1.0)
+                    }
+                    BinOp::And | BinOp::Or => l.and(r).map(|_| truth(*op == BinOp::And)),
+                    _ => {
+                        let (l, r) = (l?, r?);
+                        match op {
+                            BinOp::Lt => Some(truth(l < r)),
+                            BinOp::Le => Some(truth(l <= r)),
+                            BinOp::Gt => Some(truth(l > r)),
+                            BinOp::Ge => Some(truth(l >= r)),
+                            BinOp::Eq => Some(truth(l == r)),
+                            BinOp::Ne => Some(truth(l != r)),
+                            BinOp::Add => Some(l + r),
+                            BinOp::Sub => Some(l - r),
+                            BinOp::Mul => Some(l * r),
+                            BinOp::Div => (r != 0.0).then_some(l / r),
+                            BinOp::And | BinOp::Or => unreachable!(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Operators of each binary precedence level, loosest first
+const LEVELS: [&[(&str, BinOp)]; 5] = [
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[
+        ("<", BinOp::Lt),
+        ("<=", BinOp::Le),
+        (">", BinOp::Gt),
+        (">=", BinOp::Ge),
+        ("==", BinOp::Eq),
+        ("!=", BinOp::Ne),
+    ],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul), ("/", BinOp::Div)],
+];
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    source: &'a str,
+    fields: Vec<(String, Field)>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.source.len(), |(_, offset)| *offset)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(next)) if *next == op) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Op(next)) = self.peek() {
+            let Some(&(_, op)) = ops.iter().find(|(symbol, _)| symbol == next) else {
+                break;
+            };
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let offset = self.offset();
+        match self.tokens.get(self.position).cloned() {
+            Some((Token::Number(n), _)) => {
+                self.position += 1;
+                Ok(Expr::Number(n))
+            }
+            Some((Token::Ident(name), _)) => {
+                self.position += 1;
+                let field = Field::parse(&name)
+                    .ok_or_else(|| format!("Unknown field '{}' at {}", name, offset))?;
+                let index = match self.fields.iter().position(|(known, _)| *known == name) {
+                    Some(index) => index,
+                    None => {
+                        self.fields.push((name, field));
+                        self.fields.len() - 1
+                    }
+                };
+                Ok(Expr::Field(index))
+            }
+            Some((Token::Op("("), _)) => {
+                self.position += 1;
+                let inner = self.binary(0)?;
+                if !self.eat(")") {
+                    return Err(format!("Expected ')' at {}", self.offset()));
+                }
+                Ok(inner)
+            }
+            Some((Token::Op(op), _)) => Err(format!("Unexpected '{}' at {}", op, offset)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// A compiled filter expression
+#[derive(Debug, Clone)]
+pub struct Screen {
+    expr: Expr,
+    /// Fields the expression reads, by the name it uses
+    fields: Vec<(String, Field)>,
+}
+
+impl Screen {
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            source,
+            fields: Vec::new(),
+        };
+        if parser.tokens.is_empty() {
+            return Err("Empty expression".to_string());
+        }
+        let expr = parser.binary(0)?;
+        if parser.position < parser.tokens.len() {
+            return Err(format!("Unexpected input at {}", parser.offset()));
+        }
+        Ok(Self {
+            expr,
+            fields: parser.fields,
+        })
+    }
+
+    fn needs_fundamentals(&self) -> bool {
+        self.fields.iter().any(|(_, field)| field.fundamental())
+    }
+
+    fn matches(&self, values: &[Option<f64>]) -> bool {
+        self.expr.eval(values).is_some_and(|v| v != 0.0)
+    }
+}
+
+/// Values of the screen's fields on the last of `rows` bars
+fn field_values(
+    screen: &Screen,
+    bars: &MappedBars,
+    rows: usize,
+    fundamentals: Option<Snapshot>,
+) -> Vec<Option<f64>> {
+    let last = rows - 1;
+    let (closes, highs, lows) = (
+        &bars.closes()[..rows],
+        &bars.highs()[..rows],
+        &bars.lows()[..rows],
+    );
+    let mut indicators = IndicatorContext::with_range(closes, highs, lows);
+    let fundamentals = fundamentals.unwrap_or_default();
+    screen
+        .fields
+        .iter()
+        .map(|(_, field)| match field {
+            Field::Open => Some(bars.opens()[last]),
+            Field::High => Some(highs[last]),
+            Field::Low => Some(lows[last]),
+            Field::Close => Some(closes[last]),
+            Field::Volume => Some(bars.volumes()[last]),
+            Field::Change => last
+                .checked_sub(1)
+                .map(|previous| closes[previous])
+                .filter(|previous| *previous > 0.0)
+                .map(|previous| (closes[last] / previous - 1.0) * 100.0),
+            Field::Line(spec, line) => indicators.line(*spec, *line)[last],
+            Field::Pe => fundamentals.pe_ttm,
+            Field::Pb => fundamentals.pb,
+            Field::Ps => fundamentals.ps_ttm,
+            Field::DividendYield => fundamentals.dividend_yield,
+            Field::Roe => fundamentals.roe,
+        })
+        .collect()
+}
+
+/// A symbol the screen matched, with the values it matched on
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenMatch {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub values: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize)]
+struct MatchEvent<'a> {
+    task_id: &'a str,
+    #[serde(flatten)]
+    hit: &'a ScreenMatch,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScreenResult {
+    pub expression: String,
+    /// Symbols with daily bars to screen
+    pub scanned: usize,
+    /// Sorted by symbol
+    pub matches: Vec<ScreenMatch>,
+}
+
+fn screen_symbol(
+    app: &AppHandle,
+    screen: &Screen,
+    symbol: &str,
+    interval: &str,
+    until: Option<i64>,
+) -> Result<Option<ScreenMatch>, String> {
+    let bars = app.state::<ColumnarStore>().open(symbol, interval)?;
+    let rows = bars.rows_until(until);
+    if rows == 0 {
+        return Ok(None);
+    }
+    let fundamentals = if screen.needs_fundamentals() {
+        let db = app.state::<Database>();
+        let conn = db.conn()?;
+        let cached = Financials(&conn).cached(symbol)?;
+        cached.map(|statements| {
+            let market = Market::of(symbol).unwrap_or(Market::Cn);
+            let day = sessions::trading_day(market, bars.timestamps()[rows - 1]);
+            valuation::snapshot(&statements, day, bars.closes()[rows - 1])
+        })
+    } else {
+        None
+    };
+    let values = field_values(screen, &bars, rows, fundamentals);
+    if !screen.matches(&values) {
+        return Ok(None);
+    }
+    Ok(Some(ScreenMatch {
+        symbol: symbol.to_string(),
+        name: app.state::<InstrumentMaster>().name(symbol),
+        values: screen
+            .fields
+            .iter()
+            .zip(values)
+            .filter_map(|((name, _), value)| Some((name.clone(), value?)))
+            .collect(),
+    }))
+}
+
+/// Screen every symbol with cached daily bars against `expression`.
+///
+/// Runs as a background task; matches arrive as `screener-match` events and
+/// all of them with the `task-finished` event.
+#[tauri::command]
+pub fn run_screen(
+    app: AppHandle,
+    store: State<'_, ColumnarStore>,
+    expression: String,
+) -> Result<TaskHandle, String> {
+    let screen = Screen::compile(&expression)?;
+    let interval = series_interval(KlinePeriod::Daily, Adjust::None);
+    let symbols: Vec<String> = store
+        .series()
+        .into_iter()
+        .filter(|(_, stored)| *stored == interval)
+        .map(|(symbol, _)| symbol)
+        .collect();
+
+    let handle = app.clone();
+    let task_id = spawn_task_with(
+        &app,
+        "screener",
+        ResourceClass::Cpu,
+        Priority::Normal,
+        move |ctx: TaskContext| {
+            let until = handle.state::<SnapshotClock>().frozen_at();
+            let total = symbols.len() as u64;
+            let done = AtomicU64::new(0);
+            let matches = Mutex::new(Vec::new());
+            symbols.par_iter().try_for_each(|symbol| {
+                ctx.checkpoint()?;
+                match screen_symbol(&handle, &screen, symbol, &interval, until) {
+                    Ok(Some(hit)) => {
+                        let event = MatchEvent {
+                            task_id: ctx.task_id(),
+                            hit: &hit,
+                        };
+                        if let Err(e) = handle.emit("screener-match", event) {
+                            warn!("Failed to emit screener-match event: {}", e);
+                        }
+                        matches.lock().unwrap().push(hit);
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Skipped {} in screen: {}", symbol, e),
+                }
+                ctx.report("screen", done.fetch_add(1, Ordering::Relaxed) + 1, total);
+                Ok::<(), String>(())
+            })?;
+            let mut matches = matches.into_inner().unwrap();
+            matches.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            info!(
+                "Screen '{}' matched {} of {} symbols",
+                expression,
+                matches.len(),
+                symbols.len()
+            );
+            Ok(ScreenResult {
+                expression,
+                scanned: symbols.len(),
+                matches,
+            })
+        },
+    );
+    Ok(TaskHandle { task_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, values: &[(&str, Option<f64>)]) -> bool {
+        let screen = Screen::compile(source).unwrap();
+        let values: Vec<Option<f64>> = screen
+            .fields
+            .iter()
+            .map(|(name, _)| {
+                values
+                    .iter()
+                    .find(|(known, _)| name.as_str() == *known)
+                    .and_then(|(_, value)| *value)
+            })
+            .collect();
+        screen.matches(&values)
+    }
+
+    #[test]
+    fn test_compile_and_evaluate() {
+        let values = [
+            ("pe", Some(12.0)),
+            ("roe", Some(0.15)),
+            ("ma20", Some(10.5)),
+            ("ma60", Some(10.0)),
+            ("close", Some(11.0)),
+            ("pb", None),
+        ];
+        assert!(eval("pe < 15 && roe > 0.12 && ma20 > ma60", &values));
+        assert!(eval("PE<15&&(roe>0.2||close/ma60-1>=0.1)", &values));
+        assert!(!eval("!(pe < 15)", &values));
+        assert!(eval("-pe < 0 && close * 2 = 22", &values));
+        // Missing data makes a comparison unknown, never true
+        assert!(!eval("pb < 1", &values));
+        assert!(!eval("!(pb < 1)", &values));
+        assert!(eval("pb < 1 || pe < 15", &values));
+        assert!(!eval("pb < 1 && pe < 15", &values));
+
+        let screen = Screen::compile("ma5 > ema10 && rsi14 < 30 && k > d").unwrap();
+        assert!(!screen.needs_fundamentals());
+        assert_eq!(screen.fields[1].1, Field::Line(IndicatorSpec::Ema(10), 0));
+
+        assert_eq!(
+            Screen::compile("pe < 15 && foo > 1").unwrap_err(),
+            "Unknown field 'foo' at 11"
+        );
+        assert_eq!(
+            Screen::compile("(pe < 15").unwrap_err(),
+            "Expected ')' at 8"
+        );
+        assert!(Screen::compile("pe <").is_err());
+        assert_eq!(
+            Screen::compile("pe < 15 roe").unwrap_err(),
+            "Unexpected input at 8"
+        );
+        assert!(Screen::compile("ma999 > 1").is_err());
+        assert!(Screen::compile("pe # 1").is_err());
+        assert!(Screen::compile("  ").is_err());
+    }
+}
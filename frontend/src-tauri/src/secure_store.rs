@@ -0,0 +1,147 @@
+//! Small encrypted JSON files for secrets kept on disk.
+//!
+//! Contents are sealed with ChaCha20-Poly1305 under a per-install key stored
+//! next to them (owner-only on unix) and written as hex of nonce‖ciphertext.
+//! A file that is missing, tampered with or sealed under another key reads
+//! back as empty.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use log::warn;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::utils::{ensure_dir_exists, read_from_file, write_to_file};
+
+const NONCE_LEN: usize = 12;
+
+fn load_key(path: &Path) -> [u8; 32] {
+    let stored = read_from_file(path)
+        .ok()
+        .and_then(|content| hex::decode(content.trim()).ok())
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+    match stored {
+        Some(key) => key,
+        None => {
+            let mut key = [0u8; 32];
+            rand::rngs::OsRng.fill_bytes(&mut key);
+            if let Err(e) = write_to_file(path, &hex::encode(key)) {
+                warn!("Failed to store encryption key: {}", e);
+            }
+            restrict_permissions(path);
+            key
+        }
+    }
+}
+
+/// Write a file that is owner-only from the moment it is created, for keys
+/// that must never sit world-readable on disk
+pub fn write_private(path: &Path, content: &str) -> Result<(), std::io::Error> {
+    ensure_dir_exists(path.parent().unwrap())?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(content.as_bytes())?;
+    // The mode only applies to a file being created
+    restrict_permissions(path);
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn restrict_permissions(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Err(e) = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)) {
+        warn!(
+            "Failed to restrict permissions of {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+#[cfg(not(unix))]
+pub fn restrict_permissions(_path: &Path) {}
+
+pub struct EncryptedFile {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+}
+
+impl EncryptedFile {
+    /// The file at `path`, sealed with the key at `key_path` (created on first use)
+    pub fn new(path: PathBuf, key_path: &Path) -> Self {
+        let key = load_key(key_path);
+        Self {
+            path,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        }
+    }
+
+    pub fn read<T: DeserializeOwned + Default>(&self) -> T {
+        read_from_file(&self.path)
+            .ok()
+            .and_then(|content| hex::decode(content.trim()).ok())
+            .filter(|bytes| bytes.len() > NONCE_LEN)
+            .and_then(|bytes| {
+                let (nonce, sealed) = bytes.split_at(NONCE_LEN);
+                self.cipher.decrypt(Nonce::from_slice(nonce), sealed).ok()
+            })
+            .and_then(|plain| serde_json::from_slice(&plain).ok())
+            .unwrap_or_default()
+    }
+
+    /// Seal and write `value`; `what` names the contents in errors
+    pub fn write<T: Serialize>(&self, value: &T, what: &str) -> Result<(), String> {
+        let plain = serde_json::to_vec(value)
+            .map_err(|e| format!("Failed to serialize {}: {}", what, e))?;
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plain.as_slice())
+            .map_err(|e| format!("Failed to encrypt {}: {}", what, e))?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend(sealed);
+        write_to_file(&self.path, &hex::encode(bytes))
+            .map_err(|e| format!("Failed to save {}: {}", what, e))?;
+        restrict_permissions(&self.path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_round_trip_and_wrong_key() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let path = dir.join(format!("ssi-secure-{}.enc", id));
+        let key = dir.join(format!("ssi-secure-{}.key", id));
+        let other_key = dir.join(format!("ssi-secure-other-{}.key", id));
+
+        let mut secrets = BTreeMap::new();
+        secrets.insert("api_secret".to_string(), "s3cr3t".to_string());
+        EncryptedFile::new(path.clone(), &key)
+            .write(&secrets, "secrets")
+            .unwrap();
+        assert!(!read_from_file(&path).unwrap().contains("s3cr3t"));
+
+        let read: BTreeMap<String, String> = EncryptedFile::new(path.clone(), &key).read();
+        assert_eq!(read, secrets);
+        let unreadable: BTreeMap<String, String> =
+            EncryptedFile::new(path.clone(), &other_key).read();
+        assert!(unreadable.is_empty());
+
+        for file in [path, key, other_key] {
+            std::fs::remove_file(file).ok();
+        }
+    }
+}
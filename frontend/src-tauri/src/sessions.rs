@@ -0,0 +1,102 @@
+//! Exchange trading sessions.
+//!
+//! Session times are expressed in exchange-local minutes since midnight and
+//! converted through the exchange time zone, so minute bars can be matched
+//! against the morning and afternoon sessions (and the lunch break between
+//! them on Chinese and Hong Kong exchanges).
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Timelike};
+use chrono_tz::Tz;
+
+use crate::models::Market;
+
+/// Half-open `[start, end)` session segments in local minutes since midnight
+pub fn session_segments(market: Market) -> &'static [(u32, u32)] {
+    match market {
+        Market::Cn => &[(9 * 60 + 30, 11 * 60 + 30), (13 * 60, 15 * 60)],
+        Market::Hk => &[(9 * 60 + 30, 12 * 60), (13 * 60, 16 * 60)],
+        Market::Us => &[(9 * 60 + 30, 16 * 60)],
+    }
+}
+
+/// Local minute pre-market trading or the opening auction starts
+pub fn pre_market_start(market: Market) -> u32 {
+    match market {
+        Market::Cn => 9 * 60 + 15,
+        Market::Hk => 9 * 60,
+        Market::Us => 4 * 60,
+    }
+}
+
+pub fn timezone(market: Market) -> Tz {
+    match market {
+        Market::Cn => chrono_tz::Asia::Shanghai,
+        Market::Hk => chrono_tz::Asia::Hong_Kong,
+        Market::Us => chrono_tz::America::New_York,
+    }
+}
+
+/// Exchange-local time of a Unix timestamp
+pub fn local_time(market: Market, ts: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .with_timezone(&timezone(market))
+        .naive_local()
+}
+
+/// Exchange-local calendar day a timestamp falls on
+pub fn trading_day(market: Market, ts: i64) -> NaiveDate {
+    local_time(market, ts).date()
+}
+
+/// Index of the session segment containing a bar opening at `ts`
+pub fn session_segment(market: Market, ts: i64) -> Option<usize> {
+    let time = local_time(market, ts);
+    let minute = time.hour() * 60 + time.minute();
+    session_segments(market)
+        .iter()
+        .position(|&(start, end)| minute >= start && minute < end)
+}
+
+pub fn in_session(market: Market, ts: i64) -> bool {
+    session_segment(market, ts).is_some()
+}
+
+/// Whether `ts` falls between the pre-market start and the first session
+pub fn in_pre_market(market: Market, ts: i64) -> bool {
+    let time = local_time(market, ts);
+    let minute = time.hour() * 60 + time.minute();
+    (pre_market_start(market)..session_segments(market)[0].0).contains(&minute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn ts(market: Market, h: u32, m: u32) -> i64 {
+        timezone(market)
+            .with_ymd_and_hms(2024, 7, 1, h, m, 0)
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn test_lunch_break_and_close() {
+        assert_eq!(session_segment(Market::Cn, ts(Market::Cn, 9, 30)), Some(0));
+        assert_eq!(session_segment(Market::Cn, ts(Market::Cn, 11, 29)), Some(0));
+        assert!(!in_session(Market::Cn, ts(Market::Cn, 11, 30)));
+        assert!(!in_session(Market::Cn, ts(Market::Cn, 12, 30)));
+        assert_eq!(session_segment(Market::Cn, ts(Market::Cn, 13, 0)), Some(1));
+        assert!(!in_session(Market::Cn, ts(Market::Cn, 15, 0)));
+        assert!(in_session(Market::Hk, ts(Market::Hk, 15, 30)));
+        // New York is on daylight time in July; the local clock still reads 09:30
+        assert!(in_session(Market::Us, ts(Market::Us, 9, 30)));
+        assert!(!in_session(Market::Us, ts(Market::Us, 9, 29)));
+
+        assert!(in_pre_market(Market::Cn, ts(Market::Cn, 9, 15)));
+        assert!(!in_pre_market(Market::Cn, ts(Market::Cn, 9, 30)));
+        assert!(in_pre_market(Market::Us, ts(Market::Us, 4, 0)));
+        assert!(!in_pre_market(Market::Us, ts(Market::Us, 3, 59)));
+    }
+}
@@ -0,0 +1,213 @@
+//! Persistent user settings, stored as `settings.json` in the app data dir.
+//!
+//! Fields use `#[serde(default)]` so settings files written by older
+//! versions keep loading as new options are added.
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::ai::AiSettings;
+use crate::appearance::AppearanceSettings;
+use crate::archive::ArchiveSettings;
+use crate::brokers::BrokerSettings;
+use crate::costs::CostModel;
+use crate::delivery::DeliverySettings;
+use crate::embeddings::EmbeddingSettings;
+use crate::fonts::FontSettings;
+use crate::guest::GuestSettings;
+use crate::menu_bar::MenuBarSettings;
+use crate::notifications::NotificationSettings;
+use crate::placement::PlacementSettings;
+use crate::politeness::PolicyEngine;
+use crate::polling::PollingSettings;
+use crate::presence::PresenceSettings;
+use crate::presentation::PresentationSettings;
+use crate::providers::ProviderSettings;
+use crate::proxy::{self, ProxySettings};
+use crate::scheduler::SchedulerSettings;
+use crate::tls::TlsSettings;
+use crate::utils::{read_from_file, write_to_file};
+use crate::valuation::ValuationSettings;
+use crate::watch_folders::WatchFolderSettings;
+use crate::workspace::Workspace;
+use crate::zoom::ZoomSettings;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Pre-warm caches for frequently viewed symbols at startup
+    pub preload_enabled: bool,
+    /// How many of the most viewed symbols to pre-warm
+    pub preload_symbols: usize,
+    /// Transaction costs applied by backtests and paper trading
+    pub cost_model: CostModel,
+    /// Refresh intervals per data class
+    pub polling: PollingSettings,
+    /// Proxy route per data source
+    pub proxies: ProxySettings,
+    /// Trusted roots and certificate pins
+    pub tls: TlsSettings,
+    /// Where read-only broker integrations connect
+    pub brokers: BrokerSettings,
+    /// Scale absolute portfolio amounts for screen sharing
+    pub privacy_mode: bool,
+    /// Read-only guest sessions
+    pub guest: GuestSettings,
+    /// Saved workspace layouts
+    pub workspaces: Vec<Workspace>,
+    /// Theme, accent colour and price colour convention
+    pub appearance: AppearanceSettings,
+    /// Fonts for generated reports and chart images
+    pub fonts: FontSettings,
+    /// Local model server that embeds research documents
+    pub embeddings: EmbeddingSettings,
+    /// Chat model answering research questions
+    pub ai: AiSettings,
+    /// Muted categories, quiet hours and snoozed symbols
+    pub notifications: NotificationSettings,
+    /// Data provider priority
+    pub providers: ProviderSettings,
+    /// macOS menu bar extra
+    pub menu_bar: MenuBarSettings,
+    /// Keep the system awake while backfills and backtests run
+    pub inhibit_suspend: bool,
+    /// Pausing streaming and polling while the screen is locked or idle
+    pub presence: PresenceSettings,
+    /// Where the ticker bar is pinned
+    pub placement: PlacementSettings,
+    /// Lookback of valuation percentiles
+    pub valuation: ValuationSettings,
+    /// Zoom factor of each window
+    pub zoom: ZoomSettings,
+    /// Views and schedule of presentation mode
+    pub presentation: PresentationSettings,
+    /// Webhook, email and push channels alerts can go to
+    pub delivery: DeliverySettings,
+    /// Scheduled snapshots kept for compliance, and how long
+    pub archive: ArchiveSettings,
+    /// Folders whose files are imported as a symbol's research documents
+    pub watch_folders: WatchFolderSettings,
+    /// Program notes are edited in, given the file; the system default for
+    /// Markdown when unset
+    pub external_editor: Option<String>,
+    /// Cron schedules of background jobs changed from their defaults
+    pub scheduler: SchedulerSettings,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            preload_enabled: true,
+            preload_symbols: 20,
+            cost_model: CostModel::default(),
+            polling: PollingSettings::default(),
+            proxies: ProxySettings::default(),
+            tls: TlsSettings::default(),
+            brokers: BrokerSettings::default(),
+            privacy_mode: false,
+            guest: GuestSettings::default(),
+            workspaces: Vec::new(),
+            appearance: AppearanceSettings::default(),
+            fonts: FontSettings::default(),
+            embeddings: EmbeddingSettings::default(),
+            ai: AiSettings::default(),
+            notifications: NotificationSettings::default(),
+            providers: ProviderSettings::default(),
+            menu_bar: MenuBarSettings::default(),
+            inhibit_suspend: true,
+            presence: PresenceSettings::default(),
+            placement: PlacementSettings::default(),
+            valuation: ValuationSettings::default(),
+            zoom: ZoomSettings::default(),
+            presentation: PresentationSettings::default(),
+            delivery: DeliverySettings::default(),
+            archive: ArchiveSettings::default(),
+            watch_folders: WatchFolderSettings::default(),
+            external_editor: None,
+            scheduler: SchedulerSettings::default(),
+        }
+    }
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: RwLock<AppSettings>,
+}
+
+impl SettingsStore {
+    /// Load settings from disk, falling back to defaults
+    pub fn load(path: PathBuf) -> Self {
+        let settings = if path.exists() {
+            read_from_file(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| {
+                    warn!("Failed to load settings, using defaults: {}", e);
+                    AppSettings::default()
+                })
+        } else {
+            AppSettings::default()
+        };
+        Self {
+            path,
+            settings: RwLock::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> AppSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Validate, replace and persist the settings
+    pub fn set(&self, settings: AppSettings) -> Result<(), String> {
+        settings.polling.validate()?;
+        proxy::validate(&settings.proxies)?;
+        settings.tls.validate()?;
+        settings.notifications.validate()?;
+        settings.brokers.validate()?;
+        settings.appearance.validate()?;
+        settings.embeddings.validate()?;
+        settings.ai.validate()?;
+        settings.providers.validate()?;
+        settings.menu_bar.validate()?;
+        settings.presence.validate()?;
+        settings.placement.validate()?;
+        settings.valuation.validate()?;
+        settings.zoom.validate()?;
+        settings.presentation.validate()?;
+        settings.delivery.validate()?;
+        settings.archive.validate()?;
+        settings.watch_folders.validate()?;
+        settings.scheduler.validate()?;
+        let content = serde_json::to_string_pretty(&settings)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save settings: {}", e))?;
+        *self.settings.write().unwrap() = settings;
+        Ok(())
+    }
+}
+
+/// Get current application settings
+#[tauri::command]
+pub fn get_settings(store: State<'_, SettingsStore>) -> Result<AppSettings, String> {
+    Ok(store.get())
+}
+
+/// Replace application settings
+#[tauri::command]
+pub fn update_settings(
+    store: State<'_, SettingsStore>,
+    policies: State<'_, PolicyEngine>,
+    settings: AppSettings,
+) -> Result<AppSettings, String> {
+    info!("Updating settings");
+    store.set(settings)?;
+    let settings = store.get();
+    policies.configure(&settings.proxies, &settings.tls)?;
+    Ok(settings)
+}
@@ -0,0 +1,554 @@
+//! Request signing for authenticated provider APIs.
+//!
+//! A source declares how its requests are authenticated in its
+//! [`SourcePolicy`]: an HMAC signature over a canonical payload (method,
+//! path, query, timestamp, body hash…) placed in a header or query
+//! parameter, or a bearer token obtained and refreshed from a token
+//! endpoint. The [`PolicyEngine`] applies the scheme to every request of the
+//! source, so adding a broker open API or a paid vendor is a policy entry
+//! plus the user's credentials rather than another hand-rolled client.
+//! Credentials are kept in an encrypted file; access tokens only in memory.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use hmac::{Hmac, Mac};
+use log::info;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::politeness::{source_policy, PolicyEngine, SourcePolicy, SOURCE_POLICIES};
+use crate::secure_store::EncryptedFile;
+
+/// Tokens without an `expires_in` are trusted for this long
+const DEFAULT_TOKEN_LIFETIME_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum AuthScheme {
+    None,
+    Hmac(HmacScheme),
+    Token(TokenScheme),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HmacScheme {
+    /// Header carrying the API key
+    pub key_header: &'static str,
+    pub timestamp_header: &'static str,
+    pub timestamp: TimestampFormat,
+    /// Header carrying a random nonce, for APIs that require one
+    pub nonce_header: Option<&'static str>,
+    /// Signed text, with `{method}`, `{path}`, `{query}`, `{timestamp}`,
+    /// `{nonce}`, `{api_key}` and `{body_sha256}` placeholders
+    pub payload: &'static str,
+    pub signature: SignaturePlacement,
+    pub encoding: SignatureEncoding,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    UnixSeconds,
+    UnixMillis,
+    Rfc3339,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(tag = "in", content = "name", rename_all = "lowercase")]
+pub enum SignaturePlacement {
+    Header(&'static str),
+    Query(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    Hex,
+    Base64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenScheme {
+    /// Must be one of the source's allowed endpoints
+    pub token_url: &'static str,
+    pub grant: TokenGrant,
+    /// Header the token is sent in, with its prefix (`Bearer `)
+    pub header: &'static str,
+    pub prefix: &'static str,
+    /// Refresh tokens this long before they expire
+    pub refresh_margin_secs: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenGrant {
+    ClientCredentials,
+    RefreshToken,
+}
+
+impl AuthScheme {
+    /// Credential fields the scheme needs
+    fn required_fields(&self) -> &'static [&'static str] {
+        match self {
+            AuthScheme::None => &[],
+            AuthScheme::Hmac(_) => &["api_key", "api_secret"],
+            AuthScheme::Token(TokenScheme {
+                grant: TokenGrant::ClientCredentials,
+                ..
+            }) => &["client_id", "client_secret"],
+            AuthScheme::Token(TokenScheme {
+                grant: TokenGrant::RefreshToken,
+                ..
+            }) => &["client_id", "refresh_token"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderCredentials {
+    pub api_key: Option<String>,
+    pub api_secret: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub refresh_token: Option<String>,
+}
+
+impl ProviderCredentials {
+    fn field(&self, name: &str) -> Option<&str> {
+        match name {
+            "api_key" => self.api_key.as_deref(),
+            "api_secret" => self.api_secret.as_deref(),
+            "client_id" => self.client_id.as_deref(),
+            "client_secret" => self.client_secret.as_deref(),
+            "refresh_token" => self.refresh_token.as_deref(),
+            _ => None,
+        }
+        .filter(|value| !value.is_empty())
+    }
+
+    fn missing(&self, scheme: &AuthScheme) -> Vec<&'static str> {
+        scheme
+            .required_fields()
+            .iter()
+            .copied()
+            .filter(|name| self.field(name).is_none())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AccessToken {
+    token: String,
+    expires_at: i64,
+}
+
+fn format_timestamp(format: TimestampFormat, now: DateTime<Utc>) -> String {
+    match format {
+        TimestampFormat::UnixSeconds => now.timestamp().to_string(),
+        TimestampFormat::UnixMillis => now.timestamp_millis().to_string(),
+        TimestampFormat::Rfc3339 => now.to_rfc3339_opts(SecondsFormat::Millis, true),
+    }
+}
+
+/// Add the HMAC signature and its headers to a request
+fn sign_hmac(
+    scheme: &HmacScheme,
+    credentials: &ProviderCredentials,
+    request: &mut reqwest::Request,
+    now: DateTime<Utc>,
+    nonce: &str,
+) -> Result<(), String> {
+    let (Some(api_key), Some(secret)) = (
+        credentials.field("api_key"),
+        credentials.field("api_secret"),
+    ) else {
+        return Err("API key and secret are required to sign requests".to_string());
+    };
+    let timestamp = format_timestamp(scheme.timestamp, now);
+    let body = request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .unwrap_or_default();
+    let payload = scheme
+        .payload
+        .replace("{method}", request.method().as_str())
+        .replace("{path}", request.url().path())
+        .replace("{query}", request.url().query().unwrap_or_default())
+        .replace("{timestamp}", &timestamp)
+        .replace("{nonce}", nonce)
+        .replace("{api_key}", api_key)
+        .replace("{body_sha256}", &hex::encode(Sha256::digest(body)));
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| format!("Invalid API secret: {}", e))?;
+    mac.update(payload.as_bytes());
+    let digest = mac.finalize().into_bytes();
+    let signature = match scheme.encoding {
+        SignatureEncoding::Hex => hex::encode(digest),
+        SignatureEncoding::Base64 => BASE64.encode(digest),
+    };
+
+    let mut headers = vec![
+        (scheme.key_header, api_key.to_string()),
+        (scheme.timestamp_header, timestamp),
+    ];
+    if let Some(name) = scheme.nonce_header {
+        headers.push((name, nonce.to_string()));
+    }
+    match scheme.signature {
+        SignaturePlacement::Header(name) => headers.push((name, signature)),
+        SignaturePlacement::Query(name) => {
+            request
+                .url_mut()
+                .query_pairs_mut()
+                .append_pair(name, &signature);
+        }
+    }
+    for (name, value) in headers {
+        let value = reqwest::header::HeaderValue::from_str(&value)
+            .map_err(|e| format!("Invalid {} header: {}", name, e))?;
+        request.headers_mut().insert(name, value);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<i64>,
+    refresh_token: Option<String>,
+}
+
+/// The access token and any rotated refresh token from a token endpoint response
+fn parse_token_response(body: &str, now: i64) -> Result<(AccessToken, Option<String>), String> {
+    let response: TokenResponse =
+        serde_json::from_str(body).map_err(|e| format!("Failed to parse token response: {}", e))?;
+    Ok((
+        AccessToken {
+            token: response.access_token,
+            expires_at: now + response.expires_in.unwrap_or(DEFAULT_TOKEN_LIFETIME_SECS),
+        },
+        response.refresh_token,
+    ))
+}
+
+pub struct CredentialVault {
+    file: EncryptedFile,
+    credentials: Mutex<HashMap<String, ProviderCredentials>>,
+    tokens: Mutex<HashMap<String, AccessToken>>,
+    /// Serializes token refreshes so concurrent requests share one
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+impl CredentialVault {
+    pub fn load(path: PathBuf, key_path: PathBuf) -> Self {
+        let file = EncryptedFile::new(path, &key_path);
+        let credentials = file.read();
+        Self {
+            file,
+            credentials: Mutex::new(credentials),
+            tokens: Mutex::new(HashMap::new()),
+            refreshing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn save(&self, credentials: &HashMap<String, ProviderCredentials>) -> Result<(), String> {
+        self.file.write(credentials, "provider credentials")
+    }
+
+    pub fn set(&self, source: &str, credentials: ProviderCredentials) -> Result<(), String> {
+        let mut all = self.credentials.lock().unwrap();
+        all.insert(source.to_string(), credentials);
+        self.tokens.lock().unwrap().remove(source);
+        self.save(&all)
+    }
+
+    pub fn remove(&self, source: &str) -> Result<(), String> {
+        let mut all = self.credentials.lock().unwrap();
+        all.remove(source);
+        self.tokens.lock().unwrap().remove(source);
+        self.save(&all)
+    }
+
+    fn get(&self, source: &str) -> Result<ProviderCredentials, String> {
+        self.credentials
+            .lock()
+            .unwrap()
+            .get(source)
+            .cloned()
+            .ok_or_else(|| format!("No credentials configured for {}", source))
+    }
+
+    /// Drop a cached token the provider rejected
+    pub fn invalidate_token(&self, source: &str) {
+        self.tokens.lock().unwrap().remove(source);
+    }
+
+    fn cached_token(&self, source: &str, margin_secs: i64, now: i64) -> Option<String> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .get(source)
+            .filter(|t| t.expires_at - margin_secs > now)
+            .map(|t| t.token.clone())
+    }
+
+    /// A valid access token for the source, fetching a new one when needed
+    async fn access_token(
+        &self,
+        engine: &PolicyEngine,
+        policy: &SourcePolicy,
+        scheme: &TokenScheme,
+    ) -> Result<String, String> {
+        let margin = scheme.refresh_margin_secs;
+        if let Some(token) = self.cached_token(policy.id, margin, Utc::now().timestamp()) {
+            return Ok(token);
+        }
+        let _refreshing = self.refreshing.lock().await;
+        if let Some(token) = self.cached_token(policy.id, margin, Utc::now().timestamp()) {
+            return Ok(token);
+        }
+
+        let credentials = self.get(policy.id)?;
+        let mut form: Vec<(&str, String)> = Vec::new();
+        match scheme.grant {
+            TokenGrant::ClientCredentials => form.push(("grant_type", "client_credentials".into())),
+            TokenGrant::RefreshToken => form.push(("grant_type", "refresh_token".into())),
+        }
+        for name in ["client_id", "client_secret", "refresh_token"] {
+            if let Some(value) = credentials.field(name) {
+                if name != "refresh_token" || scheme.grant == TokenGrant::RefreshToken {
+                    form.push((name, value.to_string()));
+                }
+            }
+        }
+        let body = engine.post_form(policy.id, scheme.token_url, &form).await?;
+        let (token, rotated) = parse_token_response(&body, Utc::now().timestamp())?;
+        info!("Refreshed access token for {}", policy.id);
+
+        if let Some(refresh_token) = rotated {
+            let mut updated = credentials;
+            updated.refresh_token = Some(refresh_token);
+            let mut all = self.credentials.lock().unwrap();
+            all.insert(policy.id.to_string(), updated);
+            self.save(&all)?;
+        }
+        let value = token.token.clone();
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(policy.id.to_string(), token);
+        Ok(value)
+    }
+
+    /// Obtain whatever the source's scheme needs before a request is admitted
+    pub async fn prepare(
+        &self,
+        engine: &PolicyEngine,
+        policy: &SourcePolicy,
+    ) -> Result<Option<String>, String> {
+        match &policy.auth {
+            AuthScheme::Token(scheme) => self.access_token(engine, policy, scheme).await.map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Apply the source's scheme to a built request, right before it is sent
+    pub fn authorize(
+        &self,
+        policy: &SourcePolicy,
+        request: &mut reqwest::Request,
+        token: Option<&str>,
+    ) -> Result<(), String> {
+        match &policy.auth {
+            AuthScheme::None => Ok(()),
+            AuthScheme::Hmac(scheme) => {
+                let nonce = format!("{:016x}", rand::thread_rng().gen::<u64>());
+                sign_hmac(scheme, &self.get(policy.id)?, request, Utc::now(), &nonce)
+            }
+            AuthScheme::Token(scheme) => {
+                let token = token.ok_or("Access token missing")?;
+                let value =
+                    reqwest::header::HeaderValue::from_str(&format!("{}{}", scheme.prefix, token))
+                        .map_err(|e| format!("Invalid access token: {}", e))?;
+                request.headers_mut().insert(scheme.header, value);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What the UI sees of a source's credentials; secrets never leave the vault
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialStatus {
+    pub source: String,
+    pub auth: AuthScheme,
+    pub configured: bool,
+    pub missing: Vec<&'static str>,
+    pub token_expires_at: Option<i64>,
+}
+
+fn signed_policy(source: &str) -> Result<&'static SourcePolicy, String> {
+    let policy = source_policy(source)?;
+    if matches!(policy.auth, AuthScheme::None) {
+        return Err(format!("Source does not use signed requests: {}", source));
+    }
+    Ok(policy)
+}
+
+/// Store the API key, secret or OAuth client for a signed source
+#[tauri::command]
+pub fn set_provider_credentials(
+    vault: State<'_, CredentialVault>,
+    source: String,
+    credentials: ProviderCredentials,
+) -> Result<(), String> {
+    let policy = signed_policy(&source)?;
+    let missing = credentials.missing(&policy.auth);
+    if !missing.is_empty() {
+        return Err(format!("Missing credentials: {}", missing.join(", ")));
+    }
+    vault.set(&source, credentials)
+}
+
+#[tauri::command]
+pub fn clear_provider_credentials(
+    vault: State<'_, CredentialVault>,
+    source: String,
+) -> Result<(), String> {
+    signed_policy(&source)?;
+    vault.remove(&source)
+}
+
+/// Credential status of every source with signed requests
+#[tauri::command]
+pub fn get_provider_credentials(
+    vault: State<'_, CredentialVault>,
+) -> Result<Vec<CredentialStatus>, String> {
+    let credentials = vault.credentials.lock().unwrap();
+    let tokens = vault.tokens.lock().unwrap();
+    Ok(SOURCE_POLICIES
+        .iter()
+        .filter(|p| !matches!(p.auth, AuthScheme::None))
+        .map(|p| {
+            let stored = credentials.get(p.id);
+            CredentialStatus {
+                source: p.id.to_string(),
+                auth: p.auth.clone(),
+                configured: stored.is_some(),
+                missing: stored.cloned().unwrap_or_default().missing(&p.auth),
+                token_expires_at: tokens.get(p.id).map(|t| t.expires_at),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const SCHEME: HmacScheme = HmacScheme {
+        key_header: "X-Api-Key",
+        timestamp_header: "X-Timestamp",
+        timestamp: TimestampFormat::UnixMillis,
+        nonce_header: None,
+        payload: "{method}\n{path}\n{query}\n{timestamp}\n{body_sha256}",
+        signature: SignaturePlacement::Header("X-Signature"),
+        encoding: SignatureEncoding::Hex,
+    };
+
+    fn credentials() -> ProviderCredentials {
+        ProviderCredentials {
+            api_key: Some("key-1".to_string()),
+            api_secret: Some("secret".to_string()),
+            ..ProviderCredentials::default()
+        }
+    }
+
+    fn request(method: reqwest::Method, url: &str, body: Option<&str>) -> reqwest::Request {
+        let mut request = reqwest::Request::new(method, url.parse().unwrap());
+        if let Some(body) = body {
+            *request.body_mut() = Some(body.to_string().into());
+        }
+        request
+    }
+
+    #[test]
+    fn test_hmac_signing() {
+        let now = Utc.timestamp_millis_opt(1_700_000_000_000).unwrap();
+        let mut get = request(
+            reqwest::Method::GET,
+            "https://api.broker.example/api/v1/positions?account=1&symbol=600519",
+            None,
+        );
+        sign_hmac(&SCHEME, &credentials(), &mut get, now, "n").unwrap();
+        assert_eq!(get.headers()["X-Api-Key"], "key-1");
+        assert_eq!(get.headers()["X-Timestamp"], "1700000000000");
+        assert_eq!(
+            get.headers()["X-Signature"],
+            "05a7c39c89aa733137fe63341d07739708b634c31259fa96034798d33c54eb1c"
+        );
+
+        let query_scheme = HmacScheme {
+            signature: SignaturePlacement::Query("sign"),
+            encoding: SignatureEncoding::Base64,
+            ..SCHEME
+        };
+        let mut post = request(
+            reqwest::Method::POST,
+            "https://api.broker.example/api/v1/orders",
+            Some(r#"{"qty":100}"#),
+        );
+        sign_hmac(&query_scheme, &credentials(), &mut post, now, "n").unwrap();
+        assert_eq!(
+            post.url().query(),
+            Some("sign=oW8ns9gzobS22wPvKQBQFjN0RD2zHc4kW3Yi3Lgmwho%3D")
+        );
+
+        let mut unsigned = request(reqwest::Method::GET, "https://api.broker.example/", None);
+        assert!(sign_hmac(
+            &SCHEME,
+            &ProviderCredentials::default(),
+            &mut unsigned,
+            now,
+            "n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_token_parsing_and_required_fields() {
+        let (token, rotated) = parse_token_response(
+            r#"{"access_token": "abc", "expires_in": 7200, "refresh_token": "r2"}"#,
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(token.expires_at, 8_200);
+        assert_eq!(rotated.as_deref(), Some("r2"));
+        let (token, _) = parse_token_response(r#"{"access_token": "abc"}"#, 0).unwrap();
+        assert_eq!(token.expires_at, DEFAULT_TOKEN_LIFETIME_SECS);
+        assert!(parse_token_response(r#"{"error": "invalid_client"}"#, 0).is_err());
+
+        let scheme = AuthScheme::Token(TokenScheme {
+            token_url: "https://api.vendor.example/oauth/token",
+            grant: TokenGrant::RefreshToken,
+            header: "Authorization",
+            prefix: "Bearer ",
+            refresh_margin_secs: 60,
+        });
+        let partial = ProviderCredentials {
+            client_id: Some("app".to_string()),
+            ..ProviderCredentials::default()
+        };
+        assert_eq!(partial.missing(&scheme), ["refresh_token"]);
+        assert!(credentials().missing(&AuthScheme::Hmac(SCHEME)).is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+//! Deterministic snapshot mode.
+//!
+//! While a snapshot time is set, data commands serve only locally cached
+//! data and truncate every series at that instant, so screenshots, demos and
+//! end-to-end UI tests render identically regardless of live market noise.
+
+use std::sync::RwLock;
+
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+
+/// Payload of the `snapshot-changed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotStatus {
+    frozen_at: Option<i64>,
+}
+
+#[derive(Default)]
+pub struct SnapshotClock {
+    frozen_at: RwLock<Option<i64>>,
+}
+
+impl SnapshotClock {
+    /// The frozen Unix timestamp, if snapshot mode is active
+    pub fn frozen_at(&self) -> Option<i64> {
+        *self.frozen_at.read().unwrap()
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen_at().is_some()
+    }
+
+    /// Current time as seen by data commands
+    pub fn now(&self) -> i64 {
+        self.frozen_at().unwrap_or_else(|| Utc::now().timestamp())
+    }
+
+    pub fn set(&self, frozen_at: Option<i64>) {
+        *self.frozen_at.write().unwrap() = frozen_at;
+    }
+}
+
+/// Freeze data commands at `ts` (Unix seconds), or pass `null` to resume live data
+#[tauri::command]
+pub fn set_snapshot_time(
+    app: AppHandle,
+    clock: State<'_, SnapshotClock>,
+    ts: Option<i64>,
+) -> Result<SnapshotStatus, String> {
+    if let Some(ts) = ts {
+        if ts <= 0 || ts > Utc::now().timestamp() {
+            return Err(format!("Invalid snapshot time: {}", ts));
+        }
+    }
+
+    clock.set(ts);
+    info!("Snapshot time set to {:?}", ts);
+    let status = SnapshotStatus { frozen_at: ts };
+    if let Err(e) = app.emit("snapshot-changed", status.clone()) {
+        warn!("Failed to emit snapshot-changed event: {}", e);
+    }
+    Ok(status)
+}
+
+/// Get the active snapshot time, if any
+#[tauri::command]
+pub fn get_snapshot_time(clock: State<'_, SnapshotClock>) -> Result<SnapshotStatus, String> {
+    Ok(SnapshotStatus {
+        frozen_at: clock.frozen_at(),
+    })
+}
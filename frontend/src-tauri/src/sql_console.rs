@@ -0,0 +1,474 @@
+//! Read-only SQL console for ad-hoc questions about the user's own data.
+//!
+//! Each query runs against an in-memory SQLite snapshot of the local stores
+//! (holdings, transactions, synced broker accounts, news and backtest runs),
+//! with the app database (watchlists, alerts, notes, notifications and the
+//! rest) attached next to it read-only, so nothing a query does can change
+//! the files on disk. On top of that the connection is switched to
+//! `query_only`, a statement must be a single
+//! `SELECT` (or `WITH … SELECT`) that SQLite itself reports as read-only,
+//! results stop at a row limit and a watchdog interrupts queries that run
+//! past the time limit. Queries are refused in privacy mode, since computed
+//! columns cannot be redacted by name.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params_from_iter, Batch, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+
+use crate::backtest_runs::{BacktestRun, RunStore};
+use crate::db::Database;
+use crate::news::{NewsItem, NewsStore};
+use crate::portfolio::{Portfolio, PortfolioStore};
+use crate::privacy::Privacy;
+use crate::snapshot::SnapshotClock;
+
+pub const DEFAULT_ROW_LIMIT: usize = 500;
+pub const MAX_ROW_LIMIT: usize = 5000;
+const TIME_LIMIT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+CREATE TABLE holdings (
+    id TEXT PRIMARY KEY, symbol TEXT, quantity REAL, cost_price REAL, currency TEXT,
+    market_price REAL, note TEXT, updated_at TEXT
+);
+CREATE TABLE transactions (
+    id TEXT PRIMARY KEY, account_id TEXT, kind TEXT, symbol TEXT, quantity REAL, price REAL,
+    amount REAL, fee REAL, currency TEXT, date TEXT, note TEXT, recorded_at TEXT,
+    portfolio_id TEXT
+);
+CREATE TABLE broker_positions (
+    broker TEXT, account_id TEXT, symbol TEXT, quantity REAL, cost_price REAL,
+    currency TEXT, market_price REAL, synced_at TEXT
+);
+CREATE TABLE broker_balances (
+    broker TEXT, account_id TEXT, currency TEXT, cash REAL, market_value REAL,
+    net_liquidation REAL, synced_at TEXT
+);
+CREATE TABLE news (
+    id TEXT PRIMARY KEY, source TEXT, title TEXT, summary TEXT, url TEXT,
+    published_at INTEGER, fetched_at TEXT, tags TEXT, symbols TEXT
+);
+CREATE TABLE backtest_runs (
+    id TEXT PRIMARY KEY, strategy_id TEXT, symbols TEXT, interval TEXT, start INTEGER,
+    \"end\" INTEGER, total_return REAL, max_drawdown REAL, trades INTEGER,
+    total_costs REAL, created_at TEXT
+);
+";
+
+/// Local data copied into the query snapshot
+pub struct Snapshot {
+    pub portfolio: Portfolio,
+    pub news: Vec<NewsItem>,
+    pub runs: Vec<BacktestRun>,
+    /// App database, attached read-only as `db`
+    pub database: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    /// More rows matched than the row limit allowed
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+}
+
+/// URI opening a database file read-only, whatever characters its path holds
+fn read_only_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    // Windows paths start with a drive letter, which a URI path can't
+    let root = if path.starts_with('/') { "" } else { "/" };
+    let mut uri = format!("file:{}", root);
+    for c in path.chars() {
+        match c {
+            '%' | '?' | '#' => uri.push_str(&format!("%{:02X}", c as u32)),
+            _ => uri.push(c),
+        }
+    }
+    uri + "?mode=ro"
+}
+
+fn open_snapshot(snapshot: &Snapshot) -> Result<Connection, rusqlite::Error> {
+    let mut conn = Connection::open_in_memory()?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+    {
+        let mut insert =
+            tx.prepare("INSERT INTO holdings VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+        for h in &snapshot.portfolio.manual {
+            insert.execute(rusqlite::params![
+                h.id,
+                h.symbol,
+                h.quantity,
+                h.cost_price,
+                h.currency,
+                h.market_price,
+                h.note,
+                h.updated_at
+            ])?;
+        }
+        let mut insert = tx.prepare(
+            "INSERT INTO transactions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )?;
+        for t in &snapshot.portfolio.transactions {
+            let e = &t.entry;
+            let kind = serde_json::to_value(e.kind)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string));
+            insert.execute(rusqlite::params![
+                t.id,
+                e.account_id,
+                kind,
+                e.symbol,
+                e.quantity,
+                e.price,
+                e.amount,
+                e.fee,
+                e.currency,
+                e.date,
+                e.note,
+                t.recorded_at,
+                e.portfolio_id
+            ])?;
+        }
+        let mut positions =
+            tx.prepare("INSERT INTO broker_positions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")?;
+        let mut balances =
+            tx.prepare("INSERT INTO broker_balances VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+        for account in &snapshot.portfolio.broker_accounts {
+            for p in &account.positions {
+                positions.execute(rusqlite::params![
+                    account.broker,
+                    account.account_id,
+                    p.symbol,
+                    p.quantity,
+                    p.cost_price,
+                    p.currency,
+                    p.market_price,
+                    account.synced_at
+                ])?;
+            }
+            for b in &account.balances {
+                balances.execute(rusqlite::params![
+                    account.broker,
+                    account.account_id,
+                    b.currency,
+                    b.cash,
+                    b.market_value,
+                    b.net_liquidation,
+                    account.synced_at
+                ])?;
+            }
+        }
+        let mut insert =
+            tx.prepare("INSERT INTO news VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?;
+        for n in &snapshot.news {
+            insert.execute(rusqlite::params![
+                n.id,
+                n.source,
+                n.title,
+                n.summary,
+                n.url,
+                n.published_at,
+                n.fetched_at,
+                n.tags.join(","),
+                n.symbols.join(",")
+            ])?;
+        }
+        let mut insert = tx.prepare(
+            "INSERT INTO backtest_runs VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        for r in &snapshot.runs {
+            insert.execute(rusqlite::params![
+                r.id,
+                r.strategy_id,
+                r.symbols.join(","),
+                r.interval,
+                r.start,
+                r.end,
+                r.metrics.total_return,
+                r.metrics.max_drawdown,
+                r.metrics.trades as i64,
+                r.metrics.total_costs,
+                r.created_at
+            ])?;
+        }
+    }
+    tx.commit()?;
+    if let Some(path) = &snapshot.database {
+        conn.execute("ATTACH DATABASE ?1 AS db", [read_only_uri(path)])?;
+    }
+    conn.execute_batch("PRAGMA query_only = ON")?;
+    Ok(conn)
+}
+
+/// Reject anything but a single read-only SELECT before it is prepared
+fn check_select(sql: &str) -> Result<(), String> {
+    let keyword = sql
+        .trim_start()
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or("")
+        .to_ascii_uppercase();
+    if keyword != "SELECT" && keyword != "WITH" {
+        return Err("Only SELECT statements can be run".to_string());
+    }
+    Ok(())
+}
+
+fn to_sql(value: &Value) -> Result<SqlValue, String> {
+    Ok(match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => return Err(format!("Unsupported query parameter: {}", other)),
+    })
+}
+
+fn to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Value::String(hex::encode(b)),
+    }
+}
+
+/// Run one read-only query, stopping at `row_limit` rows or after `time_limit`
+pub fn run_query(
+    conn: &Connection,
+    sql: &str,
+    params: &[Value],
+    row_limit: usize,
+    time_limit: Duration,
+) -> Result<QueryResult, String> {
+    check_select(sql)?;
+    let started = Instant::now();
+    let mut batch = Batch::new(conn, sql);
+    let mut stmt = batch
+        .next()
+        .map_err(|e| format!("Invalid query: {}", e))?
+        .ok_or("Empty query")?;
+    if !matches!(batch.next(), Ok(None)) {
+        return Err("Run one statement at a time".to_string());
+    }
+    if !stmt.readonly() {
+        return Err("Only read-only statements can be run".to_string());
+    }
+    let params = params.iter().map(to_sql).collect::<Result<Vec<_>, _>>()?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+
+    // Interrupt the query from outside if it outlives the time limit
+    let interrupt = conn.get_interrupt_handle();
+    let (done, finished) = mpsc::channel::<()>();
+    let watchdog = std::thread::spawn(move || {
+        if finished.recv_timeout(time_limit) == Err(mpsc::RecvTimeoutError::Timeout) {
+            interrupt.interrupt();
+        }
+    });
+
+    let result = (|| {
+        let mut rows = stmt
+            .query(params_from_iter(params))
+            .map_err(|e| e.to_string())?;
+        let mut out = Vec::new();
+        let mut truncated = false;
+        while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+            if out.len() == row_limit {
+                truncated = true;
+                break;
+            }
+            out.push(
+                (0..columns.len())
+                    .map(|i| row.get_ref(i).map(to_json).unwrap_or(Value::Null))
+                    .collect(),
+            );
+        }
+        Ok((out, truncated))
+    })();
+    done.send(()).ok();
+    watchdog.join().ok();
+
+    let (rows, truncated) = result.map_err(|e: String| {
+        if started.elapsed() >= time_limit {
+            format!("Query exceeded the {} s time limit", time_limit.as_secs())
+        } else {
+            format!("Query failed: {}", e)
+        }
+    })?;
+    Ok(QueryResult {
+        columns,
+        rows,
+        truncated,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Run a read-only SELECT over a snapshot of the local data
+#[tauri::command]
+pub async fn run_readonly_query(
+    app: AppHandle,
+    privacy: State<'_, Privacy>,
+    sql: String,
+    params: Option<Vec<Value>>,
+    max_rows: Option<usize>,
+) -> Result<QueryResult, String> {
+    if privacy.enabled() {
+        return Err("Turn off privacy mode to run queries".to_string());
+    }
+    check_select(&sql)?;
+    let snapshot = Snapshot {
+        portfolio: app.state::<PortfolioStore>().get(),
+        news: app
+            .state::<NewsStore>()
+            .latest(app.state::<SnapshotClock>().frozen_at(), usize::MAX),
+        runs: app.state::<RunStore>().list(None),
+        database: Some(app.state::<Database>().path().to_path_buf()),
+    };
+    let row_limit = max_rows
+        .unwrap_or(DEFAULT_ROW_LIMIT)
+        .clamp(1, MAX_ROW_LIMIT);
+    let params = params.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || {
+        let conn = open_snapshot(&snapshot)
+            .map_err(|e| format!("Failed to build query snapshot: {}", e))?;
+        let result = run_query(&conn, &sql, &params, row_limit, TIME_LIMIT);
+        match &result {
+            Ok(r) => info!(
+                "SQL console returned {} rows in {} ms",
+                r.rows.len(),
+                r.elapsed_ms
+            ),
+            Err(e) => warn!("SQL console query rejected: {}", e),
+        }
+        result
+    })
+    .await
+    .map_err(|e| format!("Query task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::Holding;
+
+    fn snapshot() -> Connection {
+        let mut portfolio = Portfolio::default();
+        for (i, symbol) in ["SH600519", "SZ000001", "HK00700"].iter().enumerate() {
+            portfolio.manual.push(Holding {
+                id: format!("h{}", i),
+                symbol: symbol.to_string(),
+                quantity: 100.0 * (i + 1) as f64,
+                cost_price: 10.0,
+                currency: "CNY".to_string(),
+                market_price: None,
+                note: String::new(),
+                updated_at: "t".to_string(),
+            });
+        }
+        open_snapshot(&Snapshot {
+            portfolio,
+            news: Vec::new(),
+            runs: Vec::new(),
+            database: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_select_with_params_and_row_limit() {
+        let conn = snapshot();
+        let limit = Duration::from_secs(5);
+        let result = run_query(
+            &conn,
+            "SELECT symbol, quantity FROM holdings WHERE quantity >= ? ORDER BY quantity",
+            &[Value::from(200)],
+            10,
+            limit,
+        )
+        .unwrap();
+        assert_eq!(result.columns, vec!["symbol", "quantity"]);
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.rows[0][0], Value::from("SZ000001"));
+        assert!(!result.truncated);
+
+        let capped = run_query(&conn, "SELECT * FROM holdings", &[], 2, limit).unwrap();
+        assert_eq!(capped.rows.len(), 2);
+        assert!(capped.truncated);
+    }
+
+    #[test]
+    fn test_writes_and_slow_queries_rejected() {
+        let conn = snapshot();
+        let limit = Duration::from_secs(5);
+        for sql in [
+            "DELETE FROM holdings",
+            "SELECT 1; DELETE FROM holdings",
+            "WITH x AS (SELECT 1) DELETE FROM holdings",
+            "ATTACH DATABASE 'x.db' AS x",
+            "PRAGMA query_only = OFF",
+        ] {
+            assert!(run_query(&conn, sql, &[], 10, limit).is_err(), "{}", sql);
+        }
+        let count = run_query(&conn, "SELECT COUNT(*) FROM holdings", &[], 10, limit).unwrap();
+        assert_eq!(count.rows[0][0], Value::from(3));
+
+        let endless =
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT MAX(i) FROM n";
+        let err = run_query(&conn, endless, &[], 10, Duration::from_millis(200)).unwrap_err();
+        assert!(err.contains("time limit"));
+    }
+
+    #[test]
+    fn test_app_database_is_attached_read_only() {
+        use crate::db::Watchlists;
+
+        let dir = std::env::temp_dir().join(format!("ssi-sql #1?-{}", std::process::id()));
+        let path = dir.join("app.db");
+        let db = Database::open(path.clone()).unwrap();
+        {
+            let conn = db.conn().unwrap();
+            let list = Watchlists(&conn).create("自选").unwrap();
+            Watchlists(&conn)
+                .add_symbol(&list.id, "SH600519", "")
+                .unwrap();
+        }
+        let conn = open_snapshot(&Snapshot {
+            portfolio: Portfolio::default(),
+            news: Vec::new(),
+            runs: Vec::new(),
+            database: Some(path),
+        })
+        .unwrap();
+
+        let limit = Duration::from_secs(5);
+        let sql = "SELECT w.name, i.symbol FROM watchlists w
+                   JOIN watchlist_items i ON i.watchlist_id = w.id";
+        let result = run_query(&conn, sql, &[], 10, limit).unwrap();
+        assert_eq!(
+            result.rows,
+            [[Value::from("自选"), Value::from("SH600519")]]
+        );
+        let count = run_query(&conn, "SELECT COUNT(*) FROM notifications", &[], 10, limit);
+        assert_eq!(count.unwrap().rows[0][0], Value::from(0));
+
+        // Neither the pragma nor the read-only attachment lets a write through
+        conn.execute_batch("PRAGMA query_only = OFF").unwrap();
+        assert!(conn.execute("DELETE FROM db.watchlist_items", []).is_err());
+        drop(conn);
+        drop(db);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,251 @@
+//! Trading strategies driven by the backtest engine.
+//!
+//! A strategy sees the full bar series up front (so indicator series can be
+//! computed once) and is then asked, bar by bar, for the fraction of equity
+//! it wants invested. It must only look at bars up to and including the one
+//! it is asked about; the engine fills the resulting order on the next bar.
+//!
+//! Strategies the user keeps are saved with their parameter sets and latest
+//! backtest summary in `strategies.json`.
+
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::backtest::BacktestMetrics;
+use crate::explain::{Comparison, Condition};
+use crate::indicators::{rsi, sma, Series};
+use crate::models::Bar;
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+/// Serializable description of a strategy and its parameters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StrategySpec {
+    /// Fully invested while the fast SMA is above the slow SMA
+    MaCross { fast: usize, slow: usize },
+    /// Buy when RSI falls below `lower`, exit when it rises above `upper`
+    RsiReversion {
+        period: usize,
+        lower: f64,
+        upper: f64,
+    },
+}
+
+pub trait Strategy: Send {
+    /// Precompute anything needed over the whole series
+    fn prepare(&mut self, bars: &[Bar]);
+
+    /// Target fraction of equity to hold after bar `i` closes, or `None` to keep the position
+    fn target(&self, i: usize) -> Option<f64>;
+
+    /// The checks behind the target at bar `i`, with their computed values
+    fn conditions(&self, i: usize) -> Vec<Condition>;
+}
+
+impl StrategySpec {
+    pub fn build(&self) -> Result<Box<dyn Strategy>, String> {
+        match *self {
+            StrategySpec::MaCross { fast, slow } => {
+                if fast == 0 || fast >= slow {
+                    return Err(format!(
+                        "Invalid MA cross periods: fast {} must be positive and below slow {}",
+                        fast, slow
+                    ));
+                }
+                Ok(Box::new(MaCross {
+                    fast,
+                    slow,
+                    fast_ma: Vec::new(),
+                    slow_ma: Vec::new(),
+                }))
+            }
+            StrategySpec::RsiReversion {
+                period,
+                lower,
+                upper,
+            } => {
+                if period == 0 || !(0.0..upper).contains(&lower) || upper > 100.0 {
+                    return Err(format!(
+                        "Invalid RSI reversion parameters: period {}, bands {}–{}",
+                        period, lower, upper
+                    ));
+                }
+                Ok(Box::new(RsiReversion {
+                    period,
+                    lower,
+                    upper,
+                    rsi: Vec::new(),
+                }))
+            }
+        }
+    }
+}
+
+fn closes(bars: &[Bar]) -> Vec<f64> {
+    bars.iter().map(|b| b.close).collect()
+}
+
+struct MaCross {
+    fast: usize,
+    slow: usize,
+    fast_ma: Series,
+    slow_ma: Series,
+}
+
+impl Strategy for MaCross {
+    fn prepare(&mut self, bars: &[Bar]) {
+        let closes = closes(bars);
+        self.fast_ma = sma(&closes, self.fast);
+        self.slow_ma = sma(&closes, self.slow);
+    }
+
+    fn target(&self, i: usize) -> Option<f64> {
+        let (fast, slow) = (
+            self.fast_ma.get(i)?.as_ref()?,
+            self.slow_ma.get(i)?.as_ref()?,
+        );
+        Some(if fast > slow { 1.0 } else { 0.0 })
+    }
+
+    fn conditions(&self, i: usize) -> Vec<Condition> {
+        let fast = self.fast_ma.get(i).copied().flatten();
+        let slow = self.slow_ma.get(i).copied().flatten();
+        let (Some(fast), Some(slow)) = (fast, slow) else {
+            return Vec::new();
+        };
+        vec![
+            Condition::new(format!("SMA({})", self.fast), fast, Comparison::Above, slow)
+                .against(format!("SMA({})", self.slow)),
+        ]
+    }
+}
+
+struct RsiReversion {
+    period: usize,
+    lower: f64,
+    upper: f64,
+    rsi: Series,
+}
+
+impl Strategy for RsiReversion {
+    fn prepare(&mut self, bars: &[Bar]) {
+        self.rsi = rsi(&closes(bars), self.period);
+    }
+
+    fn target(&self, i: usize) -> Option<f64> {
+        let value = (*self.rsi.get(i)?)?;
+        if value < self.lower {
+            Some(1.0)
+        } else if value > self.upper {
+            Some(0.0)
+        } else {
+            None
+        }
+    }
+
+    fn conditions(&self, i: usize) -> Vec<Condition> {
+        let Some(value) = self.rsi.get(i).copied().flatten() else {
+            return Vec::new();
+        };
+        let metric = format!("RSI({})", self.period);
+        vec![
+            Condition::new(metric.clone(), value, Comparison::Below, self.lower),
+            Condition::new(metric, value, Comparison::Above, self.upper),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedStrategy {
+    pub id: String,
+    pub name: String,
+    pub spec: StrategySpec,
+    /// Alternative parameterizations kept alongside the main spec
+    #[serde(default)]
+    pub parameter_sets: Vec<StrategySpec>,
+    /// Metrics of the most recent backtest
+    #[serde(default)]
+    pub summary: Option<BacktestMetrics>,
+    pub created_at: String,
+}
+
+pub struct StrategyStore {
+    path: PathBuf,
+    strategies: RwLock<Vec<SavedStrategy>>,
+}
+
+impl StrategyStore {
+    pub fn load(path: PathBuf) -> Self {
+        let strategies = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            strategies: RwLock::new(strategies),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<SavedStrategy> {
+        self.strategies
+            .read()
+            .unwrap()
+            .iter()
+            .find(|s| s.id == id)
+            .cloned()
+    }
+
+    pub fn list(&self) -> Vec<SavedStrategy> {
+        self.strategies.read().unwrap().clone()
+    }
+
+    /// Insert or replace a strategy by id
+    pub fn save(&self, strategy: SavedStrategy) -> Result<(), String> {
+        strategy.spec.build()?;
+        let mut strategies = self.strategies.write().unwrap();
+        match strategies.iter_mut().find(|s| s.id == strategy.id) {
+            Some(existing) => *existing = strategy,
+            None => strategies.push(strategy),
+        }
+        let content = serde_json::to_string(&*strategies)
+            .map_err(|e| format!("Failed to serialize strategies: {}", e))?;
+        write_to_file(&self.path, &content).map_err(|e| format!("Failed to save strategies: {}", e))
+    }
+}
+
+/// Save a strategy; pass an existing `id` to update it
+#[tauri::command]
+pub fn save_strategy(
+    store: State<'_, StrategyStore>,
+    id: Option<String>,
+    name: String,
+    spec: StrategySpec,
+    parameter_sets: Vec<StrategySpec>,
+    summary: Option<BacktestMetrics>,
+) -> Result<SavedStrategy, String> {
+    let created_at = id
+        .as_deref()
+        .and_then(|id| store.get(id))
+        .map_or_else(get_timestamp, |s| s.created_at);
+    let strategy = SavedStrategy {
+        id: id.unwrap_or_else(|| generate_id("strategy")),
+        name,
+        spec,
+        parameter_sets,
+        summary,
+        created_at,
+    };
+    store.save(strategy.clone())?;
+    info!("Saved strategy {} ({})", strategy.name, strategy.id);
+    Ok(strategy)
+}
+
+/// List saved strategies
+#[tauri::command]
+pub fn list_strategies(store: State<'_, StrategyStore>) -> Result<Vec<SavedStrategy>, String> {
+    Ok(store.list())
+}
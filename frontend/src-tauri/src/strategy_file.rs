@@ -0,0 +1,339 @@
+//! `.ssistrat` strategy sharing files.
+//!
+//! A file is a JSON envelope holding a versioned payload (the strategy spec,
+//! its parameter sets and backtest summary) plus an Ed25519 signature made
+//! with this installation's signing key. The payload is kept as the exact
+//! JSON text that was signed, so verifying never depends on how numbers
+//! reparse. Imports verify the signature before anything else, so a file
+//! edited after export is rejected, and then migrate the payload from older
+//! format versions.
+//!
+//! Anyone can re-sign an edited file with their own key, so a valid
+//! signature only says who signed it. A file signed by another installation
+//! is therefore only previewed, with its signer's fingerprint, until the
+//! user confirms the import.
+
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use log::{info, warn};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::backtest::BacktestMetrics;
+use crate::secure_store::{restrict_permissions, write_private};
+use crate::strategy::{SavedStrategy, StrategySpec, StrategyStore};
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+pub const FILE_EXTENSION: &str = "ssistrat";
+
+const FORMAT: &str = "ssistrat";
+
+/// Current payload version
+const FORMAT_VERSION: u32 = 1;
+
+/// Payload upgrades; entry `i` turns a version `i + 1` payload into version `i + 2`
+const MIGRATIONS: &[fn(&mut Value)] = &[];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StrategyPayload {
+    name: String,
+    spec: StrategySpec,
+    #[serde(default)]
+    parameter_sets: Vec<StrategySpec>,
+    #[serde(default)]
+    summary: Option<BacktestMetrics>,
+    exported_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StrategyFile {
+    format: String,
+    version: u32,
+    /// The payload's JSON text; files from before it was kept as text hold
+    /// the payload object itself
+    payload: Value,
+    /// Hex-encoded Ed25519 public key of the exporter
+    public_key: String,
+    /// Hex-encoded signature over the version and payload
+    signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedStrategy {
+    strategy: SavedStrategy,
+    /// Short fingerprint of the key that signed the file
+    signer: String,
+    /// Whether the file was exported from this installation
+    own_signature: bool,
+    migrated_from: Option<u32>,
+    /// False while a file signed elsewhere awaits confirmation
+    saved: bool,
+}
+
+/// Message covered by the signature: the version and the payload text as
+/// written, or for files holding a payload object, the object re-serialized
+/// (`serde_json` maps serialize with sorted keys)
+fn signed_bytes(version: u32, payload: &Value) -> Vec<u8> {
+    match payload {
+        Value::String(text) => {
+            let mut bytes = version.to_be_bytes().to_vec();
+            bytes.extend_from_slice(text.as_bytes());
+            bytes
+        }
+        payload => serde_json::to_vec(&json!({ "version": version, "payload": payload }))
+            .unwrap_or_default(),
+    }
+}
+
+/// The payload of a verified file
+fn payload_value(payload: Value) -> Result<Value, String> {
+    match payload {
+        Value::String(text) => serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to read strategy file payload: {}", e)),
+        payload => Ok(payload),
+    }
+}
+
+fn fingerprint(key: &VerifyingKey) -> String {
+    hex::encode(&key.as_bytes()[..8])
+}
+
+/// This installation's signing key
+pub struct SigningIdentity {
+    key: SigningKey,
+}
+
+impl SigningIdentity {
+    /// Load the key from `path`, generating and storing one owner-only on
+    /// first use
+    pub fn load_or_create(path: PathBuf) -> Self {
+        let stored = read_from_file(&path)
+            .ok()
+            .and_then(|content| hex::decode(content.trim()).ok())
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+        let key = match stored {
+            Some(bytes) => {
+                // Keys stored by older versions were left world-readable
+                restrict_permissions(&path);
+                SigningKey::from_bytes(&bytes)
+            }
+            None => {
+                let mut bytes = [0u8; 32];
+                rand::rngs::OsRng.fill_bytes(&mut bytes);
+                if let Err(e) = write_private(&path, &hex::encode(bytes)) {
+                    warn!("Failed to store strategy signing key: {}", e);
+                }
+                SigningKey::from_bytes(&bytes)
+            }
+        };
+        Self { key }
+    }
+
+    fn sign(&self, version: u32, payload: Value) -> StrategyFile {
+        let signature = self.key.sign(&signed_bytes(version, &payload));
+        StrategyFile {
+            format: FORMAT.to_string(),
+            version,
+            payload,
+            public_key: hex::encode(self.key.verifying_key().as_bytes()),
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    fn verify(file: &StrategyFile) -> Result<VerifyingKey, String> {
+        let invalid = || "Strategy file signature is malformed".to_string();
+        let key_bytes: [u8; 32] = hex::decode(&file.public_key)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(invalid)?;
+        let signature_bytes: [u8; 64] = hex::decode(&file.signature)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(invalid)?;
+        let key = VerifyingKey::from_bytes(&key_bytes).map_err(|_| invalid())?;
+        key.verify(
+            &signed_bytes(file.version, &file.payload),
+            &Signature::from_bytes(&signature_bytes),
+        )
+        .map_err(|_| "Strategy file has been modified since it was signed".to_string())?;
+        Ok(key)
+    }
+}
+
+fn migrate(mut payload: Value, version: u32) -> Result<Value, String> {
+    if version == 0 || version > FORMAT_VERSION {
+        return Err(format!(
+            "Unsupported strategy file version {} (this app reads up to {})",
+            version, FORMAT_VERSION
+        ));
+    }
+    for upgrade in &MIGRATIONS[(version - 1) as usize..] {
+        upgrade(&mut payload);
+    }
+    Ok(payload)
+}
+
+fn encode(identity: &SigningIdentity, strategy: &SavedStrategy) -> Result<String, String> {
+    let payload = serde_json::to_string(&StrategyPayload {
+        name: strategy.name.clone(),
+        spec: strategy.spec.clone(),
+        parameter_sets: strategy.parameter_sets.clone(),
+        summary: strategy.summary.clone(),
+        exported_at: get_timestamp(),
+    })
+    .map_err(|e| format!("Failed to serialize strategy: {}", e))?;
+    serde_json::to_string_pretty(&identity.sign(FORMAT_VERSION, Value::String(payload)))
+        .map_err(|e| format!("Failed to serialize strategy file: {}", e))
+}
+
+fn decode(identity: &SigningIdentity, content: &str) -> Result<ImportedStrategy, String> {
+    let file: StrategyFile = serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse strategy file: {}", e))?;
+    if file.format != FORMAT {
+        return Err(format!("Not a strategy file (format {:?})", file.format));
+    }
+    let signer = SigningIdentity::verify(&file)?;
+    let payload = payload_value(file.payload)?;
+    let payload: StrategyPayload = serde_json::from_value(migrate(payload, file.version)?)
+        .map_err(|e| format!("Failed to read strategy file payload: {}", e))?;
+    payload.spec.build()?;
+
+    Ok(ImportedStrategy {
+        strategy: SavedStrategy {
+            id: generate_id("strategy"),
+            name: payload.name,
+            spec: payload.spec,
+            parameter_sets: payload.parameter_sets,
+            summary: payload.summary,
+            created_at: get_timestamp(),
+        },
+        signer: fingerprint(&signer),
+        own_signature: signer == identity.key.verifying_key(),
+        migrated_from: (file.version < FORMAT_VERSION).then_some(file.version),
+        saved: false,
+    })
+}
+
+/// Export a saved strategy as a signed `.ssistrat` file
+#[tauri::command]
+pub fn export_strategy(
+    store: State<'_, StrategyStore>,
+    identity: State<'_, SigningIdentity>,
+    id: String,
+    path: String,
+) -> Result<(), String> {
+    let strategy = store
+        .get(&id)
+        .ok_or_else(|| format!("Strategy not found: {}", id))?;
+    let mut path = PathBuf::from(path);
+    if path.extension().is_none() {
+        path.set_extension(FILE_EXTENSION);
+    }
+    write_to_file(&path, &encode(&identity, &strategy)?)
+        .map_err(|e| format!("Failed to write strategy file: {}", e))?;
+    info!("Exported strategy {} to {:?}", id, path);
+    Ok(())
+}
+
+/// Verify and import a `.ssistrat` file as a new saved strategy.
+///
+/// A file signed by another installation is returned unsaved for the user
+/// to check its signer, and saved when imported again with `confirmed`.
+#[tauri::command]
+pub fn import_strategy(
+    store: State<'_, StrategyStore>,
+    identity: State<'_, SigningIdentity>,
+    path: String,
+    confirmed: Option<bool>,
+) -> Result<ImportedStrategy, String> {
+    let content = read_from_file(Path::new(&path))
+        .map_err(|e| format!("Failed to read strategy file: {}", e))?;
+    let mut imported = decode(&identity, &content)?;
+    if !imported.own_signature && !confirmed.unwrap_or(false) {
+        info!(
+            "Strategy file {} is signed by {}, awaiting confirmation",
+            path, imported.signer
+        );
+        return Ok(imported);
+    }
+    store.save(imported.strategy.clone())?;
+    imported.saved = true;
+    info!(
+        "Imported strategy {} signed by {}",
+        imported.strategy.name, imported.signer
+    );
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_tamper_detection() {
+        let path = std::env::temp_dir().join(format!("ssi-signing-{}.key", std::process::id()));
+        let identity = SigningIdentity::load_or_create(path.clone());
+        let strategy = SavedStrategy {
+            id: "strategy-1".to_string(),
+            name: "Golden cross".to_string(),
+            spec: StrategySpec::MaCross { fast: 5, slow: 20 },
+            parameter_sets: vec![StrategySpec::MaCross { fast: 10, slow: 60 }],
+            summary: None,
+            created_at: get_timestamp(),
+        };
+
+        let content = encode(&identity, &strategy).unwrap();
+        let imported = decode(&identity, &content).unwrap();
+        assert_eq!(imported.strategy.spec, strategy.spec);
+        assert_eq!(imported.strategy.parameter_sets, strategy.parameter_sets);
+        assert_ne!(imported.strategy.id, strategy.id);
+        assert!(imported.own_signature);
+        assert_eq!(imported.migrated_from, None);
+
+        // The reloaded key is the same identity
+        let reloaded = SigningIdentity::load_or_create(path.clone());
+        assert!(decode(&reloaded, &content).unwrap().own_signature);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let tampered = content.replace("\\\"slow\\\":20", "\\\"slow\\\":30");
+        assert_ne!(tampered, content);
+        assert!(decode(&identity, &tampered)
+            .unwrap_err()
+            .contains("modified"));
+
+        // Metrics that don't survive a reparse bit for bit still verify
+        let mut measured = strategy.clone();
+        measured.summary = Some(BacktestMetrics {
+            total_return: 0.1 + 0.2,
+            max_drawdown: -1.0 / 3.0,
+            trades: 7,
+            total_costs: 2.0_f64.sqrt() * 1e-7,
+            settlement_blocked: 0,
+        });
+        let content = encode(&identity, &measured).unwrap();
+        assert!(decode(&identity, &content).is_ok());
+
+        // Another installation's file verifies but isn't its own
+        let other_path = path.with_extension("other");
+        let other = SigningIdentity::load_or_create(other_path.clone());
+        let imported = decode(&identity, &encode(&other, &strategy).unwrap()).unwrap();
+        assert!(!imported.own_signature);
+        assert!(!imported.saved);
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(other_path).ok();
+    }
+
+    #[test]
+    fn test_migrations_cover_every_version() {
+        assert_eq!(MIGRATIONS.len() as u32 + 1, FORMAT_VERSION);
+        assert!(migrate(json!({}), FORMAT_VERSION + 1).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+//! Streaming quotes over the Sina WebSocket feed.
+//!
+//! While quote streaming is enabled in the polling settings, a socket to
+//! Sina's feed is kept open for every subscribed A-share symbol and each
+//! pushed record goes through the same dedup as polled quotes. The socket is
+//! pinged every [`HEARTBEAT`]; one that has sent nothing, not even a pong,
+//! for [`STALE_AFTER`] is dropped. A dropped connection is retried with
+//! exponential backoff, and since the feed subscribes through its URL every
+//! reconnect subscribes the symbols wanted at that moment; a change in the
+//! subscriptions reconnects straight away. Quote polling skips only the
+//! symbols on a connected stream, so a dead feed costs nothing but latency.
+//! When more symbols are subscribed than the feed takes, the ones with the
+//! fastest refresh profile get the slots, and symbols whose watchlists want
+//! no refresh at the moment aren't streamed.
+//!
+//! The socket validates TLS against the bundled roots and the host pins, and
+//! doesn't go through proxies: when the Sina route is a proxy, or the system
+//! proxy is set, quotes stay on polling. State changes reach the frontend as
+//! `quote-stream-state` events.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::politeness::{source_policy, PolicyEngine};
+use crate::polling::jittered;
+use crate::presence;
+use crate::proxy::ProxyRoute;
+use crate::quotes::{self, provider_code};
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+
+const SOURCE: &str = "sina";
+/// Interval between pings and subscription checks
+const HEARTBEAT: Duration = Duration::from_secs(15);
+/// Silence after which a connection is considered dead
+const STALE_AFTER: Duration = Duration::from_secs(45);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to look for something to stream while idle
+const IDLE_CHECK: Duration = Duration::from_secs(5);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// The feed takes this many symbols in one URL
+const MAX_SYMBOLS: usize = 200;
+
+type Socket = WebSocketStream<TlsStream<TcpStream>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum StreamState {
+    /// Streaming is off, blocked by a proxy, paused while the screen is
+    /// locked, or there is nothing to stream
+    Idle,
+    Connecting {
+        attempt: u32,
+    },
+    Connected {
+        symbols: usize,
+    },
+    Reconnecting {
+        attempt: u32,
+        retry_in_ms: u64,
+        error: String,
+    },
+}
+
+pub struct QuoteStream {
+    state: Mutex<StreamState>,
+    /// Provider codes on the connected socket
+    streamed: Mutex<Vec<String>>,
+}
+
+impl Default for QuoteStream {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(StreamState::Idle),
+            streamed: Mutex::default(),
+        }
+    }
+}
+
+impl QuoteStream {
+    /// Whether the connected stream delivers quotes for `symbol`
+    pub fn covers(&self, symbol: &str) -> bool {
+        provider_code(symbol).is_some_and(|code| self.streamed.lock().unwrap().contains(&code))
+    }
+
+    fn state(&self) -> StreamState {
+        self.state.lock().unwrap().clone()
+    }
+
+    fn set(&self, app: &AppHandle, state: StreamState) {
+        if !matches!(state, StreamState::Connected { .. }) {
+            self.streamed.lock().unwrap().clear();
+        }
+        {
+            let mut current = self.state.lock().unwrap();
+            if *current == state {
+                return;
+            }
+            *current = state.clone();
+        }
+        if let Err(e) = app.emit("quote-stream-state", state) {
+            warn!("Failed to emit quote-stream-state event: {}", e);
+        }
+    }
+}
+
+/// Delay before reconnect attempt `attempt`, doubling from one second up to a minute
+fn backoff(attempt: u32) -> Duration {
+    MIN_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+/// Whether connections along `route` can go straight to the provider
+fn reaches_directly(route: &ProxyRoute) -> bool {
+    match route {
+        ProxyRoute::Direct => true,
+        ProxyRoute::Proxy { .. } => false,
+        ProxyRoute::System => ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+            .iter()
+            .all(|var| std::env::var_os(var).map_or(true, |value| value.is_empty())),
+    }
+}
+
+fn stream_url(codes: &[String]) -> String {
+    format!("wss://hq.sinajs.cn/wskt?list={}", codes.join(","))
+}
+
+/// Provider codes to stream now; empty when the stream should be idle
+fn wanted(app: &AppHandle) -> Vec<String> {
+    let settings = app.state::<SettingsStore>().get();
+    let route = settings.proxies.get(SOURCE).cloned().unwrap_or_default();
+    if !settings.polling.stream_quotes
+        || !reaches_directly(&route)
+        || app.state::<SnapshotClock>().is_frozen()
+        || presence::paused(app)
+    {
+        return Vec::new();
+    }
+    // Slots go to the symbols refreshed most often; ones no list wants refreshed now get none
+    let mut ranked: Vec<(Duration, String)> =
+        quotes::quote_intervals(app, chrono::Utc::now().timestamp())
+            .into_iter()
+            .filter_map(|(symbol, interval)| Some((interval?, provider_code(&symbol)?)))
+            .collect();
+    ranked.sort();
+    ranked.truncate(MAX_SYMBOLS);
+    let mut codes: Vec<String> = ranked.into_iter().map(|(_, code)| code).collect();
+    codes.sort();
+    codes
+}
+
+async fn connect(app: &AppHandle, url: &str) -> Result<Socket, String> {
+    let policy = source_policy(SOURCE)?;
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .ok_or_else(|| format!("Invalid stream URL: {}", url))?;
+    let policies = app.state::<PolicyEngine>();
+    let _permit = policies.admit(SOURCE, url).await?;
+
+    let tcp = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), 443)))
+        .await
+        .map_err(|_| format!("Timed out connecting to {}", host))?
+        .map_err(|e| format!("Failed to connect to {}: {}", host, e))?;
+    let server_name = rustls::ServerName::try_from(host.as_str())
+        .map_err(|e| format!("Invalid host name {}: {}", host, e))?;
+    let tls = TlsConnector::from(policies.stream_tls(SOURCE)?)
+        .connect(server_name, tcp)
+        .await
+        .map_err(|e| match policies.pin_failures().last_for(&host) {
+            Some(failure) => failure.describe(),
+            None => format!("TLS handshake with {} failed: {}", host, e),
+        })?;
+
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| format!("Invalid stream URL {}: {}", url, e))?;
+    let headers = request.headers_mut();
+    headers.insert("User-Agent", HeaderValue::from_static(policy.user_agent));
+    if let Some(referer) = policy.referer {
+        headers.insert("Referer", HeaderValue::from_static(referer));
+    }
+    let (socket, _) = tokio_tungstenite::client_async(request, tls)
+        .await
+        .map_err(|e| format!("WebSocket handshake with {} failed: {}", host, e))?;
+    Ok(socket)
+}
+
+/// Stream `codes` until the connection fails or the wanted symbols change
+async fn run(app: &AppHandle, codes: &[String], attempt: &mut u32) -> Result<(), String> {
+    let mut socket = connect(app, &stream_url(codes)).await?;
+    *attempt = 0;
+    info!("Quote stream connected for {} symbols", codes.len());
+    let stream = app.state::<QuoteStream>();
+    stream.set(
+        app,
+        StreamState::Connected {
+            symbols: codes.len(),
+        },
+    );
+    *stream.streamed.lock().unwrap() = codes.to_vec();
+
+    let mut heartbeat = tokio::time::interval(HEARTBEAT);
+    heartbeat.tick().await;
+    let mut last_frame = Instant::now();
+    loop {
+        tokio::select! {
+            frame = socket.next() => {
+                let frame = frame
+                    .ok_or("Quote stream closed")?
+                    .map_err(|e| format!("Quote stream failed: {}", e))?;
+                last_frame = Instant::now();
+                match frame {
+                    Message::Text(text) => quotes::publish(app, quotes::parse_sina(&text)),
+                    Message::Close(_) => return Err("Quote stream closed by the server".to_string()),
+                    _ => {}
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_frame.elapsed() > STALE_AFTER {
+                    return Err(format!(
+                        "Quote stream silent for {}s",
+                        last_frame.elapsed().as_secs()
+                    ));
+                }
+                if wanted(app) != codes {
+                    socket.close(None).await.ok();
+                    return Ok(());
+                }
+                socket
+                    .send(Message::Ping(Vec::new()))
+                    .await
+                    .map_err(|e| format!("Quote stream heartbeat failed: {}", e))?;
+            }
+        }
+    }
+}
+
+/// Keep the quote stream connected to the subscribed symbols
+pub fn start_quote_stream(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let stream = handle.state::<QuoteStream>();
+        // Consecutive failed attempts since the last good connection
+        let mut attempt = 0;
+        loop {
+            let codes = wanted(&handle);
+            if codes.is_empty() {
+                attempt = 0;
+                stream.set(&handle, StreamState::Idle);
+                presence::sleep_or_resume(&handle, IDLE_CHECK).await;
+                continue;
+            }
+            stream.set(
+                &handle,
+                StreamState::Connecting {
+                    attempt: attempt + 1,
+                },
+            );
+            if let Err(e) = run(&handle, &codes, &mut attempt).await {
+                attempt += 1;
+                let jitter = handle.state::<SettingsStore>().get().polling.jitter;
+                let delay = jittered(backoff(attempt), jitter);
+                warn!("{}; reconnecting in {:?}", e, delay);
+                stream.set(
+                    &handle,
+                    StreamState::Reconnecting {
+                        attempt,
+                        retry_in_ms: delay.as_millis() as u64,
+                        error: e,
+                    },
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_quote_stream_state(stream: State<'_, QuoteStream>) -> Result<StreamState, String> {
+    Ok(stream.state())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_and_routes() {
+        let delays: Vec<u64> = (1..=8).map(|n| backoff(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+
+        assert!(reaches_directly(&ProxyRoute::Direct));
+        assert!(!reaches_directly(&ProxyRoute::Proxy {
+            url: "socks5://127.0.0.1:1080".to_string(),
+            username: None,
+            password: None,
+        }));
+        assert!(source_policy(SOURCE)
+            .unwrap()
+            .allows(&stream_url(&["sh600519".to_string()])));
+    }
+}
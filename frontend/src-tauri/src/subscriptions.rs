@@ -0,0 +1,283 @@
+//! Reference-counted symbol subscriptions.
+//!
+//! UI components acquire a subscription for each symbol they display and
+//! release it when they close. The effective level of a symbol is the highest
+//! level anyone holds, and background refresh loops ask the registry which
+//! symbols need what, so polling follows what is actually on screen. Handles
+//! held by a window are released when that window is destroyed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, State, Window};
+
+use crate::symbols::symbol_key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionLevel {
+    /// Live quote only, e.g. a watchlist row
+    Quote,
+    /// Quote plus bar and indicator refresh for an open chart
+    Chart,
+    /// Everything, including news polling, for a focused detail view
+    Detail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionHandle {
+    pub id: u64,
+}
+
+/// Payload of the `subscriptions-changed` event; `level` is `None` once nobody holds the symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SymbolDemand {
+    pub symbol: String,
+    pub level: Option<SubscriptionLevel>,
+    pub refs: usize,
+}
+
+struct Subscription {
+    symbol: String,
+    level: SubscriptionLevel,
+    owner: String,
+}
+
+#[derive(Default)]
+pub struct SubscriptionRegistry {
+    next_id: AtomicU64,
+    subscriptions: Mutex<HashMap<u64, Subscription>>,
+}
+
+impl SubscriptionRegistry {
+    fn demand(subscriptions: &HashMap<u64, Subscription>, symbol: &str) -> SymbolDemand {
+        let held = subscriptions.values().filter(|s| s.symbol == symbol);
+        SymbolDemand {
+            symbol: symbol.to_string(),
+            level: held.clone().map(|s| s.level).max(),
+            refs: held.count(),
+        }
+    }
+
+    /// Take a subscription, returning its id and the symbol's new demand if its level changed
+    pub fn acquire(
+        &self,
+        symbol: &str,
+        level: SubscriptionLevel,
+        owner: &str,
+    ) -> (u64, Option<SymbolDemand>) {
+        let symbol = symbol_key(symbol);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let before = Self::demand(&subscriptions, &symbol).level;
+        subscriptions.insert(
+            id,
+            Subscription {
+                symbol: symbol.clone(),
+                level,
+                owner: owner.to_string(),
+            },
+        );
+        let after = Self::demand(&subscriptions, &symbol);
+        (id, (after.level != before).then_some(after))
+    }
+
+    fn remove_where(&self, matches: impl Fn(&u64, &Subscription) -> bool) -> Vec<SymbolDemand> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let removed: Vec<(u64, String)> = subscriptions
+            .iter()
+            .filter(|(id, s)| matches(id, s))
+            .map(|(id, s)| (*id, s.symbol.clone()))
+            .collect();
+        let mut before = BTreeMap::new();
+        for (_, symbol) in &removed {
+            before
+                .entry(symbol.clone())
+                .or_insert_with(|| Self::demand(&subscriptions, symbol).level);
+        }
+        for (id, _) in &removed {
+            subscriptions.remove(id);
+        }
+        before
+            .into_iter()
+            .map(|(symbol, level)| (Self::demand(&subscriptions, &symbol), level))
+            .filter(|(after, level)| after.level != *level)
+            .map(|(after, _)| after)
+            .collect()
+    }
+
+    /// Make an owner hold exactly `symbols` at `level`, returning symbols
+    /// whose level changed
+    pub fn hold(
+        &self,
+        owner: &str,
+        symbols: &[String],
+        level: SubscriptionLevel,
+    ) -> Vec<SymbolDemand> {
+        let mut wanted: Vec<String> = symbols.iter().map(|s| symbol_key(s)).collect();
+        wanted.sort();
+        wanted.dedup();
+        if self.symbols_of(owner) == wanted {
+            return Vec::new();
+        }
+        let mut changes = self.release_owner(owner);
+        for symbol in &wanted {
+            let (_, change) = self.acquire(symbol, level, owner);
+            changes.retain(|released| &released.symbol != symbol);
+            changes.extend(change);
+        }
+        changes
+    }
+
+    /// Drop a subscription, returning symbols whose level changed
+    pub fn release(&self, id: u64) -> Vec<SymbolDemand> {
+        self.remove_where(|sid, _| *sid == id)
+    }
+
+    /// Drop every subscription held by a window
+    pub fn release_owner(&self, owner: &str) -> Vec<SymbolDemand> {
+        self.remove_where(|_, s| s.owner == owner)
+    }
+
+    /// Drop the subscriptions of owners that went cold
+    pub fn release_cold(&self, cold: impl Fn(&str) -> bool) -> Vec<SymbolDemand> {
+        self.remove_where(|_, s| cold(&s.owner))
+    }
+
+    /// Subscriptions held and their estimated bytes
+    pub fn usage(&self) -> (usize, usize) {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let bytes = subscriptions
+            .values()
+            .map(|s| std::mem::size_of::<(u64, Subscription)>() + s.symbol.len() + s.owner.len())
+            .sum();
+        (subscriptions.len(), bytes)
+    }
+
+    /// Symbols held at `level` or above
+    pub fn symbols_at(&self, level: SubscriptionLevel) -> Vec<String> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let mut symbols: Vec<String> = subscriptions
+            .values()
+            .filter(|s| s.level >= level)
+            .map(|s| s.symbol.clone())
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    /// Symbols an owner holds, sorted
+    pub fn symbols_of(&self, owner: &str) -> Vec<String> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let mut symbols: Vec<String> = subscriptions
+            .values()
+            .filter(|s| s.owner == owner)
+            .map(|s| s.symbol.clone())
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    pub fn list(&self) -> Vec<SymbolDemand> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let mut symbols: Vec<&String> = subscriptions.values().map(|s| &s.symbol).collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+            .into_iter()
+            .map(|symbol| Self::demand(&subscriptions, symbol))
+            .collect()
+    }
+}
+
+pub fn emit_changes<E: Emitter>(emitter: &E, changes: Vec<SymbolDemand>) {
+    for change in changes {
+        if let Err(e) = emitter.emit("subscriptions-changed", change) {
+            warn!("Failed to emit subscriptions-changed event: {}", e);
+        }
+    }
+}
+
+/// Subscribe the calling window to a symbol at a data level
+#[tauri::command]
+pub fn acquire_symbol(
+    window: Window,
+    registry: State<'_, SubscriptionRegistry>,
+    symbol: String,
+    level: SubscriptionLevel,
+) -> Result<SubscriptionHandle, String> {
+    if symbol.trim().is_empty() {
+        return Err("Symbol must not be empty".to_string());
+    }
+    let (id, change) = registry.acquire(symbol.trim(), level, window.label());
+    emit_changes(&window, change.into_iter().collect());
+    Ok(SubscriptionHandle { id })
+}
+
+/// Release a subscription taken with `acquire_symbol`
+#[tauri::command]
+pub fn release_symbol(
+    window: Window,
+    registry: State<'_, SubscriptionRegistry>,
+    handle: SubscriptionHandle,
+) -> Result<(), String> {
+    emit_changes(&window, registry.release(handle.id));
+    Ok(())
+}
+
+/// Symbols currently subscribed, with their effective level and reference count
+#[tauri::command]
+pub fn list_subscriptions(
+    registry: State<'_, SubscriptionRegistry>,
+) -> Result<Vec<SymbolDemand>, String> {
+    Ok(registry.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_counting() {
+        let registry = SubscriptionRegistry::default();
+        let (watch, change) = registry.acquire("sh600519", SubscriptionLevel::Quote, "main");
+        assert_eq!(change.unwrap().level, Some(SubscriptionLevel::Quote));
+        let (chart, change) = registry.acquire("SH600519", SubscriptionLevel::Chart, "main");
+        assert_eq!(change.unwrap().level, Some(SubscriptionLevel::Chart));
+        let (_, change) = registry.acquire("SH600519", SubscriptionLevel::Quote, "popout");
+        assert!(change.is_none());
+        registry.acquire("SZ000001", SubscriptionLevel::Detail, "popout");
+
+        assert_eq!(
+            registry.symbols_at(SubscriptionLevel::Chart),
+            ["SH600519", "SZ000001"]
+        );
+        let changes = registry.release(chart);
+        assert_eq!(changes[0].level, Some(SubscriptionLevel::Quote));
+        assert_eq!(registry.symbols_at(SubscriptionLevel::Chart), ["SZ000001"]);
+
+        assert!(registry.release(watch).is_empty());
+        let changes = registry.release_owner("popout");
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().all(|c| c.level.is_none() && c.refs == 0));
+        assert!(registry.list().is_empty());
+    }
+
+    #[test]
+    fn test_symbols_are_keyed_like_the_refresh_loops() {
+        let registry = SubscriptionRegistry::default();
+        registry.acquire("600519", SubscriptionLevel::Chart, "main");
+        let (_, change) = registry.acquire(" sh600519 ", SubscriptionLevel::Quote, "popout");
+        assert!(change.is_none());
+        registry.hold("dock", &["000001".to_string()], SubscriptionLevel::Chart);
+        assert_eq!(
+            registry.symbols_at(SubscriptionLevel::Chart),
+            ["SH600519", "SZ000001"]
+        );
+    }
+}
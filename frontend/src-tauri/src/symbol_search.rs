@@ -0,0 +1,248 @@
+//! Offline symbol search.
+//!
+//! The search box matches against an in-memory index of the instrument
+//! master: the code, the Chinese name and the pinyin initials of the name,
+//! so `600519`, `茅台` and `gzmt` all find 贵州茅台. Characters with more than
+//! one reading contribute each of them, so 平安银行 is found by `payh` as
+//! well as `payx`. The index is built at startup and rebuilt whenever the
+//! master refreshes, and a query is a linear scan over a few thousand
+//! precomputed entries, well inside the few milliseconds a keystroke can
+//! spend without any network access.
+
+use std::sync::RwLock;
+
+use pinyin::ToPinyinMulti;
+use serde::Serialize;
+use tauri::State;
+
+use crate::instruments::Instrument;
+use crate::symbols::normalize_symbol;
+
+/// Matches returned when the caller doesn't ask for a number
+const DEFAULT_LIMIT: usize = 20;
+
+/// Readings of a name combined at most, against runs of polyphonic characters
+const MAX_READINGS: usize = 8;
+
+/// Pinyin initials of a name, lowercase, one string per combination of
+/// readings with the most common first; letters and digits in the name are
+/// kept as they are, so `*ST康美` gives `stkm`
+pub fn pinyin_initials(name: &str) -> Vec<String> {
+    let mut readings = vec![String::new()];
+    for c in name.chars() {
+        let letters: Vec<char> = match c.to_pinyin_multi() {
+            Some(multi) => multi.into_iter().fold(Vec::new(), |mut letters, pinyin| {
+                let letter = pinyin.first_letter().chars().next();
+                if let Some(letter) = letter.filter(|l| !letters.contains(l)) {
+                    letters.push(letter);
+                }
+                letters
+            }),
+            None if c.is_ascii_alphanumeric() => vec![c.to_ascii_lowercase()],
+            None => continue,
+        };
+        if letters.is_empty() {
+            continue;
+        }
+        readings = readings
+            .iter()
+            .flat_map(|reading| {
+                letters.iter().map(move |letter| {
+                    let mut reading = reading.clone();
+                    reading.push(*letter);
+                    reading
+                })
+            })
+            .take(MAX_READINGS)
+            .collect();
+    }
+    readings
+}
+
+struct Entry {
+    symbol: String,
+    /// Symbol without its exchange prefix
+    code: String,
+    name: String,
+    initials: Vec<String>,
+}
+
+impl Entry {
+    fn new(instrument: &Instrument) -> Self {
+        let code = instrument
+            .symbol
+            .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+            .to_string();
+        Self {
+            symbol: instrument.symbol.clone(),
+            code,
+            name: instrument.name.clone(),
+            initials: pinyin_initials(&instrument.name),
+        }
+    }
+
+    /// How well the entry matches a query, or `None`; higher is better
+    fn score(&self, query: &Query) -> Option<u32> {
+        if query.symbol.as_deref() == Some(self.symbol.as_str()) {
+            return Some(100);
+        }
+        let text = query.text.as_str();
+        let initials = |matches: fn(&str, &str) -> bool| {
+            self.initials.iter().any(|reading| matches(reading, text))
+        };
+        let tiers = [
+            (self.name == text, 95),
+            (initials(|reading, text| reading == text), 90),
+            (self.code.starts_with(text), 80),
+            (self.name.starts_with(text), 70),
+            (initials(|reading, text| reading.starts_with(text)), 65),
+            (self.name.contains(text), 50),
+            (initials(|reading, text| reading.contains(text)), 40),
+            (self.code.contains(text), 30),
+            (initials(|reading, text| is_subsequence(text, reading)), 20),
+        ];
+        tiers
+            .into_iter()
+            .find(|(matched, _)| *matched)
+            .map(|(_, score)| score)
+    }
+}
+
+/// Whether `needle` can be read off `haystack` in order, skipping characters
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+struct Query {
+    /// The query as a canonical symbol, when it reads as one
+    symbol: Option<String>,
+    /// Lowercased, with any exchange prefix dropped
+    text: String,
+}
+
+impl Query {
+    fn parse(input: &str) -> Self {
+        let text = input.trim().to_lowercase();
+        let text = ["sh", "sz", "bj"]
+            .iter()
+            .find_map(|prefix| text.strip_prefix(prefix))
+            .filter(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+            .map(str::to_string)
+            .unwrap_or(text);
+        Self {
+            symbol: normalize_symbol(input),
+            text,
+        }
+    }
+}
+
+/// A search hit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SymbolMatch {
+    pub symbol: String,
+    pub name: String,
+    pub initials: String,
+    pub score: u32,
+}
+
+/// In-memory search index over the instrument master
+pub struct SymbolIndex {
+    entries: RwLock<Vec<Entry>>,
+}
+
+impl SymbolIndex {
+    pub fn new(instruments: &[Instrument]) -> Self {
+        Self {
+            entries: RwLock::new(instruments.iter().map(Entry::new).collect()),
+        }
+    }
+
+    /// Replace the index after the master changed
+    pub fn rebuild(&self, instruments: &[Instrument]) {
+        let entries = instruments.iter().map(Entry::new).collect();
+        *self.entries.write().unwrap() = entries;
+    }
+
+    /// Best matches first; ties go to the shorter name, then the symbol
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SymbolMatch> {
+        let query = Query::parse(query);
+        if query.text.is_empty() {
+            return Vec::new();
+        }
+        let entries = self.entries.read().unwrap();
+        let mut hits: Vec<(u32, &Entry)> = entries
+            .iter()
+            .filter_map(|entry| entry.score(&query).map(|score| (score, entry)))
+            .collect();
+        hits.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| a.1.name.chars().count().cmp(&b.1.name.chars().count()))
+                .then_with(|| a.1.symbol.cmp(&b.1.symbol))
+        });
+        hits.into_iter()
+            .take(limit)
+            .map(|(score, entry)| SymbolMatch {
+                symbol: entry.symbol.clone(),
+                name: entry.name.clone(),
+                initials: entry.initials[0].clone(),
+                score,
+            })
+            .collect()
+    }
+}
+
+/// Search symbols by code, name or pinyin initials, without the network
+#[tauri::command]
+pub fn search_symbols(
+    index: State<'_, SymbolIndex>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<SymbolMatch>, String> {
+    Ok(index.search(&query, limit.unwrap_or(DEFAULT_LIMIT)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instrument(symbol: &str, name: &str) -> Instrument {
+        Instrument {
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_search_by_code_name_and_initials() {
+        assert_eq!(pinyin_initials("贵州茅台")[0], "gzmt");
+        assert_eq!(pinyin_initials("*ST康美")[0], "stkm");
+        assert!(pinyin_initials("平安银行").contains(&"payh".to_string()));
+
+        let index = SymbolIndex::new(&[
+            instrument("SH600519", "贵州茅台"),
+            instrument("SZ000001", "平安银行"),
+            instrument("SH601318", "中国平安"),
+            instrument("SZ000858", "五粮液"),
+        ]);
+        let symbols = |query: &str| -> Vec<String> {
+            index
+                .search(query, 10)
+                .into_iter()
+                .map(|m| m.symbol)
+                .collect()
+        };
+
+        assert_eq!(symbols("600519"), vec!["SH600519"]);
+        assert_eq!(symbols("sh6005"), vec!["SH600519"]);
+        assert_eq!(symbols("茅台"), vec!["SH600519"]);
+        assert_eq!(symbols("gzmt"), vec!["SH600519"]);
+        // A name prefix outranks the same text further into a name
+        assert_eq!(symbols("平安"), vec!["SZ000001", "SH601318"]);
+        assert_eq!(symbols("wly"), vec!["SZ000858"]);
+        assert_eq!(symbols("payh"), vec!["SZ000001"]);
+        assert_eq!(symbols("gmt"), vec!["SH600519"]);
+        assert!(symbols("xyz").is_empty());
+        assert!(symbols("  ").is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+//! Symbol normalization and classification.
+//!
+//! Symbols arrive typed by hand, pasted from broker exports or returned by
+//! providers, as `600519`, `600519.SS`, `sh600519`, `700.HK` or `BRK-B`.
+//! Everything inside the app keys on one canonical form, `SH600519`,
+//! `HK00700` or `BRK.B`, so commands that take a symbol run it through
+//! [`normalize_symbol`] or [`symbol_key`] before storing or comparing it.
+//! Bare six-digit A-share codes get their exchange from the code ranges each
+//! exchange allocates, which also tell B-shares, ETFs, funds, convertible
+//! bonds and indices apart from ordinary stocks.
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::instruments::InstrumentMaster;
+use crate::models::{Currency, Market};
+
+/// Exchange a symbol is listed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Exchange {
+    Sse,
+    Szse,
+    Bse,
+    Hkex,
+    Us,
+}
+
+/// What kind of security a symbol is, as far as its code tells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityKind {
+    Stock,
+    /// Shanghai and Shenzhen shares quoted in USD and HKD
+    BShare,
+    Etf,
+    /// Listed closed-end funds and LOFs
+    Fund,
+    ConvertibleBond,
+    Index,
+}
+
+/// A validated symbol and what it is
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SymbolInfo {
+    pub symbol: String,
+    pub market: Market,
+    pub exchange: Exchange,
+    pub kind: SecurityKind,
+    pub currency: Currency,
+    /// Name from the instrument master, for A-shares it lists
+    pub name: Option<String>,
+}
+
+/// Canonical form of a symbol: `600519`, `600519.SH` or `sh600519` to
+/// `SH600519`, `700`, `700.HK` or `00700.HK` to `HK00700`, `aapl`, `AAPL.US`
+/// or `BRK-B` to `AAPL` and `BRK.B`; `None` when it isn't a symbol
+pub fn normalize_symbol(input: &str) -> Option<String> {
+    let input = input.trim().to_uppercase();
+    let (code, suffix) = match input.rsplit_once('.') {
+        Some((code, suffix)) if ["SH", "SS", "SZ", "BJ", "HK", "US"].contains(&suffix) => {
+            (code.to_string(), Some(suffix))
+        }
+        _ => (input.clone(), None),
+    };
+    let symbol = match suffix {
+        Some("SS") => format!("SH{}", code),
+        Some(exchange @ ("SH" | "SZ" | "BJ")) => format!("{}{}", exchange, code),
+        Some("HK") => format!("HK{:0>5}", code),
+        Some(_) => code.replace('-', "."),
+        None if is_digits(&code) => match code.len() {
+            1..=5 => format!("HK{:0>5}", code),
+            6 => format!("{}{}", infer_prefix(&code)?, code),
+            _ => return None,
+        },
+        None => match code.strip_prefix("HK") {
+            Some(digits) if is_digits(digits) => format!("HK{:0>5}", digits),
+            _ => code.replace('-', "."),
+        },
+    };
+    Market::of(&symbol).map(|_| symbol)
+}
+
+/// Normalized symbol, or the trimmed and uppercased input when it doesn't
+/// parse, for lookups that shouldn't fail on unfamiliar symbols
+pub fn symbol_key(input: &str) -> String {
+    normalize_symbol(input).unwrap_or_else(|| input.trim().to_uppercase())
+}
+
+fn is_digits(code: &str) -> bool {
+    !code.is_empty() && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Exchange prefix of a bare six-digit A-share code. `000xxx` is read as a
+/// Shenzhen stock rather than a Shanghai index, as it almost always is
+fn infer_prefix(code: &str) -> Option<&'static str> {
+    match &code[..2] {
+        "92" | "43" | "83" | "87" | "88" => Some("BJ"),
+        "60" | "68" | "90" | "11" => Some("SH"),
+        _ if code.starts_with('5') => Some("SH"),
+        "00" | "30" | "20" | "12" | "15" | "16" | "39" => Some("SZ"),
+        _ => None,
+    }
+}
+
+/// Exchange of a canonical symbol
+pub fn exchange_of(symbol: &str) -> Option<Exchange> {
+    match Market::of(symbol)? {
+        Market::Cn => Some(match &symbol[..2] {
+            "SH" => Exchange::Sse,
+            "SZ" => Exchange::Szse,
+            _ => Exchange::Bse,
+        }),
+        Market::Hk => Some(Exchange::Hkex),
+        Market::Us => Some(Exchange::Us),
+    }
+}
+
+/// Kind of a canonical symbol, from the code ranges of its exchange
+pub fn security_kind(symbol: &str) -> Option<SecurityKind> {
+    let exchange = exchange_of(symbol)?;
+    let code = &symbol[2..];
+    let kind = match exchange {
+        Exchange::Sse => match &code[..3] {
+            "000" => SecurityKind::Index,
+            "900" => SecurityKind::BShare,
+            "110" | "111" | "113" | "118" => SecurityKind::ConvertibleBond,
+            "500" | "501" | "505" | "506" => SecurityKind::Fund,
+            _ if code.starts_with('5') => SecurityKind::Etf,
+            _ => SecurityKind::Stock,
+        },
+        Exchange::Szse => match &code[..3] {
+            "399" => SecurityKind::Index,
+            "200" => SecurityKind::BShare,
+            "123" | "127" | "128" => SecurityKind::ConvertibleBond,
+            "159" => SecurityKind::Etf,
+            _ if code.starts_with("15") || code.starts_with("16") => SecurityKind::Fund,
+            _ => SecurityKind::Stock,
+        },
+        Exchange::Bse | Exchange::Hkex | Exchange::Us => SecurityKind::Stock,
+    };
+    Some(kind)
+}
+
+/// Everything the symbol's form tells, without a network lookup
+pub fn symbol_info(input: &str) -> Option<SymbolInfo> {
+    let symbol = normalize_symbol(input)?;
+    let market = Market::of(&symbol)?;
+    let kind = security_kind(&symbol)?;
+    Some(SymbolInfo {
+        exchange: exchange_of(&symbol)?,
+        kind,
+        currency: match kind {
+            // Shanghai B-shares trade in USD, Shenzhen ones in HKD
+            SecurityKind::BShare if symbol.starts_with("SH") => Currency::Usd,
+            SecurityKind::BShare => Currency::Hkd,
+            _ => market.currency(),
+        },
+        market,
+        symbol,
+        name: None,
+    })
+}
+
+/// Normalize and classify a symbol typed by the user
+#[tauri::command]
+pub fn validate_symbol(
+    master: State<'_, InstrumentMaster>,
+    symbol: String,
+) -> Result<SymbolInfo, String> {
+    let mut info =
+        symbol_info(&symbol).ok_or_else(|| format!("Invalid symbol: {}", symbol.trim()))?;
+    info.name = master.name(&info.symbol);
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_symbols() {
+        assert_eq!(normalize_symbol("600519.SS").as_deref(), Some("SH600519"));
+        assert_eq!(normalize_symbol("sz000001").as_deref(), Some("SZ000001"));
+        assert_eq!(normalize_symbol("00700.HK").as_deref(), Some("HK00700"));
+        assert_eq!(normalize_symbol("700.hk").as_deref(), Some("HK00700"));
+        assert_eq!(normalize_symbol("hk9988").as_deref(), Some("HK09988"));
+        assert_eq!(normalize_symbol(" aapl ").as_deref(), Some("AAPL"));
+        assert_eq!(normalize_symbol("BRK-B.US").as_deref(), Some("BRK.B"));
+        assert_eq!(normalize_symbol("123456.HK"), None);
+
+        // Bare codes get their exchange inferred
+        assert_eq!(normalize_symbol("600519").as_deref(), Some("SH600519"));
+        assert_eq!(normalize_symbol("000001").as_deref(), Some("SZ000001"));
+        assert_eq!(normalize_symbol("300750").as_deref(), Some("SZ300750"));
+        assert_eq!(normalize_symbol("830799").as_deref(), Some("BJ830799"));
+        assert_eq!(normalize_symbol("920001").as_deref(), Some("BJ920001"));
+        assert_eq!(normalize_symbol("900901").as_deref(), Some("SH900901"));
+        assert_eq!(normalize_symbol("510300").as_deref(), Some("SH510300"));
+        assert_eq!(normalize_symbol("700").as_deref(), Some("HK00700"));
+        assert_eq!(normalize_symbol("700000"), None);
+        assert_eq!(normalize_symbol("1234567"), None);
+        assert_eq!(symbol_key(" 1234567 "), "1234567");
+    }
+
+    #[test]
+    fn test_classify_symbols() {
+        let kind = |symbol: &str| security_kind(&normalize_symbol(symbol).unwrap()).unwrap();
+        assert_eq!(kind("600519"), SecurityKind::Stock);
+        assert_eq!(kind("688981"), SecurityKind::Stock);
+        assert_eq!(kind("900901"), SecurityKind::BShare);
+        assert_eq!(kind("200002"), SecurityKind::BShare);
+        assert_eq!(kind("510300"), SecurityKind::Etf);
+        assert_eq!(kind("159915"), SecurityKind::Etf);
+        assert_eq!(kind("161725"), SecurityKind::Fund);
+        assert_eq!(kind("113050"), SecurityKind::ConvertibleBond);
+        assert_eq!(kind("123107"), SecurityKind::ConvertibleBond);
+        assert_eq!(kind("SH000001"), SecurityKind::Index);
+        assert_eq!(kind("399001"), SecurityKind::Index);
+
+        let info = symbol_info("200002").unwrap();
+        assert_eq!(info.exchange, Exchange::Szse);
+        assert_eq!(info.currency, Currency::Hkd);
+        assert_eq!(symbol_info("900901").unwrap().currency, Currency::Usd);
+        assert_eq!(symbol_info("430047").unwrap().exchange, Exchange::Bse);
+        assert_eq!(symbol_info("0700.hk").unwrap().exchange, Exchange::Hkex);
+        assert_eq!(symbol_info("msft").unwrap().currency, Currency::Usd);
+        assert!(symbol_info("not a symbol").is_none());
+    }
+}
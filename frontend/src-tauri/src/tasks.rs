@@ -0,0 +1,400 @@
+//! Long-running task registry with cooperative cancellation.
+//!
+//! Commands that may run for a long time (backfills, backtests, report
+//! generation, AI jobs, batch indicator runs) start their work through
+//! [`spawn_task`], which returns a task id immediately. The job receives a
+//! [`CancellationToken`] and is expected to call [`CancellationToken::checkpoint`]
+//! inside its loops; `cancel_task(id)` flips the token and the job unwinds at
+//! its next checkpoint. A task still queued for an executor slot leaves the
+//! queue when cancelled and never starts. Progress is reported through the
+//! job's [`ProgressReporter`] and completion through a `task-finished` event
+//! and an entry in the notification center. Kinds that run for long keep the
+//! system from suspending while they run (see [`power`](crate::power)).
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::executor::{Executor, Priority, ResourceClass};
+use crate::notifications::{self, Notification, NotificationCategory};
+use crate::power::SuspendInhibit;
+use crate::progress::ProgressReporter;
+use crate::utils::get_timestamp;
+
+pub type TaskId = String;
+
+/// Error returned by a job that stopped at a checkpoint
+pub const CANCELLED: &str = "Task cancelled";
+
+/// Finished tasks kept around for `list_tasks`
+const MAX_FINISHED_TASKS: usize = 100;
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    /// Wakes tasks waiting for a slot when they are cancelled
+    notify: Notify,
+}
+
+/// Shared flag polled by a running job
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once the token is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Registered before the check, so a cancel in between still wakes us
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Cooperative checkpoint: returns `Err(CANCELLED)` once cancelled
+    pub fn checkpoint(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    /// Waiting for an executor slot
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskInfo {
+    id: TaskId,
+    kind: String,
+    status: TaskStatus,
+    started_at: String,
+    finished_at: Option<String>,
+    error: Option<String>,
+}
+
+/// Payload of the `task-finished` event
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskFinished {
+    id: TaskId,
+    kind: String,
+    status: TaskStatus,
+    result: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+/// Handed to a background job: its cancellation token and progress reporter
+pub struct TaskContext {
+    pub token: CancellationToken,
+    pub progress: ProgressReporter,
+}
+
+impl TaskContext {
+    pub fn checkpoint(&self) -> Result<(), String> {
+        self.token.checkpoint()
+    }
+
+    pub fn report(&self, stage: &str, done: u64, total: u64) {
+        self.progress.report(stage, done, total, None);
+    }
+
+    /// Id of the task, for events the job emits along the way
+    pub fn task_id(&self) -> &str {
+        self.progress.task_id()
+    }
+}
+
+/// Returned by commands that start a long-running task
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskHandle {
+    pub task_id: TaskId,
+}
+
+struct TaskEntry {
+    info: TaskInfo,
+    token: CancellationToken,
+}
+
+#[derive(Default)]
+pub struct TaskManager {
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<TaskId, TaskEntry>>,
+}
+
+impl TaskManager {
+    /// Register a new task, queued until [`TaskManager::start`], and hand out its token
+    pub fn register(&self, kind: &str) -> (TaskId, CancellationToken) {
+        let id = format!("task-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        let token = CancellationToken::default();
+        let info = TaskInfo {
+            id: id.clone(),
+            kind: kind.to_string(),
+            status: TaskStatus::Queued,
+            started_at: get_timestamp(),
+            finished_at: None,
+            error: None,
+        };
+
+        let mut tasks = self.tasks.lock().unwrap();
+        Self::prune(&mut tasks);
+        tasks.insert(
+            id.clone(),
+            TaskEntry {
+                info,
+                token: token.clone(),
+            },
+        );
+        (id, token)
+    }
+
+    /// Mark a queued task as running; false if it was cancelled while queued
+    pub fn start(&self, id: &str) -> bool {
+        match self.tasks.lock().unwrap().get_mut(id) {
+            Some(entry) if entry.info.status == TaskStatus::Queued => {
+                entry.info.status = TaskStatus::Running;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record the final status of a task
+    pub fn finish(&self, id: &str, status: TaskStatus, error: Option<String>) {
+        if let Some(entry) = self.tasks.lock().unwrap().get_mut(id) {
+            entry.info.status = status;
+            entry.info.finished_at = Some(get_timestamp());
+            entry.info.error = error;
+        }
+    }
+
+    /// Request cancellation; a queued task is cancelled at once. Returns
+    /// false if the task is unknown or done
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.tasks.lock().unwrap().get_mut(id) {
+            Some(entry) if entry.info.status == TaskStatus::Running => {
+                entry.token.cancel();
+                true
+            }
+            Some(entry) if entry.info.status == TaskStatus::Queued => {
+                entry.token.cancel();
+                entry.info.status = TaskStatus::Cancelled;
+                entry.info.finished_at = Some(get_timestamp());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| entry.info.clone())
+            .collect();
+        tasks.sort_by(|a, b| b.started_at.cmp(&a.started_at).then(b.id.cmp(&a.id)));
+        tasks
+    }
+
+    fn prune(tasks: &mut HashMap<TaskId, TaskEntry>) {
+        let mut finished: Vec<(String, TaskId)> = tasks
+            .values()
+            .filter(|entry| !matches!(entry.info.status, TaskStatus::Queued | TaskStatus::Running))
+            .map(|entry| (entry.info.finished_at.clone().unwrap_or_default(), entry.info.id.clone()))
+            .collect();
+        if finished.len() < MAX_FINISHED_TASKS {
+            return;
+        }
+        finished.sort();
+        for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_TASKS) {
+            tasks.remove(id);
+        }
+    }
+}
+
+/// Run a blocking job as a cancellable background task on a CPU slot
+pub fn spawn_task<F, T>(app: &AppHandle, kind: &str, job: F) -> TaskId
+where
+    F: FnOnce(TaskContext) -> Result<T, String> + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    spawn_task_with(app, kind, ResourceClass::Cpu, Priority::Background, job)
+}
+
+/// Run a blocking job as a cancellable task once the executor admits it
+pub fn spawn_task_with<F, T>(
+    app: &AppHandle,
+    kind: &str,
+    class: ResourceClass,
+    priority: Priority,
+    job: F,
+) -> TaskId
+where
+    F: FnOnce(TaskContext) -> Result<T, String> + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    let (id, token) = app.state::<TaskManager>().register(kind);
+    info!("Started task {} ({})", id, kind);
+    let ctx = TaskContext {
+        token,
+        progress: ProgressReporter::new(app.clone(), &id, kind),
+    };
+
+    let app = app.clone();
+    let task_id = id.clone();
+    let kind = kind.to_string();
+    tauri::async_runtime::spawn(async move {
+        let outcome = {
+            // Cancelling a queued task drops its place in the queue
+            let permit = tokio::select! {
+                permit = app.state::<Executor>().acquire(class, priority) => Some(permit),
+                _ = ctx.token.cancelled() => None,
+            };
+            match permit {
+                Some(_permit) if app.state::<TaskManager>().start(&task_id) => {
+                    let (app, task_id, kind) = (app.clone(), task_id.clone(), kind.clone());
+                    tauri::async_runtime::spawn_blocking(move || {
+                        // Held until the job returns, however it ends
+                        let _inhibit = SuspendInhibit::acquire(&app, &task_id, &kind);
+                        job(ctx)
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Task panicked: {}", e)))
+                }
+                _ => Err(CANCELLED.to_string()),
+            }
+        };
+
+        let (status, result, error) = match outcome {
+            Ok(value) => (TaskStatus::Completed, serde_json::to_value(value).ok(), None),
+            Err(e) if e == CANCELLED => (TaskStatus::Cancelled, None, None),
+            Err(e) => (TaskStatus::Failed, None, Some(e)),
+        };
+        if let Some(e) = &error {
+            warn!("Task {} ({}) failed: {}", task_id, kind, e);
+        } else {
+            info!("Task {} ({}) finished: {:?}", task_id, kind, status);
+        }
+
+        app.state::<TaskManager>().finish(&task_id, status, error.clone());
+        if status != TaskStatus::Cancelled {
+            let notification = Notification {
+                category: NotificationCategory::Task,
+                title: match status {
+                    TaskStatus::Failed => format!("任务失败: {}", kind),
+                    _ => format!("任务完成: {}", kind),
+                },
+                body: error.clone().unwrap_or_default(),
+                actions: Vec::new(),
+            };
+            if let Err(e) = notifications::record(&app, &notification) {
+                warn!("{}", e);
+            }
+        }
+        let payload = TaskFinished {
+            id: task_id,
+            kind,
+            status,
+            result,
+            error,
+        };
+        if let Err(e) = app.emit("task-finished", payload) {
+            warn!("Failed to emit task-finished event: {}", e);
+        }
+    });
+
+    id
+}
+
+/// Request cancellation of a queued or running task
+#[tauri::command]
+pub fn cancel_task(manager: State<'_, TaskManager>, id: String) -> Result<bool, String> {
+    let cancelled = manager.cancel(&id);
+    info!("Cancel requested for task {}: {}", id, cancelled);
+    Ok(cancelled)
+}
+
+/// List running and recently finished tasks
+#[tauri::command]
+pub fn list_tasks(manager: State<'_, TaskManager>) -> Result<Vec<TaskInfo>, String> {
+    Ok(manager.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_running_task_only() {
+        let manager = TaskManager::default();
+        let (id, token) = manager.register("backtest");
+        assert!(manager.start(&id));
+        assert!(token.checkpoint().is_ok());
+
+        assert!(manager.cancel(&id));
+        assert_eq!(token.checkpoint(), Err(CANCELLED.to_string()));
+
+        manager.finish(&id, TaskStatus::Cancelled, None);
+        assert!(!manager.cancel(&id));
+        assert!(!manager.cancel("task-unknown"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_queued_task_before_it_starts() {
+        let manager = TaskManager::default();
+        let (id, token) = manager.register("backfill");
+        assert_eq!(manager.list()[0].status, TaskStatus::Queued);
+
+        let waiting = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(manager.cancel(&id));
+        waiting.await.unwrap();
+        assert_eq!(manager.list()[0].status, TaskStatus::Cancelled);
+        // Admitted later, it must not run
+        assert!(!manager.start(&id));
+        assert!(!manager.cancel(&id));
+    }
+
+    #[test]
+    fn test_prune_keeps_running_tasks() {
+        let manager = TaskManager::default();
+        let (running, _) = manager.register("backfill");
+        manager.start(&running);
+        for _ in 0..MAX_FINISHED_TASKS + 10 {
+            let (id, _) = manager.register("export");
+            manager.finish(&id, TaskStatus::Completed, None);
+        }
+        let tasks = manager.list();
+        assert!(tasks.len() <= MAX_FINISHED_TASKS + 1);
+        assert!(tasks.iter().any(|t| t.id == running));
+    }
+}
@@ -0,0 +1,135 @@
+//! Shared test scaffolding for analytics code.
+//!
+//! Golden files live under `tests/golden/` and capture reference outputs of
+//! indicator and backtest computations; run the tests with `UPDATE_GOLDEN=1`
+//! to regenerate them after an intentional change and review the diff.
+//! Responses recorded from data providers live under `tests/fixtures/`, so
+//! parsers are checked against what the providers actually send.
+
+use std::fs;
+use std::path::PathBuf;
+
+use proptest::prelude::*;
+use serde::Serialize;
+
+use crate::models::Bar;
+
+fn test_data_path(dir: &str, file: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(dir)
+        .join(file)
+}
+
+/// Compare `value` with the golden file `tests/golden/<name>.json`
+pub fn assert_golden<T: Serialize>(name: &str, value: &T) {
+    let path = test_data_path("golden", &format!("{}.json", name));
+    let actual = serde_json::to_string_pretty(value).expect("golden value must serialize");
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("{}\n", actual)).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("Missing golden file {:?}; run with UPDATE_GOLDEN=1", path));
+    assert_eq!(
+        expected.trim_end(),
+        actual,
+        "Golden mismatch for {}; run with UPDATE_GOLDEN=1 if the change is intended",
+        name
+    );
+}
+
+/// A recorded provider response, `tests/fixtures/<name>`
+pub fn fixture(name: &str) -> String {
+    let path = test_data_path("fixtures", name);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("Missing fixture {:?}: {}", path, e))
+}
+
+/// Round a float so golden files are stable against last-bit differences
+pub fn round(x: f64) -> f64 {
+    (x * 1e8).round() / 1e8
+}
+
+pub fn round_series(series: &[Option<f64>]) -> Vec<Option<f64>> {
+    series.iter().map(|v| v.map(round)).collect()
+}
+
+/// Deterministic close series for golden tests
+pub fn sample_closes() -> Vec<f64> {
+    (0..120)
+        .map(|i| {
+            let t = i as f64;
+            20.0 + (t / 7.0).sin() * 2.0 + t * 0.03
+        })
+        .collect()
+}
+
+/// Daily bars of [`sample_closes`] from 2024-01-02, each opening at the
+/// previous close
+pub fn sample_bars() -> Vec<Bar> {
+    let closes = sample_closes();
+    closes
+        .iter()
+        .enumerate()
+        .map(|(i, &close)| {
+            let open = if i == 0 { close } else { closes[i - 1] };
+            Bar {
+                // Midnight in Shanghai
+                timestamp: 1_704_124_800 + i as i64 * 86_400,
+                open,
+                high: open.max(close) * 1.01,
+                low: open.min(close) * 0.99,
+                close,
+                volume: 1_000_000.0,
+            }
+        })
+        .collect()
+}
+
+/// Strategy for positive price random walks of `len` bars
+pub fn price_series(
+    len: impl Into<prop::collection::SizeRange>,
+) -> impl Strategy<Value = Vec<f64>> {
+    (1.0f64..500.0, prop::collection::vec(-0.1f64..0.1, len)).prop_map(|(start, returns)| {
+        returns
+            .into_iter()
+            .scan(start, |price, r| {
+                *price *= 1.0 + r;
+                Some(*price)
+            })
+            .collect()
+    })
+}
+
+/// Strategy for consecutive 1-minute A-share bars from the 2024-01-02 open,
+/// `len` of them, each with its open and close inside its range
+pub fn minute_bars(len: impl Into<prop::collection::SizeRange>) -> impl Strategy<Value = Vec<Bar>> {
+    let bar = (
+        1.0f64..500.0,
+        0.0f64..0.05,
+        0.0f64..=1.0,
+        0.0f64..=1.0,
+        0u32..100_000,
+    );
+    prop::collection::vec(bar, len).prop_map(|bars| {
+        bars.into_iter()
+            .enumerate()
+            .map(|(i, (low, spread, open, close, volume))| {
+                let high = low * (1.0 + spread);
+                let within = |share: f64| (low + (high - low) * share).min(high);
+                Bar {
+                    // 09:30 in Shanghai
+                    timestamp: 1_704_159_000 + i as i64 * 60,
+                    open: within(open),
+                    high,
+                    low,
+                    close: within(close),
+                    volume: f64::from(volume),
+                }
+            })
+            .collect()
+    })
+}
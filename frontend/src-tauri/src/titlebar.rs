@@ -0,0 +1,165 @@
+//! Window controls for the custom-drawn titlebar.
+//!
+//! Windows opened without decorations draw their own titlebar, and these
+//! commands stand in for the native one: dragging, maximizing and pinning
+//! on top. [`TitlebarHints`] tells the frontend how to lay the titlebar out
+//! to feel native on each platform: controls on the left on macOS and on
+//! the right elsewhere, and whether hovering maximize should open the
+//! Windows 11 snap layouts. A custom maximize button never gets the
+//! hit-test the flyout hangs off, so [`show_snap_layouts`] opens it the way
+//! the keyboard does, with Win+Z to the focused window.
+
+use log::info;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+/// First Windows build with snap layouts
+#[cfg(windows)]
+const WINDOWS_11_BUILD: u32 = 22000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlsSide {
+    Left,
+    Right,
+}
+
+/// How a window's titlebar should be drawn and behave
+#[derive(Debug, Clone, Serialize)]
+pub struct TitlebarHints {
+    pub controls_side: ControlsSide,
+    /// Whether hovering maximize should open the snap layouts
+    pub snap_layouts: bool,
+    pub maximized: bool,
+    pub always_on_top: bool,
+}
+
+fn window(app: &AppHandle, label: &str) -> Result<WebviewWindow, String> {
+    app.get_webview_window(label)
+        .ok_or_else(|| format!("Window not found: {}", label))
+}
+
+/// Whether the OS offers snap layouts from the maximize button
+fn snap_layouts_supported() -> bool {
+    #[cfg(windows)]
+    {
+        sysinfo::System::kernel_version()
+            .and_then(|build| build.trim().parse::<u32>().ok())
+            .is_some_and(|build| build >= WINDOWS_11_BUILD)
+    }
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+/// Start moving the window with the mouse, from a press on the titlebar
+#[tauri::command]
+pub fn begin_drag(window: WebviewWindow) -> Result<(), String> {
+    window
+        .start_dragging()
+        .map_err(|e| format!("Failed to drag window: {}", e))
+}
+
+/// Maximize the window, or restore it if maximized; returns whether it is
+/// maximized now
+#[tauri::command]
+pub fn toggle_maximize(window: WebviewWindow) -> Result<bool, String> {
+    let maximized = window
+        .is_maximized()
+        .map_err(|e| format!("Failed to read window state: {}", e))?;
+    let result = if maximized {
+        window.unmaximize()
+    } else {
+        window.maximize()
+    };
+    result.map_err(|e| format!("Failed to resize window: {}", e))?;
+    Ok(!maximized)
+}
+
+#[tauri::command]
+pub fn set_always_on_top(app: AppHandle, label: String, flag: bool) -> Result<(), String> {
+    window(&app, &label)?
+        .set_always_on_top(flag)
+        .map_err(|e| format!("Failed to pin window {}: {}", label, e))?;
+    info!("Window {} always on top: {}", label, flag);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_titlebar_hints(window: WebviewWindow) -> Result<TitlebarHints, String> {
+    let macos = cfg!(target_os = "macos");
+    Ok(TitlebarHints {
+        controls_side: if macos {
+            ControlsSide::Left
+        } else {
+            ControlsSide::Right
+        },
+        snap_layouts: snap_layouts_supported(),
+        maximized: window.is_maximized().unwrap_or(false),
+        always_on_top: window.is_always_on_top().unwrap_or(false),
+    })
+}
+
+/// Open the Windows 11 snap layouts for the window, from a hover on its
+/// maximize button; returns whether the platform has them
+#[tauri::command]
+pub fn show_snap_layouts(window: WebviewWindow) -> Result<bool, String> {
+    if !snap_layouts_supported() {
+        return Ok(false);
+    }
+    window
+        .set_focus()
+        .map_err(|e| format!("Failed to focus window: {}", e))?;
+    platform::press_win_z()?;
+    Ok(true)
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP,
+        VIRTUAL_KEY, VK_LWIN,
+    };
+
+    const VK_Z: VIRTUAL_KEY = VIRTUAL_KEY(0x5A);
+
+    fn key(vk: VIRTUAL_KEY, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: vk,
+                    wScan: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        }
+    }
+
+    pub fn press_win_z() -> Result<(), String> {
+        let up = KEYEVENTF_KEYUP;
+        let none = KEYBD_EVENT_FLAGS(0);
+        let inputs = [
+            key(VK_LWIN, none),
+            key(VK_Z, none),
+            key(VK_Z, up),
+            key(VK_LWIN, up),
+        ];
+        let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+        if sent as usize != inputs.len() {
+            return Err("Failed to open snap layouts".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Only Windows has snap layouts
+#[cfg(not(windows))]
+mod platform {
+    pub fn press_win_z() -> Result<(), String> {
+        Ok(())
+    }
+}
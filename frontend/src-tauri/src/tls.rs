@@ -0,0 +1,419 @@
+//! TLS trust policy and certificate pinning.
+//!
+//! By default requests trust the operating system's certificate store, which
+//! is what corporate networks that intercept TLS rely on. Users can switch
+//! to the roots bundled with the app instead, and pin hosts (the update
+//! server, sensitive provider endpoints) to SHA-256 hashes of certificate
+//! public keys. A pinned host is always validated against the bundled roots
+//! and a pinned key must be on the validated chain, not merely among the
+//! certificates the server sent; an intercepting proxy therefore fails
+//! loudly instead of being trusted silently. Pin failures are recorded with
+//! the keys that were presented so the diagnostics can show what the
+//! connection actually saw.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use log::{info, warn};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::politeness::PolicyEngine;
+use crate::settings::SettingsStore;
+use crate::utils::get_timestamp;
+
+/// Pin failures kept for diagnostics
+const MAX_PIN_FAILURES: usize = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsRoots {
+    /// The operating system's certificate store, including CAs installed by IT
+    #[default]
+    System,
+    /// Mozilla roots shipped with the app
+    Bundled,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    pub roots: TlsRoots,
+    /// Base64 SHA-256 hashes of accepted public keys (SPKI), per host
+    pub pins: BTreeMap<String, Vec<String>>,
+}
+
+impl TlsSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (host, pins) in &self.pins {
+            if pins.is_empty() {
+                return Err(format!("No pins given for {}", host));
+            }
+            for pin in pins {
+                let valid = BASE64.decode(pin).is_ok_and(|hash| hash.len() == 32);
+                if !valid {
+                    return Err(format!(
+                        "Invalid pin for {}: expected a base64 SHA-256 hash, got {}",
+                        host, pin
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pins of the hosts among `hosts`
+    fn pins_for(&self, hosts: &[&str]) -> BTreeMap<String, Vec<String>> {
+        self.pins
+            .iter()
+            .filter(|(host, _)| hosts.contains(&host.as_str()))
+            .map(|(host, pins)| (host.clone(), pins.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinFailure {
+    pub host: String,
+    pub expected: Vec<String>,
+    /// Key hashes of the chain the server presented, leaf first
+    pub presented: Vec<String>,
+    pub at: String,
+}
+
+impl PinFailure {
+    pub fn describe(&self) -> String {
+        format!(
+            "Certificate pin mismatch for {}: the server presented keys [{}] but only [{}] are pinned. \
+             A proxy may be intercepting TLS, or the site rotated its certificate.",
+            self.host,
+            self.presented.join(", "),
+            self.expected.join(", ")
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct PinFailures(Mutex<Vec<PinFailure>>);
+
+impl PinFailures {
+    fn record(&self, failure: PinFailure) {
+        warn!("{}", failure.describe());
+        let mut failures = self.0.lock().unwrap();
+        failures.push(failure);
+        let excess = failures.len().saturating_sub(MAX_PIN_FAILURES);
+        failures.drain(..excess);
+    }
+
+    /// The most recent failure for a host
+    pub fn last_for(&self, host: &str) -> Option<PinFailure> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|f| f.host == host)
+            .cloned()
+    }
+
+    pub fn list(&self) -> Vec<PinFailure> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// One DER element: its whole encoding, its contents and the bytes after it
+struct DerElement<'a> {
+    whole: &'a [u8],
+    content: &'a [u8],
+    rest: &'a [u8],
+}
+
+fn der_element(input: &[u8]) -> Option<DerElement<'_>> {
+    let first = *input.get(1)?;
+    let (length, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let bytes = input.get(2..2 + count)?;
+        let length = bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (length, 2 + count)
+    };
+    let end = header.checked_add(length)?;
+    let whole = input.get(..end)?;
+    Some(DerElement {
+        whole,
+        content: &whole[header..],
+        rest: &input[end..],
+    })
+}
+
+/// The SubjectPublicKeyInfo of a DER certificate
+fn spki(cert: &[u8]) -> Option<&[u8]> {
+    let certificate = der_element(cert)?.content;
+    let mut rest = der_element(certificate)?.content;
+    // Optional explicit version
+    if rest.first() == Some(&0xa0) {
+        rest = der_element(rest)?.rest;
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        rest = der_element(rest)?.rest;
+    }
+    der_element(rest).map(|element| element.whole)
+}
+
+/// Base64 SHA-256 of a certificate's public key, the format pins are written in
+pub fn spki_pin(cert: &[u8]) -> Option<String> {
+    spki(cert).map(|key| BASE64.encode(Sha256::digest(key)))
+}
+
+fn bundled_roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    roots
+}
+
+/// The operating system's trusted roots, or the bundled ones if none load
+fn system_roots() -> RootCertStore {
+    let certs = match rustls_native_certs::load_native_certs() {
+        Ok(certs) => certs,
+        Err(e) => {
+            warn!(
+                "Failed to load system certificates, using bundled roots: {}",
+                e
+            );
+            return bundled_roots();
+        }
+    };
+    let ders: Vec<Vec<u8>> = certs.into_iter().map(|cert| cert.0).collect();
+    let mut roots = RootCertStore::empty();
+    let (added, skipped) = roots.add_parsable_certificates(&ders);
+    if roots.is_empty() {
+        warn!("No usable system certificates, using bundled roots");
+        return bundled_roots();
+    }
+    info!(
+        "Loaded {} system root certificates, skipped {}",
+        added, skipped
+    );
+    roots
+}
+
+/// Whether `anchor` is on a valid chain to `end_entity`: the chain is
+/// validated again with it as the only trust anchor
+fn chains_to(
+    anchor: &Certificate,
+    end_entity: &Certificate,
+    intermediates: &[Certificate],
+    server_name: &ServerName,
+    now: SystemTime,
+) -> bool {
+    if anchor == end_entity {
+        return true;
+    }
+    let mut roots = RootCertStore::empty();
+    if roots.add(anchor).is_err() {
+        return false;
+    }
+    WebPkiVerifier::new(roots, None)
+        .verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            &mut std::iter::empty(),
+            &[],
+            now,
+        )
+        .is_ok()
+}
+
+/// Chain validation, then for pinned hosts a pin check against the validated chain
+struct PinningVerifier {
+    inner: WebPkiVerifier,
+    pins: BTreeMap<String, Vec<String>>,
+    failures: Arc<PinFailures>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_string(),
+            _ => String::new(),
+        };
+        let Some(expected) = self.pins.get(&host) else {
+            return Ok(verified);
+        };
+        // A pinned certificate the server sent alongside an unrelated chain
+        // doesn't count: it must itself lead to the leaf
+        let pinned = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter(|cert| spki_pin(&cert.0).is_some_and(|pin| expected.contains(&pin)))
+            .any(|cert| chains_to(cert, end_entity, intermediates, server_name, now));
+        if pinned {
+            return Ok(verified);
+        }
+        let failure = PinFailure {
+            host,
+            expected: expected.clone(),
+            presented: std::iter::once(end_entity)
+                .chain(intermediates)
+                .filter_map(|cert| spki_pin(&cert.0))
+                .collect(),
+            at: get_timestamp(),
+        };
+        let message = failure.describe();
+        self.failures.record(failure);
+        Err(rustls::Error::General(message))
+    }
+}
+
+/// Apply the TLS policy to a client that talks to `hosts`.
+///
+/// Clients for pinned hosts, or under the bundled-roots policy, use rustls
+/// with the bundled roots; everything else keeps the platform TLS stack and
+/// the system store.
+pub fn apply(
+    builder: reqwest::ClientBuilder,
+    tls: &TlsSettings,
+    hosts: &[&str],
+    failures: &Arc<PinFailures>,
+) -> reqwest::ClientBuilder {
+    if tls.pins_for(hosts).is_empty() && tls.roots == TlsRoots::System {
+        return builder;
+    }
+    builder.use_preconfigured_tls(rustls_config(tls, hosts, failures))
+}
+
+/// rustls configuration validating `hosts` under the TLS policy: against
+/// the bundled roots and their pins when pinned, else against the roots the
+/// policy names.
+///
+/// Used directly by connections that don't go through reqwest, such as
+/// quote streams.
+pub fn rustls_config(
+    tls: &TlsSettings,
+    hosts: &[&str],
+    failures: &Arc<PinFailures>,
+) -> ClientConfig {
+    let pins = tls.pins_for(hosts);
+    let roots = if pins.is_empty() && tls.roots == TlsRoots::System {
+        system_roots()
+    } else {
+        bundled_roots()
+    };
+    let verifier = PinningVerifier {
+        inner: WebPkiVerifier::new(roots, None),
+        pins,
+        failures: failures.clone(),
+    };
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsDiagnostics {
+    pub roots: TlsRoots,
+    pub pinned_hosts: Vec<String>,
+    pub failures: Vec<PinFailure>,
+}
+
+/// TLS policy in effect and recent pin failures
+#[tauri::command]
+pub fn get_tls_diagnostics(
+    settings: State<'_, SettingsStore>,
+    policies: State<'_, PolicyEngine>,
+) -> Result<TlsDiagnostics, String> {
+    let tls = settings.get().tls;
+    Ok(TlsDiagnostics {
+        roots: tls.roots,
+        pinned_hosts: tls.pins.into_keys().collect(),
+        failures: policies.pin_failures().list(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal certificate skeleton: the parser only walks the TBS structure
+    fn certificate(key: &[u8]) -> Vec<u8> {
+        fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+            let mut out = vec![tag];
+            if content.len() < 0x80 {
+                out.push(content.len() as u8);
+            } else {
+                out.extend([0x82, (content.len() >> 8) as u8, content.len() as u8]);
+            }
+            out.extend(content);
+            out
+        }
+        let mut tbs = tlv(0xa0, &tlv(0x02, &[2]));
+        tbs.extend(tlv(0x02, &[0x01, 0x23]));
+        for _ in 0..4 {
+            tbs.extend(tlv(0x30, &[0x05, 0x00]));
+        }
+        let spki = tlv(0x30, key);
+        tbs.extend(&spki);
+        tbs.extend(tlv(0xa3, &[0x30, 0x00]));
+        let mut cert = tlv(0x30, &tbs);
+        cert.extend(tlv(0x30, &[0x06, 0x00]));
+        tlv(0x30, &cert)
+    }
+
+    #[test]
+    fn test_spki_extraction_and_pin_validation() {
+        let key = vec![0x42u8; 300];
+        let cert = certificate(&key);
+        let mut expected_spki = vec![0x30, 0x82, 0x01, 0x2c];
+        expected_spki.extend(&key);
+        assert_eq!(spki(&cert), Some(expected_spki.as_slice()));
+        assert_eq!(
+            spki_pin(&cert),
+            Some(BASE64.encode(Sha256::digest(&expected_spki)))
+        );
+        assert_eq!(spki(&cert[..20]), None);
+
+        let mut tls = TlsSettings::default();
+        tls.pins
+            .insert("api.github.com".to_string(), vec![spki_pin(&cert).unwrap()]);
+        assert!(tls.validate().is_ok());
+        assert_eq!(tls.pins_for(&["push2.eastmoney.com"]).len(), 0);
+        assert_eq!(tls.pins_for(&["api.github.com"]).len(), 1);
+        tls.pins
+            .insert("example.com".to_string(), vec!["not-a-hash".to_string()]);
+        assert!(tls.validate().is_err());
+    }
+}
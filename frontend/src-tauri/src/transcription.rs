@@ -0,0 +1,252 @@
+//! Transcribing earnings calls and podcasts into research documents.
+//!
+//! Media is taken from a local file or downloaded from a URL, converted to
+//! 16 kHz mono WAV with an `ffmpeg` sidecar and transcribed locally with the
+//! whisper.cpp `whisper-cli` sidecar. Sidecars are looked up next to the
+//! executable and then on `PATH`; the ggml model is read from the `models`
+//! directory in the app data dir. The timed segments are chunked into a
+//! transcript document that search and AI answers can cite by timestamp.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use log::info;
+use serde::Deserialize;
+use tauri::{AppHandle, Manager};
+
+use crate::documents::{chunk_segments, DocumentKind, DocumentStore, ResearchDocument};
+use crate::executor::{Priority, ResourceClass};
+use crate::tasks::{spawn_task_with, TaskContext, TaskHandle};
+use crate::utils::{ensure_dir_exists, generate_id, get_app_data_dir, sidecar_path};
+
+/// Whisper model used for transcription, under `<app data>/models`
+const MODEL_FILE: &str = "ggml-base.bin";
+
+#[derive(Deserialize)]
+struct WhisperOutput {
+    transcription: Vec<WhisperSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperSegment {
+    offsets: WhisperOffsets,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct WhisperOffsets {
+    from: u64,
+    to: u64,
+}
+
+/// Timed segments from whisper-cli's JSON output
+fn parse_whisper_json(content: &str) -> Result<Vec<(String, u64, u64)>, String> {
+    let output: WhisperOutput = serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse transcription: {}", e))?;
+    Ok(output
+        .transcription
+        .into_iter()
+        .map(|s| (s.text, s.offsets.from, s.offsets.to))
+        .collect())
+}
+
+/// Percentage from a whisper-cli `--print-progress` line
+fn parse_progress(line: &str) -> Option<u64> {
+    let (_, rest) = line.split_once("progress =")?;
+    rest.trim().trim_end_matches('%').trim().parse().ok()
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+async fn download(url: &str, target: &Path, ctx: &TaskContext) -> Result<(), String> {
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download media: {}", e))?;
+    let total = response.content_length().unwrap_or(0);
+    let mut file = std::fs::File::create(target)
+        .map_err(|e| format!("Failed to create download file: {}", e))?;
+    let mut done = 0u64;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download media: {}", e))?
+    {
+        ctx.checkpoint()?;
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write download: {}", e))?;
+        done += chunk.len() as u64;
+        ctx.report("download", done, total.max(done));
+    }
+    Ok(())
+}
+
+fn convert_to_wav(input: &Path, output: &Path) -> Result<(), String> {
+    let status = Command::new(sidecar_path("ffmpeg"))
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(input)
+        .args(["-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+        .arg(output)
+        .status()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed to convert media: {}", status));
+    }
+    Ok(())
+}
+
+fn run_whisper(
+    model: &Path,
+    wav: &Path,
+    output_base: &Path,
+    ctx: &TaskContext,
+) -> Result<(), String> {
+    let mut child = Command::new(sidecar_path("whisper-cli"))
+        .arg("-m")
+        .arg(model)
+        .arg("-f")
+        .arg(wav)
+        .args(["-l", "auto", "--output-json", "--print-progress", "-of"])
+        .arg(output_base)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run whisper-cli: {}", e))?;
+
+    if let Some(stderr) = child.stderr.take() {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            if ctx.token.is_cancelled() {
+                child.kill().ok();
+                break;
+            }
+            if let Some(percent) = parse_progress(&line) {
+                ctx.report("transcribe", percent, 100);
+            }
+        }
+    }
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for whisper-cli: {}", e))?;
+    ctx.checkpoint()?;
+    if !status.success() {
+        return Err(format!("whisper-cli failed: {}", status));
+    }
+    Ok(())
+}
+
+fn transcribe(
+    app: &AppHandle,
+    source: &str,
+    symbol: Option<String>,
+    title: Option<String>,
+    ctx: &TaskContext,
+) -> Result<ResearchDocument, String> {
+    let data_dir = get_app_data_dir().ok_or("Failed to resolve app data directory")?;
+    let model = data_dir.join("models").join(MODEL_FILE);
+    if !model.exists() {
+        return Err(format!(
+            "Whisper model not found; place {} in {}",
+            MODEL_FILE,
+            model.parent().unwrap_or(&data_dir).display()
+        ));
+    }
+    let work_dir = std::env::temp_dir().join(generate_id("transcribe"));
+    ensure_dir_exists(&work_dir).map_err(|e| format!("Failed to create work directory: {}", e))?;
+
+    let result = (|| {
+        let input = if is_url(source) {
+            let target = work_dir.join("media");
+            tauri::async_runtime::block_on(download(source, &target, ctx))?;
+            target
+        } else {
+            PathBuf::from(source)
+        };
+        ctx.checkpoint()?;
+
+        ctx.report("convert", 0, 1);
+        let wav = work_dir.join("audio.wav");
+        convert_to_wav(&input, &wav)?;
+        ctx.report("convert", 1, 1);
+
+        let output_base = work_dir.join("transcript");
+        run_whisper(&model, &wav, &output_base, ctx)?;
+        let content = std::fs::read_to_string(output_base.with_extension("json"))
+            .map_err(|e| format!("Failed to read transcription: {}", e))?;
+        let chunks = chunk_segments(&parse_whisper_json(&content)?);
+        if chunks.is_empty() {
+            return Err("Transcription produced no text".to_string());
+        }
+
+        let title = title.unwrap_or_else(|| {
+            Path::new(source)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| source.to_string())
+        });
+        app.state::<DocumentStore>()
+            .add(DocumentKind::Transcript, &title, symbol, source, chunks)
+    })();
+    std::fs::remove_dir_all(&work_dir).ok();
+    result
+}
+
+/// Transcribe a local media file or URL into a research document
+#[tauri::command]
+pub fn transcribe_media(
+    app: AppHandle,
+    path_or_url: String,
+    symbol: Option<String>,
+    title: Option<String>,
+) -> Result<TaskHandle, String> {
+    if !is_url(&path_or_url) && !Path::new(&path_or_url).exists() {
+        return Err(format!("Media file not found: {}", path_or_url));
+    }
+    let handle = app.clone();
+    let task_id = spawn_task_with(
+        &app,
+        "transcription",
+        ResourceClass::Cpu,
+        Priority::Normal,
+        move |ctx: TaskContext| {
+            let document = transcribe(&handle, &path_or_url, symbol, title, &ctx)?;
+            info!(
+                "Transcribed {} into {} chunks",
+                path_or_url,
+                document.chunks.len()
+            );
+            Ok(document.summary())
+        },
+    );
+    Ok(TaskHandle { task_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_whisper_output() {
+        let json = r#"{"transcription": [
+            {"timestamps": {"from": "00:00:00,000", "to": "00:00:03,200"},
+             "offsets": {"from": 0, "to": 3200}, "text": " 各位投资者大家好"},
+            {"timestamps": {"from": "00:00:03,200", "to": "00:00:07,000"},
+             "offsets": {"from": 3200, "to": 7000}, "text": " 下面介绍一季度经营情况"}
+        ]}"#;
+        let segments = parse_whisper_json(json).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].1, 3200);
+        assert_eq!(chunk_segments(&segments)[0].end_ms, Some(7000));
+
+        assert_eq!(
+            parse_progress("whisper_print_progress_callback: progress =  45%"),
+            Some(45)
+        );
+        assert_eq!(
+            parse_progress("whisper_init_from_file: loading model"),
+            None
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Soft deletion and undo for user data.
+//!
+//! Stores never drop user records outright: deleted items move to a per-store
+//! trash as [`Trashed`], or are marked deleted in the database, and are
+//! purged only once the retention window has passed. Destructive operations
+//! (deletes and edits) push an [`UndoEntry`]; `undo_last_action` reverts the
+//! most recent one by handing it back to the store that owns the entity, so a
+//! mis-click costs one click to repair.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+
+use crate::audit::current_user;
+use crate::db::{Alerts, Database, Note, Notes, Watchlists};
+use crate::portfolio::PortfolioStore;
+use crate::privacy::Privacy;
+use crate::utils::{generate_id, get_timestamp, read_from_file, write_to_file};
+
+/// How long deleted items and undo entries are kept
+pub const RETENTION_DAYS: i64 = 30;
+
+/// Undo entries kept, newest last
+const MAX_UNDO_ENTRIES: usize = 200;
+
+/// A deleted record awaiting purge
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trashed<T> {
+    pub item: T,
+    pub deleted_at: String,
+}
+
+impl<T> Trashed<T> {
+    pub fn new(item: T) -> Self {
+        Self {
+            item,
+            deleted_at: get_timestamp(),
+        }
+    }
+}
+
+/// Whether a `get_timestamp` string is older than the retention window
+pub fn expired(timestamp: &str) -> bool {
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S UTC")
+        .map(|at| at < Utc::now().naive_utc() - Duration::days(RETENTION_DAYS))
+        .unwrap_or(false)
+}
+
+/// Deletion time before which trashed database records are purged
+pub fn retention_cutoff() -> String {
+    (Utc::now() - Duration::days(RETENTION_DAYS))
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
+}
+
+/// Drop trashed items past the retention window; returns how many were purged
+pub fn purge_expired<T>(trash: &mut Vec<Trashed<T>>) -> usize {
+    let before = trash.len();
+    trash.retain(|t| !expired(&t.deleted_at));
+    before - trash.len()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum UndoAction {
+    /// Restore the entity from its store's trash
+    Delete,
+    /// Put back the entity as it was before an edit
+    Update { before: Value },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoEntry {
+    pub id: String,
+    /// `kind:id`, as in the audit trail
+    pub entity: String,
+    /// Shown to the user, e.g. `Delete holding SH600519`
+    pub description: String,
+    #[serde(flatten)]
+    pub action: UndoAction,
+    pub at: String,
+}
+
+pub struct UndoLog {
+    path: PathBuf,
+    entries: Mutex<Vec<UndoEntry>>,
+}
+
+impl UndoLog {
+    pub fn load(path: PathBuf) -> Self {
+        let mut entries: Vec<UndoEntry> = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        entries.retain(|e| !expired(&e.at));
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn save(&self, entries: &[UndoEntry]) -> Result<(), String> {
+        let content = serde_json::to_string(entries)
+            .map_err(|e| format!("Failed to serialize undo history: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save undo history: {}", e))
+    }
+
+    pub fn push(
+        &self,
+        entity: String,
+        description: String,
+        action: UndoAction,
+    ) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(UndoEntry {
+            id: generate_id("undo"),
+            entity,
+            description,
+            action,
+            at: get_timestamp(),
+        });
+        let excess = entries.len().saturating_sub(MAX_UNDO_ENTRIES);
+        entries.drain(..excess);
+        self.save(&entries)
+    }
+
+    pub fn last(&self) -> Option<UndoEntry> {
+        self.entries.lock().unwrap().last().cloned()
+    }
+
+    /// Drop an entry once it has been undone or can no longer be
+    pub fn remove(&self, id: &str) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|e| e.id != id);
+        self.save(&entries)
+    }
+}
+
+/// Restore a record the database trashed, or put back a note's text
+fn revert_record(db: &Database, kind: &str, id: &str, action: &UndoAction) -> Result<(), String> {
+    let conn = db.conn()?;
+    match (kind, action) {
+        ("watchlist", UndoAction::Delete) => Watchlists(&conn).restore(id).map(|_| ()),
+        ("alert", UndoAction::Delete) => Alerts(&conn).restore(id).map(|_| ()),
+        ("note", UndoAction::Delete) => Notes(&conn).restore(id).map(|_| ()),
+        ("note", UndoAction::Update { before }) => {
+            let before: Note = serde_json::from_value(before.clone())
+                .map_err(|e| format!("Failed to read previous note: {}", e))?;
+            Notes(&conn)
+                .update(id, &before.title, &before.body)
+                .map(|_| ())
+        }
+        _ => Err(format!("Cannot undo this change to {} {}", kind, id)),
+    }
+}
+
+/// Hand an entry back to the store that owns its entity
+fn revert(app: &AppHandle, entry: &UndoEntry) -> Result<(), String> {
+    let actor = format!("{} (undo)", current_user());
+    let (kind, id) = entry
+        .entity
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid undo entity: {}", entry.entity))?;
+    match kind {
+        "transaction" | "holding" => {
+            app.state::<PortfolioStore>()
+                .revert(&actor, kind, id, &entry.action)
+        }
+        "watchlist" | "alert" | "note" => {
+            revert_record(&app.state::<Database>(), kind, id, &entry.action)
+        }
+        _ => Err(format!("Cannot undo changes to {}", kind)),
+    }
+}
+
+/// Revert the most recent delete or edit; returns what was undone, if anything
+#[tauri::command]
+pub fn undo_last_action(
+    app: AppHandle,
+    undo: State<'_, UndoLog>,
+    privacy: State<'_, Privacy>,
+) -> Result<Option<UndoEntry>, String> {
+    privacy.check_editable()?;
+    let Some(entry) = undo.last() else {
+        return Ok(None);
+    };
+    let result = revert(&app, &entry);
+    // An entry that failed to revert would block every older one
+    undo.remove(&entry.id)?;
+    result.map_err(|e| format!("Cannot undo \"{}\": {}", entry.description, e))?;
+    info!("Undid {}", entry.description);
+    Ok(Some(entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_and_undo_stack() {
+        let mut trash = vec![
+            Trashed {
+                item: 1,
+                deleted_at: "2020-01-01 00:00:00 UTC".to_string(),
+            },
+            Trashed::new(2),
+        ];
+        assert_eq!(purge_expired(&mut trash), 1);
+        assert_eq!(trash[0].item, 2);
+
+        let path = std::env::temp_dir().join(format!("ssi-undo-{}.json", std::process::id()));
+        let log = UndoLog::load(path.clone());
+        log.push(
+            "transaction:a".to_string(),
+            "Delete a".to_string(),
+            UndoAction::Delete,
+        )
+        .unwrap();
+        log.push(
+            "transaction:b".to_string(),
+            "Edit b".to_string(),
+            UndoAction::Update {
+                before: Value::from(1),
+            },
+        )
+        .unwrap();
+
+        let reloaded = UndoLog::load(path.clone());
+        let last = reloaded.last().unwrap();
+        assert_eq!(last.entity, "transaction:b");
+        reloaded.remove(&last.id).unwrap();
+        assert_eq!(reloaded.last().unwrap().action, UndoAction::Delete);
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,270 @@
+//! Point-in-time index membership.
+//!
+//! Constituent lists are recorded as dated snapshots and folded into
+//! membership intervals per index, so a backtest over "CSI 300 members" on a
+//! given day sees the index as it was then, not as it is today.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use chrono::NaiveDate;
+use log::info;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::utils::{read_from_file, write_to_file};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Membership {
+    pub symbol: String,
+    pub added: NaiveDate,
+    /// First day the symbol was no longer a member
+    pub removed: Option<NaiveDate>,
+}
+
+impl Membership {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.added <= date && self.removed.map_or(true, |removed| date < removed)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct IndexHistory {
+    last_snapshot: Option<NaiveDate>,
+    memberships: Vec<Membership>,
+}
+
+impl IndexHistory {
+    fn record(&mut self, date: NaiveDate, members: &BTreeSet<String>) -> Result<(), String> {
+        if let Some(last) = self.last_snapshot {
+            if date <= last {
+                return Err(format!(
+                    "Snapshot for {} is not after the last recorded snapshot ({})",
+                    date, last
+                ));
+            }
+        }
+
+        let mut open = BTreeSet::new();
+        for membership in self.memberships.iter_mut().filter(|m| m.removed.is_none()) {
+            if members.contains(&membership.symbol) {
+                open.insert(membership.symbol.clone());
+            } else {
+                membership.removed = Some(date);
+            }
+        }
+        for symbol in members.difference(&open) {
+            self.memberships.push(Membership {
+                symbol: symbol.clone(),
+                added: date,
+                removed: None,
+            });
+        }
+        self.last_snapshot = Some(date);
+        Ok(())
+    }
+
+    fn members_at(&self, date: NaiveDate) -> Vec<String> {
+        let members: BTreeSet<&String> = self
+            .memberships
+            .iter()
+            .filter(|m| m.contains(date))
+            .map(|m| &m.symbol)
+            .collect();
+        members.into_iter().cloned().collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexCoverage {
+    pub index: String,
+    pub first_snapshot: Option<NaiveDate>,
+    pub last_snapshot: Option<NaiveDate>,
+    pub symbols: Vec<String>,
+}
+
+/// Constituent history for every tracked index, keyed by index code
+pub struct UniverseStore {
+    path: PathBuf,
+    indexes: RwLock<BTreeMap<String, IndexHistory>>,
+}
+
+impl UniverseStore {
+    pub fn load(path: PathBuf) -> Self {
+        let indexes = read_from_file(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            indexes: RwLock::new(indexes),
+        }
+    }
+
+    /// Record the full constituent list of `index` as of `date`
+    pub fn record_snapshot(
+        &self,
+        index: &str,
+        date: NaiveDate,
+        members: &[String],
+    ) -> Result<(), String> {
+        let members: BTreeSet<String> = members.iter().map(|s| s.to_uppercase()).collect();
+        let mut indexes = self.indexes.write().unwrap();
+        indexes
+            .entry(index.to_uppercase())
+            .or_default()
+            .record(date, &members)?;
+
+        let content = serde_json::to_string(&*indexes)
+            .map_err(|e| format!("Failed to serialize index history: {}", e))?;
+        write_to_file(&self.path, &content)
+            .map_err(|e| format!("Failed to save index history: {}", e))
+    }
+
+    /// Members of `index` on `date`, sorted by symbol
+    pub fn universe(&self, index: &str, date: NaiveDate) -> Result<Vec<String>, String> {
+        let indexes = self.indexes.read().unwrap();
+        let history = indexes
+            .get(&index.to_uppercase())
+            .ok_or_else(|| format!("No constituent history for index {}", index))?;
+        Ok(history.members_at(date))
+    }
+
+    /// Membership intervals of `index` that overlap `[from, to)`, by symbol
+    pub fn memberships(
+        &self,
+        index: &str,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<BTreeMap<String, Vec<Membership>>, String> {
+        let indexes = self.indexes.read().unwrap();
+        let history = indexes
+            .get(&index.to_uppercase())
+            .ok_or_else(|| format!("No constituent history for index {}", index))?;
+        let mut memberships: BTreeMap<String, Vec<Membership>> = BTreeMap::new();
+        for membership in &history.memberships {
+            let ends_after = match (membership.removed, from) {
+                (Some(removed), Some(from)) => removed > from,
+                _ => true,
+            };
+            let starts_before = to.map_or(true, |to| membership.added < to);
+            if ends_after && starts_before {
+                memberships
+                    .entry(membership.symbol.clone())
+                    .or_default()
+                    .push(membership.clone());
+            }
+        }
+        Ok(memberships)
+    }
+
+    /// Recorded span and every symbol ever listed, per index
+    pub fn coverage(&self) -> Vec<IndexCoverage> {
+        self.indexes
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(index, history)| {
+                let symbols: BTreeSet<&String> =
+                    history.memberships.iter().map(|m| &m.symbol).collect();
+                IndexCoverage {
+                    index: index.clone(),
+                    first_snapshot: history.memberships.iter().map(|m| m.added).min(),
+                    last_snapshot: history.last_snapshot,
+                    symbols: symbols.into_iter().cloned().collect(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Record the constituents of an index as of a date
+#[tauri::command]
+pub fn record_index_constituents(
+    store: State<'_, UniverseStore>,
+    index: String,
+    date: NaiveDate,
+    symbols: Vec<String>,
+) -> Result<(), String> {
+    info!(
+        "Recording {} constituents of {} as of {}",
+        symbols.len(),
+        index,
+        date
+    );
+    store.record_snapshot(&index, date, &symbols)
+}
+
+/// Point-in-time members of an index
+#[tauri::command]
+pub fn get_universe(
+    store: State<'_, UniverseStore>,
+    date: NaiveDate,
+    index: String,
+) -> Result<Vec<String>, String> {
+    store.universe(&index, date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, d).unwrap()
+    }
+
+    fn symbols(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_point_in_time_membership() {
+        let path = std::env::temp_dir().join(format!("ssi-universe-{}.json", std::process::id()));
+        let store = UniverseStore::load(path.clone());
+
+        store
+            .record_snapshot("csi300", day(1), &symbols(&["SH600000", "SZ000001"]))
+            .unwrap();
+        store
+            .record_snapshot("CSI300", day(10), &symbols(&["SH600519", "SZ000001"]))
+            .unwrap();
+        store
+            .record_snapshot("CSI300", day(20), &symbols(&["SH600000", "SH600519"]))
+            .unwrap();
+        assert!(store
+            .record_snapshot("CSI300", day(15), &symbols(&["SH600000"]))
+            .is_err());
+
+        assert_eq!(
+            store.universe("CSI300", day(5)).unwrap(),
+            ["SH600000", "SZ000001"]
+        );
+        assert_eq!(
+            store.universe("CSI300", day(10)).unwrap(),
+            ["SH600519", "SZ000001"]
+        );
+        assert_eq!(
+            store.universe("CSI300", day(25)).unwrap(),
+            ["SH600000", "SH600519"]
+        );
+        assert!(store
+            .universe("CSI300", NaiveDate::from_ymd_opt(2023, 12, 31).unwrap())
+            .unwrap()
+            .is_empty());
+        // SZ000001 left on the 20th, SH600000 was out from the 10th to the 20th
+        let spanned = store.memberships("CSI300", Some(day(20)), None).unwrap();
+        assert_eq!(spanned.keys().collect::<Vec<_>>(), ["SH600000", "SH600519"]);
+        let early = store.memberships("CSI300", None, Some(day(10))).unwrap();
+        assert_eq!(early.keys().collect::<Vec<_>>(), ["SH600000", "SZ000001"]);
+        assert_eq!(early["SH600000"][0].removed, Some(day(10)));
+
+        let reloaded = UniverseStore::load(path.clone());
+        assert_eq!(
+            reloaded.universe("CSI300", day(12)).unwrap(),
+            ["SH600519", "SZ000001"]
+        );
+        assert!(reloaded.universe("CSI500", day(12)).is_err());
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,442 @@
+//! Application updates from GitHub Releases.
+//!
+//! The latest release is read from the GitHub API through the `github`
+//! source policy, so update traffic gets the same proxy routes and
+//! certificate pins as every other request. A release is newer when its tag
+//! is a higher semantic version than this build; pre-releases and drafts are
+//! skipped. Installing downloads the platform's installer as a task, whose
+//! progress reaches the frontend as `task-progress` events, and verifies its
+//! Ed25519 signature (the `.sig` asset next to it, hex encoded) against the
+//! public key compiled into release builds before anything is run. The
+//! signature covers the release's version followed by the installer, so an
+//! older signed installer can't be passed off under a newer tag. Builds
+//! without a key can check for updates but refuse to install them.
+
+use std::cmp::Ordering;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::executor::{Priority, ResourceClass};
+use crate::notifications::{self, Notification, NotificationAction, NotificationCategory};
+use crate::politeness::PolicyEngine;
+use crate::tasks::{spawn_task_with, TaskContext, TaskHandle};
+use crate::utils::{ensure_dir_exists, get_app_data_dir};
+
+const PROVIDER: &str = "github";
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/kevin12369/smart-stock-insider/releases/latest";
+
+/// Hex Ed25519 public key update artifacts are signed with, set by release builds
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("SSI_UPDATE_PUBLIC_KEY");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    /// Pre-release identifiers, e.g. `beta.2`
+    pre: Vec<String>,
+}
+
+impl Version {
+    /// Parse `1.2.3`, `v1.2.3` or `1.2.3-beta.2`; build metadata is ignored
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().trim_start_matches(['v', 'V']);
+        let value = value.split('+').next()?;
+        let (core, pre) = match value.split_once('-') {
+            Some((core, pre)) => (core, pre.split('.').map(str::to_string).collect()),
+            None => (value, Vec::new()),
+        };
+        let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+        let version = Version {
+            major: parts.next()??,
+            minor: parts.next().unwrap_or(Some(0))?,
+            patch: parts.next().unwrap_or(Some(0))?,
+            pre,
+        };
+        parts.next().is_none().then_some(version)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                // A release ranks above its pre-releases
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => {
+                    for (a, b) in self.pre.iter().zip(&other.pre) {
+                        let order = match (a.parse::<u64>(), b.parse::<u64>()) {
+                            (Ok(a), Ok(b)) => a.cmp(&b),
+                            (Ok(_), Err(_)) => Ordering::Less,
+                            (Err(_), Ok(_)) => Ordering::Greater,
+                            (Err(_), Err(_)) => a.cmp(b),
+                        };
+                        if order != Ordering::Equal {
+                            return order;
+                        }
+                    }
+                    self.pre.len().cmp(&other.pre.len())
+                }
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateInfo {
+    pub available: bool,
+    pub current_version: String,
+    pub latest_version: Option<String>,
+    pub notes: Option<String>,
+    pub published_at: Option<String>,
+    /// Installer for this platform; none when the release has no signed one
+    pub asset: Option<String>,
+    pub size: Option<u64>,
+}
+
+/// File name endings of the installer for this platform, preferred first
+fn installer_suffixes() -> &'static [&'static str] {
+    if cfg!(target_os = "windows") {
+        &["_x64-setup.exe", "_x64_en-US.msi"]
+    } else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        &["_aarch64.dmg"]
+    } else if cfg!(target_os = "macos") {
+        &["_x64.dmg"]
+    } else {
+        &["_amd64.AppImage"]
+    }
+}
+
+/// The installer and its signature among a release's assets
+fn pick_installer<'a>(assets: &'a [Asset], suffixes: &[&str]) -> Option<(&'a Asset, &'a Asset)> {
+    suffixes.iter().find_map(|suffix| {
+        let installer = assets.iter().find(|a| a.name.ends_with(suffix))?;
+        let signature_name = format!("{}.sig", installer.name);
+        let signature = assets.iter().find(|a| a.name == signature_name)?;
+        Some((installer, signature))
+    })
+}
+
+fn update_info(release: Option<&Release>, current: &str) -> UpdateInfo {
+    let newer = release.filter(|r| {
+        !r.draft
+            && !r.prerelease
+            && match (Version::parse(&r.tag_name), Version::parse(current)) {
+                (Some(latest), Some(current)) => latest > current,
+                _ => false,
+            }
+    });
+    let installer = newer.and_then(|r| pick_installer(&r.assets, installer_suffixes()));
+    UpdateInfo {
+        available: newer.is_some(),
+        current_version: current.to_string(),
+        latest_version: release.map(|r| r.tag_name.trim_start_matches('v').to_string()),
+        notes: newer.and_then(|r| r.body.clone()),
+        published_at: newer.and_then(|r| r.published_at.clone()),
+        asset: installer.map(|(asset, _)| asset.name.clone()),
+        size: installer.map(|(asset, _)| asset.size),
+    }
+}
+
+/// Message an installer's signature covers: its version, a newline, then the installer
+fn signed_message(version: &str, bytes: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(version.len() + 1 + bytes.len());
+    message.extend_from_slice(version.as_bytes());
+    message.push(b'\n');
+    message.extend_from_slice(bytes);
+    message
+}
+
+/// Check the artifact of release `version` against a hex signature and hex public key
+pub fn verify_artifact(
+    bytes: &[u8],
+    version: &str,
+    signature: &str,
+    public_key: &str,
+) -> Result<(), String> {
+    let key_bytes: [u8; 32] = hex::decode(public_key.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or("Update signing key is malformed")?;
+    let signature_bytes: [u8; 64] = hex::decode(signature.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or("Update signature is malformed")?;
+    let key =
+        VerifyingKey::from_bytes(&key_bytes).map_err(|_| "Update signing key is malformed")?;
+    key.verify_strict(
+        &signed_message(version, bytes),
+        &Signature::from_bytes(&signature_bytes),
+    )
+    .map_err(|_| "Update signature does not match; the download was not installed".to_string())
+}
+
+async fn fetch(app: &AppHandle, url: &str) -> Result<reqwest::Response, String> {
+    app.state::<PolicyEngine>()
+        .get(PROVIDER, url)
+        .await?
+        .error_for_status()
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))
+}
+
+async fn latest_release(app: &AppHandle) -> Result<Release, String> {
+    fetch(app, LATEST_RELEASE_URL)
+        .await?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse release: {}", e))
+}
+
+async fn download(
+    app: &AppHandle,
+    asset: &Asset,
+    target: &Path,
+    ctx: &TaskContext,
+) -> Result<(), String> {
+    let mut response = fetch(app, &asset.browser_download_url).await?;
+    let total = response.content_length().unwrap_or(asset.size);
+    let mut file = std::fs::File::create(target)
+        .map_err(|e| format!("Failed to create download file: {}", e))?;
+    let mut done = 0u64;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download update: {}", e))?
+    {
+        ctx.checkpoint()?;
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write download: {}", e))?;
+        done += chunk.len() as u64;
+        ctx.report("download", done, total.max(done));
+    }
+    Ok(())
+}
+
+/// Hand the verified installer over to the platform
+fn apply(app: &AppHandle, installer: &Path) -> Result<(), String> {
+    if cfg!(target_os = "windows") {
+        // An .msi is run by the Windows Installer, the NSIS setup.exe directly
+        let mut command = if installer.extension().is_some_and(|ext| ext == "msi") {
+            let mut command = Command::new("msiexec");
+            command.arg("/i").arg(installer).arg("/passive");
+            command
+        } else {
+            let mut command = Command::new(installer);
+            command.arg("/P");
+            command
+        };
+        command
+            .spawn()
+            .map_err(|e| format!("Failed to start installer: {}", e))?;
+        app.exit(0);
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+            .arg(installer)
+            .spawn()
+            .map_err(|e| format!("Failed to open update: {}", e))?;
+    } else {
+        let current = std::env::var_os("APPIMAGE")
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                format!(
+                "This installation is managed by the system; install {} with your package manager",
+                installer.display()
+            )
+            })?;
+        std::fs::copy(installer, &current)
+            .map_err(|e| format!("Failed to replace {}: {}", current.display(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&current, std::fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Failed to make update executable: {}", e))?;
+        }
+        app.restart();
+    }
+    Ok(())
+}
+
+fn install(app: &AppHandle, ctx: &TaskContext) -> Result<UpdateInfo, String> {
+    let public_key = UPDATE_PUBLIC_KEY.ok_or(
+        "This build cannot verify updates; download the new version from the releases page",
+    )?;
+    let release = tauri::async_runtime::block_on(latest_release(app))?;
+    let info = update_info(Some(&release), env!("CARGO_PKG_VERSION"));
+    if !info.available {
+        return Err("Already up to date".to_string());
+    }
+    let (installer, signature) = pick_installer(&release.assets, installer_suffixes())
+        .ok_or("The release has no signed installer for this platform")?;
+
+    let dir = get_app_data_dir()
+        .ok_or("Failed to resolve app data directory")?
+        .join("updates");
+    ensure_dir_exists(&dir).map_err(|e| format!("Failed to create updates directory: {}", e))?;
+    let target = dir.join(&installer.name);
+    let signature = tauri::async_runtime::block_on(async {
+        fetch(app, &signature.browser_download_url)
+            .await?
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read update signature: {}", e))
+    })?;
+    tauri::async_runtime::block_on(download(app, installer, &target, ctx))?;
+
+    ctx.report("verify", 0, 1);
+    let bytes =
+        std::fs::read(&target).map_err(|e| format!("Failed to read downloaded update: {}", e))?;
+    let version = info.latest_version.as_deref().unwrap_or_default();
+    if let Err(e) = verify_artifact(&bytes, version, &signature, public_key) {
+        warn!("Rejected update {}: {}", installer.name, e);
+        std::fs::remove_file(&target).ok();
+        return Err(e);
+    }
+    ctx.report("verify", 1, 1);
+    ctx.checkpoint()?;
+
+    info!(
+        "Installing update {} ({})",
+        info.latest_version.as_deref().unwrap_or(""),
+        installer.name
+    );
+    apply(app, &target)?;
+    Ok(info)
+}
+
+/// Compare this build with the latest GitHub release
+#[tauri::command]
+pub async fn check_for_updates(app: AppHandle) -> Result<UpdateInfo, String> {
+    info!("Checking for application updates...");
+    let release = latest_release(&app).await?;
+    let info = update_info(Some(&release), env!("CARGO_PKG_VERSION"));
+    if info.available {
+        let version = info.latest_version.as_deref().unwrap_or("");
+        info!("Update available: {}", version);
+        let notification = Notification {
+            category: NotificationCategory::Update,
+            title: format!("发现新版本 {}", version),
+            body: "新版本已发布，可在设置中安装".to_string(),
+            actions: vec![NotificationAction {
+                id: "install_update".to_string(),
+                label: "安装".to_string(),
+            }],
+        };
+        if let Err(e) = notifications::notify(&app, notification) {
+            warn!("{}", e);
+        }
+    }
+    Ok(info)
+}
+
+/// Download, verify and install the latest release
+#[tauri::command]
+pub fn install_update(app: AppHandle) -> Result<TaskHandle, String> {
+    let handle = app.clone();
+    let task_id = spawn_task_with(
+        &app,
+        "install_update",
+        ResourceClass::Network,
+        Priority::Interactive,
+        move |ctx: TaskContext| install(&handle, &ctx),
+    );
+    Ok(TaskHandle { task_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            browser_download_url: format!("https://github.com/x/releases/download/v1.1.0/{}", name),
+            size: 1024,
+        }
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        assert!(v("v1.10.0") > v("1.9.3"));
+        assert!(v("1.2.0") > v("1.2.0-rc.1"));
+        assert!(v("1.2.0-beta.11") > v("1.2.0-beta.2"));
+        assert!(v("1.2.0-rc.1") > v("1.2.0-beta.2"));
+        assert_eq!(v("1.2"), v("1.2.0+build.5"));
+        assert!(Version::parse("1.2.x").is_none());
+        assert!(Version::parse("1.2.3.4").is_none());
+    }
+
+    #[test]
+    fn test_update_info_and_signature() {
+        let mut release = Release {
+            tag_name: "v1.1.0".to_string(),
+            body: Some("修复若干问题".to_string()),
+            published_at: None,
+            draft: false,
+            prerelease: false,
+            assets: vec![
+                asset("app_1.1.0_amd64.AppImage"),
+                asset("app_1.1.0_amd64.AppImage.sig"),
+                asset("app_1.1.0_x64_en-US.msi"),
+            ],
+        };
+        assert!(update_info(Some(&release), "1.0.0").available);
+        assert!(!update_info(Some(&release), "1.1.0").available);
+
+        // Installers without a signature are never offered
+        let picked = pick_installer(&release.assets, &["_x64_en-US.msi", "_amd64.AppImage"]);
+        assert_eq!(picked.unwrap().0.name, "app_1.1.0_amd64.AppImage");
+        assert!(pick_installer(&release.assets, &["_x64_en-US.msi"]).is_none());
+
+        release.prerelease = true;
+        assert!(!update_info(Some(&release), "1.0.0").available);
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        let signature = hex::encode(key.sign(&signed_message("1.1.0", b"installer")).to_bytes());
+        assert!(verify_artifact(b"installer", "1.1.0", &signature, &public_key).is_ok());
+        assert!(verify_artifact(b"tampered", "1.1.0", &signature, &public_key).is_err());
+        assert!(verify_artifact(b"installer", "1.1.0", "00", &public_key).is_err());
+        // An older installer replayed under a newer tag
+        assert!(verify_artifact(b"installer", "1.2.0", &signature, &public_key).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use log::{info, error};
 
 /// Utility functions for the application
@@ -79,6 +79,31 @@ pub fn get_timestamp() -> String {
     Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string()
 }
 
+/// Generate a unique, time-ordered id such as `txn-01j5x3k9qz8m2v7c4t6w1r0b5n`.
+///
+/// The suffix is a ULID: ids made on different machines never collide, so
+/// records keep their identity when profiles are merged
+pub fn generate_id(prefix: &str) -> String {
+    use std::sync::Mutex;
+    static GENERATOR: Mutex<ulid::Generator> = Mutex::new(ulid::Generator::new());
+    let ulid = GENERATOR
+        .lock()
+        .unwrap()
+        .generate()
+        .unwrap_or_else(|_| ulid::Ulid::new());
+    format!("{}-{}", prefix, ulid.to_string().to_lowercase())
+}
+
+/// Path of a sidecar binary shipped next to the executable, or the bare name to find it on `PATH`
+pub fn sidecar_path(name: &str) -> PathBuf {
+    let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(&file)))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(file))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,408 @@
+//! Valuation metrics for the 估值 panel.
+//!
+//! PE(TTM), PB, PS(TTM), dividend yield and ROE are computed here from the
+//! cached [financial statements](crate::financials) and unadjusted daily
+//! closes rather than read off a quote, so the same figures can be worked
+//! out for every trading day of the lookback window. Each day is valued
+//! against the latest report published by then, so history never uses
+//! figures that weren't public yet, and today's value is ranked among the
+//! daily values of the window as a percentile.
+//!
+//! Trailing figures of a quarter add the last annual report to the year to
+//! date and take off the same part of the year before. Per-share figures
+//! divide by the share count implied by a report's profit and EPS, so bonus
+//! shares and placements count from the report that first reflects them.
+//! Loss-making days have no PE and are left out of its percentile.
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::executor::Priority;
+use crate::financials::{self, FinancialStatements, IncomeStatement, PeriodKind};
+use crate::kline::{self, Adjust, KlinePeriod};
+use crate::models::Market;
+use crate::sessions;
+use crate::settings::SettingsStore;
+use crate::snapshot::SnapshotClock;
+use crate::symbols::symbol_key;
+
+/// Longest lookback, as far back as the cached statements reach
+const MAX_LOOKBACK_YEARS: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValuationSettings {
+    /// Years of history the percentiles rank against
+    pub lookback_years: u32,
+}
+
+impl Default for ValuationSettings {
+    fn default() -> Self {
+        Self { lookback_years: 5 }
+    }
+}
+
+impl ValuationSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        check_lookback(self.lookback_years)
+    }
+}
+
+fn check_lookback(years: u32) -> Result<(), String> {
+    if !(1..=MAX_LOOKBACK_YEARS).contains(&years) {
+        return Err(format!(
+            "Valuation lookback must be between 1 and {} years",
+            MAX_LOOKBACK_YEARS
+        ));
+    }
+    Ok(())
+}
+
+/// A metric today and where it stands in the lookback window
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Metric {
+    pub value: Option<f64>,
+    /// Share of days in the window valued at or below today, 0 to 100
+    pub percentile: Option<f64>,
+    pub low: Option<f64>,
+    pub high: Option<f64>,
+}
+
+impl Metric {
+    fn rank(value: Option<f64>, history: &[f64]) -> Self {
+        let low = history.iter().copied().reduce(f64::min);
+        let high = history.iter().copied().reduce(f64::max);
+        let percentile = value.filter(|_| !history.is_empty()).map(|value| {
+            let below = history.iter().filter(|v| **v <= value).count();
+            below as f64 / history.len() as f64 * 100.0
+        });
+        Self {
+            value,
+            percentile,
+            low,
+            high,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Valuation {
+    pub symbol: String,
+    /// Trading day of the close valued
+    pub as_of: NaiveDate,
+    pub close: f64,
+    pub pe_ttm: Metric,
+    pub pb: Metric,
+    pub ps_ttm: Metric,
+    /// Cash dividends with ex-dates in the last year over the close
+    pub dividend_yield: Metric,
+    /// Trailing parent net profit over average parent equity
+    pub roe: Option<f64>,
+    pub lookback_years: u32,
+    /// Trading days in the window
+    pub days: usize,
+}
+
+/// Figures of one report, per share where they meet a price
+#[derive(Debug, Clone, Copy)]
+struct ReportFigures {
+    end: NaiveDate,
+    /// First day the report was public
+    public: NaiveDate,
+    eps: Option<f64>,
+    sales: Option<f64>,
+    book: Option<f64>,
+    roe: Option<f64>,
+}
+
+/// Trailing twelve months of a year-to-date figure, as of `statement`
+fn trailing(
+    income: &[IncomeStatement],
+    statement: &IncomeStatement,
+    field: fn(&IncomeStatement) -> Option<f64>,
+) -> Option<f64> {
+    let current = field(statement)?;
+    let period = &statement.period;
+    if period.kind == PeriodKind::Annual {
+        return Some(current);
+    }
+    let earlier = |kind: PeriodKind| {
+        income
+            .iter()
+            .find(|s| s.period.fiscal_year == period.fiscal_year - 1 && s.period.kind == kind)
+            .and_then(field)
+    };
+    Some(current + earlier(PeriodKind::Annual)? - earlier(period.kind)?)
+}
+
+fn report_figures(statements: &FinancialStatements) -> Vec<ReportFigures> {
+    let income = &statements.income;
+    let parent_equity = |end: NaiveDate| {
+        statements
+            .balance
+            .iter()
+            .find(|b| b.period.end == end)
+            .and_then(|b| b.parent_equity)
+    };
+    income
+        .iter()
+        .map(|statement| {
+            let end = statement.period.end;
+            let shares = statement
+                .parent_net_profit
+                .zip(statement.basic_eps)
+                .filter(|(_, eps)| *eps != 0.0)
+                .map(|(profit, eps)| profit / eps)
+                .filter(|shares| *shares > 0.0);
+            let per_share = |total: Option<f64>| Some(total? / shares?);
+            let profit = trailing(income, statement, |s| s.parent_net_profit);
+            let equity = parent_equity(end);
+            let year_ago = end
+                .with_year(end.year() - 1)
+                .and_then(parent_equity)
+                .or(equity);
+            let roe = profit
+                .zip(equity.zip(year_ago))
+                .map(|(profit, (now, then))| (profit, (now + then) / 2.0))
+                .filter(|(_, average)| *average > 0.0)
+                .map(|(profit, average)| profit / average);
+            ReportFigures {
+                end,
+                public: statement.period.public_from(),
+                eps: trailing(income, statement, |s| s.basic_eps),
+                sales: per_share(trailing(income, statement, |s| s.revenue)),
+                book: per_share(equity),
+                roe,
+            }
+        })
+        .collect()
+}
+
+/// Latest report public on `day`
+fn figures_on(reports: &[ReportFigures], day: NaiveDate) -> Option<&ReportFigures> {
+    reports
+        .iter()
+        .filter(|report| report.public <= day)
+        .max_by_key(|report| report.end)
+}
+
+/// `price` over a per-share figure, when the figure is positive
+fn multiple(price: f64, per_share: Option<f64>) -> Option<f64> {
+    per_share.filter(|v| *v > 0.0).map(|v| price / v)
+}
+
+/// PE, PB, PS and dividend yield of a close on `day`
+fn metrics(
+    statements: &FinancialStatements,
+    reports: &[ReportFigures],
+    day: NaiveDate,
+    price: f64,
+) -> [Option<f64>; 4] {
+    let report = figures_on(reports, day);
+    let dividends: f64 = statements
+        .dividends
+        .iter()
+        .filter(|d| d.ex_date <= day && d.ex_date > day - Duration::days(365))
+        .map(|d| d.cash_per_share)
+        .sum();
+    [
+        multiple(price, report.and_then(|r| r.eps)),
+        multiple(price, report.and_then(|r| r.book)),
+        multiple(price, report.and_then(|r| r.sales)),
+        Some(dividends / price).filter(|_| price > 0.0),
+    ]
+}
+
+/// Metrics of a single close, unranked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub pe_ttm: Option<f64>,
+    pub pb: Option<f64>,
+    pub ps_ttm: Option<f64>,
+    pub dividend_yield: Option<f64>,
+    pub roe: Option<f64>,
+}
+
+/// Metrics of a close on `day`, against the reports public by then
+pub fn snapshot(statements: &FinancialStatements, day: NaiveDate, price: f64) -> Snapshot {
+    let reports = report_figures(statements);
+    let [pe_ttm, pb, ps_ttm, dividend_yield] = metrics(statements, &reports, day, price);
+    Snapshot {
+        pe_ttm,
+        pb,
+        ps_ttm,
+        dividend_yield,
+        roe: figures_on(&reports, day).and_then(|r| r.roe),
+    }
+}
+
+/// Valuation as of the last close, ranked over `lookback_years` of closes
+pub fn valuation(
+    statements: &FinancialStatements,
+    closes: &[(NaiveDate, f64)],
+    lookback_years: u32,
+) -> Result<Valuation, String> {
+    let &(as_of, close) = closes
+        .last()
+        .ok_or_else(|| format!("No prices for {}", statements.symbol))?;
+    let reports = report_figures(statements);
+    let start = as_of - Duration::days(365 * i64::from(lookback_years));
+    let on_day = |day: NaiveDate, price: f64| metrics(statements, &reports, day, price);
+
+    let mut history: [Vec<f64>; 4] = Default::default();
+    let window = closes.iter().filter(|(day, _)| *day > start);
+    let mut days = 0;
+    for &(day, price) in window {
+        days += 1;
+        for (series, metric) in history.iter_mut().zip(on_day(day, price)) {
+            series.extend(metric);
+        }
+    }
+    let today = on_day(as_of, close);
+    Ok(Valuation {
+        symbol: statements.symbol.clone(),
+        as_of,
+        close,
+        pe_ttm: Metric::rank(today[0], &history[0]),
+        pb: Metric::rank(today[1], &history[1]),
+        ps_ttm: Metric::rank(today[2], &history[2]),
+        dividend_yield: Metric::rank(today[3], &history[3]),
+        roe: figures_on(&reports, as_of).and_then(|r| r.roe),
+        lookback_years,
+        days,
+    })
+}
+
+/// Valuation metrics of an A-share and their percentiles over the lookback,
+/// the configured one unless `lookback_years` is given
+#[tauri::command]
+pub async fn get_valuation(
+    app: AppHandle,
+    symbol: String,
+    lookback_years: Option<u32>,
+) -> Result<Valuation, String> {
+    let symbol = symbol_key(&symbol);
+    let lookback_years = match lookback_years {
+        Some(years) => {
+            check_lookback(years)?;
+            years
+        }
+        None => app.state::<SettingsStore>().get().valuation.lookback_years,
+    };
+    // Both come from the cache alone while frozen, and the valuation is as of
+    // the last close at or before the snapshot time
+    let until = app.state::<SnapshotClock>().frozen_at();
+    let statements = financials::statements(&app, &symbol, false).await?;
+    let bars = kline::candles(
+        &app,
+        &symbol,
+        KlinePeriod::Daily,
+        Adjust::None,
+        Priority::Interactive,
+    )
+    .await?;
+    let market = Market::of(&symbol).unwrap_or(Market::Cn);
+    let closes: Vec<(NaiveDate, f64)> = bars
+        .iter()
+        .filter(|bar| until.map_or(true, |until| bar.timestamp <= until))
+        .map(|bar| (sessions::trading_day(market, bar.timestamp), bar.close))
+        .collect();
+    valuation(&statements, &closes, lookback_years)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::financials::{BalanceSheet, Dividend, ReportingPeriod};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn period(end: NaiveDate, kind: PeriodKind) -> ReportingPeriod {
+        ReportingPeriod {
+            end,
+            fiscal_year: end.year(),
+            kind,
+            published: None,
+        }
+    }
+
+    /// 100 shares outstanding throughout
+    fn income(end: NaiveDate, kind: PeriodKind, revenue: f64, profit: f64) -> IncomeStatement {
+        IncomeStatement {
+            period: period(end, kind),
+            revenue: Some(revenue),
+            operating_cost: None,
+            operating_profit: None,
+            total_profit: None,
+            net_profit: Some(profit),
+            parent_net_profit: Some(profit),
+            basic_eps: Some(profit / 100.0),
+        }
+    }
+
+    fn balance(end: NaiveDate, kind: PeriodKind, equity: f64) -> BalanceSheet {
+        BalanceSheet {
+            period: period(end, kind),
+            total_assets: None,
+            total_liabilities: None,
+            total_equity: None,
+            parent_equity: Some(equity),
+            cash: None,
+            current_assets: None,
+            current_liabilities: None,
+            inventory: None,
+            receivables: None,
+        }
+    }
+
+    #[test]
+    fn test_value_from_statements() {
+        let statements = FinancialStatements {
+            symbol: "SH600000".to_string(),
+            income: vec![
+                income(date(2024, 6, 30), PeriodKind::Interim, 600.0, 60.0),
+                income(date(2023, 12, 31), PeriodKind::Annual, 1000.0, 100.0),
+                income(date(2023, 6, 30), PeriodKind::Interim, 500.0, 40.0),
+            ],
+            balance: vec![
+                balance(date(2024, 6, 30), PeriodKind::Interim, 1000.0),
+                balance(date(2023, 6, 30), PeriodKind::Interim, 800.0),
+            ],
+            cash_flow: Vec::new(),
+            dividends: vec![Dividend {
+                ex_date: date(2024, 7, 1),
+                cash_per_share: 0.3,
+            }],
+            fetched_on: date(2024, 9, 2),
+            fetched_at: 0,
+        };
+        let closes = [
+            (date(2023, 9, 1), 10.0),
+            (date(2024, 5, 6), 14.0),
+            (date(2024, 9, 2), 15.0),
+        ];
+        let result = valuation(&statements, &closes, 1).unwrap();
+        let approx = |metric: Option<f64>, expected: f64| {
+            assert!((metric.unwrap() - expected).abs() < 1e-9, "{:?}", metric);
+        };
+
+        // Trailing profit 60 + 100 - 40 = 120 over 100 shares
+        approx(result.pe_ttm.value, 12.5);
+        approx(result.pb.value, 1.5);
+        approx(result.ps_ttm.value, 15.0 / 11.0);
+        approx(result.dividend_yield.value, 0.02);
+        approx(result.roe, 120.0 / 900.0);
+        // The 2023 close is outside the window; in May only the annual
+        // report was out, valuing at 14 times its earnings
+        assert_eq!(result.days, 2);
+        assert_eq!(result.pe_ttm.percentile, Some(50.0));
+        approx(result.pe_ttm.high, 14.0);
+        assert_eq!(result.dividend_yield.percentile, Some(100.0));
+
+        assert!(ValuationSettings { lookback_years: 0 }.validate().is_err());
+        assert!(valuation(&statements, &[], 5).is_err());
+    }
+}
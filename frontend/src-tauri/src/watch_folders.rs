@@ -0,0 +1,309 @@
+//! Folders watched for research documents, per symbol.
+//!
+//! Each [`WatchFolder`] ties a folder to a symbol: files dropped into it, or
+//! into any folder below it, are imported into the document store as that
+//! symbol's articles and embedded with the live model when there is one.
+//! Folders are scanned every [`SCAN_INTERVAL`] rather than watched through
+//! file system events, which network drives and synced folders deliver
+//! unreliably. A file is only read once it has been left alone for
+//! [`SETTLE`], so a copy in progress isn't imported half-way, and its path is
+//! the document source, so each file is imported once.
+//!
+//! Text comes straight from plain text and Markdown, from HTML through the
+//! article extractor, and from PDF through the `pdftotext` sidecar
+//! (poppler). Other files are left alone, and a file that fails to import is
+//! not retried until restart.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::articles::extract_readable;
+use crate::documents::{chunk_text, DocumentKind, DocumentStore, ResearchDocument};
+use crate::embeddings;
+use crate::settings::SettingsStore;
+use crate::symbols::normalize_symbol;
+use crate::utils::sidecar_path;
+
+const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+/// How long a file must go unmodified before it is imported
+const SETTLE: Duration = Duration::from_secs(10);
+/// Larger files are skipped
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+const EXTENSIONS: &[&str] = &["txt", "md", "markdown", "html", "htm", "pdf"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchFolder {
+    pub path: PathBuf,
+    /// Symbol the folder's documents attach to
+    pub symbol: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchFolderSettings {
+    pub folders: Vec<WatchFolder>,
+}
+
+impl WatchFolderSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, folder) in self.folders.iter().enumerate() {
+            if !folder.path.is_absolute() {
+                return Err(format!(
+                    "Watch folder must be an absolute path: {}",
+                    folder.path.display()
+                ));
+            }
+            if normalize_symbol(&folder.symbol).as_deref() != Some(folder.symbol.as_str()) {
+                return Err(format!("Invalid watch folder symbol: {}", folder.symbol));
+            }
+            if self.folders[..i].iter().any(|f| f.path == folder.path) {
+                return Err(format!(
+                    "Folder is already watched: {}",
+                    folder.path.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Files that failed to import, so later scans leave them alone
+#[derive(Default)]
+pub struct FolderWatcher {
+    failed: Mutex<HashSet<PathBuf>>,
+}
+
+fn is_candidate(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map_or(true, |name| name.starts_with('.') || name.starts_with("~$"));
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    !hidden && extension.is_some_and(|ext| EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Importable files under `dir` last modified before `settled_before`
+fn settled_files(dir: &Path, settled_before: SystemTime) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if metadata.is_file()
+                && metadata.len() <= MAX_FILE_BYTES
+                && metadata
+                    .modified()
+                    .is_ok_and(|modified| modified < settled_before)
+                && is_candidate(&path)
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn run_pdftotext(path: &Path) -> Result<String, String> {
+    let output = Command::new(sidecar_path("pdftotext"))
+        .args(["-enc", "UTF-8"])
+        .arg(path)
+        .arg("-")
+        .output()
+        .map_err(|e| format!("Failed to run pdftotext: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "pdftotext failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Text of a file, with the title found in it for HTML
+fn extract_text(path: &Path) -> Result<(Option<String>, String), String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    let read = || {
+        std::fs::read(path)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    };
+    match extension.as_str() {
+        "pdf" => Ok((None, run_pdftotext(path)?)),
+        "html" | "htm" => {
+            let readable = extract_readable(&read()?, "");
+            let title = Some(readable.title).filter(|title| !title.is_empty());
+            Ok((title, readable.paragraphs.join("\n")))
+        }
+        _ => Ok((None, read()?)),
+    }
+}
+
+fn import(path: &Path, symbol: &str) -> Result<ResearchDocument, String> {
+    let (title, text) = extract_text(path)?;
+    let chunks = chunk_text(&text);
+    if chunks.is_empty() {
+        return Err(format!("No text found in {}", path.display()));
+    }
+    let title = title.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    Ok(ResearchDocument::new(
+        DocumentKind::Article,
+        &title,
+        Some(symbol.to_string()),
+        &path.to_string_lossy(),
+        chunks,
+    ))
+}
+
+/// Import new files from every watched folder, returning how many were
+/// imported
+fn scan(app: &AppHandle) -> Result<usize, String> {
+    let folders = app.state::<SettingsStore>().get().watch_folders.folders;
+    let documents = app.state::<DocumentStore>();
+    let watcher = app.state::<FolderWatcher>();
+    let settled_before = SystemTime::now() - SETTLE;
+    let mut imported = Vec::new();
+    for folder in &folders {
+        for path in settled_files(&folder.path, settled_before) {
+            let source = path.to_string_lossy();
+            if documents.has_source(&source) || watcher.failed.lock().unwrap().contains(&path) {
+                continue;
+            }
+            match import(&path, &folder.symbol) {
+                Ok(document) => imported.push(document),
+                Err(e) => {
+                    warn!("Failed to import {}: {}", path.display(), e);
+                    watcher.failed.lock().unwrap().insert(path);
+                }
+            }
+        }
+    }
+    let count = imported.len();
+    if count > 0 {
+        documents.add_all(imported)?;
+        info!("Imported {} documents from watched folders", count);
+        embeddings::embed_new(app);
+    }
+    Ok(count)
+}
+
+/// Scan the watched folders for as long as the app runs
+pub fn start_folder_watch(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let app = handle.clone();
+            match tauri::async_runtime::spawn_blocking(move || scan(&app)).await {
+                Ok(Err(e)) => warn!("Watch folder scan failed: {}", e),
+                Err(e) => warn!("Watch folder scan failed: {}", e),
+                Ok(Ok(_)) => {}
+            }
+            tokio::time::sleep(SCAN_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_watch_folders(settings: State<'_, SettingsStore>) -> Result<Vec<WatchFolder>, String> {
+    Ok(settings.get().watch_folders.folders)
+}
+
+/// Attach everything dropped into `path` to `symbol`
+#[tauri::command]
+pub fn add_watch_folder(
+    settings: State<'_, SettingsStore>,
+    path: PathBuf,
+    symbol: String,
+) -> Result<Vec<WatchFolder>, String> {
+    if !path.is_dir() {
+        return Err(format!("Not a folder: {}", path.display()));
+    }
+    let symbol = normalize_symbol(&symbol).ok_or_else(|| format!("Invalid symbol: {}", symbol))?;
+    let mut updated = settings.get();
+    updated
+        .watch_folders
+        .folders
+        .push(WatchFolder { path, symbol });
+    let folders = updated.watch_folders.folders.clone();
+    settings.set(updated)?;
+    Ok(folders)
+}
+
+/// Stop watching a folder; documents already imported from it stay
+#[tauri::command]
+pub fn remove_watch_folder(
+    settings: State<'_, SettingsStore>,
+    path: PathBuf,
+) -> Result<Vec<WatchFolder>, String> {
+    let mut updated = settings.get();
+    updated.watch_folders.folders.retain(|f| f.path != path);
+    let folders = updated.watch_folders.folders.clone();
+    settings.set(updated)?;
+    Ok(folders)
+}
+
+/// Scan the watched folders now, returning how many documents were imported
+#[tauri::command]
+pub async fn scan_watch_folders(app: AppHandle) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || scan(&app))
+        .await
+        .map_err(|e| format!("Failed to scan watch folders: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settled_files() {
+        let dir = std::env::temp_dir().join(format!("ssi-watch-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("2024")).unwrap();
+        for name in [
+            "纪要.txt",
+            "2024/年报.PDF",
+            "model.xlsx",
+            ".DS_Store",
+            "~$draft.md",
+        ] {
+            std::fs::write(dir.join(name), "茅台").unwrap();
+        }
+        let later = SystemTime::now() + Duration::from_secs(60);
+        assert_eq!(
+            settled_files(&dir, later),
+            [dir.join("2024/年报.PDF"), dir.join("纪要.txt")]
+        );
+        // Files still being written wait for a later scan
+        assert!(settled_files(&dir, SystemTime::UNIX_EPOCH).is_empty());
+
+        let document = import(&dir.join("纪要.txt"), "SH600519").unwrap();
+        assert_eq!(document.title, "纪要");
+        assert_eq!(document.symbol.as_deref(), Some("SH600519"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
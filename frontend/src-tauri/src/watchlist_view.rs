@@ -0,0 +1,252 @@
+//! Watchlist rows with per-cell change indicators.
+//!
+//! `get_watchlist_view` joins a watchlist's symbols with their latest quotes
+//! and diffs every quoted field against the previous view the same window got
+//! for that list. Each field carries its value, the direction of its last
+//! change and when the change was seen, so the frontend flashes a cell whose
+//! `changed_at` is newer than its last render instead of keeping a shadow
+//! copy of every row. A field keeps its direction and time until it changes
+//! again; a quote showing up for the first time doesn't count as a change.
+//! Snapshots are kept per window and dropped when the window is destroyed.
+//! In snapshot mode, quotes newer than the snapshot time are left out and
+//! the view is stamped with that time.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use chrono::Utc;
+use serde::Serialize;
+use tauri::{State, Window};
+
+use crate::db::{Database, Watchlists};
+use crate::quotes::{Quote, QuoteFeed};
+use crate::snapshot::SnapshotClock;
+
+/// Quoted fields diffed between views
+const FIELDS: &[(&str, fn(&Quote) -> f64)] = &[
+    ("price", |q| q.price),
+    ("change", |q| q.change),
+    ("change_pct", |q| q.change_pct),
+    ("open", |q| q.open),
+    ("high", |q| q.high),
+    ("low", |q| q.low),
+    ("volume", |q| q.volume),
+    ("amount", |q| q.amount),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+    Flat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FieldChange {
+    /// `None` until the symbol has a quote
+    pub value: Option<f64>,
+    /// Direction of the last change
+    pub direction: Direction,
+    /// Unix milliseconds the last change was seen; `None` if never
+    pub changed_at: Option<i64>,
+}
+
+type Fields = BTreeMap<&'static str, FieldChange>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchlistRow {
+    pub symbol: String,
+    pub group: String,
+    pub name: Option<String>,
+    pub fields: Fields,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchlistView {
+    pub watchlist_id: String,
+    pub name: String,
+    pub rows: Vec<WatchlistRow>,
+    /// Unix milliseconds the view was built
+    pub as_of: i64,
+}
+
+/// The last fields each window saw, by window label and watchlist id
+#[derive(Default)]
+pub struct WatchlistViews {
+    snapshots: Mutex<HashMap<(String, String), HashMap<String, Fields>>>,
+}
+
+impl WatchlistViews {
+    /// Forget the snapshots of a destroyed window
+    pub fn release_window(&self, label: &str) {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .retain(|(window, _), _| window != label);
+    }
+
+    /// Keep only the snapshots of windows for which `keep` holds
+    pub fn retain_windows(&self, keep: impl Fn(&str) -> bool) {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .retain(|(window, _), _| keep(window));
+    }
+
+    /// Snapshotted rows and their estimated heap bytes
+    pub fn usage(&self) -> (usize, usize) {
+        let snapshots = self.snapshots.lock().unwrap();
+        let rows: usize = snapshots.values().map(HashMap::len).sum();
+        let per_row = FIELDS.len() * std::mem::size_of::<(&str, FieldChange)>();
+        (rows, rows * per_row)
+    }
+
+    /// Forget every snapshot; the next view of each list shows no changes
+    pub fn clear(&self) -> usize {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let cleared = snapshots.len();
+        snapshots.clear();
+        snapshots.shrink_to_fit();
+        cleared
+    }
+}
+
+fn field_change(previous: Option<&FieldChange>, value: Option<f64>, now: i64) -> FieldChange {
+    let unchanged = FieldChange {
+        value,
+        direction: Direction::Flat,
+        changed_at: None,
+    };
+    let Some(previous) = previous else {
+        return unchanged;
+    };
+    match (previous.value, value) {
+        (Some(before), Some(after)) if before != after => FieldChange {
+            value,
+            direction: if after > before {
+                Direction::Up
+            } else {
+                Direction::Down
+            },
+            changed_at: Some(now),
+        },
+        (Some(_), None) => unchanged,
+        _ => FieldChange { value, ..*previous },
+    }
+}
+
+fn row_fields(previous: Option<&Fields>, quote: Option<&Quote>, now: i64) -> Fields {
+    FIELDS
+        .iter()
+        .map(|&(name, value)| {
+            let before = previous.and_then(|fields| fields.get(name));
+            (name, field_change(before, quote.map(value), now))
+        })
+        .collect()
+}
+
+/// A watchlist's rows with the change of every quoted field since the calling
+/// window's previous view of it
+#[tauri::command]
+pub fn get_watchlist_view(
+    window: Window,
+    db: State<'_, Database>,
+    feed: State<'_, QuoteFeed>,
+    views: State<'_, WatchlistViews>,
+    clock: State<'_, SnapshotClock>,
+    watchlist_id: String,
+) -> Result<WatchlistView, String> {
+    let watchlist = {
+        let conn = db.conn()?;
+        Watchlists(&conn).get(&watchlist_id)?
+    };
+    let until = clock.frozen_at();
+    let now = until.map_or_else(|| Utc::now().timestamp_millis(), |ts| ts * 1000);
+    let mut snapshots = views.snapshots.lock().unwrap();
+    let snapshot = snapshots
+        .entry((window.label().to_string(), watchlist_id.clone()))
+        .or_default();
+    let rows: Vec<WatchlistRow> = watchlist
+        .items
+        .into_iter()
+        .map(|item| {
+            let quote = feed
+                .get(&item.symbol)
+                .filter(|q| until.map_or(true, |until| q.timestamp <= until));
+            let fields = row_fields(snapshot.get(&item.symbol), quote.as_ref(), now);
+            WatchlistRow {
+                name: quote.map(|q| q.name),
+                symbol: item.symbol,
+                group: item.group,
+                fields,
+            }
+        })
+        .collect();
+    *snapshot = rows
+        .iter()
+        .map(|row| (row.symbol.clone(), row.fields.clone()))
+        .collect();
+    Ok(WatchlistView {
+        watchlist_id,
+        name: watchlist.name,
+        rows,
+        as_of: now,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Currency;
+
+    fn quote(price: f64, volume: f64) -> Quote {
+        Quote {
+            symbol: "SH600519".to_string(),
+            name: "贵州茅台".to_string(),
+            price,
+            prev_close: 1680.0,
+            open: 1680.0,
+            high: 1700.0,
+            low: 1670.0,
+            volume,
+            amount: 0.0,
+            change: price - 1680.0,
+            change_pct: (price / 1680.0 - 1.0) * 100.0,
+            currency: Currency::Cny,
+            timestamp: 0,
+            source: "tencent".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fields_track_last_change() {
+        let unquoted = row_fields(None, None, 1);
+        assert_eq!(unquoted["price"].value, None);
+        // First quote is no change to flash
+        let first = row_fields(Some(&unquoted), Some(&quote(1688.0, 100.0)), 2);
+        assert_eq!(first["price"].changed_at, None);
+
+        let up = row_fields(Some(&first), Some(&quote(1690.0, 100.0)), 3);
+        assert_eq!(
+            up["price"],
+            FieldChange {
+                value: Some(1690.0),
+                direction: Direction::Up,
+                changed_at: Some(3),
+            }
+        );
+        assert_eq!(up["volume"].changed_at, None);
+        assert_eq!(up["open"].direction, Direction::Flat);
+
+        // Unchanged fields keep their last direction and time
+        let later = row_fields(Some(&up), Some(&quote(1690.0, 300.0)), 4);
+        assert_eq!(later["price"], up["price"]);
+        assert_eq!(later["volume"].changed_at, Some(4));
+        let down = row_fields(Some(&later), Some(&quote(1685.0, 300.0)), 5);
+        assert_eq!(
+            (down["price"].direction, down["change_pct"].direction),
+            (Direction::Down, Direction::Down)
+        );
+    }
+}
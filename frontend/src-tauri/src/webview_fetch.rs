@@ -0,0 +1,298 @@
+//! Hidden-webview fetching for pages that only render with JavaScript.
+//!
+//! A source declares where it may navigate, what to wait for and which
+//! fields to pull out with CSS selectors. The page is loaded in a hidden
+//! webview that refuses navigation outside the allowlist; an injected script
+//! waits for the page to render, extracts the fields and hands them back by
+//! navigating to an internal `ssi-scrape:` URL, which is intercepted before
+//! it leaves the webview.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Url, WebviewUrl, WebviewWindowBuilder};
+use tokio::sync::{oneshot, Semaphore};
+
+use crate::politeness::PolicyEngine;
+use crate::utils::generate_id;
+
+/// Scheme the extraction script navigates to with its result
+const RESULT_SCHEME: &str = "ssi-scrape";
+
+/// Hidden webviews alive at once; each one is a full browser engine
+const MAX_CONCURRENT: usize = 2;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrapeField {
+    pub name: &'static str,
+    pub selector: &'static str,
+    /// Attribute to read instead of the element's text
+    pub attribute: Option<&'static str>,
+    /// Collect every match rather than the first
+    pub all: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrapeSource {
+    pub id: &'static str,
+    /// Politeness policy the page load is admitted under
+    pub policy: &'static str,
+    /// Hosts the webview may load, including their subdomains
+    pub allowed_domains: &'static [&'static str],
+    /// Element whose presence means the page has rendered
+    pub ready_selector: &'static str,
+    pub fields: &'static [ScrapeField],
+    pub timeout_secs: u64,
+}
+
+pub const SCRAPE_SOURCES: &[ScrapeSource] = &[
+    ScrapeSource {
+        id: "eastmoney_f10",
+        policy: "eastmoney",
+        allowed_domains: &["emweb.securities.eastmoney.com"],
+        ready_selector: "#Table0",
+        fields: &[
+            ScrapeField {
+                name: "company_name",
+                selector: "#Table0 tr:nth-child(1) td:nth-child(2)",
+                attribute: None,
+                all: false,
+            },
+            ScrapeField {
+                name: "industry",
+                selector: "#Table0 tr:nth-child(6) td:nth-child(2)",
+                attribute: None,
+                all: false,
+            },
+            ScrapeField {
+                name: "profile",
+                selector: "#Table0 tr:last-child td",
+                attribute: None,
+                all: false,
+            },
+        ],
+        timeout_secs: 20,
+    },
+    ScrapeSource {
+        id: "cls_telegraph",
+        policy: "cls",
+        allowed_domains: &["cls.cn"],
+        ready_selector: ".telegraph-content-box",
+        fields: &[
+            ScrapeField {
+                name: "items",
+                selector: ".telegraph-content-box",
+                attribute: None,
+                all: true,
+            },
+            ScrapeField {
+                name: "times",
+                selector: ".telegraph-time-box",
+                attribute: None,
+                all: true,
+            },
+        ],
+        timeout_secs: 20,
+    },
+];
+
+pub fn scrape_source(id: &str) -> Result<&'static ScrapeSource, String> {
+    SCRAPE_SOURCES
+        .iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("Unknown scrape source: {}", id))
+}
+
+/// Whether `url` is an http(s) URL on one of the allowed domains or their subdomains
+pub fn is_allowed(url: &Url, allowed_domains: &[&str]) -> bool {
+    if !matches!(url.scheme(), "https" | "http") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    allowed_domains
+        .iter()
+        .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+}
+
+/// Script injected into every page load that extracts the source's fields
+fn extraction_script(source: &ScrapeSource) -> String {
+    let rules = serde_json::to_string(&json!({
+        "ready": source.ready_selector,
+        "fields": source.fields,
+        "timeout": source.timeout_secs * 1000,
+    }))
+    .unwrap_or_default();
+    format!(
+        r#"(function () {{
+  const rules = {rules};
+  const read = (el, f) => (f.attribute ? el.getAttribute(f.attribute) : el.textContent || "").trim();
+  const finish = (payload) => {{
+    window.location.href = "{scheme}://result/" + encodeURIComponent(JSON.stringify(payload));
+  }};
+  const started = Date.now();
+  const poll = () => {{
+    if (!document.querySelector(rules.ready)) {{
+      if (Date.now() - started > rules.timeout) return finish({{ error: "page did not render" }});
+      return setTimeout(poll, 250);
+    }}
+    const data = {{}};
+    for (const f of rules.fields) {{
+      if (f.all) {{
+        data[f.name] = Array.from(document.querySelectorAll(f.selector)).map((el) => read(el, f));
+      }} else {{
+        const el = document.querySelector(f.selector);
+        data[f.name] = el ? read(el, f) : null;
+      }}
+    }}
+    finish({{ data }});
+  }};
+  if (document.readyState === "loading") document.addEventListener("DOMContentLoaded", poll);
+  else poll();
+}})();"#,
+        rules = rules,
+        scheme = RESULT_SCHEME,
+    )
+}
+
+/// Decode the payload of a `ssi-scrape://result/...` navigation
+fn parse_result(url: &Url) -> Result<Value, String> {
+    let encoded = url.path().trim_start_matches('/');
+    let decoded: String = url::form_urlencoded::parse(format!("v={}", encoded).as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_default();
+    let payload: Value = serde_json::from_str(&decoded)
+        .map_err(|e| format!("Failed to parse scrape result: {}", e))?;
+    match payload.get("error").and_then(Value::as_str) {
+        Some(error) => Err(format!("Scrape failed: {}", error)),
+        None => Ok(payload.get("data").cloned().unwrap_or(Value::Null)),
+    }
+}
+
+pub struct WebviewFetcher {
+    permits: Semaphore,
+}
+
+impl Default for WebviewFetcher {
+    fn default() -> Self {
+        Self {
+            permits: Semaphore::new(MAX_CONCURRENT),
+        }
+    }
+}
+
+/// Load `url` in a hidden webview and extract `source`'s fields from the rendered page
+pub async fn fetch_rendered(
+    app: &AppHandle,
+    source: &ScrapeSource,
+    url: &str,
+) -> Result<Value, String> {
+    let url: Url = url.parse().map_err(|e| format!("Invalid URL: {}", e))?;
+    if !is_allowed(&url, source.allowed_domains) {
+        return Err(format!(
+            "URL is outside the allowed domains of {}: {}",
+            source.id, url
+        ));
+    }
+
+    let fetcher = app.state::<WebviewFetcher>();
+    let _permit = fetcher
+        .permits
+        .acquire()
+        .await
+        .map_err(|e| format!("Failed to acquire webview slot: {}", e))?;
+    let policies = app.state::<PolicyEngine>();
+    let _admitted = policies.admit(source.policy, url.as_str()).await?;
+
+    let (sender, receiver) = oneshot::channel();
+    let sender = Mutex::new(Some(sender));
+    let allowed_domains = source.allowed_domains;
+    let source_id = source.id;
+    let label = generate_id("scrape");
+    let window = WebviewWindowBuilder::new(app, label, WebviewUrl::External(url.clone()))
+        .visible(false)
+        .initialization_script(&extraction_script(source))
+        .on_navigation(move |target| {
+            if target.scheme() == RESULT_SCHEME {
+                if let Some(sender) = sender.lock().unwrap().take() {
+                    sender.send(parse_result(target)).ok();
+                }
+                return false;
+            }
+            let allowed = is_allowed(target, allowed_domains);
+            if !allowed {
+                warn!("Blocked navigation of {} to {}", source_id, target);
+            }
+            allowed
+        })
+        .build()
+        .map_err(|e| format!("Failed to open scrape webview: {}", e))?;
+
+    let timeout = Duration::from_secs(source.timeout_secs);
+    let result = match tokio::time::timeout(timeout, receiver).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err("Scrape webview closed before returning data".to_string()),
+        Err(_) => Err(format!(
+            "Timed out after {}s rendering {}",
+            source.timeout_secs, url
+        )),
+    };
+    if let Err(e) = window.close() {
+        warn!("Failed to close scrape webview: {}", e);
+    }
+    if result.is_ok() {
+        info!("Scraped {} from {}", source.id, url);
+    }
+    result
+}
+
+/// Extraction sources available to the provider layer
+#[tauri::command]
+pub fn list_scrape_sources() -> Result<Vec<ScrapeSource>, String> {
+    Ok(SCRAPE_SOURCES.to_vec())
+}
+
+/// Render a page for a source and return the extracted fields, for checking rules
+#[tauri::command]
+pub async fn scrape_page(app: AppHandle, source: String, url: String) -> Result<Value, String> {
+    let source = scrape_source(&source)?;
+    fetch_rendered(&app, source, &url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_and_result_decoding() {
+        let source = scrape_source("cls_telegraph").unwrap();
+        let allowed = |u: &str| is_allowed(&u.parse().unwrap(), source.allowed_domains);
+        assert!(allowed("https://www.cls.cn/telegraph"));
+        assert!(allowed("https://cls.cn/"));
+        assert!(!allowed("https://evilcls.cn/"));
+        assert!(!allowed("https://cls.cn.example.com/"));
+        assert!(!allowed("file:///etc/passwd"));
+
+        let url: Url = format!(
+            "{}://result/{}",
+            RESULT_SCHEME, "%7B%22data%22%3A%7B%22items%22%3A%5B%22%E5%BF%AB%E8%AE%AF%22%5D%7D%7D"
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(parse_result(&url).unwrap(), json!({"items": ["快讯"]}));
+
+        let failed: Url = format!(
+            "{}://result/{}",
+            RESULT_SCHEME, "%7B%22error%22%3A%22page%20did%20not%20render%22%7D"
+        )
+        .parse()
+        .unwrap();
+        assert!(parse_result(&failed).is_err());
+        assert!(extraction_script(source).contains(".telegraph-content-box"));
+    }
+}
@@ -0,0 +1,227 @@
+//! Named workspace layouts.
+//!
+//! A workspace is the frontend's layout (panels, symbols, column
+//! configuration), kept as opaque JSON, together with the position, size and
+//! maximized state of every app window at the time it was saved. Workspaces
+//! live in the settings store, so they survive restarts, and travel with
+//! exported profiles, so they survive machine moves. Loading one restores
+//! the geometry of the windows that are open and returns the workspace; the
+//! frontend rebuilds its panels from the layout and opens any windows still
+//! missing at their saved geometry.
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalSize, State, WebviewWindow};
+
+use crate::merge::Mergeable;
+use crate::settings::SettingsStore;
+use crate::utils::get_timestamp;
+
+/// Windows that belong to background work rather than the user's layout
+const TRANSIENT_WINDOWS: &[&str] = &["scrape-", "login-"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub label: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    /// Panels, symbols and column configuration, as the frontend defines them
+    pub layout: Value,
+    pub windows: Vec<WindowGeometry>,
+    pub saved_at: String,
+}
+
+impl Mergeable for Workspace {
+    const KIND: &'static str = "workspace";
+
+    fn id(&self) -> &str {
+        &self.name
+    }
+
+    fn modified_at(&self) -> &str {
+        &self.saved_at
+    }
+
+    fn fingerprint(&self) -> String {
+        serde_json::to_string(&(&self.layout, &self.windows)).unwrap_or_default()
+    }
+}
+
+fn capture(window: &WebviewWindow) -> Result<WindowGeometry, tauri::Error> {
+    let position = window.outer_position()?;
+    let size = window.outer_size()?;
+    Ok(WindowGeometry {
+        label: window.label().to_string(),
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized: window.is_maximized()?,
+    })
+}
+
+fn apply(window: &WebviewWindow, geometry: &WindowGeometry) -> Result<(), tauri::Error> {
+    if geometry.maximized {
+        return window.maximize();
+    }
+    window.unmaximize()?;
+    window.set_position(PhysicalPosition {
+        x: geometry.x,
+        y: geometry.y,
+    })?;
+    window.set_size(PhysicalSize {
+        width: geometry.width,
+        height: geometry.height,
+    })
+}
+
+fn user_windows(app: &AppHandle) -> Vec<WebviewWindow> {
+    let mut windows: Vec<(String, WebviewWindow)> = app
+        .webview_windows()
+        .into_iter()
+        .filter(|(label, _)| !TRANSIENT_WINDOWS.iter().any(|p| label.starts_with(p)))
+        .collect();
+    windows.sort_by(|a, b| a.0.cmp(&b.0));
+    windows.into_iter().map(|(_, window)| window).collect()
+}
+
+/// Insert or replace a workspace by name
+fn upsert(workspaces: &mut Vec<Workspace>, workspace: Workspace) {
+    match workspaces.iter_mut().find(|w| w.name == workspace.name) {
+        Some(existing) => *existing = workspace,
+        None => workspaces.push(workspace),
+    }
+}
+
+#[tauri::command]
+pub fn list_workspaces(settings: State<'_, SettingsStore>) -> Result<Vec<Workspace>, String> {
+    Ok(settings.get().workspaces)
+}
+
+/// Save the current layout and window geometry under `name`, replacing any workspace of that name
+#[tauri::command]
+pub fn save_workspace(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    name: String,
+    layout: Value,
+) -> Result<Workspace, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Workspace name is required".to_string());
+    }
+    let windows = user_windows(&app)
+        .iter()
+        .map(capture)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read window geometry: {}", e))?;
+    let workspace = Workspace {
+        name,
+        layout,
+        windows,
+        saved_at: get_timestamp(),
+    };
+    let mut updated = settings.get();
+    upsert(&mut updated.workspaces, workspace.clone());
+    settings.set(updated)?;
+    info!(
+        "Saved workspace {} with {} windows",
+        workspace.name,
+        workspace.windows.len()
+    );
+    Ok(workspace)
+}
+
+/// Restore the geometry of open windows and return the workspace for the frontend to rebuild
+#[tauri::command]
+pub fn load_workspace(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    name: String,
+) -> Result<Workspace, String> {
+    let workspace = settings
+        .get()
+        .workspaces
+        .into_iter()
+        .find(|w| w.name == name)
+        .ok_or_else(|| format!("Workspace not found: {}", name))?;
+    for geometry in &workspace.windows {
+        if let Some(window) = app.get_webview_window(&geometry.label) {
+            if let Err(e) = apply(&window, geometry) {
+                warn!("Failed to restore window {}: {}", geometry.label, e);
+            }
+        }
+    }
+    // Saved on another monitor setup, a window may land off-screen
+    crate::placement::rescue_windows(&app);
+    info!("Loaded workspace {}", workspace.name);
+    Ok(workspace)
+}
+
+#[tauri::command]
+pub fn delete_workspace(settings: State<'_, SettingsStore>, name: String) -> Result<(), String> {
+    let mut updated = settings.get();
+    let before = updated.workspaces.len();
+    updated.workspaces.retain(|w| w.name != name);
+    if updated.workspaces.len() == before {
+        return Err(format!("Workspace not found: {}", name));
+    }
+    settings.set(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn workspace(name: &str, layout: Value, saved_at: &str) -> Workspace {
+        Workspace {
+            name: name.to_string(),
+            layout,
+            windows: vec![WindowGeometry {
+                label: "main".to_string(),
+                x: 10,
+                y: 20,
+                width: 1280,
+                height: 800,
+                maximized: false,
+            }],
+            saved_at: saved_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_workspaces_persist_by_name() {
+        let path = std::env::temp_dir().join(format!("ssi-workspace-{}.json", std::process::id()));
+        let store = SettingsStore::load(path.clone());
+        let mut settings = store.get();
+        upsert(
+            &mut settings.workspaces,
+            workspace("trading", json!({"panels": ["chart"]}), "t1"),
+        );
+        upsert(
+            &mut settings.workspaces,
+            workspace("research", json!({}), "t1"),
+        );
+        upsert(
+            &mut settings.workspaces,
+            workspace("trading", json!({"panels": ["chart", "news"]}), "t2"),
+        );
+        store.set(settings).unwrap();
+
+        let reloaded = SettingsStore::load(path.clone()).get().workspaces;
+        assert_eq!(reloaded.len(), 2);
+        assert_eq!(reloaded[0].layout, json!({"panels": ["chart", "news"]}));
+        assert_eq!(reloaded[0].windows[0].width, 1280);
+        std::fs::remove_file(path).ok();
+    }
+}
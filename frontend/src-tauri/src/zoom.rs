@@ -0,0 +1,115 @@
+//! Per-window zoom.
+//!
+//! Zoom factors are kept in settings by window and applied from here each
+//! time a window's page loads, so a zoomed-in window comes back zoomed the
+//! next session instead of relying on the webview to remember. Chart windows
+//! share one factor, since each symbol gets a window label of its own.
+
+use std::collections::BTreeMap;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime, State, Webview};
+
+use crate::placement::CHART_WINDOW_PREFIX;
+use crate::settings::SettingsStore;
+
+const MIN_ZOOM: f64 = 0.5;
+const MAX_ZOOM: f64 = 3.0;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoomSettings {
+    /// Factor by window; windows not listed are at 1.0
+    pub factors: BTreeMap<String, f64>,
+}
+
+impl ZoomSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        self.factors
+            .values()
+            .try_for_each(|factor| check_factor(*factor))
+    }
+
+    pub fn factor(&self, label: &str) -> f64 {
+        self.factors.get(zoom_key(label)).copied().unwrap_or(1.0)
+    }
+}
+
+fn check_factor(factor: f64) -> Result<(), String> {
+    if !(MIN_ZOOM..=MAX_ZOOM).contains(&factor) {
+        return Err(format!(
+            "Zoom must be between {} and {}, got {}",
+            MIN_ZOOM, MAX_ZOOM, factor
+        ));
+    }
+    Ok(())
+}
+
+/// Settings key of a window's zoom
+fn zoom_key(label: &str) -> &str {
+    if label.starts_with(CHART_WINDOW_PREFIX) {
+        return "chart";
+    }
+    label
+}
+
+/// Apply a webview's saved zoom, as its page loads
+pub fn apply_zoom<R: Runtime>(webview: &Webview<R>) {
+    let factor = webview
+        .state::<SettingsStore>()
+        .get()
+        .zoom
+        .factor(webview.label());
+    if let Err(e) = webview.set_zoom(factor) {
+        warn!("Failed to zoom window {}: {}", webview.label(), e);
+    }
+}
+
+/// Zoom a window and remember the factor for its next session
+#[tauri::command]
+pub fn set_window_zoom(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    label: String,
+    factor: f64,
+) -> Result<(), String> {
+    check_factor(factor)?;
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| format!("Window not found: {}", label))?;
+    window
+        .set_zoom(factor)
+        .map_err(|e| format!("Failed to zoom window {}: {}", label, e))?;
+    let mut updated = settings.get();
+    let key = zoom_key(&label).to_string();
+    if factor == 1.0 {
+        updated.zoom.factors.remove(&key);
+    } else {
+        updated.zoom.factors.insert(key, factor);
+    }
+    settings.set(updated)
+}
+
+#[tauri::command]
+pub fn get_window_zoom(settings: State<'_, SettingsStore>, label: String) -> Result<f64, String> {
+    Ok(settings.get().zoom.factor(&label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zoom_factors() {
+        let settings = ZoomSettings {
+            factors: BTreeMap::from([("main".to_string(), 1.5), ("chart".to_string(), 1.25)]),
+        };
+        assert_eq!(settings.factor("main"), 1.5);
+        assert_eq!(settings.factor("chart-SH600519"), 1.25);
+        assert_eq!(settings.factor("ticker-bar"), 1.0);
+        assert!(settings.validate().is_ok());
+        assert!(check_factor(0.25).is_err());
+        assert!(check_factor(3.5).is_err());
+    }
+}